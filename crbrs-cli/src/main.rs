@@ -1,7 +1,8 @@
 // FILE: crbrs-cli/src/main.rs
 
 use clap::{Parser, Subcommand};
-use crbrs_lib::{Error, Settings}; // Ensure CompilationErrorDetail is imported
+use crbrs_lib::{say, say_raw, tr, Error, Settings}; // Ensure CompilationErrorDetail is imported
+use std::io::IsTerminal;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -11,20 +12,130 @@ struct Cli {
     command: Commands,
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
+    /// Keep config, compilers and logs under this directory (overrides CRBRS_HOME)
+    #[arg(long, global = true, value_name = "DIR")]
+    portable: Option<PathBuf>,
+    /// Apply the [profiles.<NAME>] overrides from the config (overrides CRBRS_PROFILE)
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+    /// Also write a JSON-lines log of everything this run does to FILE, at any verbosity
+    #[arg(long, global = true, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+    /// Print nothing but errors (and -v logging); the exit code tells the result
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Never prompt: questions get their default answer, or the command fails if there is none
+    #[arg(long, global = true)]
+    no_input: bool,
+    /// Never use the network: cached manifest and compiler archives only (loggers still work)
+    #[arg(long, global = true)]
+    offline: bool,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Compile a CRBasic file
+    /// Compile CRBasic files; several files or directories build them all and print a summary
     Compile {
-        /// Input CRBasic file path
-        input_file: PathBuf,
+        /// Input CRBasic file path(s), directories to search for programs, or patterns such as
+        /// 'stations/**/*.cr1x'
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
         /// Optional: Output path for compiler log/info (compiler writes to this file)
         #[arg(long)] // Changed from short 'o' to avoid conflict if we add other short flags
         output_log: Option<PathBuf>,
         /// Optional: ID of the compiler to use (overrides file association)
         #[arg(short, long)]
         compiler: Option<String>,
+        /// Compile for download: also produce the binary image sent to the logger
+        #[arg(long)]
+        image: bool,
+        /// Optional: Where to put the produced image (implies --image)
+        #[arg(long)]
+        image_out: Option<PathBuf>,
+        /// Install the associated compiler (or the project's required ones) from the manifest if not installed yet
+        #[arg(long)]
+        install_missing: bool,
+        /// Skip files whose source and includes are unchanged since their last successful build
+        #[arg(long)]
+        incremental: bool,
+        /// Also write the build summary as JSON to this file
+        #[arg(long, value_name = "FILE")]
+        summary: Option<PathBuf>,
+        /// Build on this SSH host instead (it needs crbrs and the compilers installed)
+        #[arg(long, value_name = "HOST")]
+        remote: Option<String>,
+        /// Output format: text, or sarif to print only a SARIF 2.1.0 log of the errors (for CI
+        /// code scanning)
+        #[arg(long, default_value = "text")]
+        format: crbrs_lib::sarif::CompileFormat,
+        /// Files compiled at the same time, when compiling several
+        #[arg(short = 'j', long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: u16,
+        /// Run the compiler even on files whose result is cached from an earlier run with the
+        /// same source, includes and compiler
+        #[arg(long)]
+        force: bool,
+        /// Read files as a JSON object of names and their text from stdin (e.g. generated
+        /// includes) and compile in a temporary directory holding them; inputs may name them
+        #[arg(long)]
+        stdin_includes: bool,
+        /// Stop a compiler still running after this many seconds (overrides the
+        /// compile_timeout_secs setting; 0 for no limit)
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+    },
+    /// Checksum, sign and verify produced artifacts
+    Artifact {
+        #[command(subcommand)]
+        action: ArtifactAction,
+    },
+    /// Measure toolchain performance
+    Bench {
+        #[command(subcommand)]
+        action: BenchAction,
+    },
+    /// Build every program under the given paths, recompiling only those whose source or
+    /// includes changed since their last successful build
+    Build {
+        /// Program files, directories to search for programs, or patterns such as 'stations/**/*.cr1x'
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+        /// Rebuild everything, even programs that are up to date or whose result is cached
+        #[arg(long)]
+        force: bool,
+        /// Optional: ID of the compiler to use (overrides file association)
+        #[arg(short, long)]
+        compiler: Option<String>,
+        /// Compile for download: also produce each program's binary image
+        #[arg(long)]
+        image: bool,
+        /// Also write the build summary as JSON to this file
+        #[arg(long, value_name = "FILE")]
+        summary: Option<PathBuf>,
+        /// Build on this SSH host instead (it needs crbrs and the compilers installed)
+        #[arg(long, value_name = "HOST")]
+        remote: Option<String>,
+        /// Inject the constants of this station calibration file (TOML) and build the
+        /// calibrated copies, written to the data directory as <program>_<station>.<ext>
+        #[arg(long, value_name = "FILE")]
+        with_calibration: Option<PathBuf>,
+        /// Programs compiled at the same time
+        #[arg(short = 'j', long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: u16,
+        /// Stop a compiler still running after this many seconds (overrides the
+        /// compile_timeout_secs setting; 0 for no limit)
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+    },
+    /// Remove temporary files left behind by crbrs processes that crashed or were killed
+    Clean {
+        /// Temporary directories (compile sandboxes, language server scratch files, ...) of other
+        /// processes not used for a day
+        #[arg(long, required = true)]
+        temp: bool,
+        /// Also those used within the last day (make sure no other crbrs is running)
+        #[arg(long)]
+        all: bool,
     },
     /// Manage compilers
     Compiler {
@@ -36,22 +147,610 @@ enum Commands {
         #[command(subcommand)]
         action: ConfigAction,
     },
+    /// Work with data files collected from loggers
+    Data {
+        #[command(subcommand)]
+        action: DataAction,
+    },
+    /// Check a program against the one running on the logger before deploying it
+    Deploy {
+        #[command(subcommand)]
+        action: DeployAction,
+    },
+    /// Run logger commands on every station in [stations]
+    Fleet {
+        #[command(subcommand)]
+        action: FleetAction,
+    },
+    /// Show the recorded history of sessions with stations
+    History {
+        #[command(subcommand)]
+        action: HistoryAction,
+    },
+    /// Generate Markdown/HTML documentation for a program or a directory of programs
+    Docgen {
+        /// CRBasic file or project directory
+        #[arg(default_value = ".")]
+        input: PathBuf,
+        /// Directory to write the documentation into
+        #[arg(short, long, default_value = "docs")]
+        output: PathBuf,
+        /// Output format: markdown or html
+        #[arg(short, long, default_value = "markdown")]
+        format: crbrs_lib::docgen::DocFormat,
+    },
+    /// Explain an error or lint code (e.g. E014, L002): causes and fixes; lists all codes if omitted
+    Explain {
+        /// Error code, lint code or lint rule ID
+        code: Option<String>,
+    },
+    /// Format programs, with the options of the nearest .crbrsfmt.toml, crbrs.toml [format]
+    /// table and .editorconfig files
+    Fmt {
+        /// Program files, directories to search for programs, or patterns such as 'stations/**/*.cr1x'
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+        /// Change nothing; fail if any program is not formatted (for CI)
+        #[arg(long)]
+        check: bool,
+        /// Also write keywords and instruction names in their canonical case (e.g. cardout -> CardOut)
+        #[arg(long)]
+        fix_case: bool,
+    },
+    /// Start a project: a crbrs.toml, a starter program for a logger model and a .gitignore
+    Init {
+        /// Directory to create the project in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// Logger model the program is for (CR1000X, CR1000, CR3000, CR800, CR6, CR300, CR310, CR200X); asked if omitted
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Name of the program (default: the directory's name)
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Compiler the project pins (default: the one associated with the model's extension)
+        #[arg(short, long)]
+        compiler: Option<String>,
+        /// Overwrite an existing program and .gitignore
+        #[arg(long)]
+        force: bool,
+    },
+    /// Talk to loggers over PakBus
+    Logger {
+        #[command(subcommand)]
+        action: LoggerAction,
+    },
+    /// List and search terminal/monitor session logs
+    Logs {
+        /// Session file to show (full name or a unique part of it); lists sessions if omitted
+        session: Option<String>,
+        /// Only show lines containing this text (searches all sessions if none is given)
+        #[arg(short, long)]
+        grep: Option<String>,
+    },
+    /// Document the data a program stores
+    Metadata {
+        #[command(subcommand)]
+        action: MetadataAction,
+    },
+    /// Set this machine up from a plan: install compilers, set associations and lint levels, and
+    /// check that every compiler works (e.g. for a classroom of identical machines)
+    Provision {
+        /// The plan (TOML with `compilers`, `[associations]` and optionally `[lint]`,
+        /// `compiler_repository_url` and `wine_path`)
+        #[arg(long)]
+        from: PathBuf,
+        /// Print what was done as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Bundle version, system and config details and the last run's log into a zip for a bug report
+    ReportBug {
+        /// Also include this source file (e.g. the program that fails to compile)
+        #[arg(long)]
+        source: Option<PathBuf>,
+        /// Where to write the zip (default: crbrs-bug-report-<time>.zip in the current directory)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Manage crbrs itself on this machine
+    #[command(name = "self")]
+    SelfCmd {
+        #[command(subcommand)]
+        action: SelfAction,
+    },
+    /// Keep repository tokens and logger security codes in the OS keychain instead of the config
+    Secret {
+        #[command(subcommand)]
+        action: SecretAction,
+    },
+    /// Add sensor code to a program
+    Sensor {
+        #[command(subcommand)]
+        action: SensorAction,
+    },
+    /// Manage the version header of a program
+    Version {
+        #[command(subcommand)]
+        action: VersionAction,
+    },
+    /// Check a project for CI: its compilers match crbrs.toml, its programs are formatted,
+    /// lint clean and compile; fails if any check does
+    VerifyProject {
+        /// The project's directory, or any path inside it
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Print only the report, as JSON
+        #[arg(long)]
+        json: bool,
+        /// Programs compiled at the same time
+        #[arg(short = 'j', long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: u16,
+        /// Stop a compiler still running after this many seconds (overrides the
+        /// compile_timeout_secs setting; 0 for no limit)
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DataAction {
+    /// Flag out-of-range values, spikes and flatlines in a TOA5 file
+    Qa {
+        /// TOA5 data file
+        file: PathBuf,
+        /// Checks per field or units (TOML); built-in defaults by units apply otherwise
+        #[arg(long)]
+        rules: Option<PathBuf>,
+        /// Report format: text, csv (flags only) or json
+        #[arg(short, long, default_value = "text")]
+        format: crbrs_lib::qa::QaFormat,
+        /// Write the report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Fail if any value is flagged
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Merge TOA5 files of one table, dropping repeated records, and report gaps
+    Merge {
+        /// TOA5 files, e.g. successive collections from a station
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// File to write the merged data to
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Expected time between records, e.g. 15min or 1h (defaults to the most common one)
+        #[arg(long, value_parser = crbrs_lib::merge::parse_interval)]
+        interval: Option<i64>,
+        /// Convert the merged timestamps to this UTC offset, e.g. UTC or -05:00
+        #[arg(long)]
+        to: Option<crbrs_lib::timezone::UtcOffset>,
+        /// UTC offset of the logger clock (defaults to the station's [station_timezones] entry)
+        #[arg(long, requires = "to")]
+        from: Option<crbrs_lib::timezone::UtcOffset>,
+    },
+    /// Convert a TOA5 or TOB1 file to Parquet, Arrow IPC (Feather), CSV or TOA5 with typed columns
+    Convert {
+        /// TOA5 or TOB1 data file
+        file: PathBuf,
+        /// File to write, e.g. Station12_Hourly.parquet
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Output format: parquet, arrow, csv or toa5 (defaults to the output file's extension)
+        #[arg(short, long)]
+        format: Option<crbrs_lib::convert::ConvertFormat>,
+        /// Only add records newer than the output's last one (resumes an interrupted conversion)
+        #[arg(long)]
+        append: bool,
+    },
+    /// List the data files on a pulled card (directory or card image) and optionally convert them
+    Card {
+        /// Mounted card, copy of its files, or raw card image (e.g. from dd)
+        card: PathBuf,
+        /// Convert every data file into this directory
+        #[arg(long, value_name = "DIR")]
+        convert: Option<PathBuf>,
+        /// Output format for --convert: toa5, parquet, arrow or csv
+        #[arg(short, long, default_value = "toa5")]
+        format: crbrs_lib::convert::ConvertFormat,
+        /// Only add records newer than each output's last one
+        #[arg(long, requires = "convert")]
+        append: bool,
+    },
+    /// Convert the timestamps of a TOA5 file between the station's time and UTC
+    Retime {
+        /// TOA5 data file
+        file: PathBuf,
+        /// File to write the converted data to
+        #[arg(short, long)]
+        output: PathBuf,
+        /// UTC offset of the logger clock (defaults to the station's [station_timezones] entry)
+        #[arg(long)]
+        from: Option<crbrs_lib::timezone::UtcOffset>,
+        /// UTC offset to convert to
+        #[arg(long, default_value = "UTC")]
+        to: crbrs_lib::timezone::UtcOffset,
+    },
+    /// Check that the fields of a data file match the program's DataTable definition
+    Check {
+        /// TOA5, TOB1 or TOB3 data file
+        file: PathBuf,
+        /// Program the data is expected to come from
+        #[arg(short, long)]
+        program: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DeployAction {
+    /// Compare version headers and data table schemas; fails on regressions unless --force
+    Check {
+        /// Program about to be deployed
+        program: PathBuf,
+        /// Copy of the program currently running on the logger
+        #[arg(long)]
+        against: PathBuf,
+        /// Allow version regressions and schema-breaking changes
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum FleetAction {
+    /// Read every station's status; fails with a list of alerts if any threshold is breached
+    Status {
+        /// Print the statuses and alerts as JSON
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        run: FleetArgs,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct FleetArgs {
+    /// Stations to work on at the same time
+    #[arg(short = 'j', long, default_value_t = 16)]
+    jobs: usize,
+    /// Seconds allowed for each station before it is reported as timed out
+    #[arg(long, value_name = "SECS", default_value_t = 120)]
+    timeout: u64,
+    /// Start no more stations after the first one fails or times out
+    #[arg(long)]
+    fail_fast: bool,
+}
+
+impl FleetArgs {
+    fn options(&self) -> crbrs_lib::fleet::FleetOptions {
+        crbrs_lib::fleet::FleetOptions {
+            jobs: self.jobs,
+            station_timeout: std::time::Duration::from_secs(self.timeout),
+            fail_fast: self.fail_fast,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum LoggerAction {
+    /// Show battery, panel temperature, skipped scans, watchdog errors and the compile result
+    Status {
+        /// Station name from [stations], or an address like tcp:10.0.0.5:6785 or serial:/dev/ttyUSB0
+        station: String,
+        /// PakBus address of the logger (overrides the station's)
+        #[arg(long)]
+        pakbus_address: Option<u16>,
+        /// Security code of the logger (overrides the station's)
+        #[arg(long)]
+        security_code: Option<u16>,
+        /// Print the status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Send a program to the logger, then compile and run it (recorded in `crbrs history stations`)
+    Send {
+        /// Station name from [stations], or an address like tcp:10.0.0.5:6785 or serial:/dev/ttyUSB0
+        station: String,
+        /// CRBasic program to send
+        program: PathBuf,
+        /// Compare with this copy of the program running on the logger instead of reading it back
+        /// from the logger
+        #[arg(long)]
+        against: Option<PathBuf>,
+        /// Send without checking the program against the one running on the logger (version
+        /// regressions and schema-breaking changes, see `crbrs deploy check`)
+        #[arg(long)]
+        force: bool,
+        /// Only send the file; don't compile and run it
+        #[arg(long)]
+        no_run: bool,
+        /// PakBus address of the logger (overrides the station's)
+        #[arg(long)]
+        pakbus_address: Option<u16>,
+        /// Security code of the logger (overrides the station's)
+        #[arg(long)]
+        security_code: Option<u16>,
+    },
+    /// Talk to the logger's terminal mode (the CR1000X> prompt): each line read from stdin is
+    /// sent as a command and the answer printed. The session is logged (see `crbrs logs`)
+    Terminal {
+        /// Station name from [stations], or an address like tcp:10.0.0.5:6785 or serial:/dev/ttyUSB0
+        station: String,
+        /// PakBus address of the logger (overrides the station's; used to detect @auto baud rates)
+        #[arg(long)]
+        pakbus_address: Option<u16>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryAction {
+    /// Programs sent to and data collected from stations: what, when, how much and the result
+    Stations {
+        /// Only show sessions with this station
+        station: Option<String>,
+        /// Show only the newest N sessions
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Print the sessions as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SelfAction {
+    /// Remove what crbrs keeps on this machine: all of it, or only the given categories. Lists
+    /// everything (with sizes) and asks before deleting. crbrs installs no desktop integration,
+    /// so there is none to remove.
+    Purge {
+        /// Installed compilers and their downloaded archives (other files in a shared compiler
+        /// storage directory are kept)
+        #[arg(long)]
+        compilers: bool,
+        /// The cached manifest, build state, compile results, symbol indexes and last run's log
+        #[arg(long)]
+        caches: bool,
+        /// Compile history and logger session logs
+        #[arg(long)]
+        history: bool,
+        /// The config directory (linked compilers themselves are kept)
+        #[arg(long)]
+        config: bool,
+        /// Keychain secrets the config refers to
+        #[arg(long)]
+        secrets: bool,
+        /// Don't ask before deleting
+        #[arg(short, long)]
+        yes: bool,
+        /// Only list what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SecretAction {
+    /// Store a secret (typed at a prompt, or the first line piped to stdin)
+    Set { name: String },
+    /// Print a secret
+    Get { name: String },
+    /// Delete a secret from the keychain
+    Remove { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum SensorAction {
+    /// Insert declarations, the measurement and table output for a sensor
+    Add {
+        #[command(subcommand)]
+        kind: SensorKind,
+    },
+    /// Generate the ModbusMaster calls and declarations for a register map CSV
+    /// (columns address, type, scaling, name and optionally units)
+    ImportModbus {
+        /// Register map CSV
+        csv: PathBuf,
+        /// Insert the code into this CRBasic file instead of printing it
+        #[arg(long)]
+        into: Option<PathBuf>,
+        /// Modbus slave address of the device
+        #[arg(short, long, default_value_t = 1)]
+        address: u8,
+        /// Serial port of the device
+        #[arg(long, default_value = "ComC1")]
+        port: String,
+        #[arg(long, default_value_t = 9600)]
+        baud: u32,
+        /// Function code for addresses not written as 3xxxx (input) or 4xxxx (holding registers)
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(3..=4))]
+        function: u8,
+        #[command(flatten)]
+        common: SensorArgs,
+    },
+    /// List the built-in SDI-12 sensor models
+    Models,
+}
+
+#[derive(Subcommand, Debug)]
+enum SensorKind {
+    /// An SDI-12 sensor, read with SDI12Recorder
+    Sdi12 {
+        /// CRBasic file to update in place
+        file: PathBuf,
+        /// SDI-12 address of the sensor
+        #[arg(short, long, default_value_t = '0')]
+        address: char,
+        /// Built-in model (see `crbrs sensor models`)
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Values the sensor returns, as NAME or NAME:UNITS (overrides the model's)
+        #[arg(long, value_delimiter = ',')]
+        values: Vec<crbrs_lib::syntax::sensor::SensorValue>,
+        /// Measurement command without the address (defaults to the model's, or M!)
+        #[arg(long)]
+        command: Option<String>,
+        /// Control port the sensor is wired to
+        #[arg(long, default_value = "C1")]
+        port: String,
+        #[command(flatten)]
+        common: SensorArgs,
+    },
+    /// A Modbus RTU device, read with ModbusMaster
+    Modbus {
+        /// CRBasic file to update in place
+        file: PathBuf,
+        /// Modbus slave address of the device
+        #[arg(short, long, default_value_t = 1)]
+        address: u8,
+        /// Serial port of the device
+        #[arg(long, default_value = "ComC1")]
+        port: String,
+        #[arg(long, default_value_t = 9600)]
+        baud: u32,
+        /// Function code: 3 (holding registers) or 4 (input registers)
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(3..=4))]
+        function: u8,
+        /// First register to read
+        #[arg(long, default_value_t = 1)]
+        start: u32,
+        /// Register layout: float-cdab, float-abcd, int32-cdab, int32-abcd, int16 or uint16
+        #[arg(long, default_value = "float-cdab")]
+        format: crbrs_lib::syntax::sensor::RegisterFormat,
+        /// Values to read, in register order, as NAME or NAME:UNITS
+        #[arg(long, value_delimiter = ',', required = true)]
+        values: Vec<crbrs_lib::syntax::sensor::SensorValue>,
+        #[command(flatten)]
+        common: SensorArgs,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct SensorArgs {
+    /// Name of the array for the values (defaults to the model or Modbus and the address)
+    #[arg(long)]
+    name: Option<String>,
+    /// Data table to store the values in (defaults to the first one)
+    #[arg(long)]
+    table: Option<String>,
+    /// Output processing: average, sample, minimum, maximum or none
+    #[arg(long, default_value = "average")]
+    processing: crbrs_lib::syntax::sensor::Processing,
+    /// Print the updated program instead of writing it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum VersionAction {
+    /// Increment the version and add a changelog entry (creates the header at 1.0.0 if missing)
+    Bump {
+        /// CRBasic file to update in place
+        file: PathBuf,
+        /// Component to increment: major, minor or patch
+        #[arg(default_value = "patch")]
+        level: crbrs_lib::syntax::version::BumpLevel,
+        /// Changelog message
+        #[arg(short, long)]
+        message: String,
+        /// Optional: Author (defaults to git user.name)
+        #[arg(long)]
+        author: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ArtifactAction {
+    /// Write a .sha256 sidecar (and a .minisig signature if signing_key_path is set)
+    Seal {
+        artifact: PathBuf,
+    },
+    /// Check an artifact against its checksum and signature sidecars
+    Verify {
+        artifact: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BenchAction {
+    /// Time cold and warm compiles, Wine startup and parsing of a file; prints JSON
+    Compile {
+        file: PathBuf,
+        /// How often to compile (and parse) the file
+        #[arg(short = 'n', long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+        iterations: u32,
+        /// Optional: ID of the compiler to use (overrides file association)
+        #[arg(short, long)]
+        compiler: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
 enum CompilerAction {
-    /// Install a compiler from the repository using its ID
+    /// Install a compiler from the repository using its ID, or from a local archive or directory
     Install {
-        compiler_id: String,
+        /// Compiler ID; with --from-file, the ID to install it under (default: from the
+        /// compiler's crbrs-compiler.toml, else the archive or directory name)
+        #[arg(required_unless_present = "from_file")]
+        compiler_id: Option<String>,
+        /// Install from this archive (.zip, .tar.gz, .7z) or directory instead of the repository (no network needed)
+        #[arg(long, value_name = "PATH")]
+        from_file: Option<PathBuf>,
+        /// With --from-file: the compiler executable (default: the only .exe)
+        #[arg(long, requires = "from_file")]
+        executable: Option<String>,
+        /// With --from-file: version to record
+        #[arg(long = "compiler-version", requires = "from_file")]
+        compiler_version: Option<String>,
+        /// With --from-file: description to record
+        #[arg(long, requires = "from_file")]
+        description: Option<String>,
+        /// With --from-file: compiler family, cr1000, cr200 or cr10x (default: cr200 for cr2* executables, else cr1000)
+        #[arg(long, requires = "from_file")]
+        family: Option<crbrs_lib::CompilerFamily>,
+    },
+    /// List *installed* compilers with their health, size and last use
+    List {
+        /// Print machine-readable JSON instead of a table
+        #[arg(long)]
+        json: bool,
     },
-    /// List *installed* compilers
-    List,
     /// List *available* compilers from the remote repository
     ListAvailable,
+    /// Reinstall compilers the repository has newer versions of, replacing them in place
+    Upgrade {
+        /// Compiler IDs to upgrade
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        compiler_ids: Vec<String>,
+        /// Upgrade every installed compiler the repository lists
+        #[arg(long)]
+        all: bool,
+        /// Only report which compilers are outdated
+        #[arg(long)]
+        dry_run: bool,
+    },
     /// Remove an installed compiler by its ID
     Remove {
         compiler_id: String,
     },
+    /// Show which compiler (and version) last built each file
+    History {
+        /// Only show these files
+        files: Vec<PathBuf>,
+    },
+    /// Show disk usage of installed compilers, including space shared between versions
+    Du,
+    /// Register a compiler already installed elsewhere (e.g. a shared system-wide location)
+    Link {
+        /// Directory of the installed compiler
+        path: PathBuf,
+        /// Compiler ID to register it under (defaults to the one recorded at install time)
+        #[arg(long)]
+        id: Option<String>,
+        /// Executable name, required if the directory was not installed by crbrs
+        #[arg(long)]
+        executable: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -60,6 +759,14 @@ enum ConfigAction {
     Show,
     /// Show the path to the configuration file
     Path,
+    /// Check the configuration file, reporting every problem with its line number
+    Validate {
+        /// Config file to check (defaults to the active one)
+        file: Option<PathBuf>,
+        /// Treat unknown keys as errors instead of warnings
+        #[arg(long)]
+        strict: bool,
+    },
     /// Set a specific configuration value
     Set { key: String, value: String },
     /// Associate a file extension (e.g., 'cr2') with a compiler ID
@@ -76,9 +783,41 @@ enum ConfigAction {
     },
 }
 
+#[derive(Subcommand, Debug)]
+enum MetadataAction {
+    /// Export a data dictionary (table, field, units, processing, interval) for a program
+    Export {
+        /// Input CRBasic file path
+        input_file: PathBuf,
+        /// Output format: csv, json or eml
+        #[arg(short, long, default_value = "csv")]
+        format: crbrs_lib::metadata::MetadataFormat,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Person or organization credited as the dataset's creator (eml)
+        #[arg(long)]
+        creator: Option<String>,
+        /// Who to contact about the dataset (eml)
+        #[arg(long)]
+        contact: Option<String>,
+    },
+    /// Check that the tables of a project's programs do not collide when their data is loaded
+    /// into one database (fields stored twice, same-named tables with other fields or types)
+    Check {
+        /// A program or directory in the project; the whole project is checked
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+}
+
 fn main() {
     let cli = Cli::parse();
 
+    crbrs_lib::interaction::set_quiet(cli.quiet);
+    crbrs_lib::interaction::set_no_input(cli.no_input);
+    crbrs_lib::network::set_offline(cli.offline);
+
     let log_level = match cli.verbose {
         0 => log::LevelFilter::Error, // Default: Show only CRITICAL errors from our code.
         1 => log::LevelFilter::Warn,  // -v: Show WARN (like Wine stderr) and ERROR.
@@ -86,27 +825,84 @@ fn main() {
         3 => log::LevelFilter::Debug, // -vvv: Show DEBUG, INFO, WARN, ERROR.
         _ => log::LevelFilter::Trace, // -vvvv or more: Show TRACE and all above.
     };
-    env_logger::Builder::new()
+    let console_logger = env_logger::Builder::new()
         .filter_level(log_level)
         .format_timestamp(None)      // Cleaner error logs if they appear by default
         .format_module_path(false) //
         .format_target(false)      //
-        .init();
+        .build();
+    crbrs_lib::structured_log::init(Box::new(console_logger), log_level).expect("logger is only installed once");
+    log::info!("crbrs {} started: {:?}", env!("CARGO_PKG_VERSION"), std::env::args().collect::<Vec<_>>());
 
     log::debug!("CLI arguments parsed: {:?}. Effective log level: {}", cli, log_level);
 
+    if let Some(dir) = &cli.portable {
+        if let Err(e) = crbrs_lib::config::set_portable_home(dir) {
+            eprintln!("Error: Invalid portable directory {}: {}", dir.display(), e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(name) = &cli.profile {
+        crbrs_lib::config::set_active_profile(name);
+    }
+
+    // An explicit log file is opened before settings are loaded so problems loading them are logged too.
+    if let Some(path) = &cli.log_file {
+        open_log_file(path);
+    }
+
     let mut settings = match crbrs_lib::config::load_settings() {
         Ok(s) => {
             log::debug!("Settings loaded successfully: {:?}", s);
+            for recovery in crbrs_lib::config::take_recoveries() {
+                let path = recovery.path.display().to_string();
+                match recovery.backup {
+                    Some(backup) => eprintln!(
+                        "{}",
+                        tr!("file-recovered-from-backup", path = path, reason = recovery.reason, backup = backup.display().to_string())
+                    ),
+                    None => eprintln!("{}", tr!("file-reset-to-defaults", path = path, reason = recovery.reason)),
+                }
+            }
             s
         }
         Err(e) => {
             log::error!("Critical error loading settings: {}", e); // Shows at default Error level
-            eprintln!("Error: Could not load configuration: {}", e);
+            eprintln!("{}", tr!("error-loading-config", message = e.to_string()));
+            eprintln!("{}", tr!("error-explain-hint", code = e.code()));
             std::process::exit(1);
         }
     };
 
+    if let (None, Some(path)) = (&cli.log_file, &settings.log_file) {
+        open_log_file(path);
+    }
+    // The bug report includes the previous run's log, so it must not replace it; nor should
+    // commands that only print something fixed, like where the config file is.
+    let keeps_last_run = !matches!(
+        cli.command,
+        Commands::ReportBug { .. } | Commands::Explain { .. } | Commands::Config { action: ConfigAction::Path }
+    );
+    if keeps_last_run {
+        if let Err(e) = keep_last_run_log(settings.last_run_log) {
+            log::debug!("Not keeping this run's log: {}", e);
+        }
+    }
+    crbrs_lib::structured_log::stop_holding();
+
+    // The first Ctrl-C cancels the running operation cleanly (compilers killed, partial files
+    // removed); a second one quits at once.
+    if let Err(e) = ctrlc::set_handler(|| {
+        if crbrs_lib::cancel::token().is_cancelled() {
+            std::process::exit(130);
+        }
+        eprintln!("{}", tr!("cancelling"));
+        crbrs_lib::cancel::token().cancel();
+    }) {
+        log::warn!("Could not install the Ctrl-C handler: {}", e);
+    }
+
     if let Err(e) = run_command(cli.command, &mut settings) {
         // Log the full error detail if verbosity allows (or if it's an ERROR level log)
         // The specific user-facing `eprintln!` for compilation errors is handled in `run_command`.
@@ -122,26 +918,359 @@ fn main() {
             Error::CompilationFailed { .. } | Error::GenericCompilationFailedWithLog { .. } => {
                 // User-facing message already printed by the Compile arm's error handler.
             }
+            Error::Cancelled => {
+                eprintln!("{}", tr!("cancelled"));
+                std::process::exit(130); // 128 + SIGINT, as shells report an interrupted command
+            }
+            Error::SettingsNotSaved { .. } => {
+                // Not "failed": scripts can tell that the work is done but was not recorded.
+                eprintln!("{}", tr!("error", message = e.to_string()));
+                eprintln!("{}", tr!("error-explain-hint", code = e.code()));
+                std::process::exit(3);
+            }
             _ => {
                 // For all other error types, print their Display message to the user.
-                eprintln!("Error: {}", e);
+                eprintln!("{}", tr!("error", message = e.to_string()));
             }
         }
+        eprintln!("{}", tr!("error-explain-hint", code = e.code()));
         std::process::exit(1);
     }
 
     log::debug!("Command executed successfully.");
 }
 
+/// One line for a group of identical compiler errors: its lines (the first few), the message,
+/// and how often it was reported if that is more than once per line.
+fn describe_error_group(group: &crbrs_lib::diagnostics::ErrorGroup) -> String {
+    const LINES_SHOWN: usize = 8;
+    let mut text = match group.lines.as_slice() {
+        [] => tr!("compilation-error", message = group.message.as_str()),
+        [line] => tr!("compilation-error-line", line = *line, message = group.message.as_str()),
+        lines => {
+            let mut shown: Vec<String> = lines.iter().take(LINES_SHOWN).map(u32::to_string).collect();
+            if lines.len() > LINES_SHOWN {
+                shown.push("…".to_string());
+            }
+            tr!("compilation-error-lines", lines = shown.join(", "), message = group.message.as_str())
+        }
+    };
+    if group.count > group.lines.len().max(1) {
+        text.push_str(&format!(" {}", tr!("compilation-error-repeated", count = group.count)));
+    }
+    text
+}
+
+/// Keeps this run's log in `last-run.jsonl` for `crbrs report-bug`, in as much `detail` as the
+/// `last_run_log` setting asks for. With `off`, an earlier run's log is removed instead, so a
+/// bug report doesn't pass it off as the latest.
+fn keep_last_run_log(detail: crbrs_lib::structured_log::Detail) -> Result<(), crbrs_lib::Error> {
+    let path = crbrs_lib::structured_log::last_run_path()?;
+    if detail == crbrs_lib::structured_log::Detail::Off {
+        return match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+    crbrs_lib::structured_log::open(&path, false, detail)
+}
+
+/// Starts the structured log file, exiting if it cannot be written.
+fn open_log_file(path: &std::path::Path) {
+    if let Err(e) = crbrs_lib::structured_log::open(path, true, crbrs_lib::structured_log::Detail::Trace) {
+        eprintln!("Error: Cannot write log file {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+}
+
+/// Compiles every program in `inputs`, locally or on the `remote` host, then prints (and
+/// optionally writes) the build summary. Programs in a `sandbox` are reported by the names
+/// they were given as.
+fn run_build(
+    inputs: &[PathBuf],
+    options: &crbrs_lib::build::BuildOptions,
+    summary_path: Option<&std::path::Path>,
+    remote: Option<&str>,
+    format: crbrs_lib::sarif::CompileFormat,
+    sandbox: Option<&crbrs_lib::sandbox::Sandbox>,
+    settings: &Settings,
+) -> Result<(), Error> {
+    use crbrs_lib::build::BuildSummary;
+    log::info!("Executing batch Compile command for: {:?}", inputs);
+    let sarif = format == crbrs_lib::sarif::CompileFormat::Sarif;
+    if sarif {
+        crbrs_lib::interaction::set_quiet(true); // stdout is the SARIF log alone
+    }
+    let summary = if let Some(host) = remote {
+        // The remote crbrs reports each file itself; its output is passed through.
+        say!("{}", tr!("build-remote", host = host));
+        crbrs_lib::remote::build(host, inputs, options)?
+    } else {
+        let start = std::time::Instant::now();
+        let files = crbrs_lib::build::collect_inputs(inputs)?;
+        let original = |file: &std::path::Path| sandbox.map_or_else(|| file.to_path_buf(), |sandbox| sandbox.original(file));
+        let mut results =
+            crbrs_lib::build::build_files(&files, options, settings, |result| report_file(result, &original(&result.file)))?;
+        for result in &mut results {
+            result.file = original(&result.file);
+        }
+        BuildSummary::new(results, start.elapsed())
+    };
+
+    print_build_summary(&summary);
+    if let Some(path) = summary_path {
+        summary.write_json(path)?;
+        say!("{}", tr!("build-summary-written", path = path.display().to_string()));
+    }
+    if sarif {
+        println!("{}", crbrs_lib::sarif::from_build(&summary.files).to_json()?);
+    }
+    summary.result()
+}
+
+/// Reports a file of a batch once it is built; the library prints the line for compiled ones.
+fn report_file(result: &crbrs_lib::build::FileResult, file: &std::path::Path) {
+    let file = file.display().to_string();
+    match result.outcome {
+        crbrs_lib::build::FileOutcome::Compiled => {}
+        crbrs_lib::build::FileOutcome::Cached => say!("{}", tr!("build-file-cached", file = file)),
+        crbrs_lib::build::FileOutcome::Failed => {
+            eprintln!("{}", tr!("build-file-failed", file = file, error = result.error.clone().unwrap_or_default()))
+        }
+    }
+}
+
+fn status_name(status: crbrs_lib::build::Status) -> &'static str {
+    match status {
+        crbrs_lib::build::Status::Ok => "ok",
+        crbrs_lib::build::Status::Warnings => "warnings",
+        crbrs_lib::build::Status::Failed => "failed",
+    }
+}
+
+fn print_build_summary(summary: &crbrs_lib::build::BuildSummary) {
+    say!("\n{}", tr!("build-summary"));
+    say!(
+        "  {}",
+        tr!("build-summary-files", total = summary.total, compiled = summary.compiled, cached = summary.cached, failed = summary.failed)
+    );
+    say!("  {}", tr!("build-summary-warnings", warnings = summary.warnings));
+    say!("  {}", tr!("build-summary-wall-time", seconds = format!("{:.1}", summary.wall_time_ms as f64 / 1000.0)));
+    if !summary.by_compiler.is_empty() {
+        say!("  {}", tr!("build-summary-by-compiler"));
+        for (id, tally) in &summary.by_compiler {
+            say!(
+                "    {:<30} {}",
+                id,
+                tr!("build-summary-tally", compiled = tally.compiled, cached = tally.cached, failed = tally.failed, warnings = tally.warnings)
+            );
+        }
+    }
+    say!("  {}", tr!("build-summary-status", status = status_name(summary.status)));
+}
+
+/// Records `installed` in the `crbrs.lock` of the project in the current directory, if there
+/// is one; returns the lock's path if it changed.
+fn update_project_lock(settings: &Settings, installed: &[String]) -> Result<Option<PathBuf>, Error> {
+    match crbrs_lib::project::Project::find(std::path::Path::new("."))? {
+        Some(project) if project.update_lock(installed, settings)? => Ok(Some(project.lock_path())),
+        _ => Ok(None),
+    }
+}
+
+/// Prints a `crbrs verify-project` report, check by check.
+fn print_verify_report(report: &crbrs_lib::verify::VerifyReport) {
+    use crbrs_lib::build::Status;
+    let mark = |status| match status {
+        Status::Ok => "✅",
+        Status::Warnings => "⚠️",
+        Status::Failed => "❌",
+    };
+    say!("\n{}", tr!("verify-report", manifest = report.manifest.display().to_string()));
+    let toolchain = &report.toolchain;
+    if toolchain.status == Status::Ok {
+        say!("{} {}", mark(toolchain.status), tr!("verify-toolchain-ok", count = toolchain.required.len()));
+    }
+    for (key, ids) in [
+        ("verify-toolchain-missing", &toolchain.missing),
+        ("verify-toolchain-broken", &toolchain.broken),
+        ("verify-toolchain-unlisted", &toolchain.unlisted),
+        ("verify-toolchain-mismatched", &toolchain.mismatched),
+    ] {
+        if !ids.is_empty() {
+            say!("{} {}", mark(Status::Failed), tr!(key, ids = ids.join(", ")));
+        }
+    }
+    let format = &report.format;
+    if format.unformatted.is_empty() {
+        say!("{} {}", mark(format.status), tr!("verify-format-ok", total = format.checked));
+    } else {
+        say!("{} {}", mark(format.status), tr!("verify-format-failed", count = format.unformatted.len(), total = format.checked));
+        for file in &format.unformatted {
+            say!("    {}", file.display());
+        }
+    }
+    if !format.skipped.is_empty() {
+        say!("{} {}", mark(Status::Warnings), tr!("verify-format-skipped", count = format.skipped.len()));
+        for (file, reason) in &format.skipped {
+            say!("    {}: {}", file.display(), reason);
+        }
+    }
+    let lint = &report.lint;
+    say!("{} {}", mark(lint.status), tr!("verify-lint", errors = lint.errors, warnings = lint.warnings));
+    for problem in &lint.problems {
+        let severity = format!("{:?}", problem.severity).to_lowercase();
+        say!("    {}:{}: {} [{}] {}", problem.file.display(), problem.line, severity, problem.code, problem.message);
+    }
+    match &report.build {
+        Some(build) => {
+            // Failed files were reported as they were built
+            say!("{} {}", mark(if build.failed > 0 { Status::Failed } else { Status::Ok }), tr!("verify-build", compiled = build.compiled + build.cached, failed = build.failed));
+        }
+        None => say!("{} {}", mark(Status::Failed), tr!("verify-build-skipped")),
+    }
+    say!("{}", tr!("build-summary-status", status = status_name(report.status)));
+}
+
+/// Parses a numeric `config set` value.
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, Error> {
+    value.trim().parse().map_err(|_| {
+        Error::Config(config::ConfigError::Message(format!("'{}' expects a whole number, got '{}'", key, value)))
+    })
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, Error> {
+    value.trim().parse().map_err(|_| {
+        Error::Config(config::ConfigError::Message(format!("'{}' expects true or false, got '{}'", key, value)))
+    })
+}
+
+fn parse_volts(key: &str, value: &str) -> Result<f64, Error> {
+    value.trim().parse().map_err(|_| {
+        Error::Config(config::ConfigError::Message(format!("'{}' expects a voltage, got '{}'", key, value)))
+    })
+}
+
+/// Formats a byte count with a binary unit (e.g. `12.3 MiB`).
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// `text` marked (and, on a terminal without `NO_COLOR`, colored) by `health`.
+fn health_line(health: crbrs_lib::status::Health, text: &str) -> String {
+    use crbrs_lib::status::Health;
+    let (marker, color) = match health {
+        Health::Ok => ("✅", "32"),
+        Health::Warning => ("⚠️", "33"),
+        Health::Critical => ("❌", "31"),
+    };
+    if std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+        format!("{} \x1b[{}m{}\x1b[0m", marker, color, text)
+    } else {
+        format!("{} {}", marker, text)
+    }
+}
+
 fn run_command(command: Commands, settings: &mut Settings) -> Result<(), Error> {
     match command {
         Commands::Compile {
-            input_file,
+            inputs,
             output_log, // This is Option<PathBuf> from clap
             compiler,
+            image,
+            image_out,
+            install_missing,
+            incremental,
+            summary,
+            remote,
+            format,
+            jobs,
+            force,
+            stdin_includes,
+            timeout,
         } => {
+            crbrs_lib::compile_cache::set_enabled(!force);
+            if let Some(timeout) = timeout {
+                settings.compile_timeout_secs = timeout;
+            }
+            let mut sandbox = None;
+            let inputs = if stdin_includes {
+                if image && image_out.is_none() {
+                    return Err(Error::Config(config::ConfigError::Message(
+                        "--stdin-includes compiles in a temporary directory; give --image-out to keep the image".to_string(),
+                    )));
+                }
+                let mut json = String::new();
+                std::io::Read::read_to_string(&mut std::io::stdin(), &mut json)?;
+                let mut created = crbrs_lib::sandbox::Sandbox::new(&crbrs_lib::sandbox::parse_files(&json)?)?;
+                let inputs = inputs.iter().map(|input| created.program(input)).collect::<Result<Vec<_>, _>>()?;
+                sandbox = Some(created);
+                inputs
+            } else {
+                inputs
+            };
+            let batch = inputs.len() > 1
+                || inputs.iter().any(|input| input.is_dir() || crbrs_lib::build::is_pattern(input))
+                || incremental
+                || summary.is_some()
+                || remote.is_some()
+                || format == crbrs_lib::sarif::CompileFormat::Sarif;
+            if batch {
+                if output_log.is_some() || image_out.is_some() || install_missing {
+                    return Err(Error::Config(config::ConfigError::Message(
+                        "--output-log, --image-out and --install-missing only apply when compiling a single file (as text)".to_string(),
+                    )));
+                }
+                let options = crbrs_lib::build::BuildOptions {
+                    compiler_id: compiler,
+                    image,
+                    incremental,
+                    calibration: None,
+                    jobs: jobs.into(),
+                };
+                return run_build(&inputs, &options, summary.as_deref(), remote.as_deref(), format, sandbox.as_ref(), settings);
+            }
+            let input_file = inputs.into_iter().next().expect("clap requires an input");
             log::info!("Executing Compile command for file: {:?}", input_file); // Shows with -vv
-            match crbrs_lib::compile_file(input_file.clone(), output_log.clone(), compiler, settings) {
+            let compile = |settings: &Settings| {
+                if image || image_out.is_some() {
+                    crbrs_lib::compile_for_download(input_file.clone(), output_log.clone(), compiler.clone(), image_out.clone(), settings).map(|_| ())
+                } else {
+                    crbrs_lib::compile_file(input_file.clone(), output_log.clone(), compiler.clone(), settings)
+                }
+            };
+            let result = match compile(settings) {
+                Err(Error::AssociatedCompilerNotInstalled { compiler_id, in_manifest: true, .. }) if install_missing => {
+                    say!("{}", tr!("compile-installing-missing", compiler_id = compiler_id.as_str()));
+                    crbrs_lib::installer::install_compiler(settings, &compiler_id)?;
+                    say!("{}", tr!("compiler-installed", compiler_id = compiler_id.as_str()));
+                    compile(settings)
+                }
+                Err(Error::ProjectCompilersMissing { missing, .. }) if install_missing => {
+                    for compiler_id in &missing {
+                        say!("{}", tr!("compile-installing-missing", compiler_id = compiler_id.as_str()));
+                        crbrs_lib::installer::install_compiler(settings, compiler_id)?;
+                        say!("{}", tr!("compiler-installed", compiler_id = compiler_id.as_str()));
+                    }
+                    compile(settings)
+                }
+                result => result,
+            };
+            let result = result.map_err(|e| match &sandbox {
+                Some(sandbox) => sandbox.original_error(e),
+                None => e,
+            });
+            match result {
                 Ok(_) => {
                     // Success messages (like ✅) are printed by the library function directly.
                 }
@@ -149,38 +1278,42 @@ fn run_command(command: Commands, settings: &mut Settings) -> Result<(), Error>
                     // This is where we print user-facing messages for COMPILATION errors.
                     match &e {
                         Error::CompilationFailed { file_path, errors, raw_log } => {
-                            eprintln!("\n❌ Compilation of '{}' failed.", file_path.display());
+                            eprintln!("\n{}", tr!("compilation-failed", file = file_path.display().to_string()));
                             if errors.is_empty() {
-                                eprintln!("  Compiler reported errors, but no specific error lines were parsed.");
-                                eprintln!("  (Use '-v' with `crbrs compile` to see raw compiler stdout/stderr)");
+                                eprintln!("  {}", tr!("compilation-no-error-lines"));
+                                eprintln!("  {}", tr!("compilation-verbose-hint"));
                                 log::error!(
                                     "CompilationFailed for {} but no structured errors parsed. Raw output (stdout from compiler):\n{}",
                                     file_path.display(), raw_log
                                 );
                             } else {
-                                eprintln!("Specific errors found:");
-                                for detail in errors {
-                                    if let Some(line_num) = detail.line {
-                                        eprintln!("  Line {}: {}", line_num, detail.message.trim());
-                                    } else {
-                                        eprintln!("  Error: {}", detail.message.trim());
+                                eprintln!("{}", tr!("compilation-errors-found"));
+                                let condensed = crbrs_lib::diagnostics::condense(errors, settings.max_diagnostics);
+                                let source = std::fs::read(file_path).map(|bytes| String::from_utf8_lossy(&bytes).into_owned()).unwrap_or_default();
+                                for group in &condensed.groups {
+                                    eprintln!("  {}", describe_error_group(group));
+                                    if let Some(cause) = crbrs_lib::diagnostics::root_cause(&source, &group.message) {
+                                        eprintln!("    {}", tr!("compilation-note", message = cause.message));
                                     }
                                 }
+                                if condensed.hidden > 0 {
+                                    eprintln!("  {}", tr!("compilation-more-errors", count = condensed.hidden));
+                                }
                             }
                             if let Some(log_p) = output_log { // User explicitly asked for a log file
-                                 eprintln!("\n(Full compiler log also available in '{}')", log_p.display());
+                                 eprintln!("\n{}", tr!("compilation-log-available", path = log_p.display().to_string()));
                             } else { // Default case: no log file created by crbrs
-                                 eprintln!("\n(Use '-v' with `crbrs compile` to see raw compiler output, or use --output-log to save the compiler's log)");
+                                 eprintln!("\n{}", tr!("compilation-log-hint"));
                             }
                         }
                         Error::GenericCompilationFailedWithLog { file_path, raw_log } => {
-                            eprintln!("\n❌ Compilation of '{}' failed (compiler output format unrecognized or process error).", file_path.display());
-                            eprintln!("Raw compiler output:");
+                            eprintln!("\n{}", tr!("compilation-failed-unrecognized", file = file_path.display().to_string()));
+                            eprintln!("{}", tr!("compilation-raw-output"));
                             eprintln!("--------------------------------------------------");
                             eprintln!("{}", raw_log.trim());
                             eprintln!("--------------------------------------------------");
                             if let Some(log_p) = output_log {
-                                 eprintln!("\n(Full compiler log also available in '{}')", log_p.display());
+                                 eprintln!("\n{}", tr!("compilation-log-available", path = log_p.display().to_string()));
                             }
                         }
                         _ => {
@@ -194,45 +1327,131 @@ fn run_command(command: Commands, settings: &mut Settings) -> Result<(), Error>
                 }
             }
         }
+        Commands::Artifact { action } => match action {
+            ArtifactAction::Seal { artifact } => {
+                log::info!("Executing Artifact Seal command for: {:?}", artifact);
+                let sealed = crbrs_lib::artifact::seal(&artifact, settings)?;
+                say!("✅ Wrote checksum '{}'", sealed.checksum.display());
+                if let Some(signature) = sealed.signature {
+                    say!("✅ Wrote signature '{}'", signature.display());
+                }
+            }
+            ArtifactAction::Verify { artifact } => {
+                log::info!("Executing Artifact Verify command for: {:?}", artifact);
+                crbrs_lib::artifact::verify(&artifact, settings)?;
+                say!("✅ '{}' verified", artifact.display());
+            }
+        },
+        Commands::Bench { action } => match action {
+            BenchAction::Compile { file, iterations, compiler } => {
+                log::info!("Executing Bench Compile command for: {:?}", file);
+                let bench = crbrs_lib::bench::compile(&file, compiler.as_deref(), iterations as usize, settings)?;
+                let json = serde_json::to_string_pretty(&bench).map_err(|e| Error::Io(e.into()))?;
+                say!("{}", json);
+            }
+        },
+        Commands::Build { paths, force, compiler, image, summary, remote, with_calibration, jobs, timeout } => {
+            crbrs_lib::compile_cache::set_enabled(!force);
+            if let Some(timeout) = timeout {
+                settings.compile_timeout_secs = timeout;
+            }
+            let options = crbrs_lib::build::BuildOptions {
+                compiler_id: compiler,
+                image,
+                incremental: !force,
+                calibration: with_calibration,
+                jobs: jobs.into(),
+            };
+            run_build(&paths, &options, summary.as_deref(), remote.as_deref(), crbrs_lib::sarif::CompileFormat::Text, None, settings)?;
+        }
+        Commands::Clean { temp: _, all } => {
+            log::info!("Executing Clean command (all: {})", all);
+            let older_than = if all { std::time::Duration::ZERO } else { crbrs_lib::temp::STALE_AFTER };
+            let removed = crbrs_lib::temp::clean(older_than)?;
+            if removed.is_empty() {
+                say!("{}", tr!("purge-nothing"));
+                return Ok(());
+            }
+            for (path, bytes) in &removed {
+                say!("  {} ({})", path.display(), format_bytes(*bytes));
+            }
+            say!("{}", tr!("purge-total", size = format_bytes(removed.iter().map(|(_, bytes)| bytes).sum())));
+            say!("{}", tr!("purge-done", count = removed.len()));
+        }
         Commands::Compiler { action } => {
             match action {
-                CompilerAction::Install { compiler_id } => {
-                    log::info!("Executing Compiler Install command for ID: {}", compiler_id);
-                    crbrs_lib::installer::install_compiler(settings, &compiler_id)?;
-                    println!("✅ Compiler '{}' installed successfully.", compiler_id);
+                CompilerAction::Install { compiler_id, from_file, executable, compiler_version, description, family } => {
+                    let compiler_id = match from_file {
+                        Some(source) => {
+                            log::info!("Executing Compiler Install command from file: {:?}", source);
+                            let local = crbrs_lib::installer::LocalCompiler {
+                                id: compiler_id,
+                                executable,
+                                version: compiler_version,
+                                description,
+                                family,
+                            };
+                            crbrs_lib::installer::install_from_file(settings, &source, &local)?
+                        }
+                        None => {
+                            let compiler_id = compiler_id.expect("clap requires an ID without --from-file");
+                            log::info!("Executing Compiler Install command for ID: {}", compiler_id);
+                            crbrs_lib::installer::install_compiler(settings, &compiler_id)?;
+                            compiler_id
+                        }
+                    };
+                    say!("{}", tr!("compiler-installed", compiler_id = compiler_id.as_str()));
+                    if let Some(lock) = update_project_lock(settings, std::slice::from_ref(&compiler_id))? {
+                        say!("{}", tr!("project-lock-updated", path = lock.display().to_string()));
+                    }
                 }
-                CompilerAction::List => {
+                CompilerAction::List { json } => {
                     log::info!("Executing Compiler List command...");
-                    println!("Installed Compilers (Locally):");
-                    if settings.installed_compilers.is_empty() {
-                        println!("  (None)");
-                    } else {
-                        let mut sorted_compilers: Vec<_> = settings.installed_compilers.values().collect();
-                        sorted_compilers.sort_by_key(|info| &info.id);
-                        for info in sorted_compilers {
-                            println!(
-                                "  - ID: {:<30} Version: {:<15} Description: {}",
-                                info.id, info.version, info.description,
-                            );
+                    let statuses = crbrs_lib::installer::compiler_statuses(settings)?;
+                    if json {
+                        let text = serde_json::to_string_pretty(&statuses).map_err(|e| Error::Io(e.into()))?;
+                        say!("{}", text);
+                        return Ok(());
+                    }
+                    say!("Installed Compilers (Locally):");
+                    if statuses.is_empty() {
+                        say!("  (None)");
+                    }
+                    for status in &statuses {
+                        say!(
+                            "  - ID: {:<30} Version: {:<15} Description: {}",
+                            status.id, status.version, status.description,
+                        );
+                        if status.linked {
+                            say!("    (linked: {})", settings.installed_compilers[&status.id].install_subdir.display());
+                        }
+                        say!(
+                            "    {}  Size: {}  Last used: {}",
+                            if status.executable_exists { "✅ OK" } else { "❌ executable missing" },
+                            format_bytes(status.size_bytes),
+                            status.last_used.as_deref().unwrap_or("never"),
+                        );
+                        if let Some(update) = &status.update {
+                            say!("    ⬆️ Newer version available: {} ({})", update.id, update.version);
                         }
                     }
                 }
                 CompilerAction::ListAvailable => {
                     log::info!("Executing Compiler ListAvailable command...");
-                    println!(
+                    say!(
                         "Fetching available compilers from: {}",
                         settings.compiler_repository_url
                     );
-                    match crbrs_lib::installer::fetch_manifest(&settings.compiler_repository_url) {
+                    match crbrs_lib::installer::fetch_manifest(&settings.compiler_repository_url, &settings.network) {
                         Ok(manifest) => {
-                            println!("Available Compilers (Remote - Manifest Version: {}):", manifest.manifest_version);
+                            say!("Available Compilers (Remote - Manifest Version: {}):", manifest.manifest_version);
                             if manifest.compilers.is_empty() {
-                                println!("  (None found in manifest)");
+                                say!("  (None found in manifest)");
                             } else {
                                 let mut sorted_compilers: Vec<_> = manifest.compilers.iter().collect();
-                                sorted_compilers.sort_by(|(id_a, _), (id_b, _)| id_a.cmp(id_b));
+                                sorted_compilers.sort_by_key(|(id, _)| *id);
                                 for (id, entry) in sorted_compilers {
-                                    println!(
+                                    say!(
                                         "  - ID: {:<30} Version: {:<15} Description: {}",
                                         id, entry.version, entry.description
                                     );
@@ -242,10 +1461,97 @@ fn run_command(command: Commands, settings: &mut Settings) -> Result<(), Error>
                         Err(e) => { log::error!("Failed to fetch or parse remote manifest: {}", e); return Err(e); }
                     }
                 }
+                CompilerAction::Upgrade { compiler_ids, all: _, dry_run } => {
+                    log::info!("Executing Compiler Upgrade command for: {:?}", compiler_ids);
+                    if settings.installed_compilers.is_empty() {
+                        say!("{}", tr!("compiler-none-installed"));
+                        return Ok(());
+                    }
+                    let manifest = crbrs_lib::installer::fetch_manifest(&settings.compiler_repository_url, &settings.network)?;
+                    let checks = crbrs_lib::installer::check_versions(settings, &manifest, &compiler_ids)?;
+                    for check in &checks {
+                        let (id, installed) = (check.id.as_str(), check.installed.as_str());
+                        match &check.available {
+                            None => say!("{}", tr!("compiler-up-to-date", compiler_id = id, version = installed)),
+                            Some(available) if check.linked => {
+                                say!("{}", tr!("compiler-outdated-linked", compiler_id = id, installed = installed, available = available.as_str()))
+                            }
+                            Some(available) => {
+                                say!("{}", tr!("compiler-outdated", compiler_id = id, installed = installed, available = available.as_str()))
+                            }
+                        }
+                    }
+                    let outdated: Vec<_> = checks.iter().filter(|c| c.available.is_some() && !c.linked).collect();
+                    if outdated.is_empty() {
+                        say!("{}", tr!("compiler-all-up-to-date"));
+                        return Ok(());
+                    }
+                    if dry_run {
+                        return Ok(());
+                    }
+                    let mut failures = Vec::new();
+                    for check in outdated {
+                        match crbrs_lib::installer::install_compiler(settings, &check.id) {
+                            Ok(()) => {
+                                let version = check.available.as_deref().unwrap_or_default();
+                                say!("{}", tr!("compiler-upgraded", compiler_id = check.id.as_str(), version = version));
+                            }
+                            Err(Error::Cancelled) => return Err(Error::Cancelled),
+                            Err(e) => {
+                                log::error!("Upgrading '{}' failed; the installed version is kept: {}", check.id, e);
+                                failures.push(e);
+                            }
+                        }
+                    }
+                    if let Some(e) = failures.into_iter().next() {
+                        return Err(e);
+                    }
+                }
                 CompilerAction::Remove { compiler_id } => {
                     log::info!("Executing Compiler Remove command for ID: {}", compiler_id);
                     crbrs_lib::installer::remove_compiler(settings, &compiler_id)?;
-                    println!("🗑️ Compiler '{}' removed successfully.", compiler_id);
+                    say!("{}", tr!("compiler-removed", compiler_id = compiler_id.as_str()));
+                }
+                CompilerAction::Link { path, id, executable } => {
+                    log::info!("Executing Compiler Link command for path: {:?}", path);
+                    let id = crbrs_lib::installer::link_compiler(settings, &path, id.as_deref(), executable.as_deref())?;
+                    say!("{}", tr!("compiler-linked", compiler_id = id.as_str(), path = path.display().to_string()));
+                }
+                CompilerAction::History { files } => {
+                    log::info!("Executing Compiler History command...");
+                    let history = crbrs_lib::history::load();
+                    let wanted: Vec<String> = files.iter().map(|f| crbrs_lib::history::file_key(f)).collect();
+                    let builds: Vec<_> = history.files.iter().filter(|(file, _)| wanted.is_empty() || wanted.contains(file)).collect();
+                    if builds.is_empty() {
+                        say!("{}", tr!("compiler-no-recorded-builds"));
+                    }
+                    for (file, build) in builds {
+                        say!("{}\n    {} ({}) on {}", file, build.compiler_id, build.version, build.built);
+                    }
+                }
+                CompilerAction::Du => {
+                    log::info!("Executing Compiler Du command...");
+                    let storage_path = crbrs_lib::config::get_compiler_storage_path(settings)?;
+                    let usage = crbrs_lib::dedup::usage(&storage_path, settings)?;
+                    if usage.compilers.is_empty() {
+                        say!("{}", tr!("compiler-none-installed"));
+                    } else {
+                        for compiler in &usage.compilers {
+                            say!(
+                                "  - ID: {:<30} Size: {:>10}   Shared: {:>10}",
+                                compiler.id, format_bytes(compiler.total), format_bytes(compiler.shared)
+                            );
+                        }
+                        if usage.archives > 0 {
+                            say!("  Archives kept for delta patches: {}", format_bytes(usage.archives));
+                        }
+                        let total: u64 = usage.compilers.iter().map(|c| c.total).sum::<u64>() + usage.archives;
+                        say!(
+                            "Total: {} on disk ({} saved by sharing files between versions)",
+                            format_bytes(usage.on_disk),
+                            format_bytes(total.saturating_sub(usage.on_disk))
+                        );
+                    }
                 }
             }
         }
@@ -253,34 +1559,123 @@ fn run_command(command: Commands, settings: &mut Settings) -> Result<(), Error>
             match action {
                 ConfigAction::Show => {
                     log::info!("Executing Config Show command...");
-                    println!("Configuration Settings:");
-                    println!("  Repository URL: {}", settings.compiler_repository_url);
+                    say!("Configuration Settings:");
+                    if let Some(home) = crbrs_lib::config::portable_home() {
+                        say!("  Portable Home: {}", home.display());
+                    }
+                    if !settings.include.is_empty() {
+                        say!("  Includes: {}", settings.include.join(", "));
+                    }
+                    if !settings.profiles.is_empty() {
+                        let names: Vec<&str> = settings.profiles.keys().map(String::as_str).collect();
+                        say!(
+                            "  Profile: {} (defined: {})",
+                            settings.active_profile.as_deref().unwrap_or("(none)"),
+                            names.join(", ")
+                        );
+                    }
+                    if !settings.environment.is_empty() {
+                        let overridden: Vec<String> = settings.environment.iter().map(|(path, _)| path.join(".")).collect();
+                        say!("  From the Environment: {}", overridden.join(", "));
+                    }
+                    say!("  Repository URL: {}", settings.compiler_repository_url);
+                    if settings.require_signed_compilers {
+                        say!("  Compiler Downloads: signatures required");
+                    }
                     match crbrs_lib::config::get_compiler_storage_path(settings) {
                         Ok(storage_path) => {
-                            println!("  Compiler Storage Path: {}", storage_path.display());
+                            say!("  Compiler Storage Path: {}", storage_path.display());
                         }
                         Err(e) => {
-                             println!("  Compiler Storage Path: (Error resolving: {})", e);
+                             say!("  Compiler Storage Path: (Error resolving: {})", e);
                         }
                     }
-                    println!(
+                    say!(
                         "  Wine Path: {}",
                         settings.wine_path.as_deref().unwrap_or("(Not Set - using PATH)")
                     );
-                    println!("  File Associations:");
+                    if let Some(log_file) = &settings.log_file {
+                        say!("  Log File: {}", log_file.display());
+                    }
+                    say!("  Last Run Log: {}", format!("{:?}", settings.last_run_log).to_lowercase());
+                    say!(
+                        "  Max Diagnostics: {}",
+                        if settings.max_diagnostics == 0 { "no limit".to_string() } else { settings.max_diagnostics.to_string() }
+                    );
+                    say!(
+                        "  Compile Timeout: {}",
+                        if settings.compile_timeout_secs == 0 { "no limit".to_string() } else { format!("{} s", settings.compile_timeout_secs) }
+                    );
+                    say!("  Artifacts in Place: {}", format!("{:?}", settings.artifacts_in_place).to_lowercase());
+                    say!("  File Associations:");
                     if settings.file_associations.is_empty() {
-                        println!("    (None)");
+                        say!("    (None)");
                     } else {
                         let mut sorted_associations: Vec<_> = settings.file_associations.iter().collect();
                         sorted_associations.sort_by_key(|(ext, _)| *ext);
                         for (ext, id) in sorted_associations {
-                            println!("    .{} -> {}", ext, id);
+                            say!("    .{} -> {}", ext, id);
                         }
                     }
+                    if !settings.lint.is_empty() {
+                        let mut levels: Vec<_> = settings.lint.iter().collect();
+                        levels.sort_by_key(|(rule, _)| *rule);
+                        let levels: Vec<String> = levels.iter().map(|(rule, level)| format!("{} = {:?}", rule, level).to_lowercase()).collect();
+                        say!("  Lint Rules: {}", levels.join(", "));
+                    }
+                    if !settings.station_timezones.is_empty() {
+                        let mut stations: Vec<_> = settings.station_timezones.iter().collect();
+                        stations.sort();
+                        let stations: Vec<String> = stations.iter().map(|(station, offset)| format!("{} = {}", station, offset)).collect();
+                        say!("  Station Time Zones: {}", stations.join(", "));
+                    }
+                    if !settings.stations.is_empty() {
+                        let mut stations: Vec<_> = settings.stations.iter().collect();
+                        stations.sort_by_key(|(name, _)| name.as_str());
+                        say!("  Stations:");
+                        for (name, station) in stations {
+                            say!("    {}: {} (PakBus address {})", name, station.address, station.pakbus_address);
+                        }
+                    }
+                    let network = &settings.network;
+                    say!(
+                        "  Network: connect timeout {}s, read timeout {}s, download timeout {}s, {} retries (backoff {} ms), cached manifest used for {}h{}",
+                        network.connect_timeout_secs,
+                        network.read_timeout_secs,
+                        network.download_timeout_secs,
+                        network.retries,
+                        network.backoff_ms,
+                        network.manifest_max_age_hours,
+                        if network.offline { ", offline" } else { "" }
+                    );
                 }
                 ConfigAction::Path => {
                     let path = crbrs_lib::config::get_config_file_path()?;
-                    println!("{}", path.display());
+                    say!("{}", path.display());
+                }
+                ConfigAction::Validate { file, strict } => {
+                    log::info!("Executing Config Validate command...");
+                    let path = match file {
+                        Some(path) => path,
+                        None => crbrs_lib::config::get_config_file_path()?,
+                    };
+                    if !path.exists() {
+                        say!("No config file at {}; defaults are in use.", path.display());
+                        return Ok(());
+                    }
+                    let problems = crbrs_lib::validate::validate_file(&path, strict)?;
+                    for problem in &problems {
+                        say!("{}: {}", path.display(), problem);
+                    }
+                    let errors = problems.iter().filter(|p| p.severity == crbrs_lib::validate::Severity::Error).count();
+                    if errors > 0 {
+                        return Err(Error::Config(config::ConfigError::Message(format!(
+                            "{} error(s) in {}",
+                            errors,
+                            path.display()
+                        ))));
+                    }
+                    say!("✅ {} is valid ({} warning(s)).", path.display(), problems.len());
                 }
                 ConfigAction::Set { key, value } => {
                     log::info!("Executing Config Set command (Key: '{}', Value: '{}')", key, &value);
@@ -288,14 +1683,80 @@ fn run_command(command: Commands, settings: &mut Settings) -> Result<(), Error>
                         "compiler_repository_url" => settings.compiler_repository_url = value.clone(),
                         "wine_path" => settings.wine_path = Some(value.clone()),
                         "compiler_storage_path" => settings.compiler_storage_path = Some(PathBuf::from(value.clone())),
+                        "signing_key_path" => settings.signing_key_path = Some(PathBuf::from(value.clone())),
+                        "signing_public_key" => settings.signing_public_key = Some(value.clone()),
+                        "require_signed_compilers" => settings.require_signed_compilers = parse_bool(&key, &value)?,
+                        "log_file" => settings.log_file = Some(PathBuf::from(value.clone())),
+                        "max_diagnostics" => settings.max_diagnostics = parse_number(&key, &value)?,
+                        "compile_timeout_secs" => settings.compile_timeout_secs = parse_number(&key, &value)?,
+                        "artifacts_in_place" => {
+                            settings.artifacts_in_place =
+                                value.parse().map_err(|e: String| Error::Config(config::ConfigError::Message(e)))?;
+                        }
+                        "last_run_log" => {
+                            settings.last_run_log = value.parse().map_err(|e: String| Error::Config(config::ConfigError::Message(e)))?;
+                        }
+                        "network.connect_timeout_secs" => settings.network.connect_timeout_secs = parse_number(&key, &value)?,
+                        "network.read_timeout_secs" => settings.network.read_timeout_secs = parse_number(&key, &value)?,
+                        "network.download_timeout_secs" => settings.network.download_timeout_secs = parse_number(&key, &value)?,
+                        "network.retries" => settings.network.retries = parse_number(&key, &value)?,
+                        "network.backoff_ms" => settings.network.backoff_ms = parse_number(&key, &value)?,
+                        "network.offline" => settings.network.offline = parse_bool(&key, &value)?,
+                        "network.manifest_max_age_hours" => settings.network.manifest_max_age_hours = parse_number(&key, &value)?,
+                        "session_log.enabled" => settings.session_log.enabled = parse_bool(&key, &value)?,
+                        "session_log.max_bytes" => settings.session_log.max_bytes = parse_number(&key, &value)?,
+                        "session_log.max_age_secs" => settings.session_log.max_age_secs = parse_number(&key, &value)?,
+                        "session_log.max_files" => settings.session_log.max_files = parse_number(&key, &value)?,
+                        rule if rule.starts_with("lint.") => {
+                            let rule = &rule["lint.".len()..];
+                            if crbrs_lib::syntax::lint::rule(rule).is_none() {
+                                return Err(Error::Config(config::ConfigError::Message(format!("Unknown lint rule: {}", rule))));
+                            }
+                            let level = value.parse().map_err(|e: String| Error::Config(config::ConfigError::Message(e)))?;
+                            settings.lint.insert(rule.to_string(), level);
+                        }
+                        station if station.starts_with("station_timezones.") => {
+                            let station = &station["station_timezones.".len()..];
+                            let offset: crbrs_lib::timezone::UtcOffset =
+                                value.parse().map_err(|e: String| Error::Config(config::ConfigError::Message(e)))?;
+                            settings.station_timezones.insert(station.to_string(), offset.to_string());
+                        }
+                        station if station.starts_with("stations.") => {
+                            let Some((name, field)) = station["stations.".len()..].rsplit_once('.') else {
+                                return Err(Error::Config(config::ConfigError::Message(format!(
+                                    "Expected stations.<name>.<setting> (address, pakbus_address, security_code, security_code_secret or a threshold), got {}",
+                                    key
+                                ))));
+                            };
+                            // Keys are lowercased when the config is loaded, so match the existing entry loosely.
+                            let name = settings.stations.keys().find(|k| k.eq_ignore_ascii_case(name)).cloned().unwrap_or(name.to_string());
+                            let mut entry = settings.stations.get(&name).cloned().unwrap_or_default();
+                            match field {
+                                "address" => {
+                                    value
+                                        .parse::<crbrs_lib::logger::Endpoint>()
+                                        .map_err(|e| Error::Config(config::ConfigError::Message(e)))?;
+                                    entry.address = value.clone();
+                                }
+                                "pakbus_address" => entry.pakbus_address = parse_number(&key, &value)?,
+                                "security_code" => entry.security_code = parse_number(&key, &value)?,
+                                "security_code_secret" => entry.security_code_secret = Some(value.clone()),
+                                "min_battery" => entry.min_battery = Some(parse_volts(&key, &value)?),
+                                "min_lithium_battery" => entry.min_lithium_battery = Some(parse_volts(&key, &value)?),
+                                "max_skipped_scans" => entry.max_skipped_scans = Some(parse_number(&key, &value)?),
+                                "max_watchdog_errors" => entry.max_watchdog_errors = Some(parse_number(&key, &value)?),
+                                _ => return Err(Error::Config(config::ConfigError::Message(format!("Unknown configuration key: {}", key)))),
+                            }
+                            settings.stations.insert(name, entry);
+                        }
                         _ => {
                             let err_msg = format!("Unknown configuration key: {}", key);
                             // log::error!("{}", err_msg); // Already logged by main's catch-all
                             return Err(Error::Config(config::ConfigError::Message(err_msg)));
                         }
                     }
-                    println!("Set '{}' = '{}'", key, value);
                     crbrs_lib::config::save_settings(settings)?;
+                    say!("{}", tr!("config-set", key = key.as_str(), value = value.as_str()));
                 }
                 ConfigAction::SetAssociation {
                     extension,
@@ -313,7 +1774,7 @@ fn run_command(command: Commands, settings: &mut Settings) -> Result<(), Error>
                     settings
                         .file_associations
                         .insert(cleaned_ext.clone(), compiler_id.clone());
-                    println!("Associated '.{}' with compiler '{}'", cleaned_ext, compiler_id);
+                    say!("{}", tr!("config-association-set", extension = cleaned_ext.as_str(), compiler_id = compiler_id.as_str()));
                     crbrs_lib::config::save_settings(settings)?;
                 }
                 ConfigAction::UnsetAssociation { extension } => {
@@ -323,14 +1784,684 @@ fn run_command(command: Commands, settings: &mut Settings) -> Result<(), Error>
                         cleaned_ext
                     );
                     if settings.file_associations.remove(&cleaned_ext).is_some() {
-                        println!("Removed association for '.{}'", cleaned_ext);
+                        say!("{}", tr!("config-association-removed", extension = cleaned_ext.as_str()));
                         crbrs_lib::config::save_settings(settings)?;
                     } else {
-                        println!("No association found for '.{}'", cleaned_ext);
+                        say!("{}", tr!("config-association-not-found", extension = cleaned_ext.as_str()));
+                    }
+                }
+            }
+        }
+        Commands::Data { action } => match action {
+            DataAction::Qa { file, rules, format, output, strict } => {
+                log::info!("Executing Data QA command for file: {:?} (rules: {:?})", file, rules);
+                let report = crbrs_lib::qa::check_file(&file, rules.as_deref())?;
+                let rendered = crbrs_lib::qa::render(&report, format)?;
+                match output {
+                    Some(path) => {
+                        std::fs::write(&path, rendered)?;
+                        say!("{}", tr!("qa-report-written", flags = report.flags.len(), path = path.display().to_string()));
+                    }
+                    None => say_raw!("{}", rendered),
+                }
+                if strict && !report.flags.is_empty() {
+                    return Err(Error::Data(format!("{} value(s) in '{}' failed QA checks", report.flags.len(), file.display())));
+                }
+            }
+            DataAction::Merge { files, output, interval, to, from } => {
+                log::info!("Executing Data Merge command for files: {:?} into {:?}", files, output);
+                let retime = to.map(|to| crbrs_lib::timezone::Retime { from, to });
+                let report = crbrs_lib::merge::merge_files(&files, &output, interval, retime.as_ref().map(|r| (r, &*settings)))?;
+                say!(
+                    "{}",
+                    tr!(
+                        "merge-written",
+                        files = files.len(),
+                        records = report.records,
+                        path = output.display().to_string(),
+                        duplicates = report.duplicates,
+                        conflicts = report.conflicts
+                    )
+                );
+                if let Some(interval) = report.interval_ms {
+                    let interval = crbrs_lib::merge::format_interval(interval);
+                    say!("{}", tr!("merge-gaps", gaps = report.gaps.len(), interval = interval));
+                }
+                for gap in &report.gaps {
+                    say!("  {} -> {}  ({} missing)", gap.after, gap.before, gap.missing);
+                }
+            }
+            DataAction::Convert { file, output, format, append } => {
+                log::info!("Executing Data Convert command for file: {:?} into {:?}", file, output);
+                let Some(format) = format.or_else(|| crbrs_lib::convert::ConvertFormat::from_path(&output)) else {
+                    return Err(Error::Data(format!(
+                        "Cannot tell the format of '{}' from its extension; pass --format parquet, arrow, csv or toa5",
+                        output.display()
+                    )));
+                };
+                let show_progress = std::io::stderr().is_terminal() && !crbrs_lib::interaction::is_quiet();
+                let mut progress = |p: crbrs_lib::convert::Progress| {
+                    if show_progress {
+                        let percent = p.bytes_read * 100 / p.total_bytes.max(1);
+                        eprint!("\r  {:>3}% ({} of {}, {} records)   ", percent, format_bytes(p.bytes_read), format_bytes(p.total_bytes), p.records);
+                    }
+                };
+                let result = crbrs_lib::convert::convert_file(&file, &output, format, append, &mut progress);
+                if show_progress {
+                    eprintln!();
+                }
+                let report = result?;
+                say!(
+                    "{}",
+                    tr!("convert-written", records = report.records, path = output.display().to_string(), skipped = report.skipped)
+                );
+            }
+            DataAction::Card { card, convert, format, append } => {
+                log::info!("Executing Data Card command for {:?} (convert into: {:?})", card, convert);
+                let opened = crbrs_lib::card::Card::open(&card)?;
+                let files = opened.files()?;
+                say!("{}", tr!("card-files", count = files.len(), card = card.display().to_string()));
+                for file in &files {
+                    say!("  {:<32} {:<5} {:<16} {:<16} {:>10}", file.path, file.format.name(), file.station, file.table, format_bytes(file.size));
+                }
+                let Some(out_dir) = convert else {
+                    return Ok(());
+                };
+                let show_progress = std::io::stderr().is_terminal() && !crbrs_lib::interaction::is_quiet();
+                let mut progress = |file: &crbrs_lib::card::CardFile, p: crbrs_lib::convert::Progress| {
+                    if show_progress {
+                        eprint!("\r  {}: {:>3}% ({} records)   ", file.path, p.bytes_read * 100 / p.total_bytes.max(1), p.records);
+                    }
+                };
+                let outcomes = crbrs_lib::card::convert_all(&opened, &out_dir, format, append, &mut progress)?;
+                if show_progress {
+                    eprintln!();
+                }
+                let mut failed = 0;
+                for (file, outcome) in outcomes {
+                    let path = file.path.as_str();
+                    match outcome {
+                        crbrs_lib::card::Outcome::Converted(output, report) => say!(
+                            "{}",
+                            tr!("card-converted", file = path, output = output.display().to_string(), records = report.records)
+                        ),
+                        crbrs_lib::card::Outcome::Skipped => say!("{}", tr!("card-skipped", file = path, format = file.format.name())),
+                        crbrs_lib::card::Outcome::Failed(e) => {
+                            failed += 1;
+                            say!("{}", tr!("card-failed", file = path, error = e.to_string()));
+                        }
+                    }
+                }
+                if failed > 0 {
+                    return Err(Error::Data(format!("{} file(s) on '{}' could not be converted", failed, card.display())));
+                }
+            }
+            DataAction::Retime { file, output, from, to } => {
+                log::info!("Executing Data Retime command for file: {:?} into {:?}", file, output);
+                let retime = crbrs_lib::timezone::Retime { from, to };
+                let (records, from) = crbrs_lib::timezone::retime_file(&file, &output, &retime, settings)?;
+                say!(
+                    "{}",
+                    tr!(
+                        "retime-written",
+                        records = records,
+                        path = output.display().to_string(),
+                        from = from.to_string(),
+                        to = to.to_string()
+                    )
+                );
+            }
+            DataAction::Check { file, program } => {
+                log::info!("Executing Data Check command for file: {:?} against {:?}", file, program);
+                let result = crbrs_lib::data_check::check_files(&program, &file)?;
+                for finding in &result.findings {
+                    say!("{}: {}", file.display(), finding);
+                }
+                let errors = result.errors();
+                if errors > 0 {
+                    return Err(Error::Data(format!(
+                        "'{}' does not match table '{}' of '{}' ({} error(s))",
+                        file.display(),
+                        result.table,
+                        program.display(),
+                        errors
+                    )));
+                }
+                say!(
+                    "{}",
+                    tr!(
+                        "check-matches",
+                        path = file.display().to_string(),
+                        table = result.table.clone(),
+                        program = program.display().to_string(),
+                        warnings = result.findings.len()
+                    )
+                );
+            }
+        },
+        Commands::Deploy { action } => match action {
+            DeployAction::Check { program, against, force } => {
+                log::info!("Executing Deploy Check command for {:?} against {:?}", program, against);
+                let result = crbrs_lib::deploy::guard_files(&program, &against, force)?;
+                say!(
+                    "Version: {} -> {}",
+                    result.deployed_version.as_deref().unwrap_or("unversioned"),
+                    result.new_version.as_deref().unwrap_or("unversioned")
+                );
+                for change in &result.schema_changes {
+                    let marker = if change.is_breaking() { "⚠️" } else { "  " };
+                    say!("{} {}", marker, change.describe());
+                }
+                say!("✅ '{}' is safe to deploy{}", program.display(), if force { " (forced)" } else { "" });
+            }
+        },
+        Commands::Docgen { input, output, format } => {
+            log::info!("Executing Docgen command for {:?} into {:?} ({:?})", input, output, format);
+            let pages = crbrs_lib::docgen::generate(&input, &output, format)?;
+            say!("✅ Wrote {} page(s) to '{}'", pages.len(), output.display());
+        }
+        Commands::Explain { code } => {
+            log::info!("Executing Explain command for {:?}", code);
+            match code {
+                Some(code) => match crbrs_lib::explain::explain(&code) {
+                    Some(text) => say!("{}", text),
+                    None => {
+                        return Err(Error::Io(std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            tr!("explain-unknown-code", code = code.as_str()),
+                        )))
+                    }
+                },
+                None => {
+                    for (code, title) in crbrs_lib::explain::index() {
+                        say!("{}  {}", code, title);
+                    }
+                }
+            }
+        }
+        Commands::Init { dir, model, name, compiler, force } => {
+            log::info!("Executing Init command in {:?} (model: {:?})", dir, model);
+            let model = match model {
+                Some(model) => model,
+                None => crbrs_lib::interaction::ask(&tr!("init-ask-model"), Some(crbrs_lib::init::MODELS[0].name))?,
+            };
+            let model = crbrs_lib::init::logger_model(&model)?;
+            let name = name.unwrap_or_else(|| {
+                std::path::absolute(&dir)
+                    .ok()
+                    .and_then(|dir| dir.file_name().map(|n| n.to_string_lossy().into_owned()))
+                    .unwrap_or_else(|| "station".to_string())
+            });
+            let pinned = compiler.is_some() || settings.file_associations.contains_key(model.extension);
+            let options = crbrs_lib::init::InitOptions { model, name, compiler_id: compiler, force };
+            for file in crbrs_lib::init::init(&dir, &options, settings)? {
+                let path = file.path.display().to_string();
+                if file.written {
+                    say!("{}", tr!("init-wrote", path = path));
+                } else {
+                    say!("{}", tr!("init-kept", path = path));
+                }
+            }
+            if !pinned {
+                say!("{}", tr!("init-no-compiler", extension = model.extension));
+            }
+            say!("{}", tr!("init-next-steps"));
+        }
+        Commands::Fmt { paths, check, fix_case } => {
+            log::info!("Executing Fmt command on {:?} (check: {}, fix case: {})", paths, check, fix_case);
+            let files = crbrs_lib::build::collect_inputs(&paths)?;
+            let mut changed = 0;
+            for file in &files {
+                if crbrs_lib::format::format_file(file, check, fix_case)? {
+                    changed += 1;
+                    let path = file.display().to_string();
+                    if check {
+                        say!("{}", tr!("fmt-unformatted", path = path));
+                    } else {
+                        say!("{}", tr!("fmt-formatted", path = path));
+                    }
+                }
+            }
+            if check && changed > 0 {
+                let command = if fix_case { "crbrs fmt --fix-case" } else { "crbrs fmt" };
+                return Err(Error::Format(format!("{} of {} file(s) are not formatted; `{}` formats them", changed, files.len(), command)));
+            }
+            if !check {
+                say!("{}", tr!("fmt-summary", changed = changed, total = files.len()));
+            }
+        }
+        Commands::Fleet { action } => match action {
+            FleetAction::Status { json, run } => {
+                log::info!("Executing Fleet Status command ({:?})", run);
+                let fleet = crbrs_lib::fleet::status(settings, run.options())?;
+                if json {
+                    say!("{}", serde_json::to_string_pretty(&fleet).map_err(|e| Error::Io(e.into()))?);
+                } else {
+                    for result in &fleet.stations {
+                        let name = &result.station;
+                        if result.outcome == crbrs_lib::fleet::Outcome::Skipped {
+                            say!("⏭️ {}: skipped", name);
+                            continue;
+                        }
+                        let alerts: Vec<_> = fleet.alerts.iter().filter(|a| &a.station == name).collect();
+                        let health = alerts.iter().map(|a| a.health).max().unwrap_or(crbrs_lib::status::Health::Ok);
+                        let summary: Vec<String> = alerts
+                            .iter()
+                            .map(|a| match &a.value {
+                                Some(value) => format!("{} {} ({})", a.item, value, a.problem),
+                                None => format!("{}: {}", a.item, a.problem),
+                            })
+                            .collect();
+                        let summary = if summary.is_empty() { "ok".to_string() } else { summary.join("; ") };
+                        say!("{}", health_line(health, &format!("{}: {}", name, summary)));
+                    }
+                }
+                if !fleet.alerts.is_empty() {
+                    let mut stations: Vec<&str> = fleet.alerts.iter().map(|a| a.station.as_str()).collect();
+                    stations.dedup();
+                    let skipped = fleet.stations.iter().filter(|r| r.outcome == crbrs_lib::fleet::Outcome::Skipped).count();
+                    let skipped = if skipped > 0 { format!(", {} station(s) skipped", skipped) } else { String::new() };
+                    return Err(Error::Alert(format!("{} alert(s) at {} station(s){}", fleet.alerts.len(), stations.len(), skipped)));
+                }
+                if !json {
+                    say!("{}", tr!("fleet-status-ok", stations = fleet.stations.len()));
+                }
+            }
+        },
+        Commands::History { action } => match action {
+            HistoryAction::Stations { station, limit, json } => {
+                log::info!("Executing History Stations command (station: {:?})", station);
+                let mut sessions = crbrs_lib::history::station_sessions(station.as_deref());
+                if let Some(limit) = limit {
+                    sessions.drain(..sessions.len().saturating_sub(limit));
+                }
+                if json {
+                    say!("{}", serde_json::to_string_pretty(&sessions).map_err(|e| Error::Io(e.into()))?);
+                    return Ok(());
+                }
+                if sessions.is_empty() {
+                    say!("{}", tr!("history-no-sessions"));
+                }
+                for session in &sessions {
+                    let mut text = format!("{} {} {}", session.started, session.station, session.operation);
+                    if let Some(file) = &session.file {
+                        let file = PathBuf::from(file);
+                        text.push_str(&format!(" {}", file.file_name().unwrap_or(file.as_os_str()).to_string_lossy()));
+                    }
+                    if let Some(version) = &session.program_version {
+                        text.push_str(&format!(" v{}", version));
+                    }
+                    if let Some(compiler) = &session.compiler {
+                        text.push_str(&format!(", built with {}", compiler));
+                    }
+                    text.push_str(&format!(
+                        ", {} sent, {} received in {:.1} s: {}",
+                        format_bytes(session.bytes_sent),
+                        format_bytes(session.bytes_received),
+                        session.duration_ms as f64 / 1000.0,
+                        session.error.as_deref().unwrap_or("ok")
+                    ));
+                    let health = match session.error {
+                        Some(_) => crbrs_lib::status::Health::Critical,
+                        None => crbrs_lib::status::Health::Ok,
+                    };
+                    say!("{}", health_line(health, &text));
+                }
+            }
+        },
+        Commands::Logger { action } => match action {
+            LoggerAction::Terminal { station, pakbus_address } => {
+                use std::io::BufRead;
+                log::info!("Executing Logger Terminal command for {}", station);
+                let (name, mut station) = crbrs_lib::station::resolve(settings, &station)?;
+                station.pakbus_address = pakbus_address.unwrap_or(station.pakbus_address);
+                let mut terminal = crbrs_lib::terminal::Terminal::open(&name, &station, &settings.network, &settings.session_log)?;
+                if let Some(path) = terminal.session_log() {
+                    say!("{}", tr!("logger-terminal-log", path = path.display().to_string()));
+                }
+                let show = |text: &str| {
+                    say_raw!("{}", text.replace("\r\n", "\n").replace('\r', "\n"));
+                    let _ = std::io::Write::flush(&mut std::io::stdout());
+                };
+                show(&terminal.wake()?);
+                for line in std::io::stdin().lock().lines() {
+                    show(&terminal.command(line?.trim_end())?);
+                }
+                say!();
+            }
+            LoggerAction::Send { station, program, against, force, no_run, pakbus_address, security_code } => {
+                log::info!("Executing Logger Send command for {:?} to {}", program, station);
+                let (name, mut station) = crbrs_lib::station::resolve(settings, &station)?;
+                station.pakbus_address = pakbus_address.unwrap_or(station.pakbus_address);
+                if let Some(code) = security_code {
+                    station.security_code = code;
+                    station.security_code_secret = None; // The given code wins over the keychain
+                }
+                if crbrs_lib::artifact::verify_if_sealed(&program, settings)? {
+                    log::info!("{:?} matches its checksum sidecar", program);
+                }
+                if let Some(against) = &against {
+                    match crbrs_lib::deploy::guard_files(&program, against, force) {
+                        // Unattended, this is answered no and the send stays blocked.
+                        Err(Error::DeployBlocked(reason))
+                            if crbrs_lib::interaction::confirm(&tr!("logger-send-confirm-blocked", reason = reason.as_str()), false)? =>
+                        {
+                            crbrs_lib::deploy::guard_files(&program, against, true)?;
+                        }
+                        result => {
+                            result?;
+                        }
+                    }
+                }
+                // Unless compared with a copy above or forced, the logger's program is read back.
+                let confirm = |reason: &str| crbrs_lib::interaction::confirm(&tr!("logger-send-confirm-blocked", reason = reason), false);
+                let guard: Option<crbrs_lib::deploy::ConfirmBlocked> = (against.is_none() && !force).then_some(&confirm);
+                let report = crbrs_lib::send::send_program(&name, &station, &settings.network, &program, !no_run, guard)?;
+                let sent = format_bytes(report.session.bytes_sent);
+                if no_run {
+                    say!("{}", tr!("logger-sent", file = report.file_name.as_str(), station = name.as_str(), bytes = sent));
+                } else {
+                    say!(
+                        "{}",
+                        tr!(
+                            "logger-sent-running",
+                            file = report.file_name.as_str(),
+                            station = name.as_str(),
+                            bytes = sent,
+                            seconds = report.hold_off_secs
+                        )
+                    );
+                }
+            }
+            LoggerAction::Status { station, pakbus_address, security_code, json } => {
+                log::info!("Executing Logger Status command for {}", station);
+                let (name, mut station) = crbrs_lib::station::resolve(settings, &station)?;
+                station.pakbus_address = pakbus_address.unwrap_or(station.pakbus_address);
+                if let Some(code) = security_code {
+                    station.security_code = code;
+                    station.security_code_secret = None; // The given code wins over the keychain
+                }
+                let report = crbrs_lib::status::query(&name, &station, &settings.network, &station.thresholds())?;
+                if json {
+                    say!("{}", serde_json::to_string_pretty(&report).map_err(|e| Error::Io(e.into()))?);
+                    return Ok(());
+                }
+                let status = &report.status;
+                say!(
+                    "{}",
+                    tr!(
+                        "logger-status-header",
+                        station = name.as_str(),
+                        os = status.os_version.as_deref().unwrap_or("?"),
+                        program = status.program.as_deref().unwrap_or("?")
+                    )
+                );
+                for check in &report.checks {
+                    let text = match &check.problem {
+                        Some(problem) => format!("{}: {} ({})", check.item, check.value, problem),
+                        None => format!("{}: {}", check.item, check.value),
+                    };
+                    say!("  {}", health_line(check.health, &text));
+                }
+            }
+        },
+        Commands::Logs { session, grep } => {
+            log::info!("Executing Logs command (session: {:?}, grep: {:?})", session, grep);
+            let mut sessions = crbrs_lib::session_log::list_sessions()?;
+            if let Some(wanted) = &session {
+                sessions.retain(|s| s.name.contains(wanted.as_str()));
+                match sessions.len() {
+                    1 => {}
+                    0 => return Err(Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("No session log matches '{}'", wanted),
+                    ))),
+                    n => return Err(Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("'{}' matches {} session logs; be more specific", wanted, n),
+                    ))),
+                }
+            }
+            if session.is_none() && grep.is_none() {
+                say!("Session logs ({}):", crbrs_lib::session_log::session_log_dir()?.display());
+                if sessions.is_empty() {
+                    say!("  (None)");
+                }
+                for s in &sessions {
+                    say!(
+                        "  - {:<60} {:>10} bytes  {}",
+                        s.name,
+                        s.size,
+                        crbrs_lib::clock::timestamp(s.modified)
+                    );
+                }
+            } else {
+                let multiple = sessions.len() > 1;
+                for (name, line) in crbrs_lib::session_log::search(&sessions, grep.as_deref())? {
+                    if multiple {
+                        say!("{}: {}", name, line);
+                    } else {
+                        say!("{}", line);
                     }
                 }
             }
         }
+        Commands::Metadata { action } => match action {
+            MetadataAction::Export { input_file, format, output, creator, contact } => {
+                log::info!("Executing Metadata Export command for file: {:?} ({:?})", input_file, format);
+                let parties = crbrs_lib::metadata::Parties { creator, contact };
+                let rendered = crbrs_lib::metadata::export_file(&input_file, format, &parties)?;
+                match output {
+                    Some(path) => {
+                        std::fs::write(&path, rendered)?;
+                        say!("✅ Data dictionary written to '{}'", path.display());
+                    }
+                    None => say_raw!("{}", rendered),
+                }
+            }
+            MetadataAction::Check { path } => {
+                log::info!("Executing Metadata Check command for {:?}", path);
+                let (root, collisions) = crbrs_lib::metadata::check_project(&path)?;
+                for collision in &collisions {
+                    say!("{}", collision);
+                }
+                if !collisions.is_empty() {
+                    return Err(Error::TableCollisions(collisions.len()));
+                }
+                say!("✅ The tables of the programs in '{}' do not collide", root.display());
+            }
+        },
+        Commands::Provision { from, json } => {
+            log::info!("Executing Provision command from {:?}", from);
+            let plan = crbrs_lib::provision::Plan::load(&from)?;
+            let report = crbrs_lib::provision::run(&plan, settings)?;
+            let lock = update_project_lock(settings, &report.installed)?;
+            if json {
+                say!("{}", serde_json::to_string_pretty(&report).map_err(|e| Error::Io(e.into()))?);
+            } else {
+                for compiler_id in &report.installed {
+                    say!("{}", tr!("compiler-installed", compiler_id = compiler_id.as_str()));
+                }
+                for compiler_id in &report.already_installed {
+                    say!("{}", tr!("provision-already-installed", compiler_id = compiler_id.as_str()));
+                }
+                for check in &report.checks {
+                    match &check.problem {
+                        None => say!("{}", tr!("provision-check-ok", compiler_id = check.compiler_id.as_str())),
+                        Some(problem) => eprintln!(
+                            "{}",
+                            tr!("provision-check-failed", compiler_id = check.compiler_id.as_str(), problem = problem.as_str())
+                        ),
+                    }
+                }
+                say!(
+                    "{}",
+                    tr!(
+                        "provision-done",
+                        plan = from.display().to_string(),
+                        installed = report.installed.len(),
+                        kept = report.already_installed.len(),
+                        failed = report.failed()
+                    )
+                );
+                if let Some(lock) = lock {
+                    say!("{}", tr!("project-lock-updated", path = lock.display().to_string()));
+                }
+            }
+            if report.failed() > 0 {
+                return Err(Error::Provision(format!("{} of {} compilers failed the check", report.failed(), report.checks.len())));
+            }
+        }
+        Commands::ReportBug { source, output } => {
+            log::info!("Executing Report Bug command (source: {:?})", source);
+            let output = output.unwrap_or_else(|| {
+                PathBuf::from(format!("crbrs-bug-report-{}.zip", crbrs_lib::clock::file_stamp(std::time::SystemTime::now())))
+            });
+            let report = crbrs_lib::bug_report::create(settings, &output, source.as_deref())?;
+            say!("{}", tr!("bug-report-written", path = report.path.display().to_string()));
+            for entry in &report.entries {
+                say!("    {}", entry);
+            }
+            say!("{}", tr!("bug-report-attach", url = crbrs_lib::bug_report::ISSUES_URL));
+        }
+        Commands::SelfCmd { action } => match action {
+            SelfAction::Purge { compilers, caches, history, config, secrets, yes, dry_run } => {
+                use crbrs_lib::purge::{self, Category, Target};
+                let chosen: Vec<Category> = [
+                    (compilers, Category::Compilers),
+                    (caches, Category::Caches),
+                    (history, Category::History),
+                    (config, Category::Config),
+                    (secrets, Category::Secrets),
+                ]
+                .into_iter()
+                .filter_map(|(flag, category)| flag.then_some(category))
+                .collect();
+                let categories = if chosen.is_empty() { Category::ALL.to_vec() } else { chosen };
+                log::info!("Executing Self Purge command ({:?})", categories);
+                let targets = purge::plan(settings, &categories)?;
+                if targets.is_empty() {
+                    say!("{}", tr!("purge-nothing"));
+                    return Ok(());
+                }
+                say!("{}", tr!("purge-summary"));
+                let mut total = 0;
+                for target in &targets {
+                    match target {
+                        Target::Path { category, path, bytes } => {
+                            total += bytes;
+                            say!("  [{}] {} ({})", category, path.display(), format_bytes(*bytes));
+                        }
+                        Target::Secret { name } => say!("  [{}] {}", target.category(), name),
+                    }
+                }
+                say!("{}", tr!("purge-total", size = format_bytes(total)));
+                if dry_run {
+                    return Ok(());
+                }
+                if !yes {
+                    if crbrs_lib::interaction::is_unattended() {
+                        return Err(Error::InputRequired(tr!("purge-confirm")));
+                    }
+                    if !crbrs_lib::interaction::confirm(&tr!("purge-confirm"), false)? {
+                        return Err(Error::Cancelled);
+                    }
+                }
+                let removed = purge::purge(&targets)?;
+                say!("{}", tr!("purge-done", count = removed));
+            }
+        },
+        Commands::Secret { action } => match action {
+            SecretAction::Set { name } => {
+                let value = crbrs_lib::interaction::ask_secret(&tr!("secret-prompt", name = name.as_str()))?;
+                crbrs_lib::secret::set(&name, &value)?;
+                say!("{}", tr!("secret-stored", name = name.as_str()));
+            }
+            SecretAction::Get { name } => {
+                // The value is this command's output, so it is printed even with --quiet.
+                println!("{}", crbrs_lib::secret::require(&name)?);
+            }
+            SecretAction::Remove { name } => {
+                if crbrs_lib::secret::remove(&name)? {
+                    say!("{}", tr!("secret-removed", name = name.as_str()));
+                } else {
+                    return Err(Error::Secret(tr!("secret-not-found", name = name.as_str())));
+                }
+            }
+        },
+        Commands::Sensor { action } => match action {
+            SensorAction::Add { kind } => {
+                use crbrs_lib::sensor::SensorSpec;
+                use crbrs_lib::syntax::sensor::ModbusDevice;
+                let (file, spec, common) = match kind {
+                    SensorKind::Sdi12 { file, address, model, values, command, port, common } => {
+                        (file, SensorSpec::Sdi12 { model, address, port, command, values }, common)
+                    }
+                    SensorKind::Modbus { file, address, port, baud, function, start, format, values, common } => {
+                        let device = ModbusDevice { port, baud, address, function, format };
+                        (file, SensorSpec::Modbus { device, start, values }, common)
+                    }
+                };
+                log::info!("Executing Sensor Add command for file: {:?} ({:?})", file, spec);
+                let placement = crbrs_lib::syntax::sensor::Placement { table: common.table, processing: common.processing };
+                let added = crbrs_lib::sensor::add_to_file(&file, &spec, common.name.as_deref(), &placement, common.dry_run)?;
+                if common.dry_run {
+                    say_raw!("{}", added.source);
+                } else {
+                    say!("{}", tr!("sensor-added", name = added.name, file = file.display().to_string()));
+                }
+            }
+            SensorAction::ImportModbus { csv, into, address, port, baud, function, common } => {
+                log::info!("Executing Sensor Import Modbus command for map: {:?} (into: {:?})", csv, into);
+                let entries = crbrs_lib::sensor::read_register_map(&csv, function)?;
+                let spec = crbrs_lib::sensor::SensorSpec::RegisterMap { port, baud, address, map: csv, entries };
+                match into {
+                    Some(file) => {
+                        let placement = crbrs_lib::syntax::sensor::Placement { table: common.table, processing: common.processing };
+                        let added = crbrs_lib::sensor::add_to_file(&file, &spec, common.name.as_deref(), &placement, common.dry_run)?;
+                        if common.dry_run {
+                            say_raw!("{}", added.source);
+                        } else {
+                            say!("{}", tr!("sensor-added", name = added.name, file = file.display().to_string()));
+                        }
+                    }
+                    None => {
+                        let snippet = crbrs_lib::sensor::snippet(&spec, common.name.as_deref(), common.processing)?;
+                        say_raw!("{}", snippet);
+                    }
+                }
+            }
+            SensorAction::Models => {
+                for model in crbrs_lib::syntax::sensor::sdi12_models() {
+                    let values: Vec<String> = model.values.iter().map(|(suffix, units)| format!("{} ({})", suffix, units)).collect();
+                    say!("  - {:<8} {} [{}]: {}", model.name, model.description, model.command, values.join(", "));
+                }
+            }
+        },
+        Commands::VerifyProject { path, json, jobs, timeout } => {
+            log::info!("Executing Verify Project command on {:?}", path);
+            if let Some(timeout) = timeout {
+                settings.compile_timeout_secs = timeout;
+            }
+            if json {
+                crbrs_lib::interaction::set_quiet(true); // stdout is the report alone
+            }
+            let options = crbrs_lib::build::BuildOptions { jobs: jobs.into(), ..Default::default() };
+            let report = crbrs_lib::verify::verify_project(&path, &options, settings, |result| report_file(result, &result.file))?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).map_err(|e| Error::Io(e.into()))?);
+            } else {
+                print_verify_report(&report);
+            }
+            report.result()?;
+        }
+        Commands::Version { action } => match action {
+            VersionAction::Bump { file, level, message, author } => {
+                log::info!("Executing Version Bump command for file: {:?} ({:?})", file, level);
+                let new_version = crbrs_lib::version::bump_file(&file, level, &message, author.as_deref())?;
+                say!("✅ Bumped '{}' to version {}", file.display(), new_version);
+            }
+        },
     }
     Ok(())
 }
\ No newline at end of file
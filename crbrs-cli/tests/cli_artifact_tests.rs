@@ -0,0 +1,53 @@
+// FILE: crbrs-cli/tests/cli_artifact_tests.rs
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
+    Ok(cmd)
+}
+
+#[test]
+fn test_artifact_seal_then_verify() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let image = temp_dir.path().join("station.bin");
+    std::fs::write(&image, b"\x01\x02image")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("artifact").arg("seal").arg(&image);
+    cmd.assert().success();
+
+    let sidecar = std::fs::read_to_string(temp_dir.path().join("station.bin.sha256"))?;
+    assert!(sidecar.ends_with("  station.bin\n"));
+    assert_eq!(sidecar.split_whitespace().next().unwrap().len(), 64);
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("artifact").arg("verify").arg(&image);
+    cmd.assert().success().stdout(predicate::str::contains("verified"));
+    Ok(())
+}
+
+#[test]
+fn test_artifact_verify_detects_tampering() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let image = temp_dir.path().join("station.bin");
+    std::fs::write(&image, b"original")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("artifact").arg("seal").arg(&image);
+    cmd.assert().success();
+
+    std::fs::write(&image, b"tampered")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("artifact").arg("verify").arg(&image);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("does not match its checksum"));
+    Ok(())
+}
@@ -0,0 +1,1904 @@
+// FILE: crbrs-cli/tests/cli_compiler_tests.rs
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
+    Ok(cmd)
+}
+
+#[test]
+fn test_compiler_du_no_compilers() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("du");
+    cmd.assert().success().stdout(predicate::str::contains("No compilers installed."));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compiler_du_reports_shared_files() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let storage = temp_dir.path().join("storage");
+    for id in ["comp-v1", "comp-v2"] {
+        std::fs::create_dir_all(storage.join(id))?;
+        std::fs::write(storage.join(id).join("comp.exe"), vec![b'x'; 100])?;
+    }
+    std::fs::write(storage.join("comp-v1").join("shared.dll"), vec![b'd'; 2048])?;
+    std::fs::hard_link(storage.join("comp-v1").join("shared.dll"), storage.join("comp-v2").join("shared.dll"))?;
+
+    let mut config = format!("compiler_storage_path = {:?}\n", storage.to_string_lossy());
+    for id in ["comp-v1", "comp-v2"] {
+        config.push_str(&format!(
+            "\n[installed_compilers.{id}]\nid = \"{id}\"\ndescription = \"test\"\nversion = \"1\"\n\
+             install_subdir = \"{id}\"\nexecutable_name = \"comp.exe\"\nrequires_wine = false\n"
+        ));
+    }
+    std::fs::create_dir_all(temp_dir.path().join("config/crbrs"))?;
+    std::fs::write(temp_dir.path().join("config/crbrs/config.toml"), config)?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("du");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("comp-v2"))
+        .stdout(predicate::str::contains("Shared:    2.0 KiB"))
+        .stdout(predicate::str::contains("Total: 2.2 KiB on disk (2.0 KiB saved"));
+    Ok(())
+}
+
+#[test]
+fn test_compiler_link_and_remove_keeps_files() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let shared = temp_dir.path().join("opt").join("cr1000comp");
+    std::fs::create_dir_all(&shared)?;
+    std::fs::write(shared.join("CR1000Comp.exe"), b"MZ")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&shared);
+    cmd.assert().failure().stderr(predicate::str::contains("specify the compiler executable"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&shared).arg("--executable").arg("CR1000Comp.exe");
+    cmd.assert().success().stdout(predicate::str::contains("Compiler 'cr1000comp' linked"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list");
+    cmd.assert().success().stdout(predicate::str::contains("cr1000comp")).stdout(predicate::str::contains("(linked: "));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("remove").arg("cr1000comp");
+    cmd.assert().success();
+    assert!(shared.join("CR1000Comp.exe").exists());
+    Ok(())
+}
+
+#[test]
+fn test_installed_compilers_are_kept_out_of_the_config_file() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let shared = temp_dir.path().join("opt").join("cr1000comp");
+    std::fs::create_dir_all(&shared)?;
+    std::fs::write(shared.join("CR1000Comp.exe"), b"MZ")?;
+    let config_file = temp_dir.path().join("config/crbrs/config.toml");
+    let state_file = temp_dir.path().join("data/crbrs/state.toml");
+    std::fs::create_dir_all(config_file.parent().unwrap())?;
+    let hand_edited = "# Our mirror\ncompiler_repository_url = \"https://example.com/manifest.json\"\n";
+    std::fs::write(&config_file, hand_edited)?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&shared).arg("--executable").arg("CR1000Comp.exe");
+    cmd.assert().success();
+    assert_eq!(std::fs::read_to_string(&config_file)?, hand_edited, "installing leaves the config file alone");
+    assert!(std::fs::read_to_string(&state_file)?.contains("[installed_compilers.cr1000comp]"));
+
+    // A config file from an older version, with the compilers in it, is migrated on the next save.
+    std::fs::remove_file(&state_file)?;
+    let legacy = format!(
+        "{}\n[installed_compilers.old]\nid = \"old\"\ndescription = \"test\"\nversion = \"1\"\n\
+         install_subdir = \"old\"\nexecutable_name = \"comp.exe\"\nrequires_wine = false\n",
+        hand_edited
+    );
+    std::fs::write(&config_file, legacy)?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list");
+    cmd.assert().success().stdout(predicate::str::contains("old"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&shared).arg("--executable").arg("CR1000Comp.exe");
+    cmd.assert().success();
+    let config = std::fs::read_to_string(&config_file)?;
+    assert!(!config.contains("installed_compilers"), "{}", config);
+    assert!(config.contains("https://example.com/manifest.json"), "{}", config);
+    let state = std::fs::read_to_string(&state_file)?;
+    assert!(state.contains("[installed_compilers.old]") && state.contains("[installed_compilers.cr1000comp]"), "{}", state);
+    Ok(())
+}
+
+#[test]
+fn test_compile_uses_in_source_directive() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let program = temp_dir.path().join("station.cr300");
+    std::fs::write(&program, "'crbrs: compiler=cr300-std-10.1 target=CR310\nBeginProg\nEndProg\n")?;
+
+    // The directive wins over the (missing) association for .cr300
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program);
+    cmd.assert().failure().stderr(predicate::str::contains("cr300-std-10.1"));
+
+    std::fs::write(&program, "'crbrs: target=CR310\nBeginProg\nEndProg\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program);
+    cmd.assert().failure().stderr(predicate::str::contains("No installed compiler supports target 'CR310'"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_install_from_tar_gz_and_7z_archives() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let script = b"#!/bin/sh\necho \"$1 -- Compiled OK.\"\n";
+
+    // A mirrored bundle repackaged as a tarball; its format is detected.
+    let mut tarball = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+    let mut header = tar::Header::new_gnu();
+    header.set_size(script.len() as u64);
+    header.set_mode(0o755);
+    tarball.append_data(&mut header, "fakecomp", &script[..])?;
+    let tar_url = serve(tarball.into_inner()?.finish()?);
+
+    // A 7z archive, with the Unix mode as p7zip stores it.
+    let mut sevenz = sevenz_rust::SevenZWriter::new(std::io::Cursor::new(Vec::new()))?;
+    let mut entry = sevenz_rust::SevenZArchiveEntry::new();
+    entry.name = "fakecomp".to_string();
+    entry.has_stream = true;
+    entry.has_windows_attributes = true;
+    entry.windows_attributes = 0x8000 | (0o100755 << 16);
+    sevenz.push_archive_entry(entry, Some(&script[..]))?;
+    let sevenz_url = serve(sevenz.finish()?.into_inner());
+
+    let manifest = format!(
+        "manifest_version = \"1\"\n\n\
+         [compilers.cr1000x-tar]\ndescription = \"CR1000X\"\nversion = \"1\"\ndownload_url = \"{}/cr1000x.tar.gz\"\n\
+         executable_name = \"fakecomp\"\nrequires_wine = false\n\n\
+         [compilers.cr300-7z]\ndescription = \"CR300\"\nversion = \"1\"\ndownload_url = \"{}/cr300.bin\"\n\
+         archive_format = \"7z\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n",
+        tar_url, sevenz_url
+    );
+    let url = serve(manifest);
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_repository_url").arg(format!("{}/compilers.toml", url));
+    cmd.assert().success();
+
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+    for id in ["cr1000x-tar", "cr300-7z"] {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("compiler").arg("install").arg(id);
+        cmd.assert().success();
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("compile").arg(&program).arg("--compiler").arg(id);
+        cmd.assert().success();
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unsafe_7z_entries_are_skipped_without_corrupting_the_rest() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let script = b"#!/bin/sh\necho \"$1 -- Compiled OK.\"\n";
+    let evil = b"#!/bin/sh\necho pwned\n";
+
+    // Both entries in one solid block: the skipped entry's bytes come first in the stream.
+    let mut unsafe_entry = sevenz_rust::SevenZArchiveEntry::new();
+    unsafe_entry.name = "../evil".to_string();
+    unsafe_entry.has_stream = true;
+    let mut entry = sevenz_rust::SevenZArchiveEntry::new();
+    entry.name = "fakecomp".to_string();
+    entry.has_stream = true;
+    entry.has_windows_attributes = true;
+    entry.windows_attributes = 0x8000 | (0o100755 << 16);
+    let mut sevenz = sevenz_rust::SevenZWriter::new(std::io::Cursor::new(Vec::new()))?;
+    let sources = vec![sevenz_rust::SourceReader::from(&evil[..]), sevenz_rust::SourceReader::from(&script[..])];
+    sevenz.push_archive_entries(vec![unsafe_entry, entry], sevenz_rust::SeqReader::new(sources))?;
+    let archive = temp_dir.path().join("cr300.7z");
+    std::fs::write(&archive, sevenz.finish()?.into_inner())?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("install").arg("cr300-7z").arg("--from-file").arg(&archive).arg("--executable").arg("fakecomp");
+    cmd.assert().success();
+    let compilers = temp_dir.path().join("data/crbrs/compilers");
+    assert_eq!(std::fs::read(compilers.join("cr300-7z/fakecomp"))?, script);
+    assert!(!compilers.join("evil").exists() && !compilers.join("cr300-7z/evil").exists());
+
+    let program = temp_dir.path().join("station.cr300");
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("cr300-7z");
+    cmd.assert().success();
+    Ok(())
+}
+
+/// A zip holding a fake compiler that prints `banner`.
+fn fake_compiler_zip(banner: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    use std::io::Write;
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file("fakecomp", zip::write::SimpleFileOptions::default().unix_permissions(0o755))?;
+    write!(zip, "#!/bin/sh\necho \"$1 -- {}. Compiled OK.\"\n", banner)?;
+    Ok(zip.finish()?.into_inner())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    use sha2::Digest;
+    sha2::Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_install_falls_back_to_the_next_mirror() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let archive = fake_compiler_zip("mirrored")?;
+    // Nothing listens on a port once its listener is dropped.
+    let closed = format!("http://{}", std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?);
+    let corrupt = serve("not the archive");
+    let good = serve(archive.clone());
+    let entry = |id: &str, first: &str| {
+        format!(
+            "[compilers.{}]\ndescription = \"CR1000X\"\nversion = \"1\"\ndownload_url = \"{}/archive.zip\"\n\
+             download_urls = [\"{}/archive.zip\"]\nsha256 = \"{}\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n\n",
+            id, first, good, sha256_hex(&archive)
+        )
+    };
+    let manifest = format!("manifest_version = \"1\"\n\n{}{}", entry("unreachable-first", &closed), entry("corrupt-first", &corrupt));
+    let url = serve(manifest);
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_repository_url").arg(format!("{}/compilers.toml", url));
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("network.retries").arg("0");
+    cmd.assert().success();
+
+    for id in ["unreachable-first", "corrupt-first"] {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("compiler").arg("install").arg(id);
+        cmd.assert().success();
+        let installed = std::fs::read_to_string(temp_dir.path().join("data/crbrs/compilers").join(id).join("fakecomp"))?;
+        assert!(installed.contains("mirrored"), "{}: {}", id, installed);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_install_from_a_delta_patch() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let base = fake_compiler_zip("version 1")?;
+    let target = fake_compiler_zip("version 2")?;
+    std::fs::write(temp_dir.path().join("v1.zip"), &base)?;
+    std::fs::write(temp_dir.path().join("v2.zip"), &target)?;
+    let made = Command::new("zstd")
+        .current_dir(temp_dir.path())
+        .args(["-q", "--patch-from=v1.zip", "--long=27", "v2.zip", "-o", "v1-to-v2.zst"])
+        .status();
+    if !made.is_ok_and(|status| status.success()) {
+        eprintln!("zstd is not available; skipping");
+        return Ok(());
+    }
+    let patch = std::fs::read(temp_dir.path().join("v1-to-v2.zst"))?;
+
+    // The full archive of v2 is not served, so only the patch can install it; v2-full has a
+    // corrupt patch and must fall back to its full download.
+    let entry = |id: &str, archive_url: &str, archive: &[u8], patches: &str| {
+        format!(
+            "[compilers.{}]\ndescription = \"CR1000X\"\nversion = \"2\"\ndownload_url = \"{}/archive.zip\"\n\
+             sha256 = \"{}\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n{}\n",
+            id, archive_url, sha256_hex(archive), patches
+        )
+    };
+    let patch_of = |id: &str, url: &str, patch: &[u8]| {
+        format!("[[compilers.{}.patches]]\nbase = \"cr1000x-v1\"\nurl = \"{}/v1-to-v2.zst\"\nsha256 = \"{}\"\n", id, url, sha256_hex(patch))
+    };
+    let corrupt = b"not a patch".to_vec();
+    let manifest = format!(
+        "manifest_version = \"1\"\n\n{}{}{}{}{}",
+        entry("cr1000x-v1", &serve(base.clone()), &base, ""),
+        entry("cr1000x-v2", &serve("not found"), &target, ""),
+        patch_of("cr1000x-v2", &serve(patch.clone()), &patch),
+        entry("cr1000x-v2-full", &serve(target.clone()), &target, ""),
+        patch_of("cr1000x-v2-full", &serve(corrupt.clone()), &corrupt),
+    );
+    let url = serve(manifest);
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_repository_url").arg(format!("{}/compilers.toml", url));
+    cmd.assert().success();
+
+    let archives = temp_dir.path().join("data/crbrs/compilers/.archives");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("install").arg("cr1000x-v1");
+    cmd.assert().success();
+    assert_eq!(std::fs::read(archives.join("cr1000x-v1.archive"))?, base, "the archive of a patch base is kept");
+
+    for id in ["cr1000x-v2", "cr1000x-v2-full"] {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("compiler").arg("install").arg(id);
+        cmd.assert().success();
+        let installed = std::fs::read_to_string(temp_dir.path().join("data/crbrs/compilers").join(id).join("fakecomp"))?;
+        assert!(installed.contains("version 2"), "{}: {}", id, installed);
+        assert!(!archives.join(format!("{}.archive", id)).exists(), "no patch is made against {}", id);
+    }
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("du");
+    cmd.assert().success().stdout(predicate::str::contains("Archives kept for delta patches:"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_upgrade_reinstalls_outdated_compilers() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    let temp_dir = TempDir::new()?;
+    let archive = |version: &str| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("fakecomp", zip::write::SimpleFileOptions::default().unix_permissions(0o755))?;
+        write!(zip, "#!/bin/sh\n# version {}\necho \"$1 -- Compiled OK.\"\n", version)?;
+        Ok(zip.finish()?.into_inner())
+    };
+    let manifest = |version: &str, url: &str| {
+        format!(
+            "manifest_version = \"1\"\n\n[compilers.cr1000x]\ndescription = \"CR1000X\"\nversion = \"{}\"\n\
+             download_url = \"{}/fakecomp.zip\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n",
+            version, url
+        )
+    };
+    let use_repository = |url: String| -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("config").arg("set").arg("compiler_repository_url").arg(format!("{}/compilers.toml", url));
+        cmd.assert().success();
+        Ok(())
+    };
+
+    use_repository(serve(manifest("7.0", &serve(archive("7.0")?))))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("install").arg("cr1000x");
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("upgrade").arg("--all");
+    cmd.assert().success().stdout(predicate::str::contains("cr1000x 7.0 is up to date").and(predicate::str::contains("No compilers to upgrade")));
+
+    use_repository(serve(manifest("7.10", &serve(archive("7.10")?))))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("upgrade").arg("--all").arg("--dry-run");
+    cmd.assert().success().stdout(predicate::str::contains("cr1000x: 7.0 → 7.10").and(predicate::str::contains("upgraded").not()));
+    let executable = temp_dir.path().join("data/crbrs/compilers/cr1000x/fakecomp");
+    assert!(std::fs::read_to_string(&executable)?.contains("version 7.0"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("upgrade").arg("cr1000x");
+    cmd.assert().success().stdout(predicate::str::contains("Compiler 'cr1000x' upgraded to 7.10"));
+    assert!(std::fs::read_to_string(&executable)?.contains("version 7.10"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list").arg("--json");
+    let statuses: serde_json::Value = serde_json::from_slice(&cmd.assert().success().get_output().stdout)?;
+    assert_eq!(statuses[0]["version"], "7.10");
+    let leftovers: Vec<_> = std::fs::read_dir(temp_dir.path().join("data/crbrs/compilers"))?
+        .map(|e| e.map(|e| e.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<_, _>>()?;
+    assert!(leftovers.iter().all(|name| !name.ends_with(".previous") && !name.ends_with(".partial")), "{:?}", leftovers);
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("upgrade").arg("cr1000");
+    cmd.assert().failure().stderr(predicate::str::contains("Did you mean 'cr1000x'?"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_install_checks_signatures() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    let temp_dir = TempDir::new()?;
+    let gnupg = temp_dir.path().join("gnupg");
+    std::fs::create_dir_all(&gnupg)?;
+    let gpg = |args: &[&str]| Command::new("gpg").arg("--batch").arg("--homedir").arg(&gnupg).args(args).output();
+    if !gpg(&["--passphrase", "", "--quick-gen-key", "Compiler Mirror <mirror@example.org>", "ed25519", "sign", "never"]).is_ok_and(|o| o.status.success()) {
+        eprintln!("gpg is not available; skipping");
+        return Ok(());
+    }
+    let public_key = String::from_utf8(gpg(&["--armor", "--export"])?.stdout)?;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file("fakecomp", zip::write::SimpleFileOptions::default().unix_permissions(0o755))?;
+    zip.write_all(b"#!/bin/sh\necho \"$1 -- Compiled OK.\"\n")?;
+    let archive = zip.finish()?.into_inner();
+    let sign = |data: &[u8], name: &str| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let file = temp_dir.path().join(name);
+        std::fs::write(&file, data)?;
+        let signature = temp_dir.path().join(format!("{}.sig", name));
+        assert!(gpg(&["--detach-sign", "--output", signature.to_str().unwrap(), file.to_str().unwrap()])?.status.success());
+        Ok(std::fs::read(signature)?)
+    };
+    let good = serve(sign(&archive, "archive.zip")?);
+    let other = serve(sign(b"some other file", "other")?);
+    let archive_url = serve(archive);
+    let _ = Command::new("gpgconf").arg("--homedir").arg(&gnupg).arg("--kill").arg("all").status();
+
+    let entry = |id: &str, signature_url: Option<&str>| {
+        let signed = signature_url
+            .map_or(String::new(), |url| format!("signature_url = \"{}/archive.zip.sig\"\npublic_key = '''\n{}'''\n", url, public_key));
+        format!(
+            "[compilers.{}]\ndescription = \"CR1000X\"\nversion = \"1\"\ndownload_url = \"{}/archive.zip\"\n\
+             executable_name = \"fakecomp\"\nrequires_wine = false\n{}\n",
+            id, archive_url, signed
+        )
+    };
+    // Only the archives of patch bases are kept, and so can be reinstalled offline.
+    let patch = format!("[[compilers.unsigned.patches]]\nbase = \"signed\"\nurl = \"{}/signed-to-unsigned.zst\"\n", archive_url);
+    let manifest = format!(
+        "manifest_version = \"1\"\n\n{}{}{}{}",
+        entry("signed", Some(&good)),
+        entry("tampered", Some(&other)),
+        entry("unsigned", None),
+        patch
+    );
+    let url = serve(manifest);
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_repository_url").arg(format!("{}/compilers.toml", url));
+    cmd.assert().success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("install").arg("signed");
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("install").arg("tampered");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Signature check failed for compiler 'tampered'").and(predicate::str::contains("E041")));
+    assert!(!temp_dir.path().join("data/crbrs/compilers/tampered").exists(), "nothing is unpacked");
+
+    // Unsigned entries install unless signatures are required.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("require_signed_compilers").arg("true");
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("install").arg("unsigned");
+    cmd.assert().failure().stderr(predicate::str::contains("require_signed_compilers is set"));
+    // Offline, the signature kept with the cached archive is checked.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("--offline").arg("compiler").arg("install").arg("signed");
+    cmd.assert().success();
+    Ok(())
+}
+
+/// Serves `body` to every HTTP request on a local port; returns the base URL.
+fn serve(body: impl Into<Vec<u8>>) -> String {
+    use std::io::{Read, Write};
+    let body: Vec<u8> = body.into();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+            let _ = stream.write_all(&body);
+        }
+    });
+    url
+}
+
+#[test]
+fn test_compile_association_to_uninstalled_compiler() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let url = serve("manifest_version = \"1\"\n\n[compilers.cr300comp]\ndescription = \"CR300\"\nversion = \"1\"\ndownload_url = \"http://127.0.0.1:9/none.zip\"\nexecutable_name = \"cr300comp.exe\"\nrequires_wine = true\n");
+    let program = temp_dir.path().join("station.cr300");
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_repository_url").arg(format!("{}/compilers.toml", url));
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set-association").arg("--extension").arg("cr300").arg("--compiler-id").arg("cr300comp");
+    cmd.assert().success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Files with extension '.cr300' are associated with compiler 'cr300comp', which is not installed. \
+         Install it with `crbrs compiler install cr300comp`",
+    ));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set-association").arg("--extension").arg("cr300").arg("--compiler-id").arg("nosuchcomp");
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program);
+    cmd.assert().failure().stderr(predicate::str::contains("It is not in the compiler manifest either"));
+    Ok(())
+}
+
+#[test]
+fn test_offline_uses_the_cached_manifest() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let url = serve("manifest_version = \"1\"\n\n[compilers.cr300comp]\ndescription = \"CR300\"\nversion = \"1\"\ndownload_url = \"http://127.0.0.1:9/none.zip\"\nexecutable_name = \"cr300comp.exe\"\nrequires_wine = true\n");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_repository_url").arg(format!("{}/compilers.toml", url));
+    cmd.assert().success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("--offline").arg("compiler").arg("list-available");
+    cmd.assert().failure().stderr(predicate::str::contains("Offline: skipped fetching").and(predicate::str::contains("no manifest is cached yet")));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list-available");
+    cmd.assert().success().stdout(predicate::str::contains("Offline").not());
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list-available").arg("--offline");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Offline: using the compiler manifest cached at"))
+        .stdout(predicate::str::contains("ID: cr300comp"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("--offline").arg("compiler").arg("install").arg("cr300comp");
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Offline: cannot download 'cr300comp' (offline mode is on), and no archive of it is cached",
+    ));
+
+    // An unreachable repository is detected without --offline, and doesn't wait for retries.
+    let closed_port = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_repository_url").arg(format!("http://127.0.0.1:{}/compilers.toml", closed_port));
+    cmd.assert().success();
+    let started = std::time::Instant::now();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list-available");
+    cmd.assert().success().stdout(predicate::str::contains("Offline: using the compiler manifest cached at"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(5), "took {:?}", started.elapsed());
+    Ok(())
+}
+
+#[test]
+fn test_failed_manifest_fetch_falls_back_to_a_fresh_cache() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    let temp_dir = TempDir::new()?;
+    let body = "manifest_version = \"1\"\n\n[compilers.cr300comp]\ndescription = \"CR300\"\nversion = \"1\"\ndownload_url = \"http://127.0.0.1:9/none.zip\"\nexecutable_name = \"cr300comp.exe\"\nrequires_wine = true\n";
+    // Serves the manifest until `failing` is set, then answers every request with a 500.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/compilers.toml", listener.local_addr()?);
+    let failing = Arc::new(AtomicBool::new(false));
+    let fail = Arc::clone(&failing);
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let response = if fail.load(Ordering::Relaxed) {
+                "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+            } else {
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body)
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    for (key, value) in [("compiler_repository_url", url.as_str()), ("network.retries", "1"), ("network.backoff_ms", "10")] {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("config").arg("set").arg(key).arg(value);
+        cmd.assert().success();
+    }
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list-available");
+    cmd.assert().success();
+
+    failing.store(true, Ordering::Relaxed);
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list-available");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Could not fetch the compiler manifest"))
+        .stdout(predicate::str::contains("using the copy cached at"))
+        .stdout(predicate::str::contains("ID: cr300comp"));
+
+    // A copy older than manifest_max_age_hours is not used for a failed fetch, but offline
+    // it still is, with a warning.
+    let cache = temp_dir.path().join("data/crbrs/manifest.toml");
+    let week_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(8 * 24 * 3600);
+    std::fs::File::options().append(true).open(&cache)?.set_modified(week_ago)?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list-available");
+    cmd.assert().failure().stderr(predicate::str::contains("500"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("--offline").arg("compiler").arg("list-available");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("more than 168 hours ago"))
+        .stdout(predicate::str::contains("ID: cr300comp"));
+    Ok(())
+}
+
+#[test]
+fn test_manifest_fetch_handles_rate_limits_and_is_conditional() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::{Read, Write};
+    use std::sync::{Arc, Mutex};
+    let temp_dir = TempDir::new()?;
+    let body = "manifest_version = \"1\"\n\n[compilers.cr300comp]\ndescription = \"CR300\"\nversion = \"1\"\ndownload_url = \"http://127.0.0.1:9/none.zip\"\nexecutable_name = \"cr300comp.exe\"\nrequires_wine = true\n";
+    // Throttles the first request, then serves the manifest with an ETag, and answers 304 to
+    // requests that have it.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/compilers.toml", listener.local_addr()?);
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&requests);
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0u8; 4096];
+            let n = stream.read(&mut request).unwrap_or(0);
+            if n == 0 {
+                continue; // The reachability probe
+            }
+            let request = String::from_utf8_lossy(&request[..n]).to_lowercase();
+            let response = if seen.lock().unwrap().is_empty() {
+                "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+            } else if request.contains("if-none-match: \"v1\"") {
+                "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n".to_string()
+            } else {
+                format!("HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body)
+            };
+            seen.lock().unwrap().push(request);
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_repository_url").arg(&url);
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("network.backoff_ms").arg("10");
+    cmd.assert().success();
+
+    let started = std::time::Instant::now();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list-available");
+    cmd.assert().success().stdout(predicate::str::contains("ID: cr300comp"));
+    assert!(started.elapsed() >= std::time::Duration::from_secs(1), "the retry waited for Retry-After");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list-available");
+    cmd.assert().success().stdout(predicate::str::contains("ID: cr300comp"));
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 3, "{:?}", requests);
+    assert!(!requests[1].contains("if-none-match"), "{}", requests[1]);
+    assert!(requests[2].contains("if-none-match: \"v1\""), "{}", requests[2]);
+    Ok(())
+}
+
+#[test]
+fn test_mistyped_compiler_ids_get_suggestions() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let url = serve("manifest_version = \"1\"\n\n[compilers.\"cr300-std-10.1\"]\ndescription = \"CR300\"\nversion = \"10.1\"\ndownload_url = \"http://127.0.0.1:9/none.zip\"\nexecutable_name = \"cr300comp.exe\"\nrequires_wine = true\n");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_repository_url").arg(format!("{}/compilers.toml", url));
+    cmd.assert().success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("install").arg("cr300-std-10.0");
+    cmd.assert().failure().stderr(predicate::str::contains("Did you mean 'cr300-std-10.1'?"));
+
+    let shared = temp_dir.path().join("cr1000comp");
+    std::fs::create_dir_all(&shared)?;
+    std::fs::write(shared.join("CR1000Comp.exe"), b"MZ")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&shared).arg("--executable").arg("CR1000Comp.exe");
+    cmd.assert().success();
+
+    let program = temp_dir.path().join("station.cr1");
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("CR1000-comp");
+    cmd.assert().failure().stderr(predicate::str::contains("Did you mean 'cr1000comp'?"));
+    Ok(())
+}
+
+#[test]
+fn test_compiler_list_shows_health_and_updates() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let shared = temp_dir.path().join("cr1000comp");
+    std::fs::create_dir_all(&shared)?;
+    std::fs::write(shared.join("CR1000Comp.exe"), vec![0u8; 2048])?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&shared).arg("--executable").arg("CR1000Comp.exe");
+    cmd.assert().success();
+
+    let data_dir = temp_dir.path().join("data").join("crbrs");
+    std::fs::create_dir_all(&data_dir)?;
+    std::fs::write(
+        data_dir.join("manifest.toml"),
+        "manifest_version = \"1\"\n\n[compilers.cr1000comp-v2]\ndescription = \"CR1000\"\nversion = \"2.0\"\n\
+         download_url = \"http://127.0.0.1:9/none.zip\"\nexecutable_name = \"CR1000Comp.exe\"\nrequires_wine = true\n",
+    )?;
+    std::fs::write(data_dir.join("history.json"), "{\"compilers\": {\"cr1000comp\": {\"last_used\": \"2026-10-16T08:30:00.000Z\"}}}")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("OK  Size: 2.0 KiB  Last used: 2026-10-16T08:30:00.000Z"))
+        .stdout(predicate::str::contains("Newer version available: cr1000comp-v2 (2.0)"));
+
+    std::fs::remove_file(shared.join("CR1000Comp.exe"))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list").arg("--json");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let text = String::from_utf8(output)?;
+    assert!(text.contains("\"executable_exists\": false"));
+    assert!(text.contains("\"id\": \"cr1000comp-v2\""));
+    Ok(())
+}
+
+/// Links a fake compiler (a shell script that reports success unless the source contains
+/// "Bad") under `id`.
+#[cfg(unix)]
+fn link_fake_compiler(temp_dir: &TempDir, id: &str, version: &str) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = temp_dir.path().join(id);
+    std::fs::create_dir_all(&dir)?;
+    let script = dir.join("fakecomp");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\nif grep -q Bad \"$1\"; then printf '%s -- Compile Failed!\\nline 2: Bad is not a keyword\\n' \"$1\"; \
+         else echo \"$1 -- Compiled OK.\"; fi\n",
+    )?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    std::fs::write(
+        dir.join("crbrs-compiler.toml"),
+        format!("id = \"{id}\"\ndescription = \"fake\"\nversion = \"{version}\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n"),
+    )?;
+    let mut cmd = crbrs_cmd_isolated(temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&dir);
+    cmd.assert().success();
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cascading_compiler_errors_are_grouped_and_capped() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path().join("cascade");
+    std::fs::create_dir_all(&dir)?;
+    // One undeclared variable reported on 40 lines (line 5 twice), then 5 distinct errors.
+    let script = dir.join("fakecomp");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\nprintf '%s -- Compile Failed!\\n' \"$1\"\nprintf 'line 5: Variable X is not declared.\\n'\n\
+         for i in $(seq 5 44); do printf 'line %s: Variable X is not declared.\\n' $i; done\n\
+         for i in 1 2 3 4 5; do printf 'line 5%s: Error %s\\n' $i $i; done\n",
+    )?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    std::fs::write(
+        dir.join("crbrs-compiler.toml"),
+        "id = \"cascade\"\ndescription = \"fake\"\nversion = \"1.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n",
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&dir);
+    cmd.assert().success();
+    let program = temp_dir.path().join("broken.cr1x");
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("max_diagnostics").arg("3");
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("cascade");
+    let output = cmd.assert().failure().get_output().stderr.clone();
+    let stderr = String::from_utf8(output)?;
+    assert!(
+        stderr.contains("Lines 5, 6, 7, 8, 9, 10, 11, 12, …: Variable X is not declared. (reported 41 times)"),
+        "{}",
+        stderr
+    );
+    assert_eq!(stderr.matches("Variable X").count(), 1, "{}", stderr);
+    assert!(stderr.contains("Line 51: Error 1\n  Line 52: Error 2\n  …and 3 more error(s)."), "{}", stderr);
+
+    // 0 shows every group.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("max_diagnostics").arg("0");
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("cascade");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Line 55: Error 5"))
+        .stderr(predicate::str::contains("more error(s)").not());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_undeclared_variable_errors_note_the_declaration_that_failed() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path().join("undeclared");
+    std::fs::create_dir_all(&dir)?;
+    let script = dir.join("fakecomp");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\nprintf '%s -- Compile Failed!\\n' \"$1\"\nprintf 'line 4: Variable BattV is not declared.\\nline 5: Error 2\\n'\n",
+    )?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    std::fs::write(
+        dir.join("crbrs-compiler.toml"),
+        "id = \"undeclared\"\ndescription = \"fake\"\nversion = \"1.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n",
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&dir);
+    cmd.assert().success();
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, "Public BattV, 2ndTemp\nBeginProg\n  Scan(1,Sec,0,0)\n    Battery(BattV)\n  NextScan\nEndProg\n")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("undeclared");
+    let output = cmd.assert().failure().get_output().stderr.clone();
+    let stderr = String::from_utf8(output)?;
+    assert!(
+        stderr.contains("Line 4: Variable BattV is not declared.\n    note: 'BattV' is declared on line 1, which could not be parsed"),
+        "{}",
+        stderr
+    );
+    assert_eq!(stderr.matches("note:").count(), 1, "only the undeclared variable has a cause: {}", stderr);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compile_warns_when_file_compiler_changes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    link_fake_compiler(&temp_dir, "fake-v2", "2.0")?;
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("fake-v1");
+    cmd.assert().success().stdout(predicate::str::contains("was last built with").not());
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("fake-v2");
+    cmd.assert().success().stdout(predicate::str::contains("was last built with 'fake-v1' (1.0)"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("history").arg(&program);
+    cmd.assert().success().stdout(predicate::str::contains("station.cr1x\n    fake-v2 (2.0) on "));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_batch_compile_summary_and_incremental() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set-association").arg("--extension").arg("cr1x").arg("--compiler-id").arg("fake-v1");
+    cmd.assert().success();
+
+    let fleet = temp_dir.path().join("fleet");
+    std::fs::create_dir_all(fleet.join("north"))?;
+    std::fs::write(fleet.join("a.cr1x"), "BeginProg\nEndProg\n")?;
+    std::fs::write(fleet.join("b.cr1x"), "BeginProg\nBad\nEndProg\n")?;
+    std::fs::write(fleet.join("north").join("c.cr1x"), "BeginProg\nEndProg\n")?;
+    let summary = temp_dir.path().join("summary.json");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&fleet).arg("--summary").arg(&summary);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("Files: 3 (2 compiled, 0 cached, 1 failed)"))
+        .stdout(predicate::str::contains("Status: FAILED"))
+        .stderr(predicate::str::contains("b.cr1x: line 2: Bad is not a keyword"))
+        .stderr(predicate::str::contains("1 of 3 files failed to compile."));
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary)?)?;
+    assert_eq!(json["status"], "failed");
+    assert_eq!(json["by_compiler"]["fake-v1"]["compiled"], 2);
+
+    std::fs::write(fleet.join("b.cr1x"), "BeginProg\nEndProg\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&fleet).arg("--incremental");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Files: 3 (1 compiled, 2 cached, 0 failed)"))
+        .stdout(predicate::str::contains("Status: OK"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_batch_compile_runs_jobs_in_parallel() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path().join("slow");
+    std::fs::create_dir_all(&dir)?;
+    // Like Wine starting up: every compile takes a second, the first file's two.
+    let script = dir.join("fakecomp");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\ncase \"$1\" in *a.cr1x) sleep 2;; *) sleep 1;; esac\n\
+         if grep -q Bad \"$1\"; then printf '%s -- Compile Failed!\\nline 2: Bad is not a keyword\\n' \"$1\"; \
+         else echo \"$1 -- Compiled OK.\"; fi\n",
+    )?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    std::fs::write(
+        dir.join("crbrs-compiler.toml"),
+        "id = \"slow\"\ndescription = \"fake\"\nversion = \"1.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n",
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&dir);
+    cmd.assert().success();
+
+    let fleet = temp_dir.path().join("fleet");
+    std::fs::create_dir_all(&fleet)?;
+    for (name, source) in [("a", "BeginProg\nBad\nEndProg\n"), ("b", "BeginProg\nEndProg\n"), ("c", "BeginProg\nBad\nEndProg\n"), ("d", "BeginProg\nEndProg\n")] {
+        std::fs::write(fleet.join(format!("{name}.cr1x")), source)?;
+    }
+    let summary = temp_dir.path().join("summary.json");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&fleet).arg("--compiler").arg("slow").arg("--jobs").arg("4").arg("--summary").arg(&summary);
+    let output = cmd.assert().failure().stdout(predicate::str::contains("Files: 4 (2 compiled, 0 cached, 2 failed)")).get_output().clone();
+    // a finishes last, but is still reported (and summarized) first.
+    let stderr = String::from_utf8(output.stderr)?;
+    let (a, c) = (stderr.find("a.cr1x: line 2").expect("a failed"), stderr.find("c.cr1x: line 2").expect("c failed"));
+    assert!(a < c, "{}", stderr);
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary)?)?;
+    let files: Vec<&str> = json["files"].as_array().unwrap().iter().map(|f| f["file"].as_str().unwrap()).collect();
+    assert!(files.iter().zip(["a.cr1x", "b.cr1x", "c.cr1x", "d.cr1x"]).all(|(file, name)| file.ends_with(name)), "{:?}", files);
+    // One at a time this takes five seconds.
+    assert!(json["wall_time_ms"].as_u64().unwrap() < 4000, "{}", json["wall_time_ms"]);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compile_results_are_cached_by_content() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path().join("counting");
+    std::fs::create_dir_all(&dir)?;
+    // Counts its runs next to itself.
+    let script = dir.join("fakecomp");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\necho run >> \"$(dirname \"$0\")/runs\"\n\
+         if grep -q Bad \"$1\"; then printf '%s -- Compile Failed!\\nline 2: Bad is not a keyword\\n' \"$1\"; \
+         else echo \"$1 -- Compiled OK.\"; fi\n",
+    )?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    std::fs::write(
+        dir.join("crbrs-compiler.toml"),
+        "id = \"counting\"\ndescription = \"fake\"\nversion = \"1.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n",
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&dir);
+    cmd.assert().success();
+    let runs = || std::fs::read_to_string(dir.join("runs")).map(|text| text.lines().count()).unwrap_or(0);
+
+    let station = temp_dir.path().join("station");
+    std::fs::create_dir_all(&station)?;
+    let program = station.join("main.cr1x");
+    std::fs::write(&program, "Include \"CPU:common.cr1x\"\nBeginProg\nEndProg\n")?;
+    std::fs::write(station.join("common.cr1x"), "Public x\n")?;
+    let compile = |program: &std::path::Path, force: bool| -> Result<assert_cmd::assert::Assert, Box<dyn std::error::Error>> {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("compile").arg(program).arg("--compiler").arg("counting");
+        if force {
+            cmd.arg("--force");
+        }
+        Ok(cmd.assert())
+    };
+
+    compile(&program, false)?.success().stdout(predicate::str::contains("cached result").not());
+    compile(&program, false)?.success().stdout(predicate::str::contains("main.cr1x (unchanged; cached result)"));
+    assert_eq!(runs(), 1);
+
+    // Copies elsewhere share the result; a changed include or --force runs the compiler.
+    let copy = temp_dir.path().join("copy");
+    std::fs::create_dir_all(&copy)?;
+    std::fs::copy(&program, copy.join("main.cr1x"))?;
+    std::fs::copy(station.join("common.cr1x"), copy.join("common.cr1x"))?;
+    compile(&copy.join("main.cr1x"), false)?.success();
+    assert_eq!(runs(), 1);
+    std::fs::write(station.join("common.cr1x"), "Public x, y\n")?;
+    compile(&program, false)?.success();
+    compile(&program, true)?.success().stdout(predicate::str::contains("cached result").not());
+    assert_eq!(runs(), 3);
+
+    // Failures are cached with their errors.
+    std::fs::write(&program, "BeginProg\nBad\nEndProg\n")?;
+    compile(&program, false)?.failure().stderr(predicate::str::contains("Bad is not a keyword"));
+    compile(&program, false)?.failure().stderr(predicate::str::contains("Bad is not a keyword"));
+    assert_eq!(runs(), 4);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compile_with_includes_from_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path().join("including");
+    std::fs::create_dir_all(&dir)?;
+    // Fails unless common.cr1x is next to the program and fine.
+    let script = dir.join("fakecomp");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\ncommon=\"$(dirname \"$1\")/common.cr1x\"\n\
+         if [ -f \"$common\" ] && ! grep -q Bad \"$common\"; then echo \"$1 -- Compiled OK.\"; \
+         else printf '%s -- Compile Failed!\\nline 1: Include file not found\\n' \"$1\"; fi\n",
+    )?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    std::fs::write(
+        dir.join("crbrs-compiler.toml"),
+        "id = \"including\"\ndescription = \"fake\"\nversion = \"1.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n",
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&dir);
+    cmd.assert().success();
+
+    let generated = serde_json::json!({
+        "main.cr1x": "Include \"CPU:common.cr1x\"\nBeginProg\nEndProg\n",
+        "common.cr1x": "Public x\n",
+    });
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg("main.cr1x").arg("--compiler").arg("including").arg("--stdin-includes");
+    assert_cmd::Command::from_std(cmd).write_stdin(generated.to_string()).assert().success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg("main.cr1x").arg("--compiler").arg("including").arg("--stdin-includes");
+    assert_cmd::Command::from_std(cmd).write_stdin(serde_json::json!({ "main.cr1x": "BeginProg\nEndProg\n" }).to_string())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Compilation of 'main.cr1x' failed"));
+
+    // A program on disk gets the given include instead of its own.
+    let station = temp_dir.path().join("station");
+    std::fs::create_dir_all(&station)?;
+    std::fs::write(station.join("main.cr1x"), "Include \"CPU:common.cr1x\"\nBeginProg\nEndProg\n")?;
+    std::fs::write(station.join("common.cr1x"), "Bad\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(station.join("main.cr1x")).arg("--compiler").arg("including").arg("--stdin-includes");
+    assert_cmd::Command::from_std(cmd).write_stdin(serde_json::json!({ "common.cr1x": "Public x\n" }).to_string()).assert().success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg("main.cr1x").arg("--stdin-includes");
+    assert_cmd::Command::from_std(cmd).write_stdin(r#"{"../escape.cr1x": "BeginProg"}"#).assert().failure().stderr(predicate::str::contains("without '..'"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_batch_compile_expands_patterns() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set-association").arg("--extension").arg("cr1x").arg("--compiler-id").arg("fake-v1");
+    cmd.assert().success();
+
+    let fleet = temp_dir.path().join("fleet");
+    std::fs::create_dir_all(fleet.join("north").join("tower"))?;
+    std::fs::write(fleet.join("a.cr1x"), "BeginProg\nEndProg\n")?;
+    std::fs::write(fleet.join("north").join("b.cr1x"), "BeginProg\nEndProg\n")?;
+    std::fs::write(fleet.join("north").join("tower").join("c.cr1x"), "BeginProg\nBad\nEndProg\n")?;
+    std::fs::write(fleet.join("north").join("notes.txt"), "not a program")?;
+
+    // '**' matches any depth, including none.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.current_dir(&fleet).arg("compile").arg("**/*.cr1x");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("Files: 3 (2 compiled, 0 cached, 1 failed)"))
+        .stderr(predicate::str::contains("north/tower/c.cr1x: line 2: Bad is not a keyword"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.current_dir(&fleet).arg("compile").arg("north/?.cr1x").arg("*.cr1x");
+    cmd.assert().success().stdout(predicate::str::contains("Files: 2 (2 compiled, 0 cached, 0 failed)"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.current_dir(&fleet).arg("compile").arg("south/*.cr1x");
+    cmd.assert().failure().stderr(predicate::str::contains("No programs match: south/*.cr1x"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compile_sarif_output() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set-association").arg("--extension").arg("cr1x").arg("--compiler-id").arg("fake-v1");
+    cmd.assert().success();
+    let project = temp_dir.path().join("project");
+    std::fs::create_dir_all(project.join("north"))?;
+    std::fs::write(project.join("good.cr1x"), "BeginProg\nEndProg\n")?;
+    std::fs::write(project.join("north").join("bad station.cr1x"), "BeginProg\nBad\nEndProg\n")?;
+
+    // stdout is nothing but the SARIF log, and the exit code still reports the failure.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.current_dir(&project).arg("compile").arg("--format").arg("sarif").arg(".");
+    let output = cmd.assert().failure().get_output().stdout.clone();
+    let sarif: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(sarif["version"], "2.1.0");
+    let run = &sarif["runs"][0];
+    assert_eq!(run["tool"]["driver"]["name"], "crbrs");
+    let results = run["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1, "{}", sarif);
+    assert_eq!(results[0]["ruleId"], "compiler-error");
+    assert_eq!(results[0]["level"], "error");
+    assert_eq!(results[0]["message"]["text"], "Bad is not a keyword");
+    assert_eq!(results[0]["properties"]["errorCode"], "E014");
+    let location = &results[0]["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "north/bad%20station.cr1x");
+    assert_eq!(location["region"]["startLine"], 2);
+
+    // A single file is reported the same way.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.current_dir(&project).arg("compile").arg("good.cr1x").arg("--format").arg("sarif");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let sarif: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(sarif["runs"][0]["results"], serde_json::json!([]));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_build_rebuilds_when_an_include_changes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set-association").arg("--extension").arg("cr1x").arg("--compiler-id").arg("fake-v1");
+    cmd.assert().success();
+
+    let fleet = temp_dir.path().join("fleet");
+    std::fs::create_dir_all(&fleet)?;
+    std::fs::write(fleet.join("station.cr1x"), "Include \"CPU:Common.inc\"\nBeginProg\nEndProg\n")?;
+    std::fs::write(fleet.join("other.cr1x"), "BeginProg\nEndProg\n")?;
+    std::fs::write(fleet.join("common.inc"), "Public Batt\n")?;
+    let build = |temp_dir: &TempDir, extra: &[&str]| -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = crbrs_cmd_isolated(temp_dir)?;
+        cmd.arg("build").arg(&fleet).args(extra);
+        Ok(cmd)
+    };
+
+    build(&temp_dir, &[])?.assert().success().stdout(predicate::str::contains("Files: 2 (2 compiled, 0 cached, 0 failed)"));
+    build(&temp_dir, &[])?.assert().success().stdout(predicate::str::contains("Files: 2 (0 compiled, 2 cached, 0 failed)"));
+
+    // Only the program including the changed file is rebuilt.
+    std::fs::write(fleet.join("common.inc"), "Public Batt, PTemp\n")?;
+    build(&temp_dir, &[])?.assert().success().stdout(predicate::str::contains("Files: 2 (1 compiled, 1 cached, 0 failed)"));
+    build(&temp_dir, &["--force"])?.assert().success().stdout(predicate::str::contains("Files: 2 (2 compiled, 0 cached, 0 failed)"));
+
+    let state: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(temp_dir.path().join("data/crbrs/build-state.json"))?)?;
+    let programs = state["programs"].as_object().expect("programs");
+    let station = programs.iter().find(|(key, _)| key.ends_with("station.cr1x")).expect("station recorded").1;
+    assert_eq!(station["inputs"].as_object().expect("inputs").len(), 2);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_project_manifest_overrides_the_user_config() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    link_fake_compiler(&temp_dir, "fake-v2", "2.0")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set-association").arg("--extension").arg("cr1x").arg("--compiler-id").arg("fake-v1");
+    cmd.assert().success();
+
+    let project = temp_dir.path().join("repo");
+    std::fs::create_dir_all(project.join("stations").join("north"))?;
+    std::fs::create_dir_all(project.join("shared"))?;
+    let manifest = "required_compilers = [\"fake-v2\"]\ninclude_paths = [\"shared\"]\n\n[file_associations]\ncr1x = \"fake-v2\"\n";
+    std::fs::write(project.join("crbrs.toml"), manifest)?;
+    let program = project.join("stations").join("north").join("tower.cr1x");
+    std::fs::write(&program, "Include \"CPU:common.inc\"\nBeginProg\nEndProg\n")?;
+    std::fs::write(project.join("shared").join("common.inc"), "Public Batt\n")?;
+
+    // Found by walking up from the program; its association wins over the user's.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program);
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("history").arg(&program);
+    cmd.assert().success().stdout(predicate::str::contains("fake-v2 (2.0)"));
+
+    // Includes are found in the include paths, so changing one rebuilds the program.
+    let build = |temp_dir: &TempDir| -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = crbrs_cmd_isolated(temp_dir)?;
+        cmd.arg("build").arg(project.join("stations"));
+        Ok(cmd)
+    };
+    build(&temp_dir)?.assert().success().stdout(predicate::str::contains("(1 compiled, 0 cached, 0 failed)"));
+    build(&temp_dir)?.assert().success().stdout(predicate::str::contains("(0 compiled, 1 cached, 0 failed)"));
+    std::fs::write(project.join("shared").join("common.inc"), "Public Batt, PTemp\n")?;
+    build(&temp_dir)?.assert().success().stdout(predicate::str::contains("(1 compiled, 0 cached, 0 failed)"));
+
+    // A required compiler that is not installed stops the compile, saying what to install.
+    std::fs::write(project.join("crbrs.toml"), manifest.replace("[\"fake-v2\"]", "[\"fake-v2\", \"cr1000comp-v9\"]"))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program);
+    cmd.assert().failure().stderr(predicate::str::contains("requires compilers that are not installed: cr1000comp-v9"));
+
+    // Outside the project the user's config applies.
+    let elsewhere = temp_dir.path().join("elsewhere.cr1x");
+    std::fs::write(&elsewhere, "BeginProg\nEndProg\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&elsewhere);
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("history").arg(&elsewhere);
+    cmd.assert().success().stdout(predicate::str::contains("fake-v1 (1.0)"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_build_with_calibration() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set-association").arg("--extension").arg("cr1x").arg("--compiler-id").arg("fake-v1");
+    cmd.assert().success();
+
+    let fleet = temp_dir.path().join("fleet");
+    std::fs::create_dir_all(&fleet)?;
+    let program = "' Soil station\nInclude \"CPU:common.inc\"\nConst SoilGain = 1\nPublic VWC\nBeginProg\n  VWC = SoilGain * SoilOffset\nEndProg\n";
+    std::fs::write(fleet.join("soil.cr1x"), program)?;
+    std::fs::write(fleet.join("common.inc"), "Public Batt\n")?;
+    // A hand-written program that happens to have the calibrated copy's name.
+    let hand_written = "' Station 12 by hand\nBeginProg\nEndProg\n";
+    std::fs::write(fleet.join("soil_station12.cr1x"), hand_written)?;
+    let calibration = temp_dir.path().join("station12.toml");
+    std::fs::write(&calibration, "SoilGain = 1.034\nSoilOffset = 0.012\n\n[Pyranometer]\nMultiplier = 5.12\n")?;
+    let build = |temp_dir: &TempDir| -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = crbrs_cmd_isolated(temp_dir)?;
+        cmd.arg("build").arg(&fleet).arg("--with-calibration").arg(&calibration);
+        Ok(cmd)
+    };
+
+    build(&temp_dir)?.assert().success().stdout(predicate::str::contains("Files: 2 (2 compiled, 0 cached, 0 failed)"));
+    // The copy goes to the data directory with the files it includes; the sources are untouched.
+    let copies: Vec<std::path::PathBuf> = std::fs::read_dir(temp_dir.path().join("data").join("crbrs").join("calibrated"))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(copies.len(), 1, "{:?}", copies);
+    let calibrated = std::fs::read_to_string(copies[0].join("soil_station12.cr1x"))?;
+    assert!(calibrated.contains("Const SoilOffset = 0.012\nConst Pyranometer_Multiplier = 5.12\n"), "{}", calibrated);
+    assert!(calibrated.contains("Const SoilGain = 1.034\n"), "{}", calibrated);
+    assert_eq!(std::fs::read_to_string(copies[0].join("common.inc"))?, "Public Batt\n");
+    assert_eq!(std::fs::read_to_string(fleet.join("soil.cr1x"))?, program);
+    assert_eq!(std::fs::read_to_string(fleet.join("soil_station12.cr1x"))?, hand_written);
+    std::fs::remove_file(fleet.join("soil_station12.cr1x"))?;
+
+    // The calibrated copy is only rebuilt when it or an include changes.
+    build(&temp_dir)?.assert().success().stdout(predicate::str::contains("Files: 1 (0 compiled, 1 cached, 0 failed)"));
+    std::fs::write(&calibration, "SoilGain = 1.05\nSoilOffset = 0.012\n")?;
+    build(&temp_dir)?.assert().success().stdout(predicate::str::contains("Files: 1 (1 compiled, 0 cached, 0 failed)"));
+    std::fs::write(fleet.join("common.inc"), "Public Batt, PTemp\n")?;
+    build(&temp_dir)?.assert().success().stdout(predicate::str::contains("Files: 1 (1 compiled, 0 cached, 0 failed)"));
+
+    std::fs::write(&calibration, "VWC = 2\n")?;
+    build(&temp_dir)?.assert().failure().stderr(predicate::str::contains("'VWC' is declared in the program, but not as a Const"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_build_on_remote_host() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set-association").arg("--extension").arg("cr1x").arg("--compiler-id").arg("fake-v1");
+    cmd.assert().success();
+
+    // Stands in for ssh: keeps the received archive and runs the command on this machine, where
+    // the "remote" crbrs shares the test's config. The host must come after `--`.
+    let bin = temp_dir.path().join("bin");
+    std::fs::create_dir_all(&bin)?;
+    let archive = temp_dir.path().join("received.tar");
+    std::fs::write(
+        bin.join("ssh"),
+        format!("#!/bin/sh\n[ \"$1\" = -- ] || exit 64\nshift\necho \"$1\" > '{0}.host'\ncat > '{0}'\nexec sh -c \"$2\" < '{0}'\n", archive.display()),
+    )?;
+    std::fs::set_permissions(bin.join("ssh"), std::fs::Permissions::from_mode(0o755))?;
+    let crbrs_dir = assert_cmd::cargo::cargo_bin("crbrs").parent().expect("binary directory").to_path_buf();
+    let path = std::env::join_paths([bin, crbrs_dir].into_iter().chain(std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default())))?;
+
+    let fleet = temp_dir.path().join("fleet");
+    std::fs::create_dir_all(fleet.join("north"))?;
+    std::fs::write(fleet.join("north").join("station.cr1x"), "Include \"CPU:common.inc\"\nBeginProg\nEndProg\n")?;
+    std::fs::write(fleet.join("north").join("common.inc"), "Public Batt\n")?;
+    std::fs::write(fleet.join("broken.cr1x"), "BeginProg\nBad\nEndProg\n")?;
+    let summary = temp_dir.path().join("summary.json");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.env("PATH", path).arg("build").arg(&fleet).arg("--remote").arg("buildbox").arg("--summary").arg(&summary);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("Building on buildbox..."))
+        .stdout(predicate::str::contains("Files: 2 (1 compiled, 0 cached, 1 failed)"))
+        .stderr(predicate::str::contains("broken.cr1x: line 2: Bad is not a keyword"));
+    assert_eq!(std::fs::read_to_string(temp_dir.path().join("received.tar.host"))?.trim(), "buildbox");
+
+    let listing = Command::new("tar").arg("-tf").arg(&archive).output()?;
+    let mut sent: Vec<String> = String::from_utf8(listing.stdout)?.lines().map(str::to_string).collect();
+    sent.sort();
+    assert_eq!(sent, ["broken.cr1x", "north/common.inc", "north/station.cr1x"]);
+
+    // Paths in the summary point at the local files.
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary)?)?;
+    let broken = json["files"].as_array().expect("files").iter().find(|f| f["outcome"] == "failed").expect("failed file");
+    assert_eq!(broken["file"].as_str(), Some(fleet.canonicalize()?.join("broken.cr1x").to_str().expect("UTF-8 path")));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_bench_compile_reports_timings() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("bench").arg("compile").arg(&program).arg("--compiler").arg("fake-v1").arg("-n").arg("3");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let json: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(json["compiler_id"], "fake-v1");
+    assert_eq!(json["iterations"], 3);
+    assert_eq!(json["succeeded"], true);
+    assert_eq!(json["uses_wine"], false);
+    assert!(json["wine_startup"].is_null());
+    assert_eq!(json["parse"]["runs"], 3);
+    assert_eq!(json["warm_compile"]["runs"], 2);
+    assert!(json["cold_compile_ms"].as_f64().is_some_and(|ms| ms > 0.0));
+
+    // Benchmark runs are not builds.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("history");
+    cmd.assert().success().stdout(predicate::str::contains("No recorded builds."));
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_ctrl_c_kills_the_compiler() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, Instant};
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "slow-v1", "1.0")?;
+    // A compiler that hangs in a child process of its own, as Wine's do.
+    let pid_file = temp_dir.path().join("compiler.pid");
+    let script = temp_dir.path().join("slow-v1").join("fakecomp");
+    std::fs::write(&script, format!("#!/bin/sh\nsleep 60 &\necho $! > '{}'\nwait\n", pid_file.display()))?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("slow-v1");
+    let child = cmd.stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::piped()).spawn()?;
+    let started = Instant::now();
+    while !std::fs::read_to_string(&pid_file).is_ok_and(|pid| pid.ends_with('\n')) {
+        assert!(started.elapsed() < Duration::from_secs(20), "the compiler never started");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let compiler_pid = std::fs::read_to_string(&pid_file)?.trim().to_string();
+    Command::new("kill").arg("-INT").arg(child.id().to_string()).status()?;
+
+    let output = child.wait_with_output()?;
+    assert_eq!(output.status.code(), Some(130));
+    assert!(String::from_utf8(output.stderr)?.contains("Cancelled."));
+    // Gone, or a zombie waiting to be reaped.
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", compiler_pid)).unwrap_or_default();
+    assert!(stat.is_empty() || stat.contains(") Z "), "the compiler is still running: {}", stat);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_quiet_prints_only_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let good = temp_dir.path().join("good.cr1x");
+    std::fs::write(&good, "BeginProg\nEndProg\n")?;
+    let bad = temp_dir.path().join("bad.cr1x");
+    std::fs::write(&bad, "BeginProg\nBad\nEndProg\n")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("--quiet").arg("compile").arg(&good).arg("--compiler").arg("fake-v1");
+    cmd.assert().success().stdout(predicate::str::is_empty()).stderr(predicate::str::is_empty());
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&good).arg(&bad).arg("--compiler").arg("fake-v1").arg("-q");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains("bad.cr1x: line 2: Bad is not a keyword"))
+        .stderr(predicate::str::contains("1 of 2 files failed to compile."));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_provision_from_a_plan() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let url = serve("manifest_version = \"1\"\n\n[compilers.cr300comp]\ndescription = \"CR300\"\nversion = \"1\"\ndownload_url = \"http://127.0.0.1:9/none.zip\"\nexecutable_name = \"cr300comp.exe\"\nrequires_wine = true\n");
+    let plan = temp_dir.path().join("classroom.toml");
+    std::fs::write(
+        &plan,
+        format!(
+            "compiler_repository_url = \"{}/compilers.toml\"\ncompilers = [\"fake-v1\"]\n\n[associations]\ncr1x = \"fake-v1\"\n\n[lint]\nunit-mismatch = \"off\"\n",
+            url
+        ),
+    )?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("provision").arg("--from").arg(&plan);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Compiler 'fake-v1' is already installed."))
+        .stdout(predicate::str::contains("fake-v1 compiles."))
+        .stdout(predicate::str::contains("1 already installed, 0 failing the check"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("show");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Repository URL: {}/compilers.toml", url)))
+        .stdout(predicate::str::contains(".cr1x -> fake-v1"))
+        .stdout(predicate::str::contains("unit-mismatch = off"));
+
+    std::fs::write(&plan, "compilers = [\"fake-v1\"]\n\n[associations]\ncr300 = \"cr300comp\"\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("provision").arg("--from").arg(&plan);
+    cmd.assert().failure().stderr(predicate::str::contains("'.cr300' is associated with 'cr300comp', which is not in `compilers`"));
+
+    std::fs::write(&plan, "compilers = [\"fake-v1\", \"cr3000comp\"]\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("provision").arg("--from").arg(&plan);
+    cmd.assert().failure().stderr(predicate::str::contains("Compiler ID 'cr3000comp' not found in the repository manifest"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_init_creates_a_project_that_builds() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let project = temp_dir.path().join("north-tower");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("init").arg(&project).arg("--model").arg("cr1000x").arg("--compiler").arg("fake-v1");
+    cmd.assert().success().stdout(predicate::str::contains("north-tower.cr1x"));
+    let manifest = std::fs::read_to_string(project.join("crbrs.toml"))?;
+    assert!(manifest.contains("required_compilers = [\"fake-v1\"]\n"), "{}", manifest);
+    assert!(manifest.contains("cr1x = \"fake-v1\"\n"), "{}", manifest);
+    assert!(std::fs::read_to_string(project.join(".gitignore"))?.contains("/build/"));
+
+    // The starter programs are clean for the linter.
+    for model in crbrs_lib::init::MODELS {
+        let program = crbrs_lib::init::program(model, "station");
+        assert!(crbrs_lib::syntax::lint::lint_source(&program).is_empty(), "{}:\n{}", model.name, program);
+    }
+
+    // The manifest pins the compiler, so the program builds without any association.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&project);
+    cmd.assert().success();
+
+    // A second init keeps the project as it is.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("init").arg(&project).arg("--model").arg("CR1000X");
+    cmd.assert().failure().stderr(predicate::str::contains("is already a crbrs project"));
+
+    // Without a compiler the manifest leaves it to fill in.
+    let other = temp_dir.path().join("south");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("--no-input").arg("init").arg(&other);
+    cmd.assert().success().stdout(predicate::str::contains("No compiler is associated with .cr1x yet"));
+    let manifest = std::fs::read_to_string(other.join("crbrs.toml"))?;
+    assert!(manifest.contains("# required_compilers"), "{}", manifest);
+    assert!(crbrs_lib::project::Project::load(&other.join("crbrs.toml"))?.manifest.required_compilers.is_empty());
+    assert!(other.join("south.cr1x").is_file());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_install_from_a_local_archive_or_directory() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    let temp_dir = TempDir::new()?;
+    let script = "#!/bin/sh\nif grep -q Bad \"$1\"; then printf '%s -- Compile Failed!\\nline 2: Bad is not a keyword\\n' \"$1\"; \
+                  else echo \"$1 -- Compiled OK.\"; fi\n";
+
+    // A support-provided archive with everything in one top-level folder.
+    let archive = temp_dir.path().join("CR1000XComp-support.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive)?);
+    let options = zip::write::SimpleFileOptions::default().unix_permissions(0o755);
+    zip.add_directory("CR1000XComp/", options)?;
+    zip.start_file("CR1000XComp/fakecomp", options)?;
+    zip.write_all(script.as_bytes())?;
+    zip.start_file("CR1000XComp/readme.txt", options)?;
+    zip.write_all(b"Compiler from support\n")?;
+    zip.finish()?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("install").arg("--from-file").arg(&archive).arg("--executable").arg("fakecomp").arg("--compiler-version").arg("7.1");
+    cmd.assert().success().stdout(predicate::str::contains("CR1000XComp-support"));
+    let installed = temp_dir.path().join("data/crbrs/compilers/CR1000XComp-support");
+    assert!(installed.join("fakecomp").is_file(), "the top-level folder is unwrapped");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("list").arg("--json");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let statuses: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(statuses[0]["id"], "CR1000XComp-support");
+    assert_eq!(statuses[0]["version"], "7.1");
+
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("CR1000XComp-support");
+    cmd.assert().success();
+
+    // A directory is copied; its crbrs-compiler.toml describes it, and the ID can be overridden.
+    let dir = temp_dir.path().join("usb-stick");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::copy(installed.join("fakecomp"), dir.join("fakecomp"))?;
+    std::fs::write(
+        dir.join("crbrs-compiler.toml"),
+        "id = \"cr300comp\"\ndescription = \"CR300 compiler\"\nversion = \"2.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n",
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("install").arg("cr300-field").arg("--from-file").arg(&dir);
+    cmd.assert().success();
+    assert!(dir.join("fakecomp").is_file(), "the source is left in place");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("cr300-field");
+    cmd.assert().success();
+
+    // Without metadata or --executable there is nothing to run.
+    let empty = temp_dir.path().join("empty");
+    std::fs::create_dir_all(&empty)?;
+    std::fs::write(empty.join("notes.txt"), "")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("install").arg("--from-file").arg(&empty);
+    cmd.assert().failure().stderr(predicate::str::contains("No .exe found"));
+    let leftovers: Vec<_> = std::fs::read_dir(temp_dir.path().join("data/crbrs/compilers"))?
+        .map(|e| e.map(|e| e.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<_, _>>()?;
+    assert!(leftovers.iter().all(|name| !name.starts_with(".local")), "{:?}", leftovers);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compile_timeout_stops_the_compiler_and_its_children() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path().join("wedged");
+    std::fs::create_dir_all(&dir)?;
+    // Never finishes, like a Wine waiting on a dialog; the helper it starts must go too.
+    let script = dir.join("fakecomp");
+    std::fs::write(&script, "#!/bin/sh\nsleep 60 &\necho $! > \"$(dirname \"$0\")/helper.pid\"\nwait\n")?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    std::fs::write(
+        dir.join("crbrs-compiler.toml"),
+        "id = \"wedged\"\ndescription = \"fake\"\nversion = \"1.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n",
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&dir);
+    cmd.assert().success();
+    let program = temp_dir.path().join("main.cr1x");
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+
+    let start = std::time::Instant::now();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("wedged").arg("--timeout").arg("1");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("did not finish within 1 s").and(predicate::str::contains("E043")));
+    assert!(start.elapsed() < std::time::Duration::from_secs(30), "took {:?}", start.elapsed());
+    let helper = std::fs::read_to_string(dir.join("helper.pid"))?;
+    // Gone, or a zombie waiting for an init that doesn't reap (as in some containers)
+    let state = std::process::Command::new("ps").args(["-o", "stat=", "-p", helper.trim()]).output()?;
+    let state = String::from_utf8_lossy(&state.stdout);
+    assert!(state.trim().is_empty() || state.trim().starts_with('Z'), "helper process {} survived: {}", helper.trim(), state);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compile_programs_on_shares_with_spaces_and_parentheses() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    // Like a mounted lab share; the compiler also runs there, next to the include
+    let share = temp_dir.path().join("Lab Share").join("Stations (2024)");
+    std::fs::create_dir_all(&share)?;
+    let program = share.join("North Ridge (v2).cr1x");
+    std::fs::write(&program, "Include \"CPU:common.cr1x\"\nBeginProg\nEndProg\n")?;
+    std::fs::write(share.join("common.cr1x"), "Public x\n")?;
+    let link = |id: &str, script: &str, requires_wine: bool| -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+        let dir = temp_dir.path().join(id);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("fakecomp"), script)?;
+        std::fs::set_permissions(dir.join("fakecomp"), std::fs::Permissions::from_mode(0o755))?;
+        std::fs::write(
+            dir.join("crbrs-compiler.toml"),
+            format!(
+                "id = \"{}\"\ndescription = \"fake\"\nversion = \"1.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\nrequires_wine = {}\n",
+                id, requires_wine
+            ),
+        )?;
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("compiler").arg("link").arg(&dir);
+        cmd.assert().success();
+        Ok(dir)
+    };
+
+    // Native: the whole path arrives as one argument
+    link(
+        "native",
+        "#!/bin/sh\nif [ $# -eq 1 ] && [ -f \"$1\" ] && [ -f common.cr1x ]; then echo \"$1 -- Compiled OK.\"; \
+         else printf '%s -- Compile Failed!\\nline 1: %s argument(s), in %s\\n' \"$1\" $# \"$PWD\"; fi\n",
+        false,
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("native");
+    cmd.assert().success().stdout(predicate::str::contains("North Ridge (v2).cr1x"));
+
+    // Under Wine: a path on drive Z:, as Wine maps the host's root
+    let wine = temp_dir.path().join("fakewine");
+    std::fs::write(
+        &wine,
+        "#!/bin/sh\nprintf '%s' \"$2\" > \"$(dirname \"$0\")/wine-arg\"\n\
+         host=$(printf '%s' \"$2\" | sed -e 's/^Z://' -e 's|\\\\|/|g')\n\
+         if [ $# -eq 2 ] && [ -f \"$host\" ]; then echo \"$2 -- Compiled OK.\"; else echo \"$2 -- Compile Failed!\"; fi\n",
+    )?;
+    std::fs::set_permissions(&wine, std::fs::Permissions::from_mode(0o755))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("wine_path").arg(&wine);
+    cmd.assert().success();
+    link("wined", "#!/bin/sh\nexit 1\n", true)?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(&program).arg("--compiler").arg("wined");
+    cmd.assert().success();
+    let expected = format!("Z:{}", program.to_string_lossy().replace('/', "\\"));
+    assert_eq!(std::fs::read_to_string(temp_dir.path().join("wine-arg"))?, expected);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compile_redirects_artifacts_from_tagged_checkouts_and_read_only_programs() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    let git = |dir: &std::path::Path, args: &[&str]| {
+        Command::new("git").current_dir(dir).args(["-c", "user.name=crbrs", "-c", "user.email=crbrs@example.org"]).args(args).output()
+    };
+    // A release checked out at its tag, as CI or a field laptop would have it
+    let release = temp_dir.path().join("release");
+    std::fs::create_dir_all(&release)?;
+    std::fs::write(release.join("station.cr2"), "BeginProg\nEndProg\n")?;
+    if git(&release, &["init", "-q"]).map_or(true, |output| !output.status.success()) {
+        return Ok(()); // No git here; there is nothing to test.
+    }
+    git(&release, &["add", "."])?;
+    git(&release, &["commit", "-q", "-m", "Release"])?;
+    git(&release, &["tag", "v1.0"])?;
+    git(&release, &["checkout", "-q", "v1.0"])?;
+
+    // A compiler that writes its image next to the program, and fails where it can't
+    let compiler = temp_dir.path().join("cr2comp");
+    std::fs::create_dir_all(&compiler)?;
+    std::fs::write(
+        compiler.join("fakecomp"),
+        "#!/bin/sh\nif : > \"${1%.*}.bin\"; then echo \"$1 -- Compiled OK.\"; else echo \"$1 -- Compile Failed!\"; fi\n",
+    )?;
+    std::fs::set_permissions(compiler.join("fakecomp"), std::fs::Permissions::from_mode(0o755))?;
+    std::fs::write(
+        compiler.join("crbrs-compiler.toml"),
+        "id = \"cr2comp\"\ndescription = \"fake\"\nversion = \"1.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\n\
+         requires_wine = false\nfamily = \"cr200\"\n",
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&compiler);
+    cmd.assert().success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(release.join("station.cr2")).arg("--image").arg("--compiler").arg("cr2comp");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("is a checkout of tag v1.0; compiling in a temporary directory"));
+    assert!(!release.join("station.bin").exists());
+    assert!(temp_dir.path().join("data").join("crbrs").join("artifacts").join("station.bin").is_file());
+    let status = git(&release, &["status", "--porcelain"])?;
+    assert!(status.stdout.is_empty(), "{}", String::from_utf8_lossy(&status.stdout));
+
+    // A read-only program; the image still goes where it was asked to
+    let locked = temp_dir.path().join("locked");
+    std::fs::create_dir_all(&locked)?;
+    std::fs::write(locked.join("pump.cr2"), "BeginProg\nEndProg\n")?;
+    std::fs::set_permissions(locked.join("pump.cr2"), std::fs::Permissions::from_mode(0o444))?;
+    let image = temp_dir.path().join("images").join("pump.bin");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(locked.join("pump.cr2")).arg("--image-out").arg(&image).arg("--compiler").arg("cr2comp");
+    cmd.assert().success().stdout(predicate::str::contains("pump.cr2 is read-only"));
+    assert!(image.is_file());
+    assert!(!locked.join("pump.bin").exists());
+
+    // Compilers can be left to write in place
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("artifacts_in_place").arg("always");
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compile").arg(release.join("station.cr2")).arg("--image").arg("--compiler").arg("cr2comp");
+    cmd.assert().success().stdout(predicate::str::contains("temporary directory").not());
+    assert!(release.join("station.bin").is_file());
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("artifacts_in_place").arg("sometimes");
+    cmd.assert().failure().stderr(predicate::str::contains("auto, always or never"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_verify_project_reports_every_check() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    link_fake_compiler(&temp_dir, "fake-v1", "1.0")?;
+    let project = temp_dir.path().join("north-tower");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("init").arg(&project).arg("--model").arg("cr1000x").arg("--compiler").arg("fake-v1");
+    cmd.assert().success();
+    let verify = |args: &[&str]| -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("verify-project").arg(&project).args(args);
+        Ok(cmd)
+    };
+    verify(&[])?
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Toolchain: 1 required compiler(s) installed"))
+        .stdout(predicate::str::contains("Formatting: 1 file(s) formatted"))
+        .stdout(predicate::str::contains("Lint: 0 error(s), 0 warning(s)"))
+        .stdout(predicate::str::contains("Build: 1 compiled, 0 failed"))
+        .stdout(predicate::str::contains("Status: OK"));
+
+    // An unformatted program with a lint error that does not compile, and a compiler the
+    // manifest does not pin
+    std::fs::write(project.join("pump.cr1x"), "Public Bad\nPublic Bad\nBeginProg\n  Scan(1,Sec,0,0)\nNextScan\nEndProg\n")?;
+    let manifest = std::fs::read_to_string(project.join("crbrs.toml"))?;
+    std::fs::write(project.join("crbrs.toml"), manifest.replace("cr1x = \"fake-v1\"\n", "cr1x = \"fake-v1\"\ncr6 = \"fake-v2\"\n"))?;
+    let output = verify(&["--json"])?.assert().failure().stderr(predicate::str::contains("E044")).get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(report["status"], "failed");
+    assert_eq!(report["toolchain"]["unlisted"], serde_json::json!(["fake-v2"]));
+    assert!(report["format"]["unformatted"][0].as_str().unwrap_or_default().ends_with("pump.cr1x"), "{}", report);
+    assert_eq!(report["lint"]["errors"], 1);
+    assert_eq!(report["lint"]["problems"][0]["code"], "L002");
+    assert!(report["build"].is_null(), "nothing is compiled while the toolchain check fails");
+
+    // With the toolchain in order the build runs and reports the failing program.
+    std::fs::write(project.join("crbrs.toml"), &manifest)?;
+    verify(&[])?
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("pump.cr1x:2: error [L002]"))
+        .stdout(predicate::str::contains("Build: 1 compiled, 1 failed"))
+        .stderr(predicate::str::contains("Project verification failed: format, lint, build."));
+
+    // A required compiler that is not installed
+    std::fs::write(project.join("crbrs.toml"), manifest.replace("[\"fake-v1\"]", "[\"fake-v1\", \"cr300comp\"]"))?;
+    verify(&[])?
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("required but not installed: cr300comp"))
+        .stdout(predicate::str::contains("Build: skipped while the toolchain is incomplete"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_project_lock_pins_the_installed_compilers() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let project = temp_dir.path().join("north-tower");
+    std::fs::create_dir_all(&project)?;
+    std::fs::write(project.join("crbrs.toml"), "required_compilers = [\"lockcomp\"]\n\n[file_associations]\ncr1x = \"lockcomp\"\n")?;
+    std::fs::write(project.join("main.cr1x"), "BeginProg\nEndProg\n")?;
+    // Saved by an old editor as Windows-1252 (a degree sign): not formattable, but not unformatted.
+    std::fs::write(project.join("legacy.cr1x"), b"'Air temperature in \xb0C\nBeginProg\nEndProg\n")?;
+    for version in ["1", "2"] {
+        std::fs::write(temp_dir.path().join(format!("v{}.zip", version)), fake_compiler_zip(&format!("version {}", version))?)?;
+    }
+    let install = |version: &str, dir: &std::path::Path| -> Result<assert_cmd::assert::Assert, Box<dyn std::error::Error>> {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.current_dir(dir).arg("compiler").arg("install").arg("lockcomp");
+        cmd.arg("--from-file").arg(temp_dir.path().join(format!("v{}.zip", version)));
+        cmd.arg("--executable").arg("fakecomp").arg("--compiler-version").arg(version);
+        Ok(cmd.assert())
+    };
+    let verify = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("verify-project").arg(&project);
+        Ok(cmd)
+    };
+
+    install("1", &project)?.success().stdout(predicate::str::contains("Recorded the project's compilers in"));
+    let lock = std::fs::read_to_string(project.join("crbrs.lock"))?;
+    let v1_sha256 = sha256_hex(&std::fs::read(temp_dir.path().join("v1.zip"))?);
+    assert!(lock.contains("id = \"lockcomp\"") && lock.contains("version = \"1\"") && lock.contains(&v1_sha256), "{}", lock);
+    verify()?
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Toolchain: 1 required compiler(s) installed"))
+        .stdout(predicate::str::contains("Formatting: 1 file(s) formatted"))
+        .stdout(predicate::str::contains("Formatting: 1 file(s) could not be checked"))
+        .stdout(predicate::str::contains("legacy.cr1x: "));
+
+    // Another build installed outside the project leaves the lock alone, and no longer matches it.
+    install("2", temp_dir.path())?.success().stdout(predicate::str::contains("Recorded").not());
+    assert_eq!(std::fs::read_to_string(project.join("crbrs.lock"))?, lock);
+    verify()?
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("not what crbrs.lock records: lockcomp (locked: version 1"))
+        .stderr(predicate::str::contains("Project verification failed: toolchain, build."));
+
+    // Installing it in the project updates the lock.
+    install("2", &project)?.success().stdout(predicate::str::contains("Recorded the project's compilers in"));
+    assert!(std::fs::read_to_string(project.join("crbrs.lock"))?.contains("version = \"2\""));
+    verify()?.assert().success();
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compile_image_never_passes_off_a_stale_image() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    // A compiler that reports success, but writes no image for programs mentioning NoImage
+    let compiler = temp_dir.path().join("cr2comp");
+    std::fs::create_dir_all(&compiler)?;
+    std::fs::write(
+        compiler.join("fakecomp"),
+        "#!/bin/sh\ngrep -q NoImage \"$1\" || : > \"${1%.*}.bin\"\necho \"$1 -- Compiled OK.\"\n",
+    )?;
+    std::fs::set_permissions(compiler.join("fakecomp"), std::fs::Permissions::from_mode(0o755))?;
+    std::fs::write(
+        compiler.join("crbrs-compiler.toml"),
+        "id = \"cr2comp\"\ndescription = \"fake\"\nversion = \"1.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\n\
+         requires_wine = false\nfamily = \"cr200\"\n",
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("compiler").arg("link").arg(&compiler);
+    cmd.assert().success();
+
+    let program = temp_dir.path().join("station.cr2");
+    let image = temp_dir.path().join("station.bin");
+    let compile = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("compile").arg(&program).arg("--image").arg("--compiler").arg("cr2comp");
+        Ok(cmd)
+    };
+    std::fs::write(&program, "BeginProg\nEndProg\n")?;
+    compile()?.assert().success().stdout(predicate::str::contains("station.bin"));
+    assert!(image.is_file());
+
+    // The image of the earlier run is removed first, so the missing one is noticed.
+    std::fs::write(&program, "'NoImage\nBeginProg\nEndProg\n")?;
+    compile()?.assert().failure().stderr(predicate::str::contains("no download image was found").and(predicate::str::contains("E023")));
+    assert!(!image.exists());
+    Ok(())
+}
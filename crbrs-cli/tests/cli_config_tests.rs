@@ -14,13 +14,14 @@ const CONFIG_FILENAME: &str = "config.toml";
 
 // --- Helper Functions for Test Isolation ---
 
-/// Creates a Command for `crbrs-cli` configured to use isolated config/data directories.
+/// Creates a Command for `crbrs` configured to use isolated config/data directories.
 /// Sets XDG_CONFIG_HOME and XDG_DATA_HOME environment variables pointing inside the temp_dir.
 fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
-    let mut cmd = Command::cargo_bin("crbrs-cli")?;
+    let mut cmd = Command::cargo_bin("crbrs")?;
     // Redirect config and data directories using standard XDG environment variables
     cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
     cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
     // Optional: Clear other potentially interfering env vars if needed
     // cmd.env_remove("HOME"); // Be careful with this, might break things unexpectedly
     Ok(cmd)
@@ -41,7 +42,9 @@ fn get_isolated_config_file_path(temp_dir: &TempDir) -> PathBuf {
 fn test_config_path_default() -> Result<(), Box<dyn std::error::Error>> {
     // Test the *default* path resolution without isolation override
     // This will vary based on the OS (e.g., ~/Library/... on macOS)
-    let mut cmd = Command::cargo_bin("crbrs-cli")?;
+    let temp_dir = TempDir::new()?;
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data")); // Nothing of the test goes into the real data directory
     cmd.arg("config").arg("path");
     cmd.assert()
         .success()
@@ -75,7 +78,7 @@ fn test_config_show_defaults_isolated() -> Result<(), Box<dyn std::error::Error>
     cmd.assert()
         .success()
         .stdout(predicate::str::contains(
-            "Repository URL: https://example.com/compilers.toml", // Check a default value
+            "Repository URL: https://raw.githubusercontent.com/RileyLeff/campbell-scientific-compilers", // Check a default value
         ))
         .stdout(predicate::str::contains(
             "Wine Path: (Not Set - using PATH)", // Check default wine path message
@@ -131,7 +134,7 @@ fn test_config_set_and_show_isolated() -> Result<(), Box<dyn std::error::Error>>
     cmd_show
         .assert()
         .success()
-        .stdout(predicate::str::contains(&format!(
+        .stdout(predicate::str::contains(format!(
             "Wine Path: {}",
             test_wine_path
         )));
@@ -204,4 +207,600 @@ fn test_config_set_invalid_key() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-// Add more tests as needed for edge cases, other commands (compiler list initially), etc.
\ No newline at end of file
+#[test]
+fn test_errors_carry_codes_that_explain_describes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("this_key_is_bad").arg("some_value");
+    cmd.assert().failure().stderr(predicate::str::contains("run: crbrs explain E001"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("explain").arg("e009");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("E009: No compiler for the file extension"))
+        .stdout(predicate::str::contains("crbrs config set-association"));
+
+    // Lint rules can be looked up by code or by ID.
+    for code in ["L002", "duplicate-declaration"] {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("explain").arg(code);
+        cmd.assert().success().stdout(predicate::str::contains("L002: lint rule `duplicate-declaration`"));
+    }
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("explain");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("E001  Configuration error"))
+        .stdout(predicate::str::contains("E039  Input required"))
+        .stdout(predicate::str::contains("L014  lint rule `toolchain-directive`"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("explain").arg("E999");
+    cmd.assert().failure().stderr(predicate::str::contains("Unknown error or lint code: E999"));
+    Ok(())
+}
+
+// Add more tests as needed for edge cases, other commands (compiler list initially), etc.
+#[test]
+fn test_config_set_network_isolated() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let isolated_config = get_isolated_config_file_path(&temp_dir);
+
+    let mut cmd_set = crbrs_cmd_isolated(&temp_dir)?;
+    cmd_set.arg("config").arg("set").arg("network.retries").arg("5");
+    cmd_set.assert().success();
+
+    let content = std::fs::read_to_string(&isolated_config)?;
+    assert!(content.contains("[network]"));
+    assert!(content.contains("retries = 5"));
+
+    let mut cmd_show = crbrs_cmd_isolated(&temp_dir)?;
+    cmd_show.arg("config").arg("show");
+    cmd_show.assert().success().stdout(predicate::str::contains("5 retries"));
+
+    let mut cmd_bad = crbrs_cmd_isolated(&temp_dir)?;
+    cmd_bad.arg("config").arg("set").arg("network.retries").arg("many");
+    cmd_bad.assert().failure().stderr(predicate::str::contains("expects a whole number"));
+    Ok(())
+}
+
+#[test]
+fn test_portable_mode_keeps_everything_under_one_directory() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let home = temp_dir.path().join("usb");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("--portable").arg(&home).arg("config").arg("set").arg("wine_path").arg("/usr/bin/wine");
+    cmd.assert().success();
+    assert!(home.join("config").join(CONFIG_FILENAME).exists());
+    assert!(!get_isolated_config_file_path(&temp_dir).exists());
+
+    // CRBRS_HOME selects the same layout; the storage path follows it.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.env("CRBRS_HOME", &home).arg("config").arg("show");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Portable Home:"))
+        .stdout(predicate::str::contains("/usr/bin/wine"))
+        .stdout(predicate::str::contains(home.join("data").join("compilers").to_string_lossy().as_ref()));
+    Ok(())
+}
+
+#[test]
+fn test_config_validate_reports_all_problems() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let config_path = temp_dir.path().join("check.toml");
+    std::fs::write(
+        &config_path,
+        "compiler_repository_url = \"not a url\"\ncolour = \"red\"\n\n[network]\nretries = \"three\"\n\n[file_associations]\ncr1 = \"missing\"\n",
+    )?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("validate").arg(&config_path);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("line 1: error: compiler_repository_url"))
+        .stdout(predicate::str::contains("line 2: warning: colour: unknown key"))
+        .stdout(predicate::str::contains("line 5: error: network.retries"));
+
+    std::fs::write(&config_path, "colour = \"red\"\n[file_associations]\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("validate").arg(&config_path);
+    cmd.assert().success().stdout(predicate::str::contains("is valid (1 warning(s))"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("validate").arg(&config_path).arg("--strict");
+    cmd.assert().failure().stdout(predicate::str::contains("line 1: error: colour: unknown key"));
+    Ok(())
+}
+
+#[test]
+fn test_config_profiles_override_and_save_separately() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let isolated_config = get_isolated_config_file_path(&temp_dir);
+    std::fs::create_dir_all(isolated_config.parent().unwrap())?;
+    std::fs::write(
+        &isolated_config,
+        "wine_path = \"/usr/bin/wine\"\n\n[profiles.fieldlaptop]\nwine_path = \"/opt/wine-stable/bin/wine\"\n\n[profiles.fieldlaptop.network]\nretries = 0\n",
+    )?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("--profile").arg("fieldlaptop").arg("config").arg("show");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Profile: fieldlaptop"))
+        .stdout(predicate::str::contains("/opt/wine-stable/bin/wine"));
+
+    // A setting the profile overrides is saved into the profile; others go to the base.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.env("CRBRS_PROFILE", "fieldlaptop").arg("config").arg("set").arg("wine_path").arg("/opt/wine-9/bin/wine");
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.env("CRBRS_PROFILE", "fieldlaptop").arg("config").arg("set").arg("network.backoff_ms").arg("5");
+    cmd.assert().success();
+
+    let content = std::fs::read_to_string(&isolated_config)?;
+    let saved: toml::Table = toml::from_str(&content)?;
+    assert_eq!(saved["wine_path"].as_str(), Some("/usr/bin/wine"));
+    assert_eq!(saved["network"]["backoff_ms"].as_integer(), Some(5));
+    assert_eq!(saved["network"]["retries"].as_integer(), Some(3));
+    assert_eq!(saved["profiles"]["fieldlaptop"]["wine_path"].as_str(), Some("/opt/wine-9/bin/wine"));
+    assert_eq!(saved["profiles"]["fieldlaptop"]["network"]["retries"].as_integer(), Some(0));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("--profile").arg("ci").arg("config").arg("show");
+    cmd.assert().failure().stderr(predicate::str::contains("unknown profile 'ci' (defined: fieldlaptop)"));
+    Ok(())
+}
+
+#[test]
+fn test_environment_variables_override_settings_without_being_saved() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let isolated_config = get_isolated_config_file_path(&temp_dir);
+    std::fs::create_dir_all(isolated_config.parent().unwrap())?;
+    std::fs::write(&isolated_config, "wine_path = \"/usr/bin/wine\"\n")?;
+    let with_environment = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.env("CRBRS_WINE_PATH", "/opt/ci/wine")
+            .env("CRBRS_COMPILER_STORAGE_PATH", "/cache/compilers")
+            .env("CRBRS_MAX_DIAGNOSTICS", "7")
+            .env("CRBRS_NETWORK__OFFLINE", "true");
+        Ok(cmd)
+    };
+
+    let mut cmd = with_environment()?;
+    cmd.arg("config").arg("show");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Wine Path: /opt/ci/wine"))
+        .stdout(predicate::str::contains("Compiler Storage Path: /cache/compilers"))
+        .stdout(predicate::str::contains("Max Diagnostics: 7"))
+        .stdout(predicate::str::contains("From the Environment:").and(predicate::str::contains("network.offline")));
+
+    // Saving writes what was set, not what the environment overrides.
+    let mut cmd = with_environment()?;
+    cmd.arg("config").arg("set").arg("compile_timeout_secs").arg("90");
+    cmd.assert().success();
+    let saved: toml::Table = toml::from_str(&std::fs::read_to_string(&isolated_config)?)?;
+    assert_eq!(saved["wine_path"].as_str(), Some("/usr/bin/wine"));
+    assert_eq!(saved["max_diagnostics"].as_integer(), Some(50));
+    assert_eq!(saved["network"]["offline"].as_bool(), Some(false));
+    assert!(saved.get("compiler_storage_path").is_none());
+    assert_eq!(saved["compile_timeout_secs"].as_integer(), Some(90));
+
+    // ... unless it is the setting changed.
+    let mut cmd = with_environment()?;
+    cmd.arg("config").arg("set").arg("wine_path").arg("/opt/wine-9/bin/wine");
+    cmd.assert().success();
+    let saved: toml::Table = toml::from_str(&std::fs::read_to_string(&isolated_config)?)?;
+    assert_eq!(saved["wine_path"].as_str(), Some("/opt/wine-9/bin/wine"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.env("CRBRS_MAX_DIAGNOSTICS", "many").arg("config").arg("show");
+    cmd.assert().failure().stderr(predicate::str::contains("CRBRS environment variables"));
+    Ok(())
+}
+
+#[test]
+fn test_log_file_records_json_lines_at_any_verbosity() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let log_file = temp_dir.path().join("logs").join("run.jsonl");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("--log-file").arg(&log_file).arg("config").arg("show");
+    cmd.assert().success().stderr(predicate::str::contains("Executing Config Show").not());
+
+    let content = std::fs::read_to_string(&log_file)?;
+    let records: Vec<serde_json::Value> = content.lines().map(serde_json::from_str).collect::<Result<_, _>>()?;
+    assert!(records.iter().any(|r| r["level"] == "INFO" && r["message"] == "Executing Config Show command..."));
+    assert!(records.iter().any(|r| r["level"] == "DEBUG" && r["message"].as_str().unwrap_or("").starts_with("Settings loaded")));
+
+    // The setting works the same way, appending to the file.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("log_file").arg(&log_file);
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("path");
+    cmd.assert().success();
+    let runs: std::collections::HashSet<String> = std::fs::read_to_string(&log_file)?
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).map(|r| r["run"].to_string()))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(runs.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_messages_follow_the_locale() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.env_remove("CRBRS_LANG").env("LANG", "de_DE.UTF-8");
+    cmd.arg("config").arg("set-association").arg("--extension").arg("cr1").arg("--compiler-id").arg("cr1000comp");
+    cmd.assert().success().stdout(predicate::str::contains("'.cr1' dem Compiler 'cr1000comp' zugeordnet"));
+
+    // CRBRS_LANG wins over LANG; regional variants use the language's catalog.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.env("CRBRS_LANG", "es-MX").env("LANG", "de_DE.UTF-8");
+    cmd.arg("config").arg("unset-association").arg("--extension").arg("cr1");
+    cmd.assert().success().stdout(predicate::str::contains("Asociación de '.cr1' eliminada"));
+
+    // Locales without a catalog fall back to English.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.env("CRBRS_LANG", "ja-JP");
+    cmd.arg("config").arg("unset-association").arg("--extension").arg("cr1");
+    cmd.assert().success().stdout(predicate::str::contains("No association found for '.cr1'"));
+    Ok(())
+}
+
+#[test]
+fn test_config_lint_rule_levels() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("lint.unit-mismatch").arg("off");
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("lint.table-never-called").arg("Hint");
+    cmd.assert().success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("show");
+    cmd.assert().success().stdout(predicate::str::contains("Lint Rules: table-never-called = hint, unit-mismatch = off"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("lint.no-such-rule").arg("off");
+    cmd.assert().failure().stderr(predicate::str::contains("Unknown lint rule: no-such-rule"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("lint.unit-mismatch").arg("loud");
+    cmd.assert().failure().stderr(predicate::str::contains("'loud' is not a lint level"));
+
+    let config_path = temp_dir.path().join("check.toml");
+    std::fs::write(&config_path, "[lint]\nunit-mismatch = \"off\"\nunit-mismatchh = \"off\"\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("validate").arg(&config_path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("line 3: warning: lint.unit-mismatchh: unknown lint rule 'unit-mismatchh'"));
+    Ok(())
+}
+
+#[test]
+fn test_config_includes_layer_under_the_user_file() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let isolated_config = get_isolated_config_file_path(&temp_dir);
+    let config_dir = isolated_config.parent().unwrap();
+    std::fs::create_dir_all(config_dir)?;
+    std::fs::write(
+        config_dir.join("team-defaults.toml"),
+        "compiler_repository_url = \"https://example.com/team/manifest.json\"\nwine_path = \"/opt/team/wine\"\n\n[lint]\nunit-mismatch = \"off\"\n",
+    )?;
+    // The user's file overrides one team value, and still holds a dumped built-in default.
+    let default_url = "https://raw.githubusercontent.com/RileyLeff/campbell-scientific-compilers/refs/heads/main/compilers.toml";
+    std::fs::write(
+        &isolated_config,
+        format!("include = [\"team-defaults.toml\"]\ncompiler_repository_url = \"{}\"\nwine_path = \"/usr/bin/wine\"\n", default_url),
+    )?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("show");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Includes: team-defaults.toml"))
+        .stdout(predicate::str::contains("Repository URL: https://example.com/team/manifest.json"))
+        .stdout(predicate::str::contains("Wine Path: /usr/bin/wine"))
+        .stdout(predicate::str::contains("unit-mismatch = off"));
+
+    // Saving writes only what differs from the included file.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("lint.table-never-called").arg("hint");
+    cmd.assert().success();
+    let saved: toml::Table = toml::from_str(&std::fs::read_to_string(&isolated_config)?)?;
+    assert_eq!(saved["include"].as_array().map(Vec::len), Some(1));
+    assert_eq!(saved["wine_path"].as_str(), Some("/usr/bin/wine"));
+    assert_eq!(saved["lint"]["table-never-called"].as_str(), Some("hint"));
+    assert!(saved.get("compiler_repository_url").is_none(), "{:?}", saved);
+    assert!(saved["lint"].get("unit-mismatch").is_none(), "{:?}", saved);
+
+    std::fs::write(&isolated_config, "include = [\"missing.toml\"]\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("show");
+    cmd.assert().failure().stderr(predicate::str::contains("includes 'missing.toml', which was not found"));
+    let check_path = temp_dir.path().join("check.toml");
+    std::fs::write(&check_path, "wine_path = \"/usr/bin/wine\"\ninclude = [\"missing.toml\"]\n")?;
+    std::fs::remove_file(&isolated_config)?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("validate").arg(&check_path);
+    cmd.assert().failure().stdout(predicate::str::contains("line 2: error: include:"));
+    Ok(())
+}
+
+#[test]
+fn test_damaged_config_is_recovered_from_its_backup() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let config_file = get_isolated_config_file_path(&temp_dir);
+    for wine in ["/usr/bin/wine", "/opt/wine/bin/wine"] {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("config").arg("set").arg("wine_path").arg(wine);
+        cmd.assert().success();
+    }
+    let backup = config_file.with_file_name("config.toml.bak");
+    assert!(std::fs::read_to_string(&backup)?.contains("/usr/bin/wine"), "the previous version is kept");
+    let leftovers: Vec<_> = std::fs::read_dir(config_file.parent().unwrap())?
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+
+    // A write cut short by a power loss: the backup stands in until the next save.
+    std::fs::write(&config_file, b"wine_path = \"/opt/wi\0\0\0\0")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("show");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("/usr/bin/wine"))
+        .stderr(predicate::str::contains("is damaged").and(predicate::str::contains("using its backup")));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("wine_path").arg("/opt/wine/bin/wine");
+    cmd.assert().success();
+    assert!(std::fs::read_to_string(&config_file)?.contains("/opt/wine/bin/wine"));
+    assert!(config_file.with_file_name("config.toml.damaged").exists(), "the damaged file is kept for inspection");
+    assert!(std::fs::read_to_string(&backup)?.contains("/usr/bin/wine"), "a damaged file never replaces the backup");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("show");
+    cmd.assert().success().stderr(predicate::str::contains("is damaged").not());
+
+    // Without a usable backup the defaults apply, rather than every command failing.
+    std::fs::remove_file(&backup)?;
+    std::fs::write(&config_file, "wine_path = [[[")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("show");
+    cmd.assert().success().stderr(predicate::str::contains("has no usable backup"));
+    Ok(())
+}
+
+#[test]
+fn test_self_purge_by_category() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("wine_path").arg("/usr/bin/wine");
+    cmd.assert().success();
+    let data_dir = temp_dir.path().join("data").join("crbrs");
+    std::fs::create_dir_all(data_dir.join("compilers").join("cr300comp"))?;
+    std::fs::write(data_dir.join("compilers").join("cr300comp").join("cr300comp.exe"), vec![0u8; 2048])?;
+    std::fs::write(data_dir.join("compilers").join("cr300comp").join("crbrs-compiler.toml"), "")?;
+    std::fs::write(data_dir.join("history.json"), "{}")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("self").arg("purge").arg("--compilers").arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[compilers]").and(predicate::str::contains("(2.0 KiB)")))
+        .stdout(predicate::str::contains("[history]").not());
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("self").arg("purge").arg("--compilers");
+    cmd.assert().failure().stderr(predicate::str::contains("Input required"));
+    assert!(data_dir.join("compilers").exists());
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("self").arg("purge").arg("--compilers").arg("--yes");
+    cmd.assert().success().stdout(predicate::str::contains("Removed 1 item."));
+    assert!(!data_dir.join("compilers").exists());
+    assert!(data_dir.join("history.json").exists());
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("self").arg("purge").arg("--yes");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[config]"))
+        .stdout(predicate::str::contains("[caches]"))
+        .stdout(predicate::str::contains("[history]"));
+    assert!(!get_isolated_config_file_path(&temp_dir).exists());
+    assert!(!data_dir.exists(), "{:?}", std::fs::read_dir(&data_dir).map(|d| d.flatten().map(|e| e.path()).collect::<Vec<_>>()));
+    Ok(())
+}
+
+#[test]
+fn test_self_purge_keeps_other_files_in_a_shared_compiler_storage() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let tools = temp_dir.path().join("tools");
+    std::fs::create_dir_all(tools.join("editor"))?;
+    std::fs::write(tools.join("editor").join("notes.txt"), "not crbrs'")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_storage_path").arg(&tools);
+    cmd.assert().success();
+    for dir in ["cr300comp", ".objects", ".archives", ".cr1000x.partial", ".cr300comp.previous"] {
+        std::fs::create_dir_all(tools.join(dir))?;
+    }
+    std::fs::write(tools.join("cr300comp").join("crbrs-compiler.toml"), "")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("self").arg("purge").arg("--compilers").arg("--yes");
+    cmd.assert().success().stdout(predicate::str::contains("Removed 5 items."));
+    let left: Vec<_> = std::fs::read_dir(&tools)?.map(|e| e.map(|e| e.file_name())).collect::<Result<_, _>>()?;
+    assert_eq!(left, vec![std::ffi::OsString::from("editor")]);
+    assert!(tools.join("editor").join("notes.txt").is_file());
+    Ok(())
+}
+
+#[test]
+fn test_clean_temp_removes_directories_left_by_other_processes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let tmp = temp_dir.path().join("data").join("crbrs").join("tmp");
+    // A process that has exited, and one (this test) that is still running.
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_crbrs")).arg("--version").spawn()?;
+    let dead = child.id();
+    child.wait()?;
+    let left_over = format!("sandbox-{}-0", dead);
+    let running = format!("lsp-{}", std::process::id());
+    for name in [&left_over, &running] {
+        std::fs::create_dir_all(tmp.join(name))?;
+        std::fs::write(tmp.join(name).join("main.cr1x"), vec![b' '; 1024])?;
+    }
+
+    // Used within the last day: kept unless --all
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("clean").arg("--temp");
+    cmd.assert().success().stdout(predicate::str::contains("Nothing to remove."));
+    assert!(tmp.join(&left_over).exists());
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("clean").arg("--temp").arg("--all");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{} (1.0 KiB)", left_over)))
+        .stdout(predicate::str::contains("Removed 1 item."));
+    assert!(!tmp.join(&left_over).exists());
+    assert!(tmp.join(&running).join("main.cr1x").exists(), "a running process's directory is kept");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_read_only_config_dir_falls_back_to_the_data_dir() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let temp_dir = TempDir::new()?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("wine_path").arg("/opt/managed/wine");
+    cmd.assert().success();
+    let config_file = get_isolated_config_file_path(&temp_dir);
+    let config_dir = config_file.parent().unwrap().to_path_buf();
+    let set_mode = |path: &std::path::Path, mode: u32| std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode));
+    set_mode(&config_file, 0o444)?;
+    set_mode(&config_dir, 0o555)?;
+    if std::fs::write(config_dir.join("probe"), "").is_ok() {
+        // Permissions don't stop this user (e.g. root); there is nothing to test.
+        set_mode(&config_dir, 0o755)?;
+        return Ok(());
+    }
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("compiler_repository_url").arg("https://example.org/compilers.toml");
+    cmd.assert().success();
+    let fallback = temp_dir.path().join("data").join("crbrs").join("fallback-config.toml");
+    let text = std::fs::read_to_string(&fallback)?;
+    assert!(text.contains("https://example.org/compilers.toml"), "{}", text);
+    assert!(!text.contains("/opt/managed/wine"), "only changes are stored: {}", text);
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("path");
+    cmd.assert().success().stdout(predicate::str::contains("fallback-config.toml"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("show");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("/opt/managed/wine"))
+        .stdout(predicate::str::contains("https://example.org/compilers.toml"));
+
+    // With nowhere to write, the command says so and exits with its own code.
+    let data_dir = fallback.parent().unwrap().to_path_buf();
+    set_mode(&fallback, 0o444)?;
+    set_mode(&data_dir, 0o555)?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("wine_path").arg("/usr/bin/wine");
+    let result = cmd.assert().code(3).stderr(predicate::str::contains("The settings could not be saved to"));
+    result.stdout(predicate::str::contains("Set 'wine_path'").not());
+    set_mode(&data_dir, 0o755)?;
+    set_mode(&config_dir, 0o755)?;
+    Ok(())
+}
+
+#[test]
+fn test_fmt_discovers_formatter_options_up_the_tree() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let project = temp_dir.path().join("project");
+    let program = "BeginProg\nScan(1,Sec,0,0)\nNextScan\nEndProg\n";
+    for (file, text) in [
+        (".editorconfig", "root = true\n\n[*]\nindent_style = space\nindent_size = 4\n\n[*.{cr300,cr6}]\nindent_style = tab\n"),
+        ("a.cr1x", program),
+        ("b.cr300", program),
+        ("pinned/crbrs.toml", "[format]\nindent_width = 3\n"),
+        ("pinned/c.cr1x", program),
+        ("both/crbrs.toml", "[format]\nindent_width = 3\n"),
+        ("both/.crbrsfmt.toml", "indent_width = 1\nmax_blank_lines = 0\n"),
+        ("both/d.cr1x", program),
+    ] {
+        let path = project.join(file);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, text)?;
+    }
+
+    // --check changes nothing, and fails while a program is not formatted.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.current_dir(project.join("pinned")).arg("fmt").arg("--check");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("Not formatted: ./c.cr1x"))
+        .stderr(predicate::str::contains("1 of 1 file(s) are not formatted"))
+        .stderr(predicate::str::contains("E040"));
+    assert_eq!(std::fs::read_to_string(project.join("pinned/c.cr1x"))?, program);
+
+    // Formatting from the project root or the program's directory gives the same result.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.current_dir(project.join("pinned")).arg("fmt").arg("c.cr1x");
+    cmd.assert().success().stdout(predicate::str::contains("1 of 1 file(s) reformatted"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.current_dir(&project).arg("fmt");
+    cmd.assert().success().stdout(predicate::str::contains("3 of 4 file(s) reformatted"));
+    let read = |file: &str| std::fs::read_to_string(project.join(file)).unwrap();
+    assert_eq!(read("a.cr1x"), "BeginProg\n    Scan(1,Sec,0,0)\n    NextScan\nEndProg\n", "from .editorconfig");
+    assert_eq!(read("b.cr300"), "BeginProg\n\tScan(1,Sec,0,0)\n\tNextScan\nEndProg\n", "its section overrides [*]");
+    assert_eq!(read("pinned/c.cr1x"), "BeginProg\n   Scan(1,Sec,0,0)\n   NextScan\nEndProg\n", "crbrs.toml [format]");
+    assert_eq!(read("both/d.cr1x"), "BeginProg\n Scan(1,Sec,0,0)\n NextScan\nEndProg\n", ".crbrsfmt.toml wins");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("fmt").arg("--check").arg(&project);
+    cmd.assert().success().stdout(predicate::str::contains("Not formatted").not());
+
+    std::fs::write(project.join("both/.crbrsfmt.toml"), "indent = 4\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("fmt").arg(project.join("both"));
+    cmd.assert().failure().stderr(predicate::str::contains("Unknown formatter option 'indent'"));
+    Ok(())
+}
+
+#[test]
+fn test_fmt_fix_case_canonicalizes_keywords() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let program = temp_dir.path().join("station.cr1x");
+    let source = "Public battv\nDataTable(Hourly,true,-1)\n  cardout(0,-1)\nEndTable\nbeginprog\n  scan(5,sec,0,0)\n    battery(battv)\n  nextscan\nendprog\n";
+    std::fs::write(&program, source)?;
+
+    // Plain formatting leaves case alone.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("fmt").arg("--check").arg(&program);
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("fmt").arg("--check").arg("--fix-case").arg(&program);
+    cmd.assert().failure().stderr(predicate::str::contains("`crbrs fmt --fix-case` formats them"));
+    assert_eq!(std::fs::read_to_string(&program)?, source);
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("fmt").arg("--fix-case").arg(&program);
+    cmd.assert().success();
+    assert_eq!(
+        std::fs::read_to_string(&program)?,
+        "Public battv\nDataTable(Hourly,True,-1)\n  CardOut(0,-1)\nEndTable\nBeginProg\n  Scan(5,Sec,0,0)\n    Battery(battv)\n  NextScan\nEndProg\n",
+        "keywords are canonical, the variable keeps its spelling"
+    );
+    Ok(())
+}
@@ -0,0 +1,417 @@
+// FILE: crbrs-cli/tests/cli_data_tests.rs
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
+    Ok(cmd)
+}
+
+/// Hourly table with a temperature spike at 02:00, an RH reading of 110 % at 05:00 and a
+/// temperature stuck at 15.0 from 04:00.
+fn write_hourly(dir: &std::path::Path) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    let mut text = String::from(
+        "\"TOA5\",\"Station12\",\"CR1000X\",\"1234\",\"CR1000X.Std.06\",\"CPU:soil.cr1x\",\"1234\",\"Hourly\"\r\n\
+\"TIMESTAMP\",\"RECORD\",\"AirT_Avg\",\"RH\",\"Rain_mm_Tot\"\r\n\
+\"TS\",\"RN\",\"Deg C\",\"%\",\"mm\"\r\n\
+\"\",\"\",\"Avg\",\"Smp\",\"Tot\"\r\n",
+    );
+    let temperatures = [14.2, 14.5, 35.2, 14.9].into_iter().chain(std::iter::repeat_n(15.0, 12));
+    for (i, t) in temperatures.enumerate() {
+        let rh = match i {
+            3 => "\"NAN\"".to_string(),
+            5 => "110".to_string(),
+            _ => "60".to_string(),
+        };
+        text.push_str(&format!("\"2026-10-16 {:02}:00:00\",{},{},{},0\r\n", i, i, t, rh));
+    }
+    let path = dir.join("hourly.dat");
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+#[test]
+fn test_data_qa_flags_with_unit_defaults() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let data = write_hourly(temp_dir.path())?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("qa").arg(&data).arg("--format").arg("csv");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("2026-10-16 02:00:00,2,AirT_Avg,spike,35.2,changed by 20.7 from 14.5 (limit 10)\n"))
+        .stdout(predicate::str::contains("2026-10-16 05:00:00,5,RH,range,110,outside 0 to 105\n"))
+        .stdout(predicate::str::contains("2026-10-16 15:00:00,15,AirT_Avg,flatline,15,unchanged for 12 values since 2026-10-16 04:00:00\n"))
+        .stdout(predicate::str::contains("Rain_mm_Tot").not());
+    Ok(())
+}
+
+#[test]
+fn test_data_qa_rules_override_defaults() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let data = write_hourly(temp_dir.path())?;
+    let rules = temp_dir.path().join("qa.toml");
+    std::fs::write(&rules, "[units.\"Deg C\"]\nspike = 0\n\n[fields.airt_avg]\nflatline = 0\n\n[fields.RH]\nskip = true\n")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("qa").arg(&data).arg("--rules").arg(&rules).arg("--strict");
+    cmd.assert().success().stdout(predicate::str::contains("Hourly: 16 records, 0 flags"));
+
+    std::fs::write(&rules, "[fields.RH]\nmax = 100\nspike = 0\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("qa").arg(&data).arg("--rules").arg(&rules).arg("--format").arg("json").arg("--strict");
+    cmd.assert().failure().stderr(predicate::str::contains("failed QA checks"));
+
+    std::fs::write(&rules, "[fields.RH]\nmaximum = 100\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("qa").arg(&data).arg("--rules").arg(&rules);
+    cmd.assert().failure().stderr(predicate::str::contains("unknown field `maximum`"));
+    Ok(())
+}
+
+fn toa5(records: &[(&str, u32, &str)]) -> String {
+    let mut text = String::from(
+        "\"TOA5\",\"Station12\",\"CR1000X\",\"1234\",\"CR1000X.Std.06\",\"CPU:soil.cr1x\",\"1234\",\"Hourly\"\r\n\
+\"TIMESTAMP\",\"RECORD\",\"BattV_Min\"\r\n\"TS\",\"RN\",\"Volts\"\r\n\"\",\"\",\"Min\"\r\n",
+    );
+    for (timestamp, record, value) in records {
+        text.push_str(&format!("\"{}\",{},{}\r\n", timestamp, record, value));
+    }
+    text
+}
+
+#[test]
+fn test_data_merge_dedups_sorts_and_reports_gaps() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let later = temp_dir.path().join("collect2.dat");
+    let earlier = temp_dir.path().join("collect1.dat");
+    std::fs::write(&earlier, toa5(&[("2026-10-16 00:00:00", 0, "12.8"), ("2026-10-16 01:00:00", 1, "12.7"), ("2026-10-16 02:00:00", 2, "12.7")]))?;
+    std::fs::write(&later, toa5(&[("2026-10-16 02:00:00", 2, "12.7"), ("2026-10-16 03:00:00", 3, "\"NAN\""), ("2026-10-16 06:00:00", 6, "12.9")]))?;
+    let merged = temp_dir.path().join("merged.dat");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("merge").arg(&later).arg(&earlier).arg("-o").arg(&merged);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("5 records (1 repeated records dropped, 0 conflicting)"))
+        .stdout(predicate::str::contains("Record interval 1h: 1 gaps"))
+        .stdout(predicate::str::contains("  2026-10-16 03:00:00 -> 2026-10-16 06:00:00  (2 missing)"));
+    let text = std::fs::read_to_string(&merged)?;
+    assert!(text.ends_with("\"2026-10-16 02:00:00\",2,12.7\r\n\"2026-10-16 03:00:00\",3,\"NAN\"\r\n\"2026-10-16 06:00:00\",6,12.9\r\n"), "{}", text);
+    assert!(text.starts_with("\"TOA5\",\"Station12\""));
+    assert_eq!(text.matches("2026-10-16 02:00:00").count(), 1);
+
+    let other = temp_dir.path().join("daily.dat");
+    std::fs::write(&other, toa5(&[("2026-10-17 00:00:00", 0, "12.8")]).replace("\"Hourly\"", "\"Daily\""))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("merge").arg(&earlier).arg(&other).arg("-o").arg(&merged).arg("--interval").arg("1h");
+    cmd.assert().failure().stderr(predicate::str::contains("holds table 'Daily', not 'Hourly'"));
+    Ok(())
+}
+
+#[test]
+fn test_data_retime_uses_station_timezone() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("hourly.dat");
+    std::fs::write(&input, toa5(&[("2026-10-16 22:00:00", 0, "12.8"), ("2026-10-16 23:00:00.5", 1, "12.7")]))?;
+    let output = temp_dir.path().join("hourly_utc.dat");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("retime").arg(&input).arg("-o").arg(&output);
+    cmd.assert().failure().stderr(predicate::str::contains("No time zone is configured for station 'Station12'"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("station_timezones.Station12").arg("America/New_York");
+    cmd.assert().failure().stderr(predicate::str::contains("loggers keep standard time all year"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("station_timezones.Station12").arg("UTC-5");
+    cmd.assert().success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("retime").arg(&input).arg("-o").arg(&output);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote 2 records").and(predicate::str::contains("from UTC-05:00 to UTC")));
+    let text = std::fs::read_to_string(&output)?;
+    assert!(text.ends_with("\"2026-10-17 03:00:00\",0,12.8\r\n\"2026-10-17 04:00:00.5\",1,12.7\r\n"), "{}", text);
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("retime").arg(&output).arg("-o").arg(&input).arg("--from").arg("Z").arg("--to").arg("+01:00");
+    cmd.assert().success();
+    assert!(std::fs::read_to_string(&input)?.contains("\"2026-10-17 05:00:00.5\",1,12.7"));
+    Ok(())
+}
+
+#[test]
+fn test_data_convert_formats() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("hourly.dat");
+    std::fs::write(&input, toa5(&[("2026-10-16 00:00:00", 0, "12.8"), ("2026-10-16 01:00:00", 1, "\"NAN\"")]))?;
+
+    for (name, magic) in [("hourly.parquet", &b"PAR1"[..]), ("hourly.feather", &b"ARROW1"[..])] {
+        let output = temp_dir.path().join(name);
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("data").arg("convert").arg(&input).arg("-o").arg(&output);
+        cmd.assert().success().stdout(predicate::str::contains("Wrote 2 records"));
+        let bytes = std::fs::read(&output)?;
+        assert!(bytes.starts_with(magic) && bytes.ends_with(magic), "{} is not a {:?} file", name, magic);
+    }
+
+    let output = temp_dir.path().join("hourly.txt");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("convert").arg(&input).arg("-o").arg(&output);
+    cmd.assert().failure().stderr(predicate::str::contains("pass --format"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("convert").arg(&input).arg("-o").arg(&output).arg("--format").arg("csv");
+    cmd.assert().success();
+    let text = std::fs::read_to_string(&output)?;
+    assert!(text.starts_with("TIMESTAMP,RECORD,BattV_Min\n2026-10-16 00:00:00,0,12.8\n"), "{}", text);
+    Ok(())
+}
+
+/// A TOB1 file with hourly records starting 2026-10-16 00:00, `(hour, record)` each.
+fn tob1(records: &[(u32, u32)]) -> Vec<u8> {
+    let mut bytes = b"\"TOB1\",\"Station12\",\"CR1000X\",\"1234\",\"CR1000X.Std.06\",\"CPU:soil.cr1x\",\"1234\",\"Hourly\"\r\n\
+\"SECONDS\",\"NANOSECONDS\",\"RECORD\",\"BattV_Min\",\"AirT_Avg\"\r\n\"SECONDS\",\"NANOSECONDS\",\"RN\",\"Volts\",\"Deg C\"\r\n\
+\"\",\"\",\"\",\"Min\",\"Avg\"\r\n\"ULONG\",\"ULONG\",\"ULONG\",\"FP2\",\"IEEE4\"\r\n"
+        .to_vec();
+    for (hour, record) in records {
+        bytes.extend((1_160_956_800 + hour * 3600).to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(record.to_le_bytes());
+        bytes.extend([0x45, 0x01]); // FP2 12.81
+        bytes.extend(14.5f32.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn test_data_convert_tob1_to_toa5() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("card.dat");
+    let mut bytes = tob1(&[(0, 0), (1, 1)]);
+    bytes.extend([0x01, 0x02, 0x03]); // An incomplete record from pulling the card mid-write
+    std::fs::write(&input, bytes)?;
+    let output = temp_dir.path().join("hourly.dat");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("convert").arg(&input).arg("-o").arg(&output);
+    cmd.assert().success().stdout(predicate::str::contains("Wrote 2 records"));
+    let text = std::fs::read_to_string(&output)?;
+    assert_eq!(
+        text,
+        "\"TOA5\",\"Station12\",\"CR1000X\",\"1234\",\"CR1000X.Std.06\",\"CPU:soil.cr1x\",\"1234\",\"Hourly\"\r\n\
+\"TIMESTAMP\",\"RECORD\",\"BattV_Min\",\"AirT_Avg\"\r\n\"TS\",\"RN\",\"Volts\",\"Deg C\"\r\n\"\",\"\",\"Min\",\"Avg\"\r\n\
+\"2026-10-16 00:00:00\",0,12.81,14.5\r\n\"2026-10-16 01:00:00\",1,12.81,14.5\r\n"
+    );
+
+    let parquet = temp_dir.path().join("hourly.parquet");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("convert").arg(&input).arg("-o").arg(&parquet);
+    cmd.assert().success();
+    assert!(std::fs::read(&parquet)?.starts_with(b"PAR1"));
+    assert!(!temp_dir.path().join("hourly.parquet.partial").exists());
+    Ok(())
+}
+
+#[test]
+fn test_data_convert_append_resumes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("card.dat");
+    std::fs::write(&input, tob1(&[(0, 0), (1, 1)]))?;
+    let csv = temp_dir.path().join("hourly.csv");
+    let parquet = temp_dir.path().join("hourly.parquet");
+    for output in [&csv, &parquet] {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("data").arg("convert").arg(&input).arg("-o").arg(output);
+        cmd.assert().success();
+    }
+    // An interrupted conversion left half a line behind.
+    let mut text = std::fs::read_to_string(&csv)?;
+    text.push_str("2026-10-16 02:0");
+    std::fs::write(&csv, text)?;
+
+    std::fs::write(&input, tob1(&[(0, 0), (1, 1), (2, 2), (3, 3)]))?;
+    for output in [&csv, &parquet] {
+        let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+        cmd.arg("data").arg("convert").arg(&input).arg("-o").arg(output).arg("--append");
+        cmd.assert().success().stdout(predicate::str::contains("Wrote 2 records").and(predicate::str::contains("(2 already there)")));
+    }
+    assert_eq!(
+        std::fs::read_to_string(&csv)?,
+        "TIMESTAMP,RECORD,BattV_Min,AirT_Avg\n2026-10-16 00:00:00,0,12.81,14.5\n2026-10-16 01:00:00,1,12.81,14.5\n\
+2026-10-16 02:00:00,2,12.81,14.5\n2026-10-16 03:00:00,3,12.81,14.5\n"
+    );
+
+    let other = temp_dir.path().join("other.dat");
+    std::fs::write(&other, toa5(&[("2026-10-16 04:00:00", 4, "12.8")]))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("convert").arg(&other).arg("-o").arg(&parquet).arg("--append");
+    cmd.assert().failure().stderr(predicate::str::contains("it has different fields"));
+    Ok(())
+}
+
+/// A TOB3 ring file with 100 ms records of `AirT` (IEEE4) and `Diag` (UINT2), two per 28-byte
+/// frame. The ring has wrapped: records 4-5 (new stamp) come before 2-3 (old stamp), then a
+/// frame that lost power before its footer was written, then a minor frame with record 6.
+fn tob3() -> Vec<u8> {
+    const STAMP: u32 = 4660;
+    let mut bytes = b"\"TOB3\",\"Station12\",\"CR1000X\",\"1234\",\"CR1000X.Std.06\",\"CPU:flux.cr1x\",\"1234\",\"2026-10-16 00:00:00\"\r\n\
+\"Fast\",\"100 MSEC\",\"28\",\"100\",\"4660\",\"Sec100Usec\",\"\",\"\",\"\"\r\n\"AirT\",\"Diag\"\r\n\"Deg C\",\"\"\r\n\"Smp\",\"Smp\"\r\n\"IEEE4\",\"UINT2\"\r\n"
+        .to_vec();
+    let frame_header = |first: u32| {
+        let mut header = (1_160_956_800 + first / 10).to_le_bytes().to_vec();
+        header.extend((first % 10 * 1000).to_le_bytes());
+        header.extend(first.to_le_bytes());
+        header
+    };
+    let record = |r: u32| {
+        let mut record = (20.0 + r as f32 / 2.0).to_le_bytes().to_vec();
+        record.extend((r as u16).to_be_bytes());
+        record
+    };
+    for (first, stamp) in [(4, !STAMP & 0xFFFF), (2, STAMP)] {
+        bytes.extend(frame_header(first));
+        bytes.extend(record(first));
+        bytes.extend(record(first + 1));
+        bytes.extend((stamp << 16).to_le_bytes());
+    }
+    bytes.extend(frame_header(8));
+    bytes.extend([0xFF; 16]);
+    // Minor frame: header, one record and a footer with the minor flag and its size.
+    bytes.extend([0; 6]);
+    bytes.extend(frame_header(6));
+    bytes.extend(record(6));
+    bytes.extend(((!STAMP & 0xFFFF) << 16 | 1 << 14 | 22).to_le_bytes());
+    bytes
+}
+
+#[test]
+fn test_data_convert_tob3_ring_file() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("ring.dat");
+    std::fs::write(&input, tob3())?;
+    let output = temp_dir.path().join("ring.csv");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("convert").arg(&input).arg("-o").arg(&output);
+    cmd.assert().success().stdout(predicate::str::contains("Wrote 5 records"));
+    assert_eq!(
+        std::fs::read_to_string(&output)?,
+        "TIMESTAMP,RECORD,AirT,Diag\n2026-10-16 00:00:00.2,2,21,2\n2026-10-16 00:00:00.3,3,21.5,3\n\
+2026-10-16 00:00:00.4,4,22,4\n2026-10-16 00:00:00.5,5,22.5,5\n2026-10-16 00:00:00.6,6,23,6\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_data_card_image_lists_and_converts() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let image = temp_dir.path().join("card.img");
+    {
+        let mut disk = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&image)?;
+        disk.set_len(4 * 1024 * 1024)?;
+        fatfs::format_volume(&mut disk, fatfs::FormatVolumeOptions::new())?;
+        let filesystem = fatfs::FileSystem::new(&mut disk, fatfs::FsOptions::new())?;
+        let root = filesystem.root_dir();
+        std::io::Write::write_all(&mut root.create_file("St12_Hourly.dat")?, &tob1(&[(0, 0), (1, 1)]))?;
+        std::io::Write::write_all(&mut root.create_file("powerup.ini")?, b"; not data\r\n")?;
+        let cpu = root.create_dir("CPU")?;
+        std::io::Write::write_all(
+            &mut cpu.create_file("St12_Fast.dat")?,
+            b"\"TOB2\",\"Station12\",\"CR1000X\",\"1234\",\"CR1000X.Std.06\",\"CPU:flux.cr1x\",\"1234\",\"2026-10-16 00:00:00\"\r\n\"Fast\",\"100 MSEC\",\"984\",\"86400\",\"4660\",\"Sec100Usec\",\"\",\"\",\"\"\r\n",
+        )?;
+        std::io::Write::write_all(&mut cpu.create_file("St12_Ring.dat")?, &tob3())?;
+    }
+    let before = std::fs::read(&image)?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("card").arg(&image);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("3 data files on"))
+        .stdout(predicate::str::is_match(r"CPU/St12_Fast\.dat +TOB2 +Station12 +Fast")?)
+        .stdout(predicate::str::is_match(r"CPU/St12_Ring\.dat +TOB3 +Station12 +Fast")?)
+        .stdout(predicate::str::is_match(r"St12_Hourly\.dat +TOB1 +Station12 +Hourly")?)
+        .stdout(predicate::str::contains("powerup").not());
+
+    let out_dir = temp_dir.path().join("converted");
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("card").arg(&image).arg("--convert").arg(&out_dir).arg("--format").arg("csv");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("St12_Hourly.dat -> ").and(predicate::str::contains("(2 records)")))
+        .stdout(predicate::str::contains("CPU/St12_Fast.dat: TOB2 files cannot be converted yet"))
+        .stdout(predicate::str::contains("CPU/St12_Ring.dat -> ").and(predicate::str::contains("(5 records)")));
+    assert!(std::fs::read_to_string(out_dir.join("St12_Hourly.csv"))?.contains("2026-10-16 01:00:00,1,12.81,14.5"));
+    assert_eq!(std::fs::read(&image)?, before, "the card image was modified");
+    Ok(())
+}
+
+#[test]
+fn test_data_card_directory() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let card = temp_dir.path().join("CRD");
+    std::fs::create_dir_all(card.join("old"))?;
+    std::fs::write(card.join("old").join("St12_Hourly.dat"), toa5(&[("2026-10-16 00:00:00", 0, "12.8")]))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("card").arg(&card).arg("--convert").arg(temp_dir.path().join("out")).arg("-f").arg("parquet");
+    cmd.assert().success().stdout(predicate::str::contains("old/St12_Hourly.dat -> "));
+    assert!(temp_dir.path().join("out").join("St12_Hourly.parquet").is_file());
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("card").arg(card.join("old").join("St12_Hourly.dat"));
+    cmd.assert().failure().stderr(predicate::str::contains("not a card image"));
+    Ok(())
+}
+
+fn soil_program(hourly: &str) -> String {
+    format!(
+        "Public BattV, AirT, RH\nUnits BattV = Volts\nUnits AirT = Deg C\n\n\
+DataTable (Hourly, True, -1)\n  DataInterval (0, 60, Min, 10)\n{}\nEndTable\n\n\
+BeginProg\n  Scan (1, Sec, 0, 0)\n    CallTable Hourly\n  NextScan\nEndProg\n",
+        hourly
+    )
+}
+
+#[test]
+fn test_data_check_against_program() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let data = temp_dir.path().join("Hourly.dat");
+    std::fs::write(&data, tob1(&[(0, 0)]))?;
+    let program = temp_dir.path().join("soil.cr1x");
+    std::fs::write(&program, soil_program("  Minimum (1, BattV, FP2, False, False)\n  Average (1, AirT, IEEE4, False)"))?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("check").arg(&data).arg("--program").arg(&program);
+    cmd.assert().success().stdout(predicate::str::contains("matches table Hourly of").and(predicate::str::contains("(0 warnings)")));
+
+    // A later revision stores AirT as FP2, adds RH and keeps its name.
+    std::fs::write(
+        &program,
+        soil_program("  Minimum (1, BattV, FP2, False, False)\n  Average (1, AirT, FP2, False)\n  Sample (1, RH, FP2)"),
+    )?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("check").arg(&data).arg("--program").arg(&program);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("error: AirT_Avg: stored as IEEE4 but the program writes FP2"))
+        .stdout(predicate::str::contains("error: RH: in the program but not in the data"))
+        .stderr(predicate::str::contains("does not match table 'Hourly'").and(predicate::str::contains("(2 error(s))")));
+
+    let renamed = temp_dir.path().join("soil_v2.cr1x");
+    std::fs::write(&renamed, soil_program("  Minimum (1, BattV, FP2, False, False)\n  Average (1, AirT, IEEE4, False)"))?;
+    std::fs::write(&data, toa5(&[("2026-10-16 00:00:00", 0, "12.8")]))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("data").arg("check").arg(&data).arg("--program").arg(&renamed);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("warning: the data was written by 'CPU:soil.cr1x', not 'soil_v2.cr1x'"))
+        .stdout(predicate::str::contains("error: AirT_Avg: in the program but not in the data"));
+    Ok(())
+}
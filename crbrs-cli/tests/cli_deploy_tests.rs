@@ -0,0 +1,57 @@
+// FILE: crbrs-cli/tests/cli_deploy_tests.rs
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
+    Ok(cmd)
+}
+
+fn program(version: &str, fields: &str) -> String {
+    format!(
+        "' --- Version ---\n' Version: {v}\n' Date: 2026-10-16\n' Author: Jane\n' Changelog:\n'   {v} | 2026-10-16 | Jane | x\n' ---\nConst ProgramVersion = \"{v}\"\nPublic AirT, RH\nDataTable(Hourly,True,-1)\n  DataInterval(0,60,Min,10)\n{fields}EndTable\nBeginProg\n  Scan(1,Sec,0,0)\n    CallTable Hourly\n  NextScan\nEndProg\n",
+        v = version,
+        fields = fields
+    )
+}
+
+#[test]
+fn test_deploy_check_allows_compatible_upgrade() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let deployed = temp_dir.path().join("deployed.cr1x");
+    let new = temp_dir.path().join("new.cr1x");
+    std::fs::write(&deployed, program("1.0.0", "  Average(1,AirT,FP2,False)\n"))?;
+    std::fs::write(&new, program("1.1.0", "  Average(1,AirT,FP2,False)\n"))?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("deploy").arg("check").arg(&new).arg("--against").arg(&deployed);
+    cmd.assert().success().stdout(predicate::str::contains("Version: 1.0.0 -> 1.1.0"));
+    Ok(())
+}
+
+#[test]
+fn test_deploy_check_blocks_regression_and_schema_break() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let deployed = temp_dir.path().join("deployed.cr1x");
+    let new = temp_dir.path().join("new.cr1x");
+    std::fs::write(&deployed, program("2.0.0", "  Average(1,AirT,FP2,False)\n  Sample(1,RH,FP2)\n"))?;
+    std::fs::write(&new, program("1.9.0", "  Average(1,AirT,FP2,False)\n"))?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("deploy").arg("check").arg(&new).arg("--against").arg(&deployed);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("logger runs version 2.0.0"))
+        .stderr(predicate::str::contains("removed RH"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("deploy").arg("check").arg(&new).arg("--against").arg(&deployed).arg("--force");
+    cmd.assert().success().stdout(predicate::str::contains("(forced)"));
+    Ok(())
+}
@@ -0,0 +1,91 @@
+// FILE: crbrs-cli/tests/cli_docgen_tests.rs
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+const MAIN_PROGRAM: &str = "' Met station at the north plot
+Include \"CPU:common.cr1x\"
+Const SCAN_SEC = 5
+Public AirT
+Units AirT = degC
+DataTable(Hourly,True,-1)
+  DataInterval(0,60,Min,10)
+  Average(1,AirT,FP2,False)
+EndTable
+BeginProg
+  Scan(SCAN_SEC,Sec,0,0)
+    Therm107(AirT,1,U1,U2,0,60,1,0)
+    CallTable Hourly
+  NextScan
+EndProg
+";
+
+fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
+    Ok(cmd)
+}
+
+#[test]
+fn test_docgen_markdown_for_project() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let project = temp_dir.path().join("project");
+    std::fs::create_dir_all(&project)?;
+    std::fs::write(project.join("station.cr1x"), MAIN_PROGRAM)?;
+    std::fs::write(project.join("common.cr1x"), "Sub Noop\nEndSub\n")?;
+    let out_dir = temp_dir.path().join("site");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("docgen").arg(&project).arg("--output").arg(&out_dir);
+    cmd.assert().success().stdout(predicate::str::contains("Wrote 3 page(s)"));
+
+    let index = std::fs::read_to_string(out_dir.join("index.md"))?;
+    assert!(index.contains("[station.cr1x](./station_cr1x.md)"));
+    assert!(index.contains("station_cr1x[\"station.cr1x\"] --> common_cr1x[\"common.cr1x\"]"));
+
+    let page = std::fs::read_to_string(out_dir.join("station_cr1x.md"))?;
+    assert!(page.contains("Met station at the north plot"));
+    assert!(page.contains("| AirT_Avg | degC | Avg | FP2 |"));
+    assert!(page.contains("| SCAN_SEC | 5 | 3 |"));
+    assert!(page.contains("| U1 | SEChan | Therm107 | AirT | 1 | 12 |"));
+    Ok(())
+}
+
+#[test]
+fn test_docgen_html_single_file() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, MAIN_PROGRAM)?;
+    let out_dir = temp_dir.path().join("site");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("docgen").arg(&program).arg("-o").arg(&out_dir).arg("--format").arg("html");
+    cmd.assert().success();
+
+    let page = std::fs::read_to_string(out_dir.join("station_cr1x.html"))?;
+    assert!(page.contains("<h3>Hourly</h3>"));
+    assert!(page.contains("<td>AirT_Avg</td>"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_docgen_skips_symlinked_directories() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let project = temp_dir.path().join("project");
+    std::fs::create_dir_all(&project)?;
+    std::fs::write(project.join("station.cr1x"), MAIN_PROGRAM)?;
+    std::fs::write(project.join("common.cr1x"), "Sub Noop\nEndSub\n")?;
+    // A link back up the tree would otherwise be walked forever.
+    std::os::unix::fs::symlink(&project, project.join("loop"))?;
+    let out_dir = temp_dir.path().join("site");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("docgen").arg(&project).arg("--output").arg(&out_dir);
+    assert_cmd::Command::from_std(cmd).timeout(std::time::Duration::from_secs(30)).assert().success().stdout(predicate::str::contains("Wrote 3 page(s)"));
+    Ok(())
+}
@@ -0,0 +1,346 @@
+// FILE: crbrs-cli/tests/cli_logger_tests.rs
+
+use assert_cmd::prelude::*;
+use crbrs_lib::pakbus::Value;
+use crbrs_mock_logger::{MockLogger, RunningLogger};
+use predicates::prelude::*;
+use std::net::TcpListener;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
+    Ok(cmd)
+}
+
+/// A logger with a low battery and skipped scans, and without a lithium battery, that asks
+/// for 5 s to compile programs.
+fn status_logger() -> Result<RunningLogger, Box<dyn std::error::Error>> {
+    Ok(MockLogger::new()
+        .with_status()
+        .running("CPU:soil.cr1x", "BeginProg\nEndProg\n")
+        .value("Status", "CompileResults", Value::Text("CPU:soil.cr1x -- Compiled in PipelineMode.".to_string()))
+        .value("Status", "Battery", Value::Number(11.25))
+        .value("Status", "SkippedScan", Value::Number(3.0))
+        .without("Status", "LithiumBattery")
+        .compile_hold_off(5)
+        .spawn()?)
+}
+
+#[test]
+fn test_logger_status_json() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let logger = status_logger()?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("status").arg(logger.address()).arg("--json");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(report["health"], "critical");
+    assert_eq!(report["status"]["battery"], 11.25);
+    assert_eq!(report["status"]["lithium_battery"], serde_json::Value::Null);
+    assert_eq!(report["status"]["skipped_scans"], 3);
+    let health: Vec<(&str, &str)> = report["checks"]
+        .as_array()
+        .expect("checks")
+        .iter()
+        .map(|c| (c["item"].as_str().unwrap_or_default(), c["health"].as_str().unwrap_or_default()))
+        .collect();
+    assert_eq!(
+        health,
+        [
+            ("Program", "ok"),
+            ("Battery", "critical"),
+            ("Panel temperature", "ok"),
+            ("Skipped scans", "warning"),
+            ("Watchdog errors", "ok")
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_logger_status_registered_station() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let logger = status_logger()?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("stations.North.address").arg(logger.address());
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("stations.North.address").arg("somewhere");
+    cmd.assert().failure().stderr(predicate::str::contains("is not a logger address"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("status").arg("north");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("north: CR1000X.Std.06.02, running CPU:soil.cr1x"))
+        .stdout(predicate::str::contains("❌ Battery: 11.25 V (below 11.5 V)"))
+        .stdout(predicate::str::contains("⚠️ Skipped scans: 3 (more than 0; the scan interval may be too short)"))
+        .stdout(predicate::str::contains("✅ Panel temperature: 21.5 °C"))
+        .stdout(predicate::str::contains("Lithium").not());
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("status").arg("South");
+    cmd.assert().failure().stderr(predicate::str::contains("neither a station in [stations]"));
+    Ok(())
+}
+
+#[test]
+fn test_fleet_status_alerts() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let logger = status_logger()?;
+    let closed_port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let set = |key: &str, value: &str| -> Result<(), Box<dyn std::error::Error>> {
+        crbrs_cmd_isolated(&temp_dir)?.arg("config").arg("set").arg(key).arg(value).assert().success();
+        Ok(())
+    };
+
+    set("stations.North.address", &logger.address())?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("fleet").arg("status");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("❌ north: Battery 11.25 V (below 11.5 V); Skipped scans 3 (more than 0"))
+        .stderr(predicate::str::contains("2 alert(s) at 1 station(s)"));
+
+    set("stations.North.min_battery", "11")?;
+    set("stations.North.max_skipped_scans", "5")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("fleet").arg("status");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("✅ north: ok"))
+        .stdout(predicate::str::contains("All 1 stations are ok"));
+
+    set("stations.South.address", &format!("tcp:127.0.0.1:{}", closed_port))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("fleet").arg("status").arg("--json");
+    let output = cmd.assert().failure().stderr(predicate::str::contains("1 alert(s) at 1 station(s)")).get_output().stdout.clone();
+    let fleet: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(fleet["stations"].as_array().map(Vec::len), Some(2));
+    assert_eq!(fleet["stations"][0]["outcome"], "ok");
+    assert_eq!(fleet["stations"][0]["value"]["health"], "ok");
+    assert_eq!(fleet["stations"][1]["station"], "south");
+    assert_eq!(fleet["stations"][1]["outcome"], "failed");
+    assert_eq!(fleet["alerts"][0]["station"], "south");
+    assert_eq!(fleet["alerts"][0]["item"], "Connection");
+    assert_eq!(fleet["alerts"][0]["health"], "critical");
+    Ok(())
+}
+
+#[test]
+fn test_fleet_status_timeout_and_fail_fast() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let logger = status_logger()?;
+    let silent = MockLogger::new().silent().spawn()?;
+    let set = |key: &str, value: &str| -> Result<(), Box<dyn std::error::Error>> {
+        crbrs_cmd_isolated(&temp_dir)?.arg("config").arg("set").arg(key).arg(value).assert().success();
+        Ok(())
+    };
+    set("stations.East.address", &silent.address())?;
+    set("stations.North.address", &logger.address())?;
+    set("stations.North.min_battery", "11")?;
+    set("stations.North.max_skipped_scans", "5")?;
+
+    let started = std::time::Instant::now();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("fleet").arg("status").arg("--timeout").arg("1").arg("--json");
+    let output = cmd.assert().failure().stderr(predicate::str::contains("1 alert(s) at 1 station(s)")).get_output().stdout.clone();
+    assert!(started.elapsed() < std::time::Duration::from_secs(10), "the silent station held up the fleet");
+    let fleet: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(fleet["stations"][0]["station"], "east");
+    assert_eq!(fleet["stations"][0]["outcome"], "timed_out");
+    assert_eq!(fleet["stations"][1]["outcome"], "ok");
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("fleet").arg("status").arg("--timeout").arg("1").arg("--jobs").arg("1").arg("--fail-fast");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("❌ east: Connection: no result within 1 s"))
+        .stdout(predicate::str::contains("⏭️ north: skipped"))
+        .stderr(predicate::str::contains("1 alert(s) at 1 station(s), 1 station(s) skipped"));
+    Ok(())
+}
+
+#[test]
+fn test_logger_send_recorded_in_history() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let logger = status_logger()?;
+    let closed_port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let program = temp_dir.path().join("soil.cr1x");
+    let body = "'Soil moisture logger\n".repeat(100);
+    std::fs::write(
+        &program,
+        format!(
+            "' --- Version ---\n' Version: 1.4.0\n' Date: 2026-10-01\n' Author: Jane\n' ---\nConst ProgramVersion = \"1.4.0\"\n{}BeginProg\nEndProg\n",
+            body
+        ),
+    )?;
+    crbrs_cmd_isolated(&temp_dir)?
+        .arg("config")
+        .arg("set")
+        .arg("stations.North.address")
+        .arg(logger.address())
+        .assert()
+        .success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("history").arg("stations");
+    cmd.assert().success().stdout(predicate::str::contains("No recorded sessions"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("send").arg("North").arg(&program);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Sent CPU:soil.cr1x to north"))
+        .stdout(predicate::str::contains("may not answer for 5 s"));
+    assert_eq!(logger.files().get("CPU:soil.cr1x"), Some(&std::fs::read(&program)?));
+    assert_eq!(logger.running_program().as_deref(), Some("CPU:soil.cr1x"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("send").arg(format!("tcp:127.0.0.1:{}", closed_port)).arg(&program).arg("--no-run");
+    cmd.assert().failure().stderr(predicate::str::contains("cannot open"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("history").arg("stations").arg("--json");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let sessions: serde_json::Value = serde_json::from_slice(&output)?;
+    let sessions = sessions.as_array().expect("sessions");
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0]["station"], "north");
+    assert_eq!(sessions[0]["operation"], "send");
+    assert_eq!(sessions[0]["program_version"], "1.4.0");
+    assert_eq!(sessions[0]["error"], serde_json::Value::Null);
+    let size = std::fs::metadata(&program)?.len();
+    assert!(sessions[0]["bytes_sent"].as_u64().unwrap_or_default() > size, "the program went over the link in fragments");
+    assert!(sessions[0]["bytes_received"].as_u64().unwrap_or_default() > 0);
+    assert!(sessions[1]["error"].as_str().unwrap_or_default().contains("cannot open"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("history").arg("stations").arg("NORTH");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("north send soil.cr1x v1.4.0"))
+        .stdout(predicate::str::contains(": ok"))
+        .stdout(predicate::str::contains("cannot open").not());
+    Ok(())
+}
+
+/// A versioned program with an hourly table of `fields`.
+fn versioned(version: &str, fields: &str) -> String {
+    format!(
+        "' --- Version ---\n' Version: {v}\n' Date: 2026-10-16\n' Author: Jane\n' ---\nConst ProgramVersion = \"{v}\"\nPublic AirT, RH\n\
+         DataTable(Hourly,True,-1)\n  DataInterval(0,60,Min,10)\n{fields}EndTable\nBeginProg\n  Scan(1,Sec,0,0)\n    CallTable Hourly\n  NextScan\nEndProg\n",
+        v = version,
+        fields = fields
+    )
+}
+
+#[test]
+fn test_logger_send_checks_the_running_program_first() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let deployed = versioned("2.0.0", "  Average(1,AirT,FP2,False)\n  Sample(1,RH,FP2)\n");
+    let logger = MockLogger::new().with_status().running("CPU:met.cr1x", &deployed).spawn()?;
+    let program = temp_dir.path().join("met.cr1x");
+
+    // An older version that drops a field: blocked, and without a terminal it is not sent.
+    std::fs::write(&program, versioned("1.0.0", "  Average(1,AirT,FP2,False)\n"))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("send").arg(logger.address()).arg(&program);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Deploy blocked").and(predicate::str::contains("logger runs version 2.0.0")));
+    assert_eq!(logger.files().get("CPU:met.cr1x"), Some(&deployed.clone().into_bytes()));
+    assert!(logger.requests().contains(&crbrs_lib::pakbus::FILE_RECEIVE));
+    assert!(!logger.requests().contains(&crbrs_lib::pakbus::FILE_SEND));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("send").arg(logger.address()).arg(&program).arg("--force");
+    cmd.assert().success().stdout(predicate::str::contains("Sent CPU:met.cr1x"));
+    assert_eq!(logger.files().get("CPU:met.cr1x"), Some(&std::fs::read(&program)?));
+
+    // A compatible upgrade of what now runs goes through.
+    std::fs::write(&program, versioned("1.1.0", "  Average(1,AirT,FP2,False)\n"))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("send").arg(logger.address()).arg(&program);
+    cmd.assert().success();
+    assert_eq!(logger.files().get("CPU:met.cr1x"), Some(&std::fs::read(&program)?));
+
+    // A program that has a checksum sidecar must match it.
+    std::fs::write(temp_dir.path().join("met.cr1x.sha256"), format!("{}  met.cr1x\n", "0".repeat(64)))?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("send").arg(logger.address()).arg(&program).arg("--force");
+    cmd.assert().failure().stderr(predicate::str::contains("does not match its checksum"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("artifact").arg("seal").arg(&program);
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("send").arg(logger.address()).arg(&program).arg("--force");
+    cmd.assert().success();
+    std::fs::remove_file(temp_dir.path().join("met.cr1x.sha256"))?;
+
+    // A running program that cannot be read back blocks the send too.
+    let unreadable = MockLogger::new().with_status().value("Status", "ProgName", Value::Text("CPU:gone.cr1x".to_string())).spawn()?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("send").arg(unreadable.address()).arg(&program);
+    cmd.assert().failure().stderr(predicate::str::contains("cannot read back the running program CPU:gone.cr1x"));
+    assert!(unreadable.files().is_empty());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_logger_status_detects_and_saves_baud() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let logger = MockLogger::new().with_status().spawn_pty(9_600)?;
+    let port = logger.serial_port().expect("a pseudo-terminal");
+    crbrs_cmd_isolated(&temp_dir)?
+        .arg("config")
+        .arg("set")
+        .arg("stations.Field.address")
+        .arg(format!("serial:{}@auto", port))
+        .assert()
+        .success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("status").arg("field");
+    cmd.assert().success().stdout(predicate::str::contains("field: CR1000X.Std.06.02"));
+
+    let config = std::fs::read_to_string(temp_dir.path().join("config").join("crbrs").join("config.toml"))?;
+    assert!(config.contains(&format!("address = \"serial:{}@9600\"", port)), "{}", config);
+    Ok(())
+}
+
+#[test]
+fn test_security_code_from_a_secret() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let logger = MockLogger::new().with_status().security_code(1234).spawn()?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("stations.north.address").arg(logger.address());
+    cmd.assert().success();
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("stations.north.security_code_secret").arg("north-code");
+    cmd.assert().success();
+
+    // The environment stands in for the keychain, which tests must not touch.
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.env("CRBRS_SECRET_NORTH_CODE", "1234").arg("logger").arg("status").arg("north").arg("--json");
+    cmd.assert().success();
+    let config = std::fs::read_to_string(temp_dir.path().join("config").join("crbrs").join("config.toml"))?;
+    assert!(!config.contains("1234"), "{}", config);
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.env("CRBRS_SECRET_NORTH_CODE", "4321").arg("logger").arg("status").arg("north");
+    cmd.assert().failure().stderr(predicate::str::contains("permission denied"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("secret").arg("get").arg("north code");
+    cmd.assert().failure().stderr(predicate::str::contains("'north code' is not a secret name"));
+    Ok(())
+}
@@ -0,0 +1,117 @@
+// FILE: crbrs-cli/tests/cli_logs_tests.rs
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
+    Ok(cmd)
+}
+
+#[test]
+fn test_logs_lists_and_searches_sessions() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let sessions = temp_dir.path().join("data").join("crbrs").join("sessions");
+    std::fs::create_dir_all(&sessions)?;
+    std::fs::write(
+        sessions.join("terminal-ttyUSB0-20261016-083000-0.log"),
+        "2026-10-16T08:30:00.000Z > 1\n2026-10-16T08:30:01.000Z < CR1000X Std.06\n",
+    )?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logs");
+    cmd.assert().success().stdout(predicate::str::contains("terminal-ttyUSB0-20261016-083000-0.log"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logs").arg("--grep").arg("cr1000x");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("< CR1000X Std.06"))
+        .stdout(predicate::str::contains("> 1").not());
+    Ok(())
+}
+
+/// A logger in terminal mode on a local TCP port: answers a bare carriage return with the
+/// prompt and `1` with the status.
+fn terminal_logger() -> Result<u16, Box<dyn std::error::Error>> {
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { return };
+            let mut command = Vec::new();
+            let mut byte = [0u8; 1];
+            while let Ok(1) = stream.read(&mut byte) {
+                if byte[0] != b'\r' {
+                    command.push(byte[0]);
+                    continue;
+                }
+                let answer = match command.as_slice() {
+                    b"" => "\r\nCR1000X>",
+                    b"1" => "1\r\nCR1000X Std.06\r\nCR1000X>",
+                    _ => "\r\nUnknown command\r\nCR1000X>",
+                };
+                command.clear();
+                if stream.write_all(answer.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    Ok(port)
+}
+
+#[test]
+fn test_logger_terminal_writes_a_session_log() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let address = format!("tcp:127.0.0.1:{}", terminal_logger()?);
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("terminal").arg(&address);
+    assert_cmd::Command::from_std(cmd)
+        .write_stdin("1\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Logging this session to"))
+        .stdout(predicate::str::contains("CR1000X Std.06"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logs").arg("--grep").arg("tcp:127.0.0.1");
+    cmd.assert().success().stdout(predicate::str::contains("# terminal session with"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logs").arg("--grep").arg("CR1000X");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("< CR1000X Std.06"))
+        .stdout(predicate::str::contains("< CR1000X>"));
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logs").arg("--grep").arg("> 1");
+    cmd.assert().success().stdout(predicate::str::contains("> 1"));
+    Ok(())
+}
+
+#[test]
+fn test_logger_terminal_without_session_log() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let address = format!("tcp:127.0.0.1:{}", terminal_logger()?);
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("config").arg("set").arg("session_log.enabled").arg("false");
+    cmd.assert().success();
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("logger").arg("terminal").arg(&address);
+    assert_cmd::Command::from_std(cmd)
+        .write_stdin("1\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("CR1000X Std.06"))
+        .stdout(predicate::str::contains("Logging this session to").not());
+    assert!(!temp_dir.path().join("data").join("crbrs").join("sessions").exists());
+    Ok(())
+}
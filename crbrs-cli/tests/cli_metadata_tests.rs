@@ -0,0 +1,145 @@
+// FILE: crbrs-cli/tests/cli_metadata_tests.rs
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+const PROGRAM: &str = "Public AirT, BattV
+Units AirT = degC
+Units BattV = V
+DataTable(Hourly,True,-1)
+  DataInterval(0,60,Min,10)
+  Average(1,AirT,FP2,False)
+  Minimum(1,BattV,FP2,False,False)
+EndTable
+BeginProg
+  Scan(1,Sec,0,0)
+    CallTable Hourly
+  NextScan
+EndProg
+";
+
+fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
+    Ok(cmd)
+}
+
+#[test]
+fn test_metadata_export_csv() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, PROGRAM)?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("metadata").arg("export").arg(&program);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("table,field,units,processing,data_type,interval,source,description\n"))
+        .stdout(predicate::str::contains("Hourly,AirT_Avg,degC,Avg,FP2,60 Min,AirT,"))
+        .stdout(predicate::str::contains("Hourly,BattV_Min,V,Min,FP2,60 Min,BattV,"));
+    Ok(())
+}
+
+#[test]
+fn test_metadata_export_eml_to_file() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let program = temp_dir.path().join("station.cr1x");
+    let output = temp_dir.path().join("station.eml.xml");
+    std::fs::write(&program, PROGRAM)?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("metadata").arg("export").arg(&program).arg("--format").arg("eml").arg("-o").arg(&output);
+    cmd.arg("--creator").arg("Soil Lab").arg("--contact").arg("Field Station");
+    cmd.assert().success();
+
+    let eml = std::fs::read_to_string(&output)?;
+    assert!(eml.contains("<entityName>Hourly</entityName>"));
+    assert!(eml.contains("<attributeName>AirT_Avg</attributeName>"));
+    assert!(eml.contains("<customUnit>degC</customUnit>"));
+    Ok(())
+}
+
+#[test]
+fn test_metadata_export_eml_has_the_required_elements() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(
+        &program,
+        "Public AirT, Rad, Msg As String * 16, Door As Boolean, Count As Long\nUnits AirT = degC\nUnits Rad = W/m^2\n\
+         DataTable(Hourly,True,-1)\n  DataInterval(0,60,Min,10)\n  Average(1,AirT,FP2,False)\n  Average(1,Rad,FP2,False)\n  \
+         Sample(1,Msg,String)\n  Sample(1,Door,Boolean)\n  Sample(1,Count,Long)\nEndTable\n\
+         BeginProg\n  Scan(1,Sec,0,0)\n    CallTable Hourly\n  NextScan\nEndProg\n",
+    )?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("metadata").arg("export").arg(&program).arg("--format").arg("eml").arg("--creator").arg("Soil Lab");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let eml = String::from_utf8(output)?;
+    let attribute = |name: &str| -> String {
+        let start = eml.find(&format!("<attributeName>{}</attributeName>", name)).expect("attribute is described");
+        eml[start..start + eml[start..].find("</attribute>").expect("attribute ends")].to_string()
+    };
+
+    // A dataset needs a title, a creator and a contact; a missing one gets a placeholder.
+    assert!(eml.contains("<title>Data tables of station.cr1x</title>"));
+    assert!(eml.contains("<creator>\n      <organizationName>Soil Lab</organizationName>\n    </creator>"), "{}", eml);
+    assert!(eml.contains("<contact>\n      <positionName>Not specified (set with --contact)</positionName>\n    </contact>"), "{}", eml);
+    // Every custom unit is declared.
+    assert!(attribute("AirT_Avg").contains("<customUnit>degC</customUnit>"));
+    assert!(attribute("Rad_Avg").contains("<customUnit>W_m_2</customUnit>"));
+    assert!(eml.contains("<stmml:unitList>"));
+    assert!(eml.contains("<stmml:unit id=\"degC\" name=\"degC\"/>"));
+    assert!(eml.contains("<stmml:unit id=\"W_m_2\" name=\"W/m^2\"/>"));
+    // Only numbers are ratios.
+    assert!(attribute("RECORD").contains("<standardUnit>dimensionless</standardUnit>"));
+    assert!(attribute("Count").contains("<numberType>integer</numberType>"));
+    assert!(attribute("Msg").contains("<nominal><nonNumericDomain><textDomain>"));
+    assert!(attribute("Door").contains("<nominal><nonNumericDomain><textDomain>"));
+    assert!(!attribute("Door").contains("<ratio>"));
+    Ok(())
+}
+
+#[test]
+fn test_metadata_export_rejects_unknown_format() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("metadata").arg("export").arg("x.cr1x").arg("--format").arg("yaml");
+    cmd.assert().failure().stderr(predicate::str::contains("unknown metadata format"));
+    Ok(())
+}
+
+#[test]
+fn test_metadata_check_finds_colliding_tables() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let project = temp_dir.path().join("project");
+    std::fs::create_dir_all(&project)?;
+    std::fs::write(project.join("crbrs.toml"), "")?;
+    std::fs::write(project.join("north.cr1x"), PROGRAM)?;
+    std::fs::write(
+        project.join("south.cr1x"),
+        "Public AirT, BattV\nUnits AirT = degC\nDataTable(HOURLY,True,-1)\n  DataInterval(0,60,Min,10)\n  Average(1,AirT,IEEE4,False)\n  \
+         Sample(1,BattV,FP2)\n  FieldNames(\"AirT_Avg\")\nEndTable\nBeginProg\n  Scan(1,Sec,0,0)\n    CallTable HOURLY\n  NextScan\nEndProg\n",
+    )?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("metadata").arg("check").arg(project.join("north.cr1x"));
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("south.cr1x:3: table 'HOURLY' differs only in case from 'Hourly' in north.cr1x line 4"))
+        .stdout(predicate::str::contains("south.cr1x:3: table 'HOURLY' has other fields than in north.cr1x line 4: missing BattV_Min"))
+        .stdout(predicate::str::contains("south.cr1x:5: field 'AirT_Avg' of table 'HOURLY' has type IEEE4 here but FP2 in north.cr1x line 6"))
+        .stdout(predicate::str::contains("south.cr1x:6: table 'HOURLY' stores field 'AirT_Avg' twice; it is first stored on line 5"))
+        .stdout(predicate::str::contains("south.cr1x:6: field 'AirT_Avg' of table 'HOURLY' has processing Smp here but Avg"))
+        .stderr(predicate::str::contains("Tables collide in 5 place(s)").and(predicate::str::contains("E042")));
+
+    // Tables the database does not ingest may differ; the stored index is reused.
+    std::fs::write(project.join("crbrs.toml"), "[metadata]\nignore_tables = [\"hourly\"]\n")?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("metadata").arg("check").arg(&project);
+    cmd.assert().success().stdout(predicate::str::contains("do not collide"));
+    Ok(())
+}
@@ -0,0 +1,72 @@
+// FILE: crbrs-cli/tests/cli_sensor_tests.rs
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
+    Ok(cmd)
+}
+
+const PROGRAM: &str = "Public BattV\n\nDataTable(Hourly, True, -1)\n  DataInterval(0, 60, Min, 10)\n  Minimum(1, BattV, FP2, False, False)\nEndTable\n\nBeginProg\n  Scan(10, Sec, 0, 0)\n    Battery(BattV)\n    CallTable Hourly\n  NextScan\nEndProg\n";
+
+#[test]
+fn test_sensor_add_sdi12_model() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, PROGRAM)?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("sensor").arg("add").arg("sdi12").arg(&program).arg("--address").arg("0").arg("--model").arg("CS655");
+    cmd.assert().success().stdout(predicate::str::contains("Added sensor CS655_0"));
+
+    let source = std::fs::read_to_string(&program)?;
+    assert!(source.contains("Public CS655_0(3)\n"), "{}", source);
+    assert!(source.contains("Units CS655_0_VWC = m^3/m^3\n"), "{}", source);
+    assert!(source.contains("  Average(3, CS655_0(), FP2, False)\nEndTable\n"), "{}", source);
+    assert!(source.contains("    SDI12Recorder(CS655_0(), C1, \"0\", \"M!\", 1.0, 0)\n    CallTable Hourly\n"), "{}", source);
+    Ok(())
+}
+
+#[test]
+fn test_sensor_add_rejects_taken_name() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, PROGRAM)?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("sensor").arg("add").arg("modbus").arg(&program).arg("--values").arg("Flow:L/s").arg("--name").arg("BattV");
+    cmd.assert().failure().stderr(predicate::str::contains("'BattV' is already declared"));
+
+    assert_eq!(std::fs::read_to_string(&program)?, PROGRAM);
+    Ok(())
+}
+
+#[test]
+fn test_sensor_import_modbus_register_map() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let map = temp_dir.path().join("meter.csv");
+    std::fs::write(&map, "Address,Type,Scaling,Name,Units\n40001,float,,Voltage,V\n40003,float,,Current,A\n40011,int16,0.1,Freq,Hz\n")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("sensor").arg("import-modbus").arg(&map).arg("--address").arg("2");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Public Modbus_2_Result(2) As Long\n"))
+        .stdout(predicate::str::contains("ModbusMaster(Modbus_2_Result(2), ComC1, 9600, 2, 3, Modbus_2(3), 11, 1, 3, 100, 1)\n"))
+        .stdout(predicate::str::contains("  Modbus_2_Freq = Modbus_2_Freq * 0.1\n"));
+
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, PROGRAM)?;
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("sensor").arg("import-modbus").arg(&map).arg("--into").arg(&program).arg("--name").arg("Meter");
+    cmd.assert().success().stdout(predicate::str::contains("Added sensor Meter"));
+    let source = std::fs::read_to_string(&program)?;
+    assert!(source.contains("    ModbusMaster(Meter_Result(1), ComC1, 9600, 1, 3, Meter(1), 1, 2, 3, 100, 0)\n"), "{}", source);
+    Ok(())
+}
@@ -0,0 +1,36 @@
+// FILE: crbrs-cli/tests/cli_version_tests.rs
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn crbrs_cmd_isolated(temp_dir: &TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("crbrs")?;
+    cmd.env("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.env("CRBRS_LANG", "en-US"); // Assertions match the English messages
+    Ok(cmd)
+}
+
+#[test]
+fn test_version_bump_creates_and_increments_header() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let program = temp_dir.path().join("station.cr1x");
+    std::fs::write(&program, "' Station program\nPublic T\nBeginProg\nEndProg\n")?;
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("version").arg("bump").arg(&program).arg("-m").arg("Initial").arg("--author").arg("Jane");
+    cmd.assert().success().stdout(predicate::str::contains("version 1.0.0"));
+
+    let mut cmd = crbrs_cmd_isolated(&temp_dir)?;
+    cmd.arg("version").arg("bump").arg(&program).arg("minor").arg("-m").arg("Add sensor").arg("--author").arg("Jane");
+    cmd.assert().success().stdout(predicate::str::contains("version 1.1.0"));
+
+    let source = std::fs::read_to_string(&program)?;
+    assert!(source.contains("' Version: 1.1.0\n"));
+    assert!(source.contains("'   1.1.0 | "));
+    assert!(source.contains("| Jane | Add sensor\n'   1.0.0 | "));
+    assert!(source.contains("Const ProgramVersion = \"1.1.0\"\n"));
+    Ok(())
+}
@@ -0,0 +1,29 @@
+// FILE: crbrs-ffi/build.rs
+
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set by cargo"));
+    let lib_rs = crate_dir.join("src").join("lib.rs");
+    let config_path = crate_dir.join("cbindgen.toml");
+    // Generated into OUT_DIR, never into the source tree; a test compares it with the
+    // checked-in include/crbrs.h (and updates that with CRBRS_BLESS=1).
+    let header_path = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR not set by cargo")).join("crbrs.h");
+
+    println!("cargo:rerun-if-changed={}", lib_rs.display());
+    println!("cargo:rerun-if-changed={}", config_path.display());
+
+    let config = cbindgen::Config::from_file(&config_path).unwrap_or_default();
+    // Parse only our own source file; avoids shelling out to `cargo metadata` from a build script.
+    match cbindgen::Builder::new().with_config(config).with_src(&lib_rs).generate() {
+        Ok(bindings) => {
+            bindings.write_to_file(&header_path);
+            println!("cargo:rustc-env=CRBRS_GENERATED_HEADER={}", header_path.display());
+        }
+        Err(e) => {
+            // Don't fail the Rust build over the header; the checked-in copy is still usable.
+            println!("cargo:warning=Could not generate the C header: {}", e);
+        }
+    }
+}
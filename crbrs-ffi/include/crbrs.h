@@ -0,0 +1,191 @@
+#ifndef CRBRS_H
+#define CRBRS_H
+
+/* Generated with cbindgen:0.29.4 */
+
+/* Generated by cbindgen from crbrs-ffi/src/lib.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result code returned by every fallible `crbrs_*` function.
+ */
+typedef enum CrbrsStatus {
+  CRBRS_STATUS_OK = 0,
+  CRBRS_STATUS_INVALID_ARGUMENT = 1,
+  CRBRS_STATUS_COMPILATION_FAILED = 2,
+  CRBRS_STATUS_COMPILER_NOT_FOUND = 3,
+  CRBRS_STATUS_NO_COMPILER_FOR_EXTENSION = 4,
+  CRBRS_STATUS_WINE_NOT_FOUND = 5,
+  CRBRS_STATUS_NETWORK = 6,
+  CRBRS_STATUS_IO = 7,
+  CRBRS_STATUS_CONFIG = 8,
+  CRBRS_STATUS_CHECKSUM_MISMATCH = 9,
+  CRBRS_STATUS_PANIC = 10,
+  CRBRS_STATUS_OTHER = 11,
+  CRBRS_STATUS_CANCELLED = 12,
+  /**
+   * The compiler ran longer than the `compile_timeout_secs` setting and was stopped.
+   */
+  CRBRS_STATUS_TIMEOUT = 13,
+} CrbrsStatus;
+
+/**
+ * Opaque handle to a loaded `Settings` value. Create with [`crbrs_settings_load`] or
+ * [`crbrs_settings_default`], release with [`crbrs_settings_free`].
+ */
+typedef struct CrbrsSettings CrbrsSettings;
+
+/**
+ * A single compiler diagnostic, passed to a [`CrbrsDiagnosticCallback`].
+ */
+typedef struct CrbrsDiagnostic {
+  /**
+   * File name as reported by the compiler (may be empty).
+   */
+  const char *file;
+  /**
+   * 1-based line number, or 0 if the compiler did not report one.
+   */
+  uint32_t line;
+  const char *message;
+} CrbrsDiagnostic;
+
+typedef void (*CrbrsDiagnosticCallback)(const struct CrbrsDiagnostic *diagnostic, void *user_data);
+
+/**
+ * A compiler entry, passed to a [`CrbrsCompilerCallback`] when listing compilers.
+ */
+typedef struct CrbrsCompilerEntry {
+  const char *id;
+  const char *version;
+  const char *description;
+  /**
+   * True if the compiler is installed locally.
+   */
+  bool installed;
+} CrbrsCompilerEntry;
+
+typedef void (*CrbrsCompilerCallback)(const struct CrbrsCompilerEntry *entry, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns the crbrs version as a static NUL-terminated string.
+ */
+const char *crbrs_version(void);
+
+/**
+ * Returns the message of the last error raised on this thread, or null if there is none.
+ * The pointer stays valid until the next failing `crbrs_*` call on the same thread.
+ */
+const char *crbrs_last_error(void);
+
+/**
+ * Cancels the downloads, compiles and installs running in this process, from any thread.
+ * They stop with `CRBRS_STATUS_CANCELLED`, and so do later ones until [`crbrs_reset_cancel`].
+ */
+void crbrs_cancel(void);
+
+/**
+ * Lets operations run again after [`crbrs_cancel`].
+ */
+void crbrs_reset_cancel(void);
+
+/**
+ * Loads settings from the user's `config.toml`. Returns null on failure (see [`crbrs_last_error`]).
+ */
+struct CrbrsSettings *crbrs_settings_load(void);
+
+/**
+ * Creates a settings handle holding the built-in defaults, without touching the config file.
+ */
+struct CrbrsSettings *crbrs_settings_default(void);
+
+/**
+ * Releases a settings handle. Passing null is a no-op.
+ *
+ * # Safety
+ * `settings` must be null or a handle returned by this library that has not been freed yet.
+ */
+void crbrs_settings_free(struct CrbrsSettings *settings);
+
+/**
+ * Compiles `input_file`, reporting each parsed compiler error through `on_diagnostic`.
+ *
+ * `compiler_id` may be null to resolve the compiler from the file extension association.
+ * Returns [`CrbrsStatus::CompilationFailed`] when the compiler rejected the program.
+ *
+ * # Safety
+ * `settings` must be a valid handle; string arguments must be null or valid NUL-terminated
+ * UTF-8 strings. `user_data` is passed through untouched.
+ */
+enum CrbrsStatus crbrs_compile(const struct CrbrsSettings *settings,
+                               const char *input_file,
+                               const char *compiler_id,
+                               CrbrsDiagnosticCallback on_diagnostic,
+                               void *user_data);
+
+/**
+ * Like [`crbrs_compile`], with files given in memory: `files_json` is a JSON object mapping
+ * file names (relative paths) to their text, e.g. generated includes. They are written to a
+ * temporary directory, removed afterwards, where `input_file` is compiled: one of the given
+ * names, or a program on disk, copied in with the files it includes that were not given.
+ *
+ * # Safety
+ * As for [`crbrs_compile`]; `files_json` must be a valid NUL-terminated UTF-8 string.
+ */
+enum CrbrsStatus crbrs_compile_with_files(const struct CrbrsSettings *settings,
+                                          const char *input_file,
+                                          const char *files_json,
+                                          const char *compiler_id,
+                                          CrbrsDiagnosticCallback on_diagnostic,
+                                          void *user_data);
+
+/**
+ * Calls `callback` once for every locally installed compiler, sorted by ID.
+ *
+ * # Safety
+ * `settings` must be a valid handle. `user_data` is passed through untouched.
+ */
+enum CrbrsStatus crbrs_list_installed(const struct CrbrsSettings *settings,
+                                      CrbrsCompilerCallback callback,
+                                      void *user_data);
+
+/**
+ * Fetches the remote manifest and calls `callback` once for every available compiler, sorted by ID.
+ *
+ * # Safety
+ * `settings` must be a valid handle. `user_data` is passed through untouched.
+ */
+enum CrbrsStatus crbrs_list_available(const struct CrbrsSettings *settings,
+                                      CrbrsCompilerCallback callback,
+                                      void *user_data);
+
+/**
+ * Downloads and installs a compiler from the manifest, updating and saving `settings`.
+ *
+ * # Safety
+ * `settings` must be a valid handle; `compiler_id` must be a valid NUL-terminated UTF-8 string.
+ */
+enum CrbrsStatus crbrs_install_compiler(struct CrbrsSettings *settings, const char *compiler_id);
+
+/**
+ * Removes an installed compiler, updating and saving `settings`.
+ *
+ * # Safety
+ * `settings` must be a valid handle; `compiler_id` must be a valid NUL-terminated UTF-8 string.
+ */
+enum CrbrsStatus crbrs_remove_compiler(struct CrbrsSettings *settings, const char *compiler_id);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CRBRS_H */
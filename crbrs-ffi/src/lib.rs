@@ -0,0 +1,440 @@
+// FILE: crbrs-ffi/src/lib.rs
+
+//! C ABI for `crbrs-lib`.
+//!
+//! This crate exposes a small, C-compatible surface over the library so non-Rust hosts
+//! (C, C# / .NET via P/Invoke, Python via ctypes, ...) can load settings, compile files and
+//! manage compilers. The matching header is checked in at `include/crbrs.h`; `build.rs`
+//! generates it with cbindgen, and a test fails until the checked-in copy matches (run
+//! `CRBRS_BLESS=1 cargo test -p crbrs-ffi` to update it).
+//!
+//! Conventions:
+//! * Every function returns a [`CrbrsStatus`] (or a nullable pointer) and never unwinds across
+//!   the FFI boundary; panics are caught and reported as [`CrbrsStatus::Panic`].
+//! * When a call fails, a human readable message, starting with the error's code (see
+//!   `crbrs explain`), is available from [`crbrs_last_error`] on the same thread until the
+//!   next failing call.
+//! * Strings passed to callbacks are only valid for the duration of the callback invocation.
+
+use crbrs_lib::{Error, Settings};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+// --- Public C types ---
+
+/// Result code returned by every fallible `crbrs_*` function.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrbrsStatus {
+    Ok = 0,
+    InvalidArgument = 1,
+    CompilationFailed = 2,
+    CompilerNotFound = 3,
+    NoCompilerForExtension = 4,
+    WineNotFound = 5,
+    Network = 6,
+    Io = 7,
+    Config = 8,
+    ChecksumMismatch = 9,
+    Panic = 10,
+    Other = 11,
+    Cancelled = 12,
+    /// The compiler ran longer than the `compile_timeout_secs` setting and was stopped.
+    Timeout = 13,
+}
+
+/// Opaque handle to a loaded `Settings` value. Create with [`crbrs_settings_load`] or
+/// [`crbrs_settings_default`], release with [`crbrs_settings_free`].
+pub struct CrbrsSettings {
+    inner: Settings,
+}
+
+/// A single compiler diagnostic, passed to a [`CrbrsDiagnosticCallback`].
+#[repr(C)]
+pub struct CrbrsDiagnostic {
+    /// File name as reported by the compiler (may be empty).
+    pub file: *const c_char,
+    /// 1-based line number, or 0 if the compiler did not report one.
+    pub line: u32,
+    pub message: *const c_char,
+}
+
+/// A compiler entry, passed to a [`CrbrsCompilerCallback`] when listing compilers.
+#[repr(C)]
+pub struct CrbrsCompilerEntry {
+    pub id: *const c_char,
+    pub version: *const c_char,
+    pub description: *const c_char,
+    /// True if the compiler is installed locally.
+    pub installed: bool,
+}
+
+pub type CrbrsDiagnosticCallback = Option<extern "C" fn(diagnostic: *const CrbrsDiagnostic, user_data: *mut c_void)>;
+pub type CrbrsCompilerCallback = Option<extern "C" fn(entry: *const CrbrsCompilerEntry, user_data: *mut c_void)>;
+
+// --- Error plumbing ---
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl Into<String>) {
+    // Interior NULs can't be represented in a C string; replace rather than drop the message.
+    let message = message.into().replace('\0', " ");
+    let c_message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(c_message));
+}
+
+fn status_for_error(error: &Error) -> CrbrsStatus {
+    match error {
+        Error::CompilationFailed { .. }
+        | Error::GenericCompilationFailedWithLog { .. }
+        | Error::CompilationFailedWithLog { .. }
+        | Error::BuildFailed { .. }
+        | Error::VerificationFailed { .. }
+        | Error::ImageNotProduced(_) => CrbrsStatus::CompilationFailed,
+        Error::CompilerNotFound(..)
+        | Error::CompilerIdNotFoundInManifest(..)
+        | Error::AssociatedCompilerNotInstalled { .. }
+        | Error::ProjectCompilersMissing { .. } => CrbrsStatus::CompilerNotFound,
+        Error::NoCompilerForExtension(_) | Error::InvalidExtension(_) => CrbrsStatus::NoCompilerForExtension,
+        Error::WineNotFound => CrbrsStatus::WineNotFound,
+        Error::Network(_) | Error::RateLimited { .. } | Error::Offline(_) | Error::InvalidCompilerSource(_) | Error::Logger(_) => CrbrsStatus::Network,
+        Error::Io(_) | Error::Subprocess(_) | Error::Zip(_) | Error::SettingsNotSaved { .. } => CrbrsStatus::Io,
+        Error::Config(_) | Error::DirectoryResolutionFailed | Error::InputRequired(_) | Error::Secret(_) => CrbrsStatus::Config,
+        Error::ChecksumMismatch { .. } | Error::Integrity(_) | Error::SignatureInvalid { .. } => CrbrsStatus::ChecksumMismatch,
+        Error::ImageNotSupported(_) | Error::DeployBlocked(_) | Error::Version(_) | Error::Metadata(_) | Error::Docgen(_) | Error::Format(_) => CrbrsStatus::Other,
+        Error::Remote(_) | Error::Provision(_) | Error::Sensor(_) | Error::Calibration(_) | Error::Data(_) | Error::Alert(_) => CrbrsStatus::Other,
+        Error::TableCollisions(_) => CrbrsStatus::Other,
+        Error::Cancelled => CrbrsStatus::Cancelled,
+        Error::CompilationTimeout { .. } => CrbrsStatus::Timeout,
+    }
+}
+
+/// Runs `f`, converting library errors and panics into a status code plus last-error message.
+fn guard<F>(f: F) -> CrbrsStatus
+where
+    F: FnOnce() -> Result<(), (CrbrsStatus, String)>,
+{
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => CrbrsStatus::Ok,
+        Ok(Err((status, message))) => {
+            set_last_error(message);
+            status
+        }
+        Err(_) => {
+            set_last_error("internal panic in crbrs");
+            CrbrsStatus::Panic
+        }
+    }
+}
+
+/// The message starts with the error's stable code, e.g. `[E007] Compiler ...`.
+fn lib_error(error: Error) -> (CrbrsStatus, String) {
+    (status_for_error(&error), format!("[{}] {}", error.code(), error))
+}
+
+fn invalid_argument(message: &str) -> (CrbrsStatus, String) {
+    (CrbrsStatus::InvalidArgument, message.to_string())
+}
+
+/// Reads a required, UTF-8 C string argument.
+///
+/// # Safety
+/// `ptr` must be null or point to a valid NUL-terminated string.
+unsafe fn required_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, (CrbrsStatus, String)> {
+    if ptr.is_null() {
+        return Err(invalid_argument(&format!("'{}' must not be null", name)));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| invalid_argument(&format!("'{}' is not valid UTF-8", name)))
+}
+
+/// Reads an optional, UTF-8 C string argument (null means "not provided").
+///
+/// # Safety
+/// `ptr` must be null or point to a valid NUL-terminated string.
+unsafe fn optional_str<'a>(ptr: *const c_char, name: &str) -> Result<Option<&'a str>, (CrbrsStatus, String)> {
+    if ptr.is_null() {
+        Ok(None)
+    } else {
+        required_str(ptr, name).map(Some)
+    }
+}
+
+fn to_cstring(s: &str) -> CString {
+    CString::new(s.replace('\0', " ")).unwrap_or_default()
+}
+
+fn emit_compiler(callback: extern "C" fn(*const CrbrsCompilerEntry, *mut c_void), user_data: *mut c_void, id: &str, version: &str, description: &str, installed: bool) {
+    let (id, version, description) = (to_cstring(id), to_cstring(version), to_cstring(description));
+    let entry = CrbrsCompilerEntry {
+        id: id.as_ptr(),
+        version: version.as_ptr(),
+        description: description.as_ptr(),
+        installed,
+    };
+    callback(&entry, user_data);
+}
+
+// --- Exported functions ---
+
+/// Returns the crbrs version as a static NUL-terminated string.
+#[no_mangle]
+pub extern "C" fn crbrs_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Returns the message of the last error raised on this thread, or null if there is none.
+/// The pointer stays valid until the next failing `crbrs_*` call on the same thread.
+#[no_mangle]
+pub extern "C" fn crbrs_last_error() -> *const c_char {
+    LAST_ERROR.with(|slot| slot.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Cancels the downloads, compiles and installs running in this process, from any thread.
+/// They stop with `CRBRS_STATUS_CANCELLED`, and so do later ones until [`crbrs_reset_cancel`].
+#[no_mangle]
+pub extern "C" fn crbrs_cancel() {
+    crbrs_lib::cancel::token().cancel();
+}
+
+/// Lets operations run again after [`crbrs_cancel`].
+#[no_mangle]
+pub extern "C" fn crbrs_reset_cancel() {
+    crbrs_lib::cancel::token().reset();
+}
+
+/// Loads settings from the user's `config.toml`. Returns null on failure (see [`crbrs_last_error`]).
+#[no_mangle]
+pub extern "C" fn crbrs_settings_load() -> *mut CrbrsSettings {
+    let mut loaded = None;
+    let status = guard(|| {
+        loaded = Some(crbrs_lib::config::load_settings().map_err(lib_error)?);
+        Ok(())
+    });
+    match (status, loaded) {
+        (CrbrsStatus::Ok, Some(inner)) => Box::into_raw(Box::new(CrbrsSettings { inner })),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Creates a settings handle holding the built-in defaults, without touching the config file.
+#[no_mangle]
+pub extern "C" fn crbrs_settings_default() -> *mut CrbrsSettings {
+    Box::into_raw(Box::new(CrbrsSettings { inner: Settings::default() }))
+}
+
+/// Releases a settings handle. Passing null is a no-op.
+///
+/// # Safety
+/// `settings` must be null or a handle returned by this library that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn crbrs_settings_free(settings: *mut CrbrsSettings) {
+    if !settings.is_null() {
+        drop(Box::from_raw(settings));
+    }
+}
+
+/// Compiles `input_file`, reporting each parsed compiler error through `on_diagnostic`.
+///
+/// `compiler_id` may be null to resolve the compiler from the file extension association.
+/// Returns [`CrbrsStatus::CompilationFailed`] when the compiler rejected the program.
+///
+/// # Safety
+/// `settings` must be a valid handle; string arguments must be null or valid NUL-terminated
+/// UTF-8 strings. `user_data` is passed through untouched.
+#[no_mangle]
+pub unsafe extern "C" fn crbrs_compile(
+    settings: *const CrbrsSettings,
+    input_file: *const c_char,
+    compiler_id: *const c_char,
+    on_diagnostic: CrbrsDiagnosticCallback,
+    user_data: *mut c_void,
+) -> CrbrsStatus {
+    guard(|| {
+        let settings = settings.as_ref().ok_or_else(|| invalid_argument("'settings' must not be null"))?;
+        let input_file = PathBuf::from(required_str(input_file, "input_file")?);
+        let compiler_id = optional_str(compiler_id, "compiler_id")?;
+
+        crbrs_lib::compiler::compile_file_impl(&input_file, None, compiler_id, &settings.inner)
+            .map_err(|e| report_compile_error(e, on_diagnostic, user_data))
+    })
+}
+
+/// Like [`crbrs_compile`], with files given in memory: `files_json` is a JSON object mapping
+/// file names (relative paths) to their text, e.g. generated includes. They are written to a
+/// temporary directory, removed afterwards, where `input_file` is compiled: one of the given
+/// names, or a program on disk, copied in with the files it includes that were not given.
+///
+/// # Safety
+/// As for [`crbrs_compile`]; `files_json` must be a valid NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn crbrs_compile_with_files(
+    settings: *const CrbrsSettings,
+    input_file: *const c_char,
+    files_json: *const c_char,
+    compiler_id: *const c_char,
+    on_diagnostic: CrbrsDiagnosticCallback,
+    user_data: *mut c_void,
+) -> CrbrsStatus {
+    guard(|| {
+        let settings = settings.as_ref().ok_or_else(|| invalid_argument("'settings' must not be null"))?;
+        let input_file = PathBuf::from(required_str(input_file, "input_file")?);
+        let files = crbrs_lib::sandbox::parse_files(required_str(files_json, "files_json")?)
+            .map_err(|e| invalid_argument(&e.to_string()))?;
+        let compiler_id = optional_str(compiler_id, "compiler_id")?;
+
+        crbrs_lib::sandbox::compile(&input_file, &files, compiler_id, &settings.inner)
+            .map_err(|e| report_compile_error(e, on_diagnostic, user_data))
+    })
+}
+
+/// Reports the compiler's errors in `e` through `on_diagnostic`, then converts it.
+unsafe fn report_compile_error(e: Error, on_diagnostic: CrbrsDiagnosticCallback, user_data: *mut c_void) -> (CrbrsStatus, String) {
+    if let Some(callback) = on_diagnostic {
+        match &e {
+            Error::CompilationFailed { errors, .. } => {
+                for detail in errors {
+                    let file = to_cstring(&detail.file_path_in_log);
+                    let message = to_cstring(detail.message.trim());
+                    let diagnostic = CrbrsDiagnostic {
+                        file: file.as_ptr(),
+                        line: detail.line.unwrap_or(0),
+                        message: message.as_ptr(),
+                    };
+                    callback(&diagnostic, user_data);
+                }
+            }
+            Error::GenericCompilationFailedWithLog { raw_log, .. } => {
+                // No structure available; hand the raw log over as a single line-less diagnostic.
+                let file = to_cstring("");
+                let message = to_cstring(raw_log.trim());
+                let diagnostic = CrbrsDiagnostic { file: file.as_ptr(), line: 0, message: message.as_ptr() };
+                callback(&diagnostic, user_data);
+            }
+            _ => {}
+        }
+    }
+    lib_error(e)
+}
+
+/// Calls `callback` once for every locally installed compiler, sorted by ID.
+///
+/// # Safety
+/// `settings` must be a valid handle. `user_data` is passed through untouched.
+#[no_mangle]
+pub unsafe extern "C" fn crbrs_list_installed(
+    settings: *const CrbrsSettings,
+    callback: CrbrsCompilerCallback,
+    user_data: *mut c_void,
+) -> CrbrsStatus {
+    guard(|| {
+        let settings = settings.as_ref().ok_or_else(|| invalid_argument("'settings' must not be null"))?;
+        let callback = callback.ok_or_else(|| invalid_argument("'callback' must not be null"))?;
+        let mut installed: Vec<_> = settings.inner.installed_compilers.values().collect();
+        installed.sort_by_key(|info| &info.id);
+        for info in installed {
+            emit_compiler(callback, user_data, &info.id, &info.version, &info.description, true);
+        }
+        Ok(())
+    })
+}
+
+/// Fetches the remote manifest and calls `callback` once for every available compiler, sorted by ID.
+///
+/// # Safety
+/// `settings` must be a valid handle. `user_data` is passed through untouched.
+#[no_mangle]
+pub unsafe extern "C" fn crbrs_list_available(
+    settings: *const CrbrsSettings,
+    callback: CrbrsCompilerCallback,
+    user_data: *mut c_void,
+) -> CrbrsStatus {
+    guard(|| {
+        let settings = settings.as_ref().ok_or_else(|| invalid_argument("'settings' must not be null"))?;
+        let callback = callback.ok_or_else(|| invalid_argument("'callback' must not be null"))?;
+        let manifest = crbrs_lib::installer::fetch_manifest(&settings.inner.compiler_repository_url, &settings.inner.network).map_err(lib_error)?;
+        let mut available: Vec<_> = manifest.compilers.iter().collect();
+        available.sort_by_key(|(id, _)| *id);
+        for (id, entry) in available {
+            let installed = settings.inner.installed_compilers.contains_key(id);
+            emit_compiler(callback, user_data, id, &entry.version, &entry.description, installed);
+        }
+        Ok(())
+    })
+}
+
+/// Downloads and installs a compiler from the manifest, updating and saving `settings`.
+///
+/// # Safety
+/// `settings` must be a valid handle; `compiler_id` must be a valid NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn crbrs_install_compiler(settings: *mut CrbrsSettings, compiler_id: *const c_char) -> CrbrsStatus {
+    guard(|| {
+        let settings = settings.as_mut().ok_or_else(|| invalid_argument("'settings' must not be null"))?;
+        let compiler_id = required_str(compiler_id, "compiler_id")?;
+        crbrs_lib::installer::install_compiler(&mut settings.inner, compiler_id).map_err(lib_error)
+    })
+}
+
+/// Removes an installed compiler, updating and saving `settings`.
+///
+/// # Safety
+/// `settings` must be a valid handle; `compiler_id` must be a valid NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn crbrs_remove_compiler(settings: *mut CrbrsSettings, compiler_id: *const c_char) -> CrbrsStatus {
+    guard(|| {
+        let settings = settings.as_mut().ok_or_else(|| invalid_argument("'settings' must not be null"))?;
+        let compiler_id = required_str(compiler_id, "compiler_id")?;
+        crbrs_lib::installer::remove_compiler(&mut settings.inner, compiler_id).map_err(lib_error)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error_string() -> String {
+        let ptr = crbrs_last_error();
+        assert!(!ptr.is_null());
+        unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn null_arguments_are_rejected() {
+        let status = unsafe { crbrs_compile(ptr::null(), ptr::null(), ptr::null(), None, ptr::null_mut()) };
+        assert_eq!(status, CrbrsStatus::InvalidArgument);
+        assert!(last_error_string().contains("settings"));
+    }
+
+    #[test]
+    fn missing_input_file_reports_io_error() {
+        let settings = crbrs_settings_default();
+        let path = CString::new("/definitely/not/here.cr2").unwrap();
+        let status = unsafe { crbrs_compile(settings, path.as_ptr(), ptr::null(), None, ptr::null_mut()) };
+        assert_eq!(status, CrbrsStatus::Io);
+        assert!(last_error_string().starts_with("[E002] "));
+        assert!(last_error_string().contains("Input file not found"));
+        unsafe { crbrs_settings_free(settings) };
+    }
+
+    #[test]
+    fn checked_in_header_is_current() {
+        let Some(generated) = option_env!("CRBRS_GENERATED_HEADER") else {
+            return; // cbindgen failed, and build.rs warned
+        };
+        let generated = std::fs::read_to_string(generated).unwrap();
+        let checked_in = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("include").join("crbrs.h");
+        if std::env::var_os("CRBRS_BLESS").is_some() {
+            std::fs::write(&checked_in, &generated).unwrap();
+            return;
+        }
+        let current = std::fs::read_to_string(&checked_in).unwrap_or_default();
+        assert!(current == generated, "include/crbrs.h is out of date; run `CRBRS_BLESS=1 cargo test -p crbrs-ffi` to update it");
+    }
+}
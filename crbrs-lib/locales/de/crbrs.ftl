@@ -0,0 +1,168 @@
+# crbrs-Meldungen (Deutsch). Fehlende Meldungen werden auf Englisch angezeigt.
+
+## General
+
+error = Fehler: { $message }
+error-loading-config = Fehler: Konfiguration konnte nicht geladen werden: { $message }
+error-explain-hint = Mehr zu diesem Fehler ({ $code }): crbrs explain { $code }
+explain-unknown-code = Unbekannter Fehler- oder Lint-Code: { $code }. `crbrs explain` listet alle Codes auf.
+file-recovered-from-backup = Warnung: { $path } ist beschädigt ({ $reason }); die Sicherung { $backup } wird verwendet, bis das nächste Speichern die Datei ersetzt.
+file-reset-to-defaults = Warnung: { $path } ist beschädigt ({ $reason }) und hat keine brauchbare Sicherung; es gelten die Standardwerte. Beim nächsten Speichern wird die beschädigte Datei als { $path }.damaged aufbewahrt.
+cancelling = Abbruch… Strg-C erneut drücken, um sofort zu beenden.
+cancelled = Abgebrochen.
+
+## Compiling
+
+compile-succeeded = ✅ Erfolgreich kompiliert: { $file }
+compile-succeeded-cached = ✅ Erfolgreich kompiliert: { $file } (unverändert; Ergebnis aus dem Cache)
+compile-log-created = Compiler-Protokoll erstellt: { $path }
+compile-download-image = Download-Image: { $path }
+compile-checksum = Prüfsumme: { $path }
+compile-signature = Signatur: { $path }
+compile-compiler-changed = ⚠️ { $file } wurde zuletzt am { $built } mit '{ $last_id }' ({ $last_version }) kompiliert; jetzt wird '{ $compiler_id }' ({ $version }) verwendet. Ein anderer Compiler kann das Verhalten des Programms auf dem Logger ändern.
+compile-installing-missing = Compiler '{ $compiler_id }' ist nicht installiert; er wird installiert (--install-missing)...
+compilation-failed = ❌ Kompilieren von '{ $file }' fehlgeschlagen.
+compilation-failed-unrecognized = ❌ Kompilieren von '{ $file }' fehlgeschlagen (unbekanntes Ausgabeformat des Compilers oder Prozessfehler).
+compilation-no-error-lines = Der Compiler hat Fehler gemeldet, aber es konnten keine einzelnen Fehlerzeilen erkannt werden.
+compilation-verbose-hint = (Mit '-v' zeigt `crbrs compile` die unveränderte Ausgabe des Compilers an)
+compilation-errors-found = Gefundene Fehler:
+compilation-error-line = Zeile { $line }: { $message }
+compilation-error-lines = Zeilen { $lines }: { $message }
+compilation-error-repeated = ({ $count }-mal gemeldet)
+compilation-more-errors = …und { $count } weitere Fehler. Mehr anzeigen mit `crbrs config set max_diagnostics <N>` (0 für keine Begrenzung).
+compilation-note = Hinweis: { $message }
+compilation-error = Fehler: { $message }
+compilation-raw-output = Unveränderte Ausgabe des Compilers:
+compilation-log-available = (Vollständiges Compiler-Protokoll auch in '{ $path }')
+compilation-log-hint = (Mit '-v' zeigt `crbrs compile` die unveränderte Ausgabe des Compilers an; --output-log speichert das Compiler-Protokoll)
+
+## Compilers
+
+compiler-installed = ✅ Compiler '{ $compiler_id }' erfolgreich installiert.
+project-lock-updated = Compiler des Projekts in { $path } festgehalten
+compiler-removed = 🗑️ Compiler '{ $compiler_id }' erfolgreich entfernt.
+compiler-up-to-date = ✅ { $compiler_id } { $version } ist aktuell.
+compiler-outdated = ⬆️ { $compiler_id }: { $installed } → { $available }
+compiler-outdated-linked = ⬆️ { $compiler_id }: { $installed } → { $available } (verknüpft; dort aktualisieren, wo er installiert ist)
+compiler-all-up-to-date = Keine Compiler zu aktualisieren.
+compiler-upgraded = ✅ Compiler '{ $compiler_id }' auf { $version } aktualisiert.
+manifest-offline = 📴 Offline: verwende das um { $fetched } zwischengespeicherte Compiler-Manifest.
+manifest-stale = ⚠️ Es war zuletzt vor mehr als { $hours } Stunden aktuell; seitdem veröffentlichte Compiler fehlen darin.
+manifest-fetch-failed = 📴 Das Compiler-Manifest konnte nicht abgerufen werden ({ $error }); verwende die um { $fetched } zwischengespeicherte Kopie.
+compiler-linked = ✅ Compiler '{ $compiler_id }' aus { $path } eingebunden
+compiler-none-installed = Keine Compiler installiert.
+provision-already-installed = Compiler '{ $compiler_id }' ist bereits installiert.
+provision-check-ok = ✅ { $compiler_id } kompiliert.
+provision-check-failed = ❌ { $compiler_id }: { $problem }
+provision-done = Eingerichtet nach { $plan }: { $installed } installiert, { $kept } bereits installiert, { $failed } mit fehlgeschlagener Prüfung.
+compiler-no-recorded-builds = Keine Kompilierungen aufgezeichnet.
+
+## Configuration
+
+config-set = '{ $key }' = '{ $value }' gesetzt
+config-association-set = '.{ $extension }' dem Compiler '{ $compiler_id }' zugeordnet
+config-association-removed = Zuordnung für '.{ $extension }' entfernt
+config-association-not-found = Keine Zuordnung für '.{ $extension }' gefunden
+
+## Bug reports
+
+init-ask-model = Loggermodell
+init-wrote = ✅ { $path } geschrieben
+init-kept = Vorhandene Datei { $path } beibehalten (--force ersetzt sie)
+init-no-compiler = Mit .{ $extension } ist noch kein Compiler verknüpft: required_compilers und [file_associations] in crbrs.toml ausfüllen (siehe `crbrs compiler list-available`).
+init-next-steps = Als Nächstes: das Programm mit `crbrs compile` kompilieren und das Projekt mit git einchecken.
+fmt-formatted = { $path } formatiert
+fmt-unformatted = Nicht formatiert: { $path }
+fmt-summary = { $changed } von { $total } Datei(en) neu formatiert.
+bug-report-written = ✅ Fehlerbericht geschrieben: { $path }
+bug-report-attach = Bitte den Inhalt prüfen und ihn dann an ein Issue anhängen: { $url }
+purge-summary = Entfernt wird:
+purge-total = Insgesamt: { $size }
+purge-nothing = Nichts zu entfernen.
+purge-confirm = All das entfernen? Das lässt sich nicht rückgängig machen.
+purge-done = 🧹 { $count } { $count ->
+    [one] Eintrag
+   *[other] Einträge
+} entfernt.
+secret-prompt = Wert des Geheimnisses '{ $name }':
+secret-stored = 🔒 Geheimnis '{ $name }' im Schlüsselbund gespeichert.
+secret-removed = Geheimnis '{ $name }' aus dem Schlüsselbund entfernt.
+secret-not-found = Kein Geheimnis namens '{ $name }' im Schlüsselbund.
+
+## Batch builds
+
+build-file-cached = ⏭️ Seit dem letzten Kompilieren unverändert, übersprungen: { $file }
+build-file-failed = ❌ { $file }: { $error }
+build-remote = 🌐 Kompiliere auf { $host }...
+build-summary = Zusammenfassung:
+build-summary-files = Dateien: { $total } ({ $compiled } kompiliert, { $cached } unverändert, { $failed } fehlgeschlagen)
+build-summary-warnings = Lint-Warnungen: { $warnings }
+build-summary-wall-time = Laufzeit: { $seconds } s
+build-summary-by-compiler = Nach Compiler:
+build-summary-tally = { $compiled } kompiliert, { $cached } unverändert, { $failed } fehlgeschlagen, { $warnings } Warnungen
+build-summary-status = Status: { $status ->
+        [ok] OK
+        [warnings] OK mit Warnungen
+       *[failed] FEHLGESCHLAGEN
+    }
+build-summary-written = Zusammenfassung geschrieben: { $path }
+
+## Language server
+
+lsp-invalid-uri = Ungültige Dokument-URI zum Kompilieren: { $error }. URI: { $uri }
+lsp-temp-dir-failed = Interner LSP-Fehler: Temporäres Verzeichnis konnte nicht angelegt werden.
+lsp-temp-file-failed = Interner LSP-Fehler: Temporäre Datei zum Kompilieren konnte nicht geschrieben werden.
+lsp-tool-error = crbrs-Fehler beim Kompilieren: { $error }
+lsp-more-diagnostics = …und { $count } weitere Probleme in dieser Datei, nicht angezeigt (Grenze: max_diagnostics = { $limit }).
+lsp-compile-succeeded = { $file } wurde fehlerfrei kompiliert.
+lsp-compile-failed = { $file } konnte nicht kompiliert werden ({ $errors } Fehler).
+lsp-send-unsupported = { $file } kann noch nicht an eine Station gesendet werden: crbrs kann nicht mit Loggern kommunizieren. Senden Sie es mit LoggerNet oder PC400.
+
+## Sensors
+
+sensor-added = ✅ Sensor { $name } zu { $file } hinzugefügt
+
+## Data
+
+qa-report-written = ✅ QA-Bericht ({ $flags } Markierungen) geschrieben: { $path }
+merge-written = ✅ { $files } Dateien zu { $path } zusammengeführt: { $records } Datensätze ({ $duplicates } Wiederholungen entfernt, { $conflicts } widersprüchlich)
+merge-gaps = Datensatzintervall { $interval }: { $gaps } Lücken
+convert-written = ✅ { $records } Datensätze nach { $path } geschrieben{ $skipped ->
+        [0] {""}
+       *[other] {" "}({ $skipped } bereits vorhanden)
+    }
+retime-written = ✅ { $records } Datensätze nach { $path } geschrieben, Zeitstempel von { $from } nach { $to } umgerechnet
+card-files = { $count } Datendateien auf { $card }:
+card-converted = ✅ { $file } -> { $output } ({ $records } Datensätze)
+card-skipped = ⏭️ { $file }: { $format }-Dateien können noch nicht konvertiert werden
+card-failed = ❌ { $file }: { $error }
+check-matches = ✅ { $path } passt zu Tabelle { $table } von { $program } ({ $warnings } Warnungen)
+
+## Loggers
+
+logger-status-header = { $station }: { $os }, Programm { $program }
+fleet-status-ok = ✅ Alle { $stations } Stationen sind in Ordnung
+logger-sent = ✅ { $file } an { $station } gesendet ({ $bytes })
+logger-sent-running = ✅ { $file } an { $station } gesendet ({ $bytes }); der Logger kompiliert und antwortet eventuell { $seconds } s lang nicht
+logger-send-confirm-blocked = ⚠️ Bereitstellung blockiert: { $reason }. Trotzdem senden?
+logger-terminal-log = Diese Sitzung wird in { $path } protokolliert
+history-no-sessions = Keine Sitzungen mit Stationen aufgezeichnet.
+in-place-redirected = ⚠️ { $reason }; es wird in einem temporären Verzeichnis kompiliert, damit dort nichts geschrieben wird
+in-place-read-only = { $path } ist schreibgeschützt
+in-place-tag = { $path } ist ein Checkout des Tags { $tag }
+in-place-never = artifacts_in_place ist auf never gesetzt
+
+## Projektprüfung
+
+verify-report = Prüfe das Projekt von { $manifest }:
+verify-toolchain-ok = Toolchain: { $count } benötigte(r) Compiler installiert
+verify-toolchain-missing = Toolchain: benötigt, aber nicht installiert: { $ids } (`crbrs compile --install-missing` installiert sie)
+verify-toolchain-broken = Toolchain: installiert, aber die ausführbare Datei fehlt: { $ids }
+verify-toolchain-unlisted = Toolchain: einer Endung zugeordnet, aber nicht in required_compilers: { $ids }
+verify-toolchain-mismatched = Toolchain: installiert, aber nicht wie in crbrs.lock festgehalten: { $ids } (neu installieren, oder mit `crbrs compiler install` die Sperrdatei aktualisieren)
+verify-format-ok = Formatierung: { $total } Datei(en) formatiert
+verify-format-failed = Formatierung: { $count } von { $total } Datei(en) nicht formatiert (`crbrs fmt` formatiert sie)
+verify-format-skipped = Formatierung: { $count } Datei(en) konnten nicht geprüft werden
+verify-lint = Lint: { $errors } Fehler, { $warnings } Warnung(en)
+verify-build = Build: { $compiled } kompiliert, { $failed } fehlgeschlagen
+verify-build-skipped = Build: übersprungen, solange die Toolchain unvollständig ist
@@ -0,0 +1,170 @@
+# crbrs user-facing messages (English, the fallback for every other locale).
+# Translations live next to this file as <locale>/crbrs.ftl; messages missing there fall back
+# to the ones here.
+
+## General
+
+error = Error: { $message }
+error-loading-config = Error: Could not load configuration: { $message }
+error-explain-hint = For more on this error ({ $code }), run: crbrs explain { $code }
+explain-unknown-code = Unknown error or lint code: { $code }. Run `crbrs explain` to list all codes.
+file-recovered-from-backup = Warning: { $path } is damaged ({ $reason }); using its backup { $backup } until the next save replaces it.
+file-reset-to-defaults = Warning: { $path } is damaged ({ $reason }) and has no usable backup; using the defaults. The next save keeps the damaged file as { $path }.damaged.
+cancelling = Cancelling… press Ctrl-C again to quit immediately.
+cancelled = Cancelled.
+
+## Compiling
+
+compile-succeeded = ✅ Successfully compiled: { $file }
+compile-succeeded-cached = ✅ Successfully compiled: { $file } (unchanged; cached result)
+compile-log-created = Compiler log created at: { $path }
+compile-download-image = Download image: { $path }
+compile-checksum = Checksum: { $path }
+compile-signature = Signature: { $path }
+compile-compiler-changed = ⚠️ { $file } was last built with '{ $last_id }' ({ $last_version }) on { $built }; now using '{ $compiler_id }' ({ $version }). Compiler changes can change program behavior on the logger.
+compile-installing-missing = Compiler '{ $compiler_id }' is not installed; installing it (--install-missing)...
+compilation-failed = ❌ Compilation of '{ $file }' failed.
+compilation-failed-unrecognized = ❌ Compilation of '{ $file }' failed (compiler output format unrecognized or process error).
+compilation-no-error-lines = Compiler reported errors, but no specific error lines were parsed.
+compilation-verbose-hint = (Use '-v' with `crbrs compile` to see raw compiler stdout/stderr)
+compilation-errors-found = Specific errors found:
+compilation-error-line = Line { $line }: { $message }
+compilation-error-lines = Lines { $lines }: { $message }
+compilation-error-repeated = (reported { $count } times)
+compilation-more-errors = …and { $count } more error(s). Show more with `crbrs config set max_diagnostics <N>` (0 for no limit).
+compilation-note = note: { $message }
+compilation-error = Error: { $message }
+compilation-raw-output = Raw compiler output:
+compilation-log-available = (Full compiler log also available in '{ $path }')
+compilation-log-hint = (Use '-v' with `crbrs compile` to see raw compiler output, or use --output-log to save the compiler's log)
+
+## Compilers
+
+compiler-installed = ✅ Compiler '{ $compiler_id }' installed successfully.
+project-lock-updated = Recorded the project's compilers in { $path }
+compiler-removed = 🗑️ Compiler '{ $compiler_id }' removed successfully.
+compiler-up-to-date = ✅ { $compiler_id } { $version } is up to date.
+compiler-outdated = ⬆️ { $compiler_id }: { $installed } → { $available }
+compiler-outdated-linked = ⬆️ { $compiler_id }: { $installed } → { $available } (linked; upgrade it where it is installed)
+compiler-all-up-to-date = No compilers to upgrade.
+compiler-upgraded = ✅ Compiler '{ $compiler_id }' upgraded to { $version }.
+manifest-offline = 📴 Offline: using the compiler manifest cached at { $fetched }.
+manifest-stale = ⚠️ It was last known to be current more than { $hours } hours ago; compilers published since are missing from it.
+manifest-fetch-failed = 📴 Could not fetch the compiler manifest ({ $error }); using the copy cached at { $fetched }.
+compiler-linked = ✅ Compiler '{ $compiler_id }' linked from { $path }
+compiler-none-installed = No compilers installed.
+provision-already-installed = Compiler '{ $compiler_id }' is already installed.
+provision-check-ok = ✅ { $compiler_id } compiles.
+provision-check-failed = ❌ { $compiler_id }: { $problem }
+provision-done = Provisioned from { $plan }: { $installed } installed, { $kept } already installed, { $failed } failing the check.
+compiler-no-recorded-builds = No recorded builds.
+
+## Configuration
+
+config-set = Set '{ $key }' = '{ $value }'
+config-association-set = Associated '.{ $extension }' with compiler '{ $compiler_id }'
+config-association-removed = Removed association for '.{ $extension }'
+config-association-not-found = No association found for '.{ $extension }'
+
+## Bug reports
+
+init-ask-model = Logger model
+init-wrote = ✅ Wrote { $path }
+init-kept = Kept the existing { $path } (--force replaces it)
+init-no-compiler = No compiler is associated with .{ $extension } yet: fill in required_compilers and [file_associations] in crbrs.toml (see `crbrs compiler list-available`).
+init-next-steps = Next: compile the program with `crbrs compile`, and commit the project with git.
+fmt-formatted = Formatted { $path }
+fmt-unformatted = Not formatted: { $path }
+fmt-summary = { $changed } of { $total } file(s) reformatted.
+bug-report-written = ✅ Bug report written to { $path }
+bug-report-attach = Please check its contents, then attach it to an issue at { $url }
+purge-summary = This removes:
+purge-total = Total: { $size }
+purge-nothing = Nothing to remove.
+purge-confirm = Remove all of this? It cannot be undone.
+purge-done = 🧹 Removed { $count } { $count ->
+    [one] item
+   *[other] items
+}.
+secret-prompt = Value of secret '{ $name }':
+secret-stored = 🔒 Secret '{ $name }' stored in the keychain.
+secret-removed = Secret '{ $name }' removed from the keychain.
+secret-not-found = No secret named '{ $name }' in the keychain.
+
+## Batch builds
+
+build-file-cached = ⏭️ Unchanged since its last build, skipped: { $file }
+build-file-failed = ❌ { $file }: { $error }
+build-remote = 🌐 Building on { $host }...
+build-summary = Build summary:
+build-summary-files = Files: { $total } ({ $compiled } compiled, { $cached } cached, { $failed } failed)
+build-summary-warnings = Lint warnings: { $warnings }
+build-summary-wall-time = Wall time: { $seconds }s
+build-summary-by-compiler = By compiler:
+build-summary-tally = { $compiled } compiled, { $cached } cached, { $failed } failed, { $warnings } warnings
+build-summary-status = Status: { $status ->
+        [ok] OK
+        [warnings] OK with warnings
+       *[failed] FAILED
+    }
+build-summary-written = Build summary written to { $path }
+
+## Language server
+
+lsp-invalid-uri = Invalid document URI for compilation: { $error }. URI: { $uri }
+lsp-temp-dir-failed = Internal LSP error: Could not create temporary directory.
+lsp-temp-file-failed = Internal LSP error: Could not write temporary file for compilation.
+lsp-tool-error = crbrs tool error during compilation: { $error }
+lsp-more-diagnostics = …and { $count } more problem(s) in this file, not shown (limit: max_diagnostics = { $limit }).
+lsp-compile-succeeded = { $file } compiled without errors.
+lsp-compile-failed = { $file } failed to compile ({ $errors } errors).
+lsp-send-unsupported = Sending { $file } to a station is not supported yet: crbrs cannot talk to loggers. Send it with LoggerNet or PC400.
+
+## Sensors
+
+sensor-added = ✅ Added sensor { $name } to { $file }
+
+## Data
+
+qa-report-written = ✅ QA report ({ $flags } flags) written to { $path }
+merge-written = ✅ Merged { $files } files into { $path }: { $records } records ({ $duplicates } repeated records dropped, { $conflicts } conflicting)
+merge-gaps = Record interval { $interval }: { $gaps } gaps
+convert-written = ✅ Wrote { $records } records to { $path }{ $skipped ->
+        [0] {""}
+       *[other] {" "}({ $skipped } already there)
+    }
+retime-written = ✅ Wrote { $records } records to { $path } with timestamps converted from { $from } to { $to }
+card-files = { $count } data files on { $card }:
+card-converted = ✅ { $file } -> { $output } ({ $records } records)
+card-skipped = ⏭️ { $file }: { $format } files cannot be converted yet
+card-failed = ❌ { $file }: { $error }
+check-matches = ✅ { $path } matches table { $table } of { $program } ({ $warnings } warnings)
+
+## Loggers
+
+logger-status-header = { $station }: { $os }, running { $program }
+fleet-status-ok = ✅ All { $stations } stations are ok
+logger-sent = ✅ Sent { $file } to { $station } ({ $bytes })
+logger-sent-running = ✅ Sent { $file } to { $station } ({ $bytes }); the logger is compiling it and may not answer for { $seconds } s
+logger-send-confirm-blocked = ⚠️ Deploy blocked: { $reason }. Send anyway?
+logger-terminal-log = Logging this session to { $path }
+history-no-sessions = No recorded sessions with stations.
+in-place-redirected = ⚠️ { $reason }; compiling in a temporary directory so nothing is written there
+in-place-read-only = { $path } is read-only
+in-place-tag = { $path } is a checkout of tag { $tag }
+in-place-never = artifacts_in_place is set to never
+
+## Project verification
+
+verify-report = Verifying the project of { $manifest }:
+verify-toolchain-ok = Toolchain: { $count } required compiler(s) installed
+verify-toolchain-missing = Toolchain: required but not installed: { $ids } (`crbrs compile --install-missing` installs them)
+verify-toolchain-broken = Toolchain: installed, but the executable is gone: { $ids }
+verify-toolchain-unlisted = Toolchain: associated with an extension but not in required_compilers: { $ids }
+verify-toolchain-mismatched = Toolchain: installed, but not what crbrs.lock records: { $ids } (reinstall them, or `crbrs compiler install` them to update the lock)
+verify-format-ok = Formatting: { $total } file(s) formatted
+verify-format-failed = Formatting: { $count } of { $total } file(s) not formatted (`crbrs fmt` formats them)
+verify-format-skipped = Formatting: { $count } file(s) could not be checked
+verify-lint = Lint: { $errors } error(s), { $warnings } warning(s)
+verify-build = Build: { $compiled } compiled, { $failed } failed
+verify-build-skipped = Build: skipped while the toolchain is incomplete
@@ -0,0 +1,168 @@
+# Mensajes de crbrs (español). Los mensajes que falten se muestran en inglés.
+
+## General
+
+error = Error: { $message }
+error-loading-config = Error: no se pudo cargar la configuración: { $message }
+error-explain-hint = Más información sobre este error ({ $code }): crbrs explain { $code }
+explain-unknown-code = Código de error o de lint desconocido: { $code }. Ejecute `crbrs explain` para ver todos los códigos.
+file-recovered-from-backup = Aviso: { $path } está dañado ({ $reason }); se usa su copia de seguridad { $backup } hasta que el próximo guardado lo reemplace.
+file-reset-to-defaults = Aviso: { $path } está dañado ({ $reason }) y no tiene una copia de seguridad utilizable; se usan los valores predeterminados. El próximo guardado conserva el archivo dañado como { $path }.damaged.
+cancelling = Cancelando… pulse Ctrl-C de nuevo para salir inmediatamente.
+cancelled = Cancelado.
+
+## Compiling
+
+compile-succeeded = ✅ Compilado correctamente: { $file }
+compile-succeeded-cached = ✅ Compilado correctamente: { $file } (sin cambios; resultado en caché)
+compile-log-created = Registro del compilador creado en: { $path }
+compile-download-image = Imagen para descargar: { $path }
+compile-checksum = Suma de comprobación: { $path }
+compile-signature = Firma: { $path }
+compile-compiler-changed = ⚠️ { $file } se compiló por última vez con '{ $last_id }' ({ $last_version }) el { $built }; ahora se usa '{ $compiler_id }' ({ $version }). Cambiar de compilador puede cambiar el comportamiento del programa en el datalogger.
+compile-installing-missing = El compilador '{ $compiler_id }' no está instalado; instalándolo (--install-missing)...
+compilation-failed = ❌ Falló la compilación de '{ $file }'.
+compilation-failed-unrecognized = ❌ Falló la compilación de '{ $file }' (formato de salida del compilador desconocido o error del proceso).
+compilation-no-error-lines = El compilador informó errores, pero no se pudo reconocer ninguna línea de error.
+compilation-verbose-hint = (Use '-v' con `crbrs compile` para ver la salida original del compilador)
+compilation-errors-found = Errores encontrados:
+compilation-error-line = Línea { $line }: { $message }
+compilation-error-lines = Líneas { $lines }: { $message }
+compilation-error-repeated = (notificado { $count } veces)
+compilation-more-errors = …y { $count } error(es) más. Muestre más con `crbrs config set max_diagnostics <N>` (0 para no limitar).
+compilation-note = nota: { $message }
+compilation-error = Error: { $message }
+compilation-raw-output = Salida original del compilador:
+compilation-log-available = (El registro completo del compilador también está en '{ $path }')
+compilation-log-hint = (Use '-v' con `crbrs compile` para ver la salida original del compilador, o --output-log para guardar su registro)
+
+## Compilers
+
+compiler-installed = ✅ Compilador '{ $compiler_id }' instalado correctamente.
+project-lock-updated = Compiladores del proyecto registrados en { $path }
+compiler-removed = 🗑️ Compilador '{ $compiler_id }' eliminado correctamente.
+compiler-up-to-date = ✅ { $compiler_id } { $version } está actualizado.
+compiler-outdated = ⬆️ { $compiler_id }: { $installed } → { $available }
+compiler-outdated-linked = ⬆️ { $compiler_id }: { $installed } → { $available } (enlazado; actualícelo donde está instalado)
+compiler-all-up-to-date = No hay compiladores que actualizar.
+compiler-upgraded = ✅ Compilador '{ $compiler_id }' actualizado a { $version }.
+manifest-offline = 📴 Sin conexión: se usa el manifiesto de compiladores guardado en caché el { $fetched }.
+manifest-stale = ⚠️ Se confirmó vigente por última vez hace más de { $hours } horas; faltan los compiladores publicados desde entonces.
+manifest-fetch-failed = 📴 No se pudo obtener el manifiesto de compiladores ({ $error }); se usa la copia guardada en caché el { $fetched }.
+compiler-linked = ✅ Compilador '{ $compiler_id }' enlazado desde { $path }
+compiler-none-installed = No hay compiladores instalados.
+provision-already-installed = El compilador '{ $compiler_id }' ya está instalado.
+provision-check-ok = ✅ { $compiler_id } compila.
+provision-check-failed = ❌ { $compiler_id }: { $problem }
+provision-done = Configurado según { $plan }: { $installed } instalados, { $kept } ya instalados, { $failed } con la comprobación fallida.
+compiler-no-recorded-builds = No hay compilaciones registradas.
+
+## Configuration
+
+config-set = '{ $key }' = '{ $value }' establecido
+config-association-set = '.{ $extension }' asociado con el compilador '{ $compiler_id }'
+config-association-removed = Asociación de '.{ $extension }' eliminada
+config-association-not-found = No hay ninguna asociación para '.{ $extension }'
+
+## Bug reports
+
+init-ask-model = Modelo de datalogger
+init-wrote = ✅ Escrito { $path }
+init-kept = Se conserva el archivo existente { $path } (--force lo reemplaza)
+init-no-compiler = Aún no hay un compilador asociado a .{ $extension }: complete required_compilers y [file_associations] en crbrs.toml (vea `crbrs compiler list-available`).
+init-next-steps = Siguiente paso: compile el programa con `crbrs compile` y confirme el proyecto con git.
+fmt-formatted = Formateado { $path }
+fmt-unformatted = Sin formatear: { $path }
+fmt-summary = { $changed } de { $total } archivo(s) formateados.
+bug-report-written = ✅ Informe de error guardado en { $path }
+bug-report-attach = Revise su contenido y luego adjúntelo a un issue en { $url }
+purge-summary = Se eliminará:
+purge-total = Total: { $size }
+purge-nothing = No hay nada que eliminar.
+purge-confirm = ¿Eliminar todo esto? No se puede deshacer.
+purge-done = 🧹 { $count ->
+    [one] Se eliminó { $count } elemento
+   *[other] Se eliminaron { $count } elementos
+}.
+secret-prompt = Valor del secreto '{ $name }':
+secret-stored = 🔒 Secreto '{ $name }' guardado en el llavero.
+secret-removed = Secreto '{ $name }' eliminado del llavero.
+secret-not-found = No hay ningún secreto llamado '{ $name }' en el llavero.
+
+## Batch builds
+
+build-file-cached = ⏭️ Sin cambios desde la última compilación, omitido: { $file }
+build-file-failed = ❌ { $file }: { $error }
+build-remote = 🌐 Compilando en { $host }...
+build-summary = Resumen de la compilación:
+build-summary-files = Archivos: { $total } ({ $compiled } compilados, { $cached } sin cambios, { $failed } con errores)
+build-summary-warnings = Advertencias del linter: { $warnings }
+build-summary-wall-time = Tiempo total: { $seconds } s
+build-summary-by-compiler = Por compilador:
+build-summary-tally = { $compiled } compilados, { $cached } sin cambios, { $failed } con errores, { $warnings } advertencias
+build-summary-status = Estado: { $status ->
+        [ok] OK
+        [warnings] OK con advertencias
+       *[failed] CON ERRORES
+    }
+build-summary-written = Resumen guardado en { $path }
+
+## Language server
+
+lsp-invalid-uri = URI de documento no válida para compilar: { $error }. URI: { $uri }
+lsp-temp-dir-failed = Error interno del LSP: no se pudo crear el directorio temporal.
+lsp-temp-file-failed = Error interno del LSP: no se pudo escribir el archivo temporal para compilar.
+lsp-tool-error = Error de crbrs al compilar: { $error }
+lsp-more-diagnostics = …y { $count } problema(s) más en este archivo, no mostrados (límite: max_diagnostics = { $limit }).
+lsp-compile-succeeded = { $file } se compiló sin errores.
+lsp-compile-failed = { $file } no se pudo compilar ({ $errors } errores).
+lsp-send-unsupported = Todavía no se puede enviar { $file } a una estación: crbrs no puede comunicarse con los registradores. Envíelo con LoggerNet o PC400.
+
+## Sensors
+
+sensor-added = ✅ Sensor { $name } añadido a { $file }
+
+## Data
+
+qa-report-written = ✅ Informe de QA ({ $flags } marcas) guardado en { $path }
+merge-written = ✅ { $files } archivos combinados en { $path }: { $records } registros ({ $duplicates } repeticiones descartadas, { $conflicts } en conflicto)
+merge-gaps = Intervalo de registro { $interval }: { $gaps } huecos
+convert-written = ✅ { $records } registros guardados en { $path }{ $skipped ->
+        [0] {""}
+       *[other] {" "}({ $skipped } ya presentes)
+    }
+retime-written = ✅ { $records } registros guardados en { $path } con las marcas de tiempo convertidas de { $from } a { $to }
+card-files = { $count } archivos de datos en { $card }:
+card-converted = ✅ { $file } -> { $output } ({ $records } registros)
+card-skipped = ⏭️ { $file }: los archivos { $format } aún no se pueden convertir
+card-failed = ❌ { $file }: { $error }
+check-matches = ✅ { $path } coincide con la tabla { $table } de { $program } ({ $warnings } advertencias)
+
+## Loggers
+
+logger-status-header = { $station }: { $os }, programa { $program }
+fleet-status-ok = ✅ Las { $stations } estaciones están bien
+logger-sent = ✅ { $file } enviado a { $station } ({ $bytes })
+logger-sent-running = ✅ { $file } enviado a { $station } ({ $bytes }); el logger lo está compilando y puede no responder durante { $seconds } s
+logger-send-confirm-blocked = ⚠️ Despliegue bloqueado: { $reason }. ¿Enviar de todos modos?
+logger-terminal-log = Esta sesión se registra en { $path }
+history-no-sessions = No hay sesiones registradas con estaciones.
+in-place-redirected = ⚠️ { $reason }; se compila en un directorio temporal para no escribir nada allí
+in-place-read-only = { $path } es de solo lectura
+in-place-tag = { $path } es una copia de trabajo de la etiqueta { $tag }
+in-place-never = artifacts_in_place está configurado como never
+
+## Verificación del proyecto
+
+verify-report = Verificando el proyecto de { $manifest }:
+verify-toolchain-ok = Toolchain: { $count } compilador(es) requerido(s) instalado(s)
+verify-toolchain-missing = Toolchain: requeridos pero no instalados: { $ids } (`crbrs compile --install-missing` los instala)
+verify-toolchain-broken = Toolchain: instalados, pero falta el ejecutable: { $ids }
+verify-toolchain-unlisted = Toolchain: asociados a una extensión pero ausentes de required_compilers: { $ids }
+verify-toolchain-mismatched = Toolchain: instalados, pero distintos de lo que registra crbrs.lock: { $ids } (reinstálelos, o actualice el bloqueo con `crbrs compiler install`)
+verify-format-ok = Formato: { $total } archivo(s) formateado(s)
+verify-format-failed = Formato: { $count } de { $total } archivo(s) sin formatear (`crbrs fmt` los formatea)
+verify-format-skipped = Formato: no se pudieron comprobar { $count } archivo(s)
+verify-lint = Lint: { $errors } error(es), { $warnings } advertencia(s)
+verify-build = Compilación: { $compiled } compilado(s), { $failed } con errores
+verify-build-skipped = Compilación: omitida mientras la toolchain esté incompleta
@@ -0,0 +1,372 @@
+// FILE: crbrs-lib/src/adapter.rs
+
+//! Per-family knowledge about Campbell Scientific compilers.
+//!
+//! Each compiler generation takes its arguments in a slightly different shape and reports
+//! results in its own format. A `CompilerAdapter` encapsulates both so `compile_file_impl`
+//! only deals with running the process and turning the parsed outcome into a `Result`.
+//! The family is selected by the `family` field of the manifest entry (default `cr1000`).
+
+use crate::CompilationErrorDetail;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Compiler generation, as written in the manifest's `family` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompilerFamily {
+    /// CRBasic compilers for the CR1000/CR3000/CR800/CR6/CR300/CR1000X loggers.
+    #[default]
+    Cr1000,
+    /// CRBasic compilers for the CR200/CR200X loggers.
+    Cr200,
+    /// Older Edlog-era tools for the CR10X/CR510/CR23X mixed-array loggers.
+    Cr10x,
+}
+
+impl fmt::Display for CompilerFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CompilerFamily::Cr1000 => "cr1000",
+            CompilerFamily::Cr200 => "cr200",
+            CompilerFamily::Cr10x => "cr10x",
+        })
+    }
+}
+
+impl FromStr for CompilerFamily {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cr1000" => Ok(CompilerFamily::Cr1000),
+            "cr200" => Ok(CompilerFamily::Cr200),
+            "cr10x" => Ok(CompilerFamily::Cr10x),
+            other => Err(format!(
+                "unknown compiler family '{}' (expected cr1000, cr200 or cr10x)",
+                other
+            )),
+        }
+    }
+}
+
+/// What a compiler run reported, as understood by its adapter.
+#[derive(Debug, Clone)]
+pub enum CompilerOutcome {
+    Success,
+    /// The compiler reported failure; `errors` may be empty if it gave no details.
+    Failed(Vec<CompilationErrorDetail>),
+    /// The output did not match the family's format at all.
+    Unrecognized,
+}
+
+/// How to run a "compile for download" pass and where its binary image ends up.
+#[derive(Debug, Clone)]
+pub struct DownloadImage {
+    pub args: Vec<String>,
+    pub image: PathBuf,
+}
+
+pub trait CompilerAdapter {
+    fn family(&self) -> CompilerFamily;
+
+    /// Arguments passed after the compiler executable (and after `wine`, if used).
+    fn args(&self, input_file: &Path, output_log: Option<&Path>) -> Vec<String>;
+
+    /// Interprets the compiler's stdout.
+    fn parse_output(&self, input_file: &Path, output: &str) -> CompilerOutcome;
+
+    /// Arguments for a run that also produces a downloadable image, or `None` if the
+    /// family's loggers take the program source and compile it themselves.
+    fn download_image(&self, _input_file: &Path, _output_log: Option<&Path>) -> Option<DownloadImage> {
+        None
+    }
+}
+
+/// Returns the adapter for `family`.
+pub fn adapter_for(family: CompilerFamily) -> &'static dyn CompilerAdapter {
+    match family {
+        CompilerFamily::Cr1000 => &CrBasicAdapter { family: CompilerFamily::Cr1000 },
+        CompilerFamily::Cr200 => &Cr200Adapter,
+        CompilerFamily::Cr10x => &Cr10xAdapter,
+    }
+}
+
+/// CRBasic command-line compilers for the CR1000 family (`cr1comp.exe`, `cr300comp.exe`, ...).
+///
+/// Invoked as `<compiler> <input> [<log>]`. The first line of stdout is
+/// `<file> -- Compiled OK.` or `<file> -- Compile Failed!`, followed by `line N: message`
+/// entries; lines that don't start a new entry continue the previous message.
+struct CrBasicAdapter {
+    family: CompilerFamily,
+}
+
+impl CompilerAdapter for CrBasicAdapter {
+    fn family(&self) -> CompilerFamily {
+        self.family
+    }
+
+    fn args(&self, input_file: &Path, output_log: Option<&Path>) -> Vec<String> {
+        let mut args = vec![input_file.to_string_lossy().into_owned()];
+        // The compiler writes a log file only when given a second argument.
+        if let Some(log_path) = output_log {
+            args.push(log_path.to_string_lossy().into_owned());
+        }
+        args
+    }
+
+    fn parse_output(&self, _input_file: &Path, output: &str) -> CompilerOutcome {
+        let mut lines = output.lines();
+
+        // First line indicates overall status and the filename as seen by the compiler.
+        let first_line = lines.next().unwrap_or("").trim();
+        let file_path_in_log = first_line.split(" -- ").next().unwrap_or("").trim().to_string();
+
+        if first_line.contains("Compiled OK.") {
+            return CompilerOutcome::Success;
+        }
+        if !first_line.contains("Compile Failed!") {
+            return CompilerOutcome::Unrecognized;
+        }
+
+        let re = Regex::new(r"^\s*line\s+(\d+):\s*(.+?)\s*$").expect("Invalid regex pattern");
+        let mut errors: Vec<CompilationErrorDetail> = Vec::new();
+        for line_str in lines {
+            let trimmed_line = line_str.trim();
+            if trimmed_line.is_empty() {
+                continue;
+            }
+            if let Some(caps) = re.captures(trimmed_line) {
+                errors.push(CompilationErrorDetail {
+                    file_path_in_log: file_path_in_log.clone(),
+                    line: caps[1].parse::<u32>().ok(),
+                    message: caps[2].to_string(),
+                });
+            } else if let Some(last_error) = errors.last_mut() {
+                // Continuation of a multi-line error message.
+                last_error.message.push('\n');
+                last_error.message.push_str(trimmed_line);
+            }
+        }
+        CompilerOutcome::Failed(errors)
+    }
+}
+
+/// CR200/CR200X compilers (`cr2comp.exe`, `cr200xcomp.exe`).
+///
+/// Invoked as `<compiler> <input> [-l <log>]`; the log path is an option rather than a second
+/// positional argument. Errors are reported compiler-style as `<file>(<line>) : <message>` or
+/// `Error: line N, <message>`, and the run ends with `Compile successful` or `N error(s)`.
+/// Some releases print the CR1000-style report instead, which is handled as a fallback.
+/// These formats have not been checked against captured output yet; the test samples for
+/// this family are synthetic.
+///
+/// CR200 loggers have no on-board compiler: every compile also writes the binary image
+/// (`<program>.bin`) next to the source, and that image is what gets sent.
+struct Cr200Adapter;
+
+impl CompilerAdapter for Cr200Adapter {
+    fn family(&self) -> CompilerFamily {
+        CompilerFamily::Cr200
+    }
+
+    fn args(&self, input_file: &Path, output_log: Option<&Path>) -> Vec<String> {
+        let mut args = vec![input_file.to_string_lossy().into_owned()];
+        if let Some(log_path) = output_log {
+            args.push("-l".to_string());
+            args.push(log_path.to_string_lossy().into_owned());
+        }
+        args
+    }
+
+    fn parse_output(&self, input_file: &Path, output: &str) -> CompilerOutcome {
+        let crbasic = CrBasicAdapter { family: CompilerFamily::Cr200 }.parse_output(input_file, output);
+        if !matches!(crbasic, CompilerOutcome::Unrecognized) {
+            return crbasic;
+        }
+
+        let located = Regex::new(r"^(.+?)\((\d+)\)\s*:\s*(.+?)\s*$").expect("Invalid regex pattern");
+        let prefixed = Regex::new(r"(?i)^error\s*:?\s*line\s+(\d+)\s*[:,]\s*(.+?)\s*$").expect("Invalid regex pattern");
+        let summary = Regex::new(r"(?i)^(\d+)\s+errors?\b").expect("Invalid regex pattern");
+        let input_name = input_file.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+        let mut errors: Vec<CompilationErrorDetail> = Vec::new();
+        let mut reported_errors: Option<u32> = None;
+        let mut saw_success = false;
+        for line in output.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(caps) = located.captures(line) {
+                errors.push(CompilationErrorDetail {
+                    file_path_in_log: caps[1].trim().to_string(),
+                    line: caps[2].parse::<u32>().ok(),
+                    message: caps[3].to_string(),
+                });
+            } else if let Some(caps) = prefixed.captures(line) {
+                errors.push(CompilationErrorDetail {
+                    file_path_in_log: input_name.clone(),
+                    line: caps[1].parse::<u32>().ok(),
+                    message: caps[2].to_string(),
+                });
+            } else if let Some(caps) = summary.captures(line) {
+                reported_errors = caps[1].parse::<u32>().ok();
+            } else if line.to_ascii_lowercase().contains("compile successful") {
+                saw_success = true;
+            }
+        }
+
+        match reported_errors {
+            _ if !errors.is_empty() => CompilerOutcome::Failed(errors),
+            Some(0) => CompilerOutcome::Success,
+            Some(_) => CompilerOutcome::Failed(errors),
+            None if saw_success => CompilerOutcome::Success,
+            None => CompilerOutcome::Unrecognized,
+        }
+    }
+
+    fn download_image(&self, input_file: &Path, output_log: Option<&Path>) -> Option<DownloadImage> {
+        Some(DownloadImage {
+            args: self.args(input_file, output_log),
+            image: input_file.with_extension("bin"),
+        })
+    }
+}
+
+/// Edlog-era compilers for mixed-array loggers.
+///
+/// Invoked with the program file only; these tools always write their listing next to the
+/// input, so a requested log path is not passed on. Results are reported as free-form lines:
+/// `No errors` (or `Compiled OK`) on success, otherwise one `Line N: message` (also
+/// `Error ... line N ...`) entry per problem. A successful run also writes the `.DLD`
+/// download file next to the input. As for CR200, these formats have not been checked against
+/// captured output yet and the test samples are synthetic.
+struct Cr10xAdapter;
+
+impl CompilerAdapter for Cr10xAdapter {
+    fn family(&self) -> CompilerFamily {
+        CompilerFamily::Cr10x
+    }
+
+    fn args(&self, input_file: &Path, output_log: Option<&Path>) -> Vec<String> {
+        if output_log.is_some() {
+            log::warn!("CR10X-family compilers do not take a log file argument; ignoring it.");
+        }
+        vec![input_file.to_string_lossy().into_owned()]
+    }
+
+    fn parse_output(&self, input_file: &Path, output: &str) -> CompilerOutcome {
+        let file_path_in_log = input_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let entry = Regex::new(r"(?i)^\s*line\s+(\d+)\s*[:\-]\s*(.+?)\s*$").expect("Invalid regex pattern");
+        let inline = Regex::new(r"(?i)^\s*error\b.*?\bline\s+(\d+)\b[\s:,\-]*(.*?)\s*$").expect("Invalid regex pattern");
+
+        let mut errors = Vec::new();
+        let mut saw_success = false;
+        let mut saw_error = false;
+        for line in output.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let lower = line.to_ascii_lowercase();
+            if lower.contains("no errors") || lower.contains("compiled ok") {
+                saw_success = true;
+            } else if let Some(caps) = entry.captures(line).or_else(|| inline.captures(line)) {
+                let message = if caps[2].is_empty() { line.to_string() } else { caps[2].to_string() };
+                errors.push(CompilationErrorDetail {
+                    file_path_in_log: file_path_in_log.clone(),
+                    line: caps[1].parse::<u32>().ok(),
+                    message,
+                });
+            } else if lower.starts_with("error") || lower.contains("compile failed") {
+                saw_error = true;
+            }
+        }
+
+        if !errors.is_empty() || saw_error {
+            CompilerOutcome::Failed(errors)
+        } else if saw_success {
+            CompilerOutcome::Success
+        } else {
+            CompilerOutcome::Unrecognized
+        }
+    }
+
+    fn download_image(&self, input_file: &Path, output_log: Option<&Path>) -> Option<DownloadImage> {
+        Some(DownloadImage {
+            args: self.args(input_file, output_log),
+            image: input_file.with_extension("dld"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(outcome: CompilerOutcome) -> Vec<(Option<u32>, String)> {
+        match outcome {
+            CompilerOutcome::Failed(errors) => errors.into_iter().map(|e| (e.line, e.message)).collect(),
+            other => panic!("expected a failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn crbasic_reports_are_parsed_with_continuation_lines() {
+        let adapter = adapter_for(CompilerFamily::Cr1000);
+        let input = Path::new("station.cr1x");
+        assert!(matches!(adapter.parse_output(input, "C:\\progs\\station.cr1x -- Compiled OK.\n"), CompilerOutcome::Success));
+        let output = "C:\\progs\\station.cr1x -- Compile Failed!\nline 12: Undeclared variable Batt.\n  Check the Public declarations.\nline 40: Missing NextScan.\n";
+        assert_eq!(
+            errors(adapter.parse_output(input, output)),
+            vec![
+                (Some(12), "Undeclared variable Batt.\nCheck the Public declarations.".to_string()),
+                (Some(40), "Missing NextScan.".to_string()),
+            ]
+        );
+        assert!(matches!(adapter.parse_output(input, "wine: cannot find L\"C:\\\\cr1comp.exe\"\n"), CompilerOutcome::Unrecognized));
+        assert_eq!(adapter.args(input, Some(Path::new("out.log"))), vec!["station.cr1x", "out.log"]);
+    }
+
+    #[test]
+    fn cr200_reports_are_parsed_in_every_known_format() {
+        let adapter = adapter_for(CompilerFamily::Cr200);
+        let input = Path::new("/progs/site.cr2");
+        assert!(matches!(adapter.parse_output(input, "site.cr2 -- Compiled OK.\n"), CompilerOutcome::Success));
+        assert!(matches!(adapter.parse_output(input, "Compile successful\n0 errors\n"), CompilerOutcome::Success));
+        let located = adapter.parse_output(input, "site.cr2(14) : Variable not declared\n1 error\n");
+        match located {
+            CompilerOutcome::Failed(errors) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].file_path_in_log, "site.cr2");
+                assert_eq!(errors[0].line, Some(14));
+                assert_eq!(errors[0].message, "Variable not declared");
+            }
+            other => panic!("expected a failure, got {:?}", other),
+        }
+        assert_eq!(
+            errors(adapter.parse_output(input, "Error: line 3, Scan rate too fast\n")),
+            vec![(Some(3), "Scan rate too fast".to_string())]
+        );
+        // A summary with no itemised errors still fails the build.
+        assert!(errors(adapter.parse_output(input, "2 errors\n")).is_empty());
+        assert!(matches!(adapter.parse_output(input, "Press any key\n"), CompilerOutcome::Unrecognized));
+        assert_eq!(adapter.args(input, Some(Path::new("out.log"))), vec!["/progs/site.cr2", "-l", "out.log"]);
+    }
+
+    #[test]
+    fn cr10x_reports_are_parsed_from_free_form_lines() {
+        let adapter = adapter_for(CompilerFamily::Cr10x);
+        let input = Path::new("/progs/tower.csi");
+        assert!(matches!(adapter.parse_output(input, "Compiling tower.csi\nNo errors\n"), CompilerOutcome::Success));
+        let failed = adapter.parse_output(input, "Line 7: Invalid instruction number\nError 23 on line 9, input location out of range\n");
+        assert_eq!(
+            errors(failed),
+            vec![(Some(7), "Invalid instruction number".to_string()), (Some(9), "input location out of range".to_string())]
+        );
+        assert!(errors(adapter.parse_output(input, "Compile failed\n")).is_empty());
+        assert!(matches!(adapter.parse_output(input, "Press any key\n"), CompilerOutcome::Unrecognized));
+        // The listing is always written next to the program; a log path is not passed.
+        assert_eq!(adapter.args(input, Some(Path::new("out.log"))), vec!["/progs/tower.csi"]);
+    }
+}
@@ -0,0 +1,162 @@
+// FILE: crbrs-lib/src/archive.rs
+
+//! Unpacking compiler archives: ZIP, gzipped tar and 7z.
+//!
+//! The format is taken from the manifest entry's `archive_format` if it has one, else detected
+//! from the archive's first bytes, so a mirror can repackage a bundle as a tarball without
+//! the manifest saying so. Entries that would land outside the destination (`../`, absolute
+//! paths) are skipped, and unpacking stops between entries when cancelled.
+
+use crate::cancel;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Component, Path, PathBuf};
+use zip::ZipArchive;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar.gz", alias = "tgz")]
+    TarGz,
+    #[serde(rename = "7z")]
+    SevenZ,
+}
+
+impl ArchiveFormat {
+    /// The format of `bytes` by its magic number; ZIP if it is none of the others.
+    pub fn detect(bytes: &[u8]) -> ArchiveFormat {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            ArchiveFormat::TarGz
+        } else if bytes.starts_with(b"7z\xbc\xaf\x27\x1c") {
+            ArchiveFormat::SevenZ
+        } else {
+            ArchiveFormat::Zip
+        }
+    }
+}
+
+/// `name` relative to the destination, or `None` if it would leave it.
+fn enclosed(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)).then(|| path.to_path_buf())
+}
+
+/// Extracts `archive_bytes` into `dest`, as `format` or the format detected.
+pub fn unpack(archive_bytes: &[u8], format: Option<ArchiveFormat>, dest: &Path) -> Result<(), Error> {
+    let format = format.unwrap_or_else(|| ArchiveFormat::detect(archive_bytes));
+    log::debug!("Unpacking a {:?} archive of {} bytes into {:?}", format, archive_bytes.len(), dest);
+    match format {
+        ArchiveFormat::Zip => unpack_zip(archive_bytes, dest),
+        ArchiveFormat::TarGz => unpack_tar_gz(archive_bytes, dest),
+        ArchiveFormat::SevenZ => unpack_7z(archive_bytes, dest),
+    }
+}
+
+fn unpack_zip(archive_bytes: &[u8], dest: &Path) -> Result<(), Error> {
+    let mut archive = ZipArchive::new(Cursor::new(archive_bytes))?;
+
+    for i in 0..archive.len() {
+        cancel::check()?;
+        let mut file = archive.by_index(i)?;
+        // Sanitize file path to prevent zip slip vulnerabilities
+        let outpath = match file.enclosed_name() {
+            Some(path) => dest.join(path),
+            None => {
+                log::warn!("Skipping potentially unsafe file path in zip: {}", file.name());
+                continue;
+            }
+        };
+
+        if file.name().ends_with('/') {
+            log::debug!("Creating directory from zip: {:?}", outpath);
+            fs::create_dir_all(&outpath)?;
+        } else {
+            log::debug!("Extracting file from zip: {:?} ({} bytes)", outpath, file.size());
+            if let Some(p) = outpath.parent() {
+                if !p.exists() {
+                    fs::create_dir_all(p)?;
+                }
+            }
+            let mut outfile = fs::File::create(&outpath)?;
+            io::copy(&mut file, &mut outfile)?;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(mode) = file.unix_mode() {
+                if mode != 0 { // Only set permissions if mode is non-zero
+                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn unpack_tar_gz(archive_bytes: &[u8], dest: &Path) -> Result<(), Error> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive_bytes));
+    for entry in archive.entries()? {
+        cancel::check()?;
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        // `unpack_in` refuses paths outside `dest` (and links pointing out of it).
+        if !entry.unpack_in(dest)? {
+            log::warn!("Skipping potentially unsafe file path in tar: {}", name);
+        } else {
+            log::debug!("Extracted from tar: {}", name);
+        }
+    }
+    Ok(())
+}
+
+fn unpack_7z(archive_bytes: &[u8], dest: &Path) -> Result<(), Error> {
+    let invalid = |e: sevenz_rust::Error| Error::InvalidCompilerSource(format!("Invalid 7z archive: {}", e));
+    let mut archive =
+        sevenz_rust::SevenZReader::new(Cursor::new(archive_bytes), archive_bytes.len() as u64, sevenz_rust::Password::empty()).map_err(invalid)?;
+    let mut failed: Option<Error> = None;
+    archive
+        .for_each_entries(|entry, reader| {
+            let result = (|| {
+                cancel::check()?;
+                let Some(relative) = enclosed(entry.name()) else {
+                    log::warn!("Skipping potentially unsafe file path in 7z: {}", entry.name());
+                    // Entries share one solid stream; the next one starts after this one's bytes.
+                    io::copy(reader, &mut io::sink())?;
+                    return Ok(());
+                };
+                let outpath = dest.join(relative);
+                if entry.is_directory() {
+                    fs::create_dir_all(&outpath)?;
+                    return Ok(());
+                }
+                if let Some(parent) = outpath.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                log::debug!("Extracting file from 7z: {:?} ({} bytes)", outpath, entry.size());
+                io::copy(reader, &mut fs::File::create(&outpath)?)?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    // p7zip keeps the Unix mode in the high 16 bits, flagged by 0x8000.
+                    let mode = entry.windows_attributes >> 16;
+                    if entry.has_windows_attributes && entry.windows_attributes & 0x8000 != 0 && mode != 0 {
+                        fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+                    }
+                }
+                Ok(())
+            })();
+            match result {
+                Ok(()) => Ok(true),
+                Err(e) => {
+                    failed = Some(e);
+                    Ok(false)
+                }
+            }
+        })
+        .map_err(invalid)?;
+    failed.map_or(Ok(()), Err)
+}
+
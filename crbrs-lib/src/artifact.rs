@@ -0,0 +1,148 @@
+// FILE: crbrs-lib/src/artifact.rs
+
+//! Integrity sidecars for compiler-produced artifacts (download images, bundles).
+//!
+//! `seal` writes `<artifact>.sha256` in `sha256sum` format and, when `signing_key_path` is
+//! configured, a `<artifact>.minisig` signature made with the `minisign` tool. `verify`
+//! checks both before an artifact is deployed; signatures are verified in-process against
+//! `signing_public_key`.
+
+use crate::{Error, Settings};
+use minisign_verify::{PublicKey, Signature};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Sidecar files written for an artifact.
+#[derive(Debug, Clone)]
+pub struct Sealed {
+    pub checksum: PathBuf,
+    pub signature: Option<PathBuf>,
+}
+
+fn sidecar(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+/// Path of the checksum sidecar for `path`.
+pub fn checksum_path(path: &Path) -> PathBuf {
+    sidecar(path, "sha256")
+}
+
+/// Path of the minisign signature for `path`.
+pub fn signature_path(path: &Path) -> PathBuf {
+    sidecar(path, "minisig")
+}
+
+/// Lowercase hex SHA256 of the file at `path`.
+pub fn sha256_file(path: &Path) -> Result<String, Error> {
+    let bytes = fs::read(path)?;
+    Ok(Sha256::digest(&bytes).iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Writes the checksum sidecar and, if a signing key is configured, the signature.
+pub fn seal(path: &Path, settings: &Settings) -> Result<Sealed, Error> {
+    let hash = sha256_file(path)?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let checksum = checksum_path(path);
+    fs::write(&checksum, format!("{}  {}\n", hash, file_name))?;
+    log::info!("Wrote checksum {} for {:?}", hash, path);
+
+    let signature = match &settings.signing_key_path {
+        Some(key) => Some(sign(path, key)?),
+        None => None,
+    };
+    Ok(Sealed { checksum, signature })
+}
+
+/// Signs `path` with the minisign secret key at `key`. Encrypted keys prompt for their
+/// password on the terminal.
+fn sign(path: &Path, key: &Path) -> Result<PathBuf, Error> {
+    let signature = signature_path(path);
+    log::info!("Signing {:?} with minisign key {:?}", path, key);
+    let status = Command::new("minisign")
+        .arg("-S")
+        .arg("-s")
+        .arg(key)
+        .arg("-m")
+        .arg(path)
+        .arg("-x")
+        .arg(&signature)
+        .status()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::Integrity("`minisign` was not found in PATH; install it or unset signing_key_path".to_string())
+            } else {
+                Error::Subprocess(e)
+            }
+        })?;
+    if !status.success() {
+        return Err(Error::Integrity(format!(
+            "minisign failed to sign '{}' ({})",
+            path.display(),
+            status
+        )));
+    }
+    Ok(signature)
+}
+
+/// [`verify`] for artifacts that were sealed: `path` with neither a checksum sidecar nor a
+/// signature passes unchecked. Returns whether it was checked.
+pub fn verify_if_sealed(path: &Path, settings: &Settings) -> Result<bool, Error> {
+    if !checksum_path(path).exists() && !signature_path(path).exists() {
+        return Ok(false);
+    }
+    verify(path, settings)?;
+    Ok(true)
+}
+
+/// Checks `path` against its checksum sidecar and, if present, its signature.
+///
+/// A missing checksum sidecar is an error. A signature is required only when
+/// `signing_public_key` is configured; an existing signature without a configured key
+/// cannot be checked and is reported as an error rather than silently accepted.
+pub fn verify(path: &Path, settings: &Settings) -> Result<(), Error> {
+    let checksum = checksum_path(path);
+    let recorded = fs::read_to_string(&checksum).map_err(|_| {
+        Error::Integrity(format!("no checksum sidecar found at '{}'", checksum.display()))
+    })?;
+    let expected = recorded.split_whitespace().next().unwrap_or("").to_lowercase();
+    let actual = sha256_file(path)?;
+    if expected != actual {
+        return Err(Error::Integrity(format!(
+            "'{}' does not match its checksum (expected {}, got {})",
+            path.display(),
+            expected,
+            actual
+        )));
+    }
+
+    let signature = signature_path(path);
+    match (&settings.signing_public_key, signature.exists()) {
+        (Some(public_key), true) => {
+            let public_key = PublicKey::from_base64(public_key.trim())
+                .map_err(|e| Error::Integrity(format!("invalid signing_public_key: {}", e)))?;
+            let signature = Signature::decode(&fs::read_to_string(&signature)?)
+                .map_err(|e| Error::Integrity(format!("invalid signature file: {}", e)))?;
+            public_key
+                .verify(&fs::read(path)?, &signature, false)
+                .map_err(|e| Error::Integrity(format!("signature check failed for '{}': {}", path.display(), e)))?;
+            log::info!("Signature verified for {:?}", path);
+        }
+        (Some(_), false) => {
+            return Err(Error::Integrity(format!("'{}' is not signed", path.display())));
+        }
+        (None, true) => {
+            return Err(Error::Integrity(format!(
+                "'{}' is signed but no signing_public_key is configured to check it",
+                path.display()
+            )));
+        }
+        (None, false) => {}
+    }
+    Ok(())
+}
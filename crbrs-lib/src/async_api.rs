@@ -0,0 +1,55 @@
+// FILE: crbrs-lib/src/async_api.rs
+
+//! Async versions of the main entry points, for applications running on tokio (a GUI, say),
+//! behind the `async` feature.
+//!
+//! The library itself blocks: compilers are subprocesses waited on, and network access uses
+//! reqwest's blocking client, which panics when used on a runtime thread. Each function here
+//! runs its blocking counterpart on tokio's blocking thread pool, so the caller's runtime keeps
+//! running, and needs to be called from within a runtime. Dropping the returned future does not
+//! stop the work; cancel it with [`crate::cancel::token`], as for the blocking functions.
+
+use crate::{Error, Manifest, Settings};
+use std::path::PathBuf;
+
+/// Runs `f` on the blocking thread pool. Panics in `f` resume in the caller.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T, Error> + Send + 'static) -> Result<T, Error> {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(_) => Err(Error::Cancelled), // The runtime is shutting down
+    }
+}
+
+/// [`crate::compile_file`] without blocking the runtime.
+pub async fn compile_file_async(
+    input_file: PathBuf,
+    output_log: Option<PathBuf>,
+    compiler_id: Option<String>,
+    settings: Settings,
+) -> Result<(), Error> {
+    blocking(move || crate::compile_file(input_file, output_log, compiler_id, &settings)).await
+}
+
+/// [`crate::compile_for_download`] without blocking the runtime.
+pub async fn compile_for_download_async(
+    input_file: PathBuf,
+    output_log: Option<PathBuf>,
+    compiler_id: Option<String>,
+    image_output: Option<PathBuf>,
+    settings: Settings,
+) -> Result<PathBuf, Error> {
+    blocking(move || crate::compile_for_download(input_file, output_log, compiler_id, image_output, &settings)).await
+}
+
+/// [`crate::installer::fetch_manifest`] from the settings' repository, without blocking the
+/// runtime.
+pub async fn fetch_manifest_async(settings: Settings) -> Result<Manifest, Error> {
+    blocking(move || crate::installer::fetch_manifest(&settings.compiler_repository_url, &settings.network)).await
+}
+
+/// [`crate::installer::install_compiler`] without blocking the runtime. Returns the settings
+/// with the compiler added (they are saved, as by the blocking version).
+pub async fn install_compiler_async(mut settings: Settings, compiler_id: String) -> Result<Settings, Error> {
+    blocking(move || crate::installer::install_compiler(&mut settings, &compiler_id).map(|()| settings)).await
+}
@@ -0,0 +1,128 @@
+// FILE: crbrs-lib/src/bench.rs
+
+//! `crbrs bench compile`: how long compiling a file takes, to judge regressions and whether
+//! keeping compilers running between builds would pay off.
+//!
+//! The compiler runs `iterations` times without the usual output, history or build state. The
+//! first run is reported as cold (Wine may still have to start its server, files are not
+//! cached yet), the others as warm. For Wine compilers, launching a trivial Windows program
+//! (`cmd /c exit`) the same number of times gives the Wine startup overhead included in every
+//! compile. Parsing the source with the built-in parser is timed as well.
+
+use crate::adapter::{adapter_for, CompilerOutcome};
+use crate::cancel;
+use crate::compiler::{compiler_command, resolve_compiler, uses_wine};
+use crate::syntax::parser::parse;
+use crate::{Error, Settings};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Timings of repeated runs, in milliseconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub runs: usize,
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Stats {
+    /// `None` without any runs.
+    pub fn new(durations: &[Duration]) -> Option<Self> {
+        let mut ms: Vec<f64> = durations.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        ms.sort_by(f64::total_cmp);
+        let runs = ms.len();
+        let median = match runs {
+            0 => return None,
+            n if n % 2 == 1 => ms[n / 2],
+            n => (ms[n / 2 - 1] + ms[n / 2]) / 2.0,
+        };
+        Some(Stats { runs, min_ms: ms[0], median_ms: median, mean_ms: ms.iter().sum::<f64>() / runs as f64, max_ms: ms[runs - 1] })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompileBench {
+    pub file: PathBuf,
+    pub compiler_id: String,
+    pub compiler_version: String,
+    pub uses_wine: bool,
+    pub iterations: usize,
+    /// Whether every run compiled the file without errors.
+    pub succeeded: bool,
+    /// Parsing the source with the built-in parser.
+    pub parse: Stats,
+    /// The first compile.
+    pub cold_compile_ms: f64,
+    /// The other compiles; `None` with a single iteration.
+    pub warm_compile: Option<Stats>,
+    /// Launching a trivial program under Wine; `None` for native compilers.
+    pub wine_startup: Option<Stats>,
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (Duration, T) {
+    let start = Instant::now();
+    let result = f();
+    (start.elapsed(), result)
+}
+
+/// Runs the compiler once; whether it compiled the file without errors.
+pub(crate) fn compile_once(file: &Path, compiler_id: &str, info: &crate::CompilerInfo, settings: &Settings) -> Result<bool, Error> {
+    let (mut cmd, _) = compiler_command(compiler_id, info, file, None, settings, false)?;
+    let output = cancel::output(&mut cmd)?;
+    let outcome = adapter_for(info.family).parse_output(file, &String::from_utf8_lossy(&output.stdout));
+    Ok(match outcome {
+        CompilerOutcome::Success => true,
+        CompilerOutcome::Failed(_) => false,
+        CompilerOutcome::Unrecognized => output.status.success(),
+    })
+}
+
+/// Benchmarks compiling `file` `iterations` times (at least once).
+pub fn compile(file: &Path, compiler_id: Option<&str>, iterations: usize, settings: &Settings) -> Result<CompileBench, Error> {
+    let iterations = iterations.max(1);
+    let (compiler_id, info) = resolve_compiler(file, compiler_id, settings)?;
+    let source = String::from_utf8_lossy(&fs::read(file)?).into_owned();
+    log::info!("Benchmarking {:?} with '{}', {} iterations", file, compiler_id, iterations);
+
+    let parse_times: Vec<Duration> = (0..iterations).map(|_| time(|| parse(&source)).0).collect();
+
+    let mut compile_times = Vec::new();
+    let mut succeeded = true;
+    for run in 0..iterations {
+        let (elapsed, ok) = time(|| compile_once(file, &compiler_id, info, settings));
+        log::debug!("Compile run {}: {:?}", run + 1, elapsed);
+        succeeded &= ok?;
+        compile_times.push(elapsed);
+    }
+
+    let wine_startup = if uses_wine(info) {
+        let wine = settings.wine_path.as_deref().unwrap_or("wine");
+        let mut times = Vec::new();
+        for _ in 0..iterations {
+            let (elapsed, status) = time(|| cancel::output(Command::new(wine).args(["cmd", "/c", "exit"])));
+            status?;
+            times.push(elapsed);
+        }
+        Stats::new(&times)
+    } else {
+        None
+    };
+
+    Ok(CompileBench {
+        file: file.to_path_buf(),
+        compiler_id: compiler_id.clone(),
+        compiler_version: info.version.clone(),
+        uses_wine: uses_wine(info),
+        iterations,
+        succeeded,
+        parse: Stats::new(&parse_times).expect("at least one iteration"),
+        cold_compile_ms: compile_times[0].as_secs_f64() * 1000.0,
+        warm_compile: Stats::new(&compile_times[1..]),
+        wine_startup,
+    })
+}
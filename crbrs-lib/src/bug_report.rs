@@ -0,0 +1,192 @@
+// FILE: crbrs-lib/src/bug_report.rs
+
+//! `crbrs report-bug`: one zip with everything needed to look into a problem, so users who are
+//! not developers can attach it to a GitHub issue instead of answering follow-up questions.
+//!
+//! The bundle holds `system.txt` (crbrs, OS, Wine and compiler details), the config file with
+//! secrets and URL credentials removed, the log of the last run and, on request, the source
+//! file that failed. The home directory is replaced by `~` in everything but the source file.
+
+use crate::config;
+use crate::installer::compiler_statuses;
+use crate::structured_log::last_run_path;
+use crate::validate::find_program;
+use crate::{Error, Settings};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use zip::write::SimpleFileOptions;
+
+/// Where to report bugs; printed with the bundle path.
+pub const ISSUES_URL: &str = "https://github.com/RileyLeff/crbrs/issues/new";
+
+/// Config keys whose values are replaced entirely.
+const SECRET_KEYS: [&str; 1] = ["signing_key_path"];
+
+const REDACTED: &str = "<redacted>";
+
+/// A written bug report.
+#[derive(Debug, Clone)]
+pub struct BugReport {
+    pub path: PathBuf,
+    /// Names of the files in the zip.
+    pub entries: Vec<String>,
+}
+
+/// Replaces the home directory in `text` with `~`.
+fn redact_home(text: &str) -> String {
+    match directories::BaseDirs::new() {
+        Some(dirs) if dirs.home_dir().components().count() > 1 => {
+            text.replace(dirs.home_dir().to_string_lossy().as_ref(), "~")
+        }
+        _ => text.to_string(),
+    }
+}
+
+/// Drops user names, passwords and query strings (often tokens) from URLs.
+fn redact_url(text: &str) -> Option<String> {
+    let mut url = reqwest::Url::parse(text).ok()?;
+    if url.username().is_empty() && url.password().is_none() && url.query().is_none() {
+        return None;
+    }
+    url.set_username("").ok()?;
+    url.set_password(None).ok()?;
+    url.set_query(None);
+    Some(url.to_string())
+}
+
+/// Redacts `value` in place, noting each replaced string so it can be removed from logs too.
+fn redact_value(key: &str, value: &mut toml::Value, replaced: &mut Vec<(String, String)>) {
+    match value {
+        toml::Value::String(text) => {
+            let redacted = if SECRET_KEYS.contains(&key) { Some(REDACTED.to_string()) } else { redact_url(text) };
+            if let Some(redacted) = redacted.filter(|_| !text.is_empty()) {
+                replaced.push((std::mem::replace(text, redacted.clone()), redacted));
+            }
+        }
+        toml::Value::Array(items) => items.iter_mut().for_each(|item| redact_value(key, item, replaced)),
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                redact_value(key, value, replaced);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The config file as it would be shared (secrets and URL credentials removed), and the
+/// replacements made.
+fn redacted_config() -> Result<(String, Vec<(String, String)>), Error> {
+    let path = config::get_config_file_path()?;
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok((format!("# No config file at {}; defaults are in use.\n", path.display()), Vec::new()));
+    };
+    Ok(match text.parse::<toml::Table>() {
+        Ok(table) => {
+            let mut value = toml::Value::Table(table);
+            let mut replaced = Vec::new();
+            redact_value("", &mut value, &mut replaced);
+            (toml::to_string(&value).unwrap_or_default(), replaced)
+        }
+        // The file may hold secrets, so only the parse error is included.
+        Err(e) => (format!("# {} is not valid TOML:\n# {}\n", path.display(), e.to_string().replace('\n', "\n# ")), Vec::new()),
+    })
+}
+
+fn wine_details(settings: &Settings) -> String {
+    if cfg!(windows) {
+        return "not needed on Windows".to_string();
+    }
+    let wine = settings.wine_path.as_deref().unwrap_or("wine");
+    let Some(path) = find_program(wine) else {
+        return format!("'{}' not found", wine);
+    };
+    match Command::new(&path).arg("--version").output() {
+        Ok(output) => format!("{} ({})", String::from_utf8_lossy(&output.stdout).trim(), path.display()),
+        Err(e) => format!("{} cannot be run: {}", path.display(), e),
+    }
+}
+
+/// Versions, platform, Wine and installed compilers.
+fn system_info(settings: &Settings) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "crbrs version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(out, "OS: {} ({}, {})", std::env::consts::OS, std::env::consts::FAMILY, std::env::consts::ARCH);
+    let _ = writeln!(out, "Wine: {}", wine_details(settings));
+    if let Some(prefix) = std::env::var_os("WINEPREFIX") {
+        let _ = writeln!(out, "WINEPREFIX: {}", Path::new(&prefix).display());
+    }
+    if let Some(home) = config::portable_home() {
+        let _ = writeln!(out, "Portable home: {}", home.display());
+    }
+    if let Some(profile) = &settings.active_profile {
+        let _ = writeln!(out, "Profile: {}", profile);
+    }
+    let _ = writeln!(out, "\nInstalled compilers:");
+    match compiler_statuses(settings) {
+        Ok(statuses) if statuses.is_empty() => out.push_str("  (none)\n"),
+        Ok(statuses) => {
+            for status in statuses {
+                let info = &settings.installed_compilers[&status.id];
+                let _ = writeln!(
+                    out,
+                    "  {} ({}){}{}: {}",
+                    status.id,
+                    status.version,
+                    if info.requires_wine { ", Wine" } else { "" },
+                    if status.linked { ", linked" } else { "" },
+                    if status.executable_exists { "executable OK" } else { "executable missing" },
+                );
+            }
+        }
+        Err(e) => {
+            let _ = writeln!(out, "  (cannot be listed: {})", e);
+        }
+    }
+    let _ = writeln!(out, "\nFile associations:");
+    let mut associations: Vec<_> = settings.file_associations.iter().collect();
+    associations.sort();
+    if associations.is_empty() {
+        out.push_str("  (none)\n");
+    }
+    for (extension, id) in associations {
+        let _ = writeln!(out, "  .{} -> {}", extension, id);
+    }
+    out
+}
+
+/// Writes the bug report zip to `output`, including `source` if given.
+pub fn create(settings: &Settings, output: &Path, source: Option<&Path>) -> Result<BugReport, Error> {
+    let (config, replaced) = redacted_config()?;
+    // Logs and settings dumps can contain the same values.
+    let redact = |text: &str| {
+        let text = replaced.iter().fold(text.to_string(), |text, (secret, redacted)| text.replace(secret, redacted));
+        redact_home(&text).into_bytes()
+    };
+    let mut files = vec![
+        ("system.txt".to_string(), redact(&system_info(settings))),
+        ("config.toml".to_string(), redact(&config)),
+    ];
+    match fs::read_to_string(last_run_path()?) {
+        Ok(log) => files.push(("last-run.jsonl".to_string(), redact(&log))),
+        Err(e) => log::debug!("No last run log to include: {}", e),
+    }
+    if let Some(source) = source {
+        let name = source.file_name().unwrap_or_default().to_string_lossy();
+        files.push((format!("source/{}", name), fs::read(source)?));
+    }
+
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut zip = zip::ZipWriter::new(fs::File::create(output)?);
+    for (name, contents) in &files {
+        zip.start_file(name.as_str(), SimpleFileOptions::default())?;
+        zip.write_all(contents)?;
+    }
+    zip.finish()?;
+    log::info!("Wrote bug report {:?} ({} files)", output, files.len());
+    Ok(BugReport { path: output.to_path_buf(), entries: files.into_iter().map(|(name, _)| name).collect() })
+}
@@ -0,0 +1,373 @@
+// FILE: crbrs-lib/src/build.rs
+
+//! Batch compiles: `crbrs compile` with several files or whole directories, e.g. a nightly CI
+//! job building every station program in a repository.
+//!
+//! Every file is compiled even if others fail, and the run ends with a [`BuildSummary`]:
+//! files compiled, skipped as unchanged (incremental builds) and failed, lint warnings, wall time
+//! and a per-compiler breakdown, plus the worst [`Status`] for the exit code.
+//!
+//! [`build_files`] runs up to [`BuildOptions::jobs`] compilers at once. Starting Wine dominates
+//! the time of most compiles, so a batch of small programs builds several times faster in
+//! parallel. Results are still reported and summarized in file order.
+
+use crate::build_state;
+use crate::compiler::{compile_file_impl, compile_for_download_impl, resolve_compiler};
+use crate::syntax::lint::Severity;
+use crate::{CompilationErrorDetail, Error, Settings};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Breakdown key for files whose compiler could not be determined.
+pub const UNRESOLVED_COMPILER: &str = "(unresolved)";
+
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    /// Compiler for every file, instead of each file's directive or association.
+    pub compiler_id: Option<String>,
+    /// Compile for download, producing (and sealing) each file's image.
+    pub image: bool,
+    /// Skip files whose inputs (the file and its includes) are unchanged since their last
+    /// successful build the same way; see [`crate::build_state`].
+    pub incremental: bool,
+    /// Calibration file whose constants are injected into every program; the calibrated
+    /// copies are compiled instead (see [`crate::calibration`]).
+    pub calibration: Option<PathBuf>,
+    /// Files compiled at the same time.
+    pub jobs: usize,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions { compiler_id: None, image: false, incremental: false, calibration: None, jobs: 1 }
+    }
+}
+
+/// Overall result, ordered from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    /// Everything compiled, but the linter has warnings.
+    Warnings,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileOutcome {
+    Compiled,
+    /// Unchanged since its last successful build; not compiled again.
+    Cached,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileResult {
+    pub file: PathBuf,
+    pub compiler_id: Option<String>,
+    pub outcome: FileOutcome,
+    /// Why the file failed.
+    pub error: Option<String>,
+    /// The failure's code (see [`crate::explain`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    /// The compiler's errors, when it failed with errors that could be parsed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<CompilationErrorDetail>,
+    /// Lint warnings in the source.
+    pub warnings: usize,
+    pub duration_ms: u64,
+}
+
+impl FileResult {
+    pub fn status(&self) -> Status {
+        match self.outcome {
+            FileOutcome::Failed => Status::Failed,
+            _ if self.warnings > 0 => Status::Warnings,
+            _ => Status::Ok,
+        }
+    }
+}
+
+/// Per-compiler counts in a [`BuildSummary`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompilerTally {
+    pub compiled: usize,
+    pub cached: usize,
+    pub failed: usize,
+    pub warnings: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildSummary {
+    /// Worst status of any file.
+    pub status: Status,
+    pub total: usize,
+    pub compiled: usize,
+    pub cached: usize,
+    pub failed: usize,
+    pub warnings: usize,
+    pub wall_time_ms: u64,
+    /// Keyed by compiler ID, or [`UNRESOLVED_COMPILER`].
+    pub by_compiler: BTreeMap<String, CompilerTally>,
+    pub files: Vec<FileResult>,
+}
+
+impl BuildSummary {
+    pub fn new(files: Vec<FileResult>, wall_time: Duration) -> Self {
+        let mut by_compiler: BTreeMap<String, CompilerTally> = BTreeMap::new();
+        for file in &files {
+            let tally = by_compiler.entry(file.compiler_id.clone().unwrap_or_else(|| UNRESOLVED_COMPILER.to_string())).or_default();
+            match file.outcome {
+                FileOutcome::Compiled => tally.compiled += 1,
+                FileOutcome::Cached => tally.cached += 1,
+                FileOutcome::Failed => tally.failed += 1,
+            }
+            tally.warnings += file.warnings;
+        }
+        let count = |outcome| files.iter().filter(|f| f.outcome == outcome).count();
+        BuildSummary {
+            status: files.iter().map(FileResult::status).max().unwrap_or(Status::Ok),
+            total: files.len(),
+            compiled: count(FileOutcome::Compiled),
+            cached: count(FileOutcome::Cached),
+            failed: count(FileOutcome::Failed),
+            warnings: files.iter().map(|f| f.warnings).sum(),
+            wall_time_ms: wall_time.as_millis() as u64,
+            by_compiler,
+            files,
+        }
+    }
+
+    /// Writes the summary as JSON to `path`.
+    pub fn write_json(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| Error::Io(e.into()))?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// `Err(BuildFailed)` if any file failed.
+    pub fn result(&self) -> Result<(), Error> {
+        if self.failed > 0 {
+            return Err(Error::BuildFailed { failed: self.failed, total: self.total });
+        }
+        Ok(())
+    }
+}
+
+/// Whether `input` is a wildcard pattern rather than a path.
+pub fn is_pattern(input: &Path) -> bool {
+    input.to_string_lossy().contains(['*', '?'])
+}
+
+/// Whether `name` matches `pattern`, where `*` is any run of characters and `?` any one.
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| wildcard_match(rest, &name[skip..])),
+        Some((c, rest)) => name.split_first().is_some_and(|(n, name)| (*c == '?' || c == n) && wildcard_match(rest, name)),
+    }
+}
+
+/// Whether the path `components` match the pattern's, where `**` is any number of directories.
+fn components_match(pattern: &[String], components: &[String]) -> bool {
+    match pattern.split_first() {
+        None => components.is_empty(),
+        Some((first, rest)) if first == "**" => (0..=components.len()).any(|skip| components_match(rest, &components[skip..])),
+        Some((first, rest)) => components.split_first().is_some_and(|(component, components)| {
+            wildcard_match(&first.chars().collect::<Vec<_>>(), &component.chars().collect::<Vec<_>>())
+                && components_match(rest, components)
+        }),
+    }
+}
+
+/// The programs matching `pattern` (e.g. `stations/**/*.cr1x`), searched for below the part of
+/// the pattern without wildcards.
+fn expand_pattern(pattern: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut base = PathBuf::new();
+    let mut wild = Vec::new();
+    for component in pattern.components() {
+        let text = component.as_os_str().to_string_lossy().into_owned();
+        if wild.is_empty() && !is_pattern(Path::new(&text)) {
+            base.push(component);
+        } else {
+            wild.push(text);
+        }
+    }
+    let search = if base.as_os_str().is_empty() { Path::new(".") } else { base.as_path() };
+    if !search.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut matched = Vec::new();
+    for file in crate::docgen::find_programs(search)? {
+        let relative = file.strip_prefix(search).unwrap_or(&file);
+        let components: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+        if components_match(&wild, &components) {
+            matched.push(base.join(relative));
+        }
+    }
+    Ok(matched)
+}
+
+/// The CRBasic programs to build: files as given, directories searched recursively, and
+/// wildcard patterns (`*`, `?`, `**` for any depth) expanded, for shells that don't.
+pub fn collect_inputs(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let found = if !input.exists() && is_pattern(input) {
+            let matched = expand_pattern(input)?;
+            if matched.is_empty() {
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("No programs match: {}", input.display()),
+                )));
+            }
+            matched
+        } else if !input.exists() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Input file not found: {}", input.display()),
+            )));
+        } else {
+            crate::docgen::find_programs(input)?
+        };
+        for file in found {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn lint_warnings(file: &Path, settings: &Settings) -> usize {
+    let Ok(source) = fs::read(file) else { return 0 };
+    let diagnostics = crate::lint::lint(&String::from_utf8_lossy(&source), Some(file), settings);
+    diagnostics.iter().filter(|d| d.severity == Severity::Warning).count()
+}
+
+/// One-line reason for a failed file, with at most `max_diagnostics` groups of compiler errors
+/// (see [`crate::diagnostics::condense`]).
+fn describe(error: &Error, max_diagnostics: usize) -> String {
+    match error {
+        Error::CompilationFailed { errors, .. } if !errors.is_empty() => {
+            let condensed = crate::diagnostics::condense(errors, max_diagnostics);
+            let mut parts: Vec<String> = condensed
+                .groups
+                .iter()
+                .map(|g| match g.lines.as_slice() {
+                    [] => g.message.clone(),
+                    [line] => format!("line {}: {}", line, g.message),
+                    lines => format!("lines {}: {}", lines.iter().map(u32::to_string).collect::<Vec<_>>().join(", "), g.message),
+                })
+                .collect();
+            if condensed.hidden > 0 {
+                parts.push(format!("…and {} more", condensed.hidden));
+            }
+            parts.join("; ")
+        }
+        other => other.to_string().lines().next().unwrap_or_default().to_string(),
+    }
+}
+
+/// Builds one file of a batch. Failures are reported in the result rather than returned.
+pub fn build_file(file: &Path, options: &BuildOptions, settings: &Settings) -> FileResult {
+    let start = Instant::now();
+    let resolved = resolve_compiler(file, options.compiler_id.as_deref(), settings);
+    let compiler_id = resolved.as_ref().ok().map(|(id, _)| id.clone());
+    let outcome = resolved.and_then(|(id, info)| {
+        let calibrated = options.calibration.as_deref().map(|calibration| crate::calibration::apply(file, calibration)).transpose()?;
+        let file = calibrated.as_deref().unwrap_or(file);
+        if options.incremental && build_state::is_up_to_date(&build_state::load(), file, &id, &info.version, options.image) {
+            log::info!("{:?} and its includes are unchanged since the last build; skipping.", file);
+            return Ok(FileOutcome::Cached);
+        }
+        let outputs = if options.image {
+            vec![compile_for_download_impl(file, None, Some(&id), None, settings)?]
+        } else {
+            compile_file_impl(file, None, Some(&id), settings)?;
+            Vec::new()
+        };
+        if let Err(e) = build_state::record(file, &id, &info.version, options.image, outputs) {
+            log::warn!("Could not record build state of {:?}: {}", file, e);
+        }
+        Ok(FileOutcome::Compiled)
+    });
+    let (outcome, error, error_code, errors) = match outcome {
+        Ok(outcome) => (outcome, None, None, Vec::new()),
+        Err(e) => {
+            log::error!("Building {:?} failed: {}", file, e);
+            let errors = match &e {
+                Error::CompilationFailed { errors, .. } => errors.clone(),
+                _ => Vec::new(),
+            };
+            (FileOutcome::Failed, Some(describe(&e, settings.max_diagnostics)), Some(e.code().to_string()), errors)
+        }
+    };
+    FileResult {
+        file: file.to_path_buf(),
+        compiler_id,
+        outcome,
+        error,
+        error_code,
+        errors,
+        warnings: lint_warnings(file, settings),
+        duration_ms: start.elapsed().as_millis() as u64,
+    }
+}
+
+/// Builds `files` with up to `options.jobs` of them compiling at once, and returns their
+/// results in file order. `done` is called with each result, also in file order, as soon as
+/// that file and every file before it are built. Once the run is cancelled no more files are
+/// started, and after the running ones stop this fails with [`Error::Cancelled`] (without
+/// reporting the interrupted files).
+pub fn build_files(
+    files: &[PathBuf],
+    options: &BuildOptions,
+    settings: &Settings,
+    mut done: impl FnMut(&FileResult),
+) -> Result<Vec<FileResult>, Error> {
+    let jobs = options.jobs.clamp(1, files.len().max(1));
+    log::info!("Building {} file(s), {} at a time", files.len(), jobs);
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<FileResult>> = files.iter().map(|_| None).collect();
+    let mut reported = 0;
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..jobs {
+            let (sender, next) = (sender.clone(), &next);
+            scope.spawn(move || {
+                while !crate::cancel::token().is_cancelled() {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = files.get(index) else { break };
+                    if sender.send((index, build_file(file, options, settings))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        for (index, result) in receiver {
+            if crate::cancel::token().is_cancelled() {
+                continue; // Let the running files stop; report nothing more
+            }
+            results[index] = Some(result);
+            while let Some(Some(result)) = results.get(reported) {
+                done(result);
+                reported += 1;
+            }
+        }
+    });
+    crate::cancel::check()?;
+    Ok(results.into_iter().map(|result| result.expect("every file is built unless cancelled")).collect())
+}
@@ -0,0 +1,173 @@
+// FILE: crbrs-lib/src/build_state.rs
+
+//! What each program was last built from, kept as `build-state.json` in the data directory so
+//! incremental builds only recompile programs whose inputs changed.
+//!
+//! A program's inputs are its own source and everything it pulls in through `Include`,
+//! transitively. Includes name a logger drive (`Include "CPU:common.cr1"`); the file is looked up
+//! next to the including file. Each input is recorded with its modification time, size and
+//! SHA256: if time and size are unchanged the file is trusted, otherwise its hash decides.
+//! Includes that could not be found are recorded too, so creating one triggers a rebuild.
+
+use crate::config;
+use crate::docgen::includes;
+use crate::syntax::parser::parse;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuildState {
+    /// Keyed by the absolute path of the program.
+    #[serde(default)]
+    pub programs: BTreeMap<String, ProgramState>,
+}
+
+/// The last successful build of a program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramState {
+    pub compiler_id: String,
+    pub version: String,
+    /// Built for download; `outputs` holds the image.
+    pub image: bool,
+    /// Keyed by absolute path; the program itself and its transitive includes.
+    pub inputs: BTreeMap<String, InputStamp>,
+    #[serde(default)]
+    pub outputs: Vec<PathBuf>,
+}
+
+/// State of one input file; all `None` if it did not exist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputStamp {
+    pub modified_ms: Option<u64>,
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+}
+
+fn key(path: &Path) -> String {
+    crate::history::file_key(path)
+}
+
+/// Modification time and size of `path`, if it exists.
+pub(crate) fn quick_stamp(path: &Path) -> Option<(Option<u64>, u64)> {
+    let metadata = fs::metadata(path).ok().filter(|m| m.is_file())?;
+    let modified = metadata.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_millis() as u64);
+    Some((modified, metadata.len()))
+}
+
+fn stamp(path: &Path) -> InputStamp {
+    match quick_stamp(path) {
+        Some((modified_ms, size)) => InputStamp {
+            modified_ms,
+            size: Some(size),
+            sha256: crate::artifact::sha256_file(path).ok(),
+        },
+        None => InputStamp { modified_ms: None, size: None, sha256: None },
+    }
+}
+
+/// Whether `path` still matches `recorded`.
+fn unchanged(path: &Path, recorded: &InputStamp) -> bool {
+    match (quick_stamp(path), &recorded.sha256) {
+        (None, None) => true,
+        (Some((modified, size)), Some(hash)) => {
+            (modified.is_some() && modified == recorded.modified_ms && Some(size) == recorded.size)
+                || crate::artifact::sha256_file(path).ok().as_ref() == Some(hash)
+        }
+        _ => false,
+    }
+}
+
+/// Finds `name` in `dir`, ignoring case like the logger's file system does.
+pub(crate) fn find_in(dir: &Path, name: &str) -> PathBuf {
+    let exact = dir.join(name);
+    if exact.is_file() {
+        return exact;
+    }
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.file_name().is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case(name)))
+        .unwrap_or(exact)
+}
+
+/// `program` and every file it includes, transitively. Missing includes are listed too.
+pub fn inputs(program: &Path) -> Vec<PathBuf> {
+    let mut found = vec![program.to_path_buf()];
+    let mut next = 0;
+    while next < found.len() {
+        let file = found[next].clone();
+        next += 1;
+        let Ok(source) = fs::read(&file) else { continue };
+        let dir = file.parent().unwrap_or(Path::new(""));
+        for target in includes(&parse(&String::from_utf8_lossy(&source))) {
+            let include = crate::project::find_include(dir, &target);
+            if !found.contains(&include) {
+                found.push(include);
+            }
+        }
+    }
+    found
+}
+
+pub fn state_path() -> Result<PathBuf, Error> {
+    Ok(config::data_dir()?.join("build-state.json"))
+}
+
+/// The recorded state, empty if there is none yet.
+pub fn load() -> BuildState {
+    let Ok(path) = state_path() else { return BuildState::default() };
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable build state {:?}: {}", path, e);
+            BuildState::default()
+        }),
+        Err(_) => BuildState::default(),
+    }
+}
+
+pub fn save(state: &BuildState) -> Result<(), Error> {
+    let path = state_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let text = serde_json::to_string_pretty(state).map_err(|e| Error::Io(e.into()))?;
+    fs::write(&path, text)?;
+    Ok(())
+}
+
+/// Whether `program` was last built the same way and none of its inputs changed since.
+pub fn is_up_to_date(state: &BuildState, program: &Path, compiler_id: &str, version: &str, image: bool) -> bool {
+    let Some(last) = state.programs.get(&key(program)) else { return false };
+    if last.compiler_id != compiler_id || last.version != version || last.image != image {
+        return false;
+    }
+    if !last.outputs.iter().all(|output| output.is_file()) {
+        return false;
+    }
+    // The include set itself may have changed, e.g. a new Include line.
+    let current: Vec<String> = inputs(program).iter().map(|p| key(p)).collect();
+    current.len() == last.inputs.len()
+        && current.iter().all(|input| last.inputs.get(input).is_some_and(|stamp| unchanged(Path::new(input), stamp)))
+}
+
+/// Held while the state is read, changed and written back by the files of a parallel build.
+static UPDATE: Mutex<()> = Mutex::new(());
+
+/// Notes a successful build of `program`.
+pub fn record(program: &Path, compiler_id: &str, version: &str, image: bool, outputs: Vec<PathBuf>) -> Result<(), Error> {
+    let _update = UPDATE.lock().unwrap_or_else(|e| e.into_inner());
+    let mut state = load();
+    let inputs = inputs(program).iter().map(|input| (key(input), stamp(input))).collect();
+    state.programs.insert(
+        key(program),
+        ProgramState { compiler_id: compiler_id.to_string(), version: version.to_string(), image, inputs, outputs },
+    );
+    save(&state)
+}
@@ -0,0 +1,160 @@
+// FILE: crbrs-lib/src/calibration.rs
+
+//! Per-station calibration files for `crbrs build --with-calibration`.
+//!
+//! A calibration file is TOML: top-level values become constants of the same name, and the
+//! values of a table become constants prefixed with the table name, so
+//!
+//! ```toml
+//! Station = "Station 12"
+//!
+//! [CS655_0]
+//! Offset = 0.012
+//! Gain = 1.034
+//! ```
+//!
+//! declares `Station`, `CS655_0_Offset` and `CS655_0_Gain`. [`apply`] writes a calibrated copy
+//! of a program (see `crbrs_syntax::calibration`), which is what gets compiled. Copies go to
+//! the data directory along with the files the program includes, never into the source tree,
+//! so they can't replace a hand-written program and build in read-only checkouts too.
+
+use crate::project::Project;
+use crate::syntax::calibration::{self, Constant};
+use crate::{build_state, config, Error};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{ImDocument, Item, Value};
+
+/// `value` as a CRBasic literal.
+fn literal(value: &Value) -> Result<String, String> {
+    match value {
+        Value::Integer(i) => Ok(i.value().to_string()),
+        Value::Float(f) if f.value().is_finite() => Ok(f.value().to_string()),
+        Value::Boolean(b) => Ok(if *b.value() { "True" } else { "False" }.to_string()),
+        Value::String(s) if !s.value().contains(['"', '\n', '\r']) => Ok(format!("\"{}\"", s.value())),
+        Value::String(_) => Err("strings cannot contain quotes or line breaks".to_string()),
+        other => Err(format!("{} values cannot be constants", other.type_name())),
+    }
+}
+
+/// Reads the constants of a calibration file, in file order.
+pub fn load(path: &Path) -> Result<Vec<Constant>, Error> {
+    let fail = |e: String| Error::Calibration(format!("{}: {}", path.display(), e));
+    let text = fs::read_to_string(path)?;
+    let document = ImDocument::parse(text.as_str()).map_err(|e| fail(e.message().to_string()))?;
+    let mut constants = Vec::new();
+    let mut add = |name: String, value: &Value| -> Result<(), Error> {
+        let value = literal(value).map_err(|e| fail(format!("{}: {}", name, e)))?;
+        if constants.iter().any(|c: &Constant| c.name.eq_ignore_ascii_case(&name)) {
+            return Err(fail(format!("'{}' is defined twice", name)));
+        }
+        constants.push(Constant { name, value });
+        Ok(())
+    };
+    for (key, item) in document.as_table().iter() {
+        match item {
+            Item::Value(Value::InlineTable(table)) => {
+                for (field, value) in table.iter() {
+                    add(format!("{}_{}", key, field), value)?;
+                }
+            }
+            Item::Value(value) => add(key.to_string(), value)?,
+            Item::Table(table) => {
+                for (field, item) in table.iter() {
+                    match item.as_value() {
+                        Some(value) if !value.is_inline_table() => add(format!("{}_{}", key, field), value)?,
+                        _ => return Err(fail(format!("{}.{}: tables can only be one level deep", key, field))),
+                    }
+                }
+            }
+            _ => return Err(fail(format!("{}: arrays of tables cannot be constants", key))),
+        }
+    }
+    Ok(constants)
+}
+
+/// Where calibrated copies are kept.
+pub fn calibrated_root() -> Result<PathBuf, Error> {
+    Ok(config::data_dir()?.join("calibrated"))
+}
+
+/// Directory of the calibrated copies of the programs in `program`'s directory: one per source
+/// directory under [`calibrated_root`], named after it.
+pub fn calibrated_dir(program: &Path) -> Result<PathBuf, Error> {
+    let program = std::path::absolute(program)?;
+    let dir = program.parent().unwrap_or(Path::new(""));
+    let digest = Sha256::digest(dir.to_string_lossy().as_bytes());
+    let hash: String = digest.iter().take(6).map(|byte| format!("{:02x}", byte)).collect();
+    let name = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    Ok(calibrated_root()?.join(format!("{}-{}", name, hash)))
+}
+
+/// Where the copy of `program` calibrated with `calibration` goes: in [`calibrated_dir`], named
+/// after both, e.g. `soil_station12.cr1x`.
+pub fn calibrated_path(program: &Path, calibration: &Path) -> Result<PathBuf, Error> {
+    let stem = program.file_stem().unwrap_or_default().to_string_lossy();
+    let station = calibration.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match program.extension() {
+        Some(extension) => format!("{}_{}.{}", stem, station, extension.to_string_lossy()),
+        None => format!("{}_{}", stem, station),
+    };
+    Ok(calibrated_dir(program)?.join(name))
+}
+
+/// Writes `contents` to `path` unless it already holds them, so incremental builds can skip it.
+fn write_if_changed(path: &Path, contents: &[u8]) -> Result<bool, Error> {
+    if fs::read(path).ok().as_deref() == Some(contents) {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+/// Copies the files `program` includes into `dir`, where its calibrated copy looks for them:
+/// under the same path relative to the program's directory or the project include path they
+/// were found in.
+fn copy_includes(program: &Path, dir: &Path) -> Result<(), Error> {
+    let program_dir = program.parent().unwrap_or(Path::new(""));
+    let include_dirs = match Project::find(program_dir) {
+        Ok(project) => project.map(|project| project.include_dirs()).unwrap_or_default(),
+        Err(_) => Vec::new(), // Reported when the program itself is built
+    };
+    for input in build_state::inputs(program).iter().skip(1).filter(|input| input.is_file()) {
+        let relative = input.strip_prefix(program_dir).ok().or_else(|| include_dirs.iter().find_map(|d| input.strip_prefix(d).ok()));
+        let Some(relative) = relative else {
+            log::warn!("{:?} is not under {:?} or an include path; the calibrated copy will not find it", input, program_dir);
+            continue;
+        };
+        if write_if_changed(&dir.join(relative), &fs::read(input)?)? {
+            log::debug!("Copied {:?} for the calibrated copies in {:?}", input, dir);
+        }
+    }
+    Ok(())
+}
+
+/// Writes the copy of `program` calibrated with `calibration` and returns its path. The copy
+/// is only rewritten if it changed, so incremental builds can skip it; a file at its path that
+/// crbrs did not write is left alone, with an error.
+pub fn apply(program: &Path, calibration: &Path) -> Result<PathBuf, Error> {
+    let constants = load(calibration)?;
+    let program = std::path::absolute(program)?;
+    let source = fs::read_to_string(&program)?;
+    let origin = calibration.file_name().unwrap_or_default().to_string_lossy();
+    let calibrated = calibration::inject(&source, &constants, &origin)
+        .map_err(|e| Error::Calibration(format!("{}: {}", program.display(), e)))?;
+    let path = calibrated_path(&program, calibration)?;
+    if let Ok(existing) = fs::read(&path) {
+        if !calibration::is_calibrated(&String::from_utf8_lossy(&existing)) {
+            return Err(Error::Calibration(format!("{} exists and is not a calibrated copy; not overwriting it", path.display())));
+        }
+    }
+    if write_if_changed(&path, calibrated.as_bytes())? {
+        log::info!("Wrote {:?} with {} calibration constants from {:?}", path, constants.len(), calibration);
+    }
+    copy_includes(&program, path.parent().unwrap_or(Path::new("")))?;
+    Ok(path)
+}
@@ -0,0 +1,181 @@
+// FILE: crbrs-lib/src/cancel.rs
+
+//! Cancelling long operations (downloads, compiles, logger transfers), e.g. on Ctrl-C.
+//!
+//! Operations check the process-wide [`token`] between steps and stop with
+//! [`Error::Cancelled`], cleaning up after themselves: subprocesses are killed, connections
+//! closed and partial files removed. The CLI cancels the token from its Ctrl-C handler; an
+//! embedding host can do the same from its own.
+//!
+//! A thread can also run work under a token of its own with [`scoped`], so one part of a
+//! larger operation (a station in `crbrs fleet`, say) can be stopped without the rest.
+
+use crate::Error;
+use std::io::Read;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::cell::RefCell;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a waiting operation checks for cancellation.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A flag shared by everything working on an operation; cancelling it stops them all.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Lets operations run again, e.g. once a host has dealt with a cancellation.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// `Err(Error::Cancelled)` once the token is cancelled.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The token of this process.
+pub fn token() -> &'static CancelToken {
+    static TOKEN: OnceLock<CancelToken> = OnceLock::new();
+    TOKEN.get_or_init(CancelToken::new)
+}
+
+thread_local! {
+    static SCOPED: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+/// Runs `work` with `scope` cancelling it as well as the process's [`token`], on this thread.
+pub fn scoped<R>(scope: CancelToken, work: impl FnOnce() -> R) -> R {
+    let outer = SCOPED.with(|scoped| scoped.replace(Some(scope)));
+    let result = work();
+    SCOPED.with(|scoped| *scoped.borrow_mut() = outer);
+    result
+}
+
+/// Whether this process's operations, or this thread's [`scoped`] work, are cancelled.
+pub fn is_cancelled() -> bool {
+    token().is_cancelled() || SCOPED.with(|scoped| scoped.borrow().as_ref().is_some_and(CancelToken::is_cancelled))
+}
+
+/// `Err(Error::Cancelled)` once this process's operations, or this thread's [`scoped`] work,
+/// are cancelled.
+pub fn check() -> Result<(), Error> {
+    if is_cancelled() {
+        Err(Error::Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// Sleeps for `duration`, waking early with `Err(Error::Cancelled)` on cancellation.
+pub fn sleep(duration: Duration) -> Result<(), Error> {
+    let mut left = duration;
+    while !left.is_zero() {
+        check()?;
+        let step = left.min(POLL_INTERVAL);
+        thread::sleep(step);
+        left -= step;
+    }
+    check()
+}
+
+/// Waits for `child` to exit, killing it on cancellation.
+pub fn wait(child: &mut Child) -> Result<ExitStatus, Error> {
+    wait_until(child, None)
+}
+
+/// Like [`wait`], but also kills `child` if it is still running at `deadline`, failing with
+/// an [`Error::Subprocess`] of kind [`std::io::ErrorKind::TimedOut`].
+pub fn wait_until(child: &mut Child, deadline: Option<Instant>) -> Result<ExitStatus, Error> {
+    loop {
+        if let Some(status) = child.try_wait().map_err(Error::Subprocess)? {
+            return Ok(status);
+        }
+        if is_cancelled() {
+            log::warn!("Cancelled; killing process {}", child.id());
+            kill(child);
+            let _ = child.wait();
+            return Err(Error::Cancelled);
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            log::warn!("Process {} did not finish in time; killing it", child.id());
+            kill(child);
+            let _ = child.wait();
+            return Err(Error::Subprocess(std::io::Error::new(std::io::ErrorKind::TimedOut, "the process did not finish in time")));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Kills `child` along with its process group, which [`output`] makes it lead, so helpers it
+/// started (Wine's, say) don't outlive it.
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    // SAFETY: kill(2) with a negative pid signals a process group; no memory is involved.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// Like [`Command::output`], but kills the process (and on Unix its process group) on
+/// cancellation.
+pub fn output(command: &mut Command) -> Result<Output, Error> {
+    output_within(command, None)
+}
+
+/// Like [`output`], but also kills the process (group) once it has run for `timeout`; see
+/// [`wait_until`].
+pub fn output_within(command: &mut Command, timeout: Option<Duration>) -> Result<Output, Error> {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    let child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().map_err(Error::Subprocess)?;
+    collect(child, timeout.map(|timeout| Instant::now() + timeout))
+}
+
+/// Like [`Child::wait_with_output`], but kills the process on cancellation.
+pub fn wait_with_output(child: Child) -> Result<Output, Error> {
+    collect(child, None)
+}
+
+fn collect(mut child: Child, deadline: Option<Instant>) -> Result<Output, Error> {
+    // Drain the pipes while waiting, so a chatty process doesn't block on a full pipe.
+    fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut bytes);
+            }
+            bytes
+        })
+    }
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let status = wait_until(&mut child, deadline)?;
+    Ok(Output { status, stdout: stdout.join().unwrap_or_default(), stderr: stderr.join().unwrap_or_default() })
+}
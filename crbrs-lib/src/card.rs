@@ -0,0 +1,260 @@
+// FILE: crbrs-lib/src/card.rs
+
+//! Data cards pulled from a logger (CRD: on a CR1000X/CR6, the SC115 and CF/microSD cards in
+//! general): `crbrs data card` lists the data files on a card and converts them without
+//! LoggerNet's CardConvert.
+//!
+//! A card is either a directory (the mounted card or a copy of it) or a raw image of the
+//! whole card, e.g. from `dd`. Images are FAT12/16/32, with or without a partition table,
+//! and are only ever read. Data files are recognized by their header rather than their name,
+//! since loggers name them after the station and table.
+
+use crate::convert::{self, ConvertFormat, ConvertReport, DataSource, Progress};
+use crate::toa5::split_line;
+use crate::Error;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Campbell data file formats found on cards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DataFormat {
+    Toa5,
+    Tob1,
+    Tob2,
+    Tob3,
+}
+
+impl DataFormat {
+    fn detect(header: &[u8]) -> Option<Self> {
+        [(b"\"TOA5\"", DataFormat::Toa5), (b"\"TOB1\"", DataFormat::Tob1), (b"\"TOB2\"", DataFormat::Tob2), (b"\"TOB3\"", DataFormat::Tob3)]
+            .into_iter()
+            .find(|(tag, _)| header.starts_with(*tag))
+            .map(|(_, format)| format)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DataFormat::Toa5 => "TOA5",
+            DataFormat::Tob1 => "TOB1",
+            DataFormat::Tob2 => "TOB2",
+            DataFormat::Tob3 => "TOB3",
+        }
+    }
+
+    /// Whether [`convert::convert`] reads this format.
+    pub fn convertible(self) -> bool {
+        matches!(self, DataFormat::Toa5 | DataFormat::Tob1 | DataFormat::Tob3)
+    }
+}
+
+/// A data file on a card.
+#[derive(Debug, Clone, Serialize)]
+pub struct CardFile {
+    /// Path on the card, with `/` separators.
+    pub path: String,
+    pub size: u64,
+    pub format: DataFormat,
+    pub station: String,
+    pub table: String,
+}
+
+/// A FAT volume inside an image file, starting at `offset`. Writes are refused, so an image
+/// is never modified.
+struct Volume {
+    file: File,
+    offset: u64,
+}
+
+impl Read for Volume {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for Volume {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "card images are opened read-only"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Volume {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let position = match position {
+            SeekFrom::Start(offset) => SeekFrom::Start(self.offset + offset),
+            other => other,
+        };
+        Ok(self.file.seek(position)?.saturating_sub(self.offset))
+    }
+}
+
+/// Byte offset of the FAT volume in an image: 0 if it starts with a boot sector, otherwise
+/// the start of the first FAT partition in its MBR partition table.
+fn volume_offset(sector: &[u8; 512]) -> Option<u64> {
+    if sector[510..] != [0x55, 0xAA] {
+        return None;
+    }
+    if matches!(sector[0], 0xEB | 0xE9) && (&sector[54..57] == b"FAT" || &sector[82..87] == b"FAT32") {
+        return Some(0);
+    }
+    (0..4).map(|i| &sector[446 + 16 * i..462 + 16 * i]).find_map(|entry| {
+        let fat = matches!(entry[4], 0x01 | 0x04 | 0x06 | 0x0B | 0x0C | 0x0E);
+        let start = u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]);
+        (fat && start > 0).then_some(u64::from(start) * 512)
+    })
+}
+
+enum Source {
+    Directory(PathBuf),
+    Image(Box<fatfs::FileSystem<Volume>>),
+}
+
+/// A card, mounted or as an image.
+pub struct Card {
+    source: Source,
+}
+
+impl Card {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        if path.is_dir() {
+            return Ok(Card { source: Source::Directory(path.to_path_buf()) });
+        }
+        let fail = |e: &str| Error::Data(format!("{}: {}", path.display(), e));
+        let mut file = File::open(path)?;
+        let mut sector = [0u8; 512];
+        file.read_exact(&mut sector).map_err(|_| fail("not a card image (too short)"))?;
+        let offset = volume_offset(&sector).ok_or_else(|| fail("not a card image (no FAT file system found)"))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let options = fatfs::FsOptions::new().update_accessed_date(false);
+        let filesystem = fatfs::FileSystem::new(Volume { file, offset }, options).map_err(|e| fail(&e.to_string()))?;
+        log::info!("Opened card image {:?} ({:?}, volume at byte {})", path, filesystem.fat_type(), offset);
+        Ok(Card { source: Source::Image(Box::new(filesystem)) })
+    }
+
+    /// Data files on the card, sorted by path.
+    pub fn files(&self) -> Result<Vec<CardFile>, Error> {
+        let mut files = Vec::new();
+        match &self.source {
+            Source::Directory(root) => directory_files(root, "", &mut files)?,
+            Source::Image(filesystem) => image_files(&filesystem.root_dir(), "", &mut files)?,
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+
+    /// Reads `file` from the card.
+    pub fn open_file(&self, file: &CardFile) -> Result<Box<dyn DataSource + '_>, Error> {
+        Ok(match &self.source {
+            Source::Directory(root) => Box::new(File::open(root.join(&file.path))?),
+            Source::Image(filesystem) => Box::new(filesystem.root_dir().open_file(&file.path)?),
+        })
+    }
+}
+
+/// The card file at `path` if its header is a data file's.
+fn describe(path: String, size: u64, reader: &mut dyn Read) -> io::Result<Option<CardFile>> {
+    let mut header = Vec::with_capacity(2048);
+    reader.take(2048).read_to_end(&mut header)?;
+    let Some(format) = DataFormat::detect(&header) else {
+        return Ok(None);
+    };
+    let text = String::from_utf8_lossy(&header);
+    let mut lines = text.lines().map(split_line);
+    let environment = lines.next().unwrap_or_default();
+    // TOB2/TOB3 files put the table name first on the second line.
+    let table = match format {
+        DataFormat::Toa5 | DataFormat::Tob1 => environment.get(7).cloned(),
+        DataFormat::Tob2 | DataFormat::Tob3 => lines.next().and_then(|line| line.into_iter().next()),
+    };
+    Ok(Some(CardFile {
+        path,
+        size,
+        format,
+        station: environment.get(1).cloned().unwrap_or_default(),
+        table: table.unwrap_or_default(),
+    }))
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+fn directory_files(dir: &Path, prefix: &str, files: &mut Vec<CardFile>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = join(prefix, &entry.file_name().to_string_lossy());
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            directory_files(&entry.path(), &name, files)?;
+        } else if let Some(file) = describe(name, metadata.len(), &mut File::open(entry.path())?)? {
+            files.push(file);
+        }
+    }
+    Ok(())
+}
+
+fn image_files(dir: &fatfs::Dir<'_, Volume>, prefix: &str, files: &mut Vec<CardFile>) -> Result<(), Error> {
+    for entry in dir.iter() {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == "." || name == ".." {
+            continue;
+        }
+        let path = join(prefix, &name);
+        if entry.is_dir() {
+            image_files(&entry.to_dir(), &path, files)?;
+        } else if let Some(file) = describe(path, entry.len(), &mut entry.to_file())? {
+            files.push(file);
+        }
+    }
+    Ok(())
+}
+
+/// What became of a card file in [`convert_all`].
+#[derive(Debug)]
+pub enum Outcome {
+    Converted(PathBuf, ConvertReport),
+    /// The format cannot be converted yet.
+    Skipped,
+    Failed(Error),
+}
+
+/// Converts every data file on `card` into `out_dir`, named after the file on the card with
+/// the extension of `format`. A file that fails to convert does not stop the others.
+pub fn convert_all(
+    card: &Card,
+    out_dir: &Path,
+    format: ConvertFormat,
+    append: bool,
+    progress: &mut dyn FnMut(&CardFile, Progress),
+) -> Result<Vec<(CardFile, Outcome)>, Error> {
+    fs::create_dir_all(out_dir)?;
+    let mut outcomes = Vec::new();
+    for file in card.files()? {
+        if !file.format.convertible() {
+            outcomes.push((file, Outcome::Skipped));
+            continue;
+        }
+        let name = Path::new(&file.path).file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let output = out_dir.join(format!("{}.{}", name, format.extension()));
+        let result = card
+            .open_file(&file)
+            .and_then(|source| convert::convert(source, file.size, &file.path, &output, format, append, &mut |p| progress(&file, p)));
+        let outcome = match result {
+            Ok(report) => Outcome::Converted(output, report),
+            Err(e) => Outcome::Failed(e),
+        };
+        outcomes.push((file, outcome));
+    }
+    Ok(outcomes)
+}
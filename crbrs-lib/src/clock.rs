@@ -0,0 +1,89 @@
+// FILE: crbrs-lib/src/clock.rs
+
+//! UTC date/time formatting without pulling in a date library.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// (year, month, day) of a day counted from 1970-01-01 (Howard Hinnant's algorithms).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Broken-down UTC time: (year, month, day, hour, minute, second, millisecond).
+fn civil(time: SystemTime) -> (i64, i64, i64, u64, u64, u64, u32) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let of_day = secs % 86_400;
+    (year, month, day, of_day / 3600, of_day / 60 % 60, of_day % 60, since_epoch.subsec_millis())
+}
+
+/// Milliseconds since 1970-01-01 00:00 of a logger timestamp, `YYYY-MM-DD HH:MM:SS` with
+/// optional fractional seconds, read as if it were UTC (loggers keep no time zone).
+pub fn parse_logger_time(text: &str) -> Option<i64> {
+    let (date, time) = text.trim().split_once([' ', 'T'])?;
+    let mut date_parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date_parts.next()??, date_parts.next()??, date_parts.next()??);
+    let mut time_parts = time.splitn(3, ':');
+    let hour: i64 = time_parts.next()?.parse().ok()?;
+    let minute: i64 = time_parts.next()?.parse().ok()?;
+    let seconds = time_parts.next().unwrap_or("0");
+    let (second, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    let second: i64 = second.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let millis = format!("{:0<3}", &fraction[..fraction.len().min(3)]).parse::<i64>().ok()?;
+    Some(((days_from_civil(year, month, day) * 24 + hour) * 60 + minute) * 60_000 + second * 1000 + millis)
+}
+
+/// `ms` since 1970-01-01 00:00 as a logger timestamp, `YYYY-MM-DD HH:MM:SS`, with `fraction`
+/// digits of fractional seconds (at most 3), the inverse of [`parse_logger_time`].
+pub fn format_logger_time(ms: i64, fraction: usize) -> String {
+    let (year, month, day) = civil_from_days(ms.div_euclid(86_400_000));
+    let of_day = ms.rem_euclid(86_400_000);
+    let (hour, minute, second) = (of_day / 3_600_000, of_day / 60_000 % 60, of_day / 1000 % 60);
+    let text = format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, hour, minute, second);
+    match fraction.min(3) {
+        0 => text,
+        digits => format!("{}.{}", text, &format!("{:03}", of_day % 1000)[..digits]),
+    }
+}
+
+/// Today's UTC date as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let (y, m, d, ..) = civil(SystemTime::now());
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// `time` as an RFC 3339 UTC timestamp with milliseconds, e.g. `2026-10-16T08:30:00.125Z`.
+pub fn timestamp(time: SystemTime) -> String {
+    let (y, m, d, hh, mm, ss, ms) = civil(time);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", y, m, d, hh, mm, ss, ms)
+}
+
+/// `time` in a form usable in file names, e.g. `20261016-083000`.
+pub fn file_stamp(time: SystemTime) -> String {
+    let (y, m, d, hh, mm, ss, _) = civil(time);
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", y, m, d, hh, mm, ss)
+}
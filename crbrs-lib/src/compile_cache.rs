@@ -0,0 +1,136 @@
+// FILE: crbrs-lib/src/compile_cache.rs
+
+//! Results of earlier compiles, kept as `compile-cache/<key>.json` in the data directory so
+//! compiling a file nothing has changed in answers right away instead of starting the compiler
+//! (and Wine) again.
+//!
+//! The key is the SHA256 of everything the result depends on: the compiler's ID and version,
+//! the program's file name and text, and the name and text of every file it includes,
+//! transitively (see [`crate::build_state::inputs`]). Where the program lives does not matter,
+//! so the language server, which compiles copies in scratch directories, shares results with
+//! the command line. Only compiles that produce nothing but a result are cached: runs for
+//! download (the image is needed) and runs writing a compiler log are not.
+//!
+//! `--force` on `crbrs compile` and `crbrs build` turns the cache off for the run. It holds at
+//! most [`MAX_ENTRIES`] results; the least recently used go first.
+
+use crate::build_state::inputs;
+use crate::config;
+use crate::{CompilationErrorDetail, Error};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+
+/// Results kept before the least recently used are removed.
+pub const MAX_ENTRIES: usize = 2000;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turns the cache on or off for this process (off for `--force`).
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// What the compiler made of the program.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "outcome")]
+pub enum CachedOutcome {
+    Success,
+    Failed { errors: Vec<CompilationErrorDetail>, raw_log: String },
+}
+
+pub fn cache_dir() -> Result<PathBuf, Error> {
+    Ok(config::data_dir()?.join("compile-cache"))
+}
+
+/// The key of compiling `program` with the compiler `compiler_id` at `version`; `None` while
+/// the cache is off or if the program cannot be read.
+pub fn key(program: &Path, compiler_id: &str, version: &str) -> Option<String> {
+    if !is_enabled() {
+        return None;
+    }
+    let mut hasher = Sha256::new();
+    for part in [compiler_id, version] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    let dir = program.parent().unwrap_or(Path::new(""));
+    for (i, input) in inputs(program).iter().enumerate() {
+        // The program by name only; includes by where they are found from it.
+        let name = match i {
+            0 => input.file_name().map(Path::new).unwrap_or(input),
+            _ => input.strip_prefix(dir).unwrap_or(input),
+        };
+        hasher.update(name.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        match fs::read(input) {
+            Ok(bytes) => {
+                hasher.update((bytes.len() as u64).to_le_bytes());
+                hasher.update(&bytes);
+            }
+            Err(_) if i == 0 => return None,
+            Err(_) => hasher.update(b"missing"),
+        }
+    }
+    Some(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// The stored result for `key`, if any.
+pub fn lookup(key: &str) -> Option<CachedOutcome> {
+    let path = cache_dir().ok()?.join(format!("{}.json", key));
+    let text = fs::read_to_string(&path).ok()?;
+    match serde_json::from_str(&text) {
+        Ok(outcome) => {
+            // Mark it used, for pruning.
+            let _ = fs::File::options().append(true).open(&path).and_then(|f| f.set_modified(SystemTime::now()));
+            Some(outcome)
+        }
+        Err(e) => {
+            log::warn!("Ignoring unreadable cached compile result {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Stores `outcome` under `key`, then prunes the cache. Failing to do so is not an error.
+pub fn store(key: &str, outcome: &CachedOutcome) {
+    if let Err(e) = try_store(key, outcome) {
+        log::warn!("Could not cache the compile result {}: {}", key, e);
+    }
+}
+
+fn try_store(key: &str, outcome: &CachedOutcome) -> Result<(), Error> {
+    let dir = cache_dir()?;
+    fs::create_dir_all(&dir)?;
+    let text = serde_json::to_string(outcome).map_err(|e| Error::Io(e.into()))?;
+    // Written aside and renamed, so a parallel build never reads half a result.
+    let partial = dir.join(format!("{}.json.{}.part", key, std::process::id()));
+    fs::write(&partial, text)?;
+    fs::rename(&partial, dir.join(format!("{}.json", key)))?;
+    prune(&dir, MAX_ENTRIES)
+}
+
+/// Removes the least recently used results beyond `keep`.
+fn prune(dir: &Path, keep: usize) -> Result<(), Error> {
+    let mut entries: Vec<(SystemTime, PathBuf)> = fs::read_dir(dir)?
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|e| e == "json"))
+        .map(|entry| (entry.metadata().and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH), entry.path()))
+        .collect();
+    if entries.len() <= keep {
+        return Ok(());
+    }
+    entries.sort();
+    for (_, path) in &entries[..entries.len() - keep] {
+        log::debug!("Removing cached compile result {:?}", path);
+        let _ = fs::remove_file(path);
+    }
+    Ok(())
+}
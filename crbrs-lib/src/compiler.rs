@@ -1,11 +1,19 @@
 // FILE: crbrs_lib/src/compiler.rs
 
-use crate::{CompilationErrorDetail, Error, Settings};
-use regex::Regex;
+use crate::adapter::{adapter_for, CompilerOutcome};
+use crate::cancel;
+use crate::compile_cache::CachedOutcome;
+use crate::compiler_path;
+use crate::in_place;
+use crate::project::Project;
+use crate::sandbox::{Sandbox, VirtualFiles};
+use crate::{say, tr, CompilerInfo, Error, Settings, Suggestions};
 // std::fs is not explicitly needed here anymore unless we were to do something
 // special with the user-requested log file path before passing it to the compiler.
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
+use std::time::Duration;
 
 // Helper function to determine if we are likely on a non-Windows OS
 // This is a compile-time check.
@@ -13,55 +21,6 @@ fn is_non_windows_os() -> bool {
     cfg!(not(windows))
 }
 
-/// Parses the compiler's output (typically stdout) to extract structured error details.
-fn parse_compiler_output(output_content: &str) -> Result<Vec<CompilationErrorDetail>, ()> {
-    let mut errors = Vec::new();
-    let mut lines = output_content.lines();
-
-    // First line usually indicates overall status and the filename as seen by compiler
-    let first_line = lines.next().unwrap_or("").trim();
-    let file_path_in_log = first_line // Renaming variable for clarity, though it's from stdout now
-        .split(" -- ")
-        .next()
-        .unwrap_or("")
-        .trim()
-        .to_string();
-
-    if first_line.contains("Compiled OK.") {
-        return Ok(Vec::new()); // Success, no errors
-    } else if first_line.contains("Compile Failed!") {
-        // Regex to capture "line <number>: <message>"
-        let re = Regex::new(r"^\s*line\s+(\d+):\s*(.+?)\s*$").expect("Invalid regex pattern");
-
-        for line_str in lines {
-            let trimmed_line = line_str.trim();
-            if trimmed_line.is_empty() {
-                continue;
-            }
-            if let Some(caps) = re.captures(trimmed_line) {
-                let line_num_str = caps.get(1).map_or("", |m| m.as_str());
-                let line_num = line_num_str.parse::<u32>().ok();
-                let message = caps.get(2).map_or("", |m| m.as_str()).to_string();
-                errors.push(CompilationErrorDetail {
-                    file_path_in_log: file_path_in_log.clone(),
-                    line: line_num,
-                    message,
-                });
-            } else if !errors.is_empty() && !trimmed_line.starts_with("line ") {
-                // If we have started parsing errors and this line doesn't look like a new error,
-                // append it to the message of the last parsed error (for multi-line error messages).
-                if let Some(last_error) = errors.last_mut() {
-                    last_error.message.push_str("\n"); // Add a newline separator
-                    last_error.message.push_str(trimmed_line); // Append the current line
-                }
-            }
-        }
-        return Ok(errors); // Contains parsed errors, or empty if only "Compile Failed!" was found
-    }
-
-    Err(()) // Unrecognized output format (neither "Compiled OK." nor "Compile Failed!" in first line)
-}
-
 /// Compiles a given CRBasic file using the specified or associated compiler.
 pub fn compile_file_impl(
     input_file: &Path,
@@ -69,30 +28,162 @@ pub fn compile_file_impl(
     compiler_id_param: Option<&str>,
     settings: &Settings,
 ) -> Result<(), Error> {
-    log::info!(
-        "Attempting to compile file: {:?}, explicit compiler ID: {:?}, user-requested log: {:?}",
-        input_file,
-        compiler_id_param,
-        output_log_param
-    );
+    run_compiler(input_file, output_log_param, compiler_id_param, settings, false).map(|_| ())
+}
 
-    // 1. Validate input file
-    if !input_file.exists() {
-        return Err(Error::Io(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("Input file not found: {}", input_file.display()),
-        )));
+/// Compiles a file in "compile for download" mode and collects the binary image it produces.
+///
+/// The image is moved to `image_output` if given, otherwise it stays where the compiler wrote
+/// it, and is sealed with a checksum (and signature, if configured) sidecar. Returns the final
+/// image path. Fails with `Error::ImageNotSupported` for compiler
+/// families whose loggers compile the program source themselves (CR1000 family).
+pub fn compile_for_download_impl(
+    input_file: &Path,
+    output_log_param: Option<&Path>,
+    compiler_id_param: Option<&str>,
+    image_output: Option<&Path>,
+    settings: &Settings,
+) -> Result<PathBuf, Error> {
+    // Download runs report where the image should be; not knowing is as bad as not finding it.
+    let produced = run_compiler(input_file, output_log_param, compiler_id_param, settings, true)?
+        .ok_or_else(|| Error::ImageNotProduced(input_file.to_path_buf()))?;
+    if !produced.is_file() {
+        return Err(Error::ImageNotProduced(produced));
     }
-    if !input_file.is_file() {
-        return Err(Error::Io(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            format!("Input path is not a file: {}", input_file.display()),
-        )));
+
+    // Without a destination, the project's image directory (if it has one) is where images go.
+    let project_dest = match image_output {
+        None => Project::find(input_file)?
+            .and_then(|project| project.image_dir())
+            .and_then(|dir| produced.file_name().map(|name| dir.join(name))),
+        Some(_) => None,
+    };
+    let image = match image_output.or(project_dest.as_deref()) {
+        Some(dest) if dest != produced => {
+            if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)?;
+            }
+            // Copy + remove rather than rename so moving across filesystems works.
+            fs::copy(&produced, dest)?;
+            fs::remove_file(&produced)?;
+            dest.to_path_buf()
+        }
+        _ => produced,
+    };
+    log::info!("Download image for {:?} collected at {:?}", input_file, image);
+    say!("   {}", tr!("compile-download-image", path = image.display().to_string()));
+
+    let sealed = crate::artifact::seal(&image, settings)?;
+    say!("   {}", tr!("compile-checksum", path = sealed.checksum.display().to_string()));
+    if let Some(signature) = &sealed.signature {
+        say!("   {}", tr!("compile-signature", path = signature.display().to_string()));
     }
+    Ok(image)
+}
 
-    // 2. Resolve Compiler ID
-    let compiler_id: String = match compiler_id_param {
-        Some(id) => id.to_string(),
+/// Records a successful compile in the build history; failing to do so is not an error.
+fn record_build(input_file: &Path, compiler_id: &str, version: &str) {
+    if let Err(e) = crate::history::record_build(input_file, compiler_id, version) {
+        log::warn!("Could not record build of {:?} with '{}': {}", input_file, compiler_id, e);
+    }
+}
+
+/// Error for an extension associated with a compiler that is not installed, telling the user
+/// how to fix it depending on whether the manifest offers that compiler.
+fn association_not_installed(extension: &str, compiler_id: &str, settings: &Settings) -> Error {
+    // Don't keep an offline user waiting on retries just to improve an error message.
+    let network = crate::network::NetworkSettings { retries: 0, ..settings.network.clone() };
+    let manifest = crate::installer::fetch_manifest(&settings.compiler_repository_url, &network);
+    let in_manifest = manifest.as_ref().is_ok_and(|m| m.compilers.contains_key(compiler_id));
+    let hint = match &manifest {
+        Ok(_) if in_manifest => format!(
+            "Install it with `crbrs compiler install {}` (or compile with --install-missing).",
+            compiler_id
+        ),
+        Ok(_) => format!(
+            "It is not in the compiler manifest either; point the association at an installed compiler with              `crbrs config set-association --extension {} --compiler-id <ID>`.",
+            extension
+        ),
+        Err(_) => format!("If it is available, install it with `crbrs compiler install {}`.", compiler_id),
+    };
+    Error::AssociatedCompilerNotInstalled {
+        extension: extension.to_string(),
+        compiler_id: compiler_id.to_string(),
+        in_manifest,
+        hint,
+    }
+}
+
+/// Compiler chosen by a file's toolchain directives: the `compiler` directive, or else an
+/// installed compiler supporting the `target` logger (the extension's associated compiler if
+/// it qualifies).
+fn directive_compiler(
+    directives: &crate::syntax::directive::Directives,
+    input_file: &Path,
+    project: Option<&Project>,
+    settings: &Settings,
+) -> Result<Option<String>, Error> {
+    if let Some(id) = &directives.compiler {
+        log::debug!("Using compiler '{}' from the file's crbrs directive", id);
+        return Ok(Some(id.clone()));
+    }
+    let Some(target) = &directives.target else { return Ok(None) };
+    let supports = |id: &String| {
+        settings.installed_compilers.get(id).and_then(|info| info.supported_loggers.as_ref())
+            .is_some_and(|loggers| loggers.iter().any(|l| l.eq_ignore_ascii_case(target)))
+    };
+    let associated = input_file.extension().and_then(|ext| association(&ext.to_string_lossy(), project, settings));
+    let mut candidates: Vec<&String> = settings.installed_compilers.keys().filter(|id| supports(id)).collect();
+    candidates.sort();
+    match associated.filter(|id| supports(id)).or(candidates.first().copied()) {
+        Some(id) => {
+            log::debug!("Using compiler '{}' for directive target '{}'", id, target);
+            Ok(Some(id.clone()))
+        }
+        None => Err(Error::CompilerNotFound(format!("No installed compiler supports target '{}'", target), Suggestions::none())),
+    }
+}
+
+/// Compiler ID that would build `input_file` and its installed info: `compiler_id_param` if
+/// given, then an in-source `'crbrs:` directive, then the extension association.
+pub fn resolve_compiler<'a>(
+    input_file: &Path,
+    compiler_id_param: Option<&str>,
+    settings: &'a Settings,
+) -> Result<(String, &'a crate::CompilerInfo), Error> {
+    resolve_compiler_for_source(input_file, &String::from_utf8_lossy(&fs::read(input_file)?), compiler_id_param, settings)
+}
+
+/// Like [`resolve_compiler`], with the file's directives taken from `source` (e.g. an editor's
+/// unsaved text) instead of the file on disk.
+pub fn resolve_compiler_for_source<'a>(
+    input_file: &Path,
+    source: &str,
+    compiler_id_param: Option<&str>,
+    settings: &'a Settings,
+) -> Result<(String, &'a crate::CompilerInfo), Error> {
+    let directives = crate::syntax::directive::directives_source(source);
+    resolve(input_file, compiler_id_param, &directives, settings)
+}
+
+/// The compiler associated with `extension`: the project's association, else the user's.
+fn association<'a>(extension: &str, project: Option<&'a Project>, settings: &'a Settings) -> Option<&'a String> {
+    project.and_then(|project| project.association(extension)).or_else(|| settings.file_associations.get(extension))
+}
+
+fn resolve<'a>(
+    input_file: &Path,
+    compiler_id_param: Option<&str>,
+    directives: &crate::syntax::directive::Directives,
+    settings: &'a Settings,
+) -> Result<(String, &'a crate::CompilerInfo), Error> {
+    let project = Project::find(input_file)?;
+    if let Some(project) = &project {
+        project.check_compilers(settings)?;
+    }
+    let mut associated_extension = None;
+    let compiler_id: String = match compiler_id_param.map(str::to_string).or(directive_compiler(directives, input_file, project.as_ref(), settings)?) {
+        Some(id) => id,
         None => {
             let extension = input_file
                 .extension()
@@ -106,23 +197,48 @@ pub fn compile_file_impl(
                             .into_owned(),
                     )
                 })?;
-            settings
-                .file_associations
-                .get(extension)
+            associated_extension = Some(extension);
+            association(extension, project.as_ref(), settings)
                 .cloned()
                 .ok_or_else(|| Error::NoCompilerForExtension(extension.to_string()))?
         }
     };
     log::debug!("Resolved compiler ID to use: {}", compiler_id);
 
-    // 3. Get CompilerInfo
     let compiler_info = settings
         .installed_compilers
         .get(&compiler_id)
-        .ok_or_else(|| Error::CompilerNotFound(compiler_id.clone()))?;
-    log::debug!("Using compiler info: {:?}", compiler_info);
+        .ok_or_else(|| match associated_extension {
+            Some(extension) => association_not_installed(extension, &compiler_id, settings),
+            None => Error::CompilerNotFound(
+                compiler_id.clone(),
+                Suggestions::for_id(&compiler_id, settings.installed_compilers.keys()),
+            ),
+        })?;
+    Ok((compiler_id, compiler_info))
+}
 
-    // 4. Construct Path to Compiler Executable
+/// Whether `compiler_info` runs under Wine on this platform.
+pub fn uses_wine(compiler_info: &CompilerInfo) -> bool {
+    compiler_info.requires_wine && is_non_windows_os()
+}
+
+/// The configured Wine executable (`wine_path`, else `wine` in PATH), if it exists.
+pub fn find_wine(settings: &Settings) -> Option<PathBuf> {
+    crate::validate::find_program(settings.wine_path.as_deref().unwrap_or("wine"))
+}
+
+/// The command that runs the compiler on `input_file` (through Wine if needed), and for download
+/// runs where the image will be left.
+pub(crate) fn compiler_command(
+    compiler_id: &str,
+    compiler_info: &CompilerInfo,
+    input_file: &Path,
+    output_log_param: Option<&Path>,
+    settings: &Settings,
+    for_download: bool,
+) -> Result<(Command, Option<PathBuf>), Error> {
+    // Construct Path to Compiler Executable
     let compiler_base_storage_path = crate::config::get_compiler_storage_path(settings)?;
     let compiler_executable_path = compiler_base_storage_path
         .join(&compiler_info.install_subdir)
@@ -133,16 +249,17 @@ pub fn compile_file_impl(
             "Executable for '{}' not found at expected path: {}",
             compiler_id,
             compiler_executable_path.display()
-        )));
+        ), Suggestions::none()));
     }
     log::debug!("Compiler executable: {:?}", compiler_executable_path);
     log::debug!("Input CRBasic file: {:?}", input_file);
 
-    // 5. Prepare Command
+    // Prepare Command
+    let wine = uses_wine(compiler_info);
     let mut cmd: Command;
     let mut args_for_logging: Vec<String> = Vec::new();
 
-    if compiler_info.requires_wine && is_non_windows_os() {
+    if wine {
         let wine_exe = settings.wine_path.as_deref().unwrap_or("wine");
         cmd = Command::new(wine_exe);
         cmd.arg(compiler_executable_path.to_string_lossy().as_ref()); // Compiler path is arg to wine
@@ -154,26 +271,170 @@ pub fn compile_file_impl(
         args_for_logging.push(compiler_executable_path.to_string_lossy().into_owned());
         log::info!("Running compiler natively (Windows or requires_wine=false).");
     }
+    // Run in the program's directory, e.g. on a network share, so the compiler finds what it
+    // looks for next to the program.
+    if let Some(dir) = std::path::absolute(input_file)?.parent() {
+        cmd.current_dir(dir);
+    }
 
-    // Add input file argument
-    let input_file_str = input_file.to_string_lossy().into_owned();
-    cmd.arg(&input_file_str);
-    args_for_logging.push(input_file_str.clone());
-
-    // Add output log file argument ONLY if user specified one
-    if let Some(log_path) = output_log_param {
-        let output_log_path_str = log_path.to_string_lossy().into_owned();
-        cmd.arg(&output_log_path_str);
-        args_for_logging.push(output_log_path_str);
-        log::debug!("Compiler will create log at user-specified path: {:?}", log_path);
+    // Arguments depend on the compiler family (input file, optional log file, ...), with the
+    // paths written as the compiler expects them (see crate::compiler_path)
+    let compiler_input = PathBuf::from(compiler_path::to_compiler(input_file, wine)?);
+    let compiler_log = output_log_param.map(|log| compiler_path::to_compiler(log, wine).map(PathBuf::from)).transpose()?;
+    let adapter = adapter_for(compiler_info.family);
+    log::debug!("Using {} compiler adapter.", adapter.family());
+    let (compiler_args, image_path) = if for_download {
+        let request = adapter
+            .download_image(&compiler_input, compiler_log.as_deref())
+            .ok_or_else(|| Error::ImageNotSupported(compiler_id.to_string()))?;
+        // Where the image lands, as a host path
+        let image = adapter.download_image(input_file, output_log_param).map(|host| host.image).unwrap_or(request.image);
+        (request.args, Some(image))
     } else {
+        (adapter.args(&compiler_input, compiler_log.as_deref()), None)
+    };
+    cmd.args(&compiler_args);
+    args_for_logging.extend(compiler_args.iter().cloned());
+    if output_log_param.is_none() {
         log::debug!("Compiler will output to stdout/stderr (no explicit log file argument passed).");
     }
 
+    let args_for_logging: Vec<String> = args_for_logging.iter().map(|arg| compiler_path::quote_for_log(arg)).collect();
     log::info!("Executing command: {}", args_for_logging.join(" "));
+    Ok((cmd, image_path))
+}
+
+/// Moves an image the compiler left in `sandbox` to [`in_place::artifacts_dir`] before the
+/// sandbox is removed.
+fn keep_image(image_path: Option<PathBuf>, sandbox: Option<&Sandbox>) -> Result<Option<PathBuf>, Error> {
+    match (image_path, sandbox) {
+        (Some(image), Some(_)) if image.is_file() => {
+            let dir = in_place::artifacts_dir()?;
+            fs::create_dir_all(&dir)?;
+            let kept = dir.join(image.file_name().unwrap_or_default());
+            fs::copy(&image, &kept)?;
+            Ok(Some(kept))
+        }
+        (image_path, _) => Ok(image_path),
+    }
+}
+
+/// Resolves the compiler, runs it and interprets its output. For download runs, returns where
+/// the compiler was asked to leave the image.
+fn run_compiler(
+    input_file: &Path,
+    output_log_param: Option<&Path>,
+    compiler_id_param: Option<&str>,
+    settings: &Settings,
+    for_download: bool,
+) -> Result<Option<PathBuf>, Error> {
+    log::info!(
+        "Attempting to compile file: {:?}, explicit compiler ID: {:?}, user-requested log: {:?}",
+        input_file,
+        compiler_id_param,
+        output_log_param
+    );
+
+    // 1. Validate input file
+    if !input_file.exists() {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Input file not found: {}", input_file.display()),
+        )));
+    }
+    if !input_file.is_file() {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Input path is not a file: {}", input_file.display()),
+        )));
+    }
 
-    // 6. Execute Command
-    let execution_result: Result<Output, std::io::Error> = cmd.output(); // Captures stdout, stderr, status
+    // 2. Resolve the compiler and get its CompilerInfo
+    let directives = crate::syntax::directive::directives_source(&String::from_utf8_lossy(&fs::read(input_file)?));
+    let (compiler_id, compiler_info) = resolve(input_file, compiler_id_param, &directives, settings)?;
+    log::debug!("Using compiler info: {:?}", compiler_info);
+    if let Some(last) = crate::history::last_build(input_file) {
+        if last.compiler_id != compiler_id || last.version != compiler_info.version {
+            say!(
+                "{}",
+                tr!(
+                    "compile-compiler-changed",
+                    file = input_file.display().to_string(),
+                    last_id = last.compiler_id,
+                    last_version = last.version,
+                    built = last.built,
+                    compiler_id = compiler_id.as_str(),
+                    version = compiler_info.version.as_str(),
+                )
+            );
+        }
+    }
+    if let (Some(target), Some(loggers)) = (&directives.target, &compiler_info.supported_loggers) {
+        if !loggers.iter().any(|l| l.eq_ignore_ascii_case(target)) {
+            log::warn!("Compiler '{}' does not list target '{}' among its supported loggers {:?}", compiler_id, target, loggers);
+        }
+    }
+
+    // 3. Answer from the compile cache if nothing changed since an earlier run
+    let cache_key = match (for_download, output_log_param) {
+        (false, None) => crate::compile_cache::key(input_file, &compiler_id, &compiler_info.version),
+        _ => None,
+    };
+    if let Some(cached) = cache_key.as_deref().and_then(crate::compile_cache::lookup) {
+        log::info!("Using the cached result of compiling {:?} with '{}'", input_file, compiler_id);
+        return match cached {
+            CachedOutcome::Success => {
+                say!("{}", tr!("compile-succeeded-cached", file = input_file.display().to_string()));
+                record_build(input_file, &compiler_id, &compiler_info.version);
+                Ok(None)
+            }
+            CachedOutcome::Failed { errors, raw_log } => {
+                Err(Error::CompilationFailed { file_path: input_file.to_path_buf(), errors, raw_log })
+            }
+        };
+    }
+
+    // 4. Prepare the command, for a copy of the program in a sandbox if the compiler must not
+    // write next to it (see crate::in_place)
+    let mut sandbox = None;
+    let run_input = match in_place::redirect_reason(input_file, settings.artifacts_in_place)? {
+        Some(reason) => {
+            log::info!("Compiling {:?} in a sandbox: {}", input_file, reason);
+            sandbox.insert(Sandbox::new(&VirtualFiles::new())?).program(input_file)?
+        }
+        None => input_file.to_path_buf(),
+    };
+    let (mut cmd, image_path) =
+        compiler_command(&compiler_id, compiler_info, &run_input, output_log_param, settings, for_download)?;
+    let adapter = adapter_for(compiler_info.family);
+    // An image left by an earlier run would pass for this one's if the compiler writes none.
+    if let Some(image) = &image_path {
+        match fs::remove_file(image) {
+            Ok(()) => log::debug!("Removed the stale download image {:?}", image),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(Error::Io(e)),
+        }
+    }
+
+    // 5. Execute Command (killed if the run is cancelled or takes too long, see crate::cancel)
+    let timeout = (settings.compile_timeout_secs > 0).then(|| Duration::from_secs(settings.compile_timeout_secs));
+    let execution_result: Result<Output, std::io::Error> = match cancel::output_within(&mut cmd, timeout) {
+        Ok(output) => Ok(output),
+        Err(Error::Subprocess(e)) if e.kind() != std::io::ErrorKind::TimedOut => Err(e),
+        Err(e) => {
+            if let Some(image) = &image_path {
+                let _ = fs::remove_file(image); // Don't leave a half-written image behind
+            }
+            return Err(match e {
+                Error::Subprocess(_) => {
+                    // Timed out: cancel::output_within killed the compiler
+                    log::error!("Compiling {:?} took longer than {} s", input_file, settings.compile_timeout_secs);
+                    Error::CompilationTimeout { file_path: input_file.to_path_buf(), seconds: settings.compile_timeout_secs }
+                }
+                other => other,
+            });
+        }
+    };
 
     match execution_result {
         Ok(output) => {
@@ -189,26 +450,39 @@ pub fn compile_file_impl(
                 log::warn!("  Stderr from compiler process:\n{}", stderr_content.trim());
             }
 
-            // 7. Parse stdout for success/failure and errors
-            match parse_compiler_output(&stdout_content) {
-                Ok(parsed_errors) => { // Successfully parsed stdout (found "OK" or "Failed")
-                    if parsed_errors.is_empty() { // Implies "Compiled OK."
-                        log::info!("Compilation successful for {:?}.", input_file);
-                        println!("✅ Successfully compiled: {}", input_file.display());
-                        if let Some(log_p) = output_log_param {
-                            println!("   Compiler log created at: {}", log_p.display());
+            // 6. Parse stdout for success/failure and errors
+            match adapter.parse_output(input_file, &stdout_content) {
+                CompilerOutcome::Success => {
+                    log::info!("Compilation successful for {:?}.", input_file);
+                    say!("{}", tr!("compile-succeeded", file = input_file.display().to_string()));
+                    if let Some(log_p) = output_log_param {
+                        say!("   {}", tr!("compile-log-created", path = log_p.display().to_string()));
+                    }
+                    record_build(input_file, &compiler_id, &compiler_info.version);
+                    if let Some(key) = &cache_key {
+                        crate::compile_cache::store(key, &CachedOutcome::Success);
+                    }
+                    keep_image(image_path, sandbox.as_ref())
+                }
+                CompilerOutcome::Failed(mut parsed_errors) => { // errors may be empty if the compiler gave no details
+                    log::error!("Compilation failed for {:?} based on stdout parsing.", input_file);
+                    for error in &mut parsed_errors {
+                        error.file_path_in_log = compiler_path::from_compiler(&error.file_path_in_log, uses_wine(compiler_info));
+                        if let Some(sandbox) = &sandbox {
+                            error.file_path_in_log = sandbox.original(Path::new(&error.file_path_in_log)).display().to_string();
                         }
-                        Ok(())
-                    } else { // Implies "Compile Failed!" and errors were parsed (or vec is empty but still a fail)
-                        log::error!("Compilation failed for {:?} based on stdout parsing.", input_file);
-                        Err(Error::CompilationFailed {
-                            file_path: input_file.to_path_buf(),
-                            errors: parsed_errors,
-                            raw_log: stdout_content, // The stdout is the primary "log" here
-                        })
                     }
+                    if let Some(key) = &cache_key {
+                        let cached = CachedOutcome::Failed { errors: parsed_errors.clone(), raw_log: stdout_content.clone() };
+                        crate::compile_cache::store(key, &cached);
+                    }
+                    Err(Error::CompilationFailed {
+                        file_path: input_file.to_path_buf(),
+                        errors: parsed_errors,
+                        raw_log: stdout_content, // The stdout is the primary "log" here
+                    })
                 }
-                Err(_) => { // parse_compiler_output returned Err -> unrecognized stdout format
+                CompilerOutcome::Unrecognized => {
                     log::warn!(
                         "Unrecognized compiler stdout format for {:?}. Relying on process exit status.",
                         input_file
@@ -219,18 +493,19 @@ pub fn compile_file_impl(
                             "Compiler process for {:?} exited successfully despite unrecognized stdout. Assuming success.",
                             input_file
                         );
-                        println!(
+                        say!(
                             "✅ Compilation process for {} finished successfully (exit code 0), but output format was unrecognized.",
                             input_file.display()
                         );
                         if let Some(log_p) = output_log_param {
-                            println!("   Compiler log (if created by compiler): {}", log_p.display());
+                            say!("   Compiler log (if created by compiler): {}", log_p.display());
                         }
                         // Print stdout for user to inspect if it was unrecognized
                         if !stdout_content.trim().is_empty() {
-                            println!("   Compiler output (stdout):\n{}", stdout_content.trim());
+                            say!("   Compiler output (stdout):\n{}", stdout_content.trim());
                         }
-                        Ok(())
+                        record_build(input_file, &compiler_id, &compiler_info.version);
+                        keep_image(image_path, sandbox.as_ref())
                     } else {
                         log::error!(
                             "Compiler process for {:?} failed (Exit Code: {:?}) and stdout format was unrecognized.",
@@ -251,7 +526,7 @@ pub fn compile_file_impl(
             }
         }
         Err(e) => {
-            // This error means `cmd.output()` itself failed (e.g., wine not found, compiler exe not found by OS)
+            // This error means the process could not be run (e.g., wine not found, compiler exe not found by OS)
             log::error!("Failed to execute compiler process: {}", e);
             if compiler_info.requires_wine && is_non_windows_os() && e.kind() == std::io::ErrorKind::NotFound {
                 // Check if 'wine' itself was not found
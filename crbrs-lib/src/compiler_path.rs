@@ -0,0 +1,91 @@
+// FILE: crbrs-lib/src/compiler_path.rs
+
+//! Paths as handed to compilers, and back.
+//!
+//! Programs often live on a shared drive (`/Volumes/Lab Share/Stations (2024)/...`,
+//! `\\fileserver\loggers\...`), in directories whose names have spaces and parentheses. The
+//! compilers are Windows programs that know nothing of Unix paths or of the `\\?\` prefix
+//! Windows adds to canonical paths, so paths are made absolute and then written the way the
+//! compiler expects:
+//!
+//! * Under Wine, as a path on drive `Z:`, which Wine maps to `/` by default: `/mnt/lab share/a.cr1x`
+//!   becomes `Z:\mnt\lab share\a.cr1x`. A network share mounted anywhere on the host works
+//!   like any other directory. (A leading `/` would also be taken for a switch by many Windows
+//!   programs.)
+//! * On Windows, without the `\\?\` or `\\?\UNC\` prefix, so UNC paths reach the compiler as
+//!   `\\server\share\...`.
+//!
+//! Arguments are passed to the process one by one, never through a shell, so spaces and
+//! parentheses need no quoting of our own; Wine quotes them when it builds the Windows command
+//! line. File names in the compiler's output are mapped back with [`from_compiler`].
+
+use crate::Error;
+use std::path::Path;
+
+/// `path` as the compiler is given it; `wine` if it runs under Wine.
+pub fn to_compiler(path: &Path, wine: bool) -> Result<String, Error> {
+    let absolute = std::path::absolute(path)?;
+    let absolute = absolute.to_string_lossy();
+    Ok(if wine { wine_path(&absolute) } else { without_verbatim_prefix(&absolute) })
+}
+
+/// A file name from the compiler's output as a host path (see [`to_compiler`]); names that are
+/// not paths on drive `Z:` are returned unchanged.
+pub fn from_compiler(name: &str, wine: bool) -> String {
+    let name = name.trim();
+    match name.get(..3) {
+        Some(drive) if wine && drive.eq_ignore_ascii_case("Z:\\") => format!("/{}", name[3..].replace('\\', "/")),
+        _ => name.to_string(),
+    }
+}
+
+fn wine_path(unix: &str) -> String {
+    format!("Z:{}", unix.replace('/', "\\"))
+}
+
+fn without_verbatim_prefix(windows: &str) -> String {
+    if let Some(unc) = windows.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else if let Some(local) = windows.strip_prefix(r"\\?\") {
+        local.to_string()
+    } else {
+        windows.to_string()
+    }
+}
+
+/// `arg` as it would be typed in a shell, for logging the command line.
+pub fn quote_for_log(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "()'\"&;|<>$`".contains(c)) {
+        arg.to_string()
+    } else {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wine_paths_are_on_drive_z_and_map_back() {
+        let host = "/Volumes/Lab Share/Stations (2024)/North Ridge.cr1x";
+        let wine = wine_path(host);
+        assert_eq!(wine, r"Z:\Volumes\Lab Share\Stations (2024)\North Ridge.cr1x");
+        assert_eq!(from_compiler(&wine, true), host);
+        assert_eq!(from_compiler("North Ridge.cr1x", true), "North Ridge.cr1x");
+        assert_eq!(from_compiler(&wine, false), wine);
+    }
+
+    #[test]
+    fn windows_paths_lose_the_verbatim_prefix() {
+        assert_eq!(without_verbatim_prefix(r"\\?\UNC\fileserver\loggers\a b\main.cr1x"), r"\\fileserver\loggers\a b\main.cr1x");
+        assert_eq!(without_verbatim_prefix(r"\\?\C:\Stations (old)\main.cr1x"), r"C:\Stations (old)\main.cr1x");
+        assert_eq!(without_verbatim_prefix(r"\\fileserver\loggers\main.cr1x"), r"\\fileserver\loggers\main.cr1x");
+    }
+
+    #[test]
+    fn logged_arguments_are_quoted_when_needed() {
+        assert_eq!(quote_for_log(r"Z:\a\main.cr1x"), r"Z:\a\main.cr1x");
+        assert_eq!(quote_for_log(r"Z:\Lab Share\(1)\main.cr1x"), r#""Z:\Lab Share\(1)\main.cr1x""#);
+    }
+}
@@ -1,12 +1,60 @@
 // FILE: crbrs-lib/src/config.rs
 
-use crate::{Error, Settings}; // Import from lib.rs
-use config::{Config, File};
+use crate::{CompilerInfo, Error, Settings}; // Import from lib.rs
+use config::{Config, Environment, File, FileFormat};
 use directories::ProjectDirs;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 use toml;
 
 const CONFIG_FILE_NAME: &str = "config.toml";
+const STATE_FILE_NAME: &str = "state.toml";
+
+/// Environment variable selecting portable mode (same as the CLI's `--portable <dir>`).
+pub const HOME_ENV_VAR: &str = "CRBRS_HOME";
+
+/// Environment variable selecting a config profile (same as the CLI's `--profile <name>`).
+pub const PROFILE_ENV_VAR: &str = "CRBRS_PROFILE";
+
+/// Prefix of the environment variables overriding settings, e.g. `CRBRS_WINE_PATH`.
+pub const SETTINGS_ENV_PREFIX: &str = "CRBRS";
+
+static PORTABLE_HOME: OnceLock<PathBuf> = OnceLock::new();
+static PROFILE: OnceLock<String> = OnceLock::new();
+static RECOVERIES: Mutex<Vec<Recovery>> = Mutex::new(Vec::new());
+
+/// Enables portable mode for this process: config, compiler storage and logs all live under
+/// `dir` (in `config/` and `data/`). Takes precedence over `CRBRS_HOME`; only the first call
+/// has an effect.
+pub fn set_portable_home(dir: &Path) -> Result<(), Error> {
+    let dir = std::path::absolute(dir)?;
+    log::debug!("Portable mode: using {:?}", dir);
+    let _ = PORTABLE_HOME.set(dir);
+    Ok(())
+}
+
+/// The portable home directory, if portable mode is enabled.
+pub fn portable_home() -> Option<PathBuf> {
+    PORTABLE_HOME.get().cloned().or_else(|| {
+        std::env::var_os(HOME_ENV_VAR)
+            .filter(|value| !value.is_empty())
+            .and_then(|value| std::path::absolute(PathBuf::from(value)).ok())
+    })
+}
+
+/// Selects the `[profiles.<name>]` overrides applied by `load_settings` for this process.
+/// Takes precedence over `CRBRS_PROFILE`; only the first call has an effect.
+pub fn set_active_profile(name: &str) {
+    let _ = PROFILE.set(name.to_string());
+}
+
+/// The selected profile name, if any.
+pub fn active_profile() -> Option<String> {
+    PROFILE.get().cloned().or_else(|| std::env::var(PROFILE_ENV_VAR).ok().filter(|name| !name.is_empty()))
+}
 
 // Helper to get project directories
 pub fn get_project_dirs() -> Result<ProjectDirs, Error> {
@@ -14,43 +62,399 @@ pub fn get_project_dirs() -> Result<ProjectDirs, Error> {
         .ok_or(Error::DirectoryResolutionFailed)
 }
 
+/// Directory holding `config.toml`.
+pub fn config_dir() -> Result<PathBuf, Error> {
+    match portable_home() {
+        Some(home) => Ok(home.join("config")),
+        None => Ok(get_project_dirs()?.config_dir().to_path_buf()),
+    }
+}
+
+/// Directory holding compilers, session logs and other data.
+pub fn data_dir() -> Result<PathBuf, Error> {
+    match portable_home() {
+        Some(home) => Ok(home.join("data")),
+        None => Ok(get_project_dirs()?.data_local_dir().to_path_buf()),
+    }
+}
+
 // Function to get the effective path where compilers are stored
 pub fn get_compiler_storage_path(settings: &Settings) -> Result<PathBuf, Error> {
     match &settings.compiler_storage_path {
+        // In portable mode relative paths stay inside the (relocatable) home directory
+        Some(path) if path.is_relative() => match portable_home() {
+            Some(home) => Ok(home.join(path)),
+            None => Ok(path.clone()),
+        },
         Some(path) => Ok(path.clone()),
         None => {
             // Default to a subdirectory within the project's data directory
-            let proj_dirs = get_project_dirs()?;
-            Ok(proj_dirs.data_local_dir().join("compilers"))
+            Ok(data_dir()?.join("compilers"))
         }
     }
 }
 
+/// Where settings go when the config directory can't be written (e.g. made read-only by an
+/// administrator). Once it exists it is the config file, and includes the regular one.
+pub fn fallback_config_file() -> Result<PathBuf, Error> {
+    Ok(data_dir()?.join("fallback-config.toml"))
+}
+
 // Function to get the path to the configuration file
 pub fn get_config_file_path() -> Result<PathBuf, Error> {
-    let proj_dirs = get_project_dirs()?;
-    let config_dir = proj_dirs.config_dir();
-    Ok(config_dir.join(CONFIG_FILE_NAME))
+    let fallback = fallback_config_file()?;
+    if fallback.is_file() {
+        return Ok(fallback);
+    }
+    Ok(config_dir()?.join(CONFIG_FILE_NAME))
+}
+
+/// A settings or state file that was damaged (e.g. by a power loss while it was written) and
+/// was read from its backup instead, or ignored; see [`take_recoveries`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recovery {
+    pub path: PathBuf,
+    /// What is wrong with it.
+    pub reason: String,
+    /// The backup used instead; `None` if there was no usable one and defaults were used.
+    pub backup: Option<PathBuf>,
+}
+
+/// The damaged files recovered from since the last call, for the caller to warn about.
+pub fn take_recoveries() -> Vec<Recovery> {
+    std::mem::take(&mut *RECOVERIES.lock().unwrap())
+}
+
+/// The previous version of `path`, kept by [`write_atomic`].
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// What is wrong with the file at `path`, if it exists but isn't TOML. An empty file only
+/// counts as damaged when there is a backup, i.e. crbrs wrote it.
+fn damage(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(_) => return Some("not text".to_string()),
+    };
+    if text.trim_matches(|c: char| c.is_whitespace() || c == '\0').is_empty() {
+        return backup_path(path).is_file().then(|| "empty".to_string());
+    }
+    text.parse::<toml::Table>().err().map(|e| match e.span() {
+        Some(span) => format!("line {}: {}", text[..span.start].matches('\n').count() + 1, e.message()),
+        None => e.message().to_string(),
+    })
+}
+
+/// The file to read `path` from: `path` itself, or its backup if `path` is damaged. `None`
+/// if it is damaged and there is no usable backup, in which case the defaults apply.
+fn readable(path: &Path) -> Option<PathBuf> {
+    let Some(reason) = damage(path) else { return Some(path.to_path_buf()) };
+    let backup = Some(backup_path(path)).filter(|backup| backup.is_file() && damage(backup).is_none());
+    match &backup {
+        Some(backup) => log::warn!("{:?} is damaged ({}); using its backup {:?}", path, reason, backup),
+        None => log::warn!("{:?} is damaged ({}) and has no usable backup; using the defaults", path, reason),
+    }
+    let recovery = Recovery { path: path.to_path_buf(), reason, backup: backup.clone() };
+    let mut recoveries = RECOVERIES.lock().unwrap();
+    if !recoveries.contains(&recovery) {
+        recoveries.push(recovery);
+    }
+    backup
+}
+
+/// Replaces the file at `path` with `contents` so that a crash or power loss leaves either the
+/// old or the new file, never a partial one: the contents go to a temporary file next to it,
+/// are flushed to disk and renamed over it. The old file is kept as its backup; if it was
+/// damaged it is kept as `<name>.damaged` instead, for hand edits gone wrong.
+pub(crate) fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(".tmp");
+    let temp = dir.join(temp_name);
+    let written = std::fs::File::create(&temp).and_then(|mut file| {
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e);
+    }
+    if path.is_file() {
+        let keep = match damage(path) {
+            Some(_) => path.with_extension("toml.damaged"),
+            None => backup_path(path),
+        };
+        // Copied rather than moved, so there is always a file at `path`.
+        if let Err(e) = std::fs::copy(path, &keep) {
+            log::warn!("Could not keep the previous {:?} as {:?}: {}", path, keep, e);
+        }
+    }
+    if let Err(e) = std::fs::rename(&temp, path) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e);
+    }
+    // The rename itself is only durable once the directory is flushed.
+    #[cfg(unix)]
+    if let Err(e) = std::fs::File::open(dir).and_then(|dir| dir.sync_all()) {
+        log::debug!("Could not flush {:?}: {}", dir, e);
+    }
+    Ok(())
 }
 
+/// Where crbrs keeps what it manages itself (the installed compilers), apart from the
+/// hand-edited config file.
+pub fn state_file_path() -> Result<PathBuf, Error> {
+    Ok(data_dir()?.join(STATE_FILE_NAME))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    #[serde(default)]
+    installed_compilers: HashMap<String, CompilerInfo>,
+}
 
+/// The installed compilers recorded in the state file; `None` if there is no state file yet
+/// (older versions kept them in the config file, which is then still read).
+pub fn load_installed_compilers() -> Result<Option<HashMap<String, CompilerInfo>>, Error> {
+    let Some(path) = readable(&state_file_path()?) else { return Ok(None) };
+    match std::fs::read_to_string(&path) {
+        Ok(text) => toml::from_str::<State>(&text)
+            .map(|state| Some(state.installed_compilers))
+            .map_err(|e| config_message(format!("{}: {}", path.display(), e.message()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Loads the settings, with the active profile (if any) applied.
+///
+/// The config file may build on shared files, e.g. team defaults for the repository URL and
+/// lint settings, with `include = ["team-defaults.toml"]` (paths relative to the including
+/// file). Settings are layered, each layer overriding the ones before it: the built-in
+/// defaults, the included files in order (a file's own includes come before it), the config
+/// file itself, the active profile, and finally `CRBRS_*` environment variables (see
+/// [`environment_overrides`]). A value in the config file equal to the built-in default does
+/// not override an included file, as crbrs used to write every setting out. The installed
+/// compilers come from the state file (see [`state_file_path`]).
 pub fn load_settings() -> Result<Settings, Error> {
-    let config_file_path = get_config_file_path()?;
-    let config_dir = config_file_path.parent().ok_or_else(|| Error::Io(
+    apply_environment(load_file_settings()?)
+}
+
+/// The settings from the files, without the environment's overrides.
+fn load_file_settings() -> Result<Settings, Error> {
+    let settings = load_base_settings()?;
+    match active_profile() {
+        Some(name) => apply_profile(settings, &name),
+        None => Ok(settings),
+    }
+}
+
+/// Settings overridden by environment variables, e.g. in a container: `CRBRS_WINE_PATH` sets
+/// `wine_path`, `CRBRS_COMPILER_STORAGE_PATH` sets `compiler_storage_path`, and a double
+/// underscore separates the keys of a table (`CRBRS_NETWORK__OFFLINE=true`). Numbers and
+/// booleans are parsed. The variables crbrs reads for other purposes (`CRBRS_HOME`,
+/// `CRBRS_PROFILE`, `CRBRS_LANG`, `CRBRS_SECRET_<NAME>`) are not settings.
+pub fn environment_overrides() -> Result<toml::Table, Error> {
+    let environment = Environment::with_prefix(SETTINGS_ENV_PREFIX)
+        .prefix_separator("_")
+        .separator("__")
+        .try_parsing(true)
+        .ignore_empty(true);
+    let mut table: toml::Table = Config::builder().add_source(environment).build()?.try_deserialize().map_err(Error::Config)?;
+    table.retain(|key, _| !matches!(key, "home" | "profile" | "lang") && !key.starts_with("secret_"));
+    Ok(table)
+}
+
+/// Layers [`environment_overrides`] over `settings`, remembering what they set so that saving
+/// the settings doesn't write them to the config file.
+fn apply_environment(settings: Settings) -> Result<Settings, Error> {
+    let overrides = environment_overrides()?;
+    if overrides.is_empty() {
+        return Ok(settings);
+    }
+    let mut leaves = Vec::new();
+    profile_leaves(&overrides, &[], &mut leaves);
+    let mut value = to_value(&settings)?;
+    merge(&mut value, toml::Value::Table(overrides));
+    let mut effective = from_value(value).map_err(|e| config_message(format!("{} environment variables: {}", SETTINGS_ENV_PREFIX, e)))?;
+    effective.active_profile = settings.active_profile;
+    let applied = to_value(&effective)?;
+    for path in leaves {
+        let variable = format!("{}_{}", SETTINGS_ENV_PREFIX, path.join("__").to_uppercase());
+        match get_path(&applied, &path) {
+            Some(value) => {
+                log::debug!("{} overrides {}", variable, path.join("."));
+                effective.environment.push((path, value.clone()));
+            }
+            None => log::warn!("Ignoring {}: there is no setting {}", variable, path.join(".")),
+        }
+    }
+    Ok(effective)
+}
+
+/// `settings` with the values the environment set (and nothing since changed) back to those of
+/// the files.
+fn unapply_environment(settings: &Settings) -> Result<Settings, Error> {
+    let files = to_value(&load_file_settings()?)?;
+    let mut value = to_value(settings)?;
+    for (path, from_environment) in &settings.environment {
+        if get_path(&value, path) == Some(from_environment) {
+            set_path(&mut value, path, get_path(&files, path).cloned());
+        }
+    }
+    let mut out = from_value(value)?;
+    out.active_profile = settings.active_profile.clone();
+    Ok(out)
+}
+
+fn config_message(message: String) -> Error {
+    Error::Config(config::ConfigError::Message(message))
+}
+
+/// Reads a config file as a table; `None` if it doesn't exist.
+fn read_table(path: &Path) -> Result<Option<toml::Table>, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => toml::from_str(&text).map(Some).map_err(|e| config_message(format!("{}: {}", path.display(), e.message()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The `include` list of a config file.
+fn include_list(table: &toml::Table) -> Vec<String> {
+    table
+        .get("include")
+        .and_then(|v| v.as_array())
+        .map(|names| names.iter().filter_map(|n| n.as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+/// Adds the files `path` includes to `out`, each after its own includes. `chain` holds the
+/// files being included, to catch cycles.
+fn collect_includes(path: &Path, chain: &mut Vec<PathBuf>, out: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let Some(table) = read_table(path)? else { return Ok(()) };
+    collect_named(path, &include_list(&table), chain, out)
+}
+
+/// Like [`collect_includes`], for the files `names` (relative to `path`'s directory).
+fn collect_named(path: &Path, names: &[String], chain: &mut Vec<PathBuf>, out: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for name in names {
+        let file = dir.join(name);
+        let file = std::fs::canonicalize(&file).map_err(|_| {
+            config_message(format!("{} includes '{}', which was not found at {}", path.display(), name, file.display()))
+        })?;
+        if chain.contains(&file) {
+            return Err(config_message(format!("{} includes itself (through {})", file.display(), path.display())));
+        }
+        chain.push(file.clone());
+        collect_includes(&file, chain, out)?;
+        chain.pop();
+        if !out.contains(&file) {
+            out.push(file);
+        }
+    }
+    Ok(())
+}
+
+/// The files `config_file` includes, directly or not, in the order they are layered.
+pub fn included_files(config_file: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut chain = vec![std::fs::canonicalize(config_file).unwrap_or_else(|_| config_file.to_path_buf())];
+    let mut out = Vec::new();
+    collect_includes(config_file, &mut chain, &mut out)?;
+    Ok(out)
+}
+
+/// `value` with every table key lowercased, as the config loader reads keys.
+fn lowercase_keys(value: toml::Value) -> toml::Value {
+    match value {
+        toml::Value::Table(table) => {
+            toml::Value::Table(table.into_iter().map(|(key, value)| (key.to_lowercase(), lowercase_keys(value))).collect())
+        }
+        value => value,
+    }
+}
+
+/// Removes the values of `user` that equal the built-in `defaults` where `included` sets
+/// them, so the included files decide those.
+fn drop_default_values(user: &mut toml::Table, included: &toml::Table, defaults: Option<&toml::Table>) {
+    user.retain(|key, value| {
+        let lower = key.to_lowercase();
+        let (Some(included), Some(default)) = (included.get(&lower), defaults.and_then(|d| d.get(&lower))) else {
+            return true;
+        };
+        match (value, included, default) {
+            (toml::Value::Table(user), toml::Value::Table(included), toml::Value::Table(default)) => {
+                drop_default_values(user, included, Some(default));
+                true
+            }
+            (value, _, default) => value != default,
+        }
+    });
+}
+
+/// Settings from the built-in defaults and `includes`, with `user` (the config file's own
+/// table, if any) on top.
+fn layered_settings(includes: &[PathBuf], user: Option<toml::Table>) -> Result<Settings, Error> {
+    let mut builder = Config::builder().add_source(Config::try_from(&Settings::default())?);
+    let mut included = toml::Value::Table(toml::Table::new());
+    for include in includes {
+        builder = builder.add_source(File::from(include.clone()).format(FileFormat::Toml));
+        merge(&mut included, lowercase_keys(toml::Value::Table(read_table(include)?.unwrap_or_default())));
+    }
+    let own_includes = user.as_ref().map(include_list).unwrap_or_default();
+    if let Some(mut user) = user {
+        let defaults = to_value(&Settings::default())?;
+        if let toml::Value::Table(included) = &included {
+            drop_default_values(&mut user, included, defaults.as_table());
+        }
+        let text = toml::to_string(&user).map_err(|e| Error::Config(config::ConfigError::Foreign(Box::new(e))))?;
+        builder = builder.add_source(File::from_str(&text, FileFormat::Toml));
+    }
+    let mut settings = builder.build()?.try_deserialize::<Settings>().map_err(Error::Config)?;
+    settings.include = own_includes; // Not the include lists of the included files
+    Ok(settings)
+}
+
+fn load_base_settings() -> Result<Settings, Error> {
+    let mut settings = load_config_file_settings()?;
+    if let Some(installed) = load_installed_compilers()? {
+        settings.installed_compilers = installed;
+    }
+    Ok(settings)
+}
+
+fn load_config_file_settings() -> Result<Settings, Error> {
+    let Some(config_file_path) = readable(&get_config_file_path()?) else { return Ok(Settings::default()) };
+    let _config_dir = config_file_path.parent().ok_or_else(|| Error::Io(
         std::io::Error::new(std::io::ErrorKind::NotFound, "Config directory not found") // Should not happen if get_config_file_path succeeds
     ))?;
 
     // Ensure config directory exists (optional, depends on desired behavior)
-    // std::fs::create_dir_all(config_dir)?;
+    // std::fs::create_dir_all(_config_dir)?;
 
     log::debug!("Attempting to load configuration from: {:?}", config_file_path);
 
+    // A file that doesn't parse is reported by the loader below, with its usual message.
+    let includes = match read_table(&config_file_path) {
+        Ok(Some(table)) if !include_list(&table).is_empty() => included_files(&config_file_path)?,
+        _ => Vec::new(),
+    };
+    if !includes.is_empty() {
+        log::debug!("Configuration includes: {:?}", includes);
+        return layered_settings(&includes, read_table(&config_file_path)?);
+    }
+
     let settings = Config::builder()
         // Start with default values for Settings
         .add_source(Config::try_from(&Settings::default())?)
         // Layer on the user's config file if it exists
-        .add_source(File::from(config_file_path.clone()).required(false))
-        // TODO: Add environment variable overrides? e.g., CRBRS_WINE_PATH
+        .add_source(File::from(config_file_path.clone()).format(FileFormat::Toml).required(false))
         .build()?;
 
     log::debug!("Configuration loaded successfully.");
@@ -59,22 +463,218 @@ pub fn load_settings() -> Result<Settings, Error> {
     settings.try_deserialize::<Settings>().map_err(Error::Config)
 }
 
+/// Removes the values of `value` that equal those in `base`, and tables left empty by that.
+fn strip_inherited(value: &mut toml::Table, base: &toml::Table) {
+    value.retain(|key, value| match (value, base.get(key)) {
+        (toml::Value::Table(table), Some(toml::Value::Table(base))) => {
+            let was_empty = table.is_empty();
+            strip_inherited(table, base);
+            was_empty || !table.is_empty()
+        }
+        (value, Some(base)) => value != base,
+        (_, None) => true,
+    });
+}
+
+/// Deep-merges `overlay` into `base` (tables are merged, everything else replaced).
+pub(crate) fn merge(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn to_value(settings: &Settings) -> Result<toml::Value, Error> {
+    toml::Value::try_from(settings).map_err(|e| Error::Config(config::ConfigError::Foreign(Box::new(e))))
+}
+
+fn from_value(value: toml::Value) -> Result<Settings, Error> {
+    value.try_into().map_err(|e: toml::de::Error| Error::Config(config::ConfigError::Message(e.to_string())))
+}
+
+/// Layers the `[profiles.<name>]` table over `settings`.
+fn apply_profile(settings: Settings, name: &str) -> Result<Settings, Error> {
+    let Some(profile) = settings.profiles.get(name).cloned() else {
+        let known: Vec<&str> = settings.profiles.keys().map(String::as_str).collect();
+        return Err(Error::Config(config::ConfigError::Message(format!(
+            "unknown profile '{}' (defined: {})",
+            name,
+            if known.is_empty() { "none".to_string() } else { known.join(", ") }
+        ))));
+    };
+    log::debug!("Applying config profile '{}'", name);
+    let mut value = to_value(&settings)?;
+    merge(&mut value, toml::Value::Table(profile));
+    let mut effective = from_value(value)?;
+    effective.profiles = settings.profiles;
+    effective.active_profile = Some(name.to_string());
+    Ok(effective)
+}
+
+/// Paths of the leaf values `profile` sets (tables it overrides are descended into).
+fn profile_leaves(profile: &toml::Table, prefix: &[String], out: &mut Vec<Vec<String>>) {
+    for (key, value) in profile {
+        let path: Vec<String> = prefix.iter().cloned().chain([key.clone()]).collect();
+        match value {
+            toml::Value::Table(table) => profile_leaves(table, &path, out),
+            _ => out.push(path),
+        }
+    }
+}
+
+fn get_path<'a>(value: &'a toml::Value, path: &[String]) -> Option<&'a toml::Value> {
+    path.iter().try_fold(value, |current, key| current.get(key))
+}
+
+fn set_path(value: &mut toml::Value, path: &[String], new: Option<toml::Value>) {
+    let Some((last, parents)) = path.split_last() else { return };
+    let mut current = value;
+    for key in parents {
+        let Some(table) = current.as_table_mut() else { return };
+        current = table.entry(key.clone()).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    }
+    if let Some(table) = current.as_table_mut() {
+        match new {
+            Some(new) => {
+                table.insert(last.clone(), new);
+            }
+            None => {
+                table.remove(last);
+            }
+        }
+    }
+}
+
+/// Splits settings loaded with a profile back into the base settings and the profile: keys
+/// the profile sets keep their base value and any change to them is stored in the profile.
+fn unapply_profile(settings: &Settings, name: &str) -> Result<Settings, Error> {
+    let base = to_value(&load_base_settings()?)?;
+    let mut effective = to_value(settings)?;
+    let mut profile = settings.profiles.get(name).cloned().unwrap_or_default();
+    let mut leaves = Vec::new();
+    profile_leaves(&profile, &[], &mut leaves);
+    let mut profile_value = toml::Value::Table(std::mem::take(&mut profile));
+    for path in leaves {
+        set_path(&mut profile_value, &path, get_path(&effective, &path).cloned());
+        set_path(&mut effective, &path, get_path(&base, &path).cloned());
+    }
+    let mut out = from_value(effective)?;
+    if let toml::Value::Table(profile) = profile_value {
+        out.profiles.insert(name.to_string(), profile);
+    }
+    Ok(out)
+}
+
 pub fn save_settings(settings: &Settings) -> Result<(), Error> {
+    let unapplied;
+    let settings = match settings.environment.is_empty() {
+        true => settings,
+        false => {
+            unapplied = unapply_environment(settings)?;
+            &unapplied
+        }
+    };
+    if let Some(name) = &settings.active_profile {
+        return write_settings(&unapply_profile(settings, name)?);
+    }
+    write_settings(settings)
+}
+
+/// Whether `e` means the location can't be written to at all.
+fn is_read_only(e: &std::io::Error) -> bool {
+    matches!(e.kind(), std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::ReadOnlyFilesystem)
+}
+
+/// Saves only the installed compilers, leaving the config file alone, after installing or
+/// removing a compiler. A config file still listing installed compilers (as older versions
+/// wrote them) is saved too, once, to move them out of it.
+pub fn save_state(settings: &Settings) -> Result<(), Error> {
     let config_file_path = get_config_file_path()?;
-     let config_dir = config_file_path.parent().ok_or_else(|| Error::Io(
+    match read_table(&config_file_path) {
+        Ok(Some(table)) if table.contains_key("installed_compilers") => save_settings(settings),
+        _ => write_state(settings),
+    }
+}
+
+fn write_state(settings: &Settings) -> Result<(), Error> {
+    let path = state_file_path()?;
+    if settings.installed_compilers.is_empty() && !path.exists() {
+        return Ok(()); // Nothing to record yet
+    }
+    let not_saved = |e: std::io::Error| Error::SettingsNotSaved { path: path.clone(), reason: e.to_string() };
+    let state = State { installed_compilers: settings.installed_compilers.clone() };
+    let text = toml::to_string_pretty(&state).map_err(|e| Error::Config(config::ConfigError::Foreign(Box::new(e))))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(not_saved)?;
+    }
+    write_atomic(&path, &text).map_err(not_saved)?;
+    log::debug!("State saved to: {:?}", path);
+    Ok(())
+}
+
+fn write_settings(settings: &Settings) -> Result<(), Error> {
+    write_state(settings)?;
+    let config_file_path = get_config_file_path()?;
+    let not_saved = |path: &Path, e: std::io::Error| Error::SettingsNotSaved { path: path.to_path_buf(), reason: e.to_string() };
+    match write_settings_to(settings, &config_file_path) {
+        Err(Error::Io(e)) if is_read_only(&e) && config_file_path != fallback_config_file()? => {
+            // Keep working on managed machines: save to the data directory, on top of the
+            // (read-only) config file, which stays in effect for everything not changed here.
+            let fallback = fallback_config_file()?;
+            log::warn!("Cannot write {:?} ({}); saving the settings to {:?} instead", config_file_path, e, fallback);
+            let mut settings = settings.clone();
+            settings.include = match config_file_path.is_file() {
+                true => vec![config_file_path.to_string_lossy().into_owned()],
+                false => Vec::new(),
+            };
+            write_settings_to(&settings, &fallback).map_err(|e| match e {
+                Error::Io(io) => not_saved(&fallback, io),
+                other => other,
+            })
+        }
+        Err(Error::Io(e)) => Err(not_saved(&config_file_path, e)),
+        result => result,
+    }
+}
+
+fn write_settings_to(settings: &Settings, config_file_path: &Path) -> Result<(), Error> {
+    let config_dir = config_file_path.parent().ok_or_else(|| Error::Io(
         std::io::Error::new(std::io::ErrorKind::NotFound, "Config directory not found")
     ))?;
 
     // Ensure the configuration directory exists
     std::fs::create_dir_all(config_dir)?;
 
-    // Serialize the settings into TOML format
-    let toml_content = toml::to_string_pretty(settings)
-        .map_err(|e| Error::Config(config::ConfigError::Foreign(Box::new(e))))?; // Wrap toml error
+    // Serialize the settings into TOML format. With includes, only what differs from the
+    // included files (and the built-in defaults) is written, so later changes to a shared
+    // file take effect.
+    let mut value = to_value(settings)?;
+    if let Some(table) = value.as_table_mut() {
+        table.remove("installed_compilers"); // Kept in the state file
+        if !settings.include.is_empty() {
+            let mut includes = Vec::new();
+            collect_named(config_file_path, &settings.include, &mut vec![config_file_path.to_path_buf()], &mut includes)?;
+            let base = to_value(&layered_settings(&includes, None)?)?;
+            if let Some(base) = base.as_table() {
+                strip_inherited(table, base);
+            }
+            table.insert("include".to_string(), toml::Value::try_from(&settings.include).expect("strings serialize"));
+        }
+    }
+    let toml_content = toml::to_string_pretty(&value)
+    .map_err(|e| Error::Config(config::ConfigError::Foreign(Box::new(e))))?; // Wrap toml error
 
-    // Write the TOML content to the config file
-    std::fs::write(&config_file_path, toml_content)?;
+    write_atomic(config_file_path, &toml_content)?;
 
     log::info!("Configuration saved to: {:?}", config_file_path);
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,555 @@
+// FILE: crbrs-lib/src/convert.rs
+
+//! `crbrs data convert`: writes TOA5, TOB1 or TOB3 data as Apache Parquet, Arrow IPC (Feather v2),
+//! plain CSV or TOA5, so pandas, polars or R read it without parsing Campbell headers.
+//!
+//! Conversion streams: records are read, converted and written in batches of
+//! [`BATCH_RECORDS`], so multi-gigabyte card files convert in bounded memory.
+//!
+//! Columns are typed from the header: `TS` fields become timestamps (without a time zone,
+//! like the logger clock; see `crbrs data retime`), `RN` fields 64-bit integers, TOB1 and
+//! TOB3 fields their declared type, and TOA5 fields whose values in the first batch are all numbers
+//! floats (`NAN` and empty values become nulls); everything else is a string. Each column
+//! carries its `units` and `processing` as field metadata, and the schema carries the
+//! environment line (`station`, `logger`, `program`, `table`, ...).
+//!
+//! With `append`, only records newer than the last one already in the output are added, which
+//! both resumes an interrupted conversion and adds a new collection to an existing file. Text
+//! outputs are appended to in place (an incomplete last line is dropped first); Parquet and
+//! Arrow files cannot be appended to, so their contents are copied into a new file. Those are
+//! always written under a `.partial` name and renamed when complete, so an interrupted
+//! conversion never leaves a truncated Parquet or Arrow file behind.
+
+use crate::clock::parse_logger_time;
+use crate::tob1::{Tob1Reader, ValueType};
+use crate::tob3::Tob3Reader;
+use crate::toa5::{split_line, Field, Toa5Reader};
+use crate::Error;
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, Float32Array, Float64Array, Int64Array, RecordBatch, StringArray, TimestampMillisecondArray,
+};
+use arrow_schema::{DataType, Field as ArrowField, Schema, SchemaRef, TimeUnit};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Records converted and written at a time.
+pub const BATCH_RECORDS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvertFormat {
+    Parquet,
+    /// Arrow IPC file format, also known as Feather v2.
+    Arrow,
+    /// CSV with a single header row of field names.
+    Csv,
+    Toa5,
+}
+
+impl FromStr for ConvertFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "parquet" => Ok(ConvertFormat::Parquet),
+            "arrow" | "feather" | "ipc" => Ok(ConvertFormat::Arrow),
+            "csv" => Ok(ConvertFormat::Csv),
+            "toa5" => Ok(ConvertFormat::Toa5),
+            other => Err(format!("unknown data format '{}' (expected parquet, arrow, csv or toa5)", other)),
+        }
+    }
+}
+
+impl ConvertFormat {
+    /// The format an output file name asks for, from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "parquet" | "pq" => Some(ConvertFormat::Parquet),
+            "arrow" | "feather" | "ipc" => Some(ConvertFormat::Arrow),
+            "csv" => Some(ConvertFormat::Csv),
+            "dat" => Some(ConvertFormat::Toa5),
+            _ => None,
+        }
+    }
+
+    /// File extension for outputs in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConvertFormat::Parquet => "parquet",
+            ConvertFormat::Arrow => "arrow",
+            ConvertFormat::Csv => "csv",
+            ConvertFormat::Toa5 => "dat",
+        }
+    }
+
+    fn is_text(self) -> bool {
+        matches!(self, ConvertFormat::Csv | ConvertFormat::Toa5)
+    }
+}
+
+/// How far a conversion is, for progress indicators.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+    pub records: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConvertReport {
+    /// Records written by this conversion.
+    pub records: usize,
+    /// Records skipped because the output already had them (with `append`).
+    pub skipped: usize,
+}
+
+/// Data to convert, from a file or a card image. TOB3 files are read out of order, so it
+/// must be seekable.
+pub trait DataSource: Read + Seek {}
+
+impl<T: Read + Seek> DataSource for T {}
+
+type Source<'a> = BufReader<Box<dyn DataSource + 'a>>;
+
+/// A TOA5, TOB1 or TOB3 file read record by record.
+enum DataReader<'a> {
+    Toa5(Toa5Reader<Source<'a>>),
+    Tob1(Tob1Reader<Source<'a>>),
+    Tob3(Tob3Reader<Source<'a>>),
+}
+
+/// Arrow type of a binary value.
+fn binary_type(value_type: &ValueType) -> DataType {
+    match value_type {
+        ValueType::Unsigned { .. } | ValueType::Signed { .. } => DataType::Int64,
+        ValueType::Float { size: 8, .. } => DataType::Float64,
+        ValueType::Float { .. } | ValueType::Fp2 => DataType::Float32,
+        ValueType::Bool { .. } => DataType::Boolean,
+        ValueType::Time { .. } => DataType::Timestamp(TimeUnit::Millisecond, None),
+        ValueType::Ascii { .. } => DataType::Utf8,
+    }
+}
+
+impl<'a> DataReader<'a> {
+    fn open(source: Box<dyn DataSource + 'a>, name: &str) -> Result<Self, Error> {
+        let fail = |e: String| Error::Data(format!("{}: {}", name, e));
+        let mut reader = BufReader::new(source);
+        let start = reader.fill_buf()?;
+        if start.starts_with(b"\"TOB1\"") {
+            Tob1Reader::new(reader).map(DataReader::Tob1).map_err(fail)
+        } else if start.starts_with(b"\"TOB3\"") {
+            Tob3Reader::new(reader).map(DataReader::Tob3).map_err(fail)
+        } else {
+            Toa5Reader::new(reader).map(DataReader::Toa5).map_err(fail)
+        }
+    }
+
+    fn environment(&self) -> &[String] {
+        match self {
+            DataReader::Toa5(reader) => &reader.environment,
+            DataReader::Tob1(reader) => &reader.environment,
+            DataReader::Tob3(reader) => &reader.environment,
+        }
+    }
+
+    fn fields(&self) -> &[Field] {
+        match self {
+            DataReader::Toa5(reader) => &reader.fields,
+            DataReader::Tob1(reader) => &reader.fields,
+            DataReader::Tob3(reader) => &reader.fields,
+        }
+    }
+
+    fn bytes_read(&self) -> u64 {
+        match self {
+            DataReader::Toa5(reader) => reader.bytes_read(),
+            DataReader::Tob1(reader) => reader.bytes_read(),
+            DataReader::Tob3(reader) => reader.bytes_read(),
+        }
+    }
+
+    /// Column types the file declares (TOB1) or the units imply.
+    fn declared_types(&self) -> Vec<Option<DataType>> {
+        let by_units = |field: &Field| match field.units.as_str() {
+            "TS" => Some(DataType::Timestamp(TimeUnit::Millisecond, None)),
+            "RN" => Some(DataType::Int64),
+            _ => None,
+        };
+        match self {
+            DataReader::Toa5(reader) => reader.fields.iter().map(by_units).collect(),
+            DataReader::Tob1(reader) => reader.types.iter().map(|t| Some(binary_type(t))).collect(),
+            DataReader::Tob3(reader) => reader.fields[..2]
+                .iter()
+                .map(by_units)
+                .chain(reader.types.iter().map(|t| Some(binary_type(t))))
+                .collect(),
+        }
+    }
+
+    /// Stored type of each field of a binary file.
+    fn value_types(&self) -> Vec<Option<ValueType>> {
+        match self {
+            DataReader::Toa5(reader) => vec![None; reader.fields.len()],
+            DataReader::Tob1(reader) => reader.types.iter().copied().map(Some).collect(),
+            DataReader::Tob3(reader) => [None, None].into_iter().chain(reader.types.iter().copied().map(Some)).collect(),
+        }
+    }
+
+    /// Up to `count` more records.
+    fn next_batch(&mut self, count: usize) -> Result<Vec<Vec<String>>, String> {
+        match self {
+            DataReader::Toa5(reader) => reader.by_ref().take(count).collect(),
+            DataReader::Tob1(reader) => reader.by_ref().take(count).collect(),
+            DataReader::Tob3(reader) => reader.by_ref().take(count).collect(),
+        }
+    }
+}
+
+/// Names of the environment line values, in order, as stored in the schema metadata.
+const ENVIRONMENT: [&str; 8] = ["format", "station", "logger", "serial", "os", "program", "signature", "table"];
+
+/// Values that stand for a missing number.
+fn is_missing(value: &str) -> bool {
+    matches!(value.trim(), "" | "NAN" | "NaN" | "nan")
+}
+
+/// Type of a column without a declared type, from its first values.
+fn inferred_type(values: &[&str]) -> DataType {
+    if !values.is_empty() && values.iter().all(|v| is_missing(v) || v.trim().parse::<f64>().is_ok()) {
+        DataType::Float64
+    } else {
+        DataType::Utf8
+    }
+}
+
+fn number<T: FromStr>(value: &str) -> Option<T> {
+    if is_missing(value) {
+        None
+    } else {
+        value.trim().parse().ok()
+    }
+}
+
+/// `values` as an array of `data_type`; values that do not parse become nulls.
+fn column(data_type: &DataType, values: &[&str]) -> ArrayRef {
+    match data_type {
+        DataType::Timestamp(..) => Arc::new(values.iter().map(|v| parse_logger_time(v)).collect::<TimestampMillisecondArray>()),
+        DataType::Int64 => Arc::new(values.iter().map(|v| number::<i64>(v)).collect::<Int64Array>()),
+        DataType::Float32 => Arc::new(values.iter().map(|v| number::<f32>(v)).collect::<Float32Array>()),
+        DataType::Float64 => Arc::new(values.iter().map(|v| number::<f64>(v)).collect::<Float64Array>()),
+        DataType::Boolean => Arc::new(
+            values
+                .iter()
+                .map(|v| match v.trim().to_ascii_lowercase().as_str() {
+                    "-1" | "1" | "true" => Some(true),
+                    "0" | "false" => Some(false),
+                    _ => None,
+                })
+                .collect::<BooleanArray>(),
+        ),
+        _ => Arc::new(values.iter().map(|v| Some(*v)).collect::<StringArray>()),
+    }
+}
+
+/// The Arrow schema for a file's fields, with declared types where known and types inferred
+/// from `records` otherwise.
+fn schema(environment: &[String], fields: &[Field], declared: &[Option<DataType>], records: &[Vec<String>]) -> SchemaRef {
+    let arrow_fields = fields.iter().enumerate().map(|(i, field)| {
+        let data_type = declared[i].clone().unwrap_or_else(|| {
+            inferred_type(&records.iter().map(|record| record[i].as_str()).collect::<Vec<_>>())
+        });
+        let metadata = HashMap::from([
+            ("units".to_string(), field.units.clone()),
+            ("processing".to_string(), field.processing.clone()),
+        ]);
+        ArrowField::new(field.name.as_str(), data_type, true).with_metadata(metadata)
+    });
+    let metadata = ENVIRONMENT
+        .iter()
+        .zip(environment)
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect::<HashMap<_, _>>();
+    Arc::new(Schema::new(arrow_fields.collect::<Vec<_>>()).with_metadata(metadata))
+}
+
+/// `records` as a record batch of `schema`.
+fn record_batch(schema: &SchemaRef, records: &[Vec<String>]) -> Result<RecordBatch, Error> {
+    let columns = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| column(field.data_type(), &records.iter().map(|record| record[i].as_str()).collect::<Vec<_>>()))
+        .collect();
+    RecordBatch::try_new(schema.clone(), columns).map_err(|e| Error::Data(e.to_string()))
+}
+
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// A record as a text line in `format`, with its line ending.
+fn text_line(record: &[String], format: ConvertFormat) -> String {
+    let values = record.iter().map(|v| match format {
+        ConvertFormat::Toa5 if v.parse::<f64>().is_ok_and(f64::is_finite) => v.clone(),
+        ConvertFormat::Toa5 => quoted(v),
+        _ if v.contains([',', '"', '\n']) => quoted(v),
+        _ => v.clone(),
+    });
+    let end = if format == ConvertFormat::Toa5 { "\r\n" } else { "\n" };
+    format!("{}{}", values.collect::<Vec<_>>().join(","), end)
+}
+
+/// Header lines of a text output.
+fn text_header(environment: &[String], fields: &[Field], format: ConvertFormat) -> String {
+    let names: Vec<String> = fields.iter().map(|f| f.name.clone()).collect();
+    if format != ConvertFormat::Toa5 {
+        return text_line(&names, format);
+    }
+    let mut environment = environment.to_vec();
+    if let Some(kind) = environment.first_mut() {
+        *kind = "TOA5".to_string();
+    }
+    let line = |values: Vec<String>| format!("{}\r\n", values.iter().map(|v| quoted(v)).collect::<Vec<_>>().join(","));
+    [
+        line(environment),
+        line(names),
+        line(fields.iter().map(|f| f.units.clone()).collect()),
+        line(fields.iter().map(|f| f.processing.clone()).collect()),
+    ]
+    .concat()
+}
+
+enum Writer {
+    Text(BufWriter<File>, ConvertFormat),
+    Arrow(arrow_ipc::writer::FileWriter<BufWriter<File>>),
+    Parquet(parquet::arrow::ArrowWriter<BufWriter<File>>),
+}
+
+impl Writer {
+    fn binary(path: &Path, format: ConvertFormat, schema: &SchemaRef) -> Result<Self, Error> {
+        let file = BufWriter::new(File::create(path)?);
+        let fail = |e: String| Error::Data(format!("{}: {}", path.display(), e));
+        match format {
+            ConvertFormat::Arrow => arrow_ipc::writer::FileWriter::try_new(file, schema).map(Writer::Arrow).map_err(|e| fail(e.to_string())),
+            _ => {
+                let properties = parquet::file::properties::WriterProperties::builder()
+                    .set_compression(parquet::basic::Compression::ZSTD(Default::default()))
+                    .build();
+                parquet::arrow::ArrowWriter::try_new(file, schema.clone(), Some(properties))
+                    .map(Writer::Parquet)
+                    .map_err(|e| fail(e.to_string()))
+            }
+        }
+    }
+
+    fn write(&mut self, schema: Option<&SchemaRef>, records: &[Vec<String>]) -> Result<(), Error> {
+        match (self, schema) {
+            (Writer::Text(file, format), _) => {
+                for record in records {
+                    file.write_all(text_line(record, *format).as_bytes())?;
+                }
+            }
+            (writer, Some(schema)) => writer.write_batch(&record_batch(schema, records)?)?,
+            (_, None) => {}
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self, batch: &RecordBatch) -> Result<(), Error> {
+        let result = match self {
+            Writer::Text(..) => Ok(()),
+            Writer::Arrow(writer) => writer.write(batch).map_err(|e| e.to_string()),
+            Writer::Parquet(writer) => writer.write(batch).map_err(|e| e.to_string()),
+        };
+        result.map_err(Error::Data)
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        let result = match self {
+            Writer::Text(mut file, _) => return Ok(file.flush()?),
+            Writer::Arrow(mut writer) => writer.finish().map_err(|e| e.to_string()),
+            Writer::Parquet(writer) => writer.close().map(|_| ()).map_err(|e| e.to_string()),
+        };
+        result.map_err(Error::Data)
+    }
+}
+
+/// Prepares a text output for appending: checks that it holds the same fields, drops an
+/// incomplete last line and returns the last record's timestamp.
+fn resume_text(output: &Path, fields: &[Field], format: ConvertFormat, timestamp: usize) -> Result<Option<i64>, Error> {
+    let fail = |e: &str| Error::Data(format!("Cannot append to {}: {}", output.display(), e));
+    let mut file = OpenOptions::new().read(true).write(true).open(output)?;
+    let mut header = String::new();
+    let mut reader = BufReader::new(&mut file);
+    for _ in 0..if format == ConvertFormat::Toa5 { 2 } else { 1 } {
+        header.clear();
+        reader.read_line(&mut header)?;
+    }
+    let names = split_line(header.trim_end_matches(['\r', '\n']));
+    if names.iter().ne(fields.iter().map(|f| &f.name)) {
+        return Err(fail("it has different fields"));
+    }
+
+    // Only the end of the file is read; a record line is far shorter than this.
+    let length = file.metadata()?.len();
+    let start = length.saturating_sub(64 * 1024);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    let complete = tail.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    if complete < tail.len() {
+        log::warn!("Dropping an incomplete last line from {:?}", output);
+        file.set_len(start + complete as u64)?;
+    }
+    let last_line = tail[..complete].split(|&b| b == b'\n').rev().map(|line| String::from_utf8_lossy(line)).find(|line| !line.trim().is_empty());
+    Ok(last_line.and_then(|line| split_line(line.trim_end()).get(timestamp).and_then(|v| parse_logger_time(v))))
+}
+
+/// Copies the batches of an existing Parquet or Arrow output into `writer` (created with its
+/// schema) and returns the writer, the schema and the latest timestamp.
+fn resume_binary(output: &Path, partial: &Path, format: ConvertFormat, fields: &[Field]) -> Result<(Writer, SchemaRef, Option<i64>), Error> {
+    let fail = |e: String| Error::Data(format!("Cannot append to {}: {}", output.display(), e));
+    let batches: Box<dyn Iterator<Item = Result<RecordBatch, String>>> = match format {
+        ConvertFormat::Arrow => Box::new(
+            arrow_ipc::reader::FileReader::try_new(File::open(output)?, None)
+                .map_err(|e| fail(e.to_string()))?
+                .map(|batch| batch.map_err(|e| e.to_string())),
+        ),
+        _ => Box::new(
+            parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(File::open(output)?)
+                .and_then(|builder| builder.with_batch_size(BATCH_RECORDS).build())
+                .map_err(|e| fail(e.to_string()))?
+                .map(|batch| batch.map_err(|e| e.to_string())),
+        ),
+    };
+    let mut batches = batches.peekable();
+    let schema = match batches.peek() {
+        Some(Ok(batch)) => batch.schema(),
+        Some(Err(e)) => return Err(fail(e.clone())),
+        None => return Err(fail("it holds no records".to_string())),
+    };
+    if schema.fields().iter().map(|f| f.name()).ne(fields.iter().map(|f| &f.name)) {
+        return Err(fail("it has different fields".to_string()));
+    }
+    let mut writer = Writer::binary(partial, format, &schema)?;
+    let mut latest: Option<i64> = None;
+    for batch in batches {
+        let batch = batch.map_err(fail)?;
+        if let Some(times) = batch.column_by_name("TIMESTAMP").and_then(|c| c.as_any().downcast_ref::<TimestampMillisecondArray>()) {
+            latest = times.iter().flatten().chain(latest).max();
+        }
+        writer.write_batch(&batch)?;
+    }
+    Ok((writer, schema, latest))
+}
+
+/// The header of a TOA5, TOB1 or TOB3 file.
+#[derive(Debug, Clone)]
+pub struct DataHeader {
+    pub environment: Vec<String>,
+    /// Fields as TOA5 would have them.
+    pub fields: Vec<Field>,
+    /// Stored type of each field; `None` for TOA5 files and the `TIMESTAMP` and `RECORD` of TOB3.
+    pub types: Vec<Option<ValueType>>,
+}
+
+/// Reads the header of the TOA5, TOB1 or TOB3 file at `path`, without its records.
+pub fn read_header(path: &Path) -> Result<DataHeader, Error> {
+    let reader = DataReader::open(Box::new(File::open(path)?), &path.display().to_string())?;
+    Ok(DataHeader { environment: reader.environment().to_vec(), fields: reader.fields().to_vec(), types: reader.value_types() })
+}
+
+/// Converts the TOA5, TOB1 or TOB3 file at `path` to `output`, calling `progress` after every batch.
+pub fn convert_file(
+    path: &Path,
+    output: &Path,
+    format: ConvertFormat,
+    append: bool,
+    progress: &mut dyn FnMut(Progress),
+) -> Result<ConvertReport, Error> {
+    let total_bytes = fs::metadata(path)?.len();
+    convert(Box::new(File::open(path)?), total_bytes, &path.display().to_string(), output, format, append, progress)
+}
+
+/// Converts TOA5, TOB1 or TOB3 data of `total_bytes` read from `source` (named `name` in messages),
+/// like [`convert_file`].
+pub fn convert(
+    source: Box<dyn DataSource + '_>,
+    total_bytes: u64,
+    name: &str,
+    output: &Path,
+    format: ConvertFormat,
+    append: bool,
+    progress: &mut dyn FnMut(Progress),
+) -> Result<ConvertReport, Error> {
+    let mut reader = DataReader::open(source, name)?;
+    let fields = reader.fields().to_vec();
+    let environment = reader.environment().to_vec();
+    let declared = reader.declared_types();
+    let timestamp = fields.iter().position(|f| f.name.eq_ignore_ascii_case("TIMESTAMP"));
+    let append = append && output.exists();
+    if append && timestamp.is_none() {
+        return Err(Error::Data(format!("{}: appending needs a TIMESTAMP field", name)));
+    }
+
+    let partial = PathBuf::from(format!("{}.partial", output.display()));
+    let (mut writer, mut schema, last) = match (format.is_text(), append) {
+        (true, true) => {
+            let last = resume_text(output, &fields, format, timestamp.unwrap_or_default())?;
+            let file = OpenOptions::new().append(true).open(output)?;
+            (Some(Writer::Text(BufWriter::new(file), format)), None, last)
+        }
+        (true, false) => {
+            let mut file = BufWriter::new(File::create(output)?);
+            file.write_all(text_header(&environment, &fields, format).as_bytes())?;
+            (Some(Writer::Text(file, format)), None, None)
+        }
+        (false, true) => {
+            let (writer, schema, last) = resume_binary(output, &partial, format, &fields)?;
+            (Some(writer), Some(schema), last)
+        }
+        // Created with the first batch, whose values give the types of undeclared columns.
+        (false, false) => (None, None, None),
+    };
+
+    let mut report = ConvertReport::default();
+    loop {
+        let mut records = reader.next_batch(BATCH_RECORDS).map_err(|e| Error::Data(format!("{}: {}", name, e)))?;
+        let done = records.len() < BATCH_RECORDS;
+        if let (Some(last), Some(timestamp)) = (last, timestamp) {
+            let before = records.len();
+            records.retain(|record| parse_logger_time(&record[timestamp]).is_none_or(|time| time > last));
+            report.skipped += before - records.len();
+        }
+        if writer.is_none() && (!records.is_empty() || done) {
+            let new_schema = schema.insert(self::schema(&environment, &fields, &declared, &records));
+            writer = Some(Writer::binary(&partial, format, new_schema)?);
+        }
+        if let Some(writer) = writer.as_mut() {
+            writer.write(schema.as_ref(), &records)?;
+        }
+        report.records += records.len();
+        progress(Progress { bytes_read: reader.bytes_read(), total_bytes, records: report.records });
+        if done {
+            break;
+        }
+    }
+    if let Some(writer) = writer {
+        writer.finish()?;
+    }
+    if !format.is_text() {
+        fs::rename(&partial, output)?;
+    }
+    log::info!(
+        "Converted {} to {:?} ({:?}, {} records, {} already present)",
+        name,
+        output,
+        format,
+        report.records,
+        report.skipped
+    );
+    Ok(report)
+}
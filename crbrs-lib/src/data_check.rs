@@ -0,0 +1,188 @@
+// FILE: crbrs-lib/src/data_check.rs
+
+//! `crbrs data check`: compares the header of a TOA5, TOB1 or TOB3 file with the `DataTable`
+//! definition in a program, to catch data written by another revision of the program (a field
+//! added, renamed or moved, a changed processing or output type) before it is merged or
+//! loaded into a database that expects the current layout.
+//!
+//! The table is found by the name on the file's environment line. Field names, their order
+//! and processing must match; data types are only known for binary files. Units only count
+//! where the program declares them, and differences are warnings, since they do not change
+//! what the columns hold.
+
+use crate::convert::{self, DataHeader};
+use crate::syntax::tables::{self, FieldInfo, TableInfo};
+use crate::tob1::ValueType;
+use crate::validate::Severity;
+use crate::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Field the finding concerns, if any.
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &self.field {
+            Some(field) => write!(f, "{}: {}: {}", severity, field, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DataCheck {
+    /// Table named on the environment line of the data file.
+    pub table: String,
+    /// Program named on the environment line, e.g. `CPU:station.cr1x`.
+    pub data_program: String,
+    pub findings: Vec<Finding>,
+}
+
+impl DataCheck {
+    pub fn errors(&self) -> usize {
+        self.findings.iter().filter(|f| f.severity == Severity::Error).count()
+    }
+}
+
+/// Whether a stored type is what the program's output data type (`FP2`, `IEEE4`, `Long`,
+/// ...) is written as; `None` if the program type is not one we know.
+fn same_type(program: &str, stored: ValueType) -> Option<bool> {
+    let program = program.trim().to_ascii_uppercase();
+    Some(match program.as_str() {
+        "FP2" => stored == ValueType::Fp2,
+        "IEEE4" => matches!(stored, ValueType::Float { size: 4, .. }),
+        "IEEE8" => matches!(stored, ValueType::Float { size: 8, .. }),
+        "LONG" => matches!(stored, ValueType::Signed { size: 4, .. }),
+        "UINT2" => matches!(stored, ValueType::Unsigned { size: 2, .. }),
+        "UINT4" => matches!(stored, ValueType::Unsigned { size: 4, .. }),
+        "BOOLEAN" | "BOOL8" => matches!(stored, ValueType::Bool { .. } | ValueType::Unsigned { size: 1, .. }),
+        "NSEC" => matches!(stored, ValueType::Time { .. }),
+        _ if program.starts_with("STRING") => matches!(stored, ValueType::Ascii { .. }),
+        _ => return None,
+    })
+}
+
+/// Fields the table writes: `TIMESTAMP` and `RECORD`, then those of its output instructions.
+fn expected_fields(table: &TableInfo) -> Vec<FieldInfo> {
+    let fixed = |name: &str, units: &str| FieldInfo {
+        name: name.to_string(),
+        processing: String::new(),
+        source: String::new(),
+        units: Some(units.to_string()),
+        data_type: None,
+        description: None,
+        instruction: String::new(),
+        span: table.span,
+    };
+    [fixed("TIMESTAMP", "TS"), fixed("RECORD", "RN")].into_iter().chain(table.fields.iter().cloned()).collect()
+}
+
+/// File name of a program as the environment line gives it (`CPU:station.cr1x`).
+fn program_file_name(name: &str) -> &str {
+    let name = name.rsplit_once(':').map_or(name, |(_, file)| file);
+    name.rsplit(['/', '\\']).next().unwrap_or(name)
+}
+
+/// Compares the header of a data file with the tables of the program `source`, named
+/// `program_name` (its file name).
+pub fn check(source: &str, program_name: &str, header: &DataHeader) -> DataCheck {
+    let env = |i: usize| header.environment.get(i).cloned().unwrap_or_default();
+    let (table_name, data_program) = (env(7), env(5));
+    let mut findings = Vec::new();
+    let mut finding = |severity, field: Option<&str>, message: String| {
+        findings.push(Finding { severity, field: field.map(str::to_string), message })
+    };
+
+    if !data_program.is_empty() && !program_file_name(&data_program).eq_ignore_ascii_case(program_file_name(program_name)) {
+        finding(
+            Severity::Warning,
+            None,
+            format!("the data was written by '{}', not '{}'", data_program, program_name),
+        );
+    }
+
+    let program = crate::syntax::parse(source);
+    let tables = tables::tables(source, &program);
+    let Some(table) = tables.iter().find(|t| t.name.eq_ignore_ascii_case(&table_name)) else {
+        let names: Vec<&str> = tables.iter().map(|t| t.name.as_str()).collect();
+        finding(
+            Severity::Error,
+            None,
+            format!(
+                "the program has no table '{}' (it has: {})",
+                table_name,
+                if names.is_empty() { "none".to_string() } else { names.join(", ") }
+            ),
+        );
+        return DataCheck { table: table_name, data_program, findings };
+    };
+
+    let expected = expected_fields(table);
+    let position = |name: &str| header.fields.iter().position(|f| f.name.eq_ignore_ascii_case(name));
+    for field in &expected {
+        if position(&field.name).is_none() {
+            finding(Severity::Error, Some(&field.name), "in the program but not in the data".to_string());
+        }
+    }
+    for field in &header.fields {
+        if !expected.iter().any(|e| e.name.eq_ignore_ascii_case(&field.name)) {
+            finding(Severity::Error, Some(&field.name), "in the data but not in the program".to_string());
+        }
+    }
+    let order: Vec<usize> = expected.iter().filter_map(|f| position(&f.name)).collect();
+    if order.windows(2).any(|pair| pair[0] > pair[1]) {
+        finding(Severity::Error, None, "the fields are in a different order than in the program".to_string());
+    }
+
+    for field in &expected {
+        let Some(i) = position(&field.name) else { continue };
+        let data = &header.fields[i];
+        if !field.processing.is_empty() && !data.processing.eq_ignore_ascii_case(&field.processing) {
+            finding(
+                Severity::Error,
+                Some(&field.name),
+                format!("processing is '{}' in the data but '{}' in the program", data.processing, field.processing),
+            );
+        }
+        if let (Some(program_type), Some(Some(stored))) = (&field.data_type, header.types.get(i)) {
+            if same_type(program_type, *stored) == Some(false) {
+                finding(
+                    Severity::Error,
+                    Some(&field.name),
+                    format!("stored as {} but the program writes {}", stored, program_type),
+                );
+            }
+        }
+        if let Some(units) = field.units.as_deref().filter(|u| !u.is_empty()) {
+            if data.units.trim() != units.trim() {
+                finding(
+                    Severity::Warning,
+                    Some(&field.name),
+                    format!("units are '{}' in the data but '{}' in the program", data.units, units),
+                );
+            }
+        }
+    }
+    DataCheck { table: table.name.clone(), data_program, findings }
+}
+
+/// Runs [`check`] on a program file and a data file.
+pub fn check_files(program: &Path, data: &Path) -> Result<DataCheck, Error> {
+    let source = fs::read_to_string(program)?;
+    let header = convert::read_header(data)?;
+    let name = program.file_name().unwrap_or_default().to_string_lossy();
+    let result = check(&source, &name, &header);
+    log::info!("Checked {:?} against {:?}: {} finding(s)", data, program, result.findings.len());
+    Ok(result)
+}
@@ -0,0 +1,200 @@
+// FILE: crbrs-lib/src/dedup.rs
+
+//! Content-addressed storage for extracted compiler files.
+//!
+//! Compiler versions ship largely identical DLLs, so after a compiler is unpacked every file
+//! is hashed and hard-linked to `<storage>/.objects/<sha256>`: the first copy of a file becomes
+//! the object, later copies are replaced by links to it. Where hard links are not possible
+//! (e.g. storage split across file systems) the plain copy is kept.
+//!
+//! Objects no other file links to any more are removed when a compiler is removed (Unix only;
+//! elsewhere the link count is not available and objects are kept).
+
+use crate::Settings;
+use crate::Error;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory holding the shared file objects.
+pub fn objects_dir(storage_path: &Path) -> PathBuf {
+    storage_path.join(".objects")
+}
+
+/// Files below `dir`, recursively (symlinks are not followed).
+fn files_in(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut out = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                out.push(entry.path());
+            }
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+fn hash_file(path: &Path) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Object name for `path`: its content hash, plus its mode on Unix since linked files share
+/// permissions.
+fn object_name(path: &Path, metadata: &fs::Metadata) -> Result<String, Error> {
+    let hash = hash_file(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(format!("{}-{:o}", hash, metadata.permissions().mode() & 0o7777))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        Ok(hash)
+    }
+}
+
+/// Result of [`deduplicate`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DedupStats {
+    pub files: usize,
+    /// Files replaced by a link to an existing object.
+    pub linked: usize,
+    pub bytes_saved: u64,
+}
+
+/// Links every file below `dir` into the object store of `storage_path`.
+pub fn deduplicate(storage_path: &Path, dir: &Path) -> Result<DedupStats, Error> {
+    let objects = objects_dir(storage_path);
+    fs::create_dir_all(&objects)?;
+    let mut stats = DedupStats::default();
+    for path in files_in(dir)? {
+        let metadata = fs::metadata(&path)?;
+        let object = objects.join(object_name(&path, &metadata)?);
+        stats.files += 1;
+        if object.exists() {
+            // Link next to the file first so a failed link leaves the original in place.
+            let staged = path.with_extension("crbrs-link");
+            match fs::hard_link(&object, &staged) {
+                Ok(()) => {
+                    fs::rename(&staged, &path)?;
+                    stats.linked += 1;
+                    stats.bytes_saved += metadata.len();
+                }
+                Err(e) => log::debug!("Keeping copy of {:?}, cannot link {:?}: {}", path, object, e),
+            }
+        } else if let Err(e) = fs::hard_link(&path, &object) {
+            log::debug!("Cannot add {:?} to the object store: {}", path, e);
+        }
+    }
+    log::info!(
+        "Deduplicated {:?}: {} of {} files shared, {} bytes saved",
+        dir, stats.linked, stats.files, stats.bytes_saved
+    );
+    Ok(stats)
+}
+
+/// Removes objects no compiler links to any more and returns how many were removed.
+pub fn collect_garbage(storage_path: &Path) -> Result<usize, Error> {
+    let objects = objects_dir(storage_path);
+    if !objects.is_dir() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        for path in files_in(&objects)? {
+            if fs::metadata(&path)?.nlink() <= 1 {
+                log::debug!("Removing unreferenced object {:?}", path);
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Disk usage of one installed compiler.
+#[derive(Debug, Clone)]
+pub struct CompilerUsage {
+    pub id: String,
+    /// Size of all its files.
+    pub total: u64,
+    /// Size of the files whose storage is shared with another compiler.
+    pub shared: u64,
+}
+
+/// Result of [`usage`].
+#[derive(Debug, Clone)]
+pub struct StorageUsage {
+    pub compilers: Vec<CompilerUsage>,
+    /// Size of the archives kept in `.archives` for delta patches.
+    pub archives: u64,
+    /// Space actually used on disk, counting shared files once, archives included.
+    pub on_disk: u64,
+}
+
+/// Identity of the stored data behind a file: the inode on Unix, the content elsewhere.
+fn storage_key(path: &Path, metadata: &fs::Metadata) -> Result<String, Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let _ = path;
+        Ok(format!("{}:{}", metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        hash_file(path)
+    }
+}
+
+/// Per-compiler and total disk usage of the installed compilers and their kept archives.
+pub fn usage(storage_path: &Path, settings: &Settings) -> Result<StorageUsage, Error> {
+    let mut files_by_compiler = Vec::new();
+    let mut owners: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut sizes: HashMap<String, u64> = HashMap::new();
+    let mut ids: Vec<&String> = settings.installed_compilers.keys().collect();
+    ids.sort();
+    for id in ids {
+        let dir = storage_path.join(&settings.installed_compilers[id].install_subdir);
+        let mut files = Vec::new();
+        if dir.is_dir() {
+            for path in files_in(&dir)? {
+                let metadata = fs::metadata(&path)?;
+                let key = storage_key(&path, &metadata)?;
+                owners.entry(key.clone()).or_default().insert(id.clone());
+                sizes.insert(key.clone(), metadata.len());
+                files.push((key, metadata.len()));
+            }
+        }
+        files_by_compiler.push((id.clone(), files));
+    }
+    let compilers = files_by_compiler
+        .into_iter()
+        .map(|(id, files)| CompilerUsage {
+            total: files.iter().map(|(_, size)| size).sum(),
+            shared: files.iter().filter(|(key, _)| owners[key].len() > 1).map(|(_, size)| size).sum(),
+            id,
+        })
+        .collect();
+    let archives_dir = storage_path.join(".archives");
+    let mut archives = 0;
+    if archives_dir.is_dir() {
+        for path in files_in(&archives_dir)? {
+            archives += fs::metadata(&path)?.len();
+        }
+    }
+    Ok(StorageUsage { compilers, archives, on_disk: sizes.values().sum::<u64>() + archives })
+}
@@ -0,0 +1,202 @@
+// FILE: crbrs-lib/src/deploy.rs
+
+//! Deploy guard: compares a program about to be deployed with the program currently on
+//! the logger and blocks version regressions and schema-breaking table changes unless
+//! forced.
+//!
+//! `crbrs logger send` reads the running program back from the logger (its name is
+//! `Status.ProgName`) with File Receive before every send. `crbrs deploy check` and
+//! `logger send --against` compare with a copy of it instead, e.g. one retrieved with
+//! LoggerNet.
+
+use crate::logger::Logger;
+use crate::pakbus::{Value, ValueKind};
+use crate::syntax::tables::{self, TableInfo};
+use crate::syntax::{parse, version};
+use crate::Error;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+
+/// A difference between the data tables of the deployed and the new program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaChange {
+    TableAdded(String),
+    TableRemoved(String),
+    /// Field names, order, processing or data types of an existing table changed.
+    FieldsChanged { table: String, detail: String },
+    IntervalChanged { table: String, from: Option<String>, to: Option<String> },
+}
+
+impl SchemaChange {
+    /// Whether the change makes the logger discard or split the stored data of a table.
+    pub fn is_breaking(&self) -> bool {
+        !matches!(self, SchemaChange::TableAdded(_))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            SchemaChange::TableAdded(table) => format!("table '{}' added", table),
+            SchemaChange::TableRemoved(table) => format!("table '{}' removed", table),
+            SchemaChange::FieldsChanged { table, detail } => format!("table '{}': {}", table, detail),
+            SchemaChange::IntervalChanged { table, from, to } => format!(
+                "table '{}': interval {} -> {}",
+                table,
+                from.as_deref().unwrap_or("none"),
+                to.as_deref().unwrap_or("none")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeployCheck {
+    /// Version header of the program being deployed.
+    pub new_version: Option<String>,
+    /// Version header of the program currently on the logger.
+    pub deployed_version: Option<String>,
+    pub schema_changes: Vec<SchemaChange>,
+}
+
+impl DeployCheck {
+    /// True if the logger runs a newer version than the one being deployed.
+    pub fn is_version_regression(&self) -> bool {
+        match (&self.new_version, &self.deployed_version) {
+            (Some(new), Some(deployed)) => version::compare_versions(new, deployed) == Some(Ordering::Less),
+            // Replacing a versioned program with an unversioned one loses track of the version.
+            (None, Some(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Reasons the deploy needs `--force`, empty if it is safe.
+    pub fn blockers(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.is_version_regression() {
+            out.push(format!(
+                "logger runs version {} but the program being deployed is {}",
+                self.deployed_version.as_deref().unwrap_or("?"),
+                self.new_version.as_deref().unwrap_or("unversioned")
+            ));
+        }
+        out.extend(self.schema_changes.iter().filter(|c| c.is_breaking()).map(|c| format!("schema change: {}", c.describe())));
+        out
+    }
+}
+
+fn field_signature(table: &TableInfo) -> Vec<String> {
+    table
+        .fields
+        .iter()
+        .map(|f| format!("{} ({} {})", f.name, f.processing, f.data_type.as_deref().unwrap_or("-")))
+        .collect()
+}
+
+fn compare_tables(deployed: &[TableInfo], new: &[TableInfo]) -> Vec<SchemaChange> {
+    let find = |tables: &[TableInfo], name: &str| tables.iter().find(|t| t.name.eq_ignore_ascii_case(name)).cloned();
+    let mut changes = Vec::new();
+    for old in deployed {
+        let Some(table) = find(new, &old.name) else {
+            changes.push(SchemaChange::TableRemoved(old.name.clone()));
+            continue;
+        };
+        let (before, after) = (field_signature(old), field_signature(&table));
+        if before != after {
+            let removed: Vec<&String> = before.iter().filter(|f| !after.contains(f)).collect();
+            let added: Vec<&String> = after.iter().filter(|f| !before.contains(f)).collect();
+            let detail = match (removed.is_empty(), added.is_empty()) {
+                (true, true) => "fields reordered".to_string(),
+                _ => {
+                    let list = |fields: &[&String]| fields.iter().map(|f| f.as_str()).collect::<Vec<_>>().join(", ");
+                    let mut parts = Vec::new();
+                    if !removed.is_empty() {
+                        parts.push(format!("removed {}", list(&removed)));
+                    }
+                    if !added.is_empty() {
+                        parts.push(format!("added {}", list(&added)));
+                    }
+                    parts.join("; ")
+                }
+            };
+            changes.push(SchemaChange::FieldsChanged { table: old.name.clone(), detail });
+        }
+        if old.interval != table.interval {
+            changes.push(SchemaChange::IntervalChanged {
+                table: old.name.clone(),
+                from: old.interval.clone(),
+                to: table.interval.clone(),
+            });
+        }
+    }
+    for table in new {
+        if find(deployed, &table.name).is_none() {
+            changes.push(SchemaChange::TableAdded(table.name.clone()));
+        }
+    }
+    changes
+}
+
+/// Compares the source of the program being deployed with the one on the logger.
+pub fn check(new_source: &str, deployed_source: &str) -> DeployCheck {
+    let (new_program, deployed_program) = (parse(new_source), parse(deployed_source));
+    DeployCheck {
+        new_version: version::header(&new_program).map(|h| h.version),
+        deployed_version: version::header(&deployed_program).map(|h| h.version),
+        schema_changes: compare_tables(
+            &tables::tables(deployed_source, &deployed_program),
+            &tables::tables(new_source, &new_program),
+        ),
+    }
+}
+
+/// Runs [`check`] on two files and fails with `Error::DeployBlocked` unless the deploy is
+/// safe or `force` is set.
+pub fn guard_files(new_program: &Path, deployed_program: &Path, force: bool) -> Result<DeployCheck, Error> {
+    guard(check(&fs::read_to_string(new_program)?, &fs::read_to_string(deployed_program)?), force)
+}
+
+/// The program running on a connected logger, read back from it: its name (e.g.
+/// `CPU:soil.cr1x`) and source. `None` if the logger runs no program.
+pub fn running_program(logger: &mut Logger) -> Result<Option<(String, String)>, Error> {
+    let name = match logger.value("Status", "ProgName", ValueKind::Text)? {
+        Some(Value::Text(name)) if !name.trim().is_empty() => name.trim().to_string(),
+        _ => return Ok(None),
+    };
+    let source = logger.receive_file(&name).map_err(|e| match e {
+        Error::Cancelled => e,
+        e => Error::DeployBlocked(format!("cannot read back the running program {} ({})", name, e)),
+    })?;
+    // The CRBasic editor saves Windows-1252; the tables and version header are ASCII anyway.
+    Ok(Some((name, String::from_utf8_lossy(&source).into_owned())))
+}
+
+/// Asked whether to deploy anyway, given the reason the deploy is blocked.
+pub type ConfirmBlocked<'a> = &'a dyn Fn(&str) -> Result<bool, Error>;
+
+/// Runs [`check`] of `new_source` against the program running on a connected logger. A
+/// blocked deploy goes ahead only if `confirm` (given the reason) says so; otherwise it fails
+/// with `Error::DeployBlocked`. Returns the running program's name with the result, or `None`
+/// if the logger runs no program or the block was overridden.
+pub fn guard_logger(logger: &mut Logger, new_source: &str, confirm: ConfirmBlocked) -> Result<Option<(String, DeployCheck)>, Error> {
+    let checked = running_program(logger).and_then(|running| {
+        running.map(|(name, deployed_source)| guard(check(new_source, &deployed_source), false).map(|result| (name, result))).transpose()
+    });
+    match checked {
+        Err(Error::DeployBlocked(reason)) if confirm(&reason)? => {
+            log::warn!("Deploy guard overridden: {}", reason);
+            Ok(None)
+        }
+        result => result,
+    }
+}
+
+fn guard(result: DeployCheck, force: bool) -> Result<DeployCheck, Error> {
+    let blockers = result.blockers();
+    if !blockers.is_empty() {
+        if !force {
+            return Err(Error::DeployBlocked(blockers.join("; ")));
+        }
+        log::warn!("Deploy guard overridden with --force: {}", blockers.join("; "));
+    }
+    Ok(result)
+}
@@ -0,0 +1,84 @@
+// FILE: crbrs-lib/src/diagnostics.rs
+
+//! Keeping compiler errors readable on badly broken files.
+//!
+//! One mistake (a missing `EndIf`, an undeclared variable) can make a compiler report hundreds
+//! of cascading errors, mostly the same message on line after line. [`condense`] groups
+//! errors with the same message in the same file into one entry listing its lines, in the
+//! order they were first reported, and keeps at most `max_diagnostics` entries (the setting of
+//! that name; 0 for no limit), counting the errors left out so output can end with "…and N more".
+//! The language server applies the same limit to what it publishes.
+//!
+//! [`root_cause`] links a compiler error to the mistake behind it where the source shows one,
+//! e.g. "Variable BattV is not declared" when the line declaring `BattV` could not be parsed;
+//! it is shown as a "note:" under the error, and as related information in the editor.
+
+use crate::syntax::lint::{self, Related};
+use crate::CompilationErrorDetail;
+
+/// Default for the `max_diagnostics` setting.
+pub const DEFAULT_MAX_DIAGNOSTICS: usize = 50;
+
+/// Errors with the same file and message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorGroup {
+    pub file: String,
+    pub message: String,
+    /// Lines it was reported at, without repeats, in the order first reported.
+    pub lines: Vec<u32>,
+    /// How many errors the group stands for, repeats included.
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Condensed {
+    pub groups: Vec<ErrorGroup>,
+    /// Errors in the groups left out by the limit.
+    pub hidden: usize,
+}
+
+/// Groups `errors` and keeps the first `limit` groups (all if `limit` is 0).
+pub fn condense(errors: &[CompilationErrorDetail], limit: usize) -> Condensed {
+    let mut groups: Vec<ErrorGroup> = Vec::new();
+    for error in errors {
+        let (file, message) = (error.file_path_in_log.trim(), error.message.trim());
+        let group = match groups.iter().position(|g| g.file == file && g.message == message) {
+            Some(index) => &mut groups[index],
+            None => {
+                groups.push(ErrorGroup { file: file.to_string(), message: message.to_string(), lines: Vec::new(), count: 0 });
+                groups.last_mut().expect("just pushed")
+            }
+        };
+        group.count += 1;
+        if let Some(line) = error.line.filter(|line| !group.lines.contains(line)) {
+            group.lines.push(line);
+        }
+    }
+    let hidden = if limit > 0 && groups.len() > limit { groups.drain(limit..).map(|g| g.count).sum() } else { 0 };
+    Condensed { groups, hidden }
+}
+
+/// Keeps the first `limit` of `items` (all if `limit` is 0), returning how many were dropped.
+pub fn truncate<T>(items: &mut Vec<T>, limit: usize) -> usize {
+    if limit == 0 || items.len() <= limit {
+        return 0;
+    }
+    items.drain(limit..).count()
+}
+
+/// The variable a compiler error reports as undeclared: "Variable BattV is not declared." (CR1000
+/// family) or "Undeclared variable Temp" (CR200).
+pub fn undeclared_name(message: &str) -> Option<&str> {
+    let message = message.trim().trim_end_matches('.');
+    let name = message
+        .strip_prefix("Undeclared variable ")
+        .or_else(|| message.strip_prefix("Variable ").and_then(|rest| rest.strip_suffix(" is not declared")))?
+        .trim();
+    (!name.is_empty() && !name.contains(char::is_whitespace)).then_some(name)
+}
+
+/// The earlier problem in `source` that a compiler error with `message` follows from, if the
+/// source shows one.
+pub fn root_cause(source: &str, message: &str) -> Option<Related> {
+    undeclared_name(message).and_then(|name| lint::undeclared_cause(source, name))
+}
@@ -0,0 +1,419 @@
+// FILE: crbrs-lib/src/docgen.rs
+
+use crate::syntax::ast::{walk_stmts, ExprKind, Program, StmtKind};
+use crate::syntax::tables::{self, expr_text};
+use crate::syntax::{index, version, wiring};
+use crate::Error;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Output format of `crbrs docgen`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+impl FromStr for DocFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(DocFormat::Markdown),
+            "html" => Ok(DocFormat::Html),
+            other => Err(format!("unknown documentation format '{}' (expected markdown or html)", other)),
+        }
+    }
+}
+
+impl DocFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            DocFormat::Markdown => "md",
+            DocFormat::Html => "html",
+        }
+    }
+}
+
+/// Returns true for CRBasic source extensions (`.cr1`, `.cr1x`, `.cr300`, `.cr6`, `.crb`, `.dld`, ...).
+pub fn is_crbasic_file(path: &Path) -> bool {
+    let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    let ext = ext.to_ascii_lowercase();
+    ext == "dld" || ext == "crb" || (ext.starts_with("cr") && ext.len() > 2 && ext[2..].chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Calibrated copies made by `crbrs build --with-calibration` are build outputs, not programs.
+fn is_calibrated_copy(path: &Path) -> bool {
+    std::fs::read(path).is_ok_and(|bytes| crate::syntax::calibration::is_calibrated(&String::from_utf8_lossy(&bytes)))
+}
+
+/// CRBasic programs in `path` (recursively), or `path` itself if it is a file. Sorted.
+/// Symlinked directories are skipped, so a link back up the tree doesn't loop forever.
+pub fn find_programs(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut found = Vec::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let entry_path = entry.path();
+            let hidden = entry_path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('.'));
+            if hidden {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(entry_path);
+            } else if file_type.is_symlink() && entry_path.is_dir() {
+                log::debug!("Skipping symlinked directory {:?}", entry_path);
+            } else if is_crbasic_file(&entry_path) && !is_calibrated_copy(&entry_path) {
+                found.push(entry_path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+// --- Document model ---
+
+/// Format-independent building blocks of a page.
+enum Block {
+    Heading(u8, String),
+    Paragraph(String),
+    List(Vec<String>),
+    /// List of (text, href) links.
+    Links(Vec<(String, String)>),
+    Table { headers: Vec<&'static str>, rows: Vec<Vec<String>> },
+    /// Preformatted text with a language tag (`mermaid` diagrams render on GitHub).
+    Code { lang: &'static str, text: String },
+}
+
+struct Page {
+    file_stem: String,
+    title: String,
+    blocks: Vec<Block>,
+}
+
+struct ProgramDoc {
+    path: PathBuf,
+    display: String,
+    page_stem: String,
+    includes: Vec<String>,
+}
+
+/// Generates documentation for every program under `input` into `output_dir`.
+/// Returns the paths of the written pages, index first.
+pub fn generate(input: &Path, output_dir: &Path, format: DocFormat) -> Result<Vec<PathBuf>, Error> {
+    let programs = find_programs(input)?;
+    if programs.is_empty() {
+        return Err(Error::Docgen(format!("no CRBasic programs found in '{}'", input.display())));
+    }
+    let root = if input.is_file() { input.parent().unwrap_or(Path::new(".")) } else { input };
+
+    let mut docs = Vec::new();
+    let mut pages = Vec::new();
+    for path in &programs {
+        let source = std::fs::read_to_string(path)?;
+        let display = path.strip_prefix(root).unwrap_or(path).display().to_string();
+        let page_stem = display.replace(['/', '\\', '.'], "_");
+        let program = crate::syntax::parse(&source);
+        let includes = includes(&program);
+        log::info!("Documenting {} ({} tables)", display, tables::tables(&source, &program).len());
+        pages.push(program_page(path, &display, &page_stem, &source, &program, &includes));
+        docs.push(ProgramDoc { path: path.clone(), display, page_stem, includes });
+    }
+    pages.insert(0, index_page(&docs));
+
+    std::fs::create_dir_all(output_dir)?;
+    let mut written = Vec::new();
+    for page in &pages {
+        let out_path = output_dir.join(format!("{}.{}", page.file_stem, format.extension()));
+        let text = match format {
+            DocFormat::Markdown => render_markdown(page),
+            DocFormat::Html => render_html(page),
+        };
+        std::fs::write(&out_path, text)?;
+        written.push(out_path);
+    }
+    Ok(written)
+}
+
+/// Targets of `Include "CPU:file.cr1"` statements, without the drive prefix.
+pub(crate) fn includes(program: &Program) -> Vec<String> {
+    let mut out = Vec::new();
+    walk_stmts(&program.body, &mut |stmt| {
+        if let StmtKind::Call(call) = &stmt.kind {
+            if call.name.is("Include") {
+                if let Some(ExprKind::Str(target)) = call.args.first().map(|a| &a.kind) {
+                    let file = target.rsplit([':', '/', '\\']).next().unwrap_or(target);
+                    out.push(file.to_string());
+                }
+            }
+        }
+    });
+    out
+}
+
+/// Leading comment block of the file, without comment markers.
+fn header_comment(program: &Program) -> String {
+    let first_code_line = program.body.first().map_or(u32::MAX, |s| s.span.line);
+    // The version header is shown in the summary instead.
+    let version_lines = version::header(program).map(|h| h.span.line..=h.end_line);
+    program
+        .comments
+        .iter()
+        .filter(|c| c.span.line < first_code_line && c.span.col == 0 && !version_lines.as_ref().is_some_and(|r| r.contains(&c.span.line)))
+        .map(|c| c.text.trim_start_matches('\'').trim().to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim()
+        .to_string()
+}
+
+/// `git log` entries for `path`, or `None` when it is not tracked by git.
+fn change_history(path: &Path) -> Option<Vec<Vec<String>>> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(["log", "--follow", "-n", "20", "--date=short", "--format=%h%x09%ad%x09%an%x09%s", "--"])
+        .arg(path.file_name()?)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let rows: Vec<Vec<String>> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| l.splitn(4, '\t').map(str::to_string).collect())
+        .collect();
+    (!rows.is_empty()).then_some(rows)
+}
+
+fn program_page(path: &Path, display: &str, page_stem: &str, source: &str, program: &Program, includes: &[String]) -> Page {
+    let mut blocks = Vec::new();
+    let header = header_comment(program);
+    if !header.is_empty() {
+        blocks.push(Block::Paragraph(header));
+    }
+
+    // Summary
+    let idx = index::index(program);
+    let routines: Vec<String> = idx.routines().map(|u| u.name.clone()).collect();
+    let mut scans = Vec::new();
+    walk_stmts(&program.body, &mut |stmt| {
+        if let StmtKind::Scan(scan) = &stmt.kind {
+            let interval: Vec<&str> = scan.args.iter().take(2).map(|a| expr_text(source, a)).collect();
+            scans.push(interval.join(" "));
+        }
+    });
+    let data_tables = tables::tables(source, program);
+    let mut summary = vec![
+        format!("Lines: {}", source.lines().count()),
+        format!("Scans: {}", if scans.is_empty() { "none".to_string() } else { scans.join(", ") }),
+        format!("Data tables: {}", data_tables.len()),
+        format!("Subroutines/functions: {}", if routines.is_empty() { "none".to_string() } else { routines.join(", ") }),
+    ];
+    if let Some(header) = version::header(program) {
+        summary.insert(0, format!("Version: {} ({}, {})", header.version, header.date, header.author));
+    }
+    if !program.errors.is_empty() {
+        summary.push(format!("Syntax errors: {} (documentation may be incomplete)", program.errors.len()));
+    }
+    blocks.push(Block::Heading(2, "Summary".to_string()));
+    blocks.push(Block::List(summary));
+
+    // Tables and fields
+    blocks.push(Block::Heading(2, "Data tables".to_string()));
+    if data_tables.is_empty() {
+        blocks.push(Block::Paragraph("This program defines no data tables.".to_string()));
+    }
+    for table in &data_tables {
+        blocks.push(Block::Heading(3, table.name.clone()));
+        blocks.push(Block::Paragraph(format!(
+            "Interval: {}. Trigger: {}. Size: {}.",
+            table.interval.as_deref().unwrap_or("event driven"),
+            table.trigger,
+            table.size
+        )));
+        let rows = table
+            .fields
+            .iter()
+            .map(|f| {
+                vec![
+                    f.name.clone(),
+                    f.units.clone().unwrap_or_default(),
+                    f.processing.clone(),
+                    f.data_type.clone().unwrap_or_default(),
+                    f.description.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+        blocks.push(Block::Table { headers: vec!["Field", "Units", "Processing", "Type", "Description"], rows });
+    }
+
+    // Constants
+    let mut constants = Vec::new();
+    walk_stmts(&program.body, &mut |stmt| {
+        if let StmtKind::Const(consts) = &stmt.kind {
+            for c in consts {
+                constants.push(vec![c.name.text.clone(), expr_text(source, &c.value).to_string(), (stmt.span.line + 1).to_string()]);
+            }
+        }
+    });
+    if !constants.is_empty() {
+        blocks.push(Block::Heading(2, "Constants".to_string()));
+        blocks.push(Block::Table { headers: vec!["Name", "Value", "Line"], rows: constants });
+    }
+
+    // Wiring
+    let wiring = wiring::wiring(source, program);
+    if !wiring.is_empty() {
+        blocks.push(Block::Heading(2, "Wiring".to_string()));
+        let rows = wiring
+            .into_iter()
+            .map(|w| vec![w.terminal, w.role, w.instruction, w.dest.unwrap_or_default(), w.reps.unwrap_or_default(), (w.span.line + 1).to_string()])
+            .collect();
+        blocks.push(Block::Table { headers: vec!["Terminal", "Role", "Instruction", "Destination", "Reps", "Line"], rows });
+    }
+
+    if !includes.is_empty() {
+        blocks.push(Block::Heading(2, "Includes".to_string()));
+        blocks.push(Block::List(includes.to_vec()));
+    }
+
+    if let Some(history) = change_history(path) {
+        blocks.push(Block::Heading(2, "Change history".to_string()));
+        blocks.push(Block::Table { headers: vec!["Commit", "Date", "Author", "Summary"], rows: history });
+    }
+
+    Page { file_stem: page_stem.to_string(), title: display.to_string(), blocks }
+}
+
+fn index_page(docs: &[ProgramDoc]) -> Page {
+    let mut blocks = vec![
+        Block::Paragraph(format!("Generated by crbrs {} from {} program(s).", env!("CARGO_PKG_VERSION"), docs.len())),
+        Block::Heading(2, "Programs".to_string()),
+        Block::Links(docs.iter().map(|d| (d.display.clone(), d.page_stem.clone())).collect()),
+    ];
+
+    let edges: Vec<(&ProgramDoc, &String)> = docs.iter().flat_map(|d| d.includes.iter().map(move |i| (d, i))).collect();
+    if !edges.is_empty() {
+        blocks.push(Block::Heading(2, "Include graph".to_string()));
+        let node = |name: &str| name.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+        let mut graph = String::from("graph LR\n");
+        for (doc, target) in &edges {
+            let from = doc.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let _ = writeln!(graph, "  {}[\"{}\"] --> {}[\"{}\"]", node(&from), from, node(target), target);
+        }
+        blocks.push(Block::Code { lang: "mermaid", text: graph });
+    }
+
+    Page { file_stem: "index".to_string(), title: "Project documentation".to_string(), blocks }
+}
+
+// --- Renderers ---
+
+fn md_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+fn render_markdown(page: &Page) -> String {
+    let mut out = format!("# {}\n\n", page.title);
+    for block in &page.blocks {
+        match block {
+            Block::Heading(level, text) => {
+                let _ = writeln!(out, "{} {}\n", "#".repeat(*level as usize), text);
+            }
+            Block::Paragraph(text) => {
+                let _ = writeln!(out, "{}\n", text);
+            }
+            Block::List(items) => {
+                for item in items {
+                    let _ = writeln!(out, "*   {}", item);
+                }
+                out.push('\n');
+            }
+            Block::Links(links) => {
+                for (text, stem) in links {
+                    let _ = writeln!(out, "*   [{}](./{}.md)", text, stem);
+                }
+                out.push('\n');
+            }
+            Block::Table { headers, rows } => {
+                let _ = writeln!(out, "| {} |", headers.join(" | "));
+                let _ = writeln!(out, "|{}", " --- |".repeat(headers.len()));
+                for row in rows {
+                    let _ = writeln!(out, "| {} |", row.iter().map(|c| md_cell(c)).collect::<Vec<_>>().join(" | "));
+                }
+                out.push('\n');
+            }
+            Block::Code { lang, text } => {
+                let _ = writeln!(out, "```{}\n{}```\n", lang, text);
+            }
+        }
+    }
+    out
+}
+
+fn html_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn render_html(page: &Page) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(out, "<title>{}</title>", html_escape(&page.title));
+    out.push_str("<style>body{font-family:sans-serif;max-width:60em;margin:auto}table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:2px 6px}</style>\n");
+    out.push_str("</head>\n<body>\n");
+    let _ = writeln!(out, "<h1>{}</h1>", html_escape(&page.title));
+    for block in &page.blocks {
+        match block {
+            Block::Heading(level, text) => {
+                let _ = writeln!(out, "<h{0}>{1}</h{0}>", level, html_escape(text));
+            }
+            Block::Paragraph(text) => {
+                let _ = writeln!(out, "<p>{}</p>", html_escape(text));
+            }
+            Block::List(items) => {
+                out.push_str("<ul>\n");
+                for item in items {
+                    let _ = writeln!(out, "<li>{}</li>", html_escape(item));
+                }
+                out.push_str("</ul>\n");
+            }
+            Block::Links(links) => {
+                out.push_str("<ul>\n");
+                for (text, stem) in links {
+                    let _ = writeln!(out, "<li><a href=\"{}.html\">{}</a></li>", html_escape(stem), html_escape(text));
+                }
+                out.push_str("</ul>\n");
+            }
+            Block::Table { headers, rows } => {
+                out.push_str("<table>\n<tr>");
+                for h in headers {
+                    let _ = write!(out, "<th>{}</th>", h);
+                }
+                out.push_str("</tr>\n");
+                for row in rows {
+                    out.push_str("<tr>");
+                    for cell in row {
+                        let _ = write!(out, "<td>{}</td>", html_escape(cell));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</table>\n");
+            }
+            Block::Code { lang, text } => {
+                let _ = writeln!(out, "<pre class=\"{}\">{}</pre>", lang, html_escape(text));
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
@@ -0,0 +1,344 @@
+// FILE: crbrs-lib/src/explain.rs
+
+//! Stable error codes and `crbrs explain <CODE>`.
+//!
+//! Every [`Error`] variant has a code `Ennn` ([`Error::code`]) and every lint rule a code
+//! `Lnnn` ([`crate::syntax::lint::Rule::code`]). Codes are shown with errors and diagnostics in
+//! every output format, so support docs can be searched for them and CI can assert on them.
+//! Codes are never reused or renumbered: a removed variant's code stays retired, new variants
+//! get the next free number.
+
+use crate::syntax::lint;
+use crate::Error;
+
+/// What an error code means, what causes it and how to fix it.
+#[derive(Debug, Clone, Copy)]
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub text: &'static str,
+}
+
+const fn explanation(code: &'static str, title: &'static str, text: &'static str) -> Explanation {
+    Explanation { code, title, text }
+}
+
+pub static ERRORS: &[Explanation] = &[
+    explanation("E001", "Configuration error", "\
+The settings could not be loaded or a setting was rejected.
+
+Causes: a syntax error or a value of the wrong type in config.toml, an included file that is
+missing or includes itself, an unknown key given to `crbrs config set`, or an invalid
+crbrs.toml project manifest.
+
+Fix: run `crbrs config validate` to see the offending line, or `crbrs config path` to find the
+file and correct it."),
+    explanation("E002", "I/O error", "\
+A file or directory could not be read or written.
+
+Causes: the path does not exist, permissions, a full disk, or a file locked by another program
+(on Windows, often an antivirus scanner or the logger software).
+
+Fix: check the path named in the message and that you can write to it."),
+    explanation("E003", "Network error", "\
+A download or request failed.
+
+Causes: no connection, a proxy or firewall, a TLS problem, or a server error.
+
+Fix: check the connection and the URL; behind a proxy set HTTPS_PROXY. `[network]` settings
+control timeouts and retries, and --offline uses cached files only."),
+    explanation("E004", "Rate limited", "\
+The server refused more requests for now (HTTP 429), e.g. GitHub's limit for anonymous
+downloads.
+
+Fix: wait the time given in the message, or configure a token for the host under
+`[network.tokens]`."),
+    explanation("E005", "Offline", "\
+Something needed the network while running with --offline (or the network was unreachable and
+no cached copy exists).
+
+Fix: run once with a connection so the manifest and archives are cached, or drop --offline."),
+    explanation("E006", "ZIP archive error", "\
+A compiler archive could not be unpacked.
+
+Causes: a truncated or corrupted download, a manifest pointing at something that is not an
+archive, or an `archive_format` in the manifest entry that does not match the file (ZIP,
+tar.gz and 7z are supported).
+
+Fix: `crbrs self purge --caches` removes cached archives so the next install downloads again."),
+    explanation("E007", "Compiler not found", "\
+The compiler ID given (with --compiler, a `'crbrs: compiler=` directive or a target) is not
+installed, or its executable is missing.
+
+Fix: `crbrs compiler list` shows what is installed; install the compiler with
+`crbrs compiler install <ID>`, or correct the ID (the message suggests close matches)."),
+    explanation("E008", "Checksum mismatch", "\
+A downloaded compiler archive does not have the SHA-256 checksum the manifest lists.
+
+Causes: a corrupted or interrupted download, a proxy altering downloads, or a manifest that is
+out of date with the file it points at.
+
+Fix: try again; if it persists, report it to the maintainers of the compiler repository. Do not
+install archives whose checksum does not match."),
+    explanation("E009", "No compiler for the file extension", "\
+No compiler is associated with the program's file extension, and none was chosen with
+--compiler or a directive.
+
+Fix: `crbrs config set-association --extension <EXT> --compiler-id <ID>`, or add the
+association to the project's crbrs.toml."),
+    explanation("E010", "Associated compiler not installed", "\
+The program's extension is associated with a compiler that is not installed.
+
+Fix: `crbrs compiler install <ID>` (or compile with --install-missing), or point the
+association at an installed compiler."),
+    explanation("E011", "Project compilers missing", "\
+The project's crbrs.toml lists required compilers that are not installed, so nothing in the
+project is compiled.
+
+Fix: install them with `crbrs compiler install <ID>`, or compile with --install-missing."),
+    explanation("E012", "Wine not found", "\
+The compiler is a Windows program and Wine, needed to run it on this system, was not found.
+
+Fix: install Wine (e.g. `brew install --cask wine-stable` or your distribution's package), or
+set `wine_path` to its location."),
+    explanation("E013", "Subprocess failed", "\
+A program crbrs runs (the compiler, Wine, ssh, ...) could not be started.
+
+Causes: the executable is missing, not executable, or built for another architecture.
+
+Fix: check the path in the message; `crbrs compiler list` shows compilers whose executable is
+missing."),
+    explanation("E014", "Compilation failed", "\
+The compiler rejected the program. The errors it reported are listed with their line numbers.
+
+Fix: correct the program. Errors in included files are reported by the compiler against the
+including program's line of the Include."),
+    explanation("E015", "Compilation failed (unrecognized output)", "\
+The compiler reported a failure in a format crbrs does not recognize, so its raw output is
+shown instead of parsed errors.
+
+Fix: read the raw output. If it is a new compiler version, please report the output so the
+parser can learn it."),
+    explanation("E016", "Compiler execution failed", "\
+The compiler failed and wrote its details to a log file, which is shown."),
+    explanation("E017", "Build failed", "\
+Some files of a batch compile or build failed. Each failure was reported above the summary.
+
+Fix: correct the failing programs; --summary writes the per-file results as JSON."),
+    explanation("E018", "Invalid compiler source or manifest", "\
+The compiler manifest, an archive or a compiler directory being linked is not valid.
+
+Fix: check `compiler_repository_url`, and for `crbrs compiler link` that the directory has a
+crbrs-compiler.toml or pass --executable."),
+    explanation("E019", "Application directories not found", "\
+crbrs could not determine where to keep its configuration and data, usually because HOME (or
+the platform's equivalent) is not set.
+
+Fix: set HOME, or use portable mode with --portable <dir> (or CRBRS_HOME)."),
+    explanation("E020", "Compiler not in the manifest", "\
+The compiler ID is not offered by the configured compiler repository.
+
+Fix: `crbrs compiler list-available` shows the IDs it offers; the message suggests close
+matches."),
+    explanation("E021", "Invalid file extension", "\
+The file has no extension (or one that is not valid text), so no compiler can be chosen for it.
+
+Fix: rename the file with its logger's extension (e.g. .cr1x, .cr300), or pass --compiler."),
+    explanation("E022", "Download image not supported", "\
+The compiler's family compiles programs on the logger itself (the CR1000 family), so there is no
+binary image to produce.
+
+Fix: send the program source instead of an image."),
+    explanation("E023", "Download image not produced", "\
+The compiler finished but the image it should have written is not there.
+
+Fix: check the compiler output with -v; the program may have errors the compiler did not
+report clearly."),
+    explanation("E024", "Artifact integrity check failed", "\
+An artifact does not match its checksum or signature.
+
+Fix: rebuild the artifact, or find out who changed it. Check `signing_public_key` if the
+signature is rejected."),
+    explanation("E025", "Deploy blocked", "\
+Deploying was stopped because of a difference between the new program and the one on the
+logger (e.g. a table layout change that would discard data), or because the program running
+on the logger could not be read back to compare with.
+
+Fix: collect the data first, or deploy with --force if that is intended. If the logger does
+not return its program, compare with a copy of it using --against."),
+    explanation("E026", "Version header error", "\
+The program's version header could not be read or updated.
+
+Fix: see `crbrs version --help` for the expected header format."),
+    explanation("E027", "Metadata export failed", "\
+Table metadata could not be exported from the program, usually because a table cannot be
+resolved statically."),
+    explanation("E028", "Documentation generation failed", "\
+Documentation could not be generated for the program or project."),
+    explanation("E029", "Remote build failed", "\
+Building on the SSH host failed before the programs were compiled.
+
+Fix: check that `ssh <host>` works without a password prompt and that crbrs and tar are on the
+host's non-interactive PATH."),
+    explanation("E030", "Cannot add sensor", "\
+The sensor could not be added to the program, e.g. an unknown model or no free channel."),
+    explanation("E031", "Calibration failed", "\
+The calibration file could not be applied: it is not valid TOML, or names a constant the
+program does not declare."),
+    explanation("E032", "Data file error", "\
+A data file from a logger could not be read, converted or checked, e.g. a damaged or
+unsupported file format."),
+    explanation("E033", "Logger communication failed", "\
+The logger did not answer, or answered with an error.
+
+Causes: the wrong port, address, PakBus address or security code, or the logger being busy.
+
+Fix: check the station's connection settings, and the cable or network."),
+    explanation("E034", "Stations need attention", "\
+A fleet status check found stations that are not healthy; they are listed above.
+
+This exits non-zero so monitoring jobs can alert on it."),
+    explanation("E035", "Provisioning failed", "\
+Setting up the machine from a plan failed; the steps already done are reported."),
+    explanation("E036", "Secret error", "\
+A secret could not be read from or written to the OS keychain.
+
+Fix: check that a keychain is available (on Linux, a Secret Service such as GNOME Keyring), or
+provide the secret as the environment variable CRBRS_SECRET_<NAME>."),
+    explanation("E037", "Settings not saved", "\
+The command did its work but the settings recording it could not be written, so it exits with
+code 3.
+
+Fix: make the config and data directories writable, or use --portable <dir> (or CRBRS_HOME)."),
+    explanation("E038", "Cancelled", "\
+The operation was interrupted with Ctrl-C; running compilers were stopped and partial files
+removed. Exits with code 130."),
+    explanation("E039", "Input required", "\
+A question needed an answer, but there is no terminal or --no-input was given.
+
+Fix: pass the answer as an option (e.g. --yes), or run interactively."),
+    explanation("E040", "Formatting", "\
+Programs are not formatted (`crbrs fmt --check`), or one could not be formatted because it is
+not UTF-8 text.
+
+Fix: run `crbrs fmt` on them. The options come from .crbrsfmt.toml, the [format] table of
+crbrs.toml and .editorconfig files, nearest first."),
+    explanation("E041", "Signature check failed", "\
+A compiler archive's detached signature (the manifest entry's `signature_url`) does not verify
+against the entry's `public_key`, could not be checked, or is missing while
+`require_signed_compilers` is set.
+
+Causes: an archive or signature altered on a mirror, a signature made with another key, a
+manifest entry with only one of the two fields, or `gpg` missing for OpenPGP signatures.
+
+Fix: do not run the archive; report it to whoever publishes the repository. Minisign keys are
+checked by crbrs itself; OpenPGP keys need `gpg` in PATH."),
+    explanation("E042", "Tables collide", "\
+`crbrs metadata check` found tables or fields of the project's programs that would collide when
+their data is loaded into one database: a field stored twice in a table, or a table stored by
+several programs under names differing only in case, with other fields, or with fields of
+another type, unit or processing.
+
+Fix: rename the table or field in one program (FieldNames renames fields), or, for tables the
+database does not ingest, list them in `ignore_tables` under [metadata] in crbrs.toml. Set
+`same_fields = false` there if the database adds columns as they appear."),
+    explanation("E043", "Compilation timed out", "\
+The compiler did not finish within `compile_timeout_secs` (default 600 seconds), so it was
+stopped along with the processes it started (Wine's, say).
+
+Causes: Wine waiting on a dialog or on a prefix it is still setting up, a compiler stuck on a
+file it cannot read, or a very slow machine.
+
+Fix: run the compile once by hand with -v to see where it stops (`wineboot` sets up a new Wine
+prefix); if the compiler is just slow, give it longer with --timeout or
+`crbrs config set compile_timeout_secs <N>` (0 for no limit)."),
+    explanation("E044", "Project verification failed", "\
+`crbrs verify-project` found problems; its report lists them by check:
+
+- toolchain: a compiler crbrs.toml requires is not installed (or its executable is gone), or
+  [file_associations] names a compiler missing from `required_compilers`
+- format: programs that `crbrs fmt` would change
+- lint: lint errors at the configured rule levels
+- build: programs that do not compile (skipped while the toolchain check fails)
+
+Fix: `crbrs compile --install-missing` installs the required compilers; add associated ones to
+`required_compilers`; run `crbrs fmt`; fix the lint errors (or set the rule's level under
+[lint]) and compiler errors listed."),
+];
+
+impl Error {
+    /// The error's stable code, e.g. `E014`; see [`ERRORS`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Config(_) => "E001",
+            Error::Io(_) => "E002",
+            Error::Network(_) => "E003",
+            Error::RateLimited { .. } => "E004",
+            Error::Offline(_) => "E005",
+            Error::Zip(_) => "E006",
+            Error::CompilerNotFound(..) => "E007",
+            Error::ChecksumMismatch { .. } => "E008",
+            Error::NoCompilerForExtension(_) => "E009",
+            Error::AssociatedCompilerNotInstalled { .. } => "E010",
+            Error::ProjectCompilersMissing { .. } => "E011",
+            Error::WineNotFound => "E012",
+            Error::Subprocess(_) => "E013",
+            Error::CompilationFailed { .. } => "E014",
+            Error::GenericCompilationFailedWithLog { .. } => "E015",
+            Error::CompilationFailedWithLog { .. } => "E016",
+            Error::BuildFailed { .. } => "E017",
+            Error::InvalidCompilerSource(_) => "E018",
+            Error::DirectoryResolutionFailed => "E019",
+            Error::CompilerIdNotFoundInManifest(..) => "E020",
+            Error::InvalidExtension(_) => "E021",
+            Error::ImageNotSupported(_) => "E022",
+            Error::ImageNotProduced(_) => "E023",
+            Error::Integrity(_) => "E024",
+            Error::DeployBlocked(_) => "E025",
+            Error::Version(_) => "E026",
+            Error::Metadata(_) => "E027",
+            Error::Docgen(_) => "E028",
+            Error::Remote(_) => "E029",
+            Error::Sensor(_) => "E030",
+            Error::Calibration(_) => "E031",
+            Error::Data(_) => "E032",
+            Error::Logger(_) => "E033",
+            Error::Alert(_) => "E034",
+            Error::Provision(_) => "E035",
+            Error::Secret(_) => "E036",
+            Error::SettingsNotSaved { .. } => "E037",
+            Error::Cancelled => "E038",
+            Error::InputRequired(_) => "E039",
+            Error::Format(_) => "E040",
+            Error::SignatureInvalid { .. } => "E041",
+            Error::TableCollisions(_) => "E042",
+            Error::CompilationTimeout { .. } => "E043",
+            Error::VerificationFailed { .. } => "E044",
+        }
+    }
+}
+
+/// The explanation of `code`: an error code (`E014`), a lint code (`L002`) or a lint rule ID
+/// (`duplicate-declaration`), ignoring case.
+pub fn explain(code: &str) -> Option<String> {
+    let code = code.trim();
+    if let Some(e) = ERRORS.iter().find(|e| e.code.eq_ignore_ascii_case(code)) {
+        return Some(format!("{}: {}\n\n{}", e.code, e.title, e.text));
+    }
+    let rule = lint::rule_by_code(code).or_else(|| lint::rule(&code.to_ascii_lowercase()))?;
+    Some(format!(
+        "{}: lint rule `{}` (default: {:?})\n\n{}\n\nChange its severity, or turn it off, in the [lint] settings:\n`crbrs config set lint.{} off` (or error, warning, info, hint).",
+        rule.code,
+        rule.id,
+        rule.default_severity,
+        rule.description,
+        rule.id
+    ))
+}
+
+/// Every code with its title, for `crbrs explain` without a code.
+pub fn index() -> Vec<(&'static str, String)> {
+    let errors = ERRORS.iter().map(|e| (e.code, e.title.to_string()));
+    let rules = lint::RULES.iter().map(|r| (r.code, format!("lint rule `{}`", r.id)));
+    errors.chain(rules).collect()
+}
@@ -0,0 +1,219 @@
+// FILE: crbrs-lib/src/fleet.rs
+
+//! Commands over every station in the registry (`crbrs fleet ...`).
+//!
+//! Stations are worked on concurrently, each on its own thread and connection, so one slow
+//! radio link does not hold up the others and one failing station does not stop the rest
+//! (unless `fail_fast` is set). Each station gets its own time limit and [`CancelToken`]; a
+//! station that runs over the limit is reported as timed out and its token cancelled, so its
+//! logger operations stop and clean up in the background. `fail_fast` cancels the stations
+//! still running the same way. Results come back in station order, in one report.
+//!
+//! `crbrs fleet status` reads the status of each station and judges it against the
+//! station's thresholds. Every check that is not ok, and every station that cannot be
+//! reached, is an alert; the command fails if there are any, so a cron job can mail its
+//! output only when something needs attention.
+
+use crate::cancel::{self, CancelToken};
+use crate::network::NetworkSettings;
+use crate::station::Station;
+use crate::status::{self, Health, StatusReport};
+use crate::{Error, Settings};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How fleet commands run.
+#[derive(Debug, Clone, Copy)]
+pub struct FleetOptions {
+    /// Stations worked on at the same time.
+    pub jobs: usize,
+    /// Time allowed for each station, from connecting to the last reply.
+    pub station_timeout: Duration,
+    /// Start no more stations after the first one fails.
+    pub fail_fast: bool,
+}
+
+impl Default for FleetOptions {
+    fn default() -> Self {
+        FleetOptions { jobs: 16, station_timeout: Duration::from_secs(120), fail_fast: false }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Ok,
+    Failed,
+    TimedOut,
+    /// Not started, because another station failed with `fail_fast` set.
+    Skipped,
+}
+
+/// What became of one station.
+#[derive(Debug, Clone, Serialize)]
+pub struct StationResult<T> {
+    pub station: String,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Names of the registered stations, sorted.
+pub fn station_names(settings: &Settings) -> Result<Vec<String>, Error> {
+    if settings.stations.is_empty() {
+        return Err(Error::Logger(
+            "No stations are registered; add one with `crbrs config set stations.<name>.address <address>`".to_string(),
+        ));
+    }
+    let mut names: Vec<String> = settings.stations.keys().cloned().collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Runs `operation` on every registered station, as [`FleetOptions`] says, and returns the
+/// results in station order. Each station runs under its own [`CancelToken`] (see
+/// [`cancel::scoped`]), cancelled when it times out or another fails with `fail_fast`. Fails
+/// with [`Error::Cancelled`] as soon as the operation is cancelled, leaving running stations
+/// to stop on their own.
+pub fn run<T, F>(settings: &Settings, options: FleetOptions, operation: F) -> Result<Vec<StationResult<T>>, Error>
+where
+    T: Send + 'static,
+    F: Fn(&str, &Station, &NetworkSettings) -> Result<T, Error> + Send + Sync + 'static,
+{
+    let names = station_names(settings)?;
+    let operation = Arc::new(operation);
+    let (sender, receiver) = mpsc::channel();
+    let mut results: Vec<Option<StationResult<T>>> = names.iter().map(|_| None).collect();
+    let mut queue: VecDeque<usize> = (0..names.len()).collect();
+    let mut running: Vec<(usize, Instant, CancelToken)> = Vec::new();
+    let mut stop = false;
+
+    while !queue.is_empty() || !running.is_empty() {
+        while !stop && running.len() < options.jobs.max(1) {
+            let Some(index) = queue.pop_front() else { break };
+            let (name, station, network) = (names[index].clone(), settings.stations[&names[index]].clone(), settings.network.clone());
+            let (sender, operation, token) = (sender.clone(), Arc::clone(&operation), CancelToken::new());
+            log::info!("Starting {}", name);
+            let scope = token.clone();
+            thread::spawn(move || {
+                let result = cancel::scoped(scope, || operation(&name, &station, &network));
+                // The receiver is gone if the station timed out and everything else finished.
+                let _ = sender.send((index, result));
+            });
+            running.push((index, Instant::now(), token));
+        }
+        if running.is_empty() {
+            break;
+        }
+
+        let deadline = running.iter().map(|(_, started, _)| *started + options.station_timeout).min().expect("a station is running");
+        // Wake up regularly to notice a cancellation.
+        let wait = deadline.saturating_duration_since(Instant::now()).min(cancel::POLL_INTERVAL);
+        let finished = match receiver.recv_timeout(wait) {
+            Ok((index, result)) => {
+                let Some(position) = running.iter().position(|(i, _, _)| *i == index) else { continue };
+                let (_, started, _) = running.remove(position);
+                let duration_ms = started.elapsed().as_millis() as u64;
+                let station = names[index].clone();
+                Some(match result {
+                    Ok(value) => (index, StationResult { station, outcome: Outcome::Ok, value: Some(value), error: None, duration_ms }),
+                    Err(e) => {
+                        log::warn!("{} failed: {}", names[index], e);
+                        (index, StationResult { station, outcome: Outcome::Failed, value: None, error: Some(e.to_string()), duration_ms })
+                    }
+                })
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                cancel::check()?;
+                let Some(position) = running.iter().position(|(_, started, _)| started.elapsed() >= options.station_timeout) else {
+                    continue;
+                };
+                let (index, started, token) = running.remove(position);
+                log::warn!("{} timed out after {:?}", names[index], options.station_timeout);
+                token.cancel();
+                let error = format!("no result within {} s", options.station_timeout.as_secs());
+                let duration_ms = started.elapsed().as_millis() as u64;
+                Some((
+                    index,
+                    StationResult { station: names[index].clone(), outcome: Outcome::TimedOut, value: None, error: Some(error), duration_ms },
+                ))
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => None,
+        };
+        if let Some((index, result)) = finished {
+            if result.outcome != Outcome::Ok && options.fail_fast && !stop {
+                log::warn!("Stopping after {} failed (--fail-fast)", names[index]);
+                stop = true;
+                for (_, _, token) in &running {
+                    token.cancel();
+                }
+            }
+            results[index] = Some(result);
+        }
+        if stop && running.is_empty() {
+            break;
+        }
+    }
+
+    Ok(results
+        .into_iter()
+        .zip(names)
+        .map(|(result, station)| {
+            result.unwrap_or(StationResult { station, outcome: Outcome::Skipped, value: None, error: None, duration_ms: 0 })
+        })
+        .collect())
+}
+
+/// A station that needs attention.
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub station: String,
+    pub health: Health,
+    /// What is wrong: a status item like `Battery`, or `Connection`.
+    pub item: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    pub problem: String,
+}
+
+/// Status of every station, and the alerts among it.
+#[derive(Debug, Clone, Serialize)]
+pub struct FleetStatus {
+    pub stations: Vec<StationResult<StatusReport>>,
+    pub alerts: Vec<Alert>,
+}
+
+/// Reads the status of every registered station.
+pub fn status(settings: &Settings, options: FleetOptions) -> Result<FleetStatus, Error> {
+    let stations = run(settings, options, |name, station, network| status::query(name, station, network, &station.thresholds()))?;
+    let mut alerts = Vec::new();
+    for result in &stations {
+        if let Some(report) = &result.value {
+            for check in report.checks.iter().filter(|c| c.health != Health::Ok) {
+                alerts.push(Alert {
+                    station: result.station.clone(),
+                    health: check.health,
+                    item: check.item.clone(),
+                    value: Some(check.value.clone()),
+                    problem: check.problem.clone().unwrap_or_default(),
+                });
+            }
+        } else if let Some(error) = &result.error {
+            alerts.push(Alert {
+                station: result.station.clone(),
+                health: Health::Critical,
+                item: "Connection".to_string(),
+                value: None,
+                problem: error.clone(),
+            });
+        }
+    }
+    Ok(FleetStatus { stations, alerts })
+}
@@ -0,0 +1,191 @@
+// FILE: crbrs-lib/src/format.rs
+
+//! `crbrs fmt` and the language server's formatting: which [`FormatOptions`] apply to a file.
+//!
+//! Options are discovered from the file's directory upwards, so a program is formatted the
+//! same whether it is formatted from the project root, its own directory or an editor:
+//!
+//! 1. the defaults;
+//! 2. `.editorconfig` files (`indent_style`, `indent_size`, `trim_trailing_whitespace`), read
+//!    up to one with `root = true`, nearer files and later sections overriding earlier ones;
+//! 3. the nearest `.crbrsfmt.toml`, or `[format]` table of a `crbrs.toml` project manifest
+//!    (in the same directory, `.crbrsfmt.toml` wins). Only the options it sets override.
+//!
+//! ```toml
+//! # .crbrsfmt.toml, or under [format] in crbrs.toml
+//! indent_style = "spaces"     # or "tabs"
+//! indent_width = 4
+//! max_blank_lines = 2
+//! align_comments = true
+//! ```
+
+use crate::project::{ProjectManifest, MANIFEST_FILE};
+use crate::syntax::case;
+use crate::syntax::format::{format, FormatOptions, IndentStyle};
+use crate::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const FORMAT_CONFIG_FILE: &str = ".crbrsfmt.toml";
+pub const EDITORCONFIG_FILE: &str = ".editorconfig";
+
+fn config_error(path: &Path, message: impl std::fmt::Display) -> Error {
+    Error::Config(config::ConfigError::Message(format!("{}: {}", path.display(), message)))
+}
+
+/// Whether `name` matches the editorconfig glob `pattern`: `*` is any run of characters but
+/// `/`, `**` any run, `?` any one character, `{a,b}` either alternative.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern {
+        [] => name.is_empty(),
+        ['*', '*', rest @ ..] => (0..=name.len()).any(|skip| glob_match(rest, &name[skip..])),
+        ['*', rest @ ..] => (0..=name.len()).take_while(|&skip| skip == 0 || name[skip - 1] != '/').any(|skip| glob_match(rest, &name[skip..])),
+        ['{', ..] => match pattern.iter().position(|&c| c == '}') {
+            Some(close) => {
+                let rest = &pattern[close + 1..];
+                pattern[1..close].split(|&c| c == ',').any(|alternative| {
+                    let expanded: Vec<char> = alternative.iter().chain(rest).copied().collect();
+                    glob_match(&expanded, name)
+                })
+            }
+            None => name.first() == Some(&'{') && glob_match(&pattern[1..], &name[1..]),
+        },
+        [c, rest @ ..] => name.split_first().is_some_and(|(n, name)| (*c == '?' && *n != '/' || c == n) && glob_match(rest, name)),
+    }
+}
+
+/// Whether the section `glob` of the `.editorconfig` in `dir` applies to `file`. A glob
+/// without `/` matches the file name in any directory below.
+fn section_applies(glob: &str, dir: &Path, file: &Path) -> bool {
+    let Ok(relative) = file.strip_prefix(dir) else { return false };
+    let relative: Vec<char> = relative.to_string_lossy().replace('\\', "/").chars().collect();
+    let glob = if glob.contains('/') { glob.trim_start_matches('/').to_string() } else { format!("**/{}", glob) };
+    let glob: Vec<char> = glob.chars().collect();
+    // `**/x` also matches `x` directly in `dir`.
+    glob_match(&glob, &relative) || (glob.starts_with(&['*', '*', '/']) && glob_match(&glob[3..], &relative))
+}
+
+/// Whether `.editorconfig` text says it is the root: files further up are not read.
+fn is_root(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once('='))
+        .any(|(key, value)| key.trim().eq_ignore_ascii_case("root") && value.trim().eq_ignore_ascii_case("true"))
+}
+
+/// Applies the properties `.editorconfig` text (in `dir`) gives `file` to `options`.
+fn apply_editorconfig(text: &str, dir: &Path, file: &Path, options: &mut FormatOptions) {
+    let mut applies: Option<bool> = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            applies = Some(section_applies(glob, dir, file));
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let (key, value) = (key.trim().to_ascii_lowercase(), value.trim().to_ascii_lowercase());
+        match (applies, key.as_str()) {
+            (Some(true), "indent_style") => match value.as_str() {
+                "space" => options.indent_style = IndentStyle::Spaces,
+                "tab" => options.indent_style = IndentStyle::Tabs,
+                _ => {}
+            },
+            (Some(true), "indent_size") => {
+                if let Ok(width) = value.parse() {
+                    options.indent_width = width;
+                }
+            }
+            (Some(true), "trim_trailing_whitespace") => match value.as_str() {
+                "true" => options.trim_trailing_whitespace = true,
+                "false" => options.trim_trailing_whitespace = false,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+/// The options set in a formatter table (from `path`), applied over `options`.
+fn apply_table(table: toml::Table, path: &Path, options: FormatOptions) -> Result<FormatOptions, Error> {
+    let toml::Value::Table(mut merged) = toml::Value::try_from(&options).map_err(|e| config_error(path, e))? else {
+        unreachable!("FormatOptions serializes to a table");
+    };
+    for (key, value) in table {
+        if !merged.contains_key(&key) {
+            let known: Vec<&String> = merged.keys().collect();
+            return Err(config_error(path, format!("Unknown formatter option '{}' (known: {:?})", key, known)));
+        }
+        merged.insert(key, value);
+    }
+    toml::Value::Table(merged).try_into().map_err(|e: toml::de::Error| config_error(path, e.message()))
+}
+
+/// The formatter table of `dir`: its `.crbrsfmt.toml`, else its `crbrs.toml`'s `[format]`.
+fn format_table(dir: &Path) -> Result<Option<(PathBuf, toml::Table)>, Error> {
+    let dedicated = dir.join(FORMAT_CONFIG_FILE);
+    if dedicated.is_file() {
+        let table = fs::read_to_string(&dedicated)?.parse::<toml::Table>().map_err(|e| config_error(&dedicated, e.message()))?;
+        return Ok(Some((dedicated, table)));
+    }
+    let manifest = dir.join(MANIFEST_FILE);
+    if manifest.is_file() {
+        let parsed: ProjectManifest = toml::from_str(&fs::read_to_string(&manifest)?).map_err(|e| config_error(&manifest, e.message()))?;
+        return Ok(parsed.format.map(|table| (manifest, table)));
+    }
+    Ok(None)
+}
+
+/// The options for formatting `file` (which need not exist yet).
+pub fn options_for(file: &Path) -> Result<FormatOptions, Error> {
+    let file = std::path::absolute(file)?;
+    let dirs: Vec<&Path> = file.ancestors().skip(1).collect();
+    let mut options = FormatOptions::default();
+
+    // Editorconfig files apply outermost first, from the root one down.
+    let mut editorconfigs = Vec::new();
+    for dir in &dirs {
+        if let Ok(text) = fs::read_to_string(dir.join(EDITORCONFIG_FILE)) {
+            let root = is_root(&text);
+            editorconfigs.push((*dir, text));
+            if root {
+                break;
+            }
+        }
+    }
+    for (dir, text) in editorconfigs.iter().rev() {
+        apply_editorconfig(text, dir, &file, &mut options);
+    }
+
+    for dir in &dirs {
+        if let Some((path, table)) = format_table(dir)? {
+            log::debug!("Formatting {:?} with the options in {:?}", file, path);
+            return apply_table(table, &path, options);
+        }
+    }
+    Ok(options)
+}
+
+/// Formats `file` with its options, and with `fix_case` also canonicalizes keyword case (see
+/// [`crate::syntax::case`]); writes it back unless `check`. Returns whether it was (or, with
+/// `check`, would be) changed.
+pub fn format_file(file: &Path, check: bool, fix_case: bool) -> Result<bool, Error> {
+    let options = options_for(file)?;
+    let bytes = fs::read(file)?;
+    let source = String::from_utf8(bytes).map_err(|_| Error::Format(format!("{} is not UTF-8 text", file.display())))?;
+    let mut formatted = format(&source, &options);
+    if fix_case {
+        formatted = case::fix_case(&formatted);
+    }
+    if formatted == source {
+        return Ok(false);
+    }
+    if !check {
+        fs::write(file, formatted)?;
+    }
+    Ok(true)
+}
+
@@ -0,0 +1,182 @@
+// FILE: crbrs-lib/src/history.rs
+
+//! Local build history, kept as `history.json` in the data directory: when each compiler was
+//! last used, and which compiler (and version) last built each file, so switching a file to a
+//! different compiler doesn't go unnoticed. It is bookkeeping only, so a missing or unreadable
+//! file starts a fresh history.
+//!
+//! It also keeps a record of every session with a station that changed or retrieved
+//! something (`crbrs history stations`): programs sent and data collected, with the program
+//! version, the compiler that last built it, bytes moved, duration and result. Failed
+//! sessions are recorded too. These records are never pruned, as they are the audit trail
+//! data-management plans ask for.
+
+use crate::clock;
+use crate::config;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    /// Keyed by compiler ID.
+    #[serde(default)]
+    pub compilers: BTreeMap<String, CompilerUse>,
+    /// Keyed by the absolute path of the source file.
+    #[serde(default)]
+    pub files: BTreeMap<String, FileBuild>,
+    /// Oldest first.
+    #[serde(default)]
+    pub sessions: Vec<StationSession>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompilerUse {
+    /// RFC 3339 timestamp of the last successful compile.
+    pub last_used: String,
+}
+
+/// The last successful build of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileBuild {
+    pub compiler_id: String,
+    pub version: String,
+    /// RFC 3339 timestamp.
+    pub built: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    /// A program sent to the logger.
+    Send,
+    /// Data collected from the logger.
+    Collect,
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Operation::Send => "send",
+            Operation::Collect => "collect",
+        })
+    }
+}
+
+/// A send or collect session with a station.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StationSession {
+    pub station: String,
+    /// Address the station was reached at, e.g. `tcp:10.0.0.5:6785`.
+    pub address: String,
+    pub operation: Operation,
+    /// RFC 3339 timestamp of the start.
+    pub started: String,
+    pub duration_ms: u64,
+    /// Program sent, or file the data was written to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Version header of the program.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program_version: Option<String>,
+    /// Compiler (and version) that last built the program, if crbrs built it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiler: Option<String>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Why the session failed; `None` if it succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Key under which `file` is recorded.
+pub fn file_key(file: &Path) -> String {
+    std::path::absolute(file).unwrap_or_else(|_| file.to_path_buf()).to_string_lossy().into_owned()
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+/// Held while the history is read, changed and written back, so that the files of a parallel
+/// build (see [`crate::build::build_files`]) don't overwrite each other's records.
+static UPDATE: Mutex<()> = Mutex::new(());
+
+/// Turns history off for this process, e.g. for the language server, which compiles
+/// temporary copies of files.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn history_path() -> Result<PathBuf, Error> {
+    Ok(config::data_dir()?.join("history.json"))
+}
+
+/// The recorded history, empty if there is none yet.
+pub fn load() -> History {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return History::default();
+    }
+    let Ok(path) = history_path() else { return History::default() };
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable build history {:?}: {}", path, e);
+            History::default()
+        }),
+        Err(_) => History::default(),
+    }
+}
+
+pub fn save(history: &History) -> Result<(), Error> {
+    let path = history_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let text = serde_json::to_string_pretty(history).map_err(|e| Error::Io(e.into()))?;
+    fs::write(&path, text)?;
+    Ok(())
+}
+
+/// The last successful build of `file`, if recorded.
+pub fn last_build(file: &Path) -> Option<FileBuild> {
+    load().files.remove(&file_key(file))
+}
+
+/// Notes that `file` was just built successfully with `compiler_id` at `version`.
+pub fn record_build(file: &Path, compiler_id: &str, version: &str) -> Result<(), Error> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let _update = UPDATE.lock().unwrap_or_else(|e| e.into_inner());
+    let mut history = load();
+    let now = clock::timestamp(SystemTime::now());
+    history.compilers.insert(compiler_id.to_string(), CompilerUse { last_used: now.clone() });
+    history.files.insert(
+        file_key(file),
+        FileBuild { compiler_id: compiler_id.to_string(), version: version.to_string(), built: now },
+    );
+    save(&history)
+}
+
+/// Appends `session` to the station sessions.
+pub fn record_session(session: StationSession) -> Result<(), Error> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let _update = UPDATE.lock().unwrap_or_else(|e| e.into_inner());
+    let mut history = load();
+    history.sessions.push(session);
+    save(&history)
+}
+
+/// Recorded sessions, oldest first, of `station` (matched case-insensitively) or of every
+/// station.
+pub fn station_sessions(station: Option<&str>) -> Vec<StationSession> {
+    let mut sessions = load().sessions;
+    if let Some(station) = station {
+        sessions.retain(|s| s.station.eq_ignore_ascii_case(station));
+    }
+    sessions
+}
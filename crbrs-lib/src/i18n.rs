@@ -0,0 +1,114 @@
+// FILE: crbrs-lib/src/i18n.rs
+
+//! Translated user-facing messages.
+//!
+//! Messages live in Fluent catalogs under `crbrs-lib/locales/<locale>/crbrs.ftl`, compiled into
+//! the binary. The locale comes from `CRBRS_LANG`, else the usual `LC_ALL`, `LC_MESSAGES` and
+//! `LANG` variables (the language server uses the client's locale instead, see [`set_locale`]).
+//! Messages a catalog lacks fall back to English, so translations can be partial.
+//!
+//! Use the [`tr!`](crate::tr) macro: `tr!("compiler-installed", compiler_id = id)`.
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use fluent_langneg::{negotiate_languages, NegotiationStrategy};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+pub use fluent_bundle::FluentValue;
+
+/// Overrides the locale from the environment, e.g. `de` or `es-MX`.
+pub const LANG_ENV_VAR: &str = "CRBRS_LANG";
+
+/// Locales with a catalog; the first is the fallback.
+const CATALOGS: [(&str, &str); 3] = [
+    ("en-US", include_str!("../locales/en-US/crbrs.ftl")),
+    ("de", include_str!("../locales/de/crbrs.ftl")),
+    ("es", include_str!("../locales/es/crbrs.ftl")),
+];
+
+static LOCALE: OnceLock<String> = OnceLock::new();
+static BUNDLES: OnceLock<Vec<FluentBundle<FluentResource>>> = OnceLock::new();
+
+/// Uses `locale` instead of the environment. Only takes effect before the first message.
+pub fn set_locale(locale: &str) {
+    let _ = LOCALE.set(locale.to_string());
+}
+
+/// `de_DE.UTF-8@euro` (POSIX) -> `de-DE` (BCP 47); `C` and `POSIX` mean no preference.
+fn posix_to_bcp47(value: &str) -> Option<String> {
+    let tag = value.split(['.', '@']).next().unwrap_or("").replace('_', "-");
+    (!tag.is_empty() && tag != "C" && tag != "POSIX").then_some(tag)
+}
+
+/// The requested locale: [`set_locale`], `CRBRS_LANG`, then the POSIX locale variables.
+pub fn requested_locale() -> Option<String> {
+    if let Some(locale) = LOCALE.get() {
+        return Some(locale.clone());
+    }
+    [LANG_ENV_VAR, "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| posix_to_bcp47(&value))
+}
+
+fn bundle(locale: &LanguageIdentifier) -> FluentBundle<FluentResource> {
+    let source = CATALOGS.iter().find(|(tag, _)| tag.parse().ok().as_ref() == Some(locale)).map_or("", |(_, source)| source);
+    let mut bundle = FluentBundle::new_concurrent(vec![locale.clone()]);
+    // Unicode isolation marks around arguments only show up as noise in a terminal.
+    bundle.set_use_isolating(false);
+    match FluentResource::try_new(source.to_string()) {
+        Ok(resource) => {
+            if let Err(errors) = bundle.add_resource(resource) {
+                log::warn!("Problems in the {} message catalog: {:?}", locale, errors);
+            }
+        }
+        Err((_, errors)) => log::warn!("Cannot parse the {} message catalog: {:?}", locale, errors),
+    }
+    bundle
+}
+
+/// Bundles to look messages up in, best match first and English last.
+fn bundles() -> &'static [FluentBundle<FluentResource>] {
+    BUNDLES.get_or_init(|| {
+        let available: Vec<LanguageIdentifier> = CATALOGS.iter().filter_map(|(tag, _)| tag.parse().ok()).collect();
+        let requested: Vec<LanguageIdentifier> = requested_locale().and_then(|tag| tag.parse().ok()).into_iter().collect();
+        let mut locales = negotiate_languages(&requested, &available, Some(&available[0]), NegotiationStrategy::Filtering);
+        if !locales.contains(&&available[0]) {
+            locales.push(&available[0]);
+        }
+        log::debug!("Message locales: {:?} (requested {:?})", locales, requested);
+        locales.into_iter().map(bundle).collect()
+    })
+}
+
+/// The message `id` with `args` filled in, or `id` itself if no catalog has it.
+pub fn message(id: &str, args: &[(&str, FluentValue)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+    for bundle in bundles() {
+        let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else { continue };
+        let mut errors = Vec::new();
+        let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+        if !errors.is_empty() {
+            log::warn!("Problems formatting message '{}': {:?}", id, errors);
+        }
+        return text.into_owned();
+    }
+    log::warn!("No message '{}' in any catalog", id);
+    id.to_string()
+}
+
+/// Formats a translated message: `tr!("config-set", key = key, value = value)`.
+///
+/// Arguments can be anything convertible into a [`FluentValue`] (strings and numbers); format
+/// paths with `.display().to_string()`.
+#[macro_export]
+macro_rules! tr {
+    ($id:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::message($id, &[$((stringify!($name), $crate::i18n::FluentValue::from($value))),*])
+    };
+}
@@ -0,0 +1,119 @@
+// FILE: crbrs-lib/src/in_place.rs
+
+//! Whether compilers may write next to a program.
+//!
+//! Compilers write artifacts next to the program they compile: download images (`.bin`,
+//! `.dld`), listings, logs. Where that directory can't be written, e.g. a share mounted
+//! read-only or files checked out read-only, Wine fails with a write error that names none of
+//! this; and a checkout of a release tag shouldn't get build outputs mixed into it. In those
+//! cases (the `artifacts_in_place` setting, `auto` by default) the program is compiled in a
+//! [`crate::sandbox::Sandbox`] instead, with a warning, and a download image is kept in
+//! [`artifacts_dir`] unless it is sent elsewhere anyway (`--image-out`, the project's
+//! `image_dir`).
+
+use crate::config;
+use crate::{say, tr, Error};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// The `artifacts_in_place` setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InPlace {
+    /// Next to the program, unless its directory is read-only or a checkout of a tag.
+    #[default]
+    Auto,
+    /// Always next to the program, as the compiler does on its own.
+    Always,
+    /// Never: every compile runs in a sandbox.
+    Never,
+}
+
+impl FromStr for InPlace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(InPlace::Auto),
+            "always" => Ok(InPlace::Always),
+            "never" => Ok(InPlace::Never),
+            _ => Err(format!("'{}' is not an artifacts_in_place policy (auto, always or never)", s)),
+        }
+    }
+}
+
+/// Where download images of redirected compiles are kept.
+pub fn artifacts_dir() -> Result<PathBuf, Error> {
+    Ok(config::data_dir()?.join("artifacts"))
+}
+
+/// Why `program` must not be compiled in place, if it must not. Warns once per file or
+/// directory.
+pub fn redirect_reason(program: &Path, policy: InPlace) -> Result<Option<String>, Error> {
+    static DECIDED: Mutex<Option<HashMap<PathBuf, Option<String>>>> = Mutex::new(None);
+    match policy {
+        InPlace::Always => return Ok(None),
+        InPlace::Never => return Ok(Some(tr!("in-place-never"))),
+        InPlace::Auto => {}
+    }
+    let program = std::path::absolute(program)?;
+    let dir = program.parent().map(Path::to_path_buf).unwrap_or_default();
+    // A read-only program is decided on its own, anything else by its directory.
+    let key = if read_only_file(&program) { program.clone() } else { dir.clone() };
+    let mut decided = DECIDED.lock().unwrap_or_else(|e| e.into_inner());
+    let decided = decided.get_or_insert_with(HashMap::new);
+    if let Some(reason) = decided.get(&key) {
+        return Ok(reason.clone());
+    }
+    let reason = if key == program || !writable(&dir) {
+        Some(tr!("in-place-read-only", path = key.display().to_string()))
+    } else {
+        checked_out_tag(&dir).map(|tag| tr!("in-place-tag", path = dir.display().to_string(), tag = tag))
+    };
+    if let Some(reason) = &reason {
+        say!("{}", tr!("in-place-redirected", reason = reason.as_str()));
+    }
+    decided.insert(key, reason.clone());
+    Ok(reason)
+}
+
+fn read_only_file(program: &Path) -> bool {
+    fs::metadata(program).is_ok_and(|metadata| metadata.permissions().readonly())
+}
+
+/// Whether files can be created in `dir`, tried rather than read from its permissions, which
+/// say nothing of read-only mounts or ACLs.
+fn writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".crbrs-write-test-{}", std::process::id()));
+    match fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(e) => {
+            log::debug!("{:?} is not writable: {}", dir, e);
+            false
+        }
+    }
+}
+
+/// The tag checked out in the git work tree holding `dir`, if HEAD is detached at one.
+fn checked_out_tag(dir: &Path) -> Option<String> {
+    // Reading HEAD first keeps git from being run for the usual checkout of a branch.
+    let git = dir.ancestors().map(|d| d.join(".git")).find(|git| git.exists())?;
+    let git_dir = match fs::read_to_string(&git) {
+        Ok(pointer) => git.parent()?.join(pointer.strip_prefix("gitdir:")?.trim()), // A worktree
+        Err(_) => git,
+    };
+    if fs::read_to_string(git_dir.join("HEAD")).ok()?.starts_with("ref:") {
+        return None;
+    }
+    let output = Command::new("git").current_dir(dir).args(["tag", "--points-at", "HEAD"]).output().ok()?;
+    let tags = String::from_utf8_lossy(&output.stdout);
+    tags.lines().next().filter(|_| output.status.success()).map(str::to_string)
+}
@@ -0,0 +1,192 @@
+// FILE: crbrs-lib/src/init.rs
+
+//! `crbrs init`: a new station program repository in one step.
+//!
+//! Writes a `crbrs.toml` (see [`crate::project`]) pinning the compiler for the logger model, a
+//! starter program for the model that measures the battery voltage and panel temperature into
+//! an hourly table, and a `.gitignore` for what builds produce. Existing files are left alone
+//! unless `force` is set, except that an existing `crbrs.toml` is an error: the directory is
+//! already a project.
+
+use crate::project::MANIFEST_FILE;
+use crate::{CompilerFamily, Error, Settings};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A logger model `crbrs init` has a starter program for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoggerModel {
+    pub name: &'static str,
+    /// Extension of its programs.
+    pub extension: &'static str,
+    pub family: CompilerFamily,
+    /// `PanelTemp`'s integration argument (60 Hz noise rejection), if the model has a panel
+    /// temperature sensor.
+    pub panel_temp: Option<&'static str>,
+}
+
+const fn model(name: &'static str, extension: &'static str, family: CompilerFamily, panel_temp: Option<&'static str>) -> LoggerModel {
+    LoggerModel { name, extension, family, panel_temp }
+}
+
+pub static MODELS: &[LoggerModel] = &[
+    model("CR1000X", "cr1x", CompilerFamily::Cr1000, Some("60")),
+    model("CR1000", "cr1", CompilerFamily::Cr1000, Some("_60Hz")),
+    model("CR3000", "cr3", CompilerFamily::Cr1000, Some("_60Hz")),
+    model("CR800", "cr8", CompilerFamily::Cr1000, Some("_60Hz")),
+    model("CR6", "cr6", CompilerFamily::Cr1000, Some("60")),
+    model("CR300", "cr300", CompilerFamily::Cr1000, Some("60")),
+    model("CR310", "cr300", CompilerFamily::Cr1000, Some("60")),
+    model("CR200X", "cr2", CompilerFamily::Cr200, None),
+];
+
+/// The model named `name`, ignoring case.
+pub fn logger_model(name: &str) -> Result<&'static LoggerModel, Error> {
+    MODELS.iter().find(|m| m.name.eq_ignore_ascii_case(name.trim())).ok_or_else(|| {
+        let known: Vec<&str> = MODELS.iter().map(|m| m.name).collect();
+        Error::Config(config::ConfigError::Message(format!("Unknown logger model '{}' (known: {})", name, known.join(", "))))
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct InitOptions {
+    pub model: &'static LoggerModel,
+    /// The program's name, without extension.
+    pub name: String,
+    /// Compiler the project pins; the one associated with the model's extension if `None`.
+    pub compiler_id: Option<String>,
+    /// Overwrite the program and `.gitignore` if they exist.
+    pub force: bool,
+}
+
+/// A file `init` considered, and whether it wrote it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitFile {
+    pub path: PathBuf,
+    pub written: bool,
+}
+
+pub const GITIGNORE: &str = "\
+# Build outputs: download images (with their checksums and signatures) and the
+# project's image directory
+*.bin
+*.bin.sha256
+*.bin.minisig
+*.dld
+/build/
+";
+
+/// The starter program for `model`, named `name`.
+pub fn program(model: &LoggerModel, name: &str) -> String {
+    let header = format!("'{} program: {}\n'Created by crbrs init.\n", model.name, name);
+    if model.family == CompilerFamily::Cr200 {
+        return format!(
+            "{header}
+'Declare Public Variables
+Public BattV
+
+'Declare Units
+Units BattV = Volts
+
+'Define Data Tables
+DataTable(Hourly,True,-1)
+  DataInterval(0,60,Min)
+  Minimum(1,BattV,False,False)
+EndTable
+
+'Main Program
+BeginProg
+  Scan(5,Sec)
+    Battery(BattV)
+    CallTable Hourly
+  NextScan
+EndProg
+"
+        );
+    }
+    let panel_temp = model.panel_temp.unwrap_or("60");
+    format!(
+        "{header}
+'Declare Public Variables
+Public BattV
+Public PTemp_C
+
+'Declare Units
+Units BattV = Volts
+Units PTemp_C = Deg C
+
+'Define Data Tables
+DataTable(Hourly,True,-1)
+  DataInterval(0,60,Min,10)
+  Minimum(1,BattV,FP2,False,False)
+  Average(1,PTemp_C,FP2,False)
+EndTable
+
+'Main Program
+BeginProg
+  Scan(5,Sec,1,0)
+    Battery(BattV)
+    PanelTemp(PTemp_C,{panel_temp})
+    CallTable Hourly
+  NextScan
+EndProg
+"
+    )
+}
+
+/// The project manifest pinning `compiler_id` for `model`'s programs, or with the settings to
+/// fill in commented out if there is no compiler yet.
+pub fn manifest(model: &LoggerModel, compiler_id: Option<&str>) -> String {
+    let comment = if compiler_id.is_some() { "" } else { "# " };
+    let compiler_id = compiler_id.unwrap_or("<compiler ID from crbrs compiler list-available>");
+    format!(
+        "# crbrs project manifest: the toolchain for the programs in this directory and below.
+# Its settings take precedence over each user's config.
+
+{comment}required_compilers = [\"{compiler_id}\"]
+
+# Directories searched for included files, relative to this file.
+include_paths = []
+
+{comment}[file_associations]
+{comment}{extension} = \"{compiler_id}\"
+
+[output]
+image_dir = \"build/images\"
+",
+        extension = model.extension
+    )
+}
+
+/// Writes a new project into `dir` (created if needed).
+pub fn init(dir: &Path, options: &InitOptions, settings: &Settings) -> Result<Vec<InitFile>, Error> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    if manifest_path.exists() {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists; {} is already a crbrs project", manifest_path.display(), dir.display()),
+        )));
+    }
+    let model = options.model;
+    let compiler_id = options.compiler_id.as_deref().or_else(|| settings.file_associations.get(model.extension).map(String::as_str));
+    if compiler_id.is_none() {
+        log::warn!("No compiler is associated with .{}; the manifest leaves it to fill in.", model.extension);
+    }
+    fs::create_dir_all(dir)?;
+    let files = [
+        (manifest_path, manifest(model, compiler_id)),
+        (dir.join(format!("{}.{}", options.name, model.extension)), program(model, &options.name)),
+        (dir.join(".gitignore"), GITIGNORE.to_string()),
+    ];
+    let mut written = Vec::new();
+    for (path, contents) in files {
+        let write = options.force || !path.exists();
+        if write {
+            fs::write(&path, contents)?;
+        } else {
+            log::info!("Keeping the existing {:?}", path);
+        }
+        written.push(InitFile { path, written: write });
+    }
+    Ok(written)
+}
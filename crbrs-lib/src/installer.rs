@@ -1,72 +1,258 @@
 // FILE: crbrs-lib/src/installer.rs
 
-use crate::{Error, Manifest, CompilerInfo, Settings}; // ManifestCompilerEntry is not directly used here now
-use crate::config::{get_compiler_storage_path, save_settings};
-use reqwest::blocking::Client;
+use crate::{say, tr, Error, Manifest, ManifestCompilerEntry, CompilerInfo, Settings, Suggestions};
+use crate::archive;
+use crate::cancel;
+use crate::config::{get_compiler_storage_path, save_state};
+use crate::dedup;
+use crate::network::{self, Fetched, NetworkSettings, Validators};
+use crate::signature;
+use serde::{Deserialize, Serialize};
 use std::fs::{self}; // File might not be strictly needed if not writing intermediate files
 use std::io::{self, Cursor}; // Removed Read, Write if not directly used
-use std::path::PathBuf;
-use zip::ZipArchive;
+use std::path::{Path, PathBuf};
 
 // --- Add imports for SHA256 ---
 use sha2::{Digest, Sha256}; // <-- NEW IMPORTS
 
-/// Fetches the compiler manifest from the given URL.
-pub fn fetch_manifest(repository_url: &str) -> Result<Manifest, Error> {
-    // ... (implementation remains the same) ...
+/// Description of an installed compiler, written into its directory for `link_compiler`.
+pub const COMPILER_METADATA_FILE: &str = "crbrs-compiler.toml";
+
+/// Fetches the compiler manifest from the given URL. Offline (see [`network::offline`]), uses
+/// the copy cached by the last fetch instead, and so it does when the fetch fails after its
+/// retries (a field modem dropping out mid-request) if that copy is fresh: known to be current
+/// within `manifest_max_age_hours`. The fetch is conditional on the cached copy having changed,
+/// so an up-to-date copy costs the server (and its rate limit) nothing.
+pub fn fetch_manifest(repository_url: &str, network: &NetworkSettings) -> Result<Manifest, Error> {
     log::info!("Fetching compiler manifest from: {}", repository_url);
-    let client = Client::builder().build()?;
-    let response = client.get(repository_url).send()?;
-    if !response.status().is_success() {
-        log::error!("Failed to fetch manifest. Status: {:?}, URL: {}", response.status(), repository_url);
-        return Err(Error::Network(response.error_for_status().unwrap_err()));
-    }
-    let manifest_text = response.text()?;
+    let cache = manifest_cache_path()?;
+    let validators = cached_validators(repository_url);
+    let (manifest_text, validators) = match network::get_text_if_changed(network, repository_url, &validators) {
+        Err(Error::Offline(reason)) => {
+            let text = fs::read_to_string(&cache).map_err(|_| {
+                Error::Offline(format!("{}; no manifest is cached yet, so fetch it once online (`crbrs compiler list-available`)", reason))
+            })?;
+            let (fetched, fresh) = cache_age(&cache, network);
+            say!("{}", tr!("manifest-offline", fetched = fetched.as_str()));
+            if !fresh {
+                say!("{}", tr!("manifest-stale", hours = network.manifest_max_age_hours));
+            }
+            log::warn!("Offline ({}); using the manifest cached at {}", reason, fetched);
+            return parse_cached_manifest(&text);
+        }
+        Err(e) if network::is_transient(&e) => {
+            let (fetched, fresh) = cache_age(&cache, network);
+            let text = match fs::read_to_string(&cache) {
+                Ok(text) if fresh => text,
+                _ => return Err(e),
+            };
+            say!("{}", tr!("manifest-fetch-failed", error = e.to_string(), fetched = fetched.as_str()));
+            log::warn!("Fetching the manifest failed ({}); using the copy cached at {}", e, fetched);
+            return parse_cached_manifest(&text);
+        }
+        Ok(Fetched::NotModified) => {
+            log::info!("The cached manifest is current");
+            // Its time stamp tells offline commands when it was last known to be current.
+            if let Err(e) = fs::File::options().append(true).open(&cache).and_then(|f| f.set_modified(std::time::SystemTime::now())) {
+                log::debug!("Could not touch the cached manifest: {}", e);
+            }
+            (fs::read_to_string(&cache)?, None)
+        }
+        Ok(Fetched::Changed(text, validators)) => (text, Some(validators)),
+        Err(e) => return Err(e),
+    };
     let manifest: Manifest = toml::from_str(&manifest_text)
         .map_err(|e| Error::InvalidCompilerSource(format!("Failed to parse manifest TOML: {}", e)))?;
     log::info!("Successfully fetched and parsed manifest. {} compilers listed.", manifest.compilers.len());
+    // Keep a copy so offline commands (e.g. `compiler list`) can still check for updates.
+    if let Some(validators) = validators {
+        if let Err(e) = cache_manifest(&cache, &manifest_text, repository_url, validators) {
+            log::debug!("Could not cache manifest: {}", e);
+        }
+    }
     Ok(manifest)
 }
 
+fn parse_cached_manifest(text: &str) -> Result<Manifest, Error> {
+    toml::from_str(text).map_err(|e| Error::InvalidCompilerSource(format!("Failed to parse cached manifest TOML: {}", e)))
+}
 
-/// Installs a compiler specified by its ID from the manifest.
-/// Modifies the `settings` in place and saves them.
-pub fn install_compiler(
-    settings: &mut Settings,
-    compiler_id_to_install: &str,
-) -> Result<(), Error> {
-    let manifest = fetch_manifest(&settings.compiler_repository_url)?;
+/// When the cached manifest was last known to be current, and whether that is within
+/// `manifest_max_age_hours` (0 for no limit).
+fn cache_age(cache: &Path, network: &NetworkSettings) -> (String, bool) {
+    let Ok(modified) = fs::metadata(cache).and_then(|m| m.modified()) else { return (String::new(), false) };
+    let age = modified.elapsed().unwrap_or_default();
+    let fresh = network.manifest_max_age_hours == 0 || age.as_secs() <= network.manifest_max_age_hours.saturating_mul(3600);
+    (crate::clock::timestamp(modified), fresh)
+}
 
-    let entry = manifest // This is ManifestCompilerEntry
-        .compilers
-        .get(compiler_id_to_install)
-        .ok_or_else(|| Error::CompilerIdNotFoundInManifest(compiler_id_to_install.to_string()))?;
+/// Where the last fetched manifest is kept.
+pub fn manifest_cache_path() -> Result<PathBuf, Error> {
+    Ok(crate::config::data_dir()?.join("manifest.toml"))
+}
 
-    log::info!("Attempting to install compiler: '{}' (Version: {}) from {}",
-        compiler_id_to_install, entry.version, entry.download_url);
+/// Where the cached manifest came from, for conditional requests, next to it.
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestOrigin {
+    url: String,
+    #[serde(flatten)]
+    validators: Validators,
+}
 
-    // 1. Download the compiler archive
-    let client = Client::new();
-    let response = client.get(&entry.download_url).send()?;
-    if !response.status().is_success() {
-        return Err(Error::Network(response.error_for_status().unwrap_err()));
+fn manifest_origin_path(cache: &Path) -> PathBuf {
+    cache.with_extension("origin.json")
+}
+
+/// Validators of the cached manifest if it came from `url`; empty otherwise, to fetch it whole.
+fn cached_validators(url: &str) -> Validators {
+    let Ok(cache) = manifest_cache_path() else { return Validators::default() };
+    if !cache.is_file() {
+        return Validators::default();
     }
-    let archive_bytes = response.bytes()?.to_vec();
-    log::info!("Downloaded {} bytes for compiler '{}'", archive_bytes.len(), compiler_id_to_install);
+    fs::read(manifest_origin_path(&cache))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<ManifestOrigin>(&bytes).ok())
+        .filter(|origin| origin.url == url)
+        .map(|origin| origin.validators)
+        .unwrap_or_default()
+}
 
-    // --- 2. Verify SHA256 checksum ---
-    if let Some(expected_sha256_from_manifest) = &entry.sha256 {
+fn cache_manifest(cache: &Path, text: &str, url: &str, validators: Validators) -> Result<(), Error> {
+    fs::create_dir_all(cache.parent().unwrap_or(Path::new(".")))?;
+    fs::write(cache, text)?;
+    let origin = serde_json::to_vec_pretty(&ManifestOrigin { url: url.to_string(), validators }).map_err(|e| Error::Io(e.into()))?;
+    Ok(fs::write(manifest_origin_path(cache), origin)?)
+}
+
+/// The manifest as of the last successful fetch, if any.
+pub fn cached_manifest() -> Option<Manifest> {
+    let text = fs::read_to_string(manifest_cache_path().ok()?).ok()?;
+    toml::from_str(&text).ok()
+}
+
+/// Orders version strings by their numeric components (`10.1` > `9.4`, `4.0a` = `4.0`).
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let numbers = |v: &str| -> Vec<u64> {
+        v.split(|c: char| !c.is_ascii_digit()).filter(|p| !p.is_empty()).filter_map(|p| p.parse().ok()).collect()
+    };
+    numbers(a).cmp(&numbers(b))
+}
+
+/// Health and usage of an installed compiler, for `compiler list`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompilerStatus {
+    pub id: String,
+    pub version: String,
+    pub description: String,
+    pub linked: bool,
+    pub executable: PathBuf,
+    pub executable_exists: bool,
+    /// Size of its files on disk (shared files counted in full).
+    pub size_bytes: u64,
+    /// RFC 3339 timestamp of the last successful compile, if any.
+    pub last_used: Option<String>,
+    /// A newer version of the same compiler (same executable) in the cached manifest.
+    pub update: Option<AvailableUpdate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailableUpdate {
+    pub id: String,
+    pub version: String,
+}
+
+/// Status of every installed compiler, sorted by ID. Uses the cached manifest, so it works
+/// offline.
+pub fn compiler_statuses(settings: &Settings) -> Result<Vec<CompilerStatus>, Error> {
+    let storage_path = get_compiler_storage_path(settings)?;
+    let sizes = dedup::usage(&storage_path, settings)?;
+    let history = crate::history::load();
+    let manifest = cached_manifest();
+    let mut ids: Vec<&String> = settings.installed_compilers.keys().collect();
+    ids.sort();
+    Ok(ids
+        .into_iter()
+        .map(|id| {
+            let info = &settings.installed_compilers[id];
+            let executable = storage_path.join(&info.install_subdir).join(&info.executable_name);
+            let update = manifest.as_ref().and_then(|m| {
+                m.compilers
+                    .iter()
+                    .filter(|(other_id, entry)| {
+                        (*other_id == id || entry.executable_name.eq_ignore_ascii_case(&info.executable_name))
+                            && compare_versions(&entry.version, &info.version).is_gt()
+                    })
+                    .max_by(|a, b| compare_versions(&a.1.version, &b.1.version))
+                    .map(|(other_id, entry)| AvailableUpdate { id: other_id.clone(), version: entry.version.clone() })
+            });
+            CompilerStatus {
+                id: id.clone(),
+                version: info.version.clone(),
+                description: info.description.clone(),
+                linked: info.linked,
+                executable_exists: executable.is_file(),
+                executable,
+                size_bytes: sizes.compilers.iter().find(|c| &c.id == id).map_or(0, |c| c.total),
+                last_used: history.compilers.get(id).map(|u| u.last_used.clone()),
+                update,
+            }
+        })
+        .collect())
+}
+
+/// An installed compiler's version against the manifest's, for `compiler upgrade`.
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionCheck {
+    pub id: String,
+    pub installed: String,
+    /// The manifest's version of the same ID, if it is newer.
+    pub available: Option<String>,
+    /// Linked compilers are upgraded where they are installed, not by this user.
+    pub linked: bool,
+}
+
+/// Compares installed compilers (`ids`, or all of them if empty) with the entries of the same
+/// ID in `manifest`. Compilers the manifest does not list are left out unless asked for.
+pub fn check_versions(settings: &Settings, manifest: &Manifest, ids: &[String]) -> Result<Vec<VersionCheck>, Error> {
+    let mut wanted: Vec<&String> = if ids.is_empty() { settings.installed_compilers.keys().collect() } else { ids.iter().collect() };
+    wanted.sort();
+    wanted.dedup();
+    let mut checks = Vec::new();
+    for id in wanted {
+        let info = settings
+            .installed_compilers
+            .get(id)
+            .ok_or_else(|| Error::CompilerNotFound(id.clone(), Suggestions::for_id(id, settings.installed_compilers.keys())))?;
+        let entry = match manifest.compilers.get(id) {
+            Some(entry) => entry,
+            None if ids.is_empty() => {
+                log::debug!("'{}' is not in the manifest; not checking it for upgrades.", id);
+                continue;
+            }
+            None => return Err(Error::CompilerIdNotFoundInManifest(id.clone(), Suggestions::for_id(id, manifest.compilers.keys()))),
+        };
+        checks.push(VersionCheck {
+            id: id.clone(),
+            installed: info.version.clone(),
+            available: compare_versions(&entry.version, &info.version).is_gt().then(|| entry.version.clone()),
+            linked: info.linked,
+        });
+    }
+    Ok(checks)
+}
+
+/// Lowercase hex SHA256 of `bytes`.
+fn sha256_hex(bytes: &[u8]) -> String {
+    // Using a simple loop; crates like `hex` or `data-encoding` could also be used.
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Verifies `archive_bytes` against the manifest's SHA256, if one is given.
+fn verify_checksum(compiler_id_to_install: &str, expected_sha256: Option<&String>, archive_bytes: &[u8]) -> Result<(), Error> {
+    if let Some(expected_sha256_from_manifest) = expected_sha256 {
         if !expected_sha256_from_manifest.is_empty() { // Only verify if a hash is provided
             log::info!("Verifying SHA256 checksum for '{}'...", compiler_id_to_install);
-            let mut hasher = Sha256::new();
-            hasher.update(&archive_bytes);
-            let actual_sha256_bytes = hasher.finalize();
-            // Convert bytes to hex string.
-            // Using a simple loop; crates like `hex` or `data-encoding` could also be used.
-            let actual_sha256_hex = actual_sha256_bytes
-                .iter()
-                .map(|byte| format!("{:02x}", byte))
-                .collect::<String>();
+            let actual_sha256_hex = sha256_hex(archive_bytes);
 
             if actual_sha256_hex.to_lowercase() != expected_sha256_from_manifest.to_lowercase() {
                 log::error!(
@@ -88,84 +274,451 @@ pub fn install_compiler(
     } else {
         log::warn!("No SHA256 checksum provided in manifest for '{}'. Skipping verification.", compiler_id_to_install);
     }
-    // --- End SHA256 Verification ---
+    Ok(())
+}
 
-    // 3. Determine storage path and unpack
-    let compiler_base_storage_path = get_compiler_storage_path(settings)?;
-    let install_subdir = PathBuf::from(compiler_id_to_install);
-    let compiler_install_path = compiler_base_storage_path.join(&install_subdir);
+/// Where the archive of an installed compiler is kept (for delta updates), whatever its format.
+fn archive_cache_path(storage_path: &Path, compiler_id: &str) -> PathBuf {
+    storage_path.join(".archives").join(format!("{}.archive", compiler_id))
+}
 
-    if compiler_install_path.exists() {
-        log::warn!("Compiler installation path {:?} already exists. Removing existing version first.", compiler_install_path);
-        fs::remove_dir_all(&compiler_install_path)?;
-    }
-    fs::create_dir_all(&compiler_install_path)?;
-    log::info!("Created installation directory: {:?}", compiler_install_path);
-
-    let reader = Cursor::new(archive_bytes); // Use the downloaded bytes
-    let mut archive = ZipArchive::new(reader)?;
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        // Sanitize file path to prevent zip slip vulnerabilities
-        let outpath = match file.enclosed_name() {
-            Some(path) => compiler_install_path.join(path),
-            None => {
-                log::warn!("Skipping potentially unsafe file path in zip: {}", file.name());
-                continue;
-            }
+/// Where the signature of an installed compiler's archive is kept (for offline reinstalls).
+fn signature_cache_path(storage_path: &Path, compiler_id: &str) -> PathBuf {
+    storage_path.join(".archives").join(format!("{}.sig", compiler_id))
+}
+
+/// Checks the detached signature of `archive` if the entry is signed (see [`crate::signature`]),
+/// returning the signature to keep with the cached archive. Unsigned entries are refused when
+/// `require_signed_compilers` is set.
+fn verify_signature(
+    settings: &Settings,
+    storage_path: &Path,
+    compiler_id: &str,
+    entry: &ManifestCompilerEntry,
+    archive: &[u8],
+) -> Result<Option<Vec<u8>>, Error> {
+    let invalid = |reason: &str| Error::SignatureInvalid { compiler_id: compiler_id.to_string(), reason: reason.to_string() };
+    let (url, public_key) = match (&entry.signature_url, &entry.public_key) {
+        (Some(url), Some(public_key)) => (url, public_key),
+        (None, None) if settings.require_signed_compilers => {
+            return Err(invalid("its manifest entry has no signature_url and public_key, and require_signed_compilers is set"));
+        }
+        (None, None) => {
+            log::debug!("No signature in manifest for '{}'; only the checksum is checked.", compiler_id);
+            return Ok(None);
+        }
+        _ => return Err(invalid("its manifest entry needs both signature_url and public_key")),
+    };
+    let signature = match network::offline(&settings.network, url) {
+        Some(reason) => {
+            let cached = signature_cache_path(storage_path, compiler_id);
+            fs::read(&cached).map_err(|_| {
+                Error::Offline(format!("cannot download the signature of '{}' ({}), and none is cached at {}", compiler_id, reason, cached.display()))
+            })?
+        }
+        None => network::get_bytes(&settings.network, url)?,
+    };
+    signature::verify_archive(compiler_id, archive, &signature, public_key)?;
+    Ok(Some(signature))
+}
+
+/// The archive of `compiler_id` kept by an earlier install, checked against the manifest,
+/// for installing without the network (`reason` says why it is needed).
+fn cached_archive(storage_path: &Path, compiler_id: &str, entry: &ManifestCompilerEntry, reason: &str) -> Result<Vec<u8>, Error> {
+    let path = archive_cache_path(storage_path, compiler_id);
+    let bytes = fs::read(&path).map_err(|_| {
+        Error::Offline(format!("cannot download '{}' ({}), and no archive of it is cached at {}", compiler_id, reason, path.display()))
+    })?;
+    verify_checksum(compiler_id, entry.sha256.as_ref(), &bytes)?;
+    log::info!("Offline ({}); installing '{}' from the cached archive {:?}", reason, compiler_id, path);
+    Ok(bytes)
+}
+
+/// Largest zstd window (as a power of two) a delta patch may use.
+const PATCH_WINDOW_LOG_MAX: u32 = 27;
+
+/// Rebuilds the archive of `compiler_id` from a delta patch against an installed base
+/// version, or `None` (after logging why) if no patch applies.
+///
+/// The result must match the entry's SHA256, so entries without a checksum never use patches.
+fn patched_archive(settings: &Settings, storage_path: &Path, compiler_id: &str, entry: &ManifestCompilerEntry) -> Option<Vec<u8>> {
+    if entry.sha256.as_deref().unwrap_or("").is_empty() {
+        return None;
+    }
+    for patch in &entry.patches {
+        if !settings.installed_compilers.contains_key(&patch.base) {
+            continue;
+        }
+        let Ok(base) = fs::read(archive_cache_path(storage_path, &patch.base)) else {
+            log::debug!("No cached archive for base '{}'; cannot apply patch.", patch.base);
+            continue;
         };
+        log::info!("Installing '{}' from a delta patch against '{}': {}", compiler_id, patch.base, patch.url);
+        let result = network::get_bytes(&settings.network, &patch.url)
+            .and_then(|patch_bytes| {
+                verify_checksum(&format!("{} (patch)", compiler_id), patch.sha256.as_ref(), &patch_bytes)?;
+                let mut decoder = zstd::stream::read::Decoder::with_ref_prefix(Cursor::new(patch_bytes), &base)?;
+                // Patches are made with --long=27 (128 MiB), the largest window a patch may ask for;
+                // more would let a patch make the decoder allocate up to 2 GiB.
+                decoder.window_log_max(PATCH_WINDOW_LOG_MAX)?;
+                let mut archive = Vec::new();
+                io::copy(&mut decoder, &mut archive)?;
+                Ok(archive)
+            })
+            .and_then(|archive| verify_checksum(compiler_id, entry.sha256.as_ref(), &archive).map(|_| archive));
+        match result {
+            Ok(archive) => {
+                log::info!("Rebuilt {} byte archive for '{}' from patch.", archive.len(), compiler_id);
+                return Some(archive);
+            }
+            Err(e) => log::warn!("Delta patch from '{}' failed ({}); falling back to a full download.", patch.base, e),
+        }
+    }
+    None
+}
 
-        if file.name().ends_with('/') {
-            log::debug!("Creating directory from zip: {:?}", outpath);
-            fs::create_dir_all(&outpath)?;
-        } else {
-            log::debug!("Extracting file from zip: {:?} ({} bytes)", outpath, file.size());
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(&p)?;
-                }
+/// Installs a compiler specified by its ID from the manifest.
+/// Modifies the `settings` in place and saves them.
+pub fn install_compiler(
+    settings: &mut Settings,
+    compiler_id_to_install: &str,
+) -> Result<(), Error> {
+    let manifest = fetch_manifest(&settings.compiler_repository_url, &settings.network)?;
+
+    let entry = manifest // This is ManifestCompilerEntry
+        .compilers
+        .get(compiler_id_to_install)
+        .ok_or_else(|| {
+            Error::CompilerIdNotFoundInManifest(
+                compiler_id_to_install.to_string(),
+                Suggestions::for_id(compiler_id_to_install, manifest.compilers.keys()),
+            )
+        })?;
+
+    let urls = entry.urls();
+    log::info!("Attempting to install compiler: '{}' (Version: {}) from {}",
+        compiler_id_to_install, entry.version, urls.join(", "));
+    if urls.is_empty() {
+        return Err(Error::InvalidCompilerSource(format!("No download URL listed for '{}'", compiler_id_to_install)));
+    }
+    let compiler_base_storage_path = get_compiler_storage_path(settings)?;
+
+    // 1. Download the compiler archive: a delta patch against an installed base version if
+    //    possible, otherwise the full archive, falling back to the next URL (mirror) on failure
+    // 2. Verify its SHA256 checksum; a corrupt mirror also falls through to the next URL
+    //    Offline, only an archive cached by an earlier install of the same ID can be used
+    let mut last_error = None;
+    let offline: Option<Vec<String>> = urls.iter().map(|url| network::offline(&settings.network, url)).collect();
+    let mut downloaded = match offline {
+        Some(reasons) => Some(cached_archive(&compiler_base_storage_path, compiler_id_to_install, entry, &reasons.join(", "))?),
+        None => patched_archive(settings, &compiler_base_storage_path, compiler_id_to_install, entry),
+    };
+    for url in urls.iter().filter(|_| downloaded.is_none()) {
+        let attempt = network::get_bytes(&settings.network, url).and_then(|bytes| {
+            log::info!("Downloaded {} bytes for compiler '{}' from {}", bytes.len(), compiler_id_to_install, url);
+            verify_checksum(compiler_id_to_install, entry.sha256.as_ref(), &bytes).map(|_| bytes)
+        });
+        match attempt {
+            Ok(bytes) => {
+                downloaded = Some(bytes);
+                break;
             }
-            let mut outfile = fs::File::create(&outpath)?;
-            io::copy(&mut file, &mut outfile)?;
-        }
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            if let Some(mode) = file.unix_mode() {
-                if mode != 0 { // Only set permissions if mode is non-zero
-                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
-                }
+            Err(Error::Cancelled) => return Err(Error::Cancelled),
+            Err(e) => {
+                log::warn!("Could not install '{}' from {}: {}", compiler_id_to_install, url, e);
+                last_error = Some(e);
             }
         }
     }
-    log::info!("Successfully unpacked compiler '{}' to {:?}", compiler_id_to_install, compiler_install_path);
+    let archive_bytes = match (downloaded, last_error) {
+        (Some(bytes), _) => bytes,
+        (None, Some(e)) => return Err(e),
+        (None, None) => unreachable!("at least one URL was tried"),
+    };
+    let signature = verify_signature(settings, &compiler_base_storage_path, compiler_id_to_install, entry, &archive_bytes)?;
 
-    // 4. Update settings
+    // 3. Unpack into a staging directory and move it into place, so a failed or cancelled
+    //    install never leaves a half-written compiler behind
+    let compiler_install_path = place(&compiler_base_storage_path, compiler_id_to_install, |staging| {
+        archive::unpack(&archive_bytes, entry.archive_format, staging)
+    })?;
+
+    // Keep the archive only if another version is installed from delta patches against it;
+    // otherwise it would just double the disk space of the compiler.
+    let cached_archive = archive_cache_path(&compiler_base_storage_path, compiler_id_to_install);
+    let cached_signature = signature_cache_path(&compiler_base_storage_path, compiler_id_to_install);
+    let patch_base = manifest.compilers.values().any(|other| other.patches.iter().any(|patch| patch.base == compiler_id_to_install));
+    if patch_base {
+        if let Some(parent) = cached_archive.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&cached_archive, &archive_bytes)?;
+        if let Some(signature) = signature {
+            fs::write(&cached_signature, signature)?;
+        }
+    } else {
+        log::debug!("No patch is made against '{}'; not keeping its archive.", compiler_id_to_install);
+        for stale in [&cached_archive, &cached_signature] {
+            if stale.exists() {
+                fs::remove_file(stale)?;
+            }
+        }
+    }
+
+    // 4. Update and save settings
     let installed_info = CompilerInfo {
         id: compiler_id_to_install.to_string(),
         description: entry.description.clone(),
         version: entry.version.clone(),
-        install_subdir,
+        install_subdir: PathBuf::from(compiler_id_to_install),
         executable_name: entry.executable_name.clone(),
         requires_wine: entry.requires_wine,
         supported_loggers: entry.supported_loggers.clone(),
+        family: entry.family,
+        linked: false,
+        archive_sha256: Some(sha256_hex(&archive_bytes)),
     };
-    settings.installed_compilers.insert(compiler_id_to_install.to_string(), installed_info);
-
-    // 5. Save settings
-    save_settings(settings)?;
+    register(settings, installed_info, &compiler_install_path)?;
     log::info!("Compiler '{}' installed and settings saved.", compiler_id_to_install);
 
     Ok(())
 }
 
+/// Fills a staging directory with `fill` and moves it to `<storage>/<compiler_id>`, replacing
+/// an existing install, then shares files identical to those of other installed versions.
+/// Returns the install directory.
+fn place(storage_path: &Path, compiler_id: &str, fill: impl FnOnce(&Path) -> Result<(), Error>) -> Result<PathBuf, Error> {
+    let compiler_install_path = storage_path.join(compiler_id);
+    let staging_path = storage_path.join(format!(".{}.partial", compiler_id));
+    if staging_path.exists() {
+        fs::remove_dir_all(&staging_path)?; // Left over from an interrupted install
+    }
+    fs::create_dir_all(&staging_path)?;
+    log::info!("Unpacking into staging directory: {:?}", staging_path);
+    if let Err(e) = fill(&staging_path) {
+        let _ = fs::remove_dir_all(&staging_path);
+        return Err(e);
+    }
+
+    // An existing install is moved aside rather than deleted first, so it is put back if the
+    // new one cannot be moved in: replacing (e.g. upgrading) a compiler never leaves none.
+    let previous_path = storage_path.join(format!(".{}.previous", compiler_id));
+    if compiler_install_path.exists() {
+        log::info!("Replacing the existing install at {:?}", compiler_install_path);
+        if previous_path.exists() {
+            fs::remove_dir_all(&previous_path)?;
+        }
+        fs::rename(&compiler_install_path, &previous_path)?;
+    }
+    if let Err(e) = fs::rename(&staging_path, &compiler_install_path) {
+        if previous_path.exists() {
+            let _ = fs::rename(&previous_path, &compiler_install_path);
+        }
+        let _ = fs::remove_dir_all(&staging_path);
+        return Err(e.into());
+    }
+    log::info!("Successfully unpacked compiler '{}' to {:?}", compiler_id, compiler_install_path);
+
+    // These only save space, so a failure is not fatal.
+    if let Err(e) = dedup::deduplicate(storage_path, &compiler_install_path) {
+        log::warn!("Could not deduplicate compiler files for '{}': {}", compiler_id, e);
+    }
+    if previous_path.exists() {
+        if let Err(e) = fs::remove_dir_all(&previous_path).map_err(Error::from).and_then(|_| dedup::collect_garbage(storage_path)) {
+            log::warn!("Could not remove the replaced install of '{}' at {:?}: {}", compiler_id, previous_path, e);
+        }
+    }
+    Ok(compiler_install_path)
+}
+
+/// Records an installed compiler in the settings and saves them. The compiler is also
+/// described next to its files so other users can `compiler link` it when the storage path is
+/// a shared (e.g. admin-owned) location.
+fn register(settings: &mut Settings, info: CompilerInfo, install_path: &Path) -> Result<(), Error> {
+    let metadata = toml::to_string_pretty(&info)
+        .map_err(|e| Error::InvalidCompilerSource(format!("Failed to describe compiler: {}", e)))?;
+    fs::write(install_path.join(COMPILER_METADATA_FILE), metadata)?;
+    settings.installed_compilers.insert(info.id.clone(), info);
+    save_state(settings)
+}
+
+/// What `install_from_file` records for a compiler that is not in any manifest. Fields left
+/// `None` come from the source's `crbrs-compiler.toml` if it has one, else are guessed.
+#[derive(Debug, Clone, Default)]
+pub struct LocalCompiler {
+    /// Defaults to the source's name without extension, e.g. `CR1000XComp-v7` for
+    /// `CR1000XComp-v7.zip`.
+    pub id: Option<String>,
+    /// Defaults to the only `.exe` in the source (preferring names containing `comp`).
+    pub executable: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    /// Defaults to CR200 for `cr2*` executables, else CR1000.
+    pub family: Option<crate::CompilerFamily>,
+}
+
+/// Copies the directory `from` into `to`, recursively, stopping between files if cancelled.
+fn copy_dir(from: &Path, to: &Path) -> Result<(), Error> {
+    for entry in fs::read_dir(from)? {
+        cancel::check()?;
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            fs::create_dir_all(&target)?;
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves the contents of a lone top-level directory (as in `CR1000XComp/cr1xcomp.exe`) up
+/// into `dir`.
+fn hoist_single_directory(dir: &Path) -> Result<(), Error> {
+    let entries: Vec<fs::DirEntry> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    let [only] = entries.as_slice() else { return Ok(()) };
+    if !only.file_type()?.is_dir() {
+        return Ok(());
+    }
+    let nested = dir.join(format!(".{}.nested", only.file_name().to_string_lossy()));
+    fs::rename(only.path(), &nested)?;
+    for entry in fs::read_dir(&nested)? {
+        let entry = entry?;
+        fs::rename(entry.path(), dir.join(entry.file_name()))?;
+    }
+    fs::remove_dir(&nested)?;
+    Ok(())
+}
+
+/// The compiler executable in `dir`: the only `.exe`, or the only one with `comp` in its name.
+fn find_executable(dir: &Path) -> Result<String, Error> {
+    let mut exes: Vec<String> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.to_ascii_lowercase().ends_with(".exe"))
+        .collect();
+    exes.sort();
+    if exes.len() > 1 {
+        let compilers: Vec<String> = exes.iter().filter(|name| name.to_ascii_lowercase().contains("comp")).cloned().collect();
+        if compilers.len() == 1 {
+            return Ok(compilers[0].clone());
+        }
+    }
+    match exes.as_slice() {
+        [only] => Ok(only.clone()),
+        [] => Err(Error::InvalidCompilerSource("No .exe found; specify the compiler executable".to_string())),
+        several => Err(Error::InvalidCompilerSource(format!(
+            "Several executables found ({}); specify the compiler executable",
+            several.join(", ")
+        ))),
+    }
+}
+
+/// Installs a compiler from a local archive (`.zip`, `.tar.gz` or `.7z`) or directory, e.g. one distributed by
+/// Campbell support or copied onto an air-gapped laptop, without any manifest or network.
+/// The files are copied into the compiler storage like a downloaded compiler's. Returns the
+/// compiler ID.
+pub fn install_from_file(settings: &mut Settings, source: &Path, local: &LocalCompiler) -> Result<String, Error> {
+    let is_archive = source.is_file();
+    if !is_archive && !source.is_dir() {
+        return Err(Error::Io(io::Error::new(io::ErrorKind::NotFound, format!("Compiler source not found: {}", source.display()))));
+    }
+    // `CR1000XComp-v7.tar.gz` is `CR1000XComp-v7`, like `CR1000XComp-v7.zip`.
+    let stem = source.file_stem().map(|s| s.to_string_lossy().trim_end_matches(".tar").to_string()).unwrap_or_default();
+    let storage = get_compiler_storage_path(settings)?;
+    // Unpack under a temporary ID first: the real one may only be known from the files.
+    let unpacked_id = format!(".local-{}", std::process::id());
+    let archive_bytes = if is_archive { Some(fs::read(source)?) } else { None };
+    let unpacked = place(&storage, &unpacked_id, |staging| {
+        if let Some(archive_bytes) = &archive_bytes {
+            archive::unpack(archive_bytes, None, staging)?;
+        } else {
+            copy_dir(source, staging)?;
+        }
+        hoist_single_directory(staging)
+    })?;
+    let described = match fs::read_to_string(unpacked.join(COMPILER_METADATA_FILE)) {
+        Ok(text) => Some(
+            toml::from_str::<CompilerInfo>(&text)
+                .map_err(|e| Error::InvalidCompilerSource(format!("Invalid {} in {}: {}", COMPILER_METADATA_FILE, source.display(), e))),
+        ),
+        Err(_) => None,
+    };
+    let info = described.transpose().and_then(|described| {
+        let executable_name = match (&local.executable, &described) {
+            (Some(executable), _) => executable.clone(),
+            (None, Some(described)) => described.executable_name.clone(),
+            (None, None) => find_executable(&unpacked)?,
+        };
+        if !unpacked.join(&executable_name).is_file() {
+            return Err(Error::CompilerNotFound(
+                format!("Executable '{}' not found in {}", executable_name, source.display()),
+                Suggestions::none(),
+            ));
+        }
+        let id = local.id.clone().or(described.as_ref().map(|d| d.id.clone())).unwrap_or(stem);
+        if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+            return Err(Error::InvalidCompilerSource(format!("'{}' cannot be a compiler ID; give one", id)));
+        }
+        let guessed_family =
+            if executable_name.to_ascii_lowercase().starts_with("cr2") { crate::CompilerFamily::Cr200 } else { crate::CompilerFamily::Cr1000 };
+        Ok(CompilerInfo {
+            description: local
+                .description
+                .clone()
+                .or(described.as_ref().map(|d| d.description.clone()))
+                .unwrap_or_else(|| format!("Installed from {}", source.display())),
+            version: local.version.clone().or(described.as_ref().map(|d| d.version.clone())).unwrap_or_else(|| "unknown".to_string()),
+            install_subdir: PathBuf::from(&id),
+            requires_wine: described.as_ref().map_or(executable_name.to_ascii_lowercase().ends_with(".exe"), |d| d.requires_wine),
+            supported_loggers: described.as_ref().and_then(|d| d.supported_loggers.clone()),
+            family: local.family.or(described.as_ref().map(|d| d.family)).unwrap_or(guessed_family),
+            linked: false,
+            archive_sha256: archive_bytes.as_deref().map(sha256_hex),
+            executable_name,
+            id,
+        })
+    });
+    let info = match info {
+        Ok(info) if settings.installed_compilers.get(&info.id).is_some_and(|existing| existing.linked) => Err(Error::InvalidCompilerSource(
+            format!("Compiler '{}' is linked; remove it before installing another copy", info.id),
+        )),
+        other => other,
+    };
+    let info = match info {
+        Ok(info) => info,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&unpacked);
+            return Err(e);
+        }
+    };
+    let install_path = storage.join(&info.id);
+    if install_path.exists() {
+        log::warn!("Compiler installation path {:?} already exists. Removing existing version first.", install_path);
+        fs::remove_dir_all(&install_path)?;
+    }
+    fs::rename(&unpacked, &install_path)?;
+    let id = info.id.clone();
+    log::info!("Installing compiler '{}' from {:?}", id, source);
+    register(settings, info, &install_path)?;
+    Ok(id)
+}
+
 /// Removes an installed compiler.
 pub fn remove_compiler(settings: &mut Settings, compiler_id_to_remove: &str) -> Result<(), Error> {
     if !settings.installed_compilers.contains_key(compiler_id_to_remove) {
         log::warn!("Compiler '{}' not found in settings, nothing to remove.", compiler_id_to_remove);
         return Ok(());
     }
+    if settings.installed_compilers[compiler_id_to_remove].linked {
+        // Linked compilers live in a shared location; only this user's registration goes.
+        log::info!("Unlinking compiler '{}'; its files are left in place.", compiler_id_to_remove);
+        settings.installed_compilers.remove(compiler_id_to_remove);
+        save_state(settings)?;
+        return Ok(());
+    }
     let compiler_base_storage_path = get_compiler_storage_path(settings)?;
     let compiler_install_dir = compiler_base_storage_path.join(compiler_id_to_remove);
     if compiler_install_dir.exists() {
@@ -174,8 +727,79 @@ pub fn remove_compiler(settings: &mut Settings, compiler_id_to_remove: &str) ->
     } else {
         log::warn!("Compiler directory {:?} not found, but removing from settings anyway.", compiler_install_dir);
     }
+    let cached_archive = archive_cache_path(&compiler_base_storage_path, compiler_id_to_remove);
+    if cached_archive.exists() {
+        fs::remove_file(&cached_archive)?;
+    }
+    // Archives cached by earlier versions were always named `<id>.zip`.
+    let _ = fs::remove_file(cached_archive.with_extension("zip"));
+    let _ = fs::remove_file(signature_cache_path(&compiler_base_storage_path, compiler_id_to_remove));
+    dedup::collect_garbage(&compiler_base_storage_path)?;
     settings.installed_compilers.remove(compiler_id_to_remove);
-    save_settings(settings)?;
+    save_state(settings)?;
     log::info!("Compiler '{}' removed and settings saved.", compiler_id_to_remove);
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Registers a compiler that is already installed at `path` (e.g. by an administrator into a
+/// shared, read-only location) without copying it. The compiler is described by the
+/// `crbrs-compiler.toml` that `install_compiler` writes; for other directories `executable`
+/// is required. Returns the compiler ID.
+pub fn link_compiler(settings: &mut Settings, path: &Path, id: Option<&str>, executable: Option<&str>) -> Result<String, Error> {
+    let dir = fs::canonicalize(path)?;
+    let metadata_path = dir.join(COMPILER_METADATA_FILE);
+    let mut info = if metadata_path.is_file() {
+        let text = fs::read_to_string(&metadata_path)?;
+        toml::from_str::<CompilerInfo>(&text)
+            .map_err(|e| Error::InvalidCompilerSource(format!("Invalid {:?}: {}", metadata_path, e)))?
+    } else {
+        let executable_name = executable.ok_or_else(|| {
+            Error::InvalidCompilerSource(format!(
+                "{:?} has no {}; specify the compiler executable",
+                dir, COMPILER_METADATA_FILE
+            ))
+        })?;
+        let dir_name = dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        CompilerInfo {
+            id: dir_name.clone(),
+            description: format!("Linked from {}", dir.display()),
+            version: "unknown".to_string(),
+            install_subdir: PathBuf::new(),
+            executable_name: executable_name.to_string(),
+            requires_wine: cfg!(not(windows)),
+            supported_loggers: None,
+            family: Default::default(),
+            linked: true,
+            archive_sha256: None,
+        }
+    };
+    if let Some(id) = id {
+        info.id = id.to_string();
+    }
+    if let Some(executable) = executable {
+        info.executable_name = executable.to_string();
+    }
+    info.install_subdir = dir.clone();
+    info.linked = true;
+    if !dir.join(&info.executable_name).is_file() {
+        return Err(Error::CompilerNotFound(format!(
+            "Executable '{}' not found in {}",
+            info.executable_name,
+            dir.display()
+        ), Suggestions::none()));
+    }
+    if let Some(existing) = settings.installed_compilers.get(&info.id) {
+        if !existing.linked {
+            return Err(Error::InvalidCompilerSource(format!(
+                "Compiler '{}' is already installed; remove it before linking another copy",
+                info.id
+            )));
+        }
+        log::warn!("Replacing existing link for compiler '{}'", info.id);
+    }
+    let id = info.id.clone();
+    log::info!("Linking compiler '{}' at {:?}", id, dir);
+    settings.installed_compilers.insert(id.clone(), info);
+    save_state(settings)?;
+    Ok(id)
+}
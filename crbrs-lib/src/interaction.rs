@@ -0,0 +1,165 @@
+// FILE: crbrs-lib/src/interaction.rs
+
+//! How crbrs talks to the person running it: quiet mode and prompts.
+//!
+//! In quiet mode (`--quiet`) only errors are printed; everything else written with [`say!`]
+//! is dropped, and the exit code tells the result. In no-input mode (`--no-input`), and
+//! whenever stdin is not a terminal, prompts are never shown: a question with a default
+//! gets the default, one without fails with [`Error::InputRequired`], so an unattended
+//! script behaves the same on every run.
+
+use crate::Error;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static NO_INPUT: AtomicBool = AtomicBool::new(false);
+
+/// Turns quiet mode on or off for this process.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Turns no-input mode on or off for this process.
+pub fn set_no_input(no_input: bool) {
+    NO_INPUT.store(no_input, Ordering::Relaxed);
+}
+
+/// Whether prompts are answered without asking: no-input mode, or stdin is not a terminal.
+pub fn is_unattended() -> bool {
+    NO_INPUT.load(Ordering::Relaxed) || !io::stdin().is_terminal()
+}
+
+/// Prints a line of (non-error) output unless quiet mode is on; takes `println!` arguments.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::interaction::is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Like [`say!`], without the newline; takes `print!` arguments.
+#[macro_export]
+macro_rules! say_raw {
+    ($($arg:tt)*) => {
+        if !$crate::interaction::is_quiet() {
+            print!($($arg)*);
+        }
+    };
+}
+
+/// Shows `question` on stderr and reads one line of answer, trimmed.
+fn prompt(question: &str) -> Result<String, Error> {
+    let mut stderr = io::stderr();
+    write!(stderr, "{} ", question)?;
+    stderr.flush()?;
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(Error::InputRequired(question.to_string())); // End of input
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// Asks a yes/no question; unattended, or on an empty answer, returns `default`.
+pub fn confirm(question: &str, default: bool) -> Result<bool, Error> {
+    if is_unattended() {
+        log::info!("Not asking \"{}\" (no input); answering {}", question, if default { "yes" } else { "no" });
+        return Ok(default);
+    }
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    loop {
+        match prompt(&format!("{} {}", question, hint))?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => continue,
+        }
+    }
+}
+
+/// Asks for a value; an empty answer gives `default`. Unattended, returns `default` or fails
+/// with [`Error::InputRequired`] if there is none.
+pub fn ask(question: &str, default: Option<&str>) -> Result<String, Error> {
+    if is_unattended() {
+        log::info!("Not asking \"{}\" (no input); default {:?}", question, default);
+        return default.map(str::to_string).ok_or_else(|| Error::InputRequired(question.to_string()));
+    }
+    loop {
+        let answer = match default {
+            Some(default) => prompt(&format!("{} [{}]", question, default))?,
+            None => prompt(question)?,
+        };
+        match (answer.is_empty(), default) {
+            (false, _) => return Ok(answer),
+            (true, Some(default)) => return Ok(default.to_string()),
+            (true, None) => continue,
+        }
+    }
+}
+
+/// Turns off terminal echo until dropped, so a typed secret isn't shown.
+struct NoEcho {
+    #[cfg(unix)]
+    saved: Option<libc::termios>,
+}
+
+impl NoEcho {
+    #[cfg(unix)]
+    fn new() -> NoEcho {
+        // SAFETY: tcgetattr/tcsetattr only read and write the termios struct passed to them.
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return NoEcho { saved: None };
+            }
+            let saved = termios;
+            termios.c_lflag &= !libc::ECHO;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+            NoEcho { saved: Some(saved) }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> NoEcho {
+        NoEcho {}
+    }
+}
+
+impl Drop for NoEcho {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(saved) = &self.saved {
+            // SAFETY: restores the settings read in `new`.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+            }
+        }
+    }
+}
+
+/// Asks for a secret without echoing it (on Unix terminals). With stdin not a terminal, reads
+/// the first line piped in instead; in no-input mode, fails with [`Error::InputRequired`].
+pub fn ask_secret(question: &str) -> Result<String, Error> {
+    if NO_INPUT.load(Ordering::Relaxed) {
+        return Err(Error::InputRequired(question.to_string()));
+    }
+    if !io::stdin().is_terminal() {
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Err(Error::InputRequired(question.to_string()));
+        }
+        return Ok(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+    let answer = {
+        let _no_echo = NoEcho::new();
+        prompt(question)
+    };
+    eprintln!(); // The Enter wasn't echoed either
+    answer
+}
@@ -1,32 +1,72 @@
 // FILE: crbrs-lib/src/lib.rs
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use thiserror::Error;
 use ::config::ConfigError;
 
+pub use adapter::CompilerFamily;
+
 // --- Configuration Structures ---
 
 #[derive(Debug, Serialize, Deserialize, Clone)] // Clone is useful for modifying settings
 #[serde(default)] // Ensure defaults are used if fields are missing in config file
 pub struct Settings {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>, // Shared config files this one builds on (see config::load_settings)
     pub compiler_repository_url: String,
     pub compiler_storage_path: Option<PathBuf>, // Option allows finding default if None
     pub installed_compilers: HashMap<String, CompilerInfo>,
     pub file_associations: HashMap<String, String>, // Key: extension (e.g., "cr2"), Value: compiler ID
     pub wine_path: Option<String>,
+    pub signing_key_path: Option<PathBuf>, // minisign secret key used to sign produced artifacts
+    pub signing_public_key: Option<String>, // minisign public key (base64) used to verify them
+    pub require_signed_compilers: bool, // Refuse compiler downloads without a manifest signature
+    pub session_log: session_log::SessionLogSettings, // [session_log] terminal/monitor audit logs
+    pub network: network::NetworkSettings, // [network] timeouts and retries for all network operations
+    pub log_file: Option<PathBuf>, // JSON-lines log of every run, like --log-file
+    pub last_run_log: structured_log::Detail, // How much of each run last-run.jsonl keeps for report-bug; "off" keeps none
+    pub lint: HashMap<String, syntax::lint::Level>, // [lint] rule ID -> severity or "off"
+    pub max_diagnostics: usize, // Compiler error groups / LSP diagnostics shown per file; 0 for no limit
+    pub compile_timeout_secs: u64, // Compiler runs taking longer are killed; 0 for no limit
+    pub artifacts_in_place: in_place::InPlace, // Whether compilers write next to programs in read-only or tagged checkouts
+    pub station_timezones: HashMap<String, String>, // [station_timezones] station name -> UTC offset of its clock
+    pub stations: HashMap<String, station::Station>, // [stations.<name>] loggers crbrs talks to
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, toml::Table>, // [profiles.<name>] overrides selected with --profile
+    #[serde(skip)]
+    pub active_profile: Option<String>, // Profile applied by load_settings, if any
+    #[serde(skip)]
+    pub environment: Vec<(Vec<String>, toml::Value)>, // Settings CRBRS_* variables set, with their values
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
+            include: Vec::new(), // Nothing but the built-in defaults below the user's file
             // TODO: Consider a more permanent default URL later
             compiler_repository_url: "https://raw.githubusercontent.com/RileyLeff/campbell-scientific-compilers/refs/heads/main/compilers.toml".to_string(),
             compiler_storage_path: None, // We'll resolve this to a default path at runtime
             installed_compilers: HashMap::new(),
             file_associations: HashMap::new(),
             wine_path: None, // Will try finding 'wine' in PATH by default
+            signing_key_path: None, // Artifacts are only checksummed unless a key is configured
+            signing_public_key: None,
+            require_signed_compilers: false, // Signatures are checked when the manifest has them
+            session_log: session_log::SessionLogSettings::default(),
+            network: network::NetworkSettings::default(),
+            log_file: None, // Structured logs are only written on request
+            last_run_log: structured_log::Detail::Debug,
+            lint: HashMap::new(), // Every rule at its default severity
+            max_diagnostics: diagnostics::DEFAULT_MAX_DIAGNOSTICS,
+            compile_timeout_secs: 600, // Far longer than any real compile; stops a wedged Wine
+            artifacts_in_place: in_place::InPlace::Auto, // Sandboxed where the program's directory must not be written
+            station_timezones: HashMap::new(), // Data timestamps are only converted on request
+            stations: HashMap::new(),
+            profiles: BTreeMap::new(),
+            active_profile: None,
+            environment: Vec::new(),
         }
     }
 }
@@ -41,10 +81,16 @@ pub struct CompilerInfo {
     pub description: String,        // From manifest
     pub version: String,            // From manifest
     pub install_subdir: PathBuf,    // Path to the *directory* of this compiler relative to compiler_storage_path
-                                    // (e.g., "cr2comp-v4.0/")
+                                    // (e.g., "cr2comp-v4.0/"), or absolute for linked compilers
     pub executable_name: String,    // e.g., "cr2comp.exe" (relative to install_subdir)
     pub requires_wine: bool,        // From manifest
     pub supported_loggers: Option<Vec<String>>, // From manifest
+    #[serde(default)]               // Configs written before families existed are CR1000-family
+    pub family: CompilerFamily,     // From manifest
+    #[serde(default)]               // Registered with `compiler link`: files are owned by someone else
+    pub linked: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")] // SHA256 of the archive it was installed from;
+    pub archive_sha256: Option<String>,                        // recorded in a project's crbrs.lock
 }
 
 // Helper for serde default
@@ -66,7 +112,10 @@ pub struct Manifest {
 pub struct ManifestCompilerEntry {
     pub description: String,
     pub version: String,
+    #[serde(default)] // May be omitted when only `download_urls` is given
     pub download_url: String,
+    #[serde(default)] // Mirrors, tried in order after `download_url`
+    pub download_urls: Vec<String>,
     pub executable_name: String, // e.g., "cr2comp.exe"
     #[serde(default = "default_true")] // Assume requires wine if not specified
     pub requires_wine: bool,
@@ -74,9 +123,42 @@ pub struct ManifestCompilerEntry {
     pub supported_loggers: Option<Vec<String>>,
     #[serde(default)]
     pub sha256: Option<String>, // Optional checksum for verification
+    #[serde(default)] // Selects argument/output handling; defaults to the CR1000 family
+    pub family: CompilerFamily,
+    #[serde(default)] // Delta updates from other (installed) versions
+    pub patches: Vec<ManifestPatch>,
+    #[serde(default)] // "zip", "tar.gz" or "7z"; detected from the archive if omitted
+    pub archive_format: Option<archive::ArchiveFormat>,
+    #[serde(default)] // Detached minisign or OpenPGP signature of the archive
+    pub signature_url: Option<String>,
+    #[serde(default)] // Key the signature must verify against (see the signature module)
+    pub public_key: Option<String>,
+}
+
+/// A `zstd --patch-from` delta that rebuilds a compiler's archive from the archive of an
+/// already installed base compiler.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ManifestPatch {
+    pub base: String, // Compiler ID the patch applies to
+    pub url: String,
+    #[serde(default)]
+    pub sha256: Option<String>, // Checksum of the patch file itself
 }
 
-#[derive(Debug, Clone)] // Clone might be useful
+impl ManifestCompilerEntry {
+    /// Every download URL, primary first, without duplicates.
+    pub fn urls(&self) -> Vec<String> {
+        let mut urls: Vec<String> = Vec::new();
+        for url in std::iter::once(&self.download_url).chain(&self.download_urls) {
+            if !url.is_empty() && !urls.contains(url) {
+                urls.push(url.clone());
+            }
+        }
+        urls
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)] // Clone might be useful
 pub struct CompilationErrorDetail {
     pub file_path_in_log: String, // e.g., "example.cr2" from the log's first line
     pub line: Option<u32>,
@@ -84,6 +166,42 @@ pub struct CompilationErrorDetail {
     // Add character_pos or other fields if the compiler ever provides them
 }
 
+/// Known IDs similar to a mistyped one, shown as " Did you mean ...?" in errors.
+#[derive(Debug, Clone, Default)]
+pub struct Suggestions(pub Vec<String>);
+
+impl Suggestions {
+    pub fn none() -> Self {
+        Suggestions(Vec::new())
+    }
+
+    /// Up to three of `candidates` that look like `wanted` (case-insensitive), best first.
+    pub fn for_id<'a>(wanted: &str, candidates: impl IntoIterator<Item = &'a String>) -> Self {
+        let wanted = wanted.to_lowercase();
+        let mut scored: Vec<(f64, &String)> = candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let lower = candidate.to_lowercase();
+                let score = strsim::jaro_winkler(&wanted, &lower);
+                let contains = !wanted.is_empty() && (lower.contains(&wanted) || wanted.contains(&lower));
+                (score >= 0.8 || contains).then_some((score.max(if contains { 0.9 } else { 0.0 }), candidate))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        Suggestions(scored.into_iter().take(3).map(|(_, id)| id.clone()).collect())
+    }
+}
+
+impl std::fmt::Display for Suggestions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        let quoted: Vec<String> = self.0.iter().map(|id| format!("'{}'", id)).collect();
+        write!(f, " Did you mean {}?", quoted.join(" or "))
+    }
+}
+
 // --- Error Enum ---
 
 #[derive(Error, Debug)]
@@ -97,14 +215,23 @@ pub enum Error {
     #[error("Network error: {0}")]
     Network(#[from] reqwest::Error),
 
+    #[error("Rate limited by {host}; try again in {} s", .retry_after.as_secs())]
+    RateLimited { host: String, retry_after: std::time::Duration },
+
+    #[error("Offline: {0}")]
+    Offline(String),
+
     #[error("Failed to process ZIP archive: {0}")]
     Zip(#[from] zip::result::ZipError),
 
     // #[error("Failed to process TAR archive: {0}")] // Add if/when tar support is added
     // Tar(#[from] tar::Error), // Requires tar crate
 
-    #[error("Compiler '{0}' not found in configuration.")]
-    CompilerNotFound(String),
+    #[error("Compiler '{0}' not found in configuration.{1}")]
+    CompilerNotFound(String, Suggestions),
+
+    #[error("Signature check failed for compiler '{compiler_id}': {reason}")]
+    SignatureInvalid { compiler_id: String, reason: String },
 
     #[error("SHA256 checksum mismatch for compiler '{compiler_id}'. Expected: '{expected}', Got: '{actual}'.")]
     ChecksumMismatch { // <-- NEW ERROR VARIANT
@@ -116,6 +243,21 @@ pub enum Error {
     #[error("No compiler associated with file extension '.{0}'. Please configure an association.")]
     NoCompilerForExtension(String),
 
+    #[error("Files with extension '.{extension}' are associated with compiler '{compiler_id}', which is not installed. {hint}")]
+    AssociatedCompilerNotInstalled {
+        extension: String,
+        compiler_id: String,
+        in_manifest: bool, // Whether `crbrs compiler install` can fix it
+        hint: String,
+    },
+
+    #[error(
+        "The project ({}) requires compilers that are not installed: {}. Install them with `crbrs compiler install <ID>` (or compile with --install-missing).",
+        manifest.display(),
+        missing.join(", ")
+    )]
+    ProjectCompilersMissing { manifest: PathBuf, missing: Vec<String> },
+
     #[error("Could not find Wine executable. Please install Wine or set the path in configuration.")]
     WineNotFound,
 
@@ -139,25 +281,164 @@ pub enum Error {
     #[error("Compiler execution failed. Output Log:\n{log_content}")]
     CompilationFailedWithLog { log_content: String }, // Use if we parse the log
 
+    #[error("Compiling '{}' did not finish within {seconds} s; the compiler was stopped.", file_path.display())]
+    CompilationTimeout { file_path: PathBuf, seconds: u64 },
+
+    #[error("{failed} of {total} files failed to compile.")]
+    BuildFailed { failed: usize, total: usize }, // Batch compile; details are in the build summary
+
+    #[error("Project verification failed: {}.", failed.join(", "))]
+    VerificationFailed { failed: Vec<String> }, // crbrs verify-project; details are in its report
+
     #[error("Invalid compiler source or manifest: {0}")]
     InvalidCompilerSource(String),
 
     #[error("Failed to determine application directories.")]
     DirectoryResolutionFailed,
 
-    #[error("Compiler ID '{0}' not found in the repository manifest.")]
-    CompilerIdNotFoundInManifest(String),
+    #[error("Compiler ID '{0}' not found in the repository manifest.{1}")]
+    CompilerIdNotFoundInManifest(String, Suggestions),
 
     #[error("Invalid file extension: '{0}'.")]
     InvalidExtension(String),
+
+    #[error("Compiler '{0}' cannot produce a download image; send the program source instead.")]
+    ImageNotSupported(String),
+
+    #[error("Compiler finished but no download image was found at '{0}'.")]
+    ImageNotProduced(PathBuf),
+
+    #[error("Artifact integrity check failed: {0}")]
+    Integrity(String),
+
+    #[error("Deploy blocked: {0} (use --force to deploy anyway)")]
+    DeployBlocked(String),
+
+    #[error("Version header error: {0}")]
+    Version(String),
+
+    #[error("Metadata export failed: {0}")]
+    Metadata(String),
+
+    #[error("Documentation generation failed: {0}")]
+    Docgen(String),
+
+    #[error("Remote build failed: {0}")]
+    Remote(String),
+
+    #[error("Cannot add sensor: {0}")]
+    Sensor(String),
+
+    #[error("Calibration failed: {0}")]
+    Calibration(String),
+
+    #[error("Data file error: {0}")]
+    Data(String),
+
+    #[error("Logger communication failed: {0}")]
+    Logger(String),
+
+    #[error("Stations need attention: {0}")]
+    Alert(String),
+
+    #[error("Provisioning failed: {0}")]
+    Provision(String),
+
+    #[error("Secret error: {0}")]
+    Secret(String),
+
+    /// The operation itself succeeded, but the settings recording it could not be written.
+    #[error(
+        "The settings could not be saved to {}: {reason}. Anything else the command did is done, but not recorded. Make that directory writable, or keep crbrs' files in a writable directory with --portable <dir> (or CRBRS_HOME).",
+        path.display()
+    )]
+    SettingsNotSaved { path: PathBuf, reason: String },
+
+    #[error("Cancelled.")]
+    Cancelled,
+
+    #[error("Input required: \"{0}\" cannot be answered without a terminal (--no-input).")]
+    InputRequired(String),
+
+    #[error("Formatting: {0}")]
+    Format(String),
+
+    #[error("Tables collide in {0} place(s); their data cannot share one database")]
+    TableCollisions(usize),
 }
 
 // Define pub modules for organization (create the files next)
+pub mod adapter;
+pub mod archive;
+pub mod artifact;
+#[cfg(feature = "async")]
+pub mod async_api;
+pub mod bench;
+pub mod bug_report;
+pub mod build;
+pub mod build_state;
+pub mod calibration;
+pub mod cancel;
+pub mod card;
+pub mod clock;
+pub mod compile_cache;
+pub mod compiler_path;
 pub mod config;
+pub mod convert;
+pub mod data_check;
+pub mod dedup;
+pub mod diagnostics;
 pub mod compiler;
+pub mod deploy;
+pub mod docgen;
+pub mod explain;
+pub mod fleet;
+pub mod format;
+pub mod history;
+pub mod i18n;
+pub mod in_place;
+pub mod init;
 pub mod installer;
+pub mod interaction;
+pub mod lint;
+pub mod logger;
+pub mod merge;
+pub mod metadata;
+pub mod network;
+pub mod pakbus;
+pub mod provision;
+pub mod project;
+pub mod purge;
+pub mod qa;
+pub mod remote;
+pub mod sandbox;
+pub mod sarif;
+pub mod secret;
+pub mod send;
+pub mod sensor;
+pub mod session_log;
+pub mod signature;
+pub mod station;
+pub mod status;
+pub mod structured_log;
+pub mod symbol_index;
+pub mod temp;
+pub mod terminal;
+pub mod timezone;
+pub mod toa5;
+pub mod tob1;
+pub mod tob3;
+pub mod validate;
+pub mod verify;
+pub mod version;
 // pub mod download; // Maybe later
 
+// The parser/linter/formatter live in their own I/O-free crate so they also build for wasm32.
+pub use crbrs_syntax as syntax;
+
+#[cfg(feature = "async")]
+pub use async_api::{compile_file_async, compile_for_download_async, fetch_manifest_async, install_compiler_async};
+
 // Example function signature using the types (implementation later)
 pub fn compile_file(
     input_file: PathBuf,
@@ -173,6 +454,23 @@ pub fn compile_file(
         )
 }
 
+/// Compiles `input_file` for download and returns the path of the produced binary image.
+pub fn compile_for_download(
+    input_file: PathBuf,
+    output_log: Option<PathBuf>,
+    compiler_id: Option<String>,
+    image_output: Option<PathBuf>,
+    settings: &Settings,
+) -> Result<PathBuf, Error> {
+    compiler::compile_for_download_impl(
+        &input_file,
+        output_log.as_deref(),
+        compiler_id.as_deref(),
+        image_output.as_deref(),
+        settings,
+    )
+}
+
 
 #[cfg(test)]
 mod tests {
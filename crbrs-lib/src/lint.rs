@@ -0,0 +1,48 @@
+// FILE: crbrs-lib/src/lint.rs
+
+//! Running the native linter on a program with the user's and the project's configuration:
+//! rule levels come from the `[lint]` config table, and the opt-in `spelling` rule (see
+//! [`crate::syntax::spelling`]) runs only when a level is set for it, e.g.
+//! `crbrs config set lint.spelling warning`.
+//!
+//! Words a project spells on purpose (a site called "Presure Creek") go in a
+//! `crbrs-words.txt` file, one per line, `#` starting a comment. Every such file in the
+//! program's directory and the directories above it applies.
+
+use crate::syntax::lint::{apply_levels, lint_source, Level, LintDiagnostic};
+use crate::syntax::spelling;
+use crate::Settings;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+pub const WORDS_FILE: &str = "crbrs-words.txt";
+
+/// The accepted words, in lower case, from the word lists that apply to `file`.
+pub fn accepted_words(file: &Path) -> HashSet<String> {
+    let Ok(file) = std::path::absolute(file) else { return HashSet::new() };
+    let mut words = HashSet::new();
+    for list in file.ancestors().skip(1).map(|dir| dir.join(WORDS_FILE)) {
+        let Ok(text) = fs::read_to_string(&list) else { continue };
+        log::debug!("Reading accepted words from {:?}", list);
+        words.extend(
+            text.lines()
+                .map(|line| line.split('#').next().unwrap_or_default().trim())
+                .filter(|word| !word.is_empty())
+                .map(str::to_lowercase),
+        );
+    }
+    words
+}
+
+/// The linter's diagnostics for `source`, the program at `file` if it is saved, with the
+/// configured rule levels applied.
+pub fn lint(source: &str, file: Option<&Path>, settings: &Settings) -> Vec<LintDiagnostic> {
+    let mut diagnostics = lint_source(source);
+    if settings.lint.get("spelling").is_some_and(|level| *level != Level::Off) {
+        let accepted = file.map(accepted_words).unwrap_or_default();
+        diagnostics.extend(spelling::check(source, &accepted));
+        diagnostics.sort_by_key(|d| (d.span.start, d.rule));
+    }
+    apply_levels(diagnostics, &settings.lint)
+}
@@ -0,0 +1,577 @@
+// FILE: crbrs-lib/src/logger.rs
+
+//! Connections to loggers: opens a serial port or a PakBus/TCP connection and runs PakBus
+//! transactions on it (see [`crate::pakbus`]).
+//!
+//! Addresses are written `tcp:host[:port]` (port 6785 by default), `serial:PORT[@baud]`
+//! (115200 baud by default), or just `host:port`, `/dev/ttyUSB0` or `COM3`. With
+//! `serial:PORT@auto` the baud rate is found by sending a Hello at each of [`PROBE_BAUDS`]
+//! until the logger answers, as a mismatched rate is the most common reason a field
+//! connection fails. Connections use
+//! the `[network]` connect and read timeouts; a logger that asks for more time with a Please
+//! Wait message gets it.
+//!
+//! Loggers at the same address share one connection for as long as any of them is in use,
+//! so a sequence of operations (or several loggers behind one radio base) doesn't reopen the
+//! port each time. An idle connection is kept alive with Hello messages, and one that drops
+//! is re-dialed and the interrupted request repeated, with the `[network]` retries and
+//! backoff.
+//!
+//! Requests stop with [`Error::Cancelled`] when the operation is cancelled (see
+//! [`crate::cancel`]); the link is closed, and a file only partly sent is deleted.
+
+use crate::cancel;
+use crate::network::NetworkSettings;
+use crate::pakbus::{self, FileControl, FileReceive, FileSend, GetValues, Packet, Protocol, Value, ValueKind};
+use crate::station::Station;
+use crate::Error;
+use std::fmt;
+use std::io::{self, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Baud rate of serial connections unless the address gives one.
+pub const DEFAULT_BAUD: u32 = 115_200;
+/// Baud rates tried, in order, for `serial:PORT@auto`.
+pub const PROBE_BAUDS: [u32; 3] = [115_200, 38_400, 9_600];
+/// Time a logger gets to answer at each probed baud rate.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where a logger is reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Tcp { host: String, port: u16 },
+    /// `baud` is `None` for `@auto`: detect it when the port is opened.
+    Serial { port: String, baud: Option<u32> },
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let invalid = || format!("'{}' is not a logger address like tcp:10.0.0.5:6785 or serial:/dev/ttyUSB0@115200", text);
+        let tcp = |address: &str| -> Result<Endpoint, String> {
+            let (host, port) = match address.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+                None => (address, pakbus::TCP_PORT),
+            };
+            if host.is_empty() {
+                return Err(invalid());
+            }
+            Ok(Endpoint::Tcp { host: host.trim_matches(['[', ']']).to_string(), port })
+        };
+        let serial = |address: &str| -> Result<Endpoint, String> {
+            let (port, baud) = match address.rsplit_once('@') {
+                Some((port, "auto")) => (port, None),
+                Some((port, baud)) => (port, Some(baud.parse().map_err(|_| invalid())?)),
+                None => (address, Some(DEFAULT_BAUD)),
+            };
+            if port.is_empty() {
+                return Err(invalid());
+            }
+            Ok(Endpoint::Serial { port: port.to_string(), baud })
+        };
+        let com_port = text.len() > 3
+            && text[..3].eq_ignore_ascii_case("COM")
+            && text[3..].split('@').next().is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+        if let Some(address) = text.strip_prefix("tcp:") {
+            tcp(address)
+        } else if let Some(address) = text.strip_prefix("serial:") {
+            serial(address)
+        } else if text.starts_with("/dev/") || com_port {
+            serial(text)
+        } else if text.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+            tcp(text)
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Tcp { host, port } => write!(f, "tcp:{}:{}", host, port),
+            Endpoint::Serial { port, baud: Some(baud) } => write!(f, "serial:{}@{}", port, baud),
+            Endpoint::Serial { port, baud: None } => write!(f, "serial:{}@auto", port),
+        }
+    }
+}
+
+/// An open serial port or TCP connection.
+pub(crate) trait Link: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Link for T {}
+
+/// Opens `endpoint`, detecting the baud rate at which the logger at PakBus `address` answers
+/// if the endpoint leaves it to `auto`. Returns the link and the endpoint as opened.
+pub(crate) fn open(endpoint: &Endpoint, network: &NetworkSettings, address: u16) -> Result<(Box<dyn Link>, Endpoint), Error> {
+    let unreachable = |e: &dyn fmt::Display| Error::Logger(format!("cannot open {}: {}", endpoint, e));
+    match endpoint {
+        Endpoint::Tcp { host, port } => {
+            let addresses: Vec<_> = (host.as_str(), *port).to_socket_addrs().map_err(|e| unreachable(&e))?.collect();
+            let mut last_error = io::Error::new(ErrorKind::NotFound, "host has no addresses");
+            for socket in addresses {
+                match TcpStream::connect_timeout(&socket, network.connect_timeout()) {
+                    Ok(stream) => {
+                        stream.set_read_timeout(Some(network.read_timeout()))?;
+                        stream.set_nodelay(true)?;
+                        return Ok((Box::new(stream), endpoint.clone()));
+                    }
+                    Err(e) => last_error = e,
+                }
+            }
+            Err(unreachable(&last_error))
+        }
+        Endpoint::Serial { port, baud: Some(baud) } => {
+            let link = serialport::new(port.as_str(), *baud).timeout(network.read_timeout()).open().map_err(|e| unreachable(&e))?;
+            Ok((Box::new(link), endpoint.clone()))
+        }
+        Endpoint::Serial { port, baud: None } => {
+            for baud in PROBE_BAUDS {
+                let mut link = serialport::new(port.as_str(), baud)
+                    .timeout(PROBE_TIMEOUT.min(network.read_timeout()))
+                    .open()
+                    .map_err(|e| unreachable(&e))?;
+                if answers_hello(&mut link, address)? {
+                    log::info!("PakBus address {} answers on {} at {} baud", address, port, baud);
+                    link.set_timeout(network.read_timeout()).map_err(|e| unreachable(&e))?;
+                    return Ok((Box::new(link), Endpoint::Serial { port: port.clone(), baud: Some(baud) }));
+                }
+                log::debug!("No answer on {} at {} baud", port, baud);
+            }
+            let rates: Vec<String> = PROBE_BAUDS.iter().map(u32::to_string).collect();
+            Err(Error::Logger(format!(
+                "no PakBus logger at address {} answers on {} at {} baud; check the cable, the logger's port settings and its PakBus address",
+                address,
+                port,
+                rates.join(", ")
+            )))
+        }
+    }
+}
+
+/// Whether the logger at `address` answers a Hello on `link` before it times out.
+fn answers_hello(link: &mut dyn Link, address: u16) -> Result<bool, Error> {
+    let hello = Packet {
+        destination: address,
+        source: pakbus::OUR_ADDRESS,
+        protocol: Protocol::PakCtrl,
+        message_type: pakbus::HELLO,
+        transaction: 0xFF,
+        body: hello_body(),
+    };
+    // Extra sync bytes wake up a link in power-saving mode.
+    link.write_all(&[pakbus::SYNC; 4])?;
+    link.write_all(&hello.encode())?;
+    link.flush()?;
+    let mut reader = BufReader::new(link);
+    loop {
+        match pakbus::read_frame(&mut reader) {
+            // At the wrong rate, bytes arrive garbled and fail the signature check.
+            Ok(frame) => match Packet::decode(&frame) {
+                Ok(response) if response.source == address && response.message_type == pakbus::response_to(pakbus::HELLO) => {
+                    return Ok(true)
+                }
+                _ => continue,
+            },
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => return Ok(false),
+            Err(e) => return Err(Error::Io(e)),
+        }
+    }
+}
+
+/// Idle time after which a connection checks its link with a Hello, so modems and NAT
+/// gateways don't drop it between operations.
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Errors after which the link is gone and has to be re-dialed.
+fn is_dropped(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::UnexpectedEof
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+            | ErrorKind::NotConnected
+    )
+}
+
+/// A serial port or TCP connection shared by every [`Logger`] reached through it: several
+/// operations on one logger, or several loggers behind one radio base or serial port.
+/// Requests take turns; responses are told apart by PakBus address and transaction number.
+struct Connection {
+    endpoint: Endpoint,
+    /// `endpoint` as last opened, with the detected baud rate.
+    dialed: Option<Endpoint>,
+    network: NetworkSettings,
+    /// `None` until dialed, and after the link dropped.
+    link: Option<BufReader<Box<dyn Link>>>,
+    transaction: u8,
+    last_used: Instant,
+    /// PakBus address of the last logger talked to, for keepalives.
+    last_address: u16,
+}
+
+/// Live connections by endpoint. A connection closes when its last `Logger` is dropped.
+static CONNECTIONS: Mutex<Vec<(Endpoint, Weak<Mutex<Connection>>)>> = Mutex::new(Vec::new());
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The live connection to `endpoint`, or a new one (not dialed yet) with a keepalive thread.
+fn shared_connection(endpoint: &Endpoint, network: &NetworkSettings) -> Arc<Mutex<Connection>> {
+    let mut connections = lock(&CONNECTIONS);
+    connections.retain(|(_, connection)| connection.strong_count() > 0);
+    if let Some(connection) = connections.iter().find(|(e, _)| e == endpoint).and_then(|(_, c)| c.upgrade()) {
+        log::debug!("Reusing the connection to {}", endpoint);
+        return connection;
+    }
+    let connection = Arc::new(Mutex::new(Connection {
+        endpoint: endpoint.clone(),
+        dialed: None,
+        network: network.clone(),
+        link: None,
+        transaction: 0,
+        last_used: Instant::now(),
+        last_address: pakbus::DEFAULT_ADDRESS,
+    }));
+    connections.push((endpoint.clone(), Arc::downgrade(&connection)));
+    let weak = Arc::downgrade(&connection);
+    thread::spawn(move || keepalive(weak));
+    connection
+}
+
+/// Sends a Hello on an idle connection every [`KEEPALIVE_INTERVAL`] until it is closed.
+fn keepalive(connection: Weak<Mutex<Connection>>) {
+    loop {
+        thread::sleep(Duration::from_secs(1));
+        let Some(connection) = connection.upgrade() else { return };
+        // A busy connection needs no keepalive. Waiting for it would also keep it (and a serial
+        // port) open after its last logger is dropped.
+        let mut connection = match connection.try_lock() {
+            Ok(connection) => connection,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => continue,
+        };
+        if connection.link.is_none() || connection.last_used.elapsed() < KEEPALIVE_INTERVAL {
+            continue;
+        }
+        let address = connection.last_address;
+        log::debug!("Keepalive to PakBus address {} on {}", address, connection.endpoint);
+        if let Err(e) = connection.exchange(address, Protocol::PakCtrl, pakbus::HELLO, &hello_body(), false, &mut (0, 0)) {
+            // The next request re-dials.
+            log::info!("Keepalive on {} failed: {}", connection.endpoint, e);
+            connection.link = None;
+        }
+    }
+}
+
+/// Body of a Hello: not a router, hop metric 2 (a link of up to 5 s), verify the link every
+/// 30 minutes.
+fn hello_body() -> Vec<u8> {
+    let mut body = vec![0, 2];
+    body.extend(1800u16.to_be_bytes());
+    body
+}
+
+impl Connection {
+    /// Opens the link, at the baud rate detected before if there was one.
+    fn dial(&mut self, address: u16) -> Result<(), Error> {
+        let endpoint = self.dialed.as_ref().unwrap_or(&self.endpoint);
+        log::info!("Opening {}", endpoint);
+        let (link, dialed) = open(endpoint, &self.network, address)?;
+        self.link = Some(BufReader::new(link));
+        self.dialed = Some(dialed);
+        Ok(())
+    }
+
+    /// Sends a request to `address` and returns the body of its response, dialing first if
+    /// the link is not open. Adds the bytes moved to `traffic` (sent, received). Fails with
+    /// `ErrorKind::Interrupted` if `cancellable` and the operation is cancelled.
+    fn exchange(&mut self, address: u16, protocol: Protocol, message_type: u8, body: &[u8], cancellable: bool, traffic: &mut (u64, u64)) -> io::Result<Vec<u8>> {
+        if self.link.is_none() {
+            self.dial(address).map_err(|e| io::Error::new(ErrorKind::NotConnected, e.to_string()))?;
+        }
+        let link = self.link.as_mut().expect("link was just opened");
+        self.transaction = self.transaction.wrapping_add(1).max(1);
+        self.last_address = address;
+        let packet = Packet {
+            destination: address,
+            source: pakbus::OUR_ADDRESS,
+            protocol,
+            message_type,
+            transaction: self.transaction,
+            body: body.to_vec(),
+        };
+        let frame = packet.encode();
+        link.get_mut().write_all(&frame)?;
+        link.get_mut().flush()?;
+        traffic.0 += frame.len() as u64;
+        self.last_used = Instant::now();
+        loop {
+            if cancellable && cancel::is_cancelled() {
+                return Err(io::Error::new(ErrorKind::Interrupted, "cancelled"));
+            }
+            let frame = pakbus::read_frame(link)?;
+            // Unquoted and without the sync bytes, so slightly less than went over the link.
+            traffic.1 += frame.len() as u64 + 2;
+            self.last_used = Instant::now();
+            let response = match Packet::decode(&frame) {
+                Ok(response) => response,
+                Err(e) => {
+                    log::debug!("Ignoring packet: {}", e);
+                    continue;
+                }
+            };
+            if response.source != address || response.transaction != self.transaction {
+                continue;
+            }
+            if response.message_type == pakbus::PLEASE_WAIT {
+                log::debug!("Logger asked to wait for transaction {}", self.transaction);
+                continue;
+            }
+            if response.message_type == pakbus::response_to(message_type) {
+                return Ok(response.body);
+            }
+        }
+    }
+}
+
+/// A logger, reached through a connection that may be shared with other `Logger`s.
+pub struct Logger {
+    connection: Arc<Mutex<Connection>>,
+    endpoint: Endpoint,
+    address: u16,
+    security_code: u16,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+impl Logger {
+    /// Connects to `station` and checks that the logger answers. Reuses the connection to the
+    /// station's address if one is open.
+    pub fn connect(station: &Station, network: &NetworkSettings) -> Result<Logger, Error> {
+        let endpoint: Endpoint = station.address.parse().map_err(Error::Logger)?;
+        let security_code = station.resolve_security_code()?;
+        log::info!("Connecting to {} (PakBus address {})", endpoint, station.pakbus_address);
+        let connection = shared_connection(&endpoint, network);
+        let endpoint = {
+            let mut connection = lock(&connection);
+            if connection.link.is_none() {
+                connection.dial(station.pakbus_address)?;
+            }
+            connection.dialed.clone().unwrap_or(endpoint)
+        };
+        let mut logger = Logger {
+            connection,
+            endpoint,
+            address: station.pakbus_address,
+            security_code,
+            bytes_sent: 0,
+            bytes_received: 0,
+        };
+        logger.hello()?;
+        Ok(logger)
+    }
+
+    /// Where the logger was reached, with the detected baud rate for `serial:PORT@auto`.
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Bytes this logger sent and received so far, framing included.
+    pub fn traffic(&self) -> (u64, u64) {
+        (self.bytes_sent, self.bytes_received)
+    }
+
+    /// Sends a request and returns the body of its response. If the link drops, it is
+    /// re-dialed and the request repeated, as often as `[network] retries` allows.
+    fn request(&mut self, protocol: Protocol, message_type: u8, body: Vec<u8>) -> Result<Vec<u8>, Error> {
+        cancel::check()?;
+        self.transact(protocol, message_type, body, true)
+    }
+
+    /// [`Logger::request`], optionally going ahead even if the operation is cancelled (to
+    /// clean up after it).
+    fn transact(&mut self, protocol: Protocol, message_type: u8, body: Vec<u8>, cancellable: bool) -> Result<Vec<u8>, Error> {
+        let mut connection = lock(&self.connection);
+        let mut traffic = (0, 0);
+        let mut attempt = 0;
+        let result = loop {
+            match connection.exchange(self.address, protocol, message_type, &body, cancellable, &mut traffic) {
+                Err(e) if is_dropped(&e) && attempt < connection.network.retries => {
+                    attempt += 1;
+                    let delay = connection.network.backoff(attempt);
+                    log::warn!("Lost {} ({}); re-dialing in {:?} ({}/{})", self.endpoint, e, delay, attempt, connection.network.retries);
+                    connection.link = None;
+                    if cancellable && cancel::sleep(delay).is_err() {
+                        break Err(io::Error::new(ErrorKind::Interrupted, "cancelled"));
+                    }
+                    // A re-dialed link starts with a Hello, as a new one does.
+                    if message_type != pakbus::HELLO {
+                        let hello = connection.exchange(self.address, Protocol::PakCtrl, pakbus::HELLO, &hello_body(), cancellable, &mut traffic);
+                        if let Err(e) = hello {
+                            if !is_dropped(&e) {
+                                break Err(e);
+                            }
+                            connection.link = None;
+                        }
+                    }
+                }
+                result => break result,
+            }
+        };
+        (self.bytes_sent, self.bytes_received) = (self.bytes_sent + traffic.0, self.bytes_received + traffic.1);
+        result.map_err(|e| {
+            if is_dropped(&e) || e.kind() == ErrorKind::Interrupted {
+                connection.link = None;
+            }
+            match e.kind() {
+                ErrorKind::Interrupted => Error::Cancelled,
+                ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::UnexpectedEof => Error::Logger(format!(
+                    "no response from PakBus address {} on {} ({})",
+                    self.address,
+                    self.endpoint,
+                    if e.kind() == ErrorKind::UnexpectedEof { "connection closed" } else { "timed out" }
+                )),
+                ErrorKind::NotConnected => Error::Logger(e.to_string()),
+                _ => Error::Io(e),
+            }
+        })
+    }
+
+    /// Checks that the logger is there.
+    pub fn hello(&mut self) -> Result<(), Error> {
+        self.request(Protocol::PakCtrl, pakbus::HELLO, hello_body())?;
+        Ok(())
+    }
+
+    /// Reads the logger's clock.
+    pub fn clock(&mut self) -> Result<std::time::SystemTime, Error> {
+        let mut body = self.security_code.to_be_bytes().to_vec();
+        // No adjustment: seconds and nanoseconds of zero.
+        body.extend([0; 8]);
+        let body = self.request(Protocol::Bmp5, pakbus::CLOCK, body)?;
+        match body.split_first() {
+            Some((&pakbus::RESPONSE_OK, time)) => {
+                pakbus::decode_nsec(time).ok_or_else(|| Error::Logger("reading the clock: short response".to_string()))
+            }
+            Some((&code, _)) => Err(Error::Logger(format!("reading the clock: {}", pakbus::describe_response(code)))),
+            None => Err(Error::Logger("reading the clock: empty response".to_string())),
+        }
+    }
+
+    /// Reads `swath` values of `table.field` as `kind`.
+    pub fn get_values(&mut self, table: &str, field: &str, kind: ValueKind, swath: u16) -> Result<Vec<Value>, Error> {
+        let request = GetValues { security_code: self.security_code, table: table.to_string(), kind, field: field.to_string(), swath };
+        let body = self.request(Protocol::Bmp5, pakbus::GET_VALUES, request.encode())?;
+        pakbus::decode_values(&body, kind, swath)
+            .map_err(|code| Error::Logger(format!("reading {}.{}: {}", table, field, pakbus::describe_response(code))))
+    }
+
+    /// Reads one value of `table.field`; `None` if the logger has no such field.
+    pub fn value(&mut self, table: &str, field: &str, kind: ValueKind) -> Result<Option<Value>, Error> {
+        let request = GetValues { security_code: self.security_code, table: table.to_string(), kind, field: field.to_string(), swath: 1 };
+        let body = self.request(Protocol::Bmp5, pakbus::GET_VALUES, request.encode())?;
+        match pakbus::decode_values(&body, kind, 1) {
+            Ok(values) => Ok(values.into_iter().next()),
+            Err(pakbus::RESPONSE_INVALID_NAME) => Ok(None),
+            Err(code) => Err(Error::Logger(format!("reading {}.{}: {}", table, field, pakbus::describe_response(code)))),
+        }
+    }
+
+    /// Writes `data` to `file_name` on the logger (e.g. `CPU:soil.cr1x`), in fragments. If the
+    /// operation is cancelled part way, the partial file is deleted.
+    pub fn send_file(&mut self, file_name: &str, data: &[u8]) -> Result<(), Error> {
+        let fragments: Vec<&[u8]> = if data.is_empty() { vec![data] } else { data.chunks(pakbus::FILE_FRAGMENT).collect() };
+        let mut offset = 0u32;
+        for (i, fragment) in fragments.iter().enumerate() {
+            if cancel::is_cancelled() {
+                if i > 0 {
+                    self.delete_partial(file_name);
+                }
+                return Err(Error::Cancelled);
+            }
+            let request = FileSend {
+                security_code: self.security_code,
+                file_name: file_name.to_string(),
+                close: i + 1 == fragments.len(),
+                offset,
+                data: fragment.to_vec(),
+            };
+            let body = match self.request(Protocol::Bmp5, pakbus::FILE_SEND, request.encode()) {
+                Err(Error::Cancelled) => {
+                    self.delete_partial(file_name);
+                    return Err(Error::Cancelled);
+                }
+                result => result?,
+            };
+            match body.first() {
+                Some(&pakbus::RESPONSE_OK) => {}
+                Some(&code) => {
+                    return Err(Error::Logger(format!("sending {}: {}", file_name, pakbus::describe_response(code))))
+                }
+                None => return Err(Error::Logger(format!("sending {}: empty response", file_name))),
+            }
+            offset += fragment.len() as u32;
+            log::debug!("Sent {} of {} bytes of {}", offset, data.len(), file_name);
+        }
+        Ok(())
+    }
+
+    /// Reads `file_name` from the logger (e.g. `CPU:soil.cr1x`), in fragments.
+    pub fn receive_file(&mut self, file_name: &str) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        loop {
+            let request = FileReceive {
+                security_code: self.security_code,
+                file_name: file_name.to_string(),
+                offset: data.len() as u32,
+                swath: pakbus::FILE_FRAGMENT as u16,
+            };
+            let body = self.request(Protocol::Bmp5, pakbus::FILE_RECEIVE, request.encode())?;
+            match body.as_slice() {
+                [pakbus::RESPONSE_OK, _, _, _, _, fragment @ ..] => {
+                    data.extend(fragment);
+                    log::debug!("Received {} bytes of {}", data.len(), file_name);
+                    if fragment.len() < pakbus::FILE_FRAGMENT {
+                        return Ok(data);
+                    }
+                }
+                [pakbus::RESPONSE_OK, ..] => return Err(Error::Logger(format!("receiving {}: short response", file_name))),
+                [code, ..] => return Err(Error::Logger(format!("receiving {}: {}", file_name, pakbus::describe_response(*code)))),
+                [] => return Err(Error::Logger(format!("receiving {}: empty response", file_name))),
+            }
+        }
+    }
+
+    /// Deletes what a cancelled [`Logger::send_file`] left of `file_name`, if the logger lets us.
+    fn delete_partial(&mut self, file_name: &str) {
+        log::info!("Cancelled; deleting the partly sent {}", file_name);
+        let request = FileControl { security_code: self.security_code, file_name: file_name.to_string(), command: pakbus::FILE_DELETE };
+        match self.transact(Protocol::Bmp5, pakbus::FILE_CONTROL, request.encode(), false) {
+            Ok(body) if body.first() == Some(&pakbus::RESPONSE_OK) => {}
+            Ok(body) => log::warn!("Could not delete {}: {}", file_name, pakbus::describe_response(body.first().copied().unwrap_or(0xFF))),
+            Err(e) => log::warn!("Could not delete {}: {}", file_name, e),
+        }
+    }
+
+    /// Runs File Control `command` on `file_name`. Returns the seconds the logger asks to be
+    /// left alone for (while it compiles, say).
+    pub fn control_file(&mut self, file_name: &str, command: u8) -> Result<u16, Error> {
+        let request = FileControl { security_code: self.security_code, file_name: file_name.to_string(), command };
+        let body = self.request(Protocol::Bmp5, pakbus::FILE_CONTROL, request.encode())?;
+        match body.as_slice() {
+            [pakbus::RESPONSE_OK, high, low, ..] => Ok(u16::from_be_bytes([*high, *low])),
+            [pakbus::RESPONSE_OK] => Ok(0),
+            [code, ..] => Err(Error::Logger(format!("{}: {}", file_name, pakbus::describe_response(*code)))),
+            [] => Err(Error::Logger(format!("{}: empty response", file_name))),
+        }
+    }
+}
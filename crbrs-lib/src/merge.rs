@@ -0,0 +1,157 @@
+// FILE: crbrs-lib/src/merge.rs
+
+//! `crbrs data merge`: combines TOA5 files of one table, e.g. successive collections from a
+//! station, into one file sorted by timestamp, and reports gaps in the record interval.
+//!
+//! Records are keyed by timestamp. Overlapping collections repeat records; identical repeats
+//! are dropped, and a timestamp that appears with different values (e.g. after the logger
+//! clock was set back) keeps the first file's record and counts as a conflict. The merged
+//! file takes its environment line from the file with the latest record, so it names the
+//! program that is currently running.
+
+use crate::clock::parse_logger_time;
+use crate::timezone::Retime;
+use crate::toa5::Toa5;
+use crate::{Error, Settings};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A stretch without records.
+#[derive(Debug, Clone, Serialize)]
+pub struct Gap {
+    /// Last record before the gap.
+    pub after: String,
+    /// First record after it.
+    pub before: String,
+    /// Records expected in between.
+    pub missing: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeReport {
+    pub table: String,
+    pub records: usize,
+    /// Repeated records dropped.
+    pub duplicates: usize,
+    /// Timestamps found with different values; the first file's record was kept.
+    pub conflicts: usize,
+    /// Expected time between records, given or the most common one.
+    pub interval_ms: Option<i64>,
+    pub gaps: Vec<Gap>,
+}
+
+/// Milliseconds in an interval like `15s`, `30 min`, `1h` or `1d`.
+pub fn parse_interval(text: &str) -> Result<i64, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: i64 = number.parse().map_err(|_| format!("'{}' is not an interval like 15s, 30min or 1h", text))?;
+    let unit_ms = match unit.trim().to_ascii_lowercase().as_str() {
+        "ms" | "msec" => 1,
+        "s" | "sec" => 1000,
+        "m" | "min" => 60_000,
+        "h" | "hr" => 3_600_000,
+        "d" | "day" => 86_400_000,
+        _ => return Err(format!("'{}' is not an interval like 15s, 30min or 1h", text)),
+    };
+    if number == 0 {
+        return Err("the interval cannot be 0".to_string());
+    }
+    Ok(number * unit_ms)
+}
+
+/// `ms` in the largest unit that divides it, e.g. `15min`.
+pub fn format_interval(ms: i64) -> String {
+    [(86_400_000, "d"), (3_600_000, "h"), (60_000, "min"), (1000, "s")]
+        .iter()
+        .find(|(unit, _)| ms % unit == 0)
+        .map_or_else(|| format!("{}ms", ms), |(unit, name)| format!("{}{}", ms / unit, name))
+}
+
+/// Most common time between consecutive timestamps.
+fn usual_interval(times: &[i64]) -> Option<i64> {
+    let mut counts: BTreeMap<i64, usize> = BTreeMap::new();
+    for pair in times.windows(2) {
+        *counts.entry(pair[1] - pair[0]).or_default() += 1;
+    }
+    counts.into_iter().max_by_key(|(interval, count)| (*count, std::cmp::Reverse(*interval))).map(|(interval, _)| interval)
+}
+
+/// Merges `files`, which must all hold the same table with the same fields. `interval`
+/// overrides the inferred record interval for the gap report.
+pub fn merge(files: &[Toa5], interval: Option<i64>) -> Result<(Toa5, MergeReport), String> {
+    let first = files.first().ok_or("No files to merge")?;
+    let Some(timestamp) = first.field("TIMESTAMP") else {
+        return Err("The files have no TIMESTAMP field".to_string());
+    };
+    for (i, file) in files.iter().enumerate().skip(1) {
+        if !file.table().eq_ignore_ascii_case(first.table()) {
+            return Err(format!("file {} holds table '{}', not '{}'", i + 1, file.table(), first.table()));
+        }
+        if file.fields.iter().map(|f| &f.name).ne(first.fields.iter().map(|f| &f.name)) {
+            return Err(format!("file {} has different fields than file 1; was the program changed?", i + 1));
+        }
+    }
+
+    let mut by_time: BTreeMap<i64, &Vec<String>> = BTreeMap::new();
+    let (mut duplicates, mut conflicts) = (0, 0);
+    let mut latest: Option<(i64, &Toa5)> = None;
+    for (i, file) in files.iter().enumerate() {
+        for record in &file.records {
+            let time = parse_logger_time(&record[timestamp])
+                .ok_or_else(|| format!("file {}: '{}' is not a timestamp", i + 1, record[timestamp]))?;
+            if latest.is_none_or(|(t, _)| time >= t) {
+                latest = Some((time, file));
+            }
+            match by_time.get(&time) {
+                Some(kept) if *kept == record => duplicates += 1,
+                Some(_) => conflicts += 1,
+                None => {
+                    by_time.insert(time, record);
+                }
+            }
+        }
+    }
+
+    let times: Vec<i64> = by_time.keys().copied().collect();
+    let interval = interval.or_else(|| usual_interval(&times));
+    let mut gaps = Vec::new();
+    if let Some(interval) = interval {
+        for pair in times.windows(2) {
+            let missing = (pair[1] - pair[0]) / interval - 1;
+            if missing > 0 {
+                gaps.push(Gap { after: by_time[&pair[0]][timestamp].clone(), before: by_time[&pair[1]][timestamp].clone(), missing });
+            }
+        }
+    }
+    let environment = latest.map_or(&first.environment, |(_, file)| &file.environment).clone();
+    let merged = Toa5 { environment, fields: first.fields.clone(), records: by_time.into_values().cloned().collect() };
+    let report = MergeReport {
+        table: merged.table().to_string(),
+        records: merged.records.len(),
+        duplicates,
+        conflicts,
+        interval_ms: interval,
+        gaps,
+    };
+    Ok((merged, report))
+}
+
+/// Merges the TOA5 files at `paths` into `output`, converting the merged timestamps with
+/// `retime` if given. Gaps are reported with the timestamps as written.
+pub fn merge_files(
+    paths: &[PathBuf],
+    output: &Path,
+    interval: Option<i64>,
+    retime: Option<(&Retime, &Settings)>,
+) -> Result<MergeReport, Error> {
+    let files = paths.iter().map(|path| Toa5::read(path)).collect::<Result<Vec<_>, _>>()?;
+    let (mut merged, report) = merge(&files, interval).map_err(Error::Data)?;
+    if let Some((retime, settings)) = retime {
+        retime.apply(&mut merged, settings)?;
+    }
+    std::fs::write(output, merged.to_text())?;
+    log::info!("Merged {} files into {:?} ({} records)", paths.len(), output, report.records);
+    Ok(report)
+}
@@ -0,0 +1,423 @@
+// FILE: crbrs-lib/src/metadata.rs
+
+use crate::project::{MetadataSettings, Project};
+use crate::symbol_index::WorkspaceIndex;
+use crate::syntax::tables::{self, FieldInfo, TableInfo};
+use crate::Error;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Output format of `crbrs metadata export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataFormat {
+    Csv,
+    Json,
+    /// Ecological Metadata Language 2.2 (`dataTable`/`attributeList` elements).
+    Eml,
+}
+
+impl FromStr for MetadataFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(MetadataFormat::Csv),
+            "json" => Ok(MetadataFormat::Json),
+            "eml" | "xml" => Ok(MetadataFormat::Eml),
+            other => Err(format!("unknown metadata format '{}' (expected csv, json or eml)", other)),
+        }
+    }
+}
+
+/// One row of the data dictionary.
+#[derive(Debug, Clone, Serialize)]
+pub struct DictionaryEntry {
+    pub table: String,
+    pub field: String,
+    pub units: String,
+    pub processing: String,
+    pub data_type: String,
+    pub interval: String,
+    pub source: String,
+    pub description: String,
+}
+
+/// Builds the data dictionary for a program: one entry per stored field, preceded by the
+/// `TIMESTAMP` and `RECORD` columns every table carries.
+pub fn data_dictionary(source: &str) -> Vec<DictionaryEntry> {
+    let program = crate::syntax::parse(source);
+    if !program.errors.is_empty() {
+        log::warn!(
+            "Program has {} syntax error(s); the data dictionary may be incomplete.",
+            program.errors.len()
+        );
+    }
+    tables::tables(source, &program).iter().flat_map(table_entries).collect()
+}
+
+fn table_entries(table: &TableInfo) -> Vec<DictionaryEntry> {
+    let interval = table.interval.clone().unwrap_or_else(|| "event".to_string());
+    let fixed = |field: &str, units: &str, description: &str| DictionaryEntry {
+        table: table.name.clone(),
+        field: field.to_string(),
+        units: units.to_string(),
+        processing: String::new(),
+        data_type: String::new(),
+        interval: interval.clone(),
+        source: String::new(),
+        description: description.to_string(),
+    };
+    let mut entries = vec![
+        fixed("TIMESTAMP", "TS", "Record timestamp (logger clock)"),
+        fixed("RECORD", "RN", "Record number"),
+    ];
+    entries.extend(table.fields.iter().map(|f| DictionaryEntry {
+        table: table.name.clone(),
+        field: f.name.clone(),
+        units: f.units.clone().unwrap_or_default(),
+        processing: f.processing.clone(),
+        data_type: f.data_type.clone().unwrap_or_default(),
+        interval: interval.clone(),
+        source: f.source.clone(),
+        description: f.description.clone().unwrap_or_else(|| format!("{} of {} ({})", f.processing, f.source, f.instruction)),
+    }));
+    entries
+}
+
+/// Who EML output credits for the dataset. EML requires both; a placeholder to fill in before
+/// publishing stands in for any not given.
+#[derive(Debug, Clone, Default)]
+pub struct Parties {
+    /// The person or organization that made the dataset.
+    pub creator: Option<String>,
+    /// Who to ask about it.
+    pub contact: Option<String>,
+}
+
+/// Renders the data dictionary of `source` in `format`. `title` names the dataset and
+/// `parties` credits it in EML output.
+pub fn export(source: &str, format: MetadataFormat, title: &str, parties: &Parties) -> Result<String, Error> {
+    let entries = data_dictionary(source);
+    match format {
+        MetadataFormat::Csv => Ok(to_csv(&entries)),
+        MetadataFormat::Json => serde_json::to_string_pretty(&entries).map_err(|e| Error::Metadata(e.to_string())),
+        MetadataFormat::Eml => Ok(to_eml(&entries, title, parties)),
+    }
+}
+
+/// Reads `input_file` and renders its data dictionary.
+pub fn export_file(input_file: &Path, format: MetadataFormat, parties: &Parties) -> Result<String, Error> {
+    let source = std::fs::read_to_string(input_file)?;
+    let title = input_file.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    export(&source, format, &title, parties)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(entries: &[DictionaryEntry]) -> String {
+    let mut out = String::from("table,field,units,processing,data_type,interval,source,description\n");
+    for e in entries {
+        let row = [&e.table, &e.field, &e.units, &e.processing, &e.data_type, &e.interval, &e.source, &e.description];
+        out.push_str(&row.iter().map(|v| csv_field(v)).collect::<Vec<_>>().join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// How EML describes a field's values.
+enum Scale {
+    DateTime,
+    Number { unit: String, number_type: &'static str },
+    Text,
+    Boolean,
+}
+
+fn scale(entry: &DictionaryEntry) -> Scale {
+    match (entry.field.as_str(), entry.data_type.to_ascii_lowercase().as_str()) {
+        ("TIMESTAMP", _) => Scale::DateTime,
+        ("RECORD", _) => Scale::Number { unit: String::new(), number_type: "whole" },
+        (_, "string") => Scale::Text,
+        (_, "boolean" | "bool8") => Scale::Boolean,
+        (_, data_type) => {
+            let number_type = match data_type {
+                "long" | "int4" => "integer",
+                "uint2" | "uint4" => "whole",
+                _ => "real",
+            };
+            Scale::Number { unit: entry.units.clone(), number_type }
+        }
+    }
+}
+
+/// The id a custom unit is declared under: an XML name, so units like `W/m^2` need their
+/// other characters replaced.
+fn unit_id(units: &str) -> String {
+    let id: String = units.chars().map(|c| if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') { c } else { '_' }).collect();
+    if id.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        id
+    } else {
+        format!("_{}", id)
+    }
+}
+
+/// A `creator` or `contact` element.
+fn party(out: &mut String, element: &str, name: Option<&str>, option: &str) {
+    let _ = writeln!(out, "    <{}>", element);
+    match name {
+        Some(name) => {
+            let _ = writeln!(out, "      <organizationName>{}</organizationName>", xml_escape(name));
+        }
+        None => {
+            log::warn!("No {} given for the EML document; fill in the placeholder or pass {}", element, option);
+            let _ = writeln!(out, "      <positionName>Not specified (set with {})</positionName>", option);
+        }
+    }
+    let _ = writeln!(out, "    </{}>", element);
+}
+
+fn to_eml(entries: &[DictionaryEntry], title: &str, parties: &Parties) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(
+        "<eml:eml xmlns:eml=\"https://eml.ecoinformatics.org/eml-2.2.0\" xmlns:stmml=\"http://www.xml-cml.org/schema/stmml-1.2\" \
+         packageId=\"crbrs-export\" system=\"crbrs\">\n",
+    );
+    out.push_str("  <dataset>\n");
+    let _ = writeln!(out, "    <title>Data tables of {}</title>", xml_escape(title));
+    party(&mut out, "creator", parties.creator.as_deref(), "--creator");
+    party(&mut out, "contact", parties.contact.as_deref(), "--contact");
+
+    let mut tables: Vec<&str> = Vec::new();
+    for e in entries {
+        if !tables.contains(&e.table.as_str()) {
+            tables.push(&e.table);
+        }
+    }
+    // Custom units by id, each declared once in the unit list.
+    let mut units: BTreeMap<String, String> = BTreeMap::new();
+    for table in tables {
+        let fields: Vec<&DictionaryEntry> = entries.iter().filter(|e| e.table == table).collect();
+        out.push_str("    <dataTable>\n");
+        let _ = writeln!(out, "      <entityName>{}</entityName>", xml_escape(table));
+        let _ = writeln!(out, "      <entityDescription>Records stored every {}</entityDescription>", xml_escape(&fields[0].interval));
+        out.push_str("      <attributeList>\n");
+        for f in fields {
+            out.push_str("        <attribute>\n");
+            let _ = writeln!(out, "          <attributeName>{}</attributeName>", xml_escape(&f.field));
+            let _ = writeln!(out, "          <attributeDefinition>{}</attributeDefinition>", xml_escape(&f.description));
+            out.push_str("          <measurementScale>\n");
+            match scale(f) {
+                Scale::DateTime => out.push_str(
+                    "            <dateTime><formatString>YYYY-MM-DD hh:mm:ss</formatString></dateTime>\n",
+                ),
+                Scale::Number { unit, number_type } => {
+                    out.push_str("            <ratio>\n");
+                    if unit.is_empty() {
+                        out.push_str("              <unit><standardUnit>dimensionless</standardUnit></unit>\n");
+                    } else {
+                        let id = unit_id(&unit);
+                        let _ = writeln!(out, "              <unit><customUnit>{}</customUnit></unit>", xml_escape(&id));
+                        units.insert(id, unit);
+                    }
+                    let _ = writeln!(out, "              <numericDomain><numberType>{}</numberType></numericDomain>", number_type);
+                    out.push_str("            </ratio>\n");
+                }
+                Scale::Text => out.push_str(
+                    "            <nominal><nonNumericDomain><textDomain><definition>Text</definition></textDomain></nonNumericDomain></nominal>\n",
+                ),
+                Scale::Boolean => out.push_str(
+                    "            <nominal><nonNumericDomain><textDomain><definition>Boolean: -1 (true) or 0 (false)</definition></textDomain></nonNumericDomain></nominal>\n",
+                ),
+            }
+            out.push_str("          </measurementScale>\n");
+            out.push_str("        </attribute>\n");
+        }
+        out.push_str("      </attributeList>\n");
+        out.push_str("    </dataTable>\n");
+    }
+    out.push_str("  </dataset>\n");
+    if !units.is_empty() {
+        out.push_str("  <additionalMetadata>\n");
+        out.push_str("    <metadata>\n");
+        out.push_str("      <stmml:unitList>\n");
+        for (id, name) in &units {
+            let _ = writeln!(out, "        <stmml:unit id=\"{}\" name=\"{}\"/>", xml_escape(id), xml_escape(name));
+        }
+        out.push_str("      </stmml:unitList>\n");
+        out.push_str("    </metadata>\n");
+        out.push_str("  </additionalMetadata>\n");
+    }
+    out.push_str("</eml:eml>\n");
+    out
+}
+
+/// Tables or fields that would collide when the data of a project's programs is loaded into
+/// one database.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Collision {
+    /// The program where it is reported, relative to the project root.
+    pub file: PathBuf,
+    /// 1-based line of the table or field.
+    pub line: u32,
+    pub table: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Collision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+fn place(file: &Path, span: &crate::syntax::lexer::Span) -> String {
+    format!("{} line {}", file.display(), span.line + 1)
+}
+
+/// Fields stored twice in one table: databases, like the logger, do not tell names apart by case.
+fn duplicate_fields(file: &Path, table: &TableInfo, out: &mut Vec<Collision>) {
+    let mut seen: HashMap<String, &FieldInfo> = HashMap::new();
+    for field in &table.fields {
+        match seen.get(&field.name.to_ascii_lowercase()) {
+            Some(first) => out.push(Collision {
+                file: file.to_path_buf(),
+                line: field.span.line + 1,
+                table: table.name.clone(),
+                message: format!("table '{}' stores field '{}' twice; it is first stored on line {}", table.name, field.name, first.span.line + 1),
+            }),
+            None => {
+                seen.insert(field.name.to_ascii_lowercase(), field);
+            }
+        }
+    }
+}
+
+/// Differences between `table` (in `file`) and `reference`, the first table of that name in
+/// the project.
+fn compare(file: &Path, table: &TableInfo, reference: (&Path, &TableInfo), same_fields: bool, out: &mut Vec<Collision>) {
+    let (reference_file, reference) = reference;
+    let mut report = |line: u32, message: String| {
+        out.push(Collision { file: file.to_path_buf(), line: line + 1, table: table.name.clone(), message });
+    };
+    if table.name != reference.name {
+        report(
+            table.span.line,
+            format!("table '{}' differs only in case from '{}' in {}", table.name, reference.name, place(reference_file, &reference.span)),
+        );
+    }
+    let find = |fields: &[FieldInfo], name: &str| fields.iter().find(|f| f.name.eq_ignore_ascii_case(name)).cloned();
+    if same_fields {
+        let missing: Vec<&str> = reference.fields.iter().filter(|f| find(&table.fields, &f.name).is_none()).map(|f| f.name.as_str()).collect();
+        let extra: Vec<&str> = table.fields.iter().filter(|f| find(&reference.fields, &f.name).is_none()).map(|f| f.name.as_str()).collect();
+        if !missing.is_empty() || !extra.is_empty() {
+            let mut parts = Vec::new();
+            if !missing.is_empty() {
+                parts.push(format!("missing {}", missing.join(", ")));
+            }
+            if !extra.is_empty() {
+                parts.push(format!("extra {}", extra.join(", ")));
+            }
+            report(
+                table.span.line,
+                format!("table '{}' has other fields than in {}: {}", table.name, place(reference_file, &reference.span), parts.join("; ")),
+            );
+        }
+    }
+    for field in &table.fields {
+        let Some(other) = find(&reference.fields, &field.name) else { continue };
+        let differs = |a: &Option<String>, b: &Option<String>| matches!((a, b), (Some(a), Some(b)) if !a.eq_ignore_ascii_case(b));
+        let mut conflicts = Vec::new();
+        if differs(&field.data_type, &other.data_type) {
+            conflicts.push(format!("type {} here but {}", field.data_type.as_deref().unwrap_or_default(), other.data_type.as_deref().unwrap_or_default()));
+        }
+        if differs(&field.units, &other.units) {
+            conflicts.push(format!("units {} here but {}", field.units.as_deref().unwrap_or_default(), other.units.as_deref().unwrap_or_default()));
+        }
+        if field.processing != other.processing {
+            conflicts.push(format!("processing {} here but {}", field.processing, other.processing));
+        }
+        if !conflicts.is_empty() {
+            report(
+                field.span.line,
+                format!(
+                    "field '{}' of table '{}' has {} in {}",
+                    field.name,
+                    table.name,
+                    conflicts.join(", "),
+                    place(reference_file, &other.span)
+                ),
+            );
+        }
+    }
+}
+
+/// The collisions between the tables of `programs` (each program's path and tables): fields
+/// stored twice in a table, and tables stored by several programs under names that differ
+/// only in case, with other fields (unless `settings.same_fields` is off), or with fields of
+/// another type, unit or processing. Each table is compared with the first program (in
+/// path order) storing it.
+pub fn collisions(programs: &[(PathBuf, Vec<TableInfo>)], settings: &MetadataSettings) -> Vec<Collision> {
+    let ignored = |name: &str| settings.ignore_tables.iter().any(|t| t.eq_ignore_ascii_case(name));
+    let mut programs: Vec<&(PathBuf, Vec<TableInfo>)> = programs.iter().collect();
+    programs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut out = Vec::new();
+    let mut by_name: BTreeMap<String, Vec<(&Path, &TableInfo)>> = BTreeMap::new();
+    for (file, tables) in programs {
+        for table in tables.iter().filter(|t| !ignored(&t.name)) {
+            duplicate_fields(file, table, &mut out);
+            by_name.entry(table.name.to_ascii_lowercase()).or_default().push((file, table));
+        }
+    }
+    for stored in by_name.values() {
+        let reference = stored[0];
+        for (file, table) in &stored[1..] {
+            compare(file, table, reference, settings.same_fields, &mut out);
+        }
+    }
+    out.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    out
+}
+
+/// [`collisions`] between the programs of the project `path` is in (or, outside a project,
+/// under `path`), with the project's `[metadata]` settings. Tables are read from the
+/// workspace symbol index of the project root (see [`crate::symbol_index`]), which is
+/// brought up to date first, so only programs changed since the last check are parsed.
+pub fn check_project(path: &Path) -> Result<(PathBuf, Vec<Collision>), Error> {
+    let (root, settings) = match Project::find(path)? {
+        Some(project) => (project.root, project.manifest.metadata),
+        None => (std::path::absolute(path)?, MetadataSettings::default()),
+    };
+    let mut index = WorkspaceIndex::load(&root);
+    if index.revalidate()? {
+        if let Err(e) = index.save() {
+            log::warn!("Could not store the symbol index of {:?}: {}", root, e);
+        }
+    }
+    let programs: Vec<(PathBuf, Vec<TableInfo>)> = index
+        .files
+        .iter()
+        .map(|(file, indexed)| {
+            let file = Path::new(file);
+            (file.strip_prefix(&root).unwrap_or(file).to_path_buf(), indexed.tables.clone())
+        })
+        .collect();
+    log::debug!("Checking the tables of {} program(s) under {:?}", programs.len(), root);
+    Ok((root, collisions(&programs, &settings)))
+}
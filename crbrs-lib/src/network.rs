@@ -0,0 +1,408 @@
+// FILE: crbrs-lib/src/network.rs
+
+//! Timeouts and retries shared by every network operation (manifest fetch, compiler
+//! downloads, logger TCP connections), configured through the `[network]` settings section.
+//!
+//! reqwest's defaults can leave a request hanging for minutes on a flaky field hotspot; here
+//! every request gets a connect timeout and an overall timeout, and transient failures
+//! (timeouts, refused connections, 5xx and 429 responses) are retried with exponential backoff.
+//! Downloads and backoff delays stop early when the operation is cancelled (see [`crate::cancel`]).
+//!
+//! In offline mode (`--offline`, or `offline = true`) nothing is fetched, and the same happens
+//! when a quick probe finds the host unreachable, so a laptop on a dead field modem doesn't
+//! wait out the timeouts and retries. Fetches then fail with [`Error::Offline`], and callers
+//! fall back to what they cached (the manifest, compiler archives). Logger connections are
+//! not affected.
+//!
+//! Requests to a host listed under `[network.tokens]` carry the named secret as a bearer token
+//! (see [`crate::secret`]), e.g. for a private compiler repository. Requests to GitHub carry
+//! `GITHUB_TOKEN` (or `GH_TOKEN`) from the environment if set, for its higher rate limit.
+//!
+//! A whole classroom fetching the manifest from GitHub at once gets throttled, so rate-limit
+//! responses are retried after the delay the server asks for (`Retry-After`, or GitHub's
+//! `x-ratelimit-reset`), retry delays are jittered so clients don't retry in lockstep, and
+//! [`get_text_if_changed`] makes a conditional request that costs nothing when the cached copy
+//! is current.
+
+use crate::cancel;
+use crate::secret;
+use crate::Error;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::BuildHasher;
+use std::io::Read;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// `[network]` settings.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Time allowed to establish a connection.
+    pub connect_timeout_secs: u64,
+    /// Time allowed for a small request (e.g. the manifest) to complete once connected.
+    pub read_timeout_secs: u64,
+    /// Time allowed for a large download (compiler archives) to complete.
+    pub download_timeout_secs: u64,
+    /// Additional attempts after a transient failure.
+    pub retries: u32,
+    /// Delay before the first retry; doubled for each further retry.
+    pub backoff_ms: u64,
+    /// URL prefix rewrites (e.g. github.com to an internal mirror); the first match applies.
+    pub rewrites: Vec<UrlRewrite>,
+    /// Never fetch anything; use cached copies only.
+    pub offline: bool,
+    /// How long after it was last known to be current the cached manifest stands in for one
+    /// that could not be fetched; 0 for no limit.
+    pub manifest_max_age_hours: u64,
+    /// Secrets sent as bearer tokens, by host (e.g. `"github.com" = "github-token"`).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tokens: BTreeMap<String, String>,
+}
+
+/// Replaces a URL prefix, like git's `url.<base>.insteadOf`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UrlRewrite {
+    pub from: String,
+    pub to: String,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        NetworkSettings {
+            connect_timeout_secs: 10,
+            read_timeout_secs: 30,
+            download_timeout_secs: 600,
+            retries: 3,
+            backoff_ms: 1000,
+            rewrites: Vec::new(),
+            offline: false,
+            manifest_max_age_hours: 168,
+            tokens: BTreeMap::new(),
+        }
+    }
+}
+
+impl NetworkSettings {
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_secs(self.read_timeout_secs)
+    }
+
+    /// `url` after applying the first matching rewrite rule.
+    pub fn rewrite(&self, url: &str) -> String {
+        match self.rewrites.iter().find(|r| !r.from.is_empty() && url.starts_with(&r.from)) {
+            Some(rule) => {
+                let rewritten = format!("{}{}", rule.to, &url[rule.from.len()..]);
+                log::info!("Rewriting {} -> {}", url, rewritten);
+                rewritten
+            }
+            None => url.to_string(),
+        }
+    }
+
+    /// Delay before retry number `attempt` (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_millis(self.backoff_ms.saturating_mul(1 << (attempt - 1).min(16)))
+    }
+}
+
+/// Offline mode for this process (`--offline`), whatever the settings say.
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Turns offline mode on for this process, without changing (or saving) the settings.
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Longest the reachability probe waits for a host.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Hosts probed by this process and whether they were reachable.
+static PROBED: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
+/// Whether a proxy is configured, in which case the proxy may reach hosts we can't.
+fn uses_proxy() -> bool {
+    ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|v| !v.is_empty()))
+}
+
+/// Whether a TCP connection to `url`'s host can be opened (name lookup included) within
+/// [`PROBE_TIMEOUT`] or the connect timeout, whichever is shorter. Probed once per host.
+fn reachable(network: &NetworkSettings, url: &reqwest::Url) -> bool {
+    let Some(host) = url.host_str() else { return true };
+    let key = format!("{}:{}", host, url.port_or_known_default().unwrap_or(0));
+    if let Some((_, reachable)) = PROBED.lock().unwrap_or_else(|p| p.into_inner()).iter().find(|(k, _)| *k == key) {
+        return *reachable;
+    }
+    let timeout = network.connect_timeout().min(PROBE_TIMEOUT);
+    let (sender, receiver) = mpsc::channel();
+    let url_for_probe = url.clone();
+    // Name lookups can't be given a timeout, so the probe runs on its own thread.
+    thread::spawn(move || {
+        let connected = url_for_probe
+            .socket_addrs(|| None)
+            .is_ok_and(|addrs| addrs.iter().any(|addr| TcpStream::connect_timeout(addr, timeout).is_ok()));
+        let _ = sender.send(connected);
+    });
+    let reachable = receiver.recv_timeout(timeout + Duration::from_millis(500)).unwrap_or(false);
+    if !reachable {
+        log::warn!("{} is unreachable; working offline", key);
+    }
+    PROBED.lock().unwrap_or_else(|p| p.into_inner()).push((key, reachable));
+    reachable
+}
+
+/// Why `url` can't be fetched now: offline mode is on, or its host is unreachable. `None`
+/// if it can.
+pub fn offline(network: &NetworkSettings, url: &str) -> Option<String> {
+    if network.offline || OFFLINE.load(Ordering::Relaxed) {
+        return Some("offline mode is on".to_string());
+    }
+    let parsed = reqwest::Url::parse(&network.rewrite(url)).ok()?;
+    if !matches!(parsed.scheme(), "http" | "https") || uses_proxy() || reachable(network, &parsed) {
+        return None;
+    }
+    Some(format!("{} is unreachable", parsed.host_str().unwrap_or_default()))
+}
+
+/// `Err(Error::Offline)` saying `what` was skipped if `url` can't be fetched now.
+fn check_online(network: &NetworkSettings, url: &str, what: &str) -> Result<(), Error> {
+    match offline(network, url) {
+        Some(reason) => Err(Error::Offline(format!("skipped {} {} ({})", what, url, reason))),
+        None => Ok(()),
+    }
+}
+
+/// A blocking HTTP client with the configured connect timeout and the given overall timeout.
+pub fn client(network: &NetworkSettings, timeout: Duration) -> Result<Client, Error> {
+    Ok(Client::builder().connect_timeout(network.connect_timeout()).timeout(timeout).build()?)
+}
+
+/// Whether retrying `error` might succeed.
+pub fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::Network(e) => {
+            e.is_timeout()
+                || e.is_connect()
+                || e.is_request()
+                || e.status().is_some_and(|s| s.is_server_error() || s.as_u16() == 429)
+        }
+        Error::RateLimited { .. } => true,
+        Error::Io(e) => matches!(
+            e.kind(),
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::ConnectionReset
+        ),
+        _ => false,
+    }
+}
+
+/// Longest wait a rate limit may ask for before giving up instead of retrying.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// `delay` plus up to half of it again, at random, so clients throttled together spread out.
+fn jitter(delay: Duration) -> Duration {
+    let fraction = (RandomState::new().hash_one(SystemTime::now()) % 1000) as f64 / 2000.0;
+    delay + delay.mul_f64(fraction)
+}
+
+/// Runs `op`, retrying transient failures according to `network`.
+pub fn with_retries<T>(network: &NetworkSettings, what: &str, mut op: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(e) if matches!(&e, Error::RateLimited { retry_after, .. } if *retry_after > MAX_RETRY_AFTER) => return Err(e),
+            Err(e) if attempt < network.retries && is_transient(&e) => {
+                attempt += 1;
+                let delay = match &e {
+                    Error::RateLimited { retry_after, .. } => (*retry_after).max(jitter(network.backoff(attempt))),
+                    _ => jitter(network.backoff(attempt)),
+                };
+                log::warn!("{} failed ({}); retry {}/{} in {:?}", what, e, attempt, network.retries, delay);
+                cancel::sleep(delay)?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Hosts that get `GITHUB_TOKEN` (the manifest and releases on GitHub).
+const GITHUB_HOSTS: [&str; 4] = ["github.com", "api.github.com", "raw.githubusercontent.com", "objects.githubusercontent.com"];
+
+/// The bearer token for `url`'s host: the secret `[network.tokens]` names for it, or for
+/// GitHub, `GITHUB_TOKEN` or `GH_TOKEN` if set.
+fn token(network: &NetworkSettings, url: &str) -> Result<Option<String>, Error> {
+    let Some(host) = reqwest::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)) else { return Ok(None) };
+    if let Some((_, name)) = network.tokens.iter().find(|(h, _)| h.eq_ignore_ascii_case(&host)) {
+        return secret::require(name).map(Some);
+    }
+    if GITHUB_HOSTS.iter().any(|h| h.eq_ignore_ascii_case(&host)) {
+        return Ok(["GITHUB_TOKEN", "GH_TOKEN"].iter().find_map(|var| std::env::var(var).ok().filter(|t| !t.is_empty())));
+    }
+    Ok(None)
+}
+
+/// How long a rate-limited `response` asks to wait, from `Retry-After` (seconds) on a 429 or
+/// 503, or from GitHub's `x-ratelimit-reset` once `x-ratelimit-remaining` is 0.
+fn rate_limit_wait(response: &reqwest::blocking::Response) -> Option<Duration> {
+    let header = |name: &str| response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+    let status = response.status().as_u16();
+    if matches!(status, 429 | 503) {
+        if let Some(secs) = header("retry-after").and_then(|v| v.parse::<u64>().ok()) {
+            return Some(Duration::from_secs(secs));
+        }
+    }
+    if matches!(status, 403 | 429) && header("x-ratelimit-remaining") == Some("0") {
+        let reset = header("x-ratelimit-reset")?.parse::<u64>().ok()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        return Some(Duration::from_secs(reset.saturating_sub(now).max(1)));
+    }
+    None
+}
+
+/// Validators of a response (`ETag`, `Last-Modified`), for a later conditional request.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Validators {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    fn of(response: &reqwest::blocking::Response) -> Validators {
+        let header = |name| response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        Validators { etag: header(reqwest::header::ETAG), last_modified: header(reqwest::header::LAST_MODIFIED) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+// reqwest drops the Authorization header when a redirect leads to another host.
+fn get(client: &Client, url: &str, token: Option<&str>, validators: &Validators) -> Result<reqwest::blocking::Response, Error> {
+    let mut request = client.get(url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    if let Some(etag) = &validators.etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+    }
+    let response = request.send()?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED && !validators.is_empty() {
+        return Ok(response);
+    }
+    if !response.status().is_success() {
+        log::error!("Request failed. Status: {:?}, URL: {}", response.status(), url);
+        if let Some(retry_after) = rate_limit_wait(&response) {
+            let host = reqwest::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)).unwrap_or_default();
+            return Err(Error::RateLimited { host, retry_after });
+        }
+        return Err(Error::Network(response.error_for_status().unwrap_err()));
+    }
+    Ok(response)
+}
+
+/// What a conditional fetch found.
+#[derive(Debug)]
+pub enum Fetched {
+    /// The document changed (or nothing was cached): its text and new validators.
+    Changed(String, Validators),
+    /// The copy the validators came from is current.
+    NotModified,
+}
+
+/// Fetches `url` (after rewrites) as text unless it is unchanged since the response
+/// `validators` came from.
+pub fn get_text_if_changed(network: &NetworkSettings, url: &str, validators: &Validators) -> Result<Fetched, Error> {
+    check_online(network, url, "fetching")?;
+    let url = &network.rewrite(url);
+    let client = client(network, network.read_timeout())?;
+    let token = token(network, url)?;
+    with_retries(network, &format!("GET {}", url), || {
+        let response = get(&client, url, token.as_deref(), validators)?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            log::info!("{} is unchanged", url);
+            return Ok(Fetched::NotModified);
+        }
+        let new_validators = Validators::of(&response);
+        Ok(Fetched::Changed(response.text()?, new_validators))
+    })
+}
+
+/// Fetches `url` (after rewrites) as text (small documents such as the manifest).
+pub fn get_text(network: &NetworkSettings, url: &str) -> Result<String, Error> {
+    check_online(network, url, "fetching")?;
+    let url = &network.rewrite(url);
+    let client = client(network, network.read_timeout())?;
+    let token = token(network, url)?;
+    with_retries(network, &format!("GET {}", url), || Ok(get(&client, url, token.as_deref(), &Validators::default())?.text()?))
+}
+
+/// Downloads `url` (after rewrites) into memory (compiler archives).
+pub fn get_bytes(network: &NetworkSettings, url: &str) -> Result<Vec<u8>, Error> {
+    check_online(network, url, "downloading")?;
+    let url = &network.rewrite(url);
+    let client = client(network, Duration::from_secs(network.download_timeout_secs))?;
+    let token = token(network, url)?;
+    with_retries(network, &format!("Download {}", url), || {
+        let mut response = get(&client, url, token.as_deref(), &Validators::default())?;
+        let mut bytes = Vec::with_capacity(response.content_length().unwrap_or(0) as usize);
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            cancel::check()?;
+            match response.read(&mut chunk)? {
+                0 => return Ok(bytes),
+                n => bytes.extend_from_slice(&chunk[..n]),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrites(rules: &[(&str, &str)]) -> NetworkSettings {
+        NetworkSettings {
+            rewrites: rules.iter().map(|(from, to)| UrlRewrite { from: from.to_string(), to: to.to_string() }).collect(),
+            ..NetworkSettings::default()
+        }
+    }
+
+    #[test]
+    fn the_first_matching_rewrite_applies() {
+        let network = rewrites(&[
+            ("https://github.com/other/", "https://elsewhere.example.org/"),
+            ("https://github.com/", "https://mirror.example.org/github/"),
+            ("https://github.com/RileyLeff/", "https://never.example.org/"),
+        ]);
+        assert_eq!(
+            network.rewrite("https://github.com/RileyLeff/crbrs/compilers.toml"),
+            "https://mirror.example.org/github/RileyLeff/crbrs/compilers.toml"
+        );
+        assert_eq!(network.rewrite("https://github.com/other/a.zip"), "https://elsewhere.example.org/a.zip");
+        assert_eq!(network.rewrite("https://example.com/a.zip"), "https://example.com/a.zip");
+    }
+
+    #[test]
+    fn empty_rewrite_prefixes_are_ignored() {
+        let network = rewrites(&[("", "https://everything.example.org/"), ("https://github.com/", "https://mirror.example.org/")]);
+        assert_eq!(network.rewrite("https://github.com/a.zip"), "https://mirror.example.org/a.zip");
+        assert_eq!(network.rewrite("https://example.com/a.zip"), "https://example.com/a.zip");
+    }
+}
@@ -0,0 +1,451 @@
+// FILE: crbrs-lib/src/pakbus.rs
+
+//! PakBus, the packet protocol Campbell loggers speak on serial ports, radios and TCP, and the
+//! few PakCtrl and BMP5 messages crbrs sends.
+//!
+//! On the wire a packet is framed by `0xBD` sync bytes, with `0xBD` and `0xBC` inside it
+//! quoted as `0xBC 0xDD` and `0xBC 0xDC`:
+//!
+//! ```text
+//! link state (4 bits), destination (12) | expect more (2), priority (2), source (12)
+//! protocol (4 bits), destination node (12) | hop count (4), source node (12)
+//! message type | transaction number | body ... | signature nullifier (2 bytes)
+//! ```
+//!
+//! The nullifier makes the signature of the whole packet zero, which is how a receiver tells a
+//! packet damaged in transit from a good one. Numbers in PakBus messages are MSB first.
+
+use std::io::{self, Read};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Frames packets on the wire.
+pub const SYNC: u8 = 0xBD;
+const QUOTE: u8 = 0xBC;
+
+/// Port of the PakBus/TCP service of loggers with an Ethernet or cellular interface.
+pub const TCP_PORT: u16 = 6785;
+/// PakBus address crbrs sends from (LoggerNet's default address).
+pub const OUR_ADDRESS: u16 = 4094;
+/// PakBus address loggers ship with.
+pub const DEFAULT_ADDRESS: u16 = 1;
+
+/// Link state of a packet on a link that is up.
+const LINK_READY: u8 = 0xA;
+
+/// PakCtrl Hello: checks that a node is there (and sets up the link).
+pub const HELLO: u8 = 0x09;
+/// BMP5 Clock Set/Check: reads the logger's clock and optionally adjusts it.
+pub const CLOCK: u8 = 0x17;
+/// BMP5 Get Values: reads fields of a table, e.g. `Status.Battery`.
+pub const GET_VALUES: u8 = 0x1A;
+/// BMP5 File Send: writes a fragment of a file to the logger.
+pub const FILE_SEND: u8 = 0x1C;
+/// BMP5 File Receive: reads a fragment of a file from the logger.
+pub const FILE_RECEIVE: u8 = 0x1D;
+/// BMP5 File Control: runs, stops or deletes a file on the logger.
+pub const FILE_CONTROL: u8 = 0x1E;
+/// Sent by a node that needs more time for a request.
+pub const PLEASE_WAIT: u8 = 0xA1;
+
+/// Message type of the response to `request`.
+pub fn response_to(request: u8) -> u8 {
+    request | 0x80
+}
+
+/// Signature of `bytes`, continuing from `seed` (0xAAAA for a new packet).
+pub fn signature(bytes: &[u8], seed: u16) -> u16 {
+    let mut sig = seed;
+    for &byte in bytes {
+        let previous = sig;
+        let mut rotated = (sig << 1) & 0x1FF;
+        if rotated >= 0x100 {
+            rotated += 1;
+        }
+        sig = ((rotated + (previous >> 8) + u16::from(byte)) & 0xFF) | (previous << 8);
+    }
+    sig
+}
+
+/// The two bytes that make the signature of a packet with signature `sig` zero.
+pub fn nullifier(sig: u16) -> [u8; 2] {
+    let mut out = [0u8; 2];
+    let mut sig = sig;
+    for i in 0..2 {
+        if i > 0 {
+            sig = signature(&out[..1], sig);
+        }
+        let mut rotated = (sig << 1) & 0x1FF;
+        if rotated >= 0x100 {
+            rotated += 1;
+        }
+        out[i] = (0x100 - ((rotated + (sig >> 8)) & 0xFF)) as u8;
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// Link and routing control (Hello, ...).
+    PakCtrl,
+    /// Logger messages (values, clock, files, data collection).
+    Bmp5,
+}
+
+/// A PakBus packet between two nodes on the same link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub destination: u16,
+    pub source: u16,
+    pub protocol: Protocol,
+    pub message_type: u8,
+    pub transaction: u8,
+    pub body: Vec<u8>,
+}
+
+impl Packet {
+    /// The packet as sent on the wire: framed, with its nullifier and quoted.
+    pub fn encode(&self) -> Vec<u8> {
+        let protocol: u16 = match self.protocol {
+            Protocol::PakCtrl => 0,
+            Protocol::Bmp5 => 1,
+        };
+        let mut packet = Vec::with_capacity(self.body.len() + 12);
+        packet.extend((u16::from(LINK_READY) << 12 | self.destination & 0xFFF).to_be_bytes());
+        // Expect more: 2 (more to come), priority: 1 (normal).
+        packet.extend((0x2 << 14 | 0x1 << 12 | self.source & 0xFFF).to_be_bytes());
+        packet.extend((protocol << 12 | self.destination & 0xFFF).to_be_bytes());
+        packet.extend((self.source & 0xFFF).to_be_bytes());
+        packet.push(self.message_type);
+        packet.push(self.transaction);
+        packet.extend(&self.body);
+        packet.extend(nullifier(signature(&packet, 0xAAAA)));
+
+        let mut frame = vec![SYNC];
+        for byte in packet {
+            match byte {
+                SYNC => frame.extend([QUOTE, 0xDD]),
+                QUOTE => frame.extend([QUOTE, 0xDC]),
+                byte => frame.push(byte),
+            }
+        }
+        frame.push(SYNC);
+        frame
+    }
+
+    /// Parses an unquoted packet (as returned by [`read_frame`]).
+    pub fn decode(bytes: &[u8]) -> Result<Packet, String> {
+        if bytes.len() < 12 {
+            return Err(format!("{}-byte packet is too short", bytes.len()));
+        }
+        if signature(bytes, 0xAAAA) != 0 {
+            return Err("packet signature does not match (damaged in transit)".to_string());
+        }
+        let word = |at: usize| u16::from_be_bytes([bytes[at], bytes[at + 1]]);
+        let protocol = match word(4) >> 12 {
+            0 => Protocol::PakCtrl,
+            1 => Protocol::Bmp5,
+            other => return Err(format!("unknown PakBus protocol {}", other)),
+        };
+        Ok(Packet {
+            destination: word(4) & 0xFFF,
+            source: word(6) & 0xFFF,
+            protocol,
+            message_type: bytes[8],
+            transaction: bytes[9],
+            body: bytes[10..bytes.len() - 2].to_vec(),
+        })
+    }
+}
+
+/// Reads the next frame from `reader` and returns it unquoted, without sync bytes. Bytes
+/// before the first sync byte (line noise, a modem banner) are skipped.
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut byte = [0u8; 1];
+    let mut next = |reader: &mut R| -> io::Result<u8> {
+        reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    };
+    while next(reader)? != SYNC {}
+    let mut frame = Vec::new();
+    loop {
+        match next(reader)? {
+            // Back-to-back sync bytes separate frames (or wake a link up).
+            SYNC if frame.is_empty() => {}
+            SYNC => return Ok(frame),
+            QUOTE => frame.push(next(reader)?.wrapping_sub(0x20)),
+            byte => frame.push(byte),
+        }
+    }
+}
+
+/// How a value is requested with Get Values; the logger converts to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    /// IEEE 754 single precision, MSB first (BMP5 type 9).
+    Number,
+    /// NUL-terminated string (BMP5 type 16).
+    Text,
+}
+
+impl ValueKind {
+    fn code(self) -> u8 {
+        match self {
+            ValueKind::Number => 9,
+            ValueKind::Text => 16,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            9 => Some(ValueKind::Number),
+            16 => Some(ValueKind::Text),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+}
+
+/// A Get Values request as its fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetValues {
+    pub security_code: u16,
+    pub table: String,
+    pub kind: ValueKind,
+    pub field: String,
+    /// Number of values, for array fields.
+    pub swath: u16,
+}
+
+impl GetValues {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = self.security_code.to_be_bytes().to_vec();
+        body.extend(self.table.as_bytes());
+        body.push(0);
+        body.push(self.kind.code());
+        body.extend(self.field.as_bytes());
+        body.push(0);
+        body.extend(self.swath.to_be_bytes());
+        body
+    }
+
+    pub fn decode(body: &[u8]) -> Result<GetValues, String> {
+        let invalid = || "malformed Get Values request".to_string();
+        let security_code = u16::from_be_bytes([*body.first().ok_or_else(invalid)?, *body.get(1).ok_or_else(invalid)?]);
+        let mut rest = &body[2..];
+        let text = |rest: &mut &[u8]| -> Result<String, String> {
+            let end = rest.iter().position(|&b| b == 0).ok_or_else(invalid)?;
+            let value = String::from_utf8_lossy(&rest[..end]).into_owned();
+            *rest = &rest[end + 1..];
+            Ok(value)
+        };
+        let table = text(&mut rest)?;
+        let (&kind, tail) = rest.split_first().ok_or_else(invalid)?;
+        rest = tail;
+        let kind = ValueKind::from_code(kind).ok_or_else(|| format!("unsupported value type {}", kind))?;
+        let field = text(&mut rest)?;
+        let swath = match rest {
+            [high, low, ..] => u16::from_be_bytes([*high, *low]),
+            _ => return Err(invalid()),
+        };
+        Ok(GetValues { security_code, table, kind, field, swath })
+    }
+}
+
+/// Seconds from the Unix epoch to 1990-01-01, the epoch of logger time stamps.
+const EPOCH_1990: u64 = 631_152_000;
+
+/// `time` as a BMP5 NSec: seconds since 1990 and nanoseconds, MSB first.
+pub fn encode_nsec(time: SystemTime) -> [u8; 8] {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default().saturating_sub(Duration::from_secs(EPOCH_1990));
+    let mut out = [0u8; 8];
+    out[..4].copy_from_slice(&(since.as_secs() as u32).to_be_bytes());
+    out[4..].copy_from_slice(&since.subsec_nanos().to_be_bytes());
+    out
+}
+
+/// The time in a BMP5 NSec.
+pub fn decode_nsec(bytes: &[u8]) -> Option<SystemTime> {
+    let [a, b, c, d, e, f, g, h, ..] = bytes else { return None };
+    let seconds = u64::from(u32::from_be_bytes([*a, *b, *c, *d]));
+    let nanos = u32::from_be_bytes([*e, *f, *g, *h]).min(999_999_999);
+    Some(UNIX_EPOCH + Duration::new(EPOCH_1990 + seconds, nanos))
+}
+
+/// Bytes of file data in one File Send fragment, well within the packet size loggers accept.
+pub const FILE_FRAGMENT: usize = 512;
+
+/// A File Send request: `data` written at `offset` of `file_name` (e.g. `CPU:soil.cr1x`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSend {
+    pub security_code: u16,
+    pub file_name: String,
+    /// Set on the last fragment, which closes the file.
+    pub close: bool,
+    pub offset: u32,
+    pub data: Vec<u8>,
+}
+
+impl FileSend {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = self.security_code.to_be_bytes().to_vec();
+        body.extend(self.file_name.as_bytes());
+        body.push(0);
+        // Attribute: none.
+        body.push(0);
+        body.push(u8::from(self.close));
+        body.extend(self.offset.to_be_bytes());
+        body.extend(&self.data);
+        body
+    }
+
+    pub fn decode(body: &[u8]) -> Result<FileSend, String> {
+        let invalid = || "malformed File Send request".to_string();
+        let [high, low, rest @ ..] = body else { return Err(invalid()) };
+        let end = rest.iter().position(|&b| b == 0).ok_or_else(invalid)?;
+        let file_name = String::from_utf8_lossy(&rest[..end]).into_owned();
+        let [_attribute, close, a, b, c, d, data @ ..] = &rest[end + 1..] else { return Err(invalid()) };
+        Ok(FileSend {
+            security_code: u16::from_be_bytes([*high, *low]),
+            file_name,
+            close: *close != 0,
+            offset: u32::from_be_bytes([*a, *b, *c, *d]),
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// A File Receive request: up to `swath` bytes of `file_name` from `offset`. The logger
+/// answers with the response code, the offset and the data; fewer than `swath` bytes mean the
+/// end of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReceive {
+    pub security_code: u16,
+    pub file_name: String,
+    pub offset: u32,
+    pub swath: u16,
+}
+
+impl FileReceive {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = self.security_code.to_be_bytes().to_vec();
+        body.extend(self.file_name.as_bytes());
+        body.push(0);
+        // Close flag: the logger closes the file after the last fragment anyway.
+        body.push(0);
+        body.extend(self.offset.to_be_bytes());
+        body.extend(self.swath.to_be_bytes());
+        body
+    }
+
+    pub fn decode(body: &[u8]) -> Result<FileReceive, String> {
+        let invalid = || "malformed File Receive request".to_string();
+        let [high, low, rest @ ..] = body else { return Err(invalid()) };
+        let end = rest.iter().position(|&b| b == 0).ok_or_else(invalid)?;
+        let [_close, a, b, c, d, e, f, ..] = &rest[end + 1..] else { return Err(invalid()) };
+        Ok(FileReceive {
+            security_code: u16::from_be_bytes([*high, *low]),
+            file_name: String::from_utf8_lossy(&rest[..end]).into_owned(),
+            offset: u32::from_be_bytes([*a, *b, *c, *d]),
+            swath: u16::from_be_bytes([*e, *f]),
+        })
+    }
+}
+
+/// File Control command: compile and run the program, and run it on power-up.
+pub const FILE_COMPILE_AND_RUN: u8 = 1;
+
+/// File Control command: delete the file.
+pub const FILE_DELETE: u8 = 4;
+
+/// A File Control request: `command` on `file_name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileControl {
+    pub security_code: u16,
+    pub file_name: String,
+    pub command: u8,
+}
+
+impl FileControl {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = self.security_code.to_be_bytes().to_vec();
+        body.extend(self.file_name.as_bytes());
+        body.push(0);
+        body.push(self.command);
+        body
+    }
+
+    pub fn decode(body: &[u8]) -> Result<FileControl, String> {
+        let invalid = || "malformed File Control request".to_string();
+        let [high, low, rest @ ..] = body else { return Err(invalid()) };
+        let end = rest.iter().position(|&b| b == 0).ok_or_else(invalid)?;
+        let command = *rest.get(end + 1).ok_or_else(invalid)?;
+        Ok(FileControl {
+            security_code: u16::from_be_bytes([*high, *low]),
+            file_name: String::from_utf8_lossy(&rest[..end]).into_owned(),
+            command,
+        })
+    }
+}
+
+/// Response codes of BMP5 requests.
+pub const RESPONSE_OK: u8 = 0;
+pub const RESPONSE_PERMISSION_DENIED: u8 = 1;
+pub const RESPONSE_INVALID_NAME: u8 = 7;
+
+/// What a non-zero BMP5 response code means.
+pub fn describe_response(code: u8) -> String {
+    match code {
+        RESPONSE_PERMISSION_DENIED => "permission denied (wrong security code)".to_string(),
+        2 => "the logger is out of resources".to_string(),
+        RESPONSE_INVALID_NAME => "no such table or field".to_string(),
+        8 => "the value cannot be converted to the requested type".to_string(),
+        9 => "memory bounds violation".to_string(),
+        13 => "invalid file name".to_string(),
+        14 => "the file is not accessible (in use or the device is full)".to_string(),
+        other => format!("response code {}", other),
+    }
+}
+
+/// Body of a Get Values response carrying `values`.
+pub fn encode_values(values: &[Value]) -> Vec<u8> {
+    let mut body = vec![RESPONSE_OK];
+    for value in values {
+        match value {
+            Value::Number(number) => body.extend((*number as f32).to_be_bytes()),
+            Value::Text(text) => {
+                body.extend(text.as_bytes());
+                body.push(0);
+            }
+        }
+    }
+    body
+}
+
+/// The values in a Get Values response body.
+pub fn decode_values(body: &[u8], kind: ValueKind, swath: u16) -> Result<Vec<Value>, u8> {
+    let (&code, mut rest) = body.split_first().ok_or(u8::MAX)?;
+    if code != RESPONSE_OK {
+        return Err(code);
+    }
+    let mut values = Vec::with_capacity(usize::from(swath));
+    for _ in 0..swath {
+        match kind {
+            ValueKind::Number => {
+                let [a, b, c, d, tail @ ..] = rest else { break };
+                values.push(Value::Number(f64::from(f32::from_be_bytes([*a, *b, *c, *d]))));
+                rest = tail;
+            }
+            ValueKind::Text => {
+                let Some(end) = rest.iter().position(|&b| b == 0) else { break };
+                values.push(Value::Text(String::from_utf8_lossy(&rest[..end]).into_owned()));
+                rest = &rest[end + 1..];
+            }
+        }
+    }
+    Ok(values)
+}
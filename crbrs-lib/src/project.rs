@@ -0,0 +1,247 @@
+// FILE: crbrs-lib/src/project.rs
+
+//! Per-project settings: a `crbrs.toml` checked into the repository declares the toolchain the
+//! programs below it need, so everyone building them (and CI) uses the same one. It is found
+//! by walking up from the program being compiled, and what it sets takes precedence over the
+//! user's config for programs in the project:
+//!
+//! ```toml
+//! required_compilers = ["cr1000comp-v3.2", "cr300comp-v2"]
+//! include_paths = ["shared"]          # relative to this file
+//!
+//! [file_associations]
+//! cr1x = "cr1000comp-v3.2"
+//!
+//! [output]
+//! image_dir = "build/images"          # where --image puts download images
+//!
+//! [format]                            # formatter options; see crate::format
+//! indent_width = 4
+//!
+//! [metadata]                          # crbrs metadata check; see crate::metadata
+//! ignore_tables = ["Status"]
+//! ```
+//!
+//! Next to it, `crbrs.lock` records the exact compiler each required ID was installed as, so
+//! `crbrs verify-project` can tell when a machine has a different build of the same ID:
+//!
+//! ```toml
+//! [[compilers]]
+//! id = "cr1000comp-v3.2"
+//! version = "3.2"
+//! sha256 = "..."                       # of the archive it was installed from
+//! ```
+//!
+//! `crbrs compiler install` and `crbrs provision` write it for the required compilers they
+//! install, and add the others that are installed but not locked yet; check it in with the
+//! manifest.
+//!
+//! Compiling fails while a required compiler is not installed. Include paths are searched for
+//! included files that are not next to the including program, for incremental builds and
+//! editor navigation; the compiler itself still resolves includes the way the logger would.
+
+use crate::build_state::find_in;
+use crate::{Error, Settings};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "crbrs.toml";
+pub const LOCK_FILE: &str = "crbrs.lock";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectManifest {
+    /// Compilers the project's programs are built with.
+    pub required_compilers: Vec<String>,
+    /// Extension -> compiler ID, over the user's `file_associations`.
+    pub file_associations: HashMap<String, String>,
+    /// Directories searched for included files, relative to the manifest.
+    pub include_paths: Vec<PathBuf>,
+    pub output: OutputSettings,
+    pub metadata: MetadataSettings,
+    /// Formatter options for the project's programs (see [`crate::format`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<toml::Table>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSettings {
+    /// Directory for download images, relative to the manifest; next to the program if unset.
+    pub image_dir: Option<PathBuf>,
+}
+
+/// How `crbrs metadata check` compares the tables of the project's programs (see
+/// [`crate::metadata::check_project`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetadataSettings {
+    /// Tables each program may lay out its own way, e.g. diagnostics the database does not ingest.
+    pub ignore_tables: Vec<String>,
+    /// Whether programs storing a table of the same name must store the same fields. When
+    /// false (a database that adds columns as they appear) only the fields they share must agree.
+    pub same_fields: bool,
+}
+
+impl Default for MetadataSettings {
+    fn default() -> Self {
+        MetadataSettings { ignore_tables: Vec::new(), same_fields: true }
+    }
+}
+
+/// `crbrs.lock`: the compilers a project was last set up with.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectLock {
+    /// Sorted by ID.
+    pub compilers: Vec<LockedCompiler>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedCompiler {
+    pub id: String,
+    pub version: String,
+    /// SHA256 of the archive it was installed from; absent if installed from a directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl ProjectLock {
+    pub fn get(&self, id: &str) -> Option<&LockedCompiler> {
+        self.compilers.iter().find(|locked| locked.id == id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    /// The directory holding the manifest.
+    pub root: PathBuf,
+    pub manifest: ProjectManifest,
+}
+
+impl Project {
+    /// Reads the manifest at `path`.
+    pub fn load(path: &Path) -> Result<Project, Error> {
+        let text = fs::read_to_string(path)?;
+        let manifest = toml::from_str(&text)
+            .map_err(|e| Error::Config(config::ConfigError::Message(format!("{}: {}", path.display(), e.message()))))?;
+        let root = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        Ok(Project { root, manifest })
+    }
+
+    /// The project `path` (a program or a directory) belongs to: the nearest `crbrs.toml` in
+    /// it or above it.
+    pub fn find(path: &Path) -> Result<Option<Project>, Error> {
+        let path = if path.as_os_str().is_empty() { Path::new(".") } else { path };
+        let absolute = std::path::absolute(path)?;
+        let start = if absolute.is_dir() { absolute.as_path() } else { absolute.parent().unwrap_or(&absolute) };
+        match start.ancestors().map(|dir| dir.join(MANIFEST_FILE)).find(|manifest| manifest.is_file()) {
+            Some(manifest) => {
+                log::debug!("{:?} belongs to the project at {:?}", path, manifest);
+                Project::load(&manifest).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILE)
+    }
+
+    pub fn lock_path(&self) -> PathBuf {
+        self.root.join(LOCK_FILE)
+    }
+
+    /// The project's `crbrs.lock`, if it has one.
+    pub fn lock(&self) -> Result<Option<ProjectLock>, Error> {
+        let path = self.lock_path();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        toml::from_str(&text)
+            .map(Some)
+            .map_err(|e| Error::Config(config::ConfigError::Message(format!("{}: {}", path.display(), e.message()))))
+    }
+
+    /// Records the required compilers in `installed` (just installed) in `crbrs.lock`, and the
+    /// other installed required compilers the lock does not list yet. Returns whether the
+    /// lock changed.
+    pub fn update_lock(&self, installed: &[String], settings: &Settings) -> Result<bool, Error> {
+        let old = self.lock()?.unwrap_or_default();
+        let mut lock = old.clone();
+        for id in &self.manifest.required_compilers {
+            let Some(info) = settings.installed_compilers.get(id) else { continue };
+            if old.get(id).is_some() && !installed.contains(id) {
+                continue;
+            }
+            let locked = LockedCompiler { id: id.clone(), version: info.version.clone(), sha256: info.archive_sha256.clone() };
+            lock.compilers.retain(|other| other.id != *id);
+            lock.compilers.push(locked);
+        }
+        lock.compilers.sort_by(|a, b| a.id.cmp(&b.id));
+        if lock == old {
+            return Ok(false);
+        }
+        let text = toml::to_string(&lock).map_err(|e| Error::Config(config::ConfigError::Message(e.to_string())))?;
+        fs::write(self.lock_path(), format!("# Written by crbrs; check it in with {}.\n{}", MANIFEST_FILE, text))?;
+        log::info!("Updated {:?}", self.lock_path());
+        Ok(true)
+    }
+
+    /// The compiler the project associates with `extension`, if any.
+    pub fn association(&self, extension: &str) -> Option<&String> {
+        let associations = &self.manifest.file_associations;
+        associations
+            .get(extension)
+            .or_else(|| associations.iter().find(|(ext, _)| ext.eq_ignore_ascii_case(extension)).map(|(_, id)| id))
+    }
+
+    /// Required compilers that are not installed.
+    pub fn missing_compilers(&self, settings: &Settings) -> Vec<String> {
+        self.manifest.required_compilers.iter().filter(|id| !settings.installed_compilers.contains_key(*id)).cloned().collect()
+    }
+
+    /// Fails with [`Error::ProjectCompilersMissing`] while a required compiler is not installed.
+    pub fn check_compilers(&self, settings: &Settings) -> Result<(), Error> {
+        let missing = self.missing_compilers(settings);
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(Error::ProjectCompilersMissing { manifest: self.manifest_path(), missing })
+    }
+
+    pub fn include_dirs(&self) -> Vec<PathBuf> {
+        self.manifest.include_paths.iter().map(|dir| self.root.join(dir)).collect()
+    }
+
+    pub fn image_dir(&self) -> Option<PathBuf> {
+        self.manifest.output.image_dir.as_ref().map(|dir| self.root.join(dir))
+    }
+}
+
+/// Finds the file an `Include` in `dir` names: next to the including file, else in the include
+/// paths of its project. Where it would be next to the including file if it is nowhere.
+pub fn find_include(dir: &Path, name: &str) -> PathBuf {
+    let next_to = find_in(dir, name);
+    if next_to.is_file() {
+        return next_to;
+    }
+    match Project::find(dir) {
+        Ok(Some(project)) => project
+            .include_dirs()
+            .iter()
+            .map(|include_dir| find_in(include_dir, name))
+            .find(|found| found.is_file())
+            .unwrap_or(next_to),
+        Ok(None) => next_to,
+        Err(e) => {
+            log::warn!("Ignoring the project's include paths: {}", e);
+            next_to
+        }
+    }
+}
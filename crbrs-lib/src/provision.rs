@@ -0,0 +1,159 @@
+// FILE: crbrs-lib/src/provision.rs
+
+//! `crbrs provision --from classroom.toml`: sets a machine up from a plan in one go, so an
+//! instructor can give every student machine the same toolchain.
+//!
+//! ```toml
+//! compiler_repository_url = "https://example.org/compilers.toml"   # optional
+//! wine_path = "/usr/bin/wine"                                       # optional
+//! compilers = ["cr1000xcomp", "cr300comp"]
+//!
+//! [associations]
+//! cr1x = "cr1000xcomp"
+//! cr300 = "cr300comp"
+//!
+//! [lint]
+//! unit-mismatch = "off"
+//! ```
+//!
+//! Compilers already installed are kept, so running a plan again only does what is missing.
+//! Every compiler is then checked by compiling an empty program with it.
+
+use crate::bench::compile_once;
+use crate::compiler::{find_wine, uses_wine};
+use crate::config::save_settings;
+use crate::installer::install_compiler;
+use crate::syntax::lint::{self, Level};
+use crate::{cancel, Error, Settings};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// A provisioning plan, as read from its file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Plan {
+    pub compiler_repository_url: Option<String>,
+    pub wine_path: Option<String>,
+    /// Compiler IDs to install from the manifest.
+    pub compilers: Vec<String>,
+    /// File extension -> compiler ID.
+    pub associations: BTreeMap<String, String>,
+    /// Lint rule -> level.
+    pub lint: BTreeMap<String, Level>,
+}
+
+impl Plan {
+    /// Reads and checks the plan at `path`.
+    pub fn load(path: &Path) -> Result<Plan, Error> {
+        let text = fs::read_to_string(path)?;
+        let plan: Plan = toml::from_str(&text).map_err(|e| Error::Provision(format!("{}: {}", path.display(), e.message())))?;
+        for (extension, compiler_id) in &plan.associations {
+            if extension.is_empty() || extension.contains('.') {
+                return Err(Error::InvalidExtension(extension.clone()));
+            }
+            if !plan.compilers.contains(compiler_id) {
+                return Err(Error::Provision(format!(
+                    "'.{}' is associated with '{}', which is not in `compilers`",
+                    extension, compiler_id
+                )));
+            }
+        }
+        if let Some(rule) = plan.lint.keys().find(|rule| lint::rule(rule).is_none()) {
+            return Err(Error::Provision(format!("unknown lint rule '{}'", rule)));
+        }
+        Ok(plan)
+    }
+}
+
+/// The result of checking one compiler.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolchainCheck {
+    pub compiler_id: String,
+    pub ok: bool,
+    /// What went wrong, if anything.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+}
+
+/// What provisioning did.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    pub installed: Vec<String>,
+    pub already_installed: Vec<String>,
+    pub checks: Vec<ToolchainCheck>,
+}
+
+impl Report {
+    pub fn failed(&self) -> usize {
+        self.checks.iter().filter(|c| !c.ok).count()
+    }
+}
+
+/// Applies `plan` to `settings` (saving them), installs its compilers and checks each one.
+pub fn run(plan: &Plan, settings: &mut Settings) -> Result<Report, Error> {
+    if let Some(url) = &plan.compiler_repository_url {
+        settings.compiler_repository_url = url.clone();
+    }
+    if let Some(wine_path) = &plan.wine_path {
+        settings.wine_path = Some(wine_path.clone());
+    }
+    for (extension, compiler_id) in &plan.associations {
+        settings.file_associations.insert(extension.to_lowercase(), compiler_id.clone());
+    }
+    settings.lint.extend(plan.lint.iter().map(|(rule, level)| (rule.clone(), *level)));
+    save_settings(settings)?;
+
+    let mut report = Report::default();
+    for compiler_id in &plan.compilers {
+        cancel::check()?;
+        if settings.installed_compilers.contains_key(compiler_id) {
+            log::info!("Compiler '{}' is already installed", compiler_id);
+            report.already_installed.push(compiler_id.clone());
+        } else {
+            install_compiler(settings, compiler_id)?;
+            report.installed.push(compiler_id.clone());
+        }
+    }
+
+    let dir = crate::temp::TempDir::new("provision")?;
+    for compiler_id in &plan.compilers {
+        let result = check(compiler_id, dir.path(), settings);
+        if matches!(result, Err(Error::Cancelled)) {
+            return Err(Error::Cancelled);
+        }
+        let problem = match result {
+            Ok(()) => None,
+            Err(e) => Some(e.to_string()),
+        };
+        log::info!("Toolchain check of '{}': {}", compiler_id, problem.as_deref().unwrap_or("ok"));
+        report.checks.push(ToolchainCheck { compiler_id: compiler_id.clone(), ok: problem.is_none(), problem });
+    }
+    Ok(report)
+}
+
+/// Compiles an empty program with `compiler_id`.
+fn check(compiler_id: &str, dir: &Path, settings: &Settings) -> Result<(), Error> {
+    let info = settings
+        .installed_compilers
+        .get(compiler_id)
+        .ok_or_else(|| Error::Provision(format!("'{}' is not installed", compiler_id)))?;
+    if uses_wine(info) && find_wine(settings).is_none() {
+        return Err(Error::WineNotFound);
+    }
+    // Name the program like the files the compiler is used for.
+    let extension = settings
+        .file_associations
+        .iter()
+        .filter(|(_, id)| *id == compiler_id)
+        .map(|(extension, _)| extension.as_str())
+        .min()
+        .unwrap_or("cr1x");
+    let program = dir.join(format!("check.{}", extension));
+    fs::write(&program, "BeginProg\r\nEndProg\r\n")?;
+    match compile_once(&program, compiler_id, info, settings)? {
+        true => Ok(()),
+        false => Err(Error::Provision("it failed to compile an empty program".to_string())),
+    }
+}
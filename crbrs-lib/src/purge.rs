@@ -0,0 +1,196 @@
+// FILE: crbrs-lib/src/purge.rs
+
+//! `crbrs self purge`: removes what crbrs has put on a machine, so it can be reset or handed
+//! off.
+//!
+//! What is removed is chosen by category, and listed (with sizes) before anything is deleted.
+//! Compilers registered with `compiler link` live outside crbrs' storage and are left alone;
+//! purging the config only forgets them. The compiler storage may be a shared directory
+//! (`compiler_storage_path`), so only what crbrs put there is removed, and the directory itself
+//! only if that leaves it empty.
+
+use crate::config::{self, get_compiler_storage_path};
+use crate::{installer, secret, Error, Settings};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Kinds of things crbrs keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// Installed compilers and their downloaded archives.
+    Compilers,
+    /// The cached manifest, build state, compile results, calibrated copies, symbol indexes,
+    /// temporary files and the last run's log.
+    Caches,
+    /// Compile history and logger session logs.
+    History,
+    /// The config directory.
+    Config,
+    /// Keychain secrets the config refers to.
+    Secrets,
+}
+
+impl Category {
+    pub const ALL: [Category; 5] = [Category::Compilers, Category::Caches, Category::History, Category::Config, Category::Secrets];
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Category::Compilers => "compilers",
+            Category::Caches => "caches",
+            Category::History => "history",
+            Category::Config => "config",
+            Category::Secrets => "secrets",
+        })
+    }
+}
+
+/// Something to remove.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum Target {
+    /// A file or directory.
+    Path { category: Category, path: PathBuf, bytes: u64 },
+    /// A secret in the keychain.
+    Secret { name: String },
+}
+
+impl Target {
+    pub fn category(&self) -> Category {
+        match self {
+            Target::Path { category, .. } => *category,
+            Target::Secret { .. } => Category::Secrets,
+        }
+    }
+}
+
+/// Size of the file or directory at `path`, counting hard-linked files each time.
+pub(crate) fn size_of(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else { return 0 };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path).into_iter().flatten().flatten().map(|entry| size_of(&entry.path())).sum()
+}
+
+/// What crbrs put in the compiler storage at `storage`: installed (not linked) compilers, the
+/// deduplicated objects, cached archives and leftovers of interrupted installs.
+fn storage_contents(settings: &Settings, storage: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = settings
+        .installed_compilers
+        .values()
+        .filter(|info| !info.linked && info.install_subdir.file_name().is_some())
+        .map(|info| storage.join(&info.install_subdir))
+        .collect();
+    for entry in fs::read_dir(storage).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let leftover = name.starts_with('.') && (name.ends_with(".partial") || name.ends_with(".previous"));
+        // Installs no longer in the state file still carry the metadata crbrs wrote.
+        let installed = entry.path().join(installer::COMPILER_METADATA_FILE).is_file();
+        if name == ".objects" || name == ".archives" || leftover || installed {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// What purging `categories` would remove, existing paths only.
+pub fn plan(settings: &Settings, categories: &[Category]) -> Result<Vec<Target>, Error> {
+    let data = config::data_dir()?;
+    // Secrets first: if the keychain can't be reached, nothing else has been removed yet.
+    let mut targets = Vec::new();
+    if categories.contains(&Category::Secrets) {
+        let mut names: Vec<&String> = settings
+            .stations
+            .values()
+            .filter_map(|station| station.security_code_secret.as_ref())
+            .chain(settings.network.tokens.values())
+            .collect();
+        names.sort();
+        names.dedup();
+        targets.extend(names.into_iter().map(|name| Target::Secret { name: name.clone() }));
+    }
+    let mut add = |category: Category, path: PathBuf| {
+        if categories.contains(&category) && fs::symlink_metadata(&path).is_ok() {
+            let bytes = size_of(&path);
+            targets.push(Target::Path { category, path, bytes });
+        }
+    };
+    let storage = get_compiler_storage_path(settings)?;
+    for path in storage_contents(settings, &storage) {
+        add(Category::Compilers, path);
+    }
+    add(Category::Compilers, config::state_file_path()?);
+    add(Category::Compilers, config::backup_path(&config::state_file_path()?));
+    let manifest = installer::manifest_cache_path()?;
+    add(Category::Caches, manifest.with_extension("origin.json"));
+    add(Category::Caches, manifest);
+    add(Category::Caches, crate::build_state::state_path()?);
+    add(Category::Caches, crate::compile_cache::cache_dir()?);
+    add(Category::Caches, crate::temp::root()?);
+    add(Category::Caches, data.join("symbol-index"));
+    add(Category::Caches, crate::calibration::calibrated_root()?);
+    add(Category::Caches, crate::structured_log::last_run_path()?);
+    add(Category::History, crate::history::history_path()?);
+    add(Category::History, crate::session_log::session_log_dir()?);
+    add(Category::Config, config::config_dir()?);
+    add(Category::Config, config::fallback_config_file()?);
+    add(Category::Config, config::backup_path(&config::fallback_config_file()?));
+    Ok(targets)
+}
+
+/// Removes `targets`; returns how many were removed (secrets that were not in the keychain
+/// are not counted).
+pub fn purge(targets: &[Target]) -> Result<usize, Error> {
+    let last_run = crate::structured_log::last_run_path()?;
+    if targets.iter().any(|t| matches!(t, Target::Path { path, .. } if *path == last_run)) {
+        log::info!("Closing log files to remove {:?}", last_run);
+        crate::structured_log::close(); // This run's log is one of them
+    }
+    let mut removed = 0;
+    for target in targets {
+        crate::cancel::check()?;
+        match target {
+            Target::Path { path, .. } => {
+                log::info!("Removing {:?}", path);
+                let result = match fs::symlink_metadata(path) {
+                    Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+                    Ok(_) => fs::remove_file(path),
+                    Err(_) => continue, // Already gone, e.g. inside a directory removed before
+                };
+                result?;
+                removed += 1;
+            }
+            Target::Secret { name } => {
+                if secret::remove(name)? {
+                    removed += 1;
+                }
+            }
+        }
+    }
+    // Leave no empty crbrs directories behind. The compiler storage is removed only if nothing
+    // but what crbrs put there was in it.
+    let storages: Vec<&Path> = targets
+        .iter()
+        .filter_map(|target| match target {
+            Target::Path { category: Category::Compilers, path, .. } => path.parent(),
+            _ => None,
+        })
+        .collect();
+    for storage in storages {
+        let _ = fs::remove_dir(storage);
+    }
+    if let Ok(data) = config::data_dir() {
+        let _ = fs::remove_dir(data);
+    }
+    if let Some(home) = config::portable_home() {
+        let _ = fs::remove_dir(home);
+    }
+    Ok(removed)
+}
@@ -0,0 +1,318 @@
+// FILE: crbrs-lib/src/qa.rs
+
+//! `crbrs data qa`: range, spike and flatline checks on the fields of a TOA5 file, e.g. on
+//! data just collected from a station.
+//!
+//! Each field's checks come from, in order of precedence: its entry under `[fields]` in the
+//! rules file, the entry for its units under `[units]`, and built-in defaults for common units
+//! (see [`default_checks`]):
+//!
+//! ```toml
+//! [units."Deg C"]
+//! min = -30
+//! max = 45
+//!
+//! [fields.AirT_Avg]
+//! spike = 3        # largest change between consecutive records
+//! flatline = 6     # this many identical values in a row are flagged; 0 turns it off
+//!
+//! [fields.Rain_mm_Tot]
+//! skip = true
+//! ```
+//!
+//! `NAN` and other non-numeric values are counted as missing and otherwise ignored.
+
+use crate::syntax::units::same_unit;
+use crate::toa5::{Field, Toa5};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Checks {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Largest change between consecutive values; 0 turns the check off.
+    pub spike: Option<f64>,
+    /// Number of identical values in a row that counts as a flatline; 0 turns the check off.
+    pub flatline: Option<u32>,
+    /// Don't check the field at all.
+    pub skip: Option<bool>,
+}
+
+impl Checks {
+    /// `self`, with the checks it leaves unset taken from `fallback`.
+    fn or(self, fallback: Checks) -> Checks {
+        Checks {
+            min: self.min.or(fallback.min),
+            max: self.max.or(fallback.max),
+            spike: self.spike.or(fallback.spike),
+            flatline: self.flatline.or(fallback.flatline),
+            skip: self.skip.or(fallback.skip),
+        }
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => parts.push(format!("range {}..{}", min, max)),
+            (Some(min), None) => parts.push(format!("min {}", min)),
+            (None, Some(max)) => parts.push(format!("max {}", max)),
+            (None, None) => {}
+        }
+        if let Some(spike) = self.spike.filter(|s| *s > 0.0) {
+            parts.push(format!("spike {}", spike));
+        }
+        if let Some(flatline) = self.flatline.filter(|n| *n > 0) {
+            parts.push(format!("flatline {}", flatline));
+        }
+        if parts.is_empty() {
+            "no checks".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+const fn range(min: f64, max: f64, spike: Option<f64>, flatline: Option<u32>) -> Checks {
+    Checks { min: Some(min), max: Some(max), spike, flatline, skip: None }
+}
+
+/// Built-in checks by units. Flatlines are only checked where long runs of identical values
+/// are unusual (a rain gauge or a calm anemometer legitimately reads 0 for hours).
+const UNIT_DEFAULTS: &[(&[&str], Checks)] = &[
+    (&["Deg C", "deg C", "degC", "°C", "C"], range(-50.0, 70.0, Some(10.0), Some(12))),
+    (&["%", "%RH"], range(0.0, 105.0, Some(40.0), None)),
+    (&["Volts", "V"], range(0.0, 30.0, None, None)),
+    (&["mV"], range(-5000.0, 5000.0, None, None)),
+    (&["m^3/m^3"], range(0.0, 1.0, Some(0.2), None)),
+    (&["W/m^2"], range(-10.0, 1500.0, None, None)),
+    (&["m/s"], range(0.0, 75.0, Some(30.0), None)),
+    (&["deg", "degrees"], range(0.0, 360.0, None, None)),
+    (&["kPa"], range(50.0, 110.0, Some(5.0), None)),
+    (&["hPa", "mbar", "mb"], range(500.0, 1100.0, Some(50.0), None)),
+    (&["mm"], range(0.0, 500.0, None, None)),
+    (&["dS/m"], range(0.0, 25.0, None, None)),
+    (&["uS/cm"], range(0.0, 25000.0, None, None)),
+];
+
+/// Built-in checks for fields with `units`, if crbrs knows the units.
+pub fn default_checks(units: &str) -> Option<Checks> {
+    UNIT_DEFAULTS.iter().find(|(names, _)| names.iter().any(|name| same_unit(name, units))).map(|(_, checks)| *checks)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QaRules {
+    /// Checks for every field with these units, over the built-in defaults.
+    #[serde(default)]
+    pub units: BTreeMap<String, Checks>,
+    /// Checks for single fields, over those for their units.
+    #[serde(default)]
+    pub fields: BTreeMap<String, Checks>,
+}
+
+impl QaRules {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| Error::Data(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Checks for `field`.
+    pub fn checks(&self, field: &Field) -> Checks {
+        let by_field = self.fields.iter().find(|(name, _)| name.eq_ignore_ascii_case(&field.name)).map(|(_, c)| *c);
+        let by_units = self.units.iter().find(|(units, _)| same_unit(units, &field.units)).map(|(_, c)| *c);
+        by_field.unwrap_or_default().or(by_units.unwrap_or_default()).or(default_checks(&field.units).unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Check {
+    Range,
+    Spike,
+    Flatline,
+}
+
+/// A value that failed a check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Flag {
+    /// Position of the record in the file, from 0.
+    pub index: usize,
+    pub timestamp: Option<String>,
+    pub record: Option<String>,
+    pub field: String,
+    pub check: Check,
+    pub value: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldReport {
+    pub field: String,
+    pub units: String,
+    pub checks: Checks,
+    /// Values that are not numbers, e.g. `NAN`.
+    pub missing: usize,
+    pub flags: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QaReport {
+    pub table: String,
+    pub records: usize,
+    pub fields: Vec<FieldReport>,
+    pub flags: Vec<Flag>,
+}
+
+/// `value` without floating-point noise, e.g. `20.7` rather than `20.700000000000003`.
+fn number(value: f64) -> String {
+    let text = format!("{:.6}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Runs `checks` over one field's values, in record order, adding what fails to `flags`.
+fn check_values(data: &Toa5, column: usize, checks: &Checks, flags: &mut Vec<Flag>) -> usize {
+    let field = &data.fields[column].name;
+    let record_field = data.field("RECORD");
+    let mut flag = |index: usize, check: Check, detail: String| {
+        flags.push(Flag {
+            index,
+            timestamp: data.timestamp(index).map(str::to_string),
+            record: record_field.map(|f| data.records[index][f].clone()),
+            field: field.clone(),
+            check,
+            value: data.records[index][column].clone(),
+            detail,
+        })
+    };
+    let mut missing = 0;
+    let mut previous: Option<f64> = None;
+    // Start and length of the current run of identical values.
+    let mut run = (0, 0);
+    for (index, record) in data.records.iter().enumerate() {
+        let Some(value) = record[column].trim().parse::<f64>().ok().filter(|v| v.is_finite()) else {
+            missing += 1;
+            continue;
+        };
+        if checks.min.is_some_and(|min| value < min) || checks.max.is_some_and(|max| value > max) {
+            let bound = |b: Option<f64>| b.map_or_else(|| "..".to_string(), |b| b.to_string());
+            flag(index, Check::Range, format!("outside {} to {}", bound(checks.min), bound(checks.max)));
+        }
+        if let (Some(spike), Some(previous)) = (checks.spike.filter(|s| *s > 0.0), previous) {
+            if (value - previous).abs() > spike {
+                flag(index, Check::Spike, format!("changed by {} from {} (limit {})", number(value - previous), previous, spike));
+            }
+        }
+        run = if previous == Some(value) { (run.0, run.1 + 1) } else { (index, 1) };
+        if checks.flatline.is_some_and(|n| n > 0 && run.1 == n) {
+            let since = data.timestamp(run.0).map_or_else(|| format!("record {}", run.0), str::to_string);
+            flag(index, Check::Flatline, format!("unchanged for {} values since {}", run.1, since));
+        }
+        previous = Some(value);
+    }
+    missing
+}
+
+/// Checks every field of `data` except `TIMESTAMP` and `RECORD`.
+pub fn check(data: &Toa5, rules: &QaRules) -> QaReport {
+    let mut fields = Vec::new();
+    let mut flags = Vec::new();
+    for (column, field) in data.fields.iter().enumerate() {
+        if field.name.eq_ignore_ascii_case("TIMESTAMP") || field.name.eq_ignore_ascii_case("RECORD") {
+            continue;
+        }
+        let checks = rules.checks(field);
+        let before = flags.len();
+        let missing = if checks.skip == Some(true) { 0 } else { check_values(data, column, &checks, &mut flags) };
+        fields.push(FieldReport {
+            field: field.name.clone(),
+            units: field.units.clone(),
+            checks,
+            missing,
+            flags: flags.len() - before,
+        });
+    }
+    // Fields were checked one after another; report in record order.
+    flags.sort_by_key(|flag| flag.index);
+    QaReport { table: data.table().to_string(), records: data.records.len(), fields, flags }
+}
+
+/// Checks the TOA5 file at `path` with the rules file at `rules`, or only the built-in
+/// defaults.
+pub fn check_file(path: &Path, rules: Option<&Path>) -> Result<QaReport, Error> {
+    let rules = rules.map(QaRules::load).transpose()?.unwrap_or_default();
+    Ok(check(&Toa5::read(path)?, &rules))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QaFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for QaFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(QaFormat::Text),
+            "csv" => Ok(QaFormat::Csv),
+            "json" => Ok(QaFormat::Json),
+            other => Err(format!("unknown report format '{}' (expected text, csv or json)", other)),
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// The flag report: a summary per field and every flag (text), the flags only (CSV), or
+/// everything (JSON).
+pub fn render(report: &QaReport, format: QaFormat) -> Result<String, Error> {
+    let mut out = String::new();
+    match format {
+        QaFormat::Text => {
+            let _ = writeln!(out, "{}: {} records, {} flags", report.table, report.records, report.flags.len());
+            for field in &report.fields {
+                let checks = if field.checks.skip == Some(true) { "skipped".to_string() } else { field.checks.describe() };
+                let _ = writeln!(
+                    out,
+                    "  {:<24} {:<10} {:<40} {:>5} flags {:>5} missing",
+                    field.field, field.units, checks, field.flags, field.missing
+                );
+            }
+            if !report.flags.is_empty() {
+                out.push('\n');
+            }
+            for flag in &report.flags {
+                let when = flag.timestamp.clone().unwrap_or_else(|| format!("record {}", flag.index));
+                let check = format!("{:?}", flag.check).to_ascii_lowercase();
+                let _ = writeln!(out, "  {}  {:<24} {:<8} {:>10}  {}", when, flag.field, check, flag.value, flag.detail);
+            }
+        }
+        QaFormat::Csv => {
+            out.push_str("timestamp,record,field,check,value,detail\n");
+            for flag in &report.flags {
+                let check = format!("{:?}", flag.check).to_ascii_lowercase();
+                let fields = [flag.timestamp.as_deref().unwrap_or_default(), flag.record.as_deref().unwrap_or_default(), &flag.field, &check, &flag.value, &flag.detail];
+                let _ = writeln!(out, "{}", fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+            }
+        }
+        QaFormat::Json => {
+            out = serde_json::to_string_pretty(report).map_err(|e| Error::Data(e.to_string()))?;
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
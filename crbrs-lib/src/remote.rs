@@ -0,0 +1,159 @@
+// FILE: crbrs-lib/src/remote.rs
+
+//! Remote builds (`--remote <host>`): compile on another machine over SSH, e.g. a Linux box
+//! with Wine and the compilers for users on macOS ARM.
+//!
+//! The programs and everything they include are streamed as a tar archive into a temporary
+//! directory on the host, where `crbrs compile` runs against the host's own configuration.
+//! Its output is passed through as it happens and its build summary comes back as JSON, with
+//! paths mapped to the local files. The directory is removed afterwards, so nothing is cached
+//! between runs. Needs `ssh` and `tar` locally, and `crbrs` on the host's non-interactive `PATH`.
+
+use crate::build::{collect_inputs, BuildOptions, BuildSummary};
+use crate::build_state;
+use crate::cancel;
+use crate::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+/// Summary file written by the remote run, relative to its build directory.
+const SUMMARY_FILE: &str = ".crbrs-summary.json";
+
+/// Quotes `arg` for a POSIX shell.
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Deepest directory containing all of `files`.
+fn common_dir(files: &[PathBuf]) -> PathBuf {
+    let mut dir = files.first().and_then(|f| f.parent()).map(Path::to_path_buf).unwrap_or_default();
+    while !files.iter().all(|f| f.starts_with(&dir)) && dir.pop() {}
+    dir
+}
+
+/// `path` with `/` separators, as the host expects.
+fn remote_path(path: &Path) -> String {
+    path.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+fn not_found(program: &str, e: std::io::Error) -> Error {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        Error::Remote(format!("`{}` was not found in PATH", program))
+    } else {
+        Error::Subprocess(e)
+    }
+}
+
+/// The script run on the host: unpack the sources from stdin, build them with `crbrs_args`
+/// (output to stderr) and print the summary.
+fn remote_script(crbrs_args: &[String]) -> String {
+    let args: Vec<String> = crbrs_args.iter().map(|a| quote(a)).collect();
+    format!(
+        "dir=$(mktemp -d \"${{TMPDIR:-/tmp}}/crbrs-remote.XXXXXX\") || exit 1\n\
+         trap 'rm -rf \"$dir\"' EXIT\n\
+         cd \"$dir\" && tar -xf - || exit 1\n\
+         crbrs {} >&2\n\
+         cat {}\n",
+        args.join(" "),
+        SUMMARY_FILE
+    )
+}
+
+/// Programs found in `inputs`, and the files to send for them: the programs and their includes.
+fn sources(inputs: &[PathBuf]) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Error> {
+    let programs = collect_inputs(inputs)?.iter().map(fs::canonicalize).collect::<Result<Vec<_>, _>>()?;
+    let mut files: Vec<PathBuf> = Vec::new();
+    for program in &programs {
+        for input in build_state::inputs(program) {
+            if input.is_file() && !files.contains(&input) {
+                files.push(input);
+            }
+        }
+    }
+    Ok((programs, files))
+}
+
+/// Builds every program in `inputs` on `host` and returns the summary with local paths. The
+/// wall time includes sending the sources.
+pub fn build(host: &str, inputs: &[PathBuf], options: &BuildOptions) -> Result<BuildSummary, Error> {
+    if options.image {
+        return Err(Error::Remote("images cannot be produced remotely yet; build them locally".to_string()));
+    }
+    let start = Instant::now();
+    // Calibrated copies are made here and sent instead of the programs.
+    let calibrated;
+    let inputs = match &options.calibration {
+        Some(calibration) => {
+            calibrated = collect_inputs(inputs)?
+                .iter()
+                .map(|program| crate::calibration::apply(program, calibration))
+                .collect::<Result<Vec<_>, Error>>()?;
+            &calibrated
+        }
+        None => inputs,
+    };
+    let (programs, files) = sources(inputs)?;
+    if programs.is_empty() {
+        return Ok(BuildSummary::new(Vec::new(), start.elapsed()));
+    }
+    let base = common_dir(&files);
+    let relative = |path: &Path| path.strip_prefix(&base).unwrap_or(path).to_path_buf();
+
+    let mut crbrs_args = vec!["compile".to_string()];
+    if let Some(id) = &options.compiler_id {
+        crbrs_args.extend(["--compiler".to_string(), id.clone()]);
+    }
+    if !crate::compile_cache::is_enabled() {
+        crbrs_args.push("--force".to_string());
+    }
+    if options.jobs > 1 {
+        crbrs_args.extend(["--jobs".to_string(), options.jobs.to_string()]);
+    }
+    crbrs_args.extend(["--summary".to_string(), SUMMARY_FILE.to_string(), "--".to_string()]);
+    crbrs_args.extend(programs.iter().map(|p| remote_path(&relative(p))));
+
+    log::info!("Sending {} files from {:?} to {} for {} programs", files.len(), base, host, programs.len());
+    let mut tar = Command::new("tar")
+        .env("COPYFILE_DISABLE", "1") // No AppleDouble `._` files from macOS tar
+        .arg("-cf")
+        .arg("-")
+        .arg("-C")
+        .arg(&base)
+        .arg("--") // A file named like an option is still a file
+        .args(files.iter().map(|f| relative(f)))
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| not_found("tar", e))?;
+    let archive = tar.stdout.take().expect("tar stdout is piped");
+    let ssh = Command::new("ssh")
+        .arg("--") // Nor is a host, e.g. `-oProxyCommand=...`
+        .arg(host)
+        .arg(format!("sh -c {}", quote(&remote_script(&crbrs_args))))
+        .stdin(archive)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| not_found("ssh", e))?;
+    let ssh = match cancel::wait_with_output(ssh) {
+        Ok(output) => output,
+        Err(e) => {
+            let _ = tar.kill();
+            let _ = tar.wait();
+            return Err(e);
+        }
+    };
+    let tar_status = tar.wait().map_err(Error::Subprocess)?;
+    if !tar_status.success() {
+        return Err(Error::Remote(format!("could not package the sources ({})", tar_status)));
+    }
+
+    let mut summary: BuildSummary = serde_json::from_slice(&ssh.stdout)
+        .map_err(|e| Error::Remote(format!("{} returned no build summary ({}; {})", host, ssh.status, e)))?;
+    for file in &mut summary.files {
+        file.file = base.join(&file.file);
+    }
+    summary.wall_time_ms = start.elapsed().as_millis() as u64;
+    Ok(summary)
+}
@@ -0,0 +1,135 @@
+// FILE: crbrs-lib/src/sandbox.rs
+
+//! Compiling programs whose files exist only in memory, e.g. a set of programs generated from
+//! templates (`crbrs compile --stdin-includes`, or `crbrs_compile_with_files` in the C API).
+//!
+//! The given files are written to a fresh directory, the sandbox, under their names, which are
+//! paths relative to it (`main.cr1x`, `generated/constants.cr1x`). A program named by one of
+//! them is compiled there, next to the others, so `Include "CPU:constants.cr1x"` finds the
+//! generated file. A program on disk is copied in along with the files it includes; given files
+//! take the place of included ones with the same name. The sandbox is a [`crate::temp::TempDir`],
+//! removed when dropped.
+
+use crate::build_state::inputs;
+use crate::temp::TempDir;
+use crate::Error;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// File contents keyed by path relative to the sandbox.
+pub type VirtualFiles = BTreeMap<String, String>;
+
+fn invalid(message: String) -> Error {
+    Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, message))
+}
+
+/// Reads files from a JSON object mapping each name to its text.
+pub fn parse_files(json: &str) -> Result<VirtualFiles, Error> {
+    serde_json::from_str(json).map_err(|e| invalid(format!("Expected a JSON object of file names and their text: {}", e)))
+}
+
+/// `name` as a path inside the sandbox, if it stays inside.
+fn relative(name: &str) -> Result<PathBuf, Error> {
+    let path = Path::new(name);
+    if name.is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(invalid(format!("'{}' is not a relative file name without '..'", name)));
+    }
+    Ok(path.to_path_buf())
+}
+
+#[derive(Debug)]
+pub struct Sandbox {
+    dir: TempDir,
+    names: BTreeMap<String, PathBuf>,
+    /// Programs copied in, and where they came from.
+    copied: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Sandbox {
+    /// Writes `files` to a new sandbox.
+    pub fn new(files: &VirtualFiles) -> Result<Self, Error> {
+        let mut sandbox = Sandbox { dir: TempDir::new("sandbox")?, names: BTreeMap::new(), copied: Vec::new() };
+        for (name, text) in files {
+            let path = sandbox.dir().join(relative(name)?);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, text)?;
+            sandbox.names.insert(name.clone(), path);
+        }
+        log::info!("Wrote {} file(s) to the sandbox {:?}", files.len(), sandbox.dir());
+        Ok(sandbox)
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Where to compile `program`: the given file of that name, or else a copy of the file on
+    /// disk, with the files it includes that were not given.
+    pub fn program(&mut self, program: &Path) -> Result<PathBuf, Error> {
+        if let Some(path) = self.names.get(&program.to_string_lossy().replace('\\', "/")) {
+            return Ok(path.clone());
+        }
+        if !program.is_file() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Input file not found, on disk or among the given files: {}", program.display()),
+            )));
+        }
+        let name = program.file_name().map(PathBuf::from).unwrap_or_default();
+        let copy = self.dir().join(&name);
+        let taken = self.names.values().any(|given| *given == copy)
+            || self.copied.iter().any(|(copy_of, original)| *copy_of == copy && original != program);
+        if taken {
+            return Err(invalid(format!("{} cannot be copied into the sandbox, which already has a file of that name", program.display())));
+        }
+        let dir = program.parent().unwrap_or(Path::new(""));
+        for input in inputs(program).iter().filter(|input| input.is_file()) {
+            let Ok(relative) = input.strip_prefix(dir) else { continue };
+            let target = self.dir().join(relative);
+            if input != program && target.exists() {
+                continue; // Given, or copied for another program
+            }
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(input, &target)?;
+        }
+        self.copied.push((copy.clone(), program.to_path_buf()));
+        Ok(copy)
+    }
+
+    /// The name `path` (in the sandbox) was given as, for messages.
+    pub fn original(&self, path: &Path) -> PathBuf {
+        if let Some((_, original)) = self.copied.iter().find(|(copy, _)| copy == path) {
+            return original.clone();
+        }
+        path.strip_prefix(self.dir()).map(Path::to_path_buf).unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    /// `error` naming the program as given instead of by its path in the sandbox.
+    pub fn original_error(&self, error: Error) -> Error {
+        match error {
+            Error::CompilationFailed { file_path, errors, raw_log } => {
+                Error::CompilationFailed { file_path: self.original(&file_path), errors, raw_log }
+            }
+            Error::GenericCompilationFailedWithLog { file_path, raw_log } => {
+                Error::GenericCompilationFailedWithLog { file_path: self.original(&file_path), raw_log }
+            }
+            Error::CompilationTimeout { file_path, seconds } => {
+                Error::CompilationTimeout { file_path: self.original(&file_path), seconds }
+            }
+            other => other,
+        }
+    }
+}
+
+/// Compiles `program` (a given file or one on disk, see [`Sandbox::program`]) with `files` in
+/// the sandbox. Errors name the program as given.
+pub fn compile(program: &Path, files: &VirtualFiles, compiler_id: Option<&str>, settings: &crate::Settings) -> Result<(), Error> {
+    let mut sandbox = Sandbox::new(files)?;
+    let path = sandbox.program(program)?;
+    crate::compiler::compile_file_impl(&path, None, compiler_id, settings).map_err(|e| sandbox.original_error(e))
+}
@@ -0,0 +1,229 @@
+// FILE: crbrs-lib/src/sarif.rs
+
+//! SARIF 2.1.0 output for `crbrs compile --format sarif`, so compiler errors show up as
+//! annotations in GitHub code scanning, Azure DevOps and other tools that read SARIF.
+//!
+//! Each [`CompilationErrorDetail`] becomes a result of the `compiler-error` rule at its file and
+//! line; a file that failed without errors the compiler's output could be parsed into (the
+//! compiler is missing, its output was unrecognized, ...) gets one `compile-failed` result
+//! without a line. Every result carries the crbrs error code as its `errorCode` property. Paths are written relative to the current directory, which in CI is the
+//! repository root.
+
+use crate::build::FileResult;
+use crate::CompilationErrorDetail;
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+pub const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+pub const VERSION: &str = "2.1.0";
+pub const COMPILER_ERROR_RULE: &str = "compiler-error";
+pub const COMPILE_FAILED_RULE: &str = "compile-failed";
+/// Code of [`crate::Error::CompilationFailed`], which every `compiler-error` result comes from.
+const COMPILER_ERROR_CODE: &str = "E014";
+
+/// How `crbrs compile` reports its results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompileFormat {
+    /// Messages for people.
+    #[default]
+    Text,
+    /// A SARIF log on stdout, and nothing else.
+    Sarif,
+}
+
+impl FromStr for CompileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(CompileFormat::Text),
+            "sarif" => Ok(CompileFormat::Sarif),
+            other => Err(format!("unknown output format '{}' (expected text or sarif)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SarifLog {
+    #[serde(rename = "$schema")]
+    pub schema: String,
+    pub version: String,
+    pub runs: Vec<Run>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Run {
+    pub tool: Tool,
+    pub results: Vec<SarifResult>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tool {
+    pub driver: Driver,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Driver {
+    pub name: String,
+    pub version: String,
+    pub information_uri: String,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub id: String,
+    pub short_description: Message,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifResult {
+    pub rule_id: String,
+    pub level: String,
+    pub message: Message,
+    pub locations: Vec<Location>,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Properties {
+    /// The crbrs error code, see `crbrs explain`.
+    pub error_code: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub physical_location: PhysicalLocation,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhysicalLocation {
+    pub artifact_location: ArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<Region>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtifactLocation {
+    pub uri: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Region {
+    pub start_line: u32,
+}
+
+fn rule(id: &str, description: &str) -> Rule {
+    Rule { id: id.to_string(), short_description: Message { text: description.to_string() } }
+}
+
+/// Percent-encodes what may not appear in a URI path as is.
+fn encode(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// `file` as an artifact URI: relative (with `/` separators) below the current directory,
+/// a `file://` URI otherwise.
+pub fn artifact_uri(file: &Path) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let relative = if file.is_absolute() { file.strip_prefix(&cwd).ok() } else { Some(file) };
+    match relative {
+        Some(relative) => {
+            let parts: Vec<String> = relative
+                .components()
+                .filter(|c| !matches!(c, Component::CurDir))
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            encode(&parts.join("/"))
+        }
+        None => {
+            let absolute = file.to_string_lossy().replace('\\', "/");
+            format!("file://{}{}", if absolute.starts_with('/') { "" } else { "/" }, encode(&absolute))
+        }
+    }
+}
+
+/// The file an error is in: usually the compiled `file`, but the compiler may name another
+/// one next to it, e.g. an included file.
+fn error_file(file: &Path, error: &CompilationErrorDetail) -> PathBuf {
+    let named = Path::new(error.file_path_in_log.trim());
+    match (named.file_name(), file.parent()) {
+        (Some(name), Some(dir)) if Some(name) != file.file_name() && dir.join(name).is_file() => dir.join(name),
+        _ => file.to_path_buf(),
+    }
+}
+
+fn result(rule_id: &str, file: &Path, line: Option<u32>, message: &str, error_code: &str) -> SarifResult {
+    SarifResult {
+        rule_id: rule_id.to_string(),
+        level: "error".to_string(),
+        message: Message { text: message.trim().to_string() },
+        locations: vec![Location {
+            physical_location: PhysicalLocation {
+                artifact_location: ArtifactLocation { uri: artifact_uri(file) },
+                region: line.filter(|line| *line > 0).map(|start_line| Region { start_line }),
+            },
+        }],
+        properties: Properties { error_code: error_code.to_string() },
+    }
+}
+
+/// The result for a compiler error in `file`.
+pub fn compiler_error(file: &Path, error: &CompilationErrorDetail) -> SarifResult {
+    result(COMPILER_ERROR_RULE, &error_file(file, error), error.line, &error.message, COMPILER_ERROR_CODE)
+}
+
+/// The SARIF log of a batch compile.
+pub fn from_build(files: &[FileResult]) -> SarifLog {
+    let mut results = Vec::new();
+    for file in files {
+        if !file.errors.is_empty() {
+            results.extend(file.errors.iter().map(|error| compiler_error(&file.file, error)));
+        } else if let Some(error) = &file.error {
+            let code = file.error_code.as_deref().unwrap_or_default();
+            results.push(result(COMPILE_FAILED_RULE, &file.file, None, error, code));
+        }
+    }
+    SarifLog {
+        schema: SCHEMA.to_string(),
+        version: VERSION.to_string(),
+        runs: vec![Run {
+            tool: Tool {
+                driver: Driver {
+                    name: "crbrs".to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    information_uri: "https://github.com/RileyLeff/crbrs".to_string(),
+                    rules: vec![
+                        rule(COMPILER_ERROR_RULE, "The CRBasic compiler reported an error"),
+                        rule(COMPILE_FAILED_RULE, "The program could not be compiled"),
+                    ],
+                },
+            },
+            results,
+        }],
+    }
+}
+
+impl SarifLog {
+    pub fn to_json(&self) -> Result<String, crate::Error> {
+        serde_json::to_string_pretty(self).map_err(|e| crate::Error::Io(e.into()))
+    }
+}
@@ -0,0 +1,131 @@
+// FILE: crbrs-lib/src/secret.rs
+
+//! Secrets (repository access tokens, logger security codes) kept in the OS keychain rather
+//! than in the config file, which anyone using a shared field laptop can read.
+//!
+//! The config refers to secrets by name, and `crbrs secret set/get/remove <name>` manages them:
+//!
+//! ```toml
+//! [stations.North]
+//! address = "tcp:10.0.0.5:6785"
+//! security_code_secret = "north-code"
+//!
+//! [network.tokens]
+//! "github.com" = "github-token"   # sent as a bearer token to this host only
+//! ```
+//!
+//! The keychain is the macOS keychain, the Secret Service on Linux (GNOME Keyring, KWallet) or
+//! the Windows credential manager, reached through the `keyring` crate. A secret can also be
+//! given in the environment as `CRBRS_SECRET_<NAME>` (upper case, other characters as `_`),
+//! which is looked at first, e.g. on CI machines without a keychain.
+
+use crate::Error;
+use keyring::Entry;
+
+/// The service secrets are filed under in the keychain.
+const SERVICE: &str = "crbrs";
+
+/// The environment variable that can hold secret `name`.
+pub fn env_var(name: &str) -> String {
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect();
+    format!("CRBRS_SECRET_{}", name)
+}
+
+fn check_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        return Err(Error::Secret(format!("'{}' is not a secret name (use letters, digits, '-', '_' and '.')", name)));
+    }
+    Ok(())
+}
+
+/// The keychain entry of secret `name`.
+fn entry(name: &str) -> Result<Entry, Error> {
+    check_name(name)?;
+    Entry::new(SERVICE, name).map_err(|e| failed("opening the keychain", name, e))
+}
+
+fn failed(what: &str, name: &str, error: keyring::Error) -> Error {
+    match error {
+        keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_) => Error::Secret(format!(
+            "{} failed: no keychain available ({}); give the secret as {} instead",
+            what,
+            error,
+            env_var(name)
+        )),
+        error => Error::Secret(format!("{} failed: {}", what, error)),
+    }
+}
+
+/// Stores secret `name` in the keychain, replacing any previous value.
+pub fn set(name: &str, value: &str) -> Result<(), Error> {
+    entry(name)?.set_password(value).map_err(|e| failed("storing the secret", name, e))?;
+    log::info!("Stored secret '{}' in the keychain", name);
+    Ok(())
+}
+
+/// Secret `name` from the environment or the keychain, if it is set.
+pub fn get(name: &str) -> Result<Option<String>, Error> {
+    check_name(name)?;
+    if let Ok(value) = std::env::var(env_var(name)) {
+        log::debug!("Secret '{}' from {}", name, env_var(name));
+        return Ok(Some(value));
+    }
+    match entry(name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(failed("reading the secret", name, e)),
+    }
+}
+
+/// Secret `name`, or an error saying how to set it.
+pub fn require(name: &str) -> Result<String, Error> {
+    get(name)?.ok_or_else(|| {
+        Error::Secret(format!("secret '{}' is not set (set it with `crbrs secret set {}` or {})", name, name, env_var(name)))
+    })
+}
+
+/// Deletes secret `name` from the keychain; `false` if it wasn't there.
+pub fn remove(name: &str) -> Result<bool, Error> {
+    match entry(name)?.delete_credential() {
+        Ok(()) => {
+            log::info!("Removed secret '{}' from the keychain", name);
+            Ok(true)
+        }
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(failed("removing the secret", name, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_var_names_are_upper_case() {
+        assert_eq!(env_var("north-code"), "CRBRS_SECRET_NORTH_CODE");
+        assert_eq!(env_var("github.com_token"), "CRBRS_SECRET_GITHUB_COM_TOKEN");
+    }
+
+    #[test]
+    fn only_plain_names_are_secret_names() {
+        for name in ["north-code", "github.com_token", "A1"] {
+            assert!(check_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "north code", "../north", "north\ncode"] {
+            assert!(matches!(check_name(name), Err(Error::Secret(_))), "{:?}", name);
+        }
+        // Checked before the keychain is touched.
+        assert!(set("north code", "1234").unwrap_err().to_string().contains("is not a secret name"));
+        assert!(remove("").unwrap_err().to_string().contains("is not a secret name"));
+    }
+
+    #[test]
+    fn the_environment_takes_precedence_over_the_keychain() {
+        // A name no other test uses, so setting its variable doesn't affect them.
+        let name = "crbrs-test-environment-override";
+        std::env::set_var(env_var(name), "s3cret");
+        let value = require(name);
+        std::env::remove_var(env_var(name));
+        assert_eq!(value.unwrap(), "s3cret");
+    }
+}
@@ -0,0 +1,91 @@
+// FILE: crbrs-lib/src/send.rs
+
+//! `crbrs logger send`: writes a program to the logger's CPU drive with File Send and has the
+//! logger compile and run it (and run it on power-up) with File Control. Unless told not to,
+//! the program running on the logger is read back first and the new one checked against it
+//! (see [`crate::deploy`]).
+//!
+//! Every send, failed or not, is recorded in the station sessions of the history (see
+//! [`crate::history::StationSession`]).
+
+use crate::clock;
+use crate::deploy;
+use crate::history::{self, Operation, StationSession};
+use crate::logger::Logger;
+use crate::network::NetworkSettings;
+use crate::pakbus;
+use crate::station::{self, Station};
+use crate::syntax::{parse, version};
+use crate::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Instant, SystemTime};
+
+/// The outcome of a successful send.
+#[derive(Debug, Clone)]
+pub struct SendReport {
+    /// Name of the program on the logger, e.g. `CPU:soil.cr1x`.
+    pub file_name: String,
+    /// Seconds the logger asked to be left alone for while it compiles.
+    pub hold_off_secs: u16,
+    pub session: StationSession,
+}
+
+/// Sends `program` to `station` (named `name`) and, if `run` is set, compiles and runs it.
+///
+/// With `guard`, the program is first checked against the one running on the logger; a
+/// blocked send goes ahead only if `guard` (given the reason) says so.
+pub fn send_program(
+    name: &str,
+    station: &Station,
+    network: &NetworkSettings,
+    program: &Path,
+    run: bool,
+    guard: Option<deploy::ConfirmBlocked>,
+) -> Result<SendReport, Error> {
+    let data = fs::read(program)?;
+    let base_name = program.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let file_name = format!("CPU:{}", base_name);
+    let mut session = StationSession {
+        station: name.to_string(),
+        address: station.address.clone(),
+        operation: Operation::Send,
+        started: clock::timestamp(SystemTime::now()),
+        duration_ms: 0,
+        file: Some(history::file_key(program)),
+        program_version: version::header(&parse(&String::from_utf8_lossy(&data))).map(|h| h.version),
+        compiler: history::last_build(program).map(|b| format!("{} ({})", b.compiler_id, b.version)),
+        bytes_sent: 0,
+        bytes_received: 0,
+        error: None,
+    };
+
+    let started = Instant::now();
+    let mut logger = None;
+    let result = (|| -> Result<u16, Error> {
+        let logger = logger.insert(Logger::connect(station, network)?);
+        if let Err(e) = station::remember_baud(name, station, logger.endpoint()) {
+            log::warn!("Could not save the baud rate of {}: {}", name, e);
+        }
+        if let Some(confirm) = guard {
+            deploy::guard_logger(logger, &String::from_utf8_lossy(&data), confirm)?;
+        }
+        log::info!("Sending {} ({} bytes) to {} as {}", program.display(), data.len(), name, file_name);
+        logger.send_file(&file_name, &data)?;
+        if !run {
+            return Ok(0);
+        }
+        logger.control_file(&file_name, pakbus::FILE_COMPILE_AND_RUN)
+    })();
+    session.duration_ms = started.elapsed().as_millis() as u64;
+    if let Some(logger) = &logger {
+        session.address = logger.endpoint().to_string();
+        (session.bytes_sent, session.bytes_received) = logger.traffic();
+    }
+    session.error = result.as_ref().err().map(|e| e.to_string());
+    if let Err(e) = history::record_session(session.clone()) {
+        log::warn!("Could not record the send to {} in the history: {}", name, e);
+    }
+    let hold_off_secs = result?;
+    Ok(SendReport { file_name, hold_off_secs, session })
+}
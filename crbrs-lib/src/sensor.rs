@@ -0,0 +1,90 @@
+// FILE: crbrs-lib/src/sensor.rs
+
+//! `crbrs sensor add` and `crbrs sensor import-modbus`: inserts the code for an SDI-12 sensor
+//! or Modbus device into a program, or prints it (see `crbrs_syntax::sensor`).
+
+use crate::syntax::refactor::{apply_edits, unused_name};
+use crate::syntax::sensor::{self, ModbusDevice, Placement, Processing, RegisterEntry, SensorBlock, SensorValue};
+use crate::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub enum SensorSpec {
+    /// A built-in `model`, or any sensor with explicit `values`; those (and `command`, if
+    /// given) override the model's.
+    Sdi12 { model: Option<String>, address: char, port: String, command: Option<String>, values: Vec<SensorValue> },
+    Modbus { device: ModbusDevice, start: u32, values: Vec<SensorValue> },
+    /// A Modbus device described by the register map at `map`, read with [`read_register_map`].
+    RegisterMap { port: String, baud: u32, address: u8, map: PathBuf, entries: Vec<RegisterEntry> },
+}
+
+/// A program with a sensor added.
+#[derive(Debug, Clone)]
+pub struct AddedSensor {
+    /// Name of the array holding the sensor's values.
+    pub name: String,
+    pub source: String,
+}
+
+fn block(spec: &SensorSpec, name: Option<&str>, source: &str) -> Result<SensorBlock, String> {
+    match spec {
+        SensorSpec::Sdi12 { model, address, port, command, values } => {
+            let model = match model {
+                Some(model) => Some(sensor::sdi12_model(model).ok_or_else(|| {
+                    let known: Vec<&str> = sensor::sdi12_models().iter().map(|m| m.name).collect();
+                    format!("unknown SDI-12 model '{}' (built in: {}); give its values with --values instead", model, known.join(", "))
+                })?),
+                None => None,
+            };
+            let values = match model {
+                Some(model) if values.is_empty() => model
+                    .values
+                    .iter()
+                    .map(|(suffix, units)| SensorValue { suffix: suffix.to_string(), units: Some(units.to_string()) })
+                    .collect(),
+                _ => values.clone(),
+            };
+            let base = format!("{}_{}", model.map_or("SDI12", |m| m.name), address);
+            let name = name.map_or_else(|| unused_name(source, &base), str::to_string);
+            let command = command.as_deref().or(model.map(|m| m.command)).unwrap_or("M!");
+            sensor::sdi12(&name, model.map_or("SDI-12 sensor", |m| m.name), *address, port, command, values)
+        }
+        SensorSpec::Modbus { device, start, values } => {
+            let name = name.map_or_else(|| unused_name(source, &format!("Modbus_{}", device.address)), str::to_string);
+            sensor::modbus(&name, "Modbus device", device, *start, values.clone())
+        }
+        SensorSpec::RegisterMap { port, baud, address, map, entries } => {
+            let name = name.map_or_else(|| unused_name(source, &format!("Modbus_{}", address)), str::to_string);
+            let description = format!("Modbus device ({})", map.file_name().unwrap_or_default().to_string_lossy());
+            sensor::register_map(&name, &description, port, *baud, *address, entries)
+        }
+    }
+}
+
+/// Reads the register map CSV at `path`; plain register numbers use `default_function`.
+pub fn read_register_map(path: &Path, default_function: u8) -> Result<Vec<RegisterEntry>, Error> {
+    let text = fs::read_to_string(path)?;
+    sensor::parse_register_map(&text, default_function).map_err(|e| Error::Sensor(format!("{}: {}", path.display(), e)))
+}
+
+/// The sensor's code as text to paste into a program, named `name` or after the model and
+/// address.
+pub fn snippet(spec: &SensorSpec, name: Option<&str>, processing: Processing) -> Result<String, Error> {
+    Ok(block(spec, name, "").map_err(Error::Sensor)?.snippet(processing))
+}
+
+/// Adds the sensor to the program at `path`, named `name` or after the model and address.
+/// Writes the file unless `dry_run` is set.
+pub fn add_to_file(path: &Path, spec: &SensorSpec, name: Option<&str>, placement: &Placement, dry_run: bool) -> Result<AddedSensor, Error> {
+    let source = fs::read_to_string(path)?;
+    let fail = |e: String| Error::Sensor(format!("{}: {}", path.display(), e));
+    let block = block(spec, name, &source).map_err(fail)?;
+    let edits = sensor::add_sensor(&source, &block, placement).map_err(fail)?;
+    let updated = apply_edits(&source, &edits);
+    if !dry_run {
+        fs::write(path, &updated)?;
+        log::info!("Added sensor '{}' to {:?}", block.name, path);
+    }
+    Ok(AddedSensor { name: block.name, source: updated })
+}
@@ -0,0 +1,266 @@
+// FILE: crbrs-lib/src/session_log.rs
+
+//! Timestamped logs of interactive logger sessions (`crbrs logger terminal`), kept under the data
+//! directory so field debugging sessions leave an audit trail.
+//!
+//! Each line is `<RFC 3339 timestamp> <direction> <text>`, where direction is `>` for data
+//! sent to the logger, `<` for data received and `#` for notes from crbrs itself. A session
+//! rolls over to a new file once the current one exceeds `max_bytes` or `max_age_secs`;
+//! the oldest files beyond `max_files` are deleted.
+
+use crate::clock;
+use crate::config;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// `[session_log]` settings.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SessionLogSettings {
+    pub enabled: bool,
+    /// Start a new file once the current one is larger than this.
+    pub max_bytes: u64,
+    /// Start a new file once the current one is older than this.
+    pub max_age_secs: u64,
+    /// Session files kept in total; older ones are deleted on rotation.
+    pub max_files: usize,
+}
+
+impl Default for SessionLogSettings {
+    fn default() -> Self {
+        SessionLogSettings {
+            enabled: true,
+            max_bytes: 5 * 1024 * 1024,
+            max_age_secs: 24 * 60 * 60,
+            max_files: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+    Note,
+}
+
+impl Direction {
+    fn marker(self) -> char {
+        match self {
+            Direction::Sent => '>',
+            Direction::Received => '<',
+            Direction::Note => '#',
+        }
+    }
+}
+
+/// Directory holding session logs.
+pub fn session_log_dir() -> Result<PathBuf, Error> {
+    Ok(config::data_dir()?.join("sessions"))
+}
+
+/// An open session log.
+pub struct SessionLog {
+    dir: PathBuf,
+    /// File name prefix: `<kind>-<target>`.
+    prefix: String,
+    settings: SessionLogSettings,
+    file: File,
+    path: PathBuf,
+    written: u64,
+    opened: SystemTime,
+    part: u32,
+}
+
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+impl SessionLog {
+    /// Starts a session log for `kind` (`terminal`, `monitor`) against `target` (port or host)
+    /// in the default session directory.
+    pub fn start(kind: &str, target: &str, settings: &SessionLogSettings) -> Result<Self, Error> {
+        Self::start_in(&session_log_dir()?, kind, target, settings)
+    }
+
+    pub fn start_in(dir: &Path, kind: &str, target: &str, settings: &SessionLogSettings) -> Result<Self, Error> {
+        fs::create_dir_all(dir)?;
+        let prefix = format!("{}-{}", sanitize(kind), sanitize(target));
+        let (file, path) = Self::open(dir, &prefix, 0)?;
+        let session = SessionLog {
+            dir: dir.to_path_buf(),
+            prefix,
+            settings: settings.clone(),
+            file,
+            path,
+            written: 0,
+            opened: SystemTime::now(),
+            part: 0,
+        };
+        session.prune()?;
+        Ok(session)
+    }
+
+    fn open(dir: &Path, prefix: &str, part: u32) -> Result<(File, PathBuf), Error> {
+        let path = dir.join(format!("{}-{}-{}.log", prefix, clock::file_stamp(SystemTime::now()), part));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        log::debug!("Session log file: {:?}", path);
+        Ok((file, path))
+    }
+
+    /// Path of the file currently written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends one timestamped line, rotating first if the current file is full or too old.
+    pub fn write_line(&mut self, direction: Direction, text: &str) -> Result<(), Error> {
+        let too_old = self.opened.elapsed().unwrap_or_default() >= Duration::from_secs(self.settings.max_age_secs);
+        if self.written >= self.settings.max_bytes || too_old {
+            self.rotate()?;
+        }
+        let line = format!("{} {} {}\n", clock::timestamp(SystemTime::now()), direction.marker(), text.trim_end_matches(['\r', '\n']));
+        self.file.write_all(line.as_bytes())?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        self.file.flush()?;
+        self.part += 1;
+        let (file, path) = Self::open(&self.dir, &self.prefix, self.part)?;
+        log::info!("Rotating session log to {:?}", path);
+        self.file = file;
+        self.path = path;
+        self.written = 0;
+        self.opened = SystemTime::now();
+        self.prune()
+    }
+
+    /// Deletes the oldest session files beyond `max_files`.
+    fn prune(&self) -> Result<(), Error> {
+        let sessions = list_sessions_in(&self.dir)?;
+        let excess = sessions.len().saturating_sub(self.settings.max_files.max(1));
+        for old in sessions.iter().take(excess).filter(|s| s.path != self.path) {
+            log::debug!("Removing old session log {:?}", old.path);
+            fs::remove_file(&old.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// A session log file on disk.
+#[derive(Debug, Clone)]
+pub struct SessionFile {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Session log files in the default directory, oldest first.
+pub fn list_sessions() -> Result<Vec<SessionFile>, Error> {
+    list_sessions_in(&session_log_dir()?)
+}
+
+pub fn list_sessions_in(dir: &Path) -> Result<Vec<SessionFile>, Error> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut out = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("log") {
+            continue;
+        }
+        let meta = entry.metadata()?;
+        out.push(SessionFile {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            path,
+            size: meta.len(),
+            modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        });
+    }
+    out.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.name.cmp(&b.name)));
+    Ok(out)
+}
+
+/// Lines of the given session files containing `pattern` (case-insensitive), or every line
+/// if `pattern` is `None`, as `(file name, line)` pairs.
+pub fn search(sessions: &[SessionFile], pattern: Option<&str>) -> Result<Vec<(String, String)>, Error> {
+    let needle = pattern.map(str::to_lowercase);
+    let mut out = Vec::new();
+    for session in sessions {
+        for line in BufReader::new(File::open(&session.path)?).lines() {
+            let line = line?;
+            if needle.as_ref().is_none_or(|n| line.to_lowercase().contains(n)) {
+                out.push((session.name.clone(), line));
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn settings(max_bytes: u64, max_age_secs: u64, max_files: usize) -> SessionLogSettings {
+        SessionLogSettings { enabled: true, max_bytes, max_age_secs, max_files }
+    }
+
+    fn lines(dir: &Path) -> Vec<Vec<String>> {
+        list_sessions_in(dir)
+            .unwrap()
+            .iter()
+            .map(|s| fs::read_to_string(&s.path).unwrap().lines().map(|l| l.split_once(' ').unwrap().1.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn sessions_rotate_once_a_file_is_full() {
+        let dir = TempDir::new().unwrap();
+        let mut log = SessionLog::start_in(dir.path(), "terminal", "North", &settings(40, 3600, 10)).unwrap();
+        let first = log.path().to_path_buf();
+        log.write_line(Direction::Sent, "1").unwrap();
+        log.write_line(Direction::Received, "CR1000X>").unwrap();
+        assert_eq!(log.path(), first);
+        log.write_line(Direction::Sent, "9").unwrap();
+        assert_ne!(log.path(), first);
+        assert_eq!(lines(dir.path()), vec![vec!["> 1", "< CR1000X>"], vec!["> 9"]]);
+    }
+
+    #[test]
+    fn sessions_rotate_once_a_file_is_too_old() {
+        let dir = TempDir::new().unwrap();
+        let mut log = SessionLog::start_in(dir.path(), "terminal", "North", &settings(1 << 20, 0, 10)).unwrap();
+        log.write_line(Direction::Sent, "1").unwrap();
+        log.write_line(Direction::Sent, "2").unwrap();
+        // The empty first file, then one file per line.
+        assert_eq!(lines(dir.path()), vec![vec![], vec!["> 1"], vec!["> 2"]]);
+    }
+
+    #[test]
+    fn rotation_keeps_only_the_newest_files() {
+        let dir = TempDir::new().unwrap();
+        let old = File::create(dir.path().join("terminal-South-old.log")).unwrap();
+        old.set_modified(SystemTime::now() - Duration::from_secs(3600)).unwrap();
+        let mut log = SessionLog::start_in(dir.path(), "terminal", "North", &settings(1, 3600, 2)).unwrap();
+        for command in ["1", "2", "3", "4"] {
+            log.write_line(Direction::Sent, command).unwrap();
+        }
+        let sessions = list_sessions_in(dir.path()).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions.last().unwrap().path, log.path());
+        assert_eq!(lines(dir.path()), vec![vec!["> 3"], vec!["> 4"]]);
+    }
+}
@@ -0,0 +1,112 @@
+// FILE: crbrs-lib/src/signature.rs
+
+//! Detached signatures of compiler archives, checked before they are unpacked.
+//!
+//! A manifest entry with a `signature_url` and `public_key` is verified against the key the
+//! entry names, in one of two schemes told apart by the key itself:
+//!
+//! - minisign: the public key as base64 (`RW...`) or the whole `.pub` file, and a `.minisig`
+//!   signature. Checked in-process.
+//! - OpenPGP: an ASCII-armored public key (`-----BEGIN PGP PUBLIC KEY BLOCK-----`) and a
+//!   detached `.sig`/`.asc` signature. Checked by `gpg` in a throwaway keyring, so the user's
+//!   own keyring is neither read nor changed.
+
+use crate::Error;
+use minisign_verify::{PublicKey, Signature};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const PGP_KEY_HEADER: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----";
+
+/// Checks the detached `signature` of `data` against `public_key`; `Err` says why it fails.
+pub fn verify(data: &[u8], signature: &[u8], public_key: &str) -> Result<(), String> {
+    if public_key.trim_start().starts_with(PGP_KEY_HEADER) {
+        verify_pgp(data, signature, public_key)
+    } else {
+        verify_minisign(data, signature, public_key)
+    }
+}
+
+fn verify_minisign(data: &[u8], signature: &[u8], public_key: &str) -> Result<(), String> {
+    let public_key = if public_key.contains("untrusted comment:") {
+        PublicKey::decode(public_key.trim())
+    } else {
+        PublicKey::from_base64(public_key.trim())
+    }
+    .map_err(|e| format!("invalid minisign public key: {}", e))?;
+    let signature = std::str::from_utf8(signature)
+        .map_err(|_| "the signature is not a minisign signature".to_string())
+        .and_then(|text| Signature::decode(text).map_err(|e| format!("invalid minisign signature: {}", e)))?;
+    public_key.verify(data, &signature, false).map_err(|e| e.to_string())
+}
+
+fn gpg(home: &Path, args: &[&std::ffi::OsStr]) -> Result<std::process::Output, String> {
+    Command::new("gpg")
+        .arg("--batch")
+        .arg("--no-tty")
+        .arg("--homedir")
+        .arg(home)
+        .args(args)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "`gpg` was not found in PATH; it is needed to check OpenPGP signatures".to_string(),
+            _ => format!("could not run gpg: {}", e),
+        })
+}
+
+fn verify_pgp(data: &[u8], signature: &[u8], public_key: &str) -> Result<(), String> {
+    let dir = crate::temp::TempDir::new("gpg").map_err(|e| format!("could not create a GnuPG home: {}", e))?;
+    let home = dir.path();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(home, fs::Permissions::from_mode(0o700)); // gpg warns otherwise
+    }
+    let (key, sig, file) = (home.join("key.asc"), home.join("archive.sig"), home.join("archive"));
+    for (path, contents) in [(&key, public_key.as_bytes()), (&sig, signature), (&file, data)] {
+        fs::write(path, contents).map_err(|e| e.to_string())?;
+    }
+    let import = gpg(home, &["--import".as_ref(), key.as_os_str()])?;
+    if !import.status.success() {
+        return Err(format!("invalid OpenPGP public key: {}", String::from_utf8_lossy(&import.stderr).trim()));
+    }
+    let verify = gpg(home, &["--status-fd".as_ref(), "1".as_ref(), "--verify".as_ref(), sig.as_os_str(), file.as_os_str()])?;
+    // The key is only trusted through the manifest, so a valid signature is enough.
+    if verify.status.success() && String::from_utf8_lossy(&verify.stdout).lines().any(|l| l.starts_with("[GNUPG:] VALIDSIG")) {
+        Ok(())
+    } else {
+        let reason = String::from_utf8_lossy(&verify.stderr);
+        Err(reason.lines().find(|l| l.contains("BAD") || l.contains("Can't check")).unwrap_or("no valid signature").trim().to_string())
+    }
+}
+
+/// [`verify`] for the archive of `compiler_id`, as an [`Error::SignatureInvalid`].
+pub fn verify_archive(compiler_id: &str, archive: &[u8], signature: &[u8], public_key: &str) -> Result<(), Error> {
+    verify(archive, signature, public_key)
+        .map_err(|reason| Error::SignatureInvalid { compiler_id: compiler_id.to_string(), reason })?;
+    log::info!("Signature of the archive of '{}' verified.", compiler_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLIC_KEY: &str = "RWQBAgMEBQYHCAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQBAgMEBQYHCJIK5z6mWytH98xp5BdzCkelZlUHEiMqDL3LKO9oAG50NcIT7chJRS1isHqUofwJ6dzoo1E4Ezx4Ue4guoha9gg=
+trusted comment: timestamp:1760000000\tfile:archive.zip\thashed
+Iezj1Nr4GQyUSeAgVmLSP0LLBtTsP4cVqzi/mm/7v/dwTD3gpuCSMS7mJZ2zwWxYP+qC5wcrt0WBtDIIP8o/AQ==
+";
+
+    #[test]
+    fn minisign_signatures_verify_only_the_signed_data() {
+        let data = b"crbrs compiler archive\n";
+        assert_eq!(verify(data, SIGNATURE.as_bytes(), PUBLIC_KEY), Ok(()));
+        let public_key_file = format!("untrusted comment: minisign public key 0807060504030201\n{}\n", PUBLIC_KEY);
+        assert_eq!(verify(data, SIGNATURE.as_bytes(), &public_key_file), Ok(()));
+        assert!(verify(b"crbrs compiler archive, altered\n", SIGNATURE.as_bytes(), PUBLIC_KEY).is_err());
+        assert!(verify(data, b"not a signature", PUBLIC_KEY).unwrap_err().contains("invalid minisign signature"));
+    }
+}
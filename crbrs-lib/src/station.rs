@@ -0,0 +1,124 @@
+// FILE: crbrs-lib/src/station.rs
+
+//! The station registry: loggers crbrs talks to, by name, under `[stations.<name>]`.
+//!
+//! ```toml
+//! [stations.North]
+//! address = "tcp:10.0.0.5:6785"   # or serial:/dev/ttyUSB0@115200, COM3, ...
+//! pakbus_address = 1
+//! security_code = 0       # or keep it in the keychain: security_code_secret = "north-code"
+//! min_battery = 12.0       # alert thresholds; see crate::status::Thresholds for the defaults
+//! max_skipped_scans = 10
+//! ```
+//!
+//! Commands that talk to a logger take a station name or, for one-off connections, an
+//! address (see [`crate::logger::Endpoint`]). A station with a `serial:PORT@auto` address gets
+//! the baud rate detected on its first connection written into its address.
+
+use crate::logger::Endpoint;
+use crate::pakbus;
+use crate::secret;
+use crate::status::Thresholds;
+use crate::{config, Error, Settings};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Station {
+    /// Where the logger is reached, e.g. `tcp:10.0.0.5:6785` or `serial:/dev/ttyUSB0`.
+    pub address: String,
+    pub pakbus_address: u16,
+    /// Security code of the logger; 0 if it has none.
+    pub security_code: u16,
+    /// Name of the secret holding the security code (see [`crate::secret`]); overrides
+    /// `security_code`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_code_secret: Option<String>,
+    /// Supply voltage below which the station is in alert, V.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_battery: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_lithium_battery: Option<f64>,
+    /// Skipped scans above which the station is in alert.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_skipped_scans: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_watchdog_errors: Option<u64>,
+}
+
+impl Default for Station {
+    fn default() -> Self {
+        Station {
+            address: String::new(),
+            pakbus_address: pakbus::DEFAULT_ADDRESS,
+            security_code: 0,
+            security_code_secret: None,
+            min_battery: None,
+            min_lithium_battery: None,
+            max_skipped_scans: None,
+            max_watchdog_errors: None,
+        }
+    }
+}
+
+impl Station {
+    /// The security code, looked up in the keychain if the station names a secret for it.
+    pub fn resolve_security_code(&self) -> Result<u16, Error> {
+        let Some(name) = &self.security_code_secret else { return Ok(self.security_code) };
+        let code = secret::require(name)?;
+        code.trim().parse().map_err(|_| Error::Secret(format!("secret '{}' is not a security code (0-65535)", name)))
+    }
+
+    /// The default thresholds with the station's own ones applied.
+    pub fn thresholds(&self) -> Thresholds {
+        let defaults = Thresholds::default();
+        Thresholds {
+            min_battery: self.min_battery.unwrap_or(defaults.min_battery),
+            min_lithium_battery: self.min_lithium_battery.unwrap_or(defaults.min_lithium_battery),
+            max_skipped_scans: self.max_skipped_scans.unwrap_or(defaults.max_skipped_scans),
+            max_watchdog_errors: self.max_watchdog_errors.unwrap_or(defaults.max_watchdog_errors),
+            ..defaults
+        }
+    }
+}
+
+/// The station named `target` in the registry (names match case-insensitively), or a
+/// station at address `target`. Returns the station's name (the address for unregistered
+/// ones) and its settings.
+pub fn resolve(settings: &Settings, target: &str) -> Result<(String, Station), Error> {
+    if let Some((name, station)) = settings.stations.iter().find(|(name, _)| name.eq_ignore_ascii_case(target)) {
+        return Ok((name.clone(), station.clone()));
+    }
+    match target.parse::<Endpoint>() {
+        Ok(_) => Ok((target.to_string(), Station { address: target.to_string(), ..Station::default() })),
+        Err(_) => Err(Error::Logger(format!(
+            "'{}' is neither a station in [stations] nor a logger address like tcp:10.0.0.5:6785 or serial:/dev/ttyUSB0",
+            target
+        ))),
+    }
+}
+
+/// Serializes [`remember_baud`] between the threads of a fleet command.
+static SAVING: Mutex<()> = Mutex::new(());
+
+/// Saves the baud rate found for station `name` on `dialed` into its address, if the address
+/// leaves it to `auto`, so later connections don't have to probe.
+pub fn remember_baud(name: &str, station: &Station, dialed: &Endpoint) -> Result<(), Error> {
+    let auto = matches!(station.address.parse(), Ok(Endpoint::Serial { baud: None, .. }));
+    if !auto || !matches!(dialed, Endpoint::Serial { baud: Some(_), .. }) {
+        return Ok(());
+    }
+    let _saving = SAVING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    // Reload, so settings changed since this process started are kept.
+    let mut settings = config::load_settings()?;
+    let Some(entry) = settings.stations.iter_mut().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, s)| s) else {
+        return Ok(());
+    };
+    if entry.address != station.address {
+        return Ok(());
+    }
+    log::info!("Saving the detected address of station {}: {}", name, dialed);
+    entry.address = dialed.to_string();
+    config::save_settings(&settings)
+}
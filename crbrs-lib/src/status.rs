@@ -0,0 +1,181 @@
+// FILE: crbrs-lib/src/status.rs
+
+//! `crbrs logger status`: a health snapshot of a logger from its `Status` table: supply and
+//! lithium battery voltages, panel temperature, skipped scans, watchdog errors and whether
+//! the running program compiled.
+//!
+//! Values are read one by one with Get Values, so fields an OS does not have (a CR300 has no
+//! lithium battery) are simply missing rather than failing the query.
+
+use crate::logger::Logger;
+use crate::network::NetworkSettings;
+use crate::pakbus::{Value, ValueKind};
+use crate::station::Station;
+use crate::Error;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Health {
+    Ok,
+    Warning,
+    Critical,
+}
+
+/// Values from the logger's `Status` table; `None` where the logger has no such field.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Status {
+    pub os_version: Option<String>,
+    pub program: Option<String>,
+    pub compile_result: Option<String>,
+    /// Supply voltage, V.
+    pub battery: Option<f64>,
+    /// Voltage of the internal lithium battery that keeps the clock and SRAM, V.
+    pub lithium_battery: Option<f64>,
+    /// Wiring panel temperature, °C.
+    pub panel_temp: Option<f64>,
+    pub skipped_scans: Option<u64>,
+    pub watchdog_errors: Option<u64>,
+}
+
+/// Limits a status is judged by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Below this supply voltage measurements become unreliable; loggers stop at about 9.6 V.
+    pub min_battery: f64,
+    /// Campbell recommends replacing the lithium battery below 2.7 V.
+    pub min_lithium_battery: f64,
+    /// Operating range of standard-temperature loggers, °C.
+    pub panel_temp_range: (f64, f64),
+    pub max_skipped_scans: u64,
+    pub max_watchdog_errors: u64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            min_battery: 11.5,
+            min_lithium_battery: 2.7,
+            panel_temp_range: (-40.0, 70.0),
+            max_skipped_scans: 0,
+            max_watchdog_errors: 0,
+        }
+    }
+}
+
+/// One judged value of a status.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub item: String,
+    pub value: String,
+    pub health: Health,
+    /// Why the value is not ok.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+}
+
+impl Status {
+    /// Judges every value the logger reported against `limits`.
+    pub fn checks(&self, limits: &Thresholds) -> Vec<Check> {
+        let mut checks = Vec::new();
+        let mut check = |item: &str, value: String, problem: Option<(Health, String)>| {
+            let (health, problem) = match problem {
+                Some((health, problem)) => (health, Some(problem)),
+                None => (Health::Ok, None),
+            };
+            checks.push(Check { item: item.to_string(), value, health, problem });
+        };
+        if let Some(result) = &self.compile_result {
+            let compiled = result.to_ascii_lowercase().contains("compiled")
+                && !["fail", "error"].iter().any(|word| result.to_ascii_lowercase().contains(word));
+            let problem = match (result.trim().is_empty(), compiled) {
+                (true, _) => Some((Health::Critical, "no program is running".to_string())),
+                (false, false) => Some((Health::Critical, "the program did not compile".to_string())),
+                _ => None,
+            };
+            check("Program", result.trim().to_string(), problem);
+        }
+        if let Some(volts) = self.battery {
+            let problem = (volts < limits.min_battery).then(|| (Health::Critical, format!("below {} V", limits.min_battery)));
+            check("Battery", format!("{:.2} V", volts), problem);
+        }
+        if let Some(volts) = self.lithium_battery {
+            let problem = (volts < limits.min_lithium_battery)
+                .then(|| (Health::Warning, format!("below {} V; replace the lithium battery", limits.min_lithium_battery)));
+            check("Lithium battery", format!("{:.2} V", volts), problem);
+        }
+        if let Some(temp) = self.panel_temp {
+            let (low, high) = limits.panel_temp_range;
+            let problem = (temp < low || temp > high).then(|| (Health::Warning, format!("outside {} to {} °C", low, high)));
+            check("Panel temperature", format!("{:.1} °C", temp), problem);
+        }
+        if let Some(skipped) = self.skipped_scans {
+            let problem = (skipped > limits.max_skipped_scans)
+                .then(|| (Health::Warning, format!("more than {}; the scan interval may be too short", limits.max_skipped_scans)));
+            check("Skipped scans", skipped.to_string(), problem);
+        }
+        if let Some(errors) = self.watchdog_errors {
+            let problem = (errors > limits.max_watchdog_errors)
+                .then(|| (Health::Critical, format!("more than {}; the logger has reset itself", limits.max_watchdog_errors)));
+            check("Watchdog errors", errors.to_string(), problem);
+        }
+        checks
+    }
+}
+
+/// Status of a station and how it was judged.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusReport {
+    pub station: String,
+    /// The worst health of the checks.
+    pub health: Health,
+    pub status: Status,
+    pub checks: Vec<Check>,
+}
+
+impl StatusReport {
+    pub fn new(station: &str, status: Status, limits: &Thresholds) -> StatusReport {
+        let checks = status.checks(limits);
+        let health = checks.iter().map(|c| c.health).max().unwrap_or(Health::Ok);
+        StatusReport { station: station.to_string(), health, status, checks }
+    }
+}
+
+/// Reads the `Status` table values of a connected logger.
+pub fn read_status(logger: &mut Logger) -> Result<Status, Error> {
+    let mut text = |field: &str| -> Result<Option<String>, Error> {
+        Ok(match logger.value("Status", field, ValueKind::Text)? {
+            Some(Value::Text(text)) => Some(text),
+            _ => None,
+        })
+    };
+    let (os_version, program, compile_result) = (text("OSVersion")?, text("ProgName")?, text("CompileResults")?);
+    let mut number = |field: &str| -> Result<Option<f64>, Error> {
+        Ok(match logger.value("Status", field, ValueKind::Number)? {
+            Some(Value::Number(number)) => Some(number),
+            _ => None,
+        })
+    };
+    let count = |value: Option<f64>| value.map(|v| v.max(0.0).round() as u64);
+    Ok(Status {
+        os_version,
+        program,
+        compile_result,
+        battery: number("Battery")?,
+        lithium_battery: number("LithiumBattery")?,
+        panel_temp: number("PanelTemp")?,
+        skipped_scans: count(number("SkippedScan")?),
+        watchdog_errors: count(number("WatchdogErrors")?),
+    })
+}
+
+/// Connects to `station` (named `name`) and reports its status.
+pub fn query(name: &str, station: &Station, network: &NetworkSettings, limits: &Thresholds) -> Result<StatusReport, Error> {
+    let mut logger = Logger::connect(station, network)?;
+    if let Err(e) = crate::station::remember_baud(name, station, logger.endpoint()) {
+        log::warn!("Could not save the baud rate of {}: {}", name, e);
+    }
+    let status = read_status(&mut logger)?;
+    log::info!("Read the status of {} ({})", name, logger.endpoint());
+    Ok(StatusReport::new(name, status, limits))
+}
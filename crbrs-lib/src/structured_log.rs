@@ -0,0 +1,205 @@
+// FILE: crbrs-lib/src/structured_log.rs
+
+//! Structured log file: every log record of a run as one JSON object per line, written with
+//! `--log-file` or the `log_file` setting regardless of the console verbosity, so a support
+//! request can include a complete, machine-readable trace of what crbrs did.
+//!
+//! [`init`] installs a logger that passes records on to the console logger as before and also
+//! to the log files. The CLI additionally keeps each run's log in `last-run.jsonl` in the data
+//! directory for `crbrs report-bug`, in as much [`Detail`] as the `last_run_log` setting asks
+//! for. Files are only known once settings are loaded, so records are held back until
+//! [`stop_holding`] and written to every file [`open`]ed before then.
+
+use crate::clock;
+use crate::config;
+use crate::Error;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// How much a log file records: the `last_run_log` setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Detail {
+    /// Nothing; no file is kept.
+    Off,
+    /// Info, warnings and errors.
+    Info,
+    /// Our own debug records too; dependencies (HTTP client, etc.) down to info.
+    #[default]
+    Debug,
+    /// Our own records down to `trace`; dependencies down to `debug`.
+    Trace,
+}
+
+impl Detail {
+    fn keeps(self, level: Level, target: &str) -> bool {
+        let ours = target.starts_with("crbrs");
+        let max = match self {
+            Detail::Off => LevelFilter::Off,
+            Detail::Info => LevelFilter::Info,
+            Detail::Debug if ours => LevelFilter::Debug,
+            Detail::Debug => LevelFilter::Info,
+            Detail::Trace if ours => LevelFilter::Trace,
+            Detail::Trace => LevelFilter::Debug,
+        };
+        level <= max
+    }
+}
+
+impl std::str::FromStr for Detail {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Detail::Off),
+            "info" => Ok(Detail::Info),
+            "debug" => Ok(Detail::Debug),
+            "trace" => Ok(Detail::Trace),
+            _ => Err(format!("'{}' is not a log detail (off, info, debug or trace)", s)),
+        }
+    }
+}
+
+/// A record held back until the log files are known, with what decides which files get it.
+struct Held {
+    level: Level,
+    target: String,
+    line: String,
+}
+
+struct Sink {
+    /// Records so far, while log files may still be opened.
+    held: Option<Vec<Held>>,
+    files: Vec<(File, Detail)>,
+}
+
+impl Sink {
+    fn active(&self) -> bool {
+        self.held.is_some() || !self.files.is_empty()
+    }
+}
+
+static SINK: Mutex<Sink> = Mutex::new(Sink { held: Some(Vec::new()), files: Vec::new() });
+
+/// Identifies the run in a log file shared by several runs.
+static RUN_ID: OnceLock<String> = OnceLock::new();
+
+fn run_id() -> &'static str {
+    RUN_ID.get_or_init(|| format!("{}-{}", clock::file_stamp(SystemTime::now()), std::process::id()))
+}
+
+/// Whether any log file could want the record: the most a file keeps is [`Detail::Trace`].
+fn file_enabled(metadata: &Metadata) -> bool {
+    Detail::Trace.keeps(metadata.level(), metadata.target())
+}
+
+/// One log record as a JSON line (without the trailing newline).
+pub fn json_line(record: &Record) -> String {
+    serde_json::json!({
+        "time": clock::timestamp(SystemTime::now()),
+        "run": run_id(),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+        "file": record.file(),
+        "line": record.line(),
+    })
+    .to_string()
+}
+
+struct TeeLogger {
+    console: Box<dyn Log>,
+    console_level: LevelFilter,
+}
+
+impl TeeLogger {
+    fn console_enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.console_level && self.console.enabled(metadata)
+    }
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.console_enabled(metadata) || (file_enabled(metadata) && lock().active())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.console_enabled(record.metadata()) {
+            self.console.log(record);
+        }
+        if !file_enabled(record.metadata()) {
+            return;
+        }
+        let mut sink = lock();
+        if !sink.active() {
+            return;
+        }
+        let (level, target) = (record.level(), record.target());
+        let line = json_line(record);
+        for (file, detail) in &mut sink.files {
+            if detail.keeps(level, target) {
+                // A failing log file must not fail the command; there is nowhere to report it.
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+        if let Some(held) = &mut sink.held {
+            held.push(Held { level, target: target.to_string(), line });
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        for (file, _) in &mut lock().files {
+            let _ = file.flush();
+        }
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Sink> {
+    SINK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Installs the logger: records at or above `console_level` go to `console`, everything goes to
+/// the log file once one is [`open`]ed.
+pub fn init(console: Box<dyn Log>, console_level: LevelFilter) -> Result<(), log::SetLoggerError> {
+    log::set_logger(Box::leak(Box::new(TeeLogger { console, console_level })))?;
+    log::set_max_level(LevelFilter::Trace);
+    Ok(())
+}
+
+/// Where the CLI keeps the log of its most recent run.
+pub fn last_run_path() -> Result<PathBuf, Error> {
+    Ok(config::data_dir()?.join("last-run.jsonl"))
+}
+
+/// Starts writing the records `detail` keeps to `path`, appending if it exists or replacing it
+/// otherwise. The file begins with the records held so far.
+pub fn open(path: &Path, append: bool, detail: Detail) -> Result<(), Error> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(path)?;
+    let mut sink = lock();
+    for held in sink.held.iter().flatten().filter(|held| detail.keeps(held.level, &held.target)) {
+        writeln!(file, "{}", held.line)?;
+    }
+    sink.files.push((file, detail));
+    Ok(())
+}
+
+/// Stops writing to every log file, e.g. before they are deleted.
+pub fn close() {
+    let mut sink = lock();
+    sink.held = None;
+    sink.files.clear();
+}
+
+/// All log files are open: drops the held records. Without any file, file logging stops.
+pub fn stop_holding() {
+    lock().held = None;
+}
@@ -0,0 +1,163 @@
+// FILE: crbrs-lib/src/symbol_index.rs
+
+//! Workspace symbol index for the language server: the [`FileIndex`], includes and data table
+//! layouts of every CRBasic file under a workspace root, kept in the data directory as
+//! `symbol-index/<root hash>.json` (one per root) so that after a restart workspace symbols and
+//! cross-file navigation work right away instead of after parsing the whole repository again.
+//!
+//! Entries carry the modification time and size the file had when it was indexed. The server
+//! answers from the stored index first and calls [`WorkspaceIndex::revalidate`] in the
+//! background, which re-indexes only files whose stamp changed, adds new files and drops
+//! deleted ones. `crbrs metadata check` uses the same index to compare the tables of a
+//! project's programs (see [`crate::metadata::check_project`]).
+
+use crate::build_state::quick_stamp;
+use crate::config;
+use crate::docgen::{find_programs, includes};
+use crate::syntax::index::{index, FileIndex};
+use crate::syntax::parser::parse;
+use crate::syntax::tables::{tables, TableInfo};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the stored format; indexes of another version are rebuilt.
+const INDEX_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkspaceIndex {
+    #[serde(default)]
+    pub version: u32,
+    pub root: PathBuf,
+    /// Keyed by absolute path.
+    #[serde(default)]
+    pub files: BTreeMap<String, IndexedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedFile {
+    pub modified_ms: Option<u64>,
+    pub size: u64,
+    /// Files named by the file's `Include` statements, found next to it (or where they would be).
+    pub includes: Vec<PathBuf>,
+    pub index: FileIndex,
+    /// Data tables the file declares.
+    #[serde(default)]
+    pub tables: Vec<TableInfo>,
+}
+
+fn key(path: &Path) -> String {
+    crate::history::file_key(path)
+}
+
+/// Files included by `file`, whose text is `source`.
+pub fn file_includes(file: &Path, source: &str) -> Vec<PathBuf> {
+    let dir = file.parent().unwrap_or(Path::new(""));
+    includes(&parse(source)).iter().map(|target| crate::project::find_include(dir, target)).collect()
+}
+
+/// Where the index of `root` is stored.
+pub fn index_path(root: &Path) -> Result<PathBuf, Error> {
+    let hash: String = Sha256::digest(key(root).as_bytes()).iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+    Ok(config::data_dir()?.join("symbol-index").join(format!("{}.json", hash)))
+}
+
+/// Deletes all but the `keep` most recently stored indexes besides those of `roots` (the
+/// folders in use), so indexes of folders opened once don't pile up. Returns how many were deleted.
+pub fn prune(keep: usize, roots: &[PathBuf]) -> Result<usize, Error> {
+    let dir = config::data_dir()?.join("symbol-index");
+    let in_use: Vec<PathBuf> = roots.iter().filter_map(|root| index_path(root).ok()).collect();
+    let mut stored: Vec<(std::time::SystemTime, PathBuf)> = fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "json") && !in_use.contains(path))
+        .map(|path| (fs::metadata(&path).and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH), path))
+        .collect();
+    stored.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    let mut removed = 0;
+    for (_, path) in stored.iter().skip(keep) {
+        log::debug!("Removing old symbol index {:?}", path);
+        fs::remove_file(path)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+impl WorkspaceIndex {
+    /// The stored index of `root`, empty if there is none yet. Not revalidated.
+    pub fn load(root: &Path) -> Self {
+        let empty = WorkspaceIndex { version: INDEX_VERSION, root: root.to_path_buf(), files: BTreeMap::new() };
+        let Ok(path) = index_path(root) else { return empty };
+        let Ok(text) = fs::read_to_string(&path) else { return empty };
+        match serde_json::from_str::<WorkspaceIndex>(&text) {
+            Ok(stored) if key(&stored.root) == key(root) && stored.version == INDEX_VERSION => stored,
+            Ok(_) => empty,
+            Err(e) => {
+                log::warn!("Ignoring unreadable symbol index {:?}: {}", path, e);
+                empty
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let path = index_path(&self.root)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let text = serde_json::to_string(self).map_err(|e| Error::Io(e.into()))?;
+        fs::write(&path, text)?;
+        Ok(())
+    }
+
+    /// Whether `file` is under the workspace root.
+    pub fn contains(&self, file: &Path) -> bool {
+        Path::new(&key(file)).starts_with(key(&self.root))
+    }
+
+    /// Indexes `file` with the text `source` (e.g. just saved from the editor).
+    pub fn update(&mut self, file: &Path, source: &str) {
+        let (modified_ms, size) = quick_stamp(file).unwrap_or((None, source.len() as u64));
+        let program = parse(source);
+        let entry = IndexedFile {
+            modified_ms,
+            size,
+            includes: file_includes(file, source),
+            index: index(&program),
+            tables: tables(source, &program),
+        };
+        self.files.insert(key(file), entry);
+    }
+
+    /// Brings the index up to date with the files on disk. Returns whether anything changed.
+    pub fn revalidate(&mut self) -> Result<bool, Error> {
+        let mut changed = false;
+        let mut present = BTreeSet::new();
+        for file in find_programs(&self.root)? {
+            let file_key = key(&file);
+            let stamp = quick_stamp(&file);
+            let stale = match (self.files.get(&file_key), stamp) {
+                (Some(entry), Some((modified_ms, size))) => modified_ms.is_none() || entry.modified_ms != modified_ms || entry.size != size,
+                _ => true,
+            };
+            if stale {
+                match fs::read(&file) {
+                    Ok(bytes) => {
+                        log::debug!("Indexing {:?}", file);
+                        self.update(&file, &String::from_utf8_lossy(&bytes));
+                        changed = true;
+                    }
+                    Err(e) => log::warn!("Could not index {:?}: {}", file, e),
+                }
+            }
+            present.insert(file_key);
+        }
+        let before = self.files.len();
+        self.files.retain(|file_key, _| present.contains(file_key));
+        Ok(changed || self.files.len() != before)
+    }
+}
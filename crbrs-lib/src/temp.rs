@@ -0,0 +1,175 @@
+// FILE: crbrs-lib/src/temp.rs
+
+//! Temporary directories, all kept under `tmp` in the data directory instead of the system's
+//! temp directory, so they are easy to find, count and clean up (`crbrs clean --temp`).
+//!
+//! Names are `<purpose>-<process id>-<n>` ([`TempDir`], removed when dropped) or
+//! `<purpose>-<process id>` ([`process_dir`], for the life of a process, e.g. the language
+//! server's scratch directories). Whatever a crashed or killed process leaves behind is
+//! removed by [`clean`], and when the directory grows beyond [`MAX_BYTES`] the least recently
+//! used directories of other processes are removed before a new one is made. Directories of
+//! processes that are still running (a language server, a parallel build) are never removed,
+//! however old or large they are. Staging
+//! directories for compiler installs stay next to the compilers, where they can be renamed
+//! into place.
+
+use crate::config;
+use crate::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Size the temporary directories may reach before those of other processes are removed.
+pub const MAX_BYTES: u64 = 1 << 30;
+/// Age after which a directory of another process is taken to be left over.
+pub const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn root() -> Result<PathBuf, Error> {
+    Ok(config::data_dir()?.join("tmp"))
+}
+
+/// The process that made the directory `name`.
+fn owner(name: &str) -> Option<u32> {
+    name.split('-').nth(1)?.parse().ok()
+}
+
+/// Whether the process `pid` is still running.
+#[cfg(unix)]
+fn alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists; no memory is involved.
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    // EPERM: it exists, but belongs to another user.
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Whether the process `pid` is still running.
+#[cfg(windows)]
+fn alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, ERROR_ACCESS_DENIED, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+    // SAFETY: the handle is checked before use and closed once; no memory is shared.
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            // Access denied: it exists, but belongs to another user.
+            return GetLastError() == ERROR_ACCESS_DENIED;
+        }
+        let mut code = 0;
+        let queried = GetExitCodeProcess(handle, &mut code) != 0;
+        CloseHandle(handle);
+        !queried || code == STILL_ACTIVE as u32
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn alive(_pid: u32) -> bool {
+    false
+}
+
+/// A fresh, empty directory for `purpose` (one word, e.g. `sandbox`), removed when dropped.
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(purpose: &str) -> Result<Self, Error> {
+        let root = root()?;
+        enforce_cap(&root, MAX_BYTES);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let path = root.join(format!("{}-{}-{}", purpose, std::process::id(), n));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path)?;
+        log::debug!("Created temporary directory {:?}", path);
+        Ok(TempDir { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            log::debug!("Could not remove temporary directory {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// This process's directory for `purpose`, created if needed. The caller removes it.
+pub fn process_dir(purpose: &str) -> Result<PathBuf, Error> {
+    let root = root()?;
+    let path = root.join(format!("{}-{}", purpose, std::process::id()));
+    if !path.exists() {
+        enforce_cap(&root, MAX_BYTES);
+        fs::create_dir_all(&path)?;
+    }
+    Ok(path)
+}
+
+/// Directories of processes that are no longer running, least recently used first, with
+/// their sizes.
+fn others(root: &Path) -> Vec<(SystemTime, PathBuf, u64)> {
+    let own = std::process::id();
+    let mut found: Vec<(SystemTime, PathBuf, u64)> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| match owner(&entry.file_name().to_string_lossy()) {
+            Some(pid) => pid != own && !alive(pid),
+            None => true,
+        })
+        .map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+            (modified, entry.path(), crate::purge::size_of(&entry.path()))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+fn remove(path: &Path) -> bool {
+    let removed = if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+    match removed {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("Could not remove {:?}: {}", path, e);
+            false
+        }
+    }
+}
+
+/// Removes directories of exited processes, oldest first, until the total is within `cap`.
+fn enforce_cap(root: &Path, cap: u64) {
+    let mut total = crate::purge::size_of(root);
+    if total <= cap {
+        return;
+    }
+    for (_, path, bytes) in others(root) {
+        if total <= cap {
+            break;
+        }
+        log::info!("Temporary files exceed {} bytes; removing {:?}", cap, path);
+        if remove(&path) {
+            total = total.saturating_sub(bytes);
+        }
+    }
+}
+
+/// Removes the directories of exited processes not used for `older_than`; returns what was
+/// removed, with sizes. Directories of running processes, this one included, are kept.
+pub fn clean(older_than: Duration) -> Result<Vec<(PathBuf, u64)>, Error> {
+    let now = SystemTime::now();
+    let mut removed = Vec::new();
+    for (modified, path, bytes) in others(&root()?) {
+        if now.duration_since(modified).unwrap_or_default() >= older_than && remove(&path) {
+            removed.push((path, bytes));
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,113 @@
+// FILE: crbrs-lib/src/terminal.rs
+
+//! `crbrs logger terminal`: the logger's terminal mode, the `CR1000X>` prompt that answers
+//! one-key commands (e.g. `1` for the status, `9` for the program), over the station's serial
+//! port or TCP connection.
+//!
+//! Terminal mode is plain text next to PakBus on the same port: a few carriage returns wake it,
+//! and each command is answered with text ending in the prompt again. Every line sent and
+//! received goes to a session log (see [`crate::session_log`]) unless `[session_log]` is
+//! disabled.
+
+use crate::cancel;
+use crate::logger::{self, Endpoint, Link};
+use crate::network::NetworkSettings;
+use crate::session_log::{Direction, SessionLog, SessionLogSettings};
+use crate::station::Station;
+use crate::Error;
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+
+/// Seconds without output after which an answer is taken to be complete, if the logger never
+/// printed its prompt.
+const QUIET_SECS: u64 = 2;
+/// Carriage returns sent to wake terminal mode before giving up.
+const WAKE_ATTEMPTS: usize = 3;
+
+/// An open terminal session with a logger.
+pub struct Terminal {
+    link: Box<dyn Link>,
+    endpoint: Endpoint,
+    log: Option<SessionLog>,
+}
+
+impl Terminal {
+    /// Opens `station` (named `name`) for terminal mode, starting a session log if enabled.
+    pub fn open(name: &str, station: &Station, network: &NetworkSettings, session_log: &SessionLogSettings) -> Result<Terminal, Error> {
+        let endpoint: Endpoint = station.address.parse().map_err(Error::Logger)?;
+        let network = NetworkSettings { read_timeout_secs: QUIET_SECS, ..network.clone() };
+        let (link, endpoint) = logger::open(&endpoint, &network, station.pakbus_address)?;
+        let log = if session_log.enabled { Some(SessionLog::start("terminal", name, session_log)?) } else { None };
+        let mut terminal = Terminal { link, endpoint, log };
+        terminal.note(&format!("terminal session with {} on {}", name, terminal.endpoint))?;
+        Ok(terminal)
+    }
+
+    /// The session log being written, if any.
+    pub fn session_log(&self) -> Option<&Path> {
+        self.log.as_ref().map(SessionLog::path)
+    }
+
+    /// Wakes terminal mode; returns what the logger printed, ending with its prompt.
+    pub fn wake(&mut self) -> Result<String, Error> {
+        for _ in 0..WAKE_ATTEMPTS {
+            let answer = self.exchange("")?;
+            if is_prompt(&answer) {
+                return Ok(answer);
+            }
+        }
+        Err(Error::Logger(format!("no terminal prompt from the logger on {}", self.endpoint)))
+    }
+
+    /// Sends `command` and returns what the logger printed in answer.
+    pub fn command(&mut self, command: &str) -> Result<String, Error> {
+        if let Some(log) = &mut self.log {
+            log.write_line(Direction::Sent, command)?;
+        }
+        self.exchange(command)
+    }
+
+    fn note(&mut self, text: &str) -> Result<(), Error> {
+        match &mut self.log {
+            Some(log) => log.write_line(Direction::Note, text),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes `text` and a carriage return, then reads until the prompt or a pause.
+    fn exchange(&mut self, text: &str) -> Result<String, Error> {
+        cancel::check()?;
+        self.link.write_all(text.as_bytes())?;
+        self.link.write_all(b"\r")?;
+        self.link.flush()?;
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 512];
+        loop {
+            cancel::check()?;
+            match self.link.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    received.extend(&buffer[..n]);
+                    if is_prompt(&String::from_utf8_lossy(&received)) {
+                        break;
+                    }
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::Io(e)),
+            }
+        }
+        let answer = String::from_utf8_lossy(&received).into_owned();
+        if let Some(log) = &mut self.log {
+            for line in answer.split(['\r', '\n']).filter(|line| !line.trim().is_empty()) {
+                log.write_line(Direction::Received, line)?;
+            }
+        }
+        Ok(answer)
+    }
+}
+
+/// Whether `text` ends with a terminal prompt such as `CR1000X>`.
+fn is_prompt(text: &str) -> bool {
+    text.trim_end_matches(' ').ends_with('>')
+}
@@ -0,0 +1,149 @@
+// FILE: crbrs-lib/src/timezone.rs
+
+//! Station time zones for data files.
+//!
+//! Loggers keep whatever time they were set to, without a zone and without daylight saving
+//! time: a station set to US Eastern standard time stays at UTC-05:00 all year. Zones are
+//! therefore fixed UTC offsets, never region names like `America/New_York`, whose offset
+//! changes in summer. The offset of a station is configured in `[station_timezones]`, keyed
+//! by the station name on the TOA5 environment line, or given on the command line.
+
+use crate::clock::{format_logger_time, parse_logger_time};
+use crate::toa5::Toa5;
+use crate::{Error, Settings};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A fixed offset from UTC, in minutes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UtcOffset(pub i32);
+
+impl UtcOffset {
+    pub const UTC: UtcOffset = UtcOffset(0);
+}
+
+impl FromStr for UtcOffset {
+    type Err = String;
+
+    /// Accepts `UTC`, `Z`, `-05:00`, `+0530`, `-5` and the same with a `UTC`/`GMT` prefix,
+    /// e.g. `UTC-5`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let trimmed = text.trim();
+        let upper = trimmed.to_ascii_uppercase();
+        let offset = upper.strip_prefix("UTC").or_else(|| upper.strip_prefix("GMT")).unwrap_or(&upper).trim();
+        if offset.is_empty() || offset == "Z" {
+            return Ok(UtcOffset::UTC);
+        }
+        if trimmed.contains('/') {
+            return Err(format!(
+                "'{}' is a region, whose offset changes with daylight saving time; loggers keep standard time all year, so give the station's UTC offset, e.g. -05:00",
+                trimmed
+            ));
+        }
+        let invalid = || format!("'{}' is not a UTC offset like -05:00, +01:00 or UTC", trimmed);
+        let (sign, rest) = match offset.split_at_checked(1) {
+            Some(("+", rest)) => (1, rest),
+            Some(("-", rest)) => (-1, rest),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = match rest.split_once(':') {
+            Some((hours, minutes)) => (hours, minutes),
+            None if rest.len() == 4 => rest.split_at(2),
+            None => (rest, "0"),
+        };
+        let all_digits = |s: &str| !s.is_empty() && s.len() <= 2 && s.chars().all(|c| c.is_ascii_digit());
+        if !all_digits(hours) || !all_digits(minutes) {
+            return Err(invalid());
+        }
+        let (hours, minutes): (i32, i32) = (hours.parse().map_err(|_| invalid())?, minutes.parse().map_err(|_| invalid())?);
+        if hours > 14 || minutes > 59 {
+            return Err(invalid());
+        }
+        Ok(UtcOffset(sign * (hours * 60 + minutes)))
+    }
+}
+
+impl fmt::Display for UtcOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == 0 {
+            return write!(f, "UTC");
+        }
+        let sign = if self.0 < 0 { '-' } else { '+' };
+        write!(f, "UTC{}{:02}:{:02}", sign, self.0.abs() / 60, self.0.abs() % 60)
+    }
+}
+
+/// Configured offset of `station`, if any (station names match case-insensitively).
+pub fn station_offset(settings: &Settings, station: &str) -> Result<Option<UtcOffset>, Error> {
+    let Some((name, offset)) = settings.station_timezones.iter().find(|(name, _)| name.eq_ignore_ascii_case(station)) else {
+        return Ok(None);
+    };
+    offset
+        .parse()
+        .map(Some)
+        .map_err(|e| Error::Data(format!("station_timezones.{}: {}", name, e)))
+}
+
+/// Converts the timestamps of a file from one offset to another.
+#[derive(Debug, Clone)]
+pub struct Retime {
+    /// Offset the logger clock was set to; looked up in `[station_timezones]` if `None`.
+    pub from: Option<UtcOffset>,
+    pub to: UtcOffset,
+}
+
+impl Retime {
+    /// The offset `data` is in: `from`, or the one configured for its station.
+    pub fn source(&self, data: &Toa5, settings: &Settings) -> Result<UtcOffset, Error> {
+        if let Some(from) = self.from {
+            return Ok(from);
+        }
+        let station = data.environment.get(1).map(String::as_str).unwrap_or_default();
+        station_offset(settings, station)?.ok_or_else(|| {
+            Error::Data(format!(
+                "No time zone is configured for station '{}'; pass --from or set station_timezones.{}",
+                station, station
+            ))
+        })
+    }
+
+    /// Rewrites the `TIMESTAMP` field and every other field with `TS` units (e.g. the time of
+    /// a maximum) in `data`, and returns the offset it was converted from. Empty or `NAN`
+    /// values of other timestamp fields are left alone.
+    pub fn apply(&self, data: &mut Toa5, settings: &Settings) -> Result<UtcOffset, Error> {
+        let from = self.source(data, settings)?;
+        let shift = i64::from(self.to.0 - from.0) * 60_000;
+        let timestamp = data.field("TIMESTAMP");
+        let fields: Vec<usize> = (0..data.fields.len())
+            .filter(|&i| Some(i) == timestamp || data.fields[i].units.eq_ignore_ascii_case("TS"))
+            .collect();
+        for record in &mut data.records {
+            for &i in &fields {
+                let value = &mut record[i];
+                let Some(time) = parse_logger_time(value) else {
+                    if Some(i) == timestamp {
+                        return Err(Error::Data(format!("'{}' is not a timestamp", value)));
+                    }
+                    continue;
+                };
+                let fraction = value.split_once('.').map_or(0, |(_, digits)| digits.trim().len());
+                *value = format_logger_time(time + shift, fraction);
+            }
+        }
+        Ok(from)
+    }
+}
+
+/// Converts the TOA5 file at `path` with `retime` and writes it to `output`. Returns the
+/// converted file's record count and the offset it was converted from.
+pub fn retime_file(path: &Path, output: &Path, retime: &Retime, settings: &Settings) -> Result<(usize, UtcOffset), Error> {
+    let mut data = Toa5::read(path)?;
+    let from = retime.apply(&mut data, settings).map_err(|e| match e {
+        Error::Data(message) => Error::Data(format!("{}: {}", path.display(), message)),
+        other => other,
+    })?;
+    std::fs::write(output, data.to_text())?;
+    log::info!("Wrote {:?} with timestamps converted from {} to {}", output, from, retime.to);
+    Ok((data.records.len(), from))
+}
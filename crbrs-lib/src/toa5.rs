@@ -0,0 +1,180 @@
+// FILE: crbrs-lib/src/toa5.rs
+
+//! TOA5 data files, the ASCII table format LoggerNet and the loggers write:
+//!
+//! ```text
+//! "TOA5","Station12","CR1000X","1234","CR1000X.Std.06","CPU:soil.cr1x","1234","Hourly"
+//! "TIMESTAMP","RECORD","BattV_Min","AirT_Avg"
+//! "TS","RN","Volts","Deg C"
+//! "","","Min","Avg"
+//! "2026-10-16 10:00:00",0,12.81,14.2
+//! ```
+//!
+//! Values are kept as written, without the quotes; [`Toa5::to_text`] quotes them again the
+//! way LoggerNet does (everything but numbers).
+
+use crate::Error;
+use serde::Serialize;
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Field {
+    pub name: String,
+    pub units: String,
+    /// `Avg`, `Min`, `Smp`, ... or empty.
+    pub processing: String,
+}
+
+/// A TOA5 file: the environment line, the fields and the records.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Toa5 {
+    /// `TOA5`, station name, logger model, serial number, OS version, program, program
+    /// signature and table name.
+    pub environment: Vec<String>,
+    pub fields: Vec<Field>,
+    /// One value per field, including `TIMESTAMP` and `RECORD`.
+    pub records: Vec<Vec<String>>,
+}
+
+/// Fields of a CSV line, with quotes removed (`""` inside quotes is a quote).
+pub fn split_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+fn quoted_line<'a>(values: impl Iterator<Item = &'a String>) -> String {
+    values.map(|v| quote(v)).collect::<Vec<_>>().join(",")
+}
+
+/// Reads a TOA5 file record by record, for files too large to hold in memory.
+pub struct Toa5Reader<R> {
+    pub environment: Vec<String>,
+    pub fields: Vec<Field>,
+    reader: R,
+    line: usize,
+    bytes: u64,
+}
+
+impl<R: BufRead> Toa5Reader<R> {
+    /// Reads the header.
+    pub fn new(reader: R) -> Result<Self, String> {
+        let mut toa5 = Toa5Reader { environment: Vec::new(), fields: Vec::new(), reader, line: 0, bytes: 0 };
+        let environment = toa5.next_line()?.ok_or("The file is empty")?;
+        if environment.first().map(String::as_str) != Some("TOA5") {
+            return Err("Not a TOA5 file (the first line does not start with \"TOA5\")".to_string());
+        }
+        let (Some(names), Some(units), Some(processing)) = (toa5.next_line()?, toa5.next_line()?, toa5.next_line()?) else {
+            return Err("The TOA5 header is incomplete".to_string());
+        };
+        if units.len() != names.len() || processing.len() != names.len() {
+            return Err("The TOA5 header lines have different numbers of fields".to_string());
+        }
+        toa5.environment = environment;
+        toa5.fields = names
+            .into_iter()
+            .zip(units)
+            .zip(processing)
+            .map(|((name, units), processing)| Field { name, units, processing })
+            .collect();
+        Ok(toa5)
+    }
+
+    /// Bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Next non-blank line, split into values.
+    fn next_line(&mut self) -> Result<Option<Vec<String>>, String> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = self.reader.read_until(b'\n', &mut line).map_err(|e| e.to_string())?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            self.bytes += read as u64;
+            let text = String::from_utf8_lossy(&line);
+            if !text.trim().is_empty() {
+                return Ok(Some(split_line(text.trim_end_matches(['\r', '\n']))));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Toa5Reader<R> {
+    type Item = Result<Vec<String>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_line() {
+            Ok(Some(values)) if values.len() != self.fields.len() => {
+                Some(Err(format!("line {}: {} values for {} fields", self.line, values.len(), self.fields.len())))
+            }
+            other => other.transpose(),
+        }
+    }
+}
+
+impl Toa5 {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut reader = Toa5Reader::new(text.as_bytes())?;
+        let records = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+        Ok(Toa5 { environment: reader.environment, fields: reader.fields, records })
+    }
+
+    /// The file as text, with CRLF line endings.
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            quoted_line(self.environment.iter()),
+            quoted_line(self.fields.iter().map(|f| &f.name)),
+            quoted_line(self.fields.iter().map(|f| &f.units)),
+            quoted_line(self.fields.iter().map(|f| &f.processing)),
+        ];
+        for record in &self.records {
+            let values = record.iter().map(|v| if v.parse::<f64>().is_ok_and(f64::is_finite) { v.clone() } else { quote(v) });
+            lines.push(values.collect::<Vec<_>>().join(","));
+        }
+        lines.iter().map(|line| format!("{}\r\n", line)).collect()
+    }
+
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+        Toa5::parse(&String::from_utf8_lossy(&bytes)).map_err(|e| Error::Data(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Table name from the environment line.
+    pub fn table(&self) -> &str {
+        self.environment.get(7).map(String::as_str).unwrap_or_default()
+    }
+
+    /// Index of the field named `name` (case-insensitive).
+    pub fn field(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|f| f.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Timestamp of record `i`, if the file has a `TIMESTAMP` field.
+    pub fn timestamp(&self, i: usize) -> Option<&str> {
+        self.field("TIMESTAMP").map(|f| self.records[i][f].as_str())
+    }
+}
+
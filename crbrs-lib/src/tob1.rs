@@ -0,0 +1,266 @@
+// FILE: crbrs-lib/src/tob1.rs
+
+//! TOB1 data files, the binary table format loggers write to cards and LoggerNet can collect
+//! to: five ASCII header lines like a TOA5 file's (with `TOB1` in place of `TOA5` and a fifth
+//! line of data types), followed by fixed-size binary records.
+//!
+//! ```text
+//! "TOB1","Station12","CR1000X","1234","CR1000X.Std.06","CPU:flux.cr1x","1234","Fast"
+//! "SECONDS","NANOSECONDS","RECORD","Ux","Uy","Diag"
+//! "SECONDS","NANOSECONDS","RN","m/s","m/s",""
+//! "","","","Smp","Smp","Smp"
+//! "ULONG","ULONG","ULONG","IEEE4","FP2","UINT2"
+//! ```
+//!
+//! [`Tob1Reader`] decodes one record at a time into the values a TOA5 file would hold, so
+//! multi-gigabyte card files convert in bounded memory. The leading `SECONDS` and
+//! `NANOSECONDS` fields (time since 1990-01-01) become one `TIMESTAMP` field, as
+//! CardConvert does.
+
+use crate::clock::format_logger_time;
+use crate::toa5::{split_line, Field};
+use std::io::{BufRead, ErrorKind};
+
+/// Milliseconds from 1970-01-01 to 1990-01-01, the logger epoch.
+pub const LOGGER_EPOCH_MS: i64 = 631_152_000_000;
+
+/// How a value is stored in a binary record. Integers named after C types (`ULONG`, `LONG`)
+/// and `IEEE4`/`IEEE8` are stored LSB first; the Campbell types (`UINT2`, `INT4`, `FP2`,
+/// `NSEC`, `IEEE4B`, ...) MSB first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    /// Unsigned integer of the given size.
+    Unsigned { size: usize, big_endian: bool },
+    /// Signed integer of the given size.
+    Signed { size: usize, big_endian: bool },
+    Float { size: usize, big_endian: bool },
+    /// Campbell's two-byte decimal float: sign, two exponent bits and a 13-bit mantissa.
+    Fp2,
+    /// Boolean of the given size; any non-zero byte is true.
+    Bool { size: usize },
+    /// Seconds since 1990 and nanoseconds, four bytes each.
+    Time { big_endian: bool },
+    /// Fixed-size string, padded with NULs.
+    Ascii { size: usize },
+}
+
+impl ValueType {
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_uppercase();
+        if let Some(size) = name.strip_prefix("ASCII(").and_then(|rest| rest.strip_suffix(')')) {
+            return size.parse().ok().filter(|&size| size > 0).map(|size| ValueType::Ascii { size });
+        }
+        Some(match name.as_str() {
+            "ULONG" | "UINT4L" => ValueType::Unsigned { size: 4, big_endian: false },
+            "LONG" | "INT4L" => ValueType::Signed { size: 4, big_endian: false },
+            "UINT1" => ValueType::Unsigned { size: 1, big_endian: true },
+            "UINT2" => ValueType::Unsigned { size: 2, big_endian: true },
+            "UINT4" => ValueType::Unsigned { size: 4, big_endian: true },
+            "INT1" => ValueType::Signed { size: 1, big_endian: true },
+            "INT2" => ValueType::Signed { size: 2, big_endian: true },
+            "INT4" => ValueType::Signed { size: 4, big_endian: true },
+            "IEEE4" | "IEEE4L" => ValueType::Float { size: 4, big_endian: false },
+            "IEEE8" | "IEEE8L" => ValueType::Float { size: 8, big_endian: false },
+            "IEEE4B" => ValueType::Float { size: 4, big_endian: true },
+            "IEEE8B" => ValueType::Float { size: 8, big_endian: true },
+            "FP2" => ValueType::Fp2,
+            "BOOL" => ValueType::Bool { size: 1 },
+            "BOOL2" => ValueType::Bool { size: 2 },
+            "BOOL4" => ValueType::Bool { size: 4 },
+            "NSEC" => ValueType::Time { big_endian: true },
+            "SECNANO" => ValueType::Time { big_endian: false },
+            _ => return None,
+        })
+    }
+
+    /// Bytes the value takes in a record.
+    pub fn size(self) -> usize {
+        match self {
+            ValueType::Unsigned { size, .. }
+            | ValueType::Signed { size, .. }
+            | ValueType::Float { size, .. }
+            | ValueType::Bool { size }
+            | ValueType::Ascii { size } => size,
+            ValueType::Fp2 => 2,
+            ValueType::Time { .. } => 8,
+        }
+    }
+
+    /// The value in `bytes` (exactly [`ValueType::size`] of them) as TOA5 would write it.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let unsigned = |big_endian: bool| {
+            let fold = |acc: u64, b: &u8| acc << 8 | u64::from(*b);
+            if big_endian { bytes.iter().fold(0, fold) } else { bytes.iter().rev().fold(0, fold) }
+        };
+        match self {
+            ValueType::Unsigned { big_endian, .. } => unsigned(big_endian).to_string(),
+            ValueType::Signed { size, big_endian } => {
+                let shift = 64 - 8 * size as u32;
+                ((unsigned(big_endian) << shift) as i64 >> shift).to_string()
+            }
+            ValueType::Float { size: 4, big_endian } => float(f64::from(f32::from_bits(unsigned(big_endian) as u32)), |v| (v as f32).to_string()),
+            ValueType::Float { big_endian, .. } => float(f64::from_bits(unsigned(big_endian)), |v| v.to_string()),
+            ValueType::Fp2 => fp2(u16::from_be_bytes([bytes[0], bytes[1]])),
+            ValueType::Bool { .. } => if bytes.iter().any(|&b| b != 0) { "-1" } else { "0" }.to_string(),
+            ValueType::Time { big_endian } => {
+                let half = |range: std::ops::Range<usize>| {
+                    let part: [u8; 4] = bytes[range].try_into().unwrap_or_default();
+                    if big_endian { u32::from_be_bytes(part) } else { u32::from_le_bytes(part) }
+                };
+                timestamp(i64::from(half(0..4)), i64::from(half(4..8)))
+            }
+            ValueType::Ascii { .. } => {
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                String::from_utf8_lossy(&bytes[..end]).into_owned()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ValueType {
+    /// The type's name regardless of byte order, e.g. `IEEE4` or `ASCII(16)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueType::Unsigned { size, .. } => write!(f, "UINT{}", size),
+            ValueType::Signed { size, .. } => write!(f, "INT{}", size),
+            ValueType::Float { size, .. } => write!(f, "IEEE{}", size),
+            ValueType::Fp2 => write!(f, "FP2"),
+            ValueType::Bool { .. } => write!(f, "BOOL"),
+            ValueType::Time { .. } => write!(f, "NSEC"),
+            ValueType::Ascii { size } => write!(f, "ASCII({})", size),
+        }
+    }
+}
+
+/// `value` as TOA5 writes floats: `NAN`, `INF`, `-INF` or `format`ted.
+fn float(value: f64, format: impl Fn(f64) -> String) -> String {
+    if value.is_nan() {
+        "NAN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "INF" } else { "-INF" }.to_string()
+    } else {
+        format(value)
+    }
+}
+
+/// An FP2 value with as many decimals as its exponent gives.
+fn fp2(raw: u16) -> String {
+    let negative = raw & 0x8000 != 0;
+    let exponent = usize::from(raw >> 13 & 0x3);
+    let mantissa = raw & 0x1FFF;
+    match (exponent, mantissa) {
+        (0, 8191) => if negative { "-INF" } else { "INF" }.to_string(),
+        (0, 8190) => "NAN".to_string(),
+        _ => {
+            let value = f64::from(mantissa) / 10f64.powi(exponent as i32);
+            format!("{}{:.*}", if negative && mantissa != 0 { "-" } else { "" }, exponent, value)
+        }
+    }
+}
+
+/// A logger time (seconds since 1990 and nanoseconds) as a TOA5 timestamp, with fractional
+/// seconds only where there are any.
+pub fn timestamp(seconds: i64, nanoseconds: i64) -> String {
+    let ms = LOGGER_EPOCH_MS + seconds * 1000 + nanoseconds / 1_000_000;
+    let fraction = match ms % 1000 {
+        0 => 0,
+        ms if ms % 100 == 0 => 1,
+        ms if ms % 10 == 0 => 2,
+        _ => 3,
+    };
+    format_logger_time(ms, fraction)
+}
+
+/// Reads a TOB1 file record by record.
+pub struct Tob1Reader<R> {
+    pub environment: Vec<String>,
+    /// Fields as TOA5 would have them, with `SECONDS` and `NANOSECONDS` merged into `TIMESTAMP`.
+    pub fields: Vec<Field>,
+    /// Type of each field in `fields`.
+    pub types: Vec<ValueType>,
+    record: Vec<u8>,
+    reader: R,
+    bytes: u64,
+}
+
+impl<R: BufRead> Tob1Reader<R> {
+    /// Reads the header.
+    pub fn new(mut reader: R) -> Result<Self, String> {
+        let mut bytes = 0;
+        let mut header = || -> Result<Vec<String>, String> {
+            let mut line = Vec::new();
+            bytes += reader.read_until(b'\n', &mut line).map_err(|e| e.to_string())? as u64;
+            if line.is_empty() {
+                return Err("The TOB1 header is incomplete".to_string());
+            }
+            Ok(split_line(String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n'])))
+        };
+        let environment = header()?;
+        if environment.first().map(String::as_str) != Some("TOB1") {
+            return Err("Not a TOB1 file (the first line does not start with \"TOB1\")".to_string());
+        }
+        let (names, units, processing, types) = (header()?, header()?, header()?, header()?);
+        if [&units, &processing, &types].iter().any(|line| line.len() != names.len()) {
+            return Err("The TOB1 header lines have different numbers of fields".to_string());
+        }
+        let mut fields = Vec::with_capacity(names.len());
+        let mut value_types = Vec::with_capacity(names.len());
+        for (((name, units), processing), data_type) in names.into_iter().zip(units).zip(processing).zip(&types) {
+            let value_type =
+                ValueType::parse(data_type).ok_or_else(|| format!("field '{}' has an unknown data type '{}'", name, data_type))?;
+            fields.push(Field { name, units, processing });
+            value_types.push(value_type);
+        }
+        let record = vec![0; value_types.iter().map(|t| t.size()).sum()];
+        let split_time = fields.len() >= 2
+            && fields[0].name.eq_ignore_ascii_case("SECONDS")
+            && fields[1].name.eq_ignore_ascii_case("NANOSECONDS")
+            && value_types[..2].iter().all(|t| t.size() == 4 && matches!(t, ValueType::Unsigned { .. } | ValueType::Signed { .. }));
+        if split_time {
+            let big_endian = matches!(value_types[0], ValueType::Unsigned { big_endian: true, .. } | ValueType::Signed { big_endian: true, .. });
+            fields.splice(..2, [Field { name: "TIMESTAMP".to_string(), units: "TS".to_string(), processing: String::new() }]);
+            value_types.splice(..2, [ValueType::Time { big_endian }]);
+        }
+        Ok(Tob1Reader { environment, fields, types: value_types, record, reader, bytes })
+    }
+
+    /// Bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Size of one record in bytes.
+    pub fn record_size(&self) -> usize {
+        self.record.len()
+    }
+}
+
+impl<R: BufRead> Iterator for Tob1Reader<R> {
+    type Item = Result<Vec<String>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut filled = 0;
+        while filled < self.record.len() {
+            match self.reader.read(&mut self.record[filled..]) {
+                Ok(0) if filled == 0 => return None,
+                Ok(0) => {
+                    // A card pulled while the logger was writing ends in part of a record.
+                    log::warn!("Ignoring {} bytes of an incomplete record at the end of the file", filled);
+                    return None;
+                }
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e.to_string())),
+            }
+        }
+        self.bytes += filled as u64;
+        let mut offset = 0;
+        let mut values = Vec::with_capacity(self.types.len());
+        for &value_type in &self.types {
+            let size = value_type.size();
+            values.push(value_type.decode(&self.record[offset..offset + size]));
+            offset += size;
+        }
+        Some(Ok(values))
+    }
+}
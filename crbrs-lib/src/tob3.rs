@@ -0,0 +1,287 @@
+// FILE: crbrs-lib/src/tob3.rs
+
+//! TOB3 data files, the ring files loggers keep on cards. Six ASCII header lines are followed
+//! by fixed-size data frames:
+//!
+//! ```text
+//! "TOB3","Station12","CR1000X","1234","CR1000X.Std.06","CPU:flux.cr1x","1234","2026-10-16 00:00:00"
+//! "Fast","100 MSEC","984","86400","4660","Sec100Usec","","",""
+//! "Ux","Uy","Diag"
+//! "m/s","m/s",""
+//! "Smp","Smp","Smp"
+//! "IEEE4","FP2","UINT2"
+//! ```
+//!
+//! The second line names the table and gives the record interval, the frame size in bytes,
+//! the intended table size, the validation stamp and the resolution of frame times.
+//!
+//! Each frame is a 12-byte header (seconds since 1990, sub-seconds in the frame time
+//! resolution and the number of its first record, LSB first), records without timestamps,
+//! and a 4-byte footer: the offset (11 bits), the flags file mark, remove mark, empty frame
+//! and minor frame (bits 11 to 14) and the validation stamp (upper 16 bits). A frame only
+//! counts if its stamp is the header's or its complement (the stamp flips each time the ring
+//! wraps); frames that were never finished, e.g. after a power loss, are skipped. A frame
+//! closed early holds minor frames, each with its own header and footer, whose footer offset
+//! is the minor frame's size; they are found by walking back from the end of the frame.
+//!
+//! Since the ring wraps, frames are not in record order in the file. [`Tob3Reader`] first
+//! indexes the frames (a few bytes per frame) when the first record is read, then reads them
+//! in record order.
+
+use crate::tob1::{timestamp, ValueType};
+use crate::toa5::{split_line, Field};
+use std::io::{BufRead, Read, Seek, SeekFrom};
+
+const FRAME_HEADER: usize = 12;
+const FRAME_FOOTER: usize = 4;
+const EMPTY_FRAME: u32 = 1 << 13;
+const MINOR_FRAME: u32 = 1 << 14;
+
+/// Records of one (minor) frame.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    /// Position of the first record in the file.
+    offset: u64,
+    records: usize,
+    first_record: u32,
+    /// Time of the first record, in nanoseconds since 1990.
+    time_ns: i64,
+}
+
+/// Nanoseconds in a TOB3 interval like `100 MSEC` or `30 MIN`; 0 for event tables.
+fn interval_ns(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(0);
+    }
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let number: i64 = text[..split].parse().ok()?;
+    let unit: i64 = match text[split..].trim().to_ascii_uppercase().as_str() {
+        "" | "SEC" | "SECS" => 1_000_000_000,
+        "NSEC" => 1,
+        "USEC" => 1_000,
+        "MSEC" => 1_000_000,
+        "MIN" | "MINS" => 60_000_000_000,
+        "HR" | "HRS" | "HOUR" | "HOURS" => 3_600_000_000_000,
+        "DAY" | "DAYS" => 86_400_000_000_000,
+        _ => return None,
+    };
+    Some(number * unit)
+}
+
+/// Nanoseconds in one unit of a frame time resolution like `Sec100Usec`.
+fn resolution_ns(text: &str) -> Option<i64> {
+    Some(match text.trim().to_ascii_lowercase().as_str() {
+        "secmsec" | "sec1msec" => 1_000_000,
+        "sec100usec" => 100_000,
+        "sec10usec" => 10_000,
+        "secusec" | "sec1usec" => 1_000,
+        "secnsec" => 1,
+        _ => return None,
+    })
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// What [`index`] needs to find the frames of a file.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    data_start: u64,
+    frame_size: usize,
+    stamp: u32,
+    resolution_ns: i64,
+}
+
+/// Reads a TOB3 file in record order.
+pub struct Tob3Reader<R> {
+    /// The environment line, with the table name in place of the file's creation time, so it
+    /// reads like a TOA5 one.
+    pub environment: Vec<String>,
+    /// `TIMESTAMP` and `RECORD`, then the fields stored in the records.
+    pub fields: Vec<Field>,
+    /// Type of each stored field (without `TIMESTAMP` and `RECORD`).
+    pub types: Vec<ValueType>,
+    /// Frames that did not validate and were skipped (known once the first record is read).
+    pub skipped_frames: usize,
+    /// Valid frames not read yet; `None` until the file is indexed.
+    frames: Option<std::vec::IntoIter<Frame>>,
+    layout: Layout,
+    /// Frame being read, and the index of its next record.
+    current: Option<(Frame, usize)>,
+    record: Vec<u8>,
+    interval_ns: i64,
+    reader: R,
+    bytes: u64,
+}
+
+impl<R: BufRead + Seek> Tob3Reader<R> {
+    /// Reads the header.
+    pub fn new(mut reader: R) -> Result<Self, String> {
+        let mut header = || -> Result<Vec<String>, String> {
+            let mut line = Vec::new();
+            reader.read_until(b'\n', &mut line).map_err(|e| e.to_string())?;
+            if line.is_empty() {
+                return Err("The TOB3 header is incomplete".to_string());
+            }
+            Ok(split_line(String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n'])))
+        };
+        let mut environment = header()?;
+        if environment.first().map(String::as_str) != Some("TOB3") {
+            return Err("Not a TOB3 file (the first line does not start with \"TOB3\")".to_string());
+        }
+        let table = header()?;
+        let (names, units, processing, data_types) = (header()?, header()?, header()?, header()?);
+        if [&units, &processing, &data_types].iter().any(|line| line.len() != names.len()) {
+            return Err("The TOB3 header lines have different numbers of fields".to_string());
+        }
+        let value = |i: usize, what: &str| table.get(i).map(String::as_str).ok_or_else(|| format!("The TOB3 table line has no {}", what));
+        let interval_ns = interval_ns(value(1, "record interval")?).ok_or("The TOB3 record interval is not understood")?;
+        let frame_size: usize = value(2, "frame size")?.trim().parse().map_err(|_| "The TOB3 frame size is not a number")?;
+        let stamp: u32 = value(4, "validation stamp")?.trim().parse().map_err(|_| "The TOB3 validation stamp is not a number")?;
+        let resolution_ns = resolution_ns(value(5, "frame time resolution")?).ok_or("The TOB3 frame time resolution is not understood")?;
+
+        let mut types = Vec::with_capacity(names.len());
+        let mut fields = vec![
+            Field { name: "TIMESTAMP".to_string(), units: "TS".to_string(), processing: String::new() },
+            Field { name: "RECORD".to_string(), units: "RN".to_string(), processing: String::new() },
+        ];
+        for (((name, units), processing), data_type) in names.into_iter().zip(units).zip(processing).zip(&data_types) {
+            types.push(ValueType::parse(data_type).ok_or_else(|| format!("field '{}' has an unknown data type '{}'", name, data_type))?);
+            fields.push(Field { name, units, processing });
+        }
+        let record_size: usize = types.iter().map(|t| t.size()).sum();
+        if record_size == 0 || frame_size < FRAME_HEADER + FRAME_FOOTER + record_size {
+            return Err(format!("The TOB3 frame size {} cannot hold a record of {} bytes", frame_size, record_size));
+        }
+        if environment.len() > 7 {
+            environment[7] = table[0].clone();
+        }
+
+        let data_start = reader.stream_position().map_err(|e| e.to_string())?;
+        Ok(Tob3Reader {
+            environment,
+            fields,
+            types,
+            skipped_frames: 0,
+            frames: None,
+            layout: Layout { data_start, frame_size, stamp, resolution_ns },
+            current: None,
+            record: vec![0; record_size],
+            interval_ns,
+            reader,
+            bytes: data_start,
+        })
+    }
+
+    /// Bytes of records read so far (plus the header).
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes
+    }
+}
+
+/// The valid (minor) frames of a file in record order, and the number of invalid frames.
+fn index<R: Read + Seek>(reader: &mut R, layout: Layout, record_size: usize) -> Result<(Vec<Frame>, usize), String> {
+    let Layout { data_start, frame_size, stamp, resolution_ns } = layout;
+    let valid = |footer: u32| footer >> 16 == stamp || footer >> 16 == !stamp & 0xFFFF;
+    reader.seek(SeekFrom::Start(data_start)).map_err(|e| e.to_string())?;
+    let mut frames = Vec::new();
+    let mut skipped = 0;
+    let mut buffer = vec![0; frame_size];
+    let mut frame_start = data_start;
+    loop {
+        match reader.read_exact(&mut buffer) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.to_string()),
+        }
+        let mut end = frame_size;
+        let mut found = Vec::new();
+        while end >= FRAME_HEADER + FRAME_FOOTER {
+            let footer = u32_at(&buffer, end - FRAME_FOOTER);
+            if !valid(footer) {
+                break;
+            }
+            let minor = footer & MINOR_FRAME != 0;
+            let size = if minor { (footer & 0x7FF) as usize } else { end };
+            if size < FRAME_HEADER + FRAME_FOOTER || size > end || (!minor && end != frame_size) {
+                break;
+            }
+            let start = end - size;
+            if footer & EMPTY_FRAME == 0 {
+                let seconds = i64::from(u32_at(&buffer, start));
+                let sub_seconds = i64::from(u32_at(&buffer, start + 4));
+                found.push(Frame {
+                    offset: frame_start + (start + FRAME_HEADER) as u64,
+                    records: (size - FRAME_HEADER - FRAME_FOOTER) / record_size,
+                    first_record: u32_at(&buffer, start + 8),
+                    time_ns: seconds * 1_000_000_000 + sub_seconds * resolution_ns,
+                });
+            }
+            if start == 0 {
+                break;
+            }
+            end = start;
+        }
+        if found.is_empty() && !valid(u32_at(&buffer, frame_size - FRAME_FOOTER)) {
+            skipped += 1;
+        }
+        frames.extend(found);
+        frame_start += frame_size as u64;
+    }
+    frames.sort_by_key(|frame| frame.first_record);
+    frames.dedup_by_key(|frame| frame.first_record);
+    if skipped > 0 {
+        log::warn!("Skipped {} TOB3 frames that were never completed or are from an older file", skipped);
+    }
+    Ok((frames, skipped))
+}
+
+impl<R: BufRead + Seek> Iterator for Tob3Reader<R> {
+    type Item = Result<Vec<String>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frames.is_none() {
+            match index(&mut self.reader, self.layout, self.record.len()) {
+                Ok((frames, skipped)) => {
+                    self.frames = Some(frames.into_iter());
+                    self.skipped_frames = skipped;
+                }
+                Err(e) => {
+                    self.frames = Some(Vec::new().into_iter());
+                    return Some(Err(e));
+                }
+            }
+        }
+        let (frame, index) = loop {
+            match self.current {
+                Some((frame, index)) if index < frame.records => break (frame, index),
+                _ => {
+                    let frame = self.frames.as_mut()?.next()?;
+                    if let Err(e) = self.reader.seek(SeekFrom::Start(frame.offset)) {
+                        return Some(Err(e.to_string()));
+                    }
+                    self.current = Some((frame, 0));
+                }
+            }
+        };
+        if let Err(e) = self.reader.read_exact(&mut self.record) {
+            return Some(Err(e.to_string()));
+        }
+        self.current = Some((frame, index + 1));
+        self.bytes += self.record.len() as u64;
+
+        let time_ns = frame.time_ns + index as i64 * self.interval_ns;
+        let mut values = Vec::with_capacity(self.types.len() + 2);
+        values.push(timestamp(time_ns.div_euclid(1_000_000_000), time_ns.rem_euclid(1_000_000_000)));
+        values.push((u64::from(frame.first_record) + index as u64).to_string());
+        let mut offset = 0;
+        for &value_type in &self.types {
+            let size = value_type.size();
+            values.push(value_type.decode(&self.record[offset..offset + size]));
+            offset += size;
+        }
+        Some(Ok(values))
+    }
+}
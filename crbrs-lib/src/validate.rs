@@ -0,0 +1,464 @@
+// FILE: crbrs-lib/src/validate.rs
+
+//! `crbrs config validate`: checks a config file and reports every problem at once, with the
+//! line it was found on.
+//!
+//! Loading settings is lenient (unknown keys are ignored, missing paths only fail when used);
+//! this is the strict counterpart for catching typos and stale references before going to
+//! the field.
+
+use crate::config::{get_compiler_storage_path, merge};
+use crate::network::{NetworkSettings, UrlRewrite};
+use crate::session_log::SessionLogSettings;
+use crate::station::Station;
+use crate::{CompilerInfo, Error, Settings};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml_edit::{ImDocument, Item, TableLike, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigProblem {
+    pub severity: Severity,
+    /// 1-based line in the config file, if the problem can be pinned to one.
+    pub line: Option<usize>,
+    /// Dotted key the problem concerns, e.g. `network.retries`.
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.line {
+            Some(line) => write!(f, "line {}: {}: {}: {}", line, severity, self.key, self.message),
+            None => write!(f, "{}: {}: {}", severity, self.key, self.message),
+        }
+    }
+}
+
+/// Validates the config file at `path`, including that the files it includes exist. With
+/// `strict`, unknown keys are errors rather than warnings.
+pub fn validate_file(path: &Path, strict: bool) -> Result<Vec<ConfigProblem>, Error> {
+    let text = fs::read_to_string(path)?;
+    let mut problems = validate_str(&text, strict);
+    if problems.iter().all(|p| p.key != "(syntax)") {
+        if let Err(Error::Config(e)) = crate::config::included_files(path) {
+            let line = text.lines().position(|l| l.trim_start().starts_with("include")).map(|i| i + 1);
+            problems.push(ConfigProblem { severity: Severity::Error, line, key: "include".to_string(), message: e.to_string() });
+            problems.sort_by_key(|p| p.line.unwrap_or(usize::MAX));
+        }
+    }
+    Ok(problems)
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// Validates config file contents.
+pub fn validate_str(text: &str, strict: bool) -> Vec<ConfigProblem> {
+    let document = match ImDocument::parse(text) {
+        Ok(document) => document,
+        Err(e) => {
+            return vec![ConfigProblem {
+                severity: Severity::Error,
+                line: e.span().map(|span| line_of(text, span.start)),
+                key: "(syntax)".to_string(),
+                message: one_line(e.message()),
+            }];
+        }
+    };
+    let mut checker = Checker { text, root: document.as_table(), problems: Vec::new() };
+    checker.unknown_keys(strict);
+    if let Some(settings) = checker.settings() {
+        checker.profiles(&settings);
+        checker.references(&settings);
+        checker.lint_rules(&settings);
+        checker.station_timezones(&settings);
+        checker.stations(&settings);
+    }
+    let mut problems = checker.problems;
+    problems.sort_by_key(|p| p.line.unwrap_or(usize::MAX));
+    problems
+}
+
+/// A settings value with every optional field filled in, so its serialized form lists every
+/// key the config may contain. The literals are exhaustive on purpose: adding a setting
+/// without updating this fails to compile.
+fn schema() -> toml::Value {
+    let compiler = CompilerInfo {
+        id: String::new(),
+        description: String::new(),
+        version: String::new(),
+        install_subdir: PathBuf::new(),
+        executable_name: String::new(),
+        requires_wine: false,
+        supported_loggers: Some(Vec::new()),
+        family: Default::default(),
+        linked: false,
+        archive_sha256: Some(String::new()),
+    };
+    let settings = Settings {
+        include: vec![String::new()],
+        compiler_repository_url: String::new(),
+        compiler_storage_path: Some(PathBuf::new()),
+        installed_compilers: HashMap::from([(String::new(), compiler)]),
+        file_associations: HashMap::from([(String::new(), String::new())]),
+        wine_path: Some(String::new()),
+        signing_key_path: Some(PathBuf::new()),
+        signing_public_key: Some(String::new()),
+        require_signed_compilers: false,
+        session_log: SessionLogSettings::default(),
+        network: NetworkSettings {
+            rewrites: vec![UrlRewrite { from: String::new(), to: String::new() }],
+            tokens: BTreeMap::from([(String::new(), String::new())]),
+            ..NetworkSettings::default()
+        },
+        log_file: Some(PathBuf::new()),
+        last_run_log: crate::structured_log::Detail::Off,
+        lint: HashMap::from([(String::new(), crate::syntax::lint::Level::Off)]),
+        max_diagnostics: 0,
+        compile_timeout_secs: 0,
+        artifacts_in_place: crate::in_place::InPlace::Auto,
+        station_timezones: HashMap::from([(String::new(), String::new())]),
+        stations: HashMap::from([(
+            String::new(),
+            Station {
+                security_code_secret: Some(String::new()),
+                min_battery: Some(0.0),
+                min_lithium_battery: Some(0.0),
+                max_skipped_scans: Some(0),
+                max_watchdog_errors: Some(0),
+                ..Station::default()
+            },
+        )]),
+        profiles: BTreeMap::from([(String::new(), toml::Table::new())]),
+        active_profile: None,
+        environment: Vec::new(),
+    };
+    toml::Value::try_from(settings).expect("settings serialize to TOML")
+}
+
+/// Tables whose keys are free-form (compiler IDs, extensions, lint rules, station names, hosts)
+/// rather than field names.
+const MAP_KEYS: [&str; 6] = ["installed_compilers", "file_associations", "lint", "station_timezones", "stations", "network.tokens"];
+
+struct Checker<'a> {
+    text: &'a str,
+    root: &'a toml_edit::Table,
+    problems: Vec<ConfigProblem>,
+}
+
+impl Checker<'_> {
+    fn push(&mut self, severity: Severity, span: Option<Range<usize>>, key: &str, message: String) {
+        let line = span.map(|span| line_of(self.text, span.start));
+        self.problems.push(ConfigProblem { severity, line, key: key.to_string(), message });
+    }
+
+    /// Span of the key at the dotted `path`, if present in the file.
+    fn span_of(&self, path: &[&str]) -> Option<Range<usize>> {
+        let mut table: &dyn TableLike = self.root;
+        let (last, parents) = path.split_last()?;
+        for part in parents {
+            table = table.get(part)?.as_table_like()?;
+        }
+        let (key, item) = table.get_key_value(last)?;
+        key.span().or_else(|| item.span())
+    }
+
+    fn unknown_keys(&mut self, strict: bool) {
+        let severity = if strict { Severity::Error } else { Severity::Warning };
+        let schema = schema();
+        let root = self.root;
+        self.check_table(root, schema.as_table().expect("settings are a table"), "", severity);
+    }
+
+    fn check_table(&mut self, table: &dyn TableLike, schema: &toml::Table, prefix: &str, severity: Severity) {
+        for (name, item) in table.iter() {
+            let key = if prefix.is_empty() { name.to_string() } else { format!("{}.{}", prefix, name) };
+            let Some(expected) = schema.get(name) else {
+                let span = table.get_key_value(name).and_then(|(k, _)| k.span());
+                self.push(severity, span, &key, "unknown key".to_string());
+                continue;
+            };
+            if prefix.is_empty() && name == "profiles" {
+                // A profile may override any setting (but not define further profiles).
+                for (profile_name, profile) in item.as_table_like().into_iter().flat_map(|t| t.iter()) {
+                    if let Some(profile_table) = profile.as_table_like() {
+                        let mut profile_schema = schema.clone();
+                        profile_schema.remove("profiles");
+                        self.check_table(profile_table, &profile_schema, &format!("{}.{}", key, profile_name), severity);
+                    }
+                }
+                continue;
+            }
+            if prefix.is_empty() && MAP_KEYS.contains(&name) || MAP_KEYS.contains(&key.as_str()) {
+                // Every entry of a map is checked against the schema's single sample entry.
+                if let (Some(entries), Some(sample)) = (item.as_table_like(), expected.as_table().and_then(|t| t.values().next())) {
+                    for (entry_name, entry) in entries.iter() {
+                        if let (Some(entry_table), Some(sample_table)) = (entry.as_table_like(), sample.as_table()) {
+                            self.check_table(entry_table, sample_table, &format!("{}.{}", key, entry_name), severity);
+                        }
+                    }
+                }
+                continue;
+            }
+            match expected {
+                toml::Value::Table(sub_schema) => {
+                    if let Some(sub_table) = item.as_table_like() {
+                        self.check_table(sub_table, sub_schema, &key, severity);
+                    }
+                }
+                toml::Value::Array(samples) => {
+                    let Some(sample) = samples.first().and_then(|s| s.as_table()) else { continue };
+                    for element in array_tables(item) {
+                        self.check_table(element, sample, &key, severity);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The settings the file describes (on top of the defaults), leaving out keys with the
+    /// wrong type after reporting them, or `None` if they cannot be loaded at all.
+    fn settings(&mut self) -> Option<Settings> {
+        let mut user: toml::Table = match toml::from_str(self.text) {
+            Ok(table) => table,
+            Err(e) => {
+                let span = e.span();
+                self.push(Severity::Error, span, "(syntax)", one_line(e.message()));
+                return None;
+            }
+        };
+        let e = match load(&[], toml::Value::Table(user.clone())) {
+            Ok(settings) => return Some(settings),
+            Err(e) => e,
+        };
+        // Pin the error(s) down by loading one key at a time.
+        let mut invalid = Vec::new();
+        self.type_errors(&user, &[], &mut invalid);
+        if invalid.is_empty() {
+            self.push(Severity::Error, None, "(settings)", e);
+            return None;
+        }
+        for path in &invalid {
+            remove(&mut user, path);
+        }
+        load(&[], toml::Value::Table(user)).ok()
+    }
+
+    /// Reports every key below `path` whose value has the wrong type and adds it to `invalid`.
+    fn type_errors(&mut self, table: &toml::Table, path: &[&str], invalid: &mut Vec<Vec<String>>) {
+        for (name, value) in table {
+            let key_path: Vec<&str> = path.iter().copied().chain([name.as_str()]).collect();
+            let Err(e) = load(&key_path, value.clone()) else { continue };
+            match value {
+                toml::Value::Table(sub_table) if !(path.is_empty() && MAP_KEYS.contains(&name.as_str())) => {
+                    let before = invalid.len();
+                    self.type_errors(sub_table, &key_path, invalid);
+                    if invalid.len() > before {
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            let span = self.span_of(&key_path);
+            self.push(Severity::Error, span, &key_path.join("."), e);
+            invalid.push(key_path.iter().map(|k| k.to_string()).collect());
+        }
+    }
+
+    /// Reports profiles that do not produce valid settings when applied.
+    fn profiles(&mut self, settings: &Settings) {
+        for (name, profile) in &settings.profiles {
+            let mut value = toml::Value::try_from(settings).expect("settings serialize to TOML");
+            merge(&mut value, toml::Value::Table(profile.clone()));
+            if let Err(e) = value.try_into::<Settings>() {
+                let span = self.span_of(&["profiles", name]);
+                self.push(Severity::Error, span, &format!("profiles.{}", name), one_line(e.message()));
+            }
+        }
+    }
+
+    fn references(&mut self, settings: &Settings) {
+        self.check_url(&["compiler_repository_url"], &settings.compiler_repository_url);
+        for (i, rewrite) in settings.network.rewrites.iter().enumerate() {
+            let span = self.span_of(&["network", "rewrites"]);
+            let key = format!("network.rewrites[{}].to", i);
+            if let Err(e) = reqwest::Url::parse(&rewrite.to) {
+                self.push(Severity::Error, span, &key, format!("'{}' is not a valid URL: {}", rewrite.to, e));
+            }
+        }
+
+        if let Some(path) = &settings.compiler_storage_path {
+            let resolved = get_compiler_storage_path(settings).unwrap_or_else(|_| path.clone());
+            if !resolved.is_dir() {
+                let span = self.span_of(&["compiler_storage_path"]);
+                self.push(Severity::Error, span, "compiler_storage_path", format!("directory {} does not exist", resolved.display()));
+            }
+        }
+        if let Some(wine) = &settings.wine_path {
+            if find_program(wine).is_none() {
+                let span = self.span_of(&["wine_path"]);
+                self.push(Severity::Error, span, "wine_path", format!("'{}' not found", wine));
+            }
+        }
+        if let Some(key_path) = &settings.signing_key_path {
+            if !key_path.is_file() {
+                let span = self.span_of(&["signing_key_path"]);
+                self.push(Severity::Error, span, "signing_key_path", format!("file {} does not exist", key_path.display()));
+            }
+        }
+        if let Some(public_key) = &settings.signing_public_key {
+            if let Err(e) = minisign_verify::PublicKey::from_base64(public_key.trim()) {
+                let span = self.span_of(&["signing_public_key"]);
+                self.push(Severity::Error, span, "signing_public_key", format!("not a minisign public key: {}", e));
+            }
+        }
+
+        let storage = get_compiler_storage_path(settings).ok();
+        let mut ids: Vec<&String> = settings.installed_compilers.keys().collect();
+        ids.sort();
+        for id in ids {
+            let info = &settings.installed_compilers[id];
+            let executable = storage.as_deref().unwrap_or(Path::new("")).join(&info.install_subdir).join(&info.executable_name);
+            if !executable.is_file() {
+                let span = self.span_of(&["installed_compilers", id]);
+                self.push(
+                    Severity::Error,
+                    span,
+                    &format!("installed_compilers.{}", id),
+                    format!("executable {} does not exist", executable.display()),
+                );
+            }
+        }
+
+        // Compilers are normally recorded in the state file rather than the config.
+        let recorded = crate::config::load_installed_compilers().ok().flatten().unwrap_or_default();
+        let mut extensions: Vec<(&String, &String)> = settings.file_associations.iter().collect();
+        extensions.sort();
+        for (extension, compiler_id) in extensions {
+            if !settings.installed_compilers.contains_key(compiler_id) && !recorded.contains_key(compiler_id) {
+                let span = self.span_of(&["file_associations", extension]);
+                self.push(
+                    Severity::Error,
+                    span,
+                    &format!("file_associations.{}", extension),
+                    format!("compiler '{}' is not installed", compiler_id),
+                );
+            }
+        }
+    }
+
+    fn lint_rules(&mut self, settings: &Settings) {
+        let mut rules: Vec<&String> = settings.lint.keys().collect();
+        rules.sort();
+        for rule in rules {
+            if crate::syntax::lint::rule(rule).is_none() {
+                let span = self.span_of(&["lint", rule]);
+                self.push(Severity::Warning, span, &format!("lint.{}", rule), format!("unknown lint rule '{}'", rule));
+            }
+        }
+    }
+
+    fn station_timezones(&mut self, settings: &Settings) {
+        let mut stations: Vec<(&String, &String)> = settings.station_timezones.iter().collect();
+        stations.sort();
+        for (station, offset) in stations {
+            if let Err(e) = offset.parse::<crate::timezone::UtcOffset>() {
+                let span = self.span_of(&["station_timezones", station]);
+                self.push(Severity::Error, span, &format!("station_timezones.{}", station), e);
+            }
+        }
+    }
+
+    fn stations(&mut self, settings: &Settings) {
+        let mut stations: Vec<(&String, &Station)> = settings.stations.iter().collect();
+        stations.sort_by_key(|(name, _)| name.as_str());
+        for (name, station) in stations {
+            let problem = if station.address.is_empty() {
+                Some("no address; set stations.NAME.address".to_string())
+            } else {
+                station.address.parse::<crate::logger::Endpoint>().err()
+            };
+            if let Some(problem) = problem {
+                let span = self.span_of(&["stations", name, "address"]).or_else(|| self.span_of(&["stations", name]));
+                self.push(Severity::Error, span, &format!("stations.{}.address", name), problem);
+            }
+        }
+    }
+
+    fn check_url(&mut self, path: &[&str], url: &str) {
+        let problem = match reqwest::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https" | "file") => return,
+            Ok(parsed) => format!("unsupported URL scheme '{}'", parsed.scheme()),
+            Err(e) => format!("'{}' is not a valid URL: {}", url, e),
+        };
+        let span = self.span_of(path);
+        self.push(Severity::Error, span, &path.join("."), problem);
+    }
+}
+
+/// Tables of an array of tables, or inline tables of an array value.
+fn array_tables(item: &Item) -> Vec<&dyn TableLike> {
+    match item {
+        Item::ArrayOfTables(array) => array.iter().map(|t| t as &dyn TableLike).collect(),
+        Item::Value(Value::Array(array)) => {
+            array.iter().filter_map(|v| v.as_inline_table()).map(|t| t as &dyn TableLike).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Loads the default settings with `value` placed at `path` (the whole file if empty).
+fn load(path: &[&str], value: toml::Value) -> Result<Settings, String> {
+    let overlay = path.iter().rev().fold(value, |inner, key| {
+        toml::Value::Table(toml::Table::from_iter([(key.to_string(), inner)]))
+    });
+    let mut merged = toml::Value::try_from(Settings::default()).expect("settings serialize to TOML");
+    merge(&mut merged, overlay);
+    merged.try_into::<Settings>().map_err(|e| one_line(e.message()))
+}
+
+fn one_line(message: &str) -> String {
+    message.trim().lines().collect::<Vec<_>>().join(": ")
+}
+
+/// Removes the key at `path` from `table`.
+fn remove(table: &mut toml::Table, path: &[String]) {
+    match path {
+        [key] => {
+            table.remove(key);
+        }
+        [key, rest @ ..] => {
+            if let Some(toml::Value::Table(sub_table)) = table.get_mut(key) {
+                remove(sub_table, rest);
+            }
+        }
+        [] => {}
+    }
+}
+
+/// Resolves a program name or path the way running it would.
+pub(crate) fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 || path.is_absolute() {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| [dir.join(program), dir.join(format!("{}.exe", program))])
+        .find(|candidate| candidate.is_file())
+}
@@ -0,0 +1,218 @@
+// FILE: crbrs-lib/src/verify.rs
+
+//! `crbrs verify-project`: every check a project's CI needs, in one run and one report, like
+//! `cargo check`, `cargo fmt --check` and `cargo clippy` together.
+//!
+//! 1. Toolchain: the compilers `crbrs.toml` requires are installed and their executables are
+//!    still there, and every compiler its `[file_associations]` names is among them, so the
+//!    manifest pins everything the build uses. If the project has a `crbrs.lock`, each
+//!    installed compiler must also be the version and archive the lock records.
+//! 2. Formatting: every program is formatted (see [`crate::format`]). Programs the formatter
+//!    cannot read, e.g. ones saved as Windows-1252 rather than UTF-8, are listed as skipped.
+//! 3. Lint: no program has lint errors at the configured rule levels (see [`crate::lint`]);
+//!    warnings only lower the status to `warnings`.
+//! 4. Build: every program compiles (see [`crate::build`]). Skipped while the toolchain is
+//!    incomplete, where every compile would fail the same way.
+//!
+//! Each check runs even if an earlier one failed, so one run lists everything to fix.
+
+use crate::build::{self, BuildOptions, BuildSummary, FileResult, Status};
+use crate::project::Project;
+use crate::syntax::lint::Severity;
+use crate::{Error, Settings};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolchainReport {
+    pub status: Status,
+    pub required: Vec<String>,
+    /// Required compilers that are not installed.
+    pub missing: Vec<String>,
+    /// Required compilers installed, but whose executable is gone.
+    pub broken: Vec<String>,
+    /// Compilers the project associates with an extension without requiring them.
+    pub unlisted: Vec<String>,
+    /// Installed compilers that differ from `crbrs.lock`, with what differs.
+    pub mismatched: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormatReport {
+    pub status: Status,
+    pub checked: usize,
+    pub unformatted: Vec<PathBuf>,
+    /// Programs that could not be checked (e.g. not UTF-8 text), with why.
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintProblem {
+    pub file: PathBuf,
+    /// 1-based.
+    pub line: u32,
+    pub code: String,
+    pub rule: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintReport {
+    pub status: Status,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Errors and warnings, by file and line.
+    pub problems: Vec<LintProblem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyReport {
+    /// Worst status of any check.
+    pub status: Status,
+    pub manifest: PathBuf,
+    pub toolchain: ToolchainReport,
+    pub format: FormatReport,
+    pub lint: LintReport,
+    /// `None` if the build was skipped because the toolchain is incomplete.
+    pub build: Option<BuildSummary>,
+}
+
+impl VerifyReport {
+    /// Names of the checks that failed.
+    pub fn failed_checks(&self) -> Vec<&'static str> {
+        let build = self.build.as_ref().map_or(Status::Failed, |build| build.status);
+        [("toolchain", self.toolchain.status), ("format", self.format.status), ("lint", self.lint.status), ("build", build)]
+            .into_iter()
+            .filter(|(_, status)| *status == Status::Failed)
+            .map(|(check, _)| check)
+            .collect()
+    }
+
+    /// `Err(VerificationFailed)` if any check failed.
+    pub fn result(&self) -> Result<(), Error> {
+        let failed = self.failed_checks();
+        if failed.is_empty() {
+            return Ok(());
+        }
+        Err(Error::VerificationFailed { failed: failed.iter().map(|check| check.to_string()).collect() })
+    }
+}
+
+fn status_of(failed: bool) -> Status {
+    if failed { Status::Failed } else { Status::Ok }
+}
+
+fn check_toolchain(project: &Project, settings: &Settings) -> Result<ToolchainReport, Error> {
+    let storage_path = crate::config::get_compiler_storage_path(settings)?;
+    let required = project.manifest.required_compilers.clone();
+    let missing = project.missing_compilers(settings);
+    let broken: Vec<String> = required
+        .iter()
+        .filter(|id| {
+            settings.installed_compilers.get(*id).is_some_and(|info| !storage_path.join(&info.install_subdir).join(&info.executable_name).is_file())
+        })
+        .cloned()
+        .collect();
+    let mut unlisted: Vec<String> =
+        project.manifest.file_associations.values().filter(|id| !required.contains(id)).cloned().collect();
+    unlisted.sort();
+    unlisted.dedup();
+    let mut mismatched = Vec::new();
+    if let Some(lock) = project.lock()? {
+        let short = |sha256: &Option<String>| sha256.as_deref().map_or("no archive".to_string(), |sha256| sha256.chars().take(12).collect());
+        for id in &required {
+            let (Some(locked), Some(info)) = (lock.get(id), settings.installed_compilers.get(id)) else { continue };
+            let archive_differs = locked.sha256.as_ref().is_some_and(|sha256| info.archive_sha256.as_ref().is_none_or(|actual| !actual.eq_ignore_ascii_case(sha256)));
+            if locked.version != info.version || archive_differs {
+                mismatched.push(format!(
+                    "{} (locked: version {}, {}; installed: version {}, {})",
+                    id, locked.version, short(&locked.sha256), info.version, short(&info.archive_sha256)
+                ));
+            }
+        }
+    }
+    let failed = !missing.is_empty() || !broken.is_empty() || !unlisted.is_empty() || !mismatched.is_empty();
+    Ok(ToolchainReport { status: status_of(failed), required, missing, broken, unlisted, mismatched })
+}
+
+fn check_format(files: &[PathBuf]) -> FormatReport {
+    let mut unformatted = Vec::new();
+    let mut skipped = Vec::new();
+    for file in files {
+        match crate::format::format_file(file, true, false) {
+            Ok(true) => unformatted.push(file.clone()),
+            Ok(false) => {}
+            Err(e) => {
+                log::warn!("Could not check the formatting of {:?}: {}", file, e);
+                skipped.push((file.clone(), e.to_string()));
+            }
+        }
+    }
+    FormatReport { status: status_of(!unformatted.is_empty()), checked: files.len() - skipped.len(), unformatted, skipped }
+}
+
+fn check_lint(files: &[PathBuf], settings: &Settings) -> Result<LintReport, Error> {
+    let mut problems = Vec::new();
+    for file in files {
+        let source = String::from_utf8_lossy(&fs::read(file)?).into_owned();
+        problems.extend(
+            crate::lint::lint(&source, Some(file), settings)
+                .into_iter()
+                .filter(|d| matches!(d.severity, Severity::Error | Severity::Warning))
+                .map(|d| LintProblem {
+                    file: file.clone(),
+                    line: d.span.line + 1,
+                    code: d.code.to_string(),
+                    rule: d.rule.to_string(),
+                    severity: d.severity,
+                    message: d.message,
+                }),
+        );
+    }
+    let count = |severity| problems.iter().filter(|p| p.severity == severity).count();
+    let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+    let status = match (errors, warnings) {
+        (0, 0) => Status::Ok,
+        (0, _) => Status::Warnings,
+        _ => Status::Failed,
+    };
+    Ok(LintReport { status, errors, warnings, problems })
+}
+
+/// Verifies the project `path` (a directory or program) belongs to. `done` is called with
+/// each compiled file's result, as by [`build::build_files`].
+pub fn verify_project(
+    path: &Path,
+    options: &BuildOptions,
+    settings: &Settings,
+    done: impl FnMut(&FileResult),
+) -> Result<VerifyReport, Error> {
+    let Some(project) = Project::find(path)? else {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("No {} in or above {}; `crbrs init` starts a project", crate::project::MANIFEST_FILE, path.display()),
+        )));
+    };
+    log::info!("Verifying the project at {:?}", project.root);
+    let files = build::collect_inputs(std::slice::from_ref(&project.root))?;
+
+    let toolchain = check_toolchain(&project, settings)?;
+    let format = check_format(&files);
+    let lint = check_lint(&files, settings)?;
+    let build = if toolchain.status == Status::Failed {
+        log::warn!("Not compiling: the project's toolchain is incomplete");
+        None
+    } else {
+        let start = Instant::now();
+        let results = build::build_files(&files, options, settings, done)?;
+        Some(BuildSummary::new(results, start.elapsed()))
+    };
+    let status = [toolchain.status, format.status, lint.status, build.as_ref().map_or(Status::Failed, |build| build.status)]
+        .into_iter()
+        .max()
+        .unwrap_or(Status::Ok);
+    Ok(VerifyReport { status, manifest: project.manifest_path(), toolchain, format, lint, build })
+}
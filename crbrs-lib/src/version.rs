@@ -0,0 +1,38 @@
+// FILE: crbrs-lib/src/version.rs
+
+//! `crbrs version bump`: maintains the structured version header described in
+//! `crbrs_syntax::version`, filling in today's date and the author from git.
+
+use crate::Error;
+use crbrs_syntax::version::{self, BumpLevel};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Author for a changelog entry: `git config user.name` in the file's directory, falling
+/// back to the login name.
+fn default_author(path: &Path) -> String {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    Command::new("git")
+        .current_dir(dir)
+        .args(["config", "user.name"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|name| !name.is_empty())
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Bumps the version header of the program at `path` in place and returns the new version.
+pub fn bump_file(path: &Path, level: BumpLevel, message: &str, author: Option<&str>) -> Result<String, Error> {
+    let source = fs::read_to_string(path)?;
+    let author = author.map_or_else(|| default_author(path), str::to_string);
+    let (updated, new_version) = version::bump(&source, level, &crate::clock::today(), &author, message)
+        .map_err(|e| Error::Version(format!("{}: {}", path.display(), e)))?;
+    fs::write(path, updated)?;
+    log::info!("Bumped {:?} to version {}", path, new_version);
+    Ok(new_version)
+}
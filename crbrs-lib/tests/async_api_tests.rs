@@ -0,0 +1,47 @@
+// FILE: crbrs-lib/tests/async_api_tests.rs
+
+//! The async entry points (`cargo test -p crbrs-lib --features async`).
+
+use crbrs_lib::{compile_file_async, fetch_manifest_async, Error, Settings};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// Serves `body` to every request, on a free local port; returns the URL.
+fn serve(body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind a local port");
+    let url = format!("http://{}/compilers.toml", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || {
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                    line.clear(); // Headers, up to the blank line
+                }
+                let _ = write!(&stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+            });
+        }
+    });
+    url
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn compile_file_async_returns_the_library_error() {
+    let missing = std::env::temp_dir().join("crbrs-async-test-missing.cr1x");
+    let result = compile_file_async(missing, None, None, Settings::default()).await;
+    assert!(matches!(result, Err(Error::Io(ref e)) if e.kind() == std::io::ErrorKind::NotFound), "{:?}", result);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn fetch_manifest_async_runs_the_blocking_client_off_the_runtime() {
+    let home = std::env::temp_dir().join(format!("crbrs-async-test-{}", std::process::id()));
+    // The fetched manifest is cached there. Only this test reads the home directory.
+    std::env::set_var(crbrs_lib::config::HOME_ENV_VAR, &home);
+    let settings = Settings { compiler_repository_url: serve("manifest_version = \"7\"\n[compilers]\n"), ..Settings::default() };
+    // Called on this runtime's thread, reqwest's blocking client would panic.
+    let manifest = fetch_manifest_async(settings).await.expect("fetch the manifest");
+    std::env::remove_var(crbrs_lib::config::HOME_ENV_VAR);
+    assert_eq!(manifest.manifest_version, "7");
+    let _ = std::fs::remove_dir_all(&home);
+}
@@ -0,0 +1,117 @@
+// FILE: crbrs-lib/tests/compiler_output_tests.rs
+
+//! Compiler adapters checked against a corpus of compiler output, so parsing can be extended
+//! for new formats without breaking the ones already handled.
+//!
+//! Every `tests/fixtures/compiler-output/<family>/<name>.txt` is output of a compiler of that
+//! family, and `<name>.expected.toml` what its adapter must make of it:
+//!
+//! ```toml
+//! input = "station.cr1x"     # the file the compiler was given
+//! outcome = "failed"         # success, failed or unrecognized
+//! synthetic = true           # written by hand, not captured from a compiler
+//!
+//! [[errors]]
+//! file = "station.cr1x"      # as named in the output
+//! line = 14
+//! message = "Variable BattV is not declared."
+//! ```
+//!
+//! The cr200 and cr10x samples are synthetic: no output of those compilers was at hand, so they
+//! were written from the formats their adapters accept, and only show that those formats keep
+//! parsing. Replace them with captured output (and drop `synthetic`) when some turns up.
+//! Samples taken from real runs should have user names, paths and program details replaced.
+//! To add one, drop in the `.txt` and run the tests with `CRBRS_BLESS=1` to write the
+//! `.expected.toml` from the current parser, then check it by hand.
+
+use crbrs_lib::adapter::{adapter_for, CompilerOutcome};
+use crbrs_lib::CompilerFamily;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Expected {
+    input: String,
+    outcome: String,
+    /// Written by hand rather than captured from a compiler.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    synthetic: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<ExpectedError>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectedError {
+    file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+    message: String,
+}
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("compiler-output")
+}
+
+/// What the adapter makes of `output`, in the shape of the expectation file.
+fn parse(family: CompilerFamily, input: &str, output: &str) -> Expected {
+    let (outcome, errors) = match adapter_for(family).parse_output(Path::new(input), output) {
+        CompilerOutcome::Success => ("success", Vec::new()),
+        CompilerOutcome::Failed(errors) => ("failed", errors),
+        CompilerOutcome::Unrecognized => ("unrecognized", Vec::new()),
+    };
+    Expected {
+        input: input.to_string(),
+        outcome: outcome.to_string(),
+        synthetic: false,
+        errors: errors
+            .into_iter()
+            .map(|e| ExpectedError { file: e.file_path_in_log, line: e.line, message: e.message })
+            .collect(),
+    }
+}
+
+#[test]
+fn test_adapters_parse_the_fixture_corpus() -> Result<(), Box<dyn std::error::Error>> {
+    let bless = std::env::var_os("CRBRS_BLESS").is_some();
+    let mut checked = 0;
+    let mut failures = Vec::new();
+    let mut family_dirs: Vec<PathBuf> = fs::read_dir(fixtures_dir())?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+    family_dirs.sort();
+    for family_dir in family_dirs.into_iter().filter(|p| p.is_dir()) {
+        let family: CompilerFamily = family_dir.file_name().unwrap().to_string_lossy().parse()?;
+        let mut samples: Vec<PathBuf> = fs::read_dir(&family_dir)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+        samples.retain(|p| p.extension().is_some_and(|e| e == "txt"));
+        samples.sort();
+        for sample in samples {
+            let output = fs::read_to_string(&sample)?;
+            let expected_path = sample.with_extension("expected.toml");
+            let expected: Option<Expected> = match fs::read_to_string(&expected_path) {
+                Ok(text) => Some(toml::from_str(&text).map_err(|e| format!("{}: {}", expected_path.display(), e))?),
+                Err(_) => None,
+            };
+            let input = match &expected {
+                Some(expected) => expected.input.clone(),
+                None => format!("program.{}", if family == CompilerFamily::Cr200 { "cr2" } else { "cr1x" }),
+            };
+            let mut actual = parse(family, &input, &output);
+            actual.synthetic = expected.as_ref().is_some_and(|expected| expected.synthetic);
+            if bless {
+                fs::write(&expected_path, toml::to_string(&actual)?)?;
+            } else if expected.as_ref() != Some(&actual) {
+                failures.push(format!(
+                    "{}:\n  expected {:?}\n  parsed   {:?}",
+                    sample.strip_prefix(fixtures_dir()).unwrap_or(&sample).display(),
+                    expected,
+                    actual
+                ));
+            }
+            checked += 1;
+        }
+    }
+    assert!(checked > 0, "no fixtures found in {}", fixtures_dir().display());
+    assert!(failures.is_empty(), "{} of {} fixtures parsed differently:\n{}", failures.len(), checked, failures.join("\n"));
+    Ok(())
+}
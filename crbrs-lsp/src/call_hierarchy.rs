@@ -0,0 +1,153 @@
+// crbrs-lsp/src/call_hierarchy.rs
+//! `textDocument/prepareCallHierarchy`, `callHierarchy/incomingCalls`,
+//! `callHierarchy/outgoingCalls` and `workspace/symbol` over the index of every open document
+//! and every file in the workspace folders.
+
+use crbrs_lib::syntax::index::{FileIndex, Unit, UnitKind};
+use crbrs_lib::syntax::lexer::Span;
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, Location, OneOf, Position, Range, SymbolKind, Uri,
+    WorkspaceSymbol,
+};
+
+pub struct ProjectFile {
+    pub uri: Uri,
+    pub index: FileIndex,
+    /// Files the document includes.
+    pub includes: Vec<Uri>,
+}
+
+/// Index of every open document and workspace file.
+pub struct ProjectIndex {
+    files: Vec<ProjectFile>,
+}
+
+impl ProjectIndex {
+    pub fn new(files: Vec<ProjectFile>) -> Self {
+        ProjectIndex { files }
+    }
+
+    fn file(&self, uri: &Uri) -> Option<&FileIndex> {
+        self.files.iter().find(|f| f.uri == *uri).map(|f| &f.index)
+    }
+
+    /// Finds a routine declared anywhere in the project, preferring `from` itself and the files
+    /// it includes when several declare it.
+    fn routine(&self, name: &str, from: Option<&Uri>) -> Option<(&Uri, &Unit)> {
+        let includes = from.and_then(|uri| self.files.iter().find(|f| f.uri == *uri)).map(|f| f.includes.as_slice()).unwrap_or_default();
+        let declared = |file: &&ProjectFile| file.index.routines().any(|u| u.name.eq_ignore_ascii_case(name));
+        let file = self
+            .files
+            .iter()
+            .filter(declared)
+            .min_by_key(|f| (Some(&f.uri) != from, !includes.contains(&f.uri)))?;
+        file.index.routines().find(|u| u.name.eq_ignore_ascii_case(name)).map(|u| (&file.uri, u))
+    }
+
+    /// Routines whose name contains `query` (ignoring case), for `workspace/symbol`.
+    pub fn workspace_symbols(&self, query: &str) -> Vec<WorkspaceSymbol> {
+        let query = query.to_ascii_lowercase();
+        let mut out = Vec::new();
+        for file in &self.files {
+            for unit in file.index.routines().filter(|u| u.name.to_ascii_lowercase().contains(&query)) {
+                let item = item(&file.uri, unit);
+                out.push(WorkspaceSymbol {
+                    name: item.name,
+                    kind: item.kind,
+                    tags: None,
+                    container_name: item.detail,
+                    location: OneOf::Left(Location { uri: item.uri, range: item.selection_range }),
+                    data: None,
+                });
+            }
+        }
+        out
+    }
+
+    /// Item for the routine declared or called at `position`, or for the scan containing it.
+    pub fn prepare(&self, uri: &Uri, position: Position) -> Option<Vec<CallHierarchyItem>> {
+        let file = self.file(uri)?;
+        let on = |span: &Span| span.line == position.line && span.col <= position.character && position.character <= end_col(span);
+
+        if let Some(unit) = file.units.iter().find(|u| u.is_routine() && on(&u.name_span)) {
+            return Some(vec![item(uri, unit)]);
+        }
+        if let Some((decl_uri, unit)) = file.calls.iter().find(|c| on(&c.span)).and_then(|c| self.routine(&c.callee, Some(uri))) {
+            return Some(vec![item(decl_uri, unit)]);
+        }
+        let unit = &file.units[file.unit_at_line(position.line)?];
+        Some(vec![item(uri, unit)])
+    }
+
+    pub fn incoming(&self, target: &CallHierarchyItem) -> Vec<CallHierarchyIncomingCall> {
+        if !self.files.iter().any(|f| f.uri == target.uri && f.index.routines().any(|u| u.name == target.name)) {
+            return Vec::new();
+        }
+        let mut out: Vec<CallHierarchyIncomingCall> = Vec::new();
+        for file in &self.files {
+            for call in file.index.calls_to(&target.name) {
+                if self.routine(&call.callee, Some(&file.uri)).is_none_or(|(uri, _)| *uri != target.uri) {
+                    continue; // calls a routine of the same name declared elsewhere
+                }
+                let from = item(&file.uri, &file.index.units[call.caller]);
+                match out.iter_mut().find(|c| c.from == from) {
+                    Some(existing) => existing.from_ranges.push(range(&call.span)),
+                    None => out.push(CallHierarchyIncomingCall { from, from_ranges: vec![range(&call.span)] }),
+                }
+            }
+        }
+        out
+    }
+
+    pub fn outgoing(&self, source: &CallHierarchyItem) -> Vec<CallHierarchyOutgoingCall> {
+        let Some(file) = self.file(&source.uri) else {
+            return Vec::new();
+        };
+        let Some(caller) = file.units.iter().position(|u| u.name == source.name && u.start.line == source.range.start.line) else {
+            return Vec::new();
+        };
+        let mut out: Vec<CallHierarchyOutgoingCall> = Vec::new();
+        for call in file.calls_from(caller) {
+            let Some((uri, unit)) = self.routine(&call.callee, Some(&source.uri)) else {
+                continue; // built-in instruction or unknown name
+            };
+            let to = item(uri, unit);
+            match out.iter_mut().find(|c| c.to == to) {
+                Some(existing) => existing.from_ranges.push(range(&call.span)),
+                None => out.push(CallHierarchyOutgoingCall { to, from_ranges: vec![range(&call.span)] }),
+            }
+        }
+        out
+    }
+}
+
+fn end_col(span: &Span) -> u32 {
+    span.col + (span.end - span.start) as u32
+}
+
+pub(crate) fn range(span: &Span) -> Range {
+    Range {
+        start: Position { line: span.line, character: span.col },
+        end: Position { line: span.line, character: end_col(span) },
+    }
+}
+
+fn item(uri: &Uri, unit: &Unit) -> CallHierarchyItem {
+    let (kind, detail) = match unit.kind {
+        UnitKind::Sub => (SymbolKind::FUNCTION, "Sub"),
+        UnitKind::Function => (SymbolKind::FUNCTION, "Function"),
+        UnitKind::Scan => (SymbolKind::EVENT, "main scan"),
+        UnitKind::SlowSequence => (SymbolKind::EVENT, "slow sequence scan"),
+        UnitKind::Program => (SymbolKind::MODULE, "program body"),
+    };
+    CallHierarchyItem {
+        name: unit.name.clone(),
+        kind,
+        tags: None,
+        detail: Some(detail.to_string()),
+        uri: uri.clone(),
+        range: Range { start: range(&unit.start).start, end: range(&unit.end).end },
+        selection_range: range(&unit.name_span),
+        data: None,
+    }
+}
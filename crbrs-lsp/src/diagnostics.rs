@@ -0,0 +1,153 @@
+// crbrs-lsp/src/diagnostics.rs
+//! Diagnostics published for a document, from two sources: the native linter runs on every
+//! change (it is fast and needs neither Wine nor a compiler), the vendor compiler only on open
+//! and save. Both are merged into one `publishDiagnostics`, told apart by their `source`.
+//! Rule severities follow the `[lint]` table of the config, and the `spelling` rule accepts the
+//! words of the project's word lists. Repeated diagnostics are published once, and at most
+//! `max_diagnostics` of them, most severe first (see [`condense`]).
+//! Diagnostics that follow from an earlier problem (a name that is undeclared because its
+//! declaration could not be parsed) carry `relatedInformation` pointing at it.
+
+use crate::call_hierarchy::range;
+use crate::file_uri_to_pathbuf;
+use crbrs_lib::syntax::lint::{Related, Severity};
+use crbrs_lib::{tr, Error as CrbrsError, Settings};
+use lsp_types::{Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Position, Range, Uri};
+
+pub const LINT_SOURCE: &str = "crbrs-lint";
+pub const COMPILER_SOURCE: &str = "crbrs-compiler";
+/// Problems running the toolchain itself.
+pub const TOOL_SOURCE: &str = "crbrs-lsp";
+
+fn severity(severity: Severity) -> DiagnosticSeverity {
+    match severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Info => DiagnosticSeverity::INFORMATION,
+        Severity::Hint => DiagnosticSeverity::HINT,
+    }
+}
+
+/// `related` as LSP related information in the document `uri`, or `None` if there is none.
+fn related_information(uri: &Uri, related: Vec<Related>) -> Option<Vec<DiagnosticRelatedInformation>> {
+    let information: Vec<DiagnosticRelatedInformation> = related
+        .into_iter()
+        .map(|r| DiagnosticRelatedInformation { location: Location { uri: uri.clone(), range: range(&r.span) }, message: r.message })
+        .collect();
+    (!information.is_empty()).then_some(information)
+}
+
+/// The linter's diagnostics for the document `uri` with `content`, with the configured rule
+/// levels applied (see [`crbrs_lib::lint`]).
+pub fn lint(uri: &Uri, content: &str, settings: &Settings) -> Vec<Diagnostic> {
+    let path = file_uri_to_pathbuf(uri).ok();
+    crbrs_lib::lint::lint(content, path.as_deref(), settings)
+        .into_iter()
+        .map(|d| Diagnostic {
+            range: range(&d.span),
+            severity: Some(severity(d.severity)),
+            code: Some(NumberOrString::String(d.code.to_string())),
+            source: Some(LINT_SOURCE.to_string()),
+            related_information: related_information(uri, d.related),
+            message: d.message,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// `diagnostics` without repeats (same range, severity, source and message), most severe
+/// first, and cut to `limit` (0 for no limit) with a final note saying how many were left out,
+/// so a badly broken file does not bury the editor in cascading errors.
+pub fn condense(diagnostics: Vec<Diagnostic>, limit: usize) -> Vec<Diagnostic> {
+    let mut unique: Vec<Diagnostic> = Vec::with_capacity(diagnostics.len());
+    for diagnostic in diagnostics {
+        let repeat = unique.iter().any(|d| {
+            d.range == diagnostic.range && d.severity == diagnostic.severity && d.source == diagnostic.source && d.message == diagnostic.message
+        });
+        if !repeat {
+            unique.push(diagnostic);
+        }
+    }
+    // Stable, so diagnostics of equal severity keep their order. ERROR is 1, HINT 4.
+    unique.sort_by_key(|d| d.severity.unwrap_or(DiagnosticSeverity::ERROR));
+    let hidden = crbrs_lib::diagnostics::truncate(&mut unique, limit);
+    if hidden > 0 {
+        let last = unique.last().map(|d| d.range).unwrap_or_default();
+        unique.push(Diagnostic {
+            range: last,
+            severity: Some(DiagnosticSeverity::INFORMATION),
+            source: Some(TOOL_SOURCE.to_string()),
+            message: tr!("lsp-more-diagnostics", count = hidden, limit = limit),
+            ..Default::default()
+        });
+    }
+    unique
+}
+
+/// Compiles `content` (a copy in the document's scratch directory, so unsaved text can be
+/// checked) and returns the compiler's errors, or a single diagnostic if the compiler could not be run.
+pub fn compile(uri: &Uri, content: &str, settings: &Settings) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let file_path = match file_uri_to_pathbuf(uri) {
+        Ok(p) => p,
+        Err(err_msg) => {
+            eprintln!("LSP Error: Could not convert URI to file path: {} (URI: {:?})", err_msg, uri);
+            diagnostics.push(Diagnostic {
+                range: Range::default(), severity: Some(DiagnosticSeverity::ERROR),
+                source: Some(TOOL_SOURCE.to_string()),
+                message: tr!("lsp-invalid-uri", error = err_msg, uri = format!("{:?}", uri)),
+                ..Default::default()
+            });
+            return diagnostics;
+        }
+    };
+
+    let temp_dir = match crate::memory::scratch_dir(uri) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("LSP Error: Could not create temp dir: {}", e);
+            diagnostics.push(Diagnostic {
+                range: Range::default(), severity: Some(DiagnosticSeverity::ERROR),
+                source: Some(TOOL_SOURCE.to_string()),
+                message: tr!("lsp-temp-dir-failed"),
+                ..Default::default()
+            });
+            return diagnostics;
+        }
+    };
+    let original_filename = file_path.file_name().unwrap_or_else(|| std::ffi::OsStr::new("lsp_temp.crb"));
+    let temp_file_path = temp_dir.join(original_filename);
+    if let Err(e) = std::fs::write(&temp_file_path, content) {
+        eprintln!("LSP Error: Could not write to temp file {:?}: {}", temp_file_path, e);
+        diagnostics.push(Diagnostic {
+            range: Range::default(), severity: Some(DiagnosticSeverity::ERROR),
+            source: Some(TOOL_SOURCE.to_string()),
+            message: tr!("lsp-temp-file-failed"),
+            ..Default::default()
+        });
+        return diagnostics;
+    }
+    eprintln!("LSP: Compiling temp file for diagnostics: {:?}", temp_file_path);
+    match crbrs_lib::compiler::compile_file_impl(&temp_file_path, None, None, settings) {
+        Ok(_) => { eprintln!("LSP: Background compilation successful for {:?}.", temp_file_path); }
+        Err(e) => {
+            let code = Some(NumberOrString::String(e.code().to_string()));
+            match e {
+                CrbrsError::CompilationFailed { errors, .. } => {
+                    eprintln!("LSP: Background compilation of {:?} failed. {} errors found.", temp_file_path, errors.len());
+                    for err_detail in errors {
+                        let line_0_indexed = err_detail.line.unwrap_or(1).saturating_sub(1);
+                        let range = Range { start: Position { line: line_0_indexed, character: 0 }, end: Position { line: line_0_indexed, character: u32::MAX }, };
+                        let related = crbrs_lib::diagnostics::root_cause(content, &err_detail.message).into_iter().collect();
+                        diagnostics.push(Diagnostic { range, severity: Some(DiagnosticSeverity::ERROR), code: code.clone(), source: Some(COMPILER_SOURCE.to_string()), related_information: related_information(uri, related), message: err_detail.message, ..Default::default() });
+                    }
+                }
+                other_crbrs_error => {
+                    eprintln!("LSP: Error during background compilation for {:?}: {}", temp_file_path, other_crbrs_error);
+                    diagnostics.push(Diagnostic { range: Range::default(), severity: Some(DiagnosticSeverity::ERROR), code, source: Some(TOOL_SOURCE.to_string()), message: tr!("lsp-tool-error", error = other_crbrs_error.to_string()), ..Default::default() });
+                }
+            }
+        }
+    }
+    diagnostics
+}
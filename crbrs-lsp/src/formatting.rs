@@ -0,0 +1,30 @@
+// crbrs-lsp/src/formatting.rs
+//! `textDocument/formatting`, with the options `crbrs fmt` would use for the file (see
+//! [`crbrs_lib::format`]) rather than the editor's tab settings, so a program is formatted the
+//! same from the editor and the command line. Documents that are not files (untitled ones) get
+//! the default options.
+
+use crate::file_uri_to_pathbuf;
+use crbrs_lib::syntax::format::{format, FormatOptions};
+use lsp_types::{Position, Range, TextEdit, Uri};
+
+/// The position just after the last character of `text`.
+fn end_of(text: &str) -> Position {
+    let line = text.matches('\n').count() as u32;
+    let last = text.rsplit('\n').next().unwrap_or_default();
+    Position { line, character: last.encode_utf16().count() as u32 }
+}
+
+/// Edits formatting the document `uri` with `content`: one replacing it whole, or none if it
+/// is formatted already.
+pub fn format_document(uri: &Uri, content: &str) -> Result<Vec<TextEdit>, String> {
+    let options = match file_uri_to_pathbuf(uri) {
+        Ok(path) => crbrs_lib::format::options_for(&path).map_err(|e| e.to_string())?,
+        Err(_) => FormatOptions::default(),
+    };
+    let formatted = format(content, &options);
+    if formatted == content {
+        return Ok(Vec::new());
+    }
+    Ok(vec![TextEdit { range: Range { start: Position::default(), end: end_of(content) }, new_text: formatted }])
+}
@@ -0,0 +1,484 @@
+// crbrs-lsp/src/lib.rs
+//! The crbrs language server. `main.rs` serves it over stdio; [`serve`] takes any
+//! [`Connection`], so the protocol tests can drive it over in-memory channels.
+mod call_hierarchy;
+mod diagnostics;
+mod formatting;
+mod memory;
+mod refactor;
+mod status;
+mod toolchain;
+mod workspace;
+
+use lsp_server::{Connection, Message, Notification as LspServerNotification, Request, RequestId, Response};
+use lsp_types::{
+    notification::Notification as LspNotificationTrait,
+    request::Request as LspRequestTrait,
+    ApplyWorkspaceEditParams, CallHierarchyIncomingCallsParams, CallHierarchyOutgoingCallsParams,
+    CallHierarchyPrepareParams, CallHierarchyServerCapability, ClientCapabilities, CodeActionKind, CodeActionOptions, CodeActionParams,
+    CodeActionProviderCapability, Diagnostic, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, DocumentFormattingParams, ExecuteCommandOptions, ExecuteCommandParams,
+    InitializeParams, OneOf, PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Uri, WorkspaceSymbolParams, WorkspaceSymbolResponse,
+};
+use crbrs_lib::Settings;
+use std::collections::HashMap;
+use std::path::PathBuf; // Keep this for file_uri_to_pathbuf
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often to check whether the config file changed.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+struct DocumentState {
+    uri: Uri,
+    content: String,
+    version: Option<i32>,
+    /// From the last compile (open or save), shown with the lint results until the next one.
+    compiler_diagnostics: Vec<Diagnostic>,
+    /// How long the last compile took.
+    last_compile_ms: Option<u64>,
+}
+
+impl DocumentState {
+    fn new(uri: Uri, content: String, version: Option<i32>) -> Self {
+        DocumentState { uri, content, version, compiler_diagnostics: Vec::new(), last_compile_ms: None }
+    }
+
+    /// Runs the compiler on the current content, keeping its diagnostics and duration.
+    fn compile(&mut self, settings: &Settings) {
+        let start = Instant::now();
+        self.compiler_diagnostics = diagnostics::compile(&self.uri, &self.content, settings);
+        self.last_compile_ms = Some(start.elapsed().as_millis() as u64);
+    }
+}
+
+/// A toolchain command that ran on a worker thread, for the main loop to answer.
+struct CommandDone {
+    id: RequestId,
+    command: String,
+    /// The copy of the document the command ran on.
+    document: DocumentState,
+    outcome: Result<(serde_json::Value, Option<lsp_types::ShowMessageParams>), String>,
+}
+
+fn file_uri_to_pathbuf(uri: &Uri) -> Result<PathBuf, String> {
+    if uri.scheme().is_some_and(|s| s.as_str() == "file") {
+        let path_str = uri.path().as_str();
+        #[cfg(windows)]
+        let corrected_path_str = if path_str.starts_with('/') && path_str.get(1..3).map_or(false, |s| s.chars().nth(1) == Some(':')) {
+            path_str.get(1..).unwrap_or(path_str)
+        } else { path_str };
+        #[cfg(not(windows))]
+        let corrected_path_str = path_str;
+        Ok(PathBuf::from(corrected_path_str))
+    } else {
+        Err(format!("URI scheme is not 'file': {:?}", uri.scheme().map_or("<none>", |s| s.as_str())))
+    }
+}
+
+/// Runs the server on `connection` (initialize handshake, then messages until shutdown and
+/// exit).
+pub fn serve(connection: Connection) -> anyhow::Result<()> {
+    // Background compiles run on temporary copies; keep them out of the build history.
+    crbrs_lib::history::set_enabled(false);
+    let server_capabilities = initialize_server_capabilities();
+    let initialize_params_json = connection.initialize(serde_json::to_value(&server_capabilities)?)?;
+    let initialize_params: InitializeParams = serde_json::from_value(initialize_params_json)?;
+    // Messages follow the editor's UI language unless CRBRS_LANG says otherwise.
+    if let (Err(_), Some(locale)) = (std::env::var(crbrs_lib::i18n::LANG_ENV_VAR), &initialize_params.locale) {
+        crbrs_lib::i18n::set_locale(locale);
+    }
+    let _client_capabilities: &ClientCapabilities = &initialize_params.capabilities;
+    eprintln!("crbrs-lsp server initialized.");
+
+    let settings_arc = Arc::new(Mutex::new(
+        crbrs_lib::config::load_settings().unwrap_or_else(|e| {
+            eprintln!("LSP: Failed to load crbrs settings: {}. Using defaults.", e);
+            Settings::default()
+        }),
+    ));
+    let open_documents_arc = Arc::new(Mutex::new(HashMap::<Uri, DocumentState>::new()));
+    let workspaces = workspace::load(&workspace::roots(&initialize_params));
+
+    main_loop(connection, settings_arc, open_documents_arc, &workspaces)?;
+    workspace::save(&workspaces);
+    memory::release_all();
+    Ok(())
+}
+
+fn initialize_server_capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+            code_action_kinds: Some(vec![
+                refactor::organize_declarations_kind(),
+                refactor::fix_keyword_case_kind(),
+                CodeActionKind::REFACTOR_EXTRACT,
+            ]),
+            ..Default::default()
+        })),
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        workspace_symbol_provider: Some(OneOf::Left(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        execute_command_provider: Some(ExecuteCommandOptions {
+            commands: refactor::commands().into_iter().chain(toolchain::commands()).collect(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn main_loop(
+    connection: Connection,
+    settings_arc: Arc<Mutex<Settings>>, // Renamed for clarity
+    open_documents_arc: Arc<Mutex<HashMap<Uri, DocumentState>>>, // Renamed for clarity
+    workspaces: &workspace::Workspaces,
+) -> anyhow::Result<()> {
+    // Ids for requests we send to the client (workspace/applyEdit).
+    let mut next_request_id = 0i32;
+    // The document the status notifications are about, and the last status sent.
+    let mut current_uri: Option<Uri> = None;
+    let mut last_status: Option<status::Status> = None;
+    let mut config_modified = config_modified_time();
+    let config_poll = crossbeam_channel::tick(CONFIG_POLL_INTERVAL);
+    let cleanup = crossbeam_channel::tick(memory::CLEANUP_INTERVAL);
+    // Toolchain commands (compiling can take a while) run on worker threads that report here.
+    let (command_done, commands_done) = crossbeam_channel::unbounded::<CommandDone>();
+    memory::cleanup(&workspace::roots_of(workspaces));
+    send_status(&connection, &open_documents_arc, current_uri.as_ref(), &settings_arc, &mut last_status)?;
+    loop {
+        let msg = crossbeam_channel::select! {
+            recv(connection.receiver) -> msg => match msg {
+                Ok(msg) => msg,
+                Err(_) => break,
+            },
+            recv(config_poll) -> _ => {
+                let modified = config_modified_time();
+                if modified != config_modified {
+                    config_modified = modified;
+                    eprintln!("LSP: Config file changed; reloading settings.");
+                    match crbrs_lib::config::load_settings() {
+                        Ok(settings) => *settings_arc.lock().unwrap() = settings,
+                        Err(e) => eprintln!("LSP: Failed to reload crbrs settings: {}. Keeping the previous ones.", e),
+                    }
+                    send_status(&connection, &open_documents_arc, current_uri.as_ref(), &settings_arc, &mut last_status)?;
+                }
+                continue;
+            },
+            recv(cleanup) -> _ => {
+                memory::cleanup(&workspace::roots_of(workspaces));
+                continue;
+            },
+            recv(commands_done) -> done => {
+                if let Ok(done) = done {
+                    finish_command(&connection, &open_documents_arc, &settings_arc, done)?;
+                    send_status(&connection, &open_documents_arc, current_uri.as_ref(), &settings_arc, &mut last_status)?;
+                }
+                continue;
+            },
+        };
+        // For concise logging, let's see the method for requests/notifications
+        match &msg {
+            Message::Request(req) => eprintln!("LSP Received Request: method = {}", req.method),
+            Message::Notification(not) => eprintln!("LSP Received Notification: method = {}", not.method),
+            Message::Response(_) => eprintln!("LSP Received Response"),
+        }
+
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    eprintln!("LSP: Shutdown request received, exiting main loop.");
+                    return Ok(());
+                }
+                match req.method.as_str() {
+                    lsp_types::request::CodeActionRequest::METHOD => {
+                        let Some(params) = request_params::<CodeActionParams>(&connection, &req.id, req.params)? else { continue };
+                        let actions = match open_documents_arc.lock().unwrap().get(&params.text_document.uri) {
+                            Some(doc_state) => refactor::code_actions(&params, &doc_state.content),
+                            None => Vec::new(),
+                        };
+                        connection.sender.send(Message::Response(Response::new_ok(req.id, actions)))?;
+                    }
+                    lsp_types::request::CallHierarchyPrepare::METHOD => {
+                        let Some(params) = request_params::<CallHierarchyPrepareParams>(&connection, &req.id, req.params)? else { continue };
+                        let position = params.text_document_position_params;
+                        let items = project_index(&open_documents_arc, workspaces).prepare(&position.text_document.uri, position.position);
+                        connection.sender.send(Message::Response(Response::new_ok(req.id, items)))?;
+                    }
+                    lsp_types::request::CallHierarchyIncomingCalls::METHOD => {
+                        let Some(params) = request_params::<CallHierarchyIncomingCallsParams>(&connection, &req.id, req.params)? else { continue };
+                        let calls = project_index(&open_documents_arc, workspaces).incoming(&params.item);
+                        connection.sender.send(Message::Response(Response::new_ok(req.id, calls)))?;
+                    }
+                    lsp_types::request::CallHierarchyOutgoingCalls::METHOD => {
+                        let Some(params) = request_params::<CallHierarchyOutgoingCallsParams>(&connection, &req.id, req.params)? else { continue };
+                        let calls = project_index(&open_documents_arc, workspaces).outgoing(&params.item);
+                        connection.sender.send(Message::Response(Response::new_ok(req.id, calls)))?;
+                    }
+                    lsp_types::request::WorkspaceSymbolRequest::METHOD => {
+                        let Some(params) = request_params::<WorkspaceSymbolParams>(&connection, &req.id, req.params)? else { continue };
+                        let symbols = project_index(&open_documents_arc, workspaces).workspace_symbols(&params.query);
+                        connection.sender.send(Message::Response(Response::new_ok(req.id, WorkspaceSymbolResponse::Nested(symbols))))?;
+                    }
+                    lsp_types::request::Formatting::METHOD => {
+                        let Some(params) = request_params::<DocumentFormattingParams>(&connection, &req.id, req.params)? else { continue };
+                        let uri = params.text_document.uri;
+                        let content = open_documents_arc.lock().unwrap().get(&uri).map(|d| d.content.clone());
+                        let resp = match content.map(|content| formatting::format_document(&uri, &content)) {
+                            Some(Ok(edits)) => Response::new_ok(req.id, edits),
+                            Some(Err(e)) => {
+                                eprintln!("LSP: Formatting failed: {}", e);
+                                Response::new_err(req.id, lsp_server::ErrorCode::RequestFailed as i32, e)
+                            }
+                            None => Response::new_ok(req.id, serde_json::Value::Null),
+                        };
+                        connection.sender.send(Message::Response(resp))?;
+                    }
+                    memory::METHOD => {
+                        let status = memory::status(open_documents_arc.lock().unwrap().values(), workspaces);
+                        connection.sender.send(Message::Response(Response::new_ok(req.id, status)))?;
+                    }
+                    lsp_types::request::ExecuteCommand::METHOD => {
+                        let Some(params) = request_params::<ExecuteCommandParams>(&connection, &req.id, req.params)? else { continue };
+                        eprintln!("LSP: Executing command: {}", params.command);
+                        if toolchain::is_toolchain_command(&params.command) {
+                            // Run on a copy, so edits and other requests are not held up meanwhile.
+                            let document = toolchain::document_uri(&params).and_then(|uri| {
+                                open_documents_arc.lock().unwrap().get(&uri).cloned().ok_or_else(|| format!("Document is not open: {:?}", uri))
+                            });
+                            match document {
+                                Ok(mut document) => {
+                                    let current_settings = settings_arc.lock().unwrap().clone();
+                                    let command_done = command_done.clone();
+                                    std::thread::spawn(move || {
+                                        let uri = document.uri.clone();
+                                        let outcome = toolchain::execute_command(&params.command, &uri, &mut document, &current_settings);
+                                        // Fails only if the server has shut down meanwhile.
+                                        let _ = command_done.send(CommandDone { id: req.id, command: params.command, document, outcome });
+                                    });
+                                }
+                                Err(e) => {
+                                    eprintln!("LSP: Command failed: {}", e);
+                                    let resp = Response::new_err(req.id, lsp_server::ErrorCode::RequestFailed as i32, e);
+                                    connection.sender.send(Message::Response(resp))?;
+                                }
+                            }
+                            continue;
+                        }
+                        let result = refactor::execute_command(params, |uri| {
+                            open_documents_arc.lock().unwrap().get(uri).map(|d| d.content.clone())
+                        });
+                        let resp = match result {
+                            Ok(edit) => {
+                                if let Some((label, edit)) = edit {
+                                    next_request_id += 1;
+                                    let apply = Request::new(
+                                        RequestId::from(next_request_id),
+                                        lsp_types::request::ApplyWorkspaceEdit::METHOD.to_string(),
+                                        ApplyWorkspaceEditParams { label: Some(label), edit },
+                                    );
+                                    connection.sender.send(Message::Request(apply))?;
+                                }
+                                Response::new_ok(req.id, serde_json::Value::Null)
+                            }
+                            Err(e) => {
+                                eprintln!("LSP: Command failed: {}", e);
+                                Response::new_err(req.id, lsp_server::ErrorCode::RequestFailed as i32, e)
+                            }
+                        };
+                        connection.sender.send(Message::Response(resp))?;
+                    }
+                    _ => {
+                        eprintln!("LSP: Unhandled request: method = {}", req.method);
+                        let resp = Response::new_err(
+                            req.id,
+                            lsp_server::ErrorCode::MethodNotFound as i32,
+                            format!("Method '{}' not handled by crbrs-lsp.", req.method),
+                        );
+                        connection.sender.send(Message::Response(resp))?;
+                    }
+                }
+            }
+            Message::Response(resp) => {
+                eprintln!("LSP: Received (and ignored) response: id = {:?}", resp.id);
+            }
+            Message::Notification(not) => {
+                match not.method.as_str() {
+                    "textDocument/didOpen" => {
+                        let params: DidOpenTextDocumentParams = serde_json::from_value(not.params)?;
+                        let doc_text = params.text_document; // This is TextDocumentItem
+                        eprintln!("LSP: Opened file: {:?}", doc_text.uri);
+                        let current_settings = settings_arc.lock().unwrap().clone(); // Clone settings for this task
+                        let mut document_state = DocumentState::new(doc_text.uri.clone(), doc_text.text, Some(doc_text.version));
+                        document_state.compile(&current_settings);
+                        publish_diagnostics_for_uri(&connection, &document_state, &current_settings)?;
+                        open_documents_arc.lock().unwrap().insert(doc_text.uri.clone(), document_state);
+                        current_uri = Some(doc_text.uri);
+                        send_status(&connection, &open_documents_arc, current_uri.as_ref(), &settings_arc, &mut last_status)?;
+                    }
+                    "textDocument/didChange" => {
+                        let params: DidChangeTextDocumentParams = serde_json::from_value(not.params)?;
+                        let doc_id = params.text_document; // This is VersionedTextDocumentIdentifier
+                        if let Some(change) = params.content_changes.into_iter().next() {
+                            eprintln!("LSP: Changed file: {:?}", doc_id.uri);
+                            let mut documents = open_documents_arc.lock().unwrap();
+                            // Only the linter runs on changes; compiler results wait for the next save.
+                            let mut document_state = DocumentState::new(doc_id.uri.clone(), change.text, Some(doc_id.version));
+                            if let Some(previous) = documents.remove(&doc_id.uri) {
+                                document_state.compiler_diagnostics = previous.compiler_diagnostics;
+                                document_state.last_compile_ms = previous.last_compile_ms;
+                            }
+                            let current_settings = settings_arc.lock().unwrap().clone(); // Clone settings
+                            publish_diagnostics_for_uri(&connection, &document_state, &current_settings)?;
+                            documents.insert(doc_id.uri.clone(), document_state);
+                            current_uri = Some(doc_id.uri);
+                        }
+                    }
+                    "textDocument/didSave" => {
+                        let params: DidSaveTextDocumentParams = serde_json::from_value(not.params)?;
+                        let doc_id = params.text_document; // This is TextDocumentIdentifier
+                        eprintln!("LSP: Saved file: {:?}", doc_id.uri);
+                        if let Some(doc_state) = open_documents_arc.lock().unwrap().get_mut(&doc_id.uri) {
+                            let current_settings = settings_arc.lock().unwrap().clone(); // Clone settings
+                            doc_state.compile(&current_settings);
+                            publish_diagnostics_for_uri(&connection, doc_state, &current_settings)?;
+                            workspace::update(workspaces, doc_state);
+                        } else {
+                            eprintln!("LSP Warning: didSave received for unknown document: {:?}", doc_id.uri);
+                        }
+                        current_uri = Some(doc_id.uri);
+                        send_status(&connection, &open_documents_arc, current_uri.as_ref(), &settings_arc, &mut last_status)?;
+                    }
+                    "textDocument/didClose" => {
+                        let params: DidCloseTextDocumentParams = serde_json::from_value(not.params)?;
+                        let uri = params.text_document.uri;
+                        eprintln!("LSP: Closed file: {:?}", uri);
+                        open_documents_arc.lock().unwrap().remove(&uri);
+                        memory::release(&uri);
+                        // Diagnostics of a closed document would otherwise stay in the problems list.
+                        connection.sender.send(Message::Notification(LspServerNotification {
+                            method: lsp_types::notification::PublishDiagnostics::METHOD.to_string(),
+                            params: serde_json::to_value(PublishDiagnosticsParams { uri: uri.clone(), diagnostics: Vec::new(), version: None })?,
+                        }))?;
+                        if current_uri.as_ref() == Some(&uri) {
+                            current_uri = None;
+                            send_status(&connection, &open_documents_arc, current_uri.as_ref(), &settings_arc, &mut last_status)?;
+                        }
+                    }
+                    "exit" => {
+                        eprintln!("LSP: Exit notification received.");
+                        return Ok(());
+                    }
+                    _ => {
+                        eprintln!("LSP: Unhandled notification: method = {}", not.method);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Answers a toolchain command that finished on a worker thread. A compile's results go to the
+/// document if it is still open, like those of a compile on save.
+fn finish_command(
+    connection: &Connection,
+    open_documents_arc: &Arc<Mutex<HashMap<Uri, DocumentState>>>,
+    settings_arc: &Arc<Mutex<Settings>>,
+    done: CommandDone,
+) -> anyhow::Result<()> {
+    let resp = match done.outcome {
+        Ok((result, message)) => {
+            if done.command == toolchain::COMPILE_CURRENT_FILE {
+                let current_settings = settings_arc.lock().unwrap().clone();
+                if let Some(document) = open_documents_arc.lock().unwrap().get_mut(&done.document.uri) {
+                    document.compiler_diagnostics = done.document.compiler_diagnostics;
+                    document.last_compile_ms = done.document.last_compile_ms;
+                    publish_diagnostics_for_uri(connection, document, &current_settings)?;
+                }
+            }
+            if let Some(message) = message {
+                connection.sender.send(Message::Notification(LspServerNotification {
+                    method: lsp_types::notification::ShowMessage::METHOD.to_string(),
+                    params: serde_json::to_value(message)?,
+                }))?;
+            }
+            Response::new_ok(done.id, result)
+        }
+        Err(e) => {
+            eprintln!("LSP: Command failed: {}", e);
+            Response::new_err(done.id, lsp_server::ErrorCode::RequestFailed as i32, e)
+        }
+    };
+    connection.sender.send(Message::Response(resp))?;
+    Ok(())
+}
+
+/// Reads the params of request `id`, or answers it with `InvalidParams` if they don't fit.
+fn request_params<P: serde::de::DeserializeOwned>(connection: &Connection, id: &RequestId, params: serde_json::Value) -> anyhow::Result<Option<P>> {
+    match serde_json::from_value(params) {
+        Ok(params) => Ok(Some(params)),
+        Err(e) => {
+            eprintln!("LSP: Invalid params: {}", e);
+            let resp = Response::new_err(id.clone(), lsp_server::ErrorCode::InvalidParams as i32, format!("Invalid params: {}", e));
+            connection.sender.send(Message::Response(resp))?;
+            Ok(None)
+        }
+    }
+}
+
+/// Indexes every open document (cheap enough to redo per request) and adds the workspace files.
+fn project_index(
+    open_documents_arc: &Arc<Mutex<HashMap<Uri, DocumentState>>>,
+    workspaces: &workspace::Workspaces,
+) -> call_hierarchy::ProjectIndex {
+    workspace::project_index(open_documents_arc.lock().unwrap().values(), workspaces)
+}
+
+fn config_modified_time() -> Option<SystemTime> {
+    let path = crbrs_lib::config::get_config_file_path().ok()?;
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Sends a `crbrs/status` notification for the document at `uri`, unless nothing changed since
+/// `last_status`.
+fn send_status(
+    connection: &Connection,
+    open_documents_arc: &Arc<Mutex<HashMap<Uri, DocumentState>>>,
+    uri: Option<&Uri>,
+    settings_arc: &Arc<Mutex<Settings>>,
+    last_status: &mut Option<status::Status>,
+) -> anyhow::Result<()> {
+    let settings = settings_arc.lock().unwrap().clone();
+    let documents = open_documents_arc.lock().unwrap();
+    let current = status::status(uri.and_then(|uri| documents.get(uri)), &settings);
+    if last_status.as_ref() == Some(&current) {
+        return Ok(());
+    }
+    connection.sender.send(Message::Notification(LspServerNotification {
+        method: status::METHOD.to_string(),
+        params: serde_json::to_value(&current)?,
+    }))?;
+    *last_status = Some(current);
+    Ok(())
+}
+
+/// Publishes the lint results for the document's current text together with the results of
+/// its last compile.
+fn publish_diagnostics_for_uri(connection: &Connection, document: &DocumentState, settings: &Settings) -> anyhow::Result<()> {
+    eprintln!("LSP: Publishing diagnostics for: {:?}", document.uri);
+    let mut diagnostics = diagnostics::lint(&document.uri, &document.content, settings);
+    diagnostics.extend(document.compiler_diagnostics.iter().cloned());
+    let diagnostics = diagnostics::condense(diagnostics, settings.max_diagnostics);
+    connection.sender.send(Message::Notification(LspServerNotification {
+        method: lsp_types::notification::PublishDiagnostics::METHOD.to_string(), // Correct usage
+        params: serde_json::to_value(PublishDiagnosticsParams {
+            uri: document.uri.clone(),
+            diagnostics,
+            version: document.version,
+        })?,
+    }))?;
+    Ok(())
+}
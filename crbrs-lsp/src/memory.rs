@@ -0,0 +1,140 @@
+// crbrs-lsp/src/memory.rs
+//! Keeping a server that runs for days from growing: compile scratch directories are reused per
+//! document (at most [`MAX_SCRATCH_DIRS`], all under one temporary directory per server
+//! process, see `crbrs_lib::temp`) and removed when the document is closed, a periodic cleanup
+//! deletes temporary files left by servers that crashed or were killed and old stored symbol
+//! indexes, and the
+//! `crbrs/memoryStatus` request reports what the server is holding, for debugging.
+
+use crate::DocumentState;
+use crbrs_lib::{symbol_index, temp};
+use lsp_types::Uri;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const METHOD: &str = "crbrs/memoryStatus";
+
+/// How often [`cleanup`] runs.
+pub const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Scratch directories kept for reuse; the least recently compiled document's goes first.
+const MAX_SCRATCH_DIRS: usize = 16;
+/// Stored symbol indexes kept besides those of the open workspace folders.
+const MAX_STORED_INDEXES: usize = 32;
+
+/// Scratch directories by document, least recently used first.
+static SCRATCH: Mutex<Vec<(Uri, PathBuf)>> = Mutex::new(Vec::new());
+
+/// This process's directory for scratch directories.
+fn scratch_root() -> io::Result<PathBuf> {
+    temp::process_dir("lsp").map_err(io::Error::other)
+}
+
+/// An empty scratch directory for compiling `uri`, the same one on every compile.
+pub fn scratch_dir(uri: &Uri) -> io::Result<PathBuf> {
+    let mut scratch = SCRATCH.lock().unwrap();
+    let dir = match scratch.iter().position(|(u, _)| u == uri) {
+        Some(i) => scratch.remove(i).1,
+        None => {
+            let used: Vec<&PathBuf> = scratch.iter().map(|(_, dir)| dir).collect();
+            let n = (0..).find(|n| !used.iter().any(|dir| dir.ends_with(n.to_string()))).unwrap_or_default();
+            scratch_root()?.join(n.to_string())
+        }
+    };
+    if scratch.len() >= MAX_SCRATCH_DIRS {
+        let (_, oldest) = scratch.remove(0);
+        remove(&oldest);
+    }
+    // Whatever the last compile left there goes.
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    scratch.push((uri.clone(), dir.clone()));
+    Ok(dir)
+}
+
+/// Removes the scratch directory of a closed document.
+pub fn release(uri: &Uri) {
+    let mut scratch = SCRATCH.lock().unwrap();
+    if let Some(i) = scratch.iter().position(|(u, _)| u == uri) {
+        remove(&scratch.remove(i).1);
+    }
+}
+
+/// Removes every scratch directory of this process, on shutdown.
+pub fn release_all() {
+    SCRATCH.lock().unwrap().clear();
+    if let Ok(root) = scratch_root() {
+        remove(&root);
+    }
+}
+
+fn remove(dir: &Path) {
+    if let Err(e) = fs::remove_dir_all(dir) {
+        if e.kind() != io::ErrorKind::NotFound {
+            eprintln!("LSP: Failed to remove scratch directory {:?}: {}", dir, e);
+        }
+    }
+}
+
+/// Deletes temporary files of other processes not touched for `crbrs_lib::temp::STALE_AFTER`
+/// and all but the most recent stored symbol indexes, keeping those of `roots`.
+pub fn cleanup(roots: &[PathBuf]) {
+    match temp::clean(temp::STALE_AFTER) {
+        Ok(removed) => {
+            for (path, _) in removed {
+                eprintln!("LSP: Removed stale temporary directory {:?}", path);
+            }
+        }
+        Err(e) => eprintln!("LSP: Failed to clean temporary files: {}", e),
+    }
+    match symbol_index::prune(MAX_STORED_INDEXES, roots) {
+        Ok(0) => {}
+        Ok(n) => eprintln!("LSP: Removed {} old symbol index(es).", n),
+        Err(e) => eprintln!("LSP: Failed to prune symbol indexes: {}", e),
+    }
+}
+
+/// Answer to `crbrs/memoryStatus`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStatus {
+    pub open_documents: usize,
+    /// Text of the open documents.
+    pub open_document_bytes: usize,
+    /// Compiler diagnostics kept for the open documents.
+    pub compiler_diagnostics: usize,
+    pub workspace_indexes: usize,
+    /// Files in the workspace indexes.
+    pub indexed_files: usize,
+    pub scratch_dirs: usize,
+    /// What the scratch directories hold on disk.
+    pub scratch_bytes: u64,
+}
+
+fn size_of(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else { return 0 };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path).into_iter().flatten().flatten().map(|entry| size_of(&entry.path())).sum()
+}
+
+pub fn status<'a>(documents: impl IntoIterator<Item = &'a DocumentState>, workspaces: &crate::workspace::Workspaces) -> MemoryStatus {
+    let documents: Vec<&DocumentState> = documents.into_iter().collect();
+    let workspaces = workspaces.lock().unwrap();
+    let scratch = SCRATCH.lock().unwrap();
+    MemoryStatus {
+        open_documents: documents.len(),
+        open_document_bytes: documents.iter().map(|d| d.content.len()).sum(),
+        compiler_diagnostics: documents.iter().map(|d| d.compiler_diagnostics.len()).sum(),
+        workspace_indexes: workspaces.len(),
+        indexed_files: workspaces.iter().map(|w| w.files.len()).sum(),
+        scratch_dirs: scratch.len(),
+        scratch_bytes: scratch.iter().map(|(_, dir)| size_of(dir)).sum(),
+    }
+}
@@ -0,0 +1,128 @@
+// crbrs-lsp/src/refactor.rs
+//! Code actions backed by `crbrs_lib::syntax::refactor` and `crbrs_lib::syntax::case`.
+//!
+//! Code actions only carry a command; the edit itself is computed when the client runs
+//! `workspace/executeCommand` and is pushed back with a `workspace/applyEdit` request, so the
+//! refactoring always sees the latest document text.
+
+use crbrs_lib::syntax::case;
+use crbrs_lib::syntax::refactor::{self, LineEdit};
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, Command, ExecuteCommandParams, Position, Range,
+    TextEdit, Uri, WorkspaceEdit,
+};
+use std::collections::HashMap;
+
+pub const ORGANIZE_DECLARATIONS: &str = "crbrs.organizeDeclarations";
+pub const EXTRACT_SUBROUTINE: &str = "crbrs.extractSubroutine";
+pub const FIX_KEYWORD_CASE: &str = "crbrs.fixKeywordCase";
+
+pub fn commands() -> Vec<String> {
+    vec![ORGANIZE_DECLARATIONS.to_string(), EXTRACT_SUBROUTINE.to_string(), FIX_KEYWORD_CASE.to_string()]
+}
+
+pub fn organize_declarations_kind() -> CodeActionKind {
+    CodeActionKind::new("source.organizeDeclarations")
+}
+
+pub fn fix_keyword_case_kind() -> CodeActionKind {
+    CodeActionKind::new("source.fixKeywordCase")
+}
+
+/// Code actions offered for `params.range` in a document whose text is `content`.
+pub fn code_actions(params: &CodeActionParams, content: &str) -> Vec<CodeActionOrCommand> {
+    let uri = serde_json::to_value(&params.text_document.uri).unwrap_or_default();
+    let mut actions = Vec::new();
+
+    if refactor::organize_declarations(content).is_ok_and(|edits| !edits.is_empty()) {
+        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+            title: "Organize declarations".to_string(),
+            kind: Some(organize_declarations_kind()),
+            command: Some(Command {
+                title: "Organize declarations".to_string(),
+                command: ORGANIZE_DECLARATIONS.to_string(),
+                arguments: Some(vec![uri.clone()]),
+            }),
+            ..Default::default()
+        }));
+    }
+
+    if !case::case_fixes(content).is_empty() {
+        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+            title: "Fix keyword case".to_string(),
+            kind: Some(fix_keyword_case_kind()),
+            command: Some(Command {
+                title: "Fix keyword case".to_string(),
+                command: FIX_KEYWORD_CASE.to_string(),
+                arguments: Some(vec![uri.clone()]),
+            }),
+            ..Default::default()
+        }));
+    }
+
+    let range = params.range;
+    // A selection ending at column 0 does not include that line.
+    let end_line = if range.end.character == 0 && range.end.line > range.start.line { range.end.line - 1 } else { range.end.line };
+    let selected_something = range.start != range.end;
+    if selected_something {
+        let name = refactor::unused_name(content, "ExtractedSub");
+        if refactor::extract_subroutine(content, range.start.line, end_line, &name).is_ok() {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Extract subroutine".to_string(),
+                kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+                command: Some(Command {
+                    title: "Extract subroutine".to_string(),
+                    command: EXTRACT_SUBROUTINE.to_string(),
+                    arguments: Some(vec![uri, range.start.line.into(), end_line.into()]),
+                }),
+                ..Default::default()
+            }));
+        }
+    }
+    actions
+}
+
+/// Runs one of our commands, returning the label and edit to send with `workspace/applyEdit`.
+/// `Ok(None)` means there is nothing to change.
+pub fn execute_command(
+    params: ExecuteCommandParams,
+    document_text: impl Fn(&Uri) -> Option<String>,
+) -> Result<Option<(String, WorkspaceEdit)>, String> {
+    let mut args = params.arguments.into_iter();
+    let uri: Uri = args
+        .next()
+        .and_then(|v| serde_json::from_value(v).ok())
+        .ok_or_else(|| format!("{}: missing document URI argument", params.command))?;
+    let content = document_text(&uri).ok_or_else(|| format!("Document is not open: {:?}", uri))?;
+
+    let (label, edits) = match params.command.as_str() {
+        ORGANIZE_DECLARATIONS => ("Organize declarations", refactor::organize_declarations(&content)?),
+        FIX_KEYWORD_CASE => ("Fix keyword case", case::line_edits(&content)),
+        EXTRACT_SUBROUTINE => {
+            let mut line = || args.next().and_then(|v| v.as_u64()).map(|n| n as u32);
+            let (Some(start), Some(end)) = (line(), line()) else {
+                return Err(format!("{}: expected start and end line arguments", params.command));
+            };
+            let name = refactor::unused_name(&content, "ExtractedSub");
+            ("Extract subroutine", refactor::extract_subroutine(&content, start, end, &name)?)
+        }
+        other => return Err(format!("Unknown command '{}'", other)),
+    };
+    if edits.is_empty() {
+        return Ok(None);
+    }
+
+    let text_edits = edits.into_iter().map(to_text_edit).collect();
+    let edit = WorkspaceEdit { changes: Some(HashMap::from([(uri, text_edits)])), ..Default::default() };
+    Ok(Some((label.to_string(), edit)))
+}
+
+fn to_text_edit(edit: LineEdit) -> TextEdit {
+    TextEdit {
+        range: Range {
+            start: Position { line: edit.start_line, character: 0 },
+            end: Position { line: edit.end_line, character: 0 },
+        },
+        new_text: edit.new_text,
+    }
+}
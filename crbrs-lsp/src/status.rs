@@ -0,0 +1,71 @@
+// crbrs-lsp/src/status.rs
+//! `crbrs/status` notifications: the toolchain state for the document being edited, for an
+//! editor extension to show in its status bar. Sent when a document is opened or saved, after
+//! compiles, and when the config file changes (settings edited, compilers installed or
+//! removed), but only if something changed since the last one.
+
+use crate::{file_uri_to_pathbuf, DocumentState};
+use crbrs_lib::compiler::{find_wine, resolve_compiler_for_source, uses_wine};
+use crbrs_lib::{CompilerInfo, Settings};
+use lsp_types::Uri;
+use serde::Serialize;
+
+pub const METHOD: &str = "crbrs/status";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WineState {
+    Ok,
+    Missing,
+    /// On Windows, or the compiler runs natively.
+    NotNeeded,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    /// The document the status is for, the one last opened, edited or saved.
+    pub uri: Option<Uri>,
+    pub compiler_id: Option<String>,
+    pub compiler_version: Option<String>,
+    /// Why no compiler could be selected for the document.
+    pub compiler_error: Option<String>,
+    pub wine: WineState,
+    /// How long the document's last compile took.
+    pub last_compile_ms: Option<u64>,
+}
+
+fn wine_state(compiler: Option<&CompilerInfo>, settings: &Settings) -> WineState {
+    match compiler {
+        Some(info) if !uses_wine(info) => WineState::NotNeeded,
+        None if cfg!(windows) => WineState::NotNeeded,
+        _ if find_wine(settings).is_some() => WineState::Ok,
+        _ => WineState::Missing,
+    }
+}
+
+/// The status for `document`, or only the Wine state without one.
+pub fn status(document: Option<&DocumentState>, settings: &Settings) -> Status {
+    let Some(document) = document else {
+        return Status {
+            uri: None,
+            compiler_id: None,
+            compiler_version: None,
+            compiler_error: None,
+            wine: wine_state(None, settings),
+            last_compile_ms: None,
+        };
+    };
+    let compiler = file_uri_to_pathbuf(&document.uri).and_then(|path| {
+        resolve_compiler_for_source(&path, &document.content, None, settings).map_err(|e| e.to_string())
+    });
+    let info = compiler.as_ref().ok().map(|(_, info)| *info);
+    Status {
+        uri: Some(document.uri.clone()),
+        compiler_id: compiler.as_ref().ok().map(|(id, _)| id.clone()),
+        compiler_version: info.map(|info| info.version.clone()),
+        compiler_error: compiler.as_ref().err().cloned(),
+        wine: wine_state(info, settings),
+        last_compile_ms: document.last_compile_ms,
+    }
+}
@@ -0,0 +1,89 @@
+// crbrs-lsp/src/toolchain.rs
+//! Toolchain commands for editor extensions, run through `workspace/executeCommand` so buttons
+//! and palette entries use the server's settings and open documents instead of shelling out to
+//! the CLI. Each takes the document URI as its first argument and answers with JSON. They run
+//! on a worker thread, on a copy of the document, so the server keeps answering meanwhile.
+//!
+//! * `crbrs.compileCurrentFile`: compiles the document as currently edited, refreshes its
+//!   compiler diagnostics and answers `{ "success", "diagnostics" }`.
+//! * `crbrs.sendToStation`: reserved for sending the program to a logger; fails for now, since
+//!   crbrs cannot talk to loggers yet.
+//! * `crbrs.showWiring`: the terminal connections of the program (see `crbrs_lib::syntax::wiring`)
+//!   with their ranges, for a wiring panel.
+
+use crate::call_hierarchy::range;
+use crate::DocumentState;
+use crbrs_lib::syntax::{parse, wiring};
+use crbrs_lib::{tr, Settings};
+use lsp_types::{DiagnosticSeverity, ExecuteCommandParams, MessageType, ShowMessageParams, Uri};
+
+pub const COMPILE_CURRENT_FILE: &str = "crbrs.compileCurrentFile";
+pub const SEND_TO_STATION: &str = "crbrs.sendToStation";
+pub const SHOW_WIRING: &str = "crbrs.showWiring";
+
+pub fn commands() -> Vec<String> {
+    vec![COMPILE_CURRENT_FILE.to_string(), SEND_TO_STATION.to_string(), SHOW_WIRING.to_string()]
+}
+
+pub fn is_toolchain_command(command: &str) -> bool {
+    commands().iter().any(|c| c == command)
+}
+
+fn file_name(uri: &Uri) -> String {
+    uri.path().as_str().rsplit('/').next().unwrap_or_default().to_string()
+}
+
+/// The document a command applies to: its first argument.
+pub fn document_uri(params: &ExecuteCommandParams) -> Result<Uri, String> {
+    params
+        .arguments
+        .first()
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .ok_or_else(|| format!("{}: missing document URI argument", params.command))
+}
+
+/// Runs one of the toolchain commands on the open document at `uri`. Returns the command's
+/// result and a message for the user, if any.
+pub fn execute_command(
+    command: &str,
+    uri: &Uri,
+    document: &mut DocumentState,
+    settings: &Settings,
+) -> Result<(serde_json::Value, Option<ShowMessageParams>), String> {
+    match command {
+        COMPILE_CURRENT_FILE => {
+            document.compile(settings);
+            let errors = document
+                .compiler_diagnostics
+                .iter()
+                .filter(|d| d.severity == Some(DiagnosticSeverity::ERROR))
+                .count();
+            let message = if errors == 0 {
+                ShowMessageParams { typ: MessageType::INFO, message: tr!("lsp-compile-succeeded", file = file_name(uri)) }
+            } else {
+                ShowMessageParams { typ: MessageType::ERROR, message: tr!("lsp-compile-failed", file = file_name(uri), errors = errors) }
+            };
+            let result = serde_json::json!({ "success": errors == 0, "diagnostics": document.compiler_diagnostics });
+            Ok((result, Some(message)))
+        }
+        SEND_TO_STATION => Err(tr!("lsp-send-unsupported", file = file_name(uri))),
+        SHOW_WIRING => {
+            let program = parse(&document.content);
+            let entries: Vec<serde_json::Value> = wiring::wiring(&document.content, &program)
+                .into_iter()
+                .map(|entry| {
+                    serde_json::json!({
+                        "terminal": entry.terminal,
+                        "role": entry.role,
+                        "instruction": entry.instruction,
+                        "dest": entry.dest,
+                        "reps": entry.reps,
+                        "range": range(&entry.span),
+                    })
+                })
+                .collect();
+            Ok((serde_json::Value::Array(entries), None))
+        }
+        other => Err(format!("Unknown command '{}'", other)),
+    }
+}
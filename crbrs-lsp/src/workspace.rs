@@ -0,0 +1,123 @@
+// crbrs-lsp/src/workspace.rs
+//! Symbol indexes of the workspace folders (see `crbrs_lib::symbol_index`). The stored indexes
+//! are loaded at startup so workspace symbols and call hierarchies across files work at once;
+//! a background thread then revalidates them against the files on disk and stores the result.
+//! Saved documents are re-indexed as they are saved, and the indexes are stored again on
+//! shutdown.
+
+use crate::call_hierarchy::{ProjectFile, ProjectIndex};
+use crate::{file_uri_to_pathbuf, DocumentState};
+use crbrs_lib::symbol_index::{file_includes, WorkspaceIndex};
+use crbrs_lib::syntax::{index, parse};
+use lsp_types::{InitializeParams, Uri};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub type Workspaces = Arc<Mutex<Vec<WorkspaceIndex>>>;
+
+/// Local folders the editor opened.
+pub fn roots(params: &InitializeParams) -> Vec<PathBuf> {
+    params.workspace_folders.iter().flatten().filter_map(|folder| file_uri_to_pathbuf(&folder.uri).ok()).collect()
+}
+
+/// Loads the stored index of every root and starts revalidating them in the background.
+pub fn load(roots: &[PathBuf]) -> Workspaces {
+    let indexes: Vec<WorkspaceIndex> = roots.iter().map(|root| WorkspaceIndex::load(root)).collect();
+    for index in &indexes {
+        eprintln!("LSP: Loaded symbol index of {:?} ({} files).", index.root, index.files.len());
+    }
+    let workspaces = Arc::new(Mutex::new(indexes));
+    let background = Arc::clone(&workspaces);
+    std::thread::spawn(move || {
+        for root in roots_of(&background) {
+            let Some(mut index) = background.lock().unwrap().iter().find(|w| w.root == root).cloned() else { continue };
+            match index.revalidate() {
+                Ok(changed) => {
+                    eprintln!("LSP: Revalidated symbol index of {:?} ({} files).", root, index.files.len());
+                    if changed {
+                        if let Err(e) = index.save() {
+                            eprintln!("LSP: Failed to store symbol index of {:?}: {}", root, e);
+                        }
+                    }
+                    // Files saved meanwhile are newer than what was read from disk.
+                    let mut workspaces = background.lock().unwrap();
+                    if let Some(current) = workspaces.iter_mut().find(|w| w.root == root) {
+                        for (file, entry) in std::mem::take(&mut current.files) {
+                            if index.files.get(&file).is_some_and(|e| e.modified_ms < entry.modified_ms) {
+                                index.files.insert(file, entry);
+                            }
+                        }
+                        *current = index;
+                    }
+                }
+                Err(e) => eprintln!("LSP: Failed to revalidate symbol index of {:?}: {}", root, e),
+            }
+        }
+    });
+    workspaces
+}
+
+pub fn roots_of(workspaces: &Workspaces) -> Vec<PathBuf> {
+    workspaces.lock().unwrap().iter().map(|w| w.root.clone()).collect()
+}
+
+/// Re-indexes a saved document if it belongs to a workspace folder.
+pub fn update(workspaces: &Workspaces, document: &DocumentState) {
+    let Ok(path) = file_uri_to_pathbuf(&document.uri) else { return };
+    if let Some(workspace) = workspaces.lock().unwrap().iter_mut().find(|w| w.contains(&path)) {
+        workspace.update(&path, &document.content);
+    }
+}
+
+/// Stores every index, e.g. on shutdown.
+pub fn save(workspaces: &Workspaces) {
+    for workspace in workspaces.lock().unwrap().iter() {
+        if let Err(e) = workspace.save() {
+            eprintln!("LSP: Failed to store symbol index of {:?}: {}", workspace.root, e);
+        }
+    }
+}
+
+/// `file://` URI for `path`, percent-encoding everything but unreserved characters.
+pub fn path_to_uri(path: &Path) -> Option<Uri> {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from(if path.starts_with('/') { "file://" } else { "file:///" });
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri.parse().ok()
+}
+
+/// Index of the open documents, as edited, and of every other workspace file.
+pub fn project_index<'a>(documents: impl IntoIterator<Item = &'a DocumentState>, workspaces: &Workspaces) -> ProjectIndex {
+    let mut files = Vec::new();
+    let mut open_paths = Vec::new();
+    for document in documents {
+        let path = file_uri_to_pathbuf(&document.uri).ok();
+        let includes = path.as_ref().map(|p| file_includes(p, &document.content)).unwrap_or_default();
+        files.push(ProjectFile {
+            uri: document.uri.clone(),
+            index: index::index(&parse(&document.content)),
+            includes: includes.iter().filter_map(|p| path_to_uri(p)).collect(),
+        });
+        open_paths.extend(path);
+    }
+    for workspace in workspaces.lock().unwrap().iter() {
+        for (file, entry) in &workspace.files {
+            let path = Path::new(file);
+            if open_paths.iter().any(|open| open == path) {
+                continue;
+            }
+            let Some(uri) = path_to_uri(path) else { continue };
+            files.push(ProjectFile {
+                uri,
+                index: entry.index.clone(),
+                includes: entry.includes.iter().filter_map(|p| path_to_uri(p)).collect(),
+            });
+        }
+    }
+    ProjectIndex::new(files)
+}
@@ -0,0 +1,418 @@
+// FILE: crbrs-lsp/tests/lsp_protocol_tests.rs
+
+// Protocol conformance: the server runs in-process over in-memory channels and is driven like an
+// editor would. Settings and scratch directories are process-wide, so the tests share one
+// config (with a fake compiler) and take turns.
+
+use crbrs_lib::config::{load_settings, save_settings};
+use crbrs_lib::installer::link_compiler;
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use serde_json::{json, Value};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+const URI: &str = "file:///project/main.cr1x";
+
+/// Points crbrs at a temporary config with a fake compiler (a shell script that reports "line 2:
+/// Bad is not a keyword" when the source contains "Bad", and takes a second over sources
+/// containing "Slow") associated with `.cr1x`.
+fn environment() -> MutexGuard<'static, ()> {
+    static ENVIRONMENT: OnceLock<(TempDir, Mutex<()>)> = OnceLock::new();
+    let (_, lock) = ENVIRONMENT.get_or_init(|| {
+        let temp_dir = TempDir::new().expect("temp dir");
+        std::env::set_var("XDG_CONFIG_HOME", temp_dir.path().join("config"));
+        std::env::set_var("XDG_DATA_HOME", temp_dir.path().join("data"));
+        std::env::set_var("CRBRS_LANG", "en-US"); // Assertions match the English messages
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let dir = temp_dir.path().join("fake");
+            std::fs::create_dir_all(&dir).expect("compiler dir");
+            let script = dir.join("fakecomp");
+            std::fs::write(
+                &script,
+                "#!/bin/sh\ngrep -q Slow \"$1\" && sleep 1\nif grep -q Bad \"$1\"; then printf '%s -- Compile Failed!\\nline 2: Bad is not a keyword\\n' \"$1\"; \
+                 else echo \"$1 -- Compiled OK.\"; fi\n",
+            )
+            .expect("compiler script");
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).expect("permissions");
+            std::fs::write(
+                dir.join("crbrs-compiler.toml"),
+                "id = \"fake\"\ndescription = \"fake\"\nversion = \"1.0\"\ninstall_subdir = \"\"\nexecutable_name = \"fakecomp\"\nrequires_wine = false\n",
+            )
+            .expect("compiler metadata");
+            let mut settings = load_settings().expect("settings");
+            link_compiler(&mut settings, &dir, None, None).expect("link");
+            settings.file_associations.insert("cr1x".to_string(), "fake".to_string());
+            save_settings(&settings).expect("save");
+        }
+        (temp_dir, Mutex::new(()))
+    });
+    lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The editor's end of a connection to a running server.
+struct Client {
+    connection: Connection,
+    server: Option<JoinHandle<anyhow::Result<()>>>,
+    next_id: i32,
+    /// Notifications received while waiting for something else.
+    notifications: Vec<Notification>,
+    /// Requests from the server (e.g. `workspace/applyEdit`), unanswered.
+    server_requests: Vec<Request>,
+}
+
+impl Client {
+    /// Starts a server and completes the initialize handshake; returns the client and the
+    /// `initialize` result.
+    fn start() -> (Client, Value) {
+        let (server, connection) = Connection::memory();
+        let server = std::thread::spawn(move || crbrs_lsp::serve(server));
+        let mut client = Client { connection, server: Some(server), next_id: 0, notifications: Vec::new(), server_requests: Vec::new() };
+        let result = client.request("initialize", json!({ "capabilities": {}, "processId": null }));
+        client.notify("initialized", json!({}));
+        (client, result.expect("initialize succeeds"))
+    }
+
+    fn recv(&self) -> Message {
+        self.connection.receiver.recv_timeout(TIMEOUT).expect("the server answers")
+    }
+
+    /// Sends a request and waits for its response.
+    fn request(&mut self, method: &str, params: Value) -> Result<Value, lsp_server::ResponseError> {
+        let id = self.send_request(method, params);
+        self.response(id)
+    }
+
+    /// Sends a request without waiting; returns its ID.
+    fn send_request(&mut self, method: &str, params: Value) -> RequestId {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        self.connection.sender.send(Message::Request(Request::new(id.clone(), method.to_string(), params))).unwrap();
+        id
+    }
+
+    /// Waits for the response to the request `id`; a response to another request first fails.
+    fn response(&mut self, id: RequestId) -> Result<Value, lsp_server::ResponseError> {
+        loop {
+            match self.recv() {
+                Message::Response(Response { id: response_id, result, error }) if response_id == id => {
+                    return match error {
+                        Some(error) => Err(error),
+                        None => Ok(result.unwrap_or(Value::Null)),
+                    };
+                }
+                Message::Notification(notification) => self.notifications.push(notification),
+                Message::Request(request) => self.server_requests.push(request),
+                other => panic!("unexpected message: {:?}", other),
+            }
+        }
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.connection.sender.send(Message::Notification(Notification::new(method.to_string(), params))).unwrap();
+    }
+
+    /// Parameters of the next `method` notification.
+    fn notification(&mut self, method: &str) -> Value {
+        if let Some(i) = self.notifications.iter().position(|n| n.method == method) {
+            return self.notifications.remove(i).params;
+        }
+        loop {
+            match self.recv() {
+                Message::Notification(notification) if notification.method == method => return notification.params,
+                Message::Notification(notification) => self.notifications.push(notification),
+                other => panic!("unexpected message while waiting for {}: {:?}", method, other),
+            }
+        }
+    }
+
+    /// Parameters of the next `method` notification about `uri`.
+    fn notification_for(&mut self, method: &str, uri: &str) -> Value {
+        loop {
+            let params = self.notification(method);
+            if params["uri"] == uri {
+                return params;
+            }
+        }
+    }
+
+    /// The next `publishDiagnostics` for `uri`.
+    fn diagnostics(&mut self, uri: &str) -> Value {
+        self.notification_for("textDocument/publishDiagnostics", uri)
+    }
+
+    /// Shuts the server down the way editors do; it must exit cleanly.
+    fn shutdown(mut self) {
+        assert_eq!(self.request("shutdown", Value::Null).expect("shutdown succeeds"), Value::Null);
+        self.notify("exit", Value::Null);
+        let server = self.server.take().unwrap();
+        server.join().expect("the server does not panic").expect("the server exits cleanly");
+    }
+}
+
+fn sources(diagnostics: &Value) -> Vec<&str> {
+    diagnostics["diagnostics"].as_array().unwrap().iter().map(|d| d["source"].as_str().unwrap()).collect()
+}
+
+#[test]
+fn test_initialize_advertises_capabilities() {
+    let _environment = environment();
+    let (client, result) = Client::start();
+    let capabilities = &result["capabilities"];
+    assert_eq!(capabilities["textDocumentSync"], 1, "full document sync");
+    assert_eq!(capabilities["callHierarchyProvider"], true);
+    assert_eq!(capabilities["workspaceSymbolProvider"], true);
+    assert_eq!(capabilities["documentFormattingProvider"], true);
+    assert!(capabilities["codeActionProvider"]["codeActionKinds"].as_array().is_some_and(|kinds| !kinds.is_empty()));
+    let commands = capabilities["executeCommandProvider"]["commands"].as_array().unwrap();
+    assert!(commands.contains(&json!("crbrs.compileCurrentFile")), "{:?}", commands);
+    client.shutdown();
+}
+
+#[test]
+fn test_unknown_requests_fail_without_stopping_the_server() {
+    let _environment = environment();
+    let (mut client, _) = Client::start();
+    let error = client.request("textDocument/unknownFeature", json!({})).expect_err("not handled");
+    assert_eq!(error.code, lsp_server::ErrorCode::MethodNotFound as i32);
+    // Unknown notifications are ignored, and the server keeps answering.
+    client.notify("$/unknownNotification", json!({}));
+    let status = client.request("crbrs/memoryStatus", Value::Null).expect("memory status");
+    assert_eq!(status["openDocuments"], 0);
+    client.shutdown();
+}
+
+#[test]
+fn test_invalid_params_fail_without_stopping_the_server() {
+    let _environment = environment();
+    let (mut client, _) = Client::start();
+    for method in ["textDocument/codeAction", "textDocument/prepareCallHierarchy", "callHierarchy/incomingCalls", "callHierarchy/outgoingCalls", "workspace/symbol", "textDocument/formatting", "workspace/executeCommand"] {
+        let error = client.request(method, json!({ "unexpected": true })).expect_err("params do not fit");
+        assert_eq!(error.code, lsp_server::ErrorCode::InvalidParams as i32, "{}", method);
+    }
+    let status = client.request("crbrs/memoryStatus", Value::Null).expect("memory status");
+    assert_eq!(status["openDocuments"], 0);
+    client.shutdown();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_document_lifecycle_publishes_diagnostics() {
+    let _environment = environment();
+    let (mut client, _) = Client::start();
+
+    // Opening compiles: the fake compiler accepts the program.
+    let text = "BeginProg\r\nEndProg\r\n";
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": URI, "languageId": "crbasic", "version": 1, "text": text } }),
+    );
+    let published = client.diagnostics(URI);
+    assert_eq!(published["version"], 1);
+    assert!(!sources(&published).contains(&"crbrs-compiler"), "{}", published);
+    let status = client.notification_for("crbrs/status", URI);
+    assert_eq!(status["compilerId"], "fake");
+    assert_eq!(status["wine"], "notNeeded");
+
+    // Changes are only linted; compiler results wait for the save.
+    let bad = "BeginProg\r\nBad\r\nEndProg\r\n";
+    client.notify(
+        "textDocument/didChange",
+        json!({ "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": bad }] }),
+    );
+    let published = client.diagnostics(URI);
+    assert_eq!(published["version"], 2);
+    assert!(!sources(&published).contains(&"crbrs-compiler"), "{}", published);
+
+    client.notify("textDocument/didSave", json!({ "textDocument": { "uri": URI } }));
+    let published = client.diagnostics(URI);
+    let compiler: Vec<&Value> =
+        published["diagnostics"].as_array().unwrap().iter().filter(|d| d["source"] == "crbrs-compiler").collect();
+    assert_eq!(compiler.len(), 1, "{}", published);
+    assert_eq!(compiler[0]["range"]["start"]["line"], 1, "compiler lines are 1-based");
+    assert!(compiler[0]["message"].as_str().unwrap().contains("Bad is not a keyword"));
+
+    let status = client.request("crbrs/memoryStatus", Value::Null).expect("memory status");
+    assert_eq!(status["openDocuments"], 1);
+    assert_eq!(status["scratchDirs"], 1);
+
+    // Closing clears the document's diagnostics and releases what was kept for it.
+    client.notify("textDocument/didClose", json!({ "textDocument": { "uri": URI } }));
+    let published = client.diagnostics(URI);
+    assert_eq!(published["diagnostics"], json!([]));
+    let status = client.request("crbrs/memoryStatus", Value::Null).expect("memory status");
+    assert_eq!(status["openDocuments"], 0);
+    assert_eq!(status["scratchDirs"], 0);
+    client.shutdown();
+}
+
+#[test]
+fn test_fix_keyword_case_code_action() {
+    let _environment = environment();
+    let (mut client, _) = Client::start();
+    let text = "Public BattV\nbeginprog\n  scan(5,sec,0,0)\n    battery(BattV) 'battery stays\n  NextScan\nEndProg\n";
+    client.notify("textDocument/didOpen", json!({ "textDocument": { "uri": URI, "languageId": "crbasic", "version": 1, "text": text } }));
+    let range = json!({ "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } });
+    let actions = client
+        .request("textDocument/codeAction", json!({ "textDocument": { "uri": URI }, "range": range, "context": { "diagnostics": [] } }))
+        .expect("code actions");
+    let action = actions.as_array().unwrap().iter().find(|a| a["kind"] == "source.fixKeywordCase").expect("a fix keyword case action").clone();
+
+    let command = &action["command"];
+    client
+        .request("workspace/executeCommand", json!({ "command": command["command"], "arguments": command["arguments"] }))
+        .expect("the command runs");
+    let apply = client.server_requests.pop().expect("the server applies an edit");
+    assert_eq!(apply.method, "workspace/applyEdit");
+    let edits = &apply.params["edit"]["changes"][URI];
+    let new_texts: Vec<&str> = edits.as_array().unwrap().iter().map(|e| e["newText"].as_str().unwrap()).collect();
+    assert_eq!(new_texts, ["BeginProg\n", "  Scan(5,Sec,0,0)\n", "    Battery(BattV) 'battery stays\n"]);
+    assert_eq!(edits[0]["range"], json!({ "start": { "line": 1, "character": 0 }, "end": { "line": 2, "character": 0 } }));
+    client.shutdown();
+}
+
+#[test]
+fn test_formatting_uses_the_project_options() {
+    let _environment = environment();
+    let (mut client, _) = Client::start();
+    let project = TempDir::new().unwrap();
+    std::fs::write(project.path().join(".crbrsfmt.toml"), "indent_style = \"tabs\"\n").unwrap();
+    let uri = format!("file://{}", project.path().join("station.cr1x").display());
+    let text = "BeginProg\r\nScan(1,Sec,0,0)\r\nNextScan\r\nEndProg";
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": uri, "languageId": "crbasic", "version": 1, "text": text } }),
+    );
+    let params = json!({ "textDocument": { "uri": uri }, "options": { "tabSize": 8, "insertSpaces": true } });
+    let edits = client.request("textDocument/formatting", params.clone()).expect("formatting");
+    assert_eq!(edits.as_array().unwrap().len(), 1, "{}", edits);
+    assert_eq!(edits[0]["range"]["end"], json!({ "line": 3, "character": 7 }));
+    assert_eq!(edits[0]["newText"], "BeginProg\r\n\tScan(1,Sec,0,0)\r\n\tNextScan\r\nEndProg");
+
+    let formatted = edits[0]["newText"].clone();
+    client.notify(
+        "textDocument/didChange",
+        json!({ "textDocument": { "uri": uri, "version": 2 }, "contentChanges": [{ "text": formatted }] }),
+    );
+    assert_eq!(client.request("textDocument/formatting", params).expect("formatting"), json!([]));
+    client.shutdown();
+}
+
+#[test]
+fn test_diagnostics_are_capped_with_a_note() {
+    let _environment = environment();
+    let (mut client, _) = Client::start();
+
+    // 60 duplicate declarations, over the default limit of 50.
+    let text = format!("{}BeginProg\r\nEndProg\r\n", "Public A\r\n".repeat(61));
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": URI, "languageId": "crbasic", "version": 1, "text": text } }),
+    );
+    let published = client.diagnostics(URI);
+    let diagnostics = published["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 51, "{}", published);
+    assert!(diagnostics[..50].iter().all(|d| d["code"] == "L002"), "{}", published);
+    // Each duplicate points at the first declaration.
+    let related = &diagnostics[0]["relatedInformation"][0];
+    assert_eq!(related["location"]["uri"], URI);
+    assert_eq!(related["location"]["range"]["start"]["line"], 0);
+    assert_eq!(related["message"], "'A' is first declared here");
+    let note = &diagnostics[50];
+    assert_eq!(note["source"], "crbrs-lsp");
+    assert_eq!(note["severity"], 3);
+    assert!(note["message"].as_str().unwrap().starts_with("…and 10 more problem(s)"), "{}", note);
+    client.shutdown();
+}
+
+#[test]
+fn test_spelling_is_opt_in_and_accepts_project_words() {
+    let _environment = environment();
+    let project = TempDir::new().unwrap();
+    std::fs::write(project.path().join("crbrs-words.txt"), "# site names\nPresure\n").unwrap();
+    let uri = format!("file://{}", project.path().join("station.cr1x").display());
+    let text = "Public AirT 'Air temprature at Presure Creek\r\nBeginProg\r\nEndProg\r\n";
+    let spelling = |published: &Value| -> Vec<String> {
+        let diagnostics = published["diagnostics"].as_array().unwrap().iter();
+        diagnostics.filter(|d| d["code"] == "L015").map(|d| d["message"].as_str().unwrap().to_string()).collect()
+    };
+
+    let (mut client, _) = Client::start();
+    client.notify("textDocument/didOpen", json!({ "textDocument": { "uri": uri, "languageId": "crbasic", "version": 1, "text": text } }));
+    assert_eq!(spelling(&client.diagnostics(&uri)), Vec::<String>::new(), "off by default");
+    client.shutdown();
+
+    let mut settings = load_settings().expect("settings");
+    settings.lint.insert("spelling".to_string(), "info".parse().unwrap());
+    save_settings(&settings).expect("save");
+    let (mut client, _) = Client::start();
+    client.notify("textDocument/didOpen", json!({ "textDocument": { "uri": uri, "languageId": "crbasic", "version": 1, "text": text } }));
+    let published = client.diagnostics(&uri);
+    client.shutdown();
+    settings.lint.remove("spelling");
+    save_settings(&settings).expect("save");
+
+    assert_eq!(spelling(&published), ["'temprature' is misspelled; did you mean 'temperature'?"], "{}", published);
+    let diagnostic = published["diagnostics"].as_array().unwrap().iter().find(|d| d["code"] == "L015").unwrap();
+    assert_eq!(diagnostic["severity"], 3);
+    assert_eq!(diagnostic["range"]["start"], json!({ "line": 0, "character": 17 }));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compile_current_file_command() {
+    let _environment = environment();
+    let (mut client, _) = Client::start();
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": URI, "languageId": "crbasic", "version": 1, "text": "BeginProg\r\nEndProg\r\n" } }),
+    );
+    client.diagnostics(URI);
+    client.notify(
+        "textDocument/didChange",
+        json!({ "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": "BeginProg\r\nBad\r\nEndProg\r\n" }] }),
+    );
+    client.diagnostics(URI);
+
+    // Unsaved changes are compiled, and the diagnostics published again.
+    let result = client
+        .request("workspace/executeCommand", json!({ "command": "crbrs.compileCurrentFile", "arguments": [URI] }))
+        .expect("the command runs");
+    assert_eq!(result["success"], false);
+    assert_eq!(result["diagnostics"].as_array().map(Vec::len), Some(1), "{}", result);
+    assert!(sources(&client.diagnostics(URI)).contains(&"crbrs-compiler"));
+
+    let error = client
+        .request("workspace/executeCommand", json!({ "command": "crbrs.compileCurrentFile", "arguments": ["file:///not/open.cr1x"] }))
+        .expect_err("the document is not open");
+    assert_eq!(error.code, lsp_server::ErrorCode::RequestFailed as i32);
+    client.shutdown();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_commands_do_not_hold_up_other_requests() {
+    let _environment = environment();
+    let (mut client, _) = Client::start();
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": URI, "languageId": "crbasic", "version": 1, "text": "'Slow\r\nBeginProg\r\nEndProg\r\n" } }),
+    );
+    client.diagnostics(URI);
+
+    // The compile takes a second; edits and requests made meanwhile are answered first.
+    let compile = client.send_request("workspace/executeCommand", json!({ "command": "crbrs.compileCurrentFile", "arguments": [URI] }));
+    client.notify(
+        "textDocument/didChange",
+        json!({ "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": "'Slow\r\nBeginProg\r\nBad\r\nEndProg\r\n" }] }),
+    );
+    let edits = client.request("textDocument/formatting", json!({ "textDocument": { "uri": URI }, "options": { "tabSize": 4, "insertSpaces": true } }));
+    assert!(edits.expect("formatting succeeds").as_array().is_some_and(|edits| !edits.is_empty()));
+    let result = client.response(compile).expect("the command runs");
+    assert_eq!(result["success"], true, "the text as it was when the command was given is compiled");
+    client.shutdown();
+}
@@ -0,0 +1,408 @@
+// FILE: crbrs-mock-logger/src/lib.rs
+
+//! A simulated Campbell logger that speaks enough PakBus/BMP5 over TCP, or on a Unix
+//! pseudo-terminal, to test crbrs's logger commands in CI without hardware: Hello, Get Values,
+//! File Send, File Receive, File Control and Clock.
+//!
+//! ```no_run
+//! use crbrs_lib::pakbus::Value;
+//! use crbrs_mock_logger::MockLogger;
+//!
+//! let logger = MockLogger::new().with_status().value("Status", "Battery", Value::Number(11.2)).spawn().unwrap();
+//! // Point crbrs at logger.address(), e.g. `crbrs logger status tcp:127.0.0.1:<port>`.
+//! assert!(logger.files().is_empty());
+//! ```
+//!
+//! Like a real logger it ignores packets for other PakBus addresses (so a wrong address
+//! times out), answers requests with the wrong security code with "permission denied", and
+//! takes files only on the `CPU:` and `USR:` drives. Compiling a program sent to it always
+//! succeeds and updates `Status.ProgName` and `Status.CompileResults`. Data collection is not
+//! simulated, as crbrs does not collect data yet.
+
+use crbrs_lib::pakbus::{self, FileControl, FileReceive, FileSend, GetValues, Packet, Value};
+use std::collections::BTreeMap;
+use std::io::{self, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// BMP5 response code for a file name the logger does not accept.
+const RESPONSE_INVALID_FILE_NAME: u8 = 13;
+
+/// Everything the simulated logger knows, shared between its connections and the test.
+#[derive(Debug, Clone)]
+struct State {
+    address: u16,
+    security_code: u16,
+    /// Keyed by (table, field); names match case-insensitively, as on a logger.
+    values: Vec<((String, String), Value)>,
+    files: BTreeMap<String, Vec<u8>>,
+    running: Option<String>,
+    hold_off: u16,
+    /// Logger clock minus the system clock, in nanoseconds.
+    clock_offset: i128,
+    silent: bool,
+    /// Message types received, in order.
+    requests: Vec<u8>,
+}
+
+impl State {
+    fn value(&self, table: &str, field: &str) -> Option<&Value> {
+        self.values
+            .iter()
+            .find(|((t, f), _)| t.eq_ignore_ascii_case(table) && f.eq_ignore_ascii_case(field))
+            .map(|(_, value)| value)
+    }
+
+    fn set_value(&mut self, table: &str, field: &str, value: Value) {
+        match self.values.iter_mut().find(|((t, f), _)| t.eq_ignore_ascii_case(table) && f.eq_ignore_ascii_case(field)) {
+            Some((_, old)) => *old = value,
+            None => self.values.push(((table.to_string(), field.to_string()), value)),
+        }
+    }
+
+    fn clock(&self) -> SystemTime {
+        let offset = Duration::from_nanos(self.clock_offset.unsigned_abs() as u64);
+        if self.clock_offset < 0 {
+            SystemTime::now() - offset
+        } else {
+            SystemTime::now() + offset
+        }
+    }
+
+    /// Body of the response to `request`; `None` for messages a logger would not answer.
+    fn respond(&mut self, request: &Packet) -> Option<Vec<u8>> {
+        self.requests.push(request.message_type);
+        if request.message_type == pakbus::HELLO {
+            return Some(request.body.clone());
+        }
+        let security_code = u16::from_be_bytes([*request.body.first()?, *request.body.get(1)?]);
+        if self.security_code != 0 && security_code != self.security_code {
+            return Some(vec![pakbus::RESPONSE_PERMISSION_DENIED]);
+        }
+        match request.message_type {
+            pakbus::GET_VALUES => {
+                let get = GetValues::decode(&request.body).ok()?;
+                Some(match self.value(&get.table, &get.field) {
+                    Some(value) => pakbus::encode_values(std::slice::from_ref(value)),
+                    None => vec![pakbus::RESPONSE_INVALID_NAME],
+                })
+            }
+            pakbus::FILE_SEND => {
+                let send = FileSend::decode(&request.body).ok()?;
+                if !["CPU:", "USR:"].iter().any(|drive| send.file_name.starts_with(drive)) {
+                    return Some(vec![RESPONSE_INVALID_FILE_NAME]);
+                }
+                let file = self.files.entry(send.file_name.clone()).or_default();
+                file.truncate(send.offset as usize);
+                file.extend(&send.data);
+                let mut body = vec![pakbus::RESPONSE_OK];
+                body.extend((file.len() as u32).to_be_bytes());
+                Some(body)
+            }
+            pakbus::FILE_RECEIVE => {
+                let receive = FileReceive::decode(&request.body).ok()?;
+                let Some(file) = self.files.get(&receive.file_name) else {
+                    return Some(vec![RESPONSE_INVALID_FILE_NAME]);
+                };
+                let start = (receive.offset as usize).min(file.len());
+                let end = (start + usize::from(receive.swath)).min(file.len());
+                let mut body = vec![pakbus::RESPONSE_OK];
+                body.extend(receive.offset.to_be_bytes());
+                body.extend(&file[start..end]);
+                Some(body)
+            }
+            pakbus::FILE_CONTROL => {
+                let control = FileControl::decode(&request.body).ok()?;
+                if !self.files.contains_key(&control.file_name) {
+                    return Some(vec![RESPONSE_INVALID_FILE_NAME]);
+                }
+                if control.command == pakbus::FILE_DELETE {
+                    self.files.remove(&control.file_name);
+                }
+                if control.command == pakbus::FILE_COMPILE_AND_RUN {
+                    let results = format!("{} -- Compiled in PipelineMode.", control.file_name);
+                    self.set_value("Status", "ProgName", Value::Text(control.file_name.clone()));
+                    self.set_value("Status", "CompileResults", Value::Text(results));
+                    self.running = Some(control.file_name);
+                }
+                let mut body = vec![pakbus::RESPONSE_OK];
+                body.extend(self.hold_off.to_be_bytes());
+                Some(body)
+            }
+            pakbus::CLOCK => {
+                let adjust = |at: usize| request.body.get(at..at + 4).map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+                let (seconds, nanos) = (adjust(2)?, adjust(6)?);
+                let old = self.clock();
+                self.clock_offset += i128::from(seconds) * 1_000_000_000 + i128::from(nanos);
+                let mut body = vec![pakbus::RESPONSE_OK];
+                body.extend(pakbus::encode_nsec(old));
+                Some(body)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Builds a simulated logger.
+#[derive(Debug, Clone)]
+pub struct MockLogger {
+    state: State,
+}
+
+impl Default for MockLogger {
+    fn default() -> Self {
+        MockLogger {
+            state: State {
+                address: pakbus::DEFAULT_ADDRESS,
+                security_code: 0,
+                values: Vec::new(),
+                files: BTreeMap::new(),
+                running: None,
+                hold_off: 0,
+                clock_offset: 0,
+                silent: false,
+                requests: Vec::new(),
+            },
+        }
+    }
+}
+
+impl MockLogger {
+    /// A logger at PakBus address 1, without a security code, tables or files.
+    pub fn new() -> MockLogger {
+        MockLogger::default()
+    }
+
+    /// Adds the `Status` table of a healthy CR1000X.
+    pub fn with_status(self) -> MockLogger {
+        self.value("Status", "OSVersion", Value::Text("CR1000X.Std.06.02".to_string()))
+            .value("Status", "ProgName", Value::Text(String::new()))
+            .value("Status", "CompileResults", Value::Text(String::new()))
+            .value("Status", "Battery", Value::Number(12.8))
+            .value("Status", "LithiumBattery", Value::Number(3.1))
+            .value("Status", "PanelTemp", Value::Number(21.5))
+            .value("Status", "SkippedScan", Value::Number(0.0))
+            .value("Status", "WatchdogErrors", Value::Number(0.0))
+    }
+
+    /// Sets `table.field`, adding it if the logger doesn't have it yet.
+    pub fn value(mut self, table: &str, field: &str, value: Value) -> MockLogger {
+        self.state.set_value(table, field, value);
+        self
+    }
+
+    /// Removes `table.field`, e.g. the lithium battery of a CR300.
+    pub fn without(mut self, table: &str, field: &str) -> MockLogger {
+        self.state.values.retain(|((t, f), _)| !(t.eq_ignore_ascii_case(table) && f.eq_ignore_ascii_case(field)));
+        self
+    }
+
+    /// Puts `source` on the logger as `file_name` (e.g. `CPU:soil.cr1x`) and runs it, as if it
+    /// had been sent and compiled earlier.
+    pub fn running(mut self, file_name: &str, source: &str) -> MockLogger {
+        self.state.files.insert(file_name.to_string(), source.as_bytes().to_vec());
+        self.state.set_value("Status", "ProgName", Value::Text(file_name.to_string()));
+        self.state.running = Some(file_name.to_string());
+        self
+    }
+
+    pub fn pakbus_address(mut self, address: u16) -> MockLogger {
+        self.state.address = address;
+        self
+    }
+
+    pub fn security_code(mut self, code: u16) -> MockLogger {
+        self.state.security_code = code;
+        self
+    }
+
+    /// Seconds the logger asks to be left alone for after File Control.
+    pub fn compile_hold_off(mut self, seconds: u16) -> MockLogger {
+        self.state.hold_off = seconds;
+        self
+    }
+
+    /// Makes the logger's clock run `offset` ahead of (or, if negative, behind) the system clock.
+    pub fn clock_offset(mut self, offset_secs: f64) -> MockLogger {
+        self.state.clock_offset = (offset_secs * 1e9) as i128;
+        self
+    }
+
+    /// Accepts connections but never answers, like a radio link that has gone quiet.
+    pub fn silent(mut self) -> MockLogger {
+        self.state.silent = true;
+        self
+    }
+
+    /// Starts serving on a free port of 127.0.0.1.
+    pub fn spawn(self) -> io::Result<RunningLogger> {
+        self.spawn_on("127.0.0.1:0")
+    }
+
+    /// Starts serving on `address`, e.g. `0.0.0.0:6785`.
+    pub fn spawn_on(self, address: &str) -> io::Result<RunningLogger> {
+        let listener = TcpListener::bind(address)?;
+        let local = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(self.state));
+        let streams = Arc::new(Mutex::new(Vec::new()));
+        let (shared, accepted) = (Arc::clone(&state), Arc::clone(&streams));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                if let Ok(clone) = stream.try_clone() {
+                    lock(&accepted).push(clone);
+                }
+                let state = Arc::clone(&shared);
+                thread::spawn(move || {
+                    if let Err(e) = serve(stream, &state) {
+                        log::debug!("Mock logger connection ended: {}", e);
+                    }
+                });
+            }
+        });
+        log::info!("Mock logger listening on {}", local);
+        Ok(RunningLogger { address: format!("tcp:{}", local), serial_port: None, state, streams })
+    }
+
+    /// Starts serving on a new pseudo-terminal, answering only at `baud`; at any other rate
+    /// requests are ignored, as a real logger would see them garbled.
+    #[cfg(unix)]
+    pub fn spawn_pty(self, baud: u32) -> io::Result<RunningLogger> {
+        use serialport::SerialPort;
+
+        let (master, slave) = serialport::TTYPort::pair()?;
+        let path = slave.name().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "pseudo-terminal has no name"))?;
+        // Closed, so crbrs can open it.
+        drop(slave);
+        let state = Arc::new(Mutex::new(self.state));
+        let shared = Arc::clone(&state);
+        let line = master.try_clone_native()?;
+        let mut writer = master.try_clone_native()?;
+        thread::spawn(move || {
+            let mut reader = BufReader::new(master);
+            loop {
+                match pakbus::read_frame(&mut reader) {
+                    // The line's settings are the ones crbrs opened the other side with.
+                    Ok(frame) if line.baud_rate().ok() == Some(baud) => {
+                        if answer(&frame, &shared, &mut writer).is_err() {
+                            thread::sleep(Duration::from_millis(20));
+                        }
+                    }
+                    Ok(_) => log::debug!("Mock logger ignoring a frame at the wrong baud rate"),
+                    // Timed out, or nothing has the other side open.
+                    Err(_) => thread::sleep(Duration::from_millis(20)),
+                }
+            }
+        });
+        log::info!("Mock logger on {} at {} baud", path, baud);
+        Ok(RunningLogger {
+            address: format!("serial:{}@{}", path, baud),
+            serial_port: Some(path),
+            state,
+            streams: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // A test that panicked while holding the lock leaves the state as it was; keep serving it.
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Answers the packets of one connection until it closes.
+fn serve(stream: TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    loop {
+        let frame = pakbus::read_frame(&mut reader)?;
+        answer(&frame, state, &mut writer)?;
+    }
+}
+
+/// Writes the response to the packet in `frame`, if it is one the logger answers.
+fn answer(frame: &[u8], state: &Mutex<State>, writer: &mut impl Write) -> io::Result<()> {
+    let request = match Packet::decode(frame) {
+        Ok(request) => request,
+        Err(e) => {
+            log::debug!("Mock logger ignoring packet: {}", e);
+            return Ok(());
+        }
+    };
+    let mut state = lock(state);
+    if state.silent || request.destination != state.address {
+        return Ok(());
+    }
+    let Some(body) = state.respond(&request) else { return Ok(()) };
+    drop(state);
+    let response = Packet {
+        destination: request.source,
+        source: request.destination,
+        protocol: request.protocol,
+        message_type: pakbus::response_to(request.message_type),
+        transaction: request.transaction,
+        body,
+    };
+    writer.write_all(&response.encode())?;
+    writer.flush()
+}
+
+/// A simulated logger serving in the background until the process exits.
+#[derive(Debug, Clone)]
+pub struct RunningLogger {
+    address: String,
+    serial_port: Option<String>,
+    state: Arc<Mutex<State>>,
+    /// Every connection accepted, open or not.
+    streams: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl RunningLogger {
+    /// The crbrs address of the logger, e.g. `tcp:127.0.0.1:40123` or `serial:/dev/pts/3@38400`.
+    pub fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    /// Path of the pseudo-terminal of a logger started with [`MockLogger::spawn_pty`].
+    pub fn serial_port(&self) -> Option<&str> {
+        self.serial_port.as_deref()
+    }
+
+    /// Files sent to the logger, by name (e.g. `CPU:soil.cr1x`).
+    pub fn files(&self) -> BTreeMap<String, Vec<u8>> {
+        lock(&self.state).files.clone()
+    }
+
+    /// The program compiled and running, if any.
+    pub fn running_program(&self) -> Option<String> {
+        lock(&self.state).running.clone()
+    }
+
+    /// The logger's clock now.
+    pub fn clock(&self) -> SystemTime {
+        lock(&self.state).clock()
+    }
+
+    /// Message types of the requests the logger answered or refused, in order.
+    pub fn requests(&self) -> Vec<u8> {
+        lock(&self.state).requests.clone()
+    }
+
+    /// Connections accepted so far.
+    pub fn connections(&self) -> usize {
+        lock(&self.streams).len()
+    }
+
+    /// Closes every open connection, like a radio link dropping.
+    pub fn disconnect(&self) {
+        for stream in lock(&self.streams).iter() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Changes `table.field` while the logger runs.
+    pub fn set_value(&self, table: &str, field: &str, value: Value) {
+        lock(&self.state).set_value(table, field, value);
+    }
+}
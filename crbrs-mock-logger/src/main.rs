@@ -0,0 +1,66 @@
+// FILE: crbrs-mock-logger/src/main.rs
+
+//! `crbrs-mock-logger`: serves a simulated logger on a TCP port until interrupted, e.g. for
+//! trying `crbrs logger` commands or for test suites outside Rust.
+
+use clap::Parser;
+use crbrs_lib::pakbus::Value;
+use crbrs_mock_logger::MockLogger;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Serve a simulated Campbell logger over PakBus/TCP")]
+struct Cli {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:6785")]
+    listen: String,
+    /// PakBus address of the logger
+    #[arg(long, default_value_t = 1)]
+    pakbus_address: u16,
+    /// Security code of the logger (0 for none)
+    #[arg(long, default_value_t = 0)]
+    security_code: u16,
+    /// Set a value, e.g. Status.Battery=11.2 or Public.Site=North (repeatable)
+    #[arg(long = "value", value_name = "TABLE.FIELD=VALUE")]
+    values: Vec<String>,
+    /// Seconds the logger asks to be left alone for after compiling a program
+    #[arg(long, default_value_t = 0)]
+    hold_off: u16,
+    /// Seconds the logger's clock is ahead of (negative: behind) this machine's
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    clock_offset: f64,
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let cli = Cli::parse();
+    let mut logger = MockLogger::new()
+        .with_status()
+        .pakbus_address(cli.pakbus_address)
+        .security_code(cli.security_code)
+        .compile_hold_off(cli.hold_off)
+        .clock_offset(cli.clock_offset);
+    for setting in &cli.values {
+        let Some((name, value)) = setting.split_once('=') else {
+            eprintln!("Error: '{}' is not TABLE.FIELD=VALUE", setting);
+            std::process::exit(2);
+        };
+        let Some((table, field)) = name.split_once('.') else {
+            eprintln!("Error: '{}' is not TABLE.FIELD", name);
+            std::process::exit(2);
+        };
+        let value = value.parse().map(Value::Number).unwrap_or_else(|_| Value::Text(value.to_string()));
+        logger = logger.value(table, field, value);
+    }
+    match logger.spawn_on(&cli.listen) {
+        Ok(running) => {
+            println!("Mock logger at PakBus address {} on {}", cli.pakbus_address, running.address());
+            loop {
+                std::thread::park();
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: cannot listen on {}: {}", cli.listen, e);
+            std::process::exit(1);
+        }
+    }
+}
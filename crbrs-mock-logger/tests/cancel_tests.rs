@@ -0,0 +1,38 @@
+// FILE: crbrs-mock-logger/tests/cancel_tests.rs
+
+// Cancellation is process-wide, so these tests live in their own test binary.
+
+use crbrs_lib::cancel;
+use crbrs_lib::logger::Logger;
+use crbrs_lib::network::NetworkSettings;
+use crbrs_lib::station::Station;
+use crbrs_lib::Error;
+use crbrs_mock_logger::MockLogger;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_cancelled_send_deletes_the_partial_file() -> Result<(), Box<dyn std::error::Error>> {
+    let logger = MockLogger::new().with_status().spawn()?;
+    let network = NetworkSettings { connect_timeout_secs: 2, read_timeout_secs: 1, ..NetworkSettings::default() };
+    let mut connection = Logger::connect(&Station { address: logger.address(), ..Station::default() }, &network)?;
+
+    let watcher = {
+        let logger = logger.clone();
+        thread::spawn(move || {
+            while !logger.files().contains_key("CPU:big.cr1x") {
+                thread::sleep(Duration::from_millis(1));
+            }
+            cancel::token().cancel();
+        })
+    };
+    let result = connection.send_file("CPU:big.cr1x", &vec![b'x'; 8 << 20]);
+    watcher.join().expect("the watcher does not panic");
+    assert!(matches!(result, Err(Error::Cancelled)), "{:?}", result.err());
+    assert!(logger.files().is_empty(), "the partial file is deleted");
+
+    assert!(matches!(connection.hello(), Err(Error::Cancelled)));
+    cancel::token().reset();
+    connection.hello()?;
+    Ok(())
+}
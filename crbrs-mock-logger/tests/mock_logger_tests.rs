@@ -0,0 +1,146 @@
+// FILE: crbrs-mock-logger/tests/mock_logger_tests.rs
+
+use crbrs_lib::fleet::{self, FleetOptions, Outcome};
+use crbrs_lib::logger::Logger;
+use crbrs_lib::network::NetworkSettings;
+use crbrs_lib::pakbus::{self, Value, ValueKind};
+use crbrs_lib::station::Station;
+use crbrs_lib::status::{self, Health, Thresholds};
+use crbrs_lib::{Error, Settings};
+use crbrs_mock_logger::MockLogger;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+fn network() -> NetworkSettings {
+    NetworkSettings { connect_timeout_secs: 2, read_timeout_secs: 1, ..NetworkSettings::default() }
+}
+
+fn station(address: String) -> Station {
+    Station { address, ..Station::default() }
+}
+
+#[test]
+fn test_status_query() -> Result<(), Box<dyn std::error::Error>> {
+    let logger = MockLogger::new()
+        .with_status()
+        .value("Status", "Battery", Value::Number(11.0))
+        .without("Status", "LithiumBattery")
+        .spawn()?;
+
+    let report = status::query("north", &station(logger.address()), &network(), &Thresholds::default())?;
+    assert_eq!(report.health, Health::Critical);
+    assert_eq!(report.status.os_version.as_deref(), Some("CR1000X.Std.06.02"));
+    assert_eq!(report.status.battery, Some(11.0));
+    assert_eq!(report.status.lithium_battery, None);
+    assert_eq!(logger.requests()[0], pakbus::HELLO);
+    Ok(())
+}
+
+#[test]
+fn test_send_compile_and_clock() -> Result<(), Box<dyn std::error::Error>> {
+    let logger = MockLogger::new().with_status().compile_hold_off(3).clock_offset(-90.0).spawn()?;
+    let mut connection = Logger::connect(&station(logger.address()), &network())?;
+
+    // Several fragments, with bytes that need quoting on the wire.
+    let program: Vec<u8> = (0..2000u32).map(|i| [b'A', pakbus::SYNC, 0xBC, b'\n'][i as usize % 4]).collect();
+    connection.send_file("CPU:soil.cr1x", &program)?;
+    assert_eq!(logger.files().get("CPU:soil.cr1x"), Some(&program));
+    assert_eq!(connection.receive_file("CPU:soil.cr1x")?, program);
+    let error = connection.receive_file("CPU:other.cr1x").expect_err("there is no such file");
+    assert!(error.to_string().contains("invalid file name"), "{}", error);
+    assert_eq!(connection.control_file("CPU:soil.cr1x", pakbus::FILE_COMPILE_AND_RUN)?, 3);
+    assert_eq!(logger.running_program().as_deref(), Some("CPU:soil.cr1x"));
+    assert_eq!(
+        connection.value("Status", "ProgName", ValueKind::Text)?,
+        Some(Value::Text("CPU:soil.cr1x".to_string()))
+    );
+    let error = connection.send_file("C:soil.cr1x", b"x").expect_err("the drive is not accepted");
+    assert!(error.to_string().contains("invalid file name"), "{}", error);
+
+    let behind = SystemTime::now().duration_since(connection.clock()?)?;
+    assert!(behind > Duration::from_secs(85) && behind < Duration::from_secs(95), "{:?}", behind);
+    Ok(())
+}
+
+#[test]
+fn test_security_code_and_address() -> Result<(), Box<dyn std::error::Error>> {
+    let logger = MockLogger::new().with_status().security_code(1234).pakbus_address(7).spawn()?;
+
+    let mut station = Station { pakbus_address: 7, ..station(logger.address()) };
+    let mut connection = Logger::connect(&station, &network())?;
+    let error = connection.value("Status", "Battery", ValueKind::Number).expect_err("no security code");
+    assert!(error.to_string().contains("permission denied"), "{}", error);
+
+    station.security_code = 1234;
+    let mut connection = Logger::connect(&station, &network())?;
+    assert_eq!(connection.value("Status", "Battery", ValueKind::Number)?, Some(Value::Number(f64::from(12.8f32))));
+
+    station.pakbus_address = 1;
+    let error = Logger::connect(&station, &network()).err().expect("nothing at PakBus address 1");
+    assert!(error.to_string().contains("no response from PakBus address 1"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn test_connection_shared_and_redialed() -> Result<(), Box<dyn std::error::Error>> {
+    let logger = MockLogger::new().with_status().spawn()?;
+    let network = NetworkSettings { backoff_ms: 10, ..network() };
+
+    let mut first = Logger::connect(&station(logger.address()), &network)?;
+    let mut second = Logger::connect(&station(logger.address()), &network)?;
+    assert!(first.clock().is_ok());
+    assert!(second.value("Status", "OSVersion", ValueKind::Text)?.is_some());
+    assert_eq!(logger.connections(), 1, "both loggers use one connection");
+
+    logger.disconnect();
+    assert!(first.value("Status", "PanelTemp", ValueKind::Number)?.is_some(), "the dropped link is re-dialed");
+    assert_eq!(logger.connections(), 2);
+    let requests = logger.requests();
+    assert_eq!(requests[requests.len() - 2..], [pakbus::HELLO, pakbus::GET_VALUES], "a re-dialed link starts with a Hello");
+
+    drop((first, second));
+    let _third = Logger::connect(&station(logger.address()), &network)?;
+    assert_eq!(logger.connections(), 3, "the connection closes with its last logger");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fleet_cancels_a_timed_out_station() -> Result<(), Box<dyn std::error::Error>> {
+    let logger = MockLogger::new().with_status().spawn()?;
+    let mut settings = Settings { network: network(), ..Settings::default() };
+    settings.stations.insert("north".to_string(), station(logger.address()));
+
+    let (sender, stopped) = mpsc::channel();
+    let options = FleetOptions { station_timeout: Duration::from_millis(100), ..FleetOptions::default() };
+    let results = fleet::run(&settings, options, move |_, station, network| {
+        let result = Logger::connect(station, network)?.send_file("CPU:big.cr1x", &vec![b'x'; 8 << 20]);
+        let _ = sender.send(matches!(result, Err(Error::Cancelled)));
+        result
+    })?;
+    assert_eq!(results[0].outcome, Outcome::TimedOut);
+    assert!(stopped.recv_timeout(Duration::from_secs(10))?, "the send is cancelled");
+    assert!(logger.files().is_empty(), "the partial file is deleted");
+    Ok(())
+}
+
+#[test]
+fn test_serial_baud_detection() -> Result<(), Box<dyn std::error::Error>> {
+    let logger = MockLogger::new().with_status().spawn_pty(38_400)?;
+    let port = logger.serial_port().expect("a pseudo-terminal").to_string();
+
+    let error = Logger::connect(&station(format!("serial:{}@115200", port)), &network()).err().expect("wrong baud rate");
+    assert!(error.to_string().contains("no response from PakBus address 1"), "{}", error);
+
+    let mut connection = Logger::connect(&station(format!("serial:{}@auto", port)), &network())?;
+    assert_eq!(connection.endpoint().to_string(), format!("serial:{}@38400", port));
+    assert!(connection.value("Status", "OSVersion", ValueKind::Text)?.is_some());
+    drop(connection);
+
+    let logger = MockLogger::new().with_status().spawn_pty(1_200)?;
+    let error = Logger::connect(&station(format!("serial:{}@auto", logger.serial_port().unwrap_or_default())), &network())
+        .err()
+        .expect("no probed rate matches");
+    assert!(error.to_string().contains("at 115200, 38400, 9600 baud"), "{}", error);
+    Ok(())
+}
@@ -0,0 +1,14 @@
+// FILE: crbrs-syntax/fuzz/fuzz_targets/format.rs
+
+//! The formatter must not panic, and formatting its output again must not change it.
+
+#![no_main]
+
+use crbrs_syntax::format::{format, FormatOptions};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let options = FormatOptions::default();
+    let once = format(source, &options);
+    assert_eq!(format(&once, &options), once);
+});
@@ -0,0 +1,14 @@
+// FILE: crbrs-syntax/fuzz/fuzz_targets/front_end.rs
+
+//! Tokenizer, parser, symbol index and linter must not panic on any input.
+
+#![no_main]
+
+use crbrs_syntax::{index, lexer, lint, parse};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    lexer::tokenize(source);
+    index::index(&parse(source));
+    lint::lint_source(source);
+});
@@ -0,0 +1,371 @@
+// FILE: crbrs-syntax/src/ast.rs
+
+//! Syntax tree produced by [`crate::parser::parse`].
+//!
+//! The tree is deliberately shallow: CRBasic is line oriented, so statements map to source
+//! lines and blocks (`BeginProg`, `Scan`, `DataTable`, `Sub`, `If`, ...) simply own the
+//! statements between their opening and closing lines.
+
+use crate::lexer::Span;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Program {
+    pub body: Vec<Stmt>,
+    /// Every comment in the file, in source order (the statements themselves carry none).
+    pub comments: Vec<Comment>,
+    pub errors: Vec<ParseError>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Comment {
+    /// Comment text including the leading `'` or `REM`.
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+/// An identifier together with where it was written.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Name {
+    pub text: String,
+    pub span: Span,
+}
+
+impl Name {
+    pub fn is(&self, other: &str) -> bool {
+        self.text.eq_ignore_ascii_case(other)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    /// Span of the statement's first line (from its first to its last token).
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum StmtKind {
+    /// `Public ...` / `Dim ...`
+    Declare(Declaration),
+    /// `Const NAME = value`
+    Const(Vec<ConstDecl>),
+    /// `Alias Original = NewName`
+    Alias { target: Expr, name: Name },
+    /// `Units Name = text`; `units` is the raw text after `=`.
+    Units { names: Vec<Name>, units: String },
+    DataTable(DataTable),
+    Routine(Routine),
+    BeginProg(Block),
+    Scan(Scan),
+    /// `SlowSequence` marker; the following `Scan` runs in its own task.
+    SlowSequence,
+    /// `EndSequence` closing a slow sequence.
+    EndSequence,
+    /// Begin/end pairs without special semantics (`SubScan`/`NextSubScan`, `DialSequence`, ...).
+    Block(GenericBlock),
+    If(IfStmt),
+    For(ForLoop),
+    Do(DoLoop),
+    While(WhileLoop),
+    Select(SelectCase),
+    Assign { target: Expr, value: Expr },
+    /// Instruction or subroutine call: `Battery(BattV)`, `CallTable Test`, `Call MySub(1)`.
+    Call(Call),
+    /// `ExitScan`, `ExitFor`, `Exit Do`, `ContinueScan`, `Return`, ...; normalized lower-case keyword.
+    Exit(String),
+    /// Preprocessor line such as `#If LoggerType = CR1000X Then`; raw text.
+    Directive(String),
+    /// A line the parser could not make sense of (an error has been recorded).
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum DeclScope {
+    Public,
+    Dim,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Declaration {
+    pub scope: DeclScope,
+    pub vars: Vec<VarDecl>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VarDecl {
+    pub name: Name,
+    /// Array dimensions, e.g. `Temp(4,2)` has two.
+    pub dims: Vec<Expr>,
+    pub ty: Option<TypeSpec>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeSpec {
+    /// Type name as written (`Float`, `Long`, `Boolean`, `String`, ...).
+    pub name: Name,
+    /// Size clause for strings: `As String * 24`.
+    pub size: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConstDecl {
+    pub name: Name,
+    pub ty: Option<TypeSpec>,
+    pub value: Expr,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataTable {
+    pub name: Name,
+    /// Remaining `DataTable(...)` arguments (trigger, size).
+    pub args: Vec<Expr>,
+    pub body: Vec<Stmt>,
+    pub end: Option<Span>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum RoutineKind {
+    Sub,
+    Function,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Routine {
+    pub kind: RoutineKind,
+    pub name: Name,
+    pub params: Vec<VarDecl>,
+    pub return_type: Option<TypeSpec>,
+    pub body: Vec<Stmt>,
+    pub end: Option<Span>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Block {
+    pub body: Vec<Stmt>,
+    pub end: Option<Span>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Scan {
+    pub args: Vec<Expr>,
+    pub body: Vec<Stmt>,
+    pub end: Option<Span>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GenericBlock {
+    /// Opening keyword as written.
+    pub keyword: Name,
+    pub args: Vec<Expr>,
+    pub body: Vec<Stmt>,
+    pub end: Option<Span>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IfStmt {
+    pub cond: Expr,
+    pub then_body: Vec<Stmt>,
+    pub else_ifs: Vec<(Expr, Vec<Stmt>)>,
+    pub else_body: Option<Vec<Stmt>>,
+    /// True for `If x Then y = 1` written on one line.
+    pub single_line: bool,
+    pub end: Option<Span>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForLoop {
+    pub var: Expr,
+    pub from: Expr,
+    pub to: Expr,
+    pub step: Option<Expr>,
+    pub body: Vec<Stmt>,
+    pub end: Option<Span>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum LoopCondKind {
+    While,
+    Until,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DoLoop {
+    /// `Do While x` / `Do Until x`
+    pub pre: Option<(LoopCondKind, Expr)>,
+    /// `Loop While x` / `Loop Until x`
+    pub post: Option<(LoopCondKind, Expr)>,
+    pub body: Vec<Stmt>,
+    pub end: Option<Span>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WhileLoop {
+    pub cond: Expr,
+    pub body: Vec<Stmt>,
+    pub end: Option<Span>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectCase {
+    pub subject: Expr,
+    pub cases: Vec<Case>,
+    pub end: Option<Span>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Case {
+    /// Empty for `Case Else`.
+    pub values: Vec<Expr>,
+    pub is_else: bool,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Call {
+    pub name: Name,
+    pub args: Vec<Expr>,
+    /// Whether the arguments were written in parentheses.
+    pub parenthesized: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum ExprKind {
+    /// Numeric literal as written.
+    Number(String),
+    /// String literal contents without the surrounding quotes (`""` unescaped).
+    Str(String),
+    /// Variable, array element, dotted table field or function call: `x`, `T(1,2)`, `Status.Battery`, `Sin(x)`.
+    Ref { name: String, args: Option<Vec<Expr>> },
+    Unary { op: String, expr: Box<Expr> },
+    Binary { op: String, lhs: Box<Expr>, rhs: Box<Expr> },
+    Paren(Box<Expr>),
+    /// Placeholder for an argument the parser could not read.
+    Error,
+}
+
+impl Expr {
+    /// Returns the referenced name for a bare or indexed reference.
+    pub fn ref_name(&self) -> Option<&str> {
+        match &self.kind {
+            ExprKind::Ref { name, .. } => Some(name),
+            ExprKind::Paren(inner) => inner.ref_name(),
+            _ => None,
+        }
+    }
+
+    /// Visits this expression and all sub-expressions in pre-order.
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        f(self);
+        match &self.kind {
+            ExprKind::Ref { args: Some(args), .. } => args.iter().for_each(|a| a.walk(f)),
+            ExprKind::Unary { expr, .. } | ExprKind::Paren(expr) => expr.walk(f),
+            ExprKind::Binary { lhs, rhs, .. } => {
+                lhs.walk(f);
+                rhs.walk(f);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Stmt {
+    /// Child statement lists owned by this statement (block bodies, branches, cases).
+    pub fn children(&self) -> Vec<&[Stmt]> {
+        match &self.kind {
+            StmtKind::DataTable(t) => vec![&t.body],
+            StmtKind::Routine(r) => vec![&r.body],
+            StmtKind::BeginProg(b) => vec![&b.body],
+            StmtKind::Scan(s) => vec![&s.body],
+            StmtKind::Block(b) => vec![&b.body],
+            StmtKind::If(i) => {
+                let mut out: Vec<&[Stmt]> = vec![&i.then_body];
+                out.extend(i.else_ifs.iter().map(|(_, body)| body.as_slice()));
+                if let Some(else_body) = &i.else_body {
+                    out.push(else_body);
+                }
+                out
+            }
+            StmtKind::For(f) => vec![&f.body],
+            StmtKind::Do(d) => vec![&d.body],
+            StmtKind::While(w) => vec![&w.body],
+            StmtKind::Select(s) => s.cases.iter().map(|c| c.body.as_slice()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Expressions written directly on this statement's own lines (not those of nested
+    /// statements), e.g. an assignment's target and value or an `If` condition.
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match &self.kind {
+            StmtKind::Declare(d) => d.vars.iter().flat_map(|v| v.dims.iter()).collect(),
+            StmtKind::Const(consts) => consts.iter().map(|c| &c.value).collect(),
+            StmtKind::Alias { target, .. } => vec![target],
+            StmtKind::DataTable(t) => t.args.iter().collect(),
+            StmtKind::Scan(s) => s.args.iter().collect(),
+            StmtKind::Block(b) => b.args.iter().collect(),
+            StmtKind::If(i) => {
+                let mut out = vec![&i.cond];
+                out.extend(i.else_ifs.iter().map(|(cond, _)| cond));
+                out
+            }
+            StmtKind::For(f) => {
+                let mut out = vec![&f.var, &f.from, &f.to];
+                out.extend(&f.step);
+                out
+            }
+            StmtKind::Do(d) => d.pre.iter().chain(&d.post).map(|(_, cond)| cond).collect(),
+            StmtKind::While(w) => vec![&w.cond],
+            StmtKind::Select(s) => {
+                let mut out = vec![&s.subject];
+                out.extend(s.cases.iter().flat_map(|c| c.values.iter()));
+                out
+            }
+            StmtKind::Assign { target, value } => vec![target, value],
+            StmtKind::Call(c) => c.args.iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Line of the statement's last source line: the closer for blocks, the first line otherwise.
+    /// `None` for a block whose closer is missing.
+    pub fn end_line(&self) -> Option<u32> {
+        let end = match &self.kind {
+            StmtKind::DataTable(t) => t.end,
+            StmtKind::Routine(r) => r.end,
+            StmtKind::BeginProg(b) => b.end,
+            StmtKind::Scan(s) => s.end,
+            StmtKind::Block(b) => b.end,
+            StmtKind::If(i) if !i.single_line => i.end,
+            StmtKind::For(f) => f.end,
+            StmtKind::Do(d) => d.end,
+            StmtKind::While(w) => w.end,
+            StmtKind::Select(s) => s.end,
+            _ => return Some(self.span.line),
+        };
+        end.map(|s| s.line)
+    }
+}
+
+/// Visits every statement in `body` (recursively, pre-order).
+pub fn walk_stmts<'a>(body: &'a [Stmt], f: &mut impl FnMut(&'a Stmt)) {
+    for stmt in body {
+        f(stmt);
+        for child in stmt.children() {
+            walk_stmts(child, f);
+        }
+    }
+}
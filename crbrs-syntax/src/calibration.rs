@@ -0,0 +1,123 @@
+// FILE: crbrs-syntax/src/calibration.rs
+
+//! Calibration constants for `crbrs build --with-calibration`.
+//!
+//! Station-specific coefficients live in a calibration file rather than in the program, and
+//! [`inject`] writes them into a copy of the program as `Const` declarations. A constant the
+//! program already declares (e.g. a placeholder used during development) gets the calibrated
+//! value; the others are declared after the program's leading comments, under a marker line
+//! that also identifies the copy as generated (see [`is_calibrated`]).
+
+use crate::ast::*;
+use crate::parser::parse;
+use crate::refactor::{ensure_parses, global_names, newline_of};
+
+/// Start of the line marking a calibrated copy.
+const MARKER: &str = "' Calibrated by crbrs with ";
+
+/// A calibration constant with its value as a CRBasic literal, e.g. `1.034`, `True` or
+/// `"2026-03-01"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constant {
+    pub name: String,
+    pub value: String,
+}
+
+/// Whether `source` is a calibrated copy made by [`inject`].
+pub fn is_calibrated(source: &str) -> bool {
+    source.lines().any(|line| line.starts_with(MARKER))
+}
+
+/// Whether `name` can be declared as a constant.
+pub fn is_valid_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Byte offset of the first line that is neither blank nor a whole-line comment.
+fn leading_comments_end(source: &str) -> usize {
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if !(trimmed.is_empty() || trimmed.starts_with('\'')) {
+            break;
+        }
+        offset += line.len();
+    }
+    offset
+}
+
+/// `source` with `constants` declared; `origin` names the calibration file in the marker line.
+pub fn inject(source: &str, constants: &[Constant], origin: &str) -> Result<String, String> {
+    let program = parse(source);
+    ensure_parses(&program)?;
+    if is_calibrated(source) {
+        return Err("The program is already a calibrated copy".to_string());
+    }
+    let declared = global_names(&program);
+    let newline = newline_of(source);
+
+    // (start, end, replacement), applied back to front so earlier offsets stay valid.
+    let mut edits = Vec::new();
+    let mut added = Vec::new();
+    for constant in constants {
+        if !is_valid_name(&constant.name) {
+            return Err(format!("'{}' is not a valid constant name", constant.name));
+        }
+        let existing = program.body.iter().find_map(|stmt| match &stmt.kind {
+            StmtKind::Const(consts) => consts.iter().find(|c| c.name.is(&constant.name)),
+            _ => None,
+        });
+        match existing {
+            Some(c) => edits.push((c.value.span.start, c.value.span.end, constant.value.clone())),
+            None if declared.contains(&constant.name.to_ascii_lowercase()) => {
+                return Err(format!("'{}' is declared in the program, but not as a Const", constant.name));
+            }
+            None => added.push(format!("Const {} = {}{}", constant.name, constant.value, newline)),
+        }
+    }
+
+    let at = leading_comments_end(source);
+    let mut block = format!("{}{}; edit the calibration file instead of this copy{}", MARKER, origin, newline);
+    block.extend(added);
+    // Keep the constants apart from the program's first declarations.
+    if source[at..].starts_with(|c: char| c != '\r' && c != '\n') {
+        block.push_str(newline);
+    }
+    edits.push((at, at, block));
+    edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+    let mut out = source.to_string();
+    for (start, end, text) in edits {
+        out.replace_range(start..end, &text);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant(name: &str, value: &str) -> Constant {
+        Constant { name: name.to_string(), value: value.to_string() }
+    }
+
+    #[test]
+    fn replaces_and_declares_constants() {
+        let src = "'crbrs: target=CR1000X\n' Soil station\nConst SoilOffset = 0 ' placeholder\nPublic VWC\n\nBeginProg\n  VWC = VWC + SoilOffset * SoilGain\nEndProg\n";
+        let out = inject(src, &[constant("soiloffset", "0.012"), constant("SoilGain", "1.034")], "station12.toml").unwrap();
+        assert_eq!(
+            out,
+            "'crbrs: target=CR1000X\n' Soil station\n' Calibrated by crbrs with station12.toml; edit the calibration file instead of this copy\nConst SoilGain = 1.034\n\nConst SoilOffset = 0.012 ' placeholder\nPublic VWC\n\nBeginProg\n  VWC = VWC + SoilOffset * SoilGain\nEndProg\n"
+        );
+        assert!(is_calibrated(&out));
+        assert!(!is_calibrated(src));
+        assert!(parse(&out).errors.is_empty());
+        assert!(inject(&out, &[], "station12.toml").is_err());
+    }
+
+    #[test]
+    fn rejects_names_declared_otherwise() {
+        let src = "Public Gain\nBeginProg\nEndProg\n";
+        assert!(inject(src, &[constant("Gain", "2")], "cal.toml").unwrap_err().contains("not as a Const"));
+        assert!(inject(src, &[constant("2x", "2")], "cal.toml").is_err());
+    }
+}
@@ -0,0 +1,124 @@
+// FILE: crbrs-syntax/src/case.rs
+
+//! Keyword-case canonicalization: `cardout` → `CardOut`, `endif` → `EndIf`.
+//!
+//! Instruction names get the spelling of the [instruction database](crate::instructions),
+//! keywords and `#If` directives the vendor editor's. It works on tokens, so strings, comments
+//! and broken programs are fine, and identifiers are never touched: names the program declares
+//! (even one spelled like an instruction) and fields of dotted references (`Status.Battery`)
+//! are left as written.
+
+use crate::lexer::{tokenize, Span, TokenKind};
+use crate::parser::parse;
+use crate::refactor::LineEdit;
+use std::collections::HashSet;
+
+/// Canonical spellings of keywords, types and unit names that are not instructions.
+const KEYWORDS: &[&str] = &[
+    "If", "Then", "Else", "ElseIf", "EndIf", "For", "To", "Step", "Next", "Do", "Loop", "While", "Until", "Wend",
+    "Select", "Case", "Is", "EndSelect", "Sub", "EndSub", "Function", "EndFunction", "Return", "ExitFor", "ExitDo",
+    "ExitSub", "ExitFunction", "ExitWhile", "Public", "Dim", "Const", "As", "Call", "Include", "ConstTable",
+    "EndConstTable", "And", "Or", "Not", "Xor", "Mod", "INTDV", "IMP", "EQV", "True", "False", "Long", "Float",
+    "Double", "String", "Boolean", "UINT2", "FP2", "IEEE4", "IEEE8", "Sec", "mSec", "uSec", "Min", "Hr",
+];
+
+/// Preprocessor directives.
+const DIRECTIVES: &[&str] = &["#If", "#ElseIf", "#Else", "#EndIf"];
+
+/// A word not in its canonical case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseFix {
+    pub span: Span,
+    pub canonical: &'static str,
+}
+
+/// The canonical spelling of the keyword or instruction `word`, if it is one.
+pub fn canonical(word: &str) -> Option<&'static str> {
+    crate::instructions::lookup(word)
+        .map(|instruction| instruction.name)
+        .or_else(|| KEYWORDS.iter().copied().find(|k| k.eq_ignore_ascii_case(word)))
+}
+
+/// Every keyword and instruction name in `source` not written in its canonical case.
+pub fn case_fixes(source: &str) -> Vec<CaseFix> {
+    let mut declared = HashSet::new();
+    crate::ast::walk_stmts(&parse(source).body, &mut |stmt| {
+        declared.extend(crate::lint::declared_names(std::slice::from_ref(stmt)).iter().map(|n| n.text.to_ascii_lowercase()));
+        if let crate::ast::StmtKind::Routine(routine) = &stmt.kind {
+            declared.extend(routine.params.iter().map(|p| p.name.text.to_ascii_lowercase()));
+        }
+    });
+
+    let tokens = tokenize(source);
+    let mut fixes = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let canonical = match token.kind {
+            TokenKind::Ident if i > 0 && tokens[i - 1].kind == TokenKind::Dot => None,
+            TokenKind::Ident if declared.contains(&token.text.to_ascii_lowercase()) => None,
+            TokenKind::Ident => canonical(&token.text),
+            TokenKind::Directive => DIRECTIVES.iter().copied().find(|d| d.eq_ignore_ascii_case(&token.text)),
+            _ => None,
+        };
+        if let Some(canonical) = canonical.filter(|c| *c != token.text) {
+            fixes.push(CaseFix { span: token.span, canonical });
+        }
+    }
+    fixes
+}
+
+/// `source` with [`case_fixes`] applied.
+pub fn fix_case(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut pos = 0;
+    for fix in case_fixes(source) {
+        out.push_str(&source[pos..fix.span.start]);
+        out.push_str(fix.canonical);
+        pos = fix.span.end;
+    }
+    out.push_str(&source[pos..]);
+    out
+}
+
+/// [`case_fixes`] as whole-line edits, for editors.
+pub fn line_edits(source: &str) -> Vec<LineEdit> {
+    let mut line_starts = vec![0];
+    line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+    let mut edits: Vec<LineEdit> = Vec::new();
+    let fixes = case_fixes(source);
+    let mut fixes = fixes.iter().peekable();
+    while let Some(first) = fixes.next() {
+        let line = first.span.line as usize;
+        let start = line_starts[line];
+        let end = line_starts.get(line + 1).copied().unwrap_or(source.len());
+        let mut text = String::new();
+        let mut pos = start;
+        for fix in std::iter::once(first).chain(std::iter::from_fn(|| fixes.next_if(|f| f.span.line as usize == line))) {
+            text.push_str(&source[pos..fix.span.start]);
+            text.push_str(fix.canonical);
+            pos = fix.span.end;
+        }
+        text.push_str(&source[pos..end]);
+        edits.push(LineEdit { start_line: line as u32, end_line: line as u32 + 1, new_text: text });
+    }
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixes_keywords_and_instructions_but_not_identifiers() {
+        let src = "public battv\nPublic Sample\n#if DEBUG\nbeginprog\n  scan(5,sec,0,0)\n    battery(battv)\n    \
+                   if battv < 11 then x = status.battery : sample = 1 'cardout stays\n    callTable Hourly\n  nextscan\nendprog\n#endif\n";
+        assert_eq!(
+            fix_case(src),
+            "Public battv\nPublic Sample\n#If DEBUG\nBeginProg\n  Scan(5,Sec,0,0)\n    Battery(battv)\n    \
+             If battv < 11 Then x = status.battery : sample = 1 'cardout stays\n    CallTable Hourly\n  NextScan\nEndProg\n#EndIf\n"
+        );
+        assert_eq!(fix_case(&fix_case(src)), fix_case(src));
+        let edits = line_edits("x = 1\nbeginprog\nendprog");
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[1], LineEdit { start_line: 2, end_line: 3, new_text: "EndProg".to_string() });
+    }
+}
@@ -0,0 +1,102 @@
+// FILE: crbrs-syntax/src/directive.rs
+
+//! In-source toolchain directives: a magic comment at the top of a program that says how to
+//! build it, so a single file is self-describing:
+//!
+//! ```text
+//! 'crbrs: compiler=cr300-std-10.1 target=CR310
+//! ```
+//!
+//! Only comments before the first statement count. `compiler` names the compiler ID to use
+//! and `target` the logger model; both override the extension association. Problems (unknown
+//! keys, malformed pairs) are reported by the `toolchain-directive` lint rule.
+
+use crate::ast::*;
+use crate::lexer::Span;
+use crate::parser::parse;
+use serde::Serialize;
+
+const PREFIX: &str = "crbrs:";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Directives {
+    /// Compiler ID to build with.
+    pub compiler: Option<String>,
+    /// Logger model the program is written for, e.g. `CR310`.
+    pub target: Option<String>,
+    /// Span of the first directive comment, if any.
+    pub span: Option<Span>,
+    /// Problems found while reading the directives, for the `toolchain-directive` lint rule.
+    pub problems: Vec<(Span, String)>,
+}
+
+impl Directives {
+    pub fn is_empty(&self) -> bool {
+        self.compiler.is_none() && self.target.is_none()
+    }
+}
+
+/// Body of a `'crbrs: ...` comment, if `comment` is one.
+fn directive_body(comment: &str) -> Option<&str> {
+    let body = comment.strip_prefix('\'').unwrap_or(comment).trim_start();
+    let prefix = body.get(..PREFIX.len())?;
+    prefix.eq_ignore_ascii_case(PREFIX).then(|| &body[PREFIX.len()..])
+}
+
+/// Reads the directives from the comments before the first statement of `program`.
+pub fn directives(program: &Program) -> Directives {
+    let first_statement = program.body.first().map_or(usize::MAX, |stmt| stmt.span.start);
+    let mut out = Directives::default();
+    for comment in program.comments.iter().take_while(|c| c.span.start < first_statement) {
+        let Some(body) = directive_body(&comment.text) else { continue };
+        out.span.get_or_insert(comment.span);
+        for pair in body.split_whitespace() {
+            let Some((key, value)) = pair.split_once('=').filter(|(k, v)| !k.is_empty() && !v.is_empty()) else {
+                out.problems.push((comment.span, format!("expected key=value, found '{}'", pair)));
+                continue;
+            };
+            let slot = match key.to_ascii_lowercase().as_str() {
+                "compiler" => &mut out.compiler,
+                "target" => &mut out.target,
+                _ => {
+                    out.problems.push((comment.span, format!("unknown directive '{}' (expected compiler or target)", key)));
+                    continue;
+                }
+            };
+            if let Some(previous) = slot.replace(value.to_string()) {
+                out.problems.push((comment.span, format!("'{}' given twice ('{}' and '{}')", key, previous, value)));
+            }
+        }
+    }
+    out
+}
+
+/// Parses `source` and reads its directives.
+pub fn directives_source(source: &str) -> Directives {
+    directives(&parse(source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_leading_directives_only() {
+        let d = directives_source("' Station program\n'crbrs: compiler=cr300-std-10.1 target=CR310\nPublic x\n'crbrs: compiler=late\n");
+        assert_eq!(d.compiler.as_deref(), Some("cr300-std-10.1"));
+        assert_eq!(d.target.as_deref(), Some("CR310"));
+        assert!(d.problems.is_empty());
+        assert_eq!(d.span.map(|s| s.line), Some(1));
+    }
+
+    #[test]
+    fn reports_malformed_directives() {
+        let d = directives_source("'CRBRS: compiler=a color=red flag\n'crbrs: compiler=b\n");
+        assert_eq!(d.compiler.as_deref(), Some("b"));
+        let messages: Vec<&str> = d.problems.iter().map(|(_, m)| m.as_str()).collect();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[0].contains("unknown directive 'color'"));
+        assert!(messages[1].contains("expected key=value"));
+        assert!(messages[2].contains("given twice"));
+    }
+}
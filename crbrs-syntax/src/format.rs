@@ -0,0 +1,469 @@
+// FILE: crbrs-syntax/src/format.rs
+
+//! Source formatter.
+//!
+//! The formatter only re-indents lines according to block structure, trims trailing
+//! whitespace and limits runs of blank lines. It never reorders tokens or touches the
+//! contents of strings and comments, so formatting is always safe on broken input.
+//!
+//! Habits of the vendor editor that people want to keep are options, off by default: inline
+//! comments aligned to one column, hand-aligned `DataTable` blocks left as written, and the
+//! blank-line policies in [`FormatOptions`].
+//!
+//! Lines continued with ` _` are indented one level deeper than the statement they belong
+//! to. Each `#ElseIf`/`#Else` branch starts from the block structure at its `#If`, and after
+//! `#EndIf` the structure continues from the end of the first branch, as the parser reads it.
+
+use crate::lexer::{tokenize, Token, TokenKind};
+use crate::parser::{is_continuation, line_head};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStyle {
+    Spaces,
+    Tabs,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatOptions {
+    pub indent_style: IndentStyle,
+    /// Spaces per level when `indent_style` is `Spaces`.
+    pub indent_width: usize,
+    pub trim_trailing_whitespace: bool,
+    /// Longest allowed run of consecutive blank lines.
+    pub max_blank_lines: usize,
+    /// Align the inline comments of consecutive lines to one column, one space after the
+    /// longest code among them.
+    pub align_comments: bool,
+    /// Keep the indentation and comment columns of the lines inside `DataTable` blocks as
+    /// written, for tables laid out by hand.
+    pub preserve_table_alignment: bool,
+    /// Drop blank lines at the start and end of block bodies (after `Scan`, before `NextScan`).
+    pub trim_blank_lines_in_blocks: bool,
+    /// Put a blank line before each top-level `DataTable`, `Sub`, `Function` and `BeginProg`
+    /// (above the comments directly over it), even where `max_blank_lines` is 0.
+    pub blank_line_before_sections: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent_style: IndentStyle::Spaces,
+            indent_width: 2,
+            trim_trailing_whitespace: true,
+            max_blank_lines: 1,
+            align_comments: false,
+            preserve_table_alignment: false,
+            trim_blank_lines_in_blocks: false,
+            blank_line_before_sections: false,
+        }
+    }
+}
+
+/// Block openers and the closers that end them.
+const OPENERS: &[(&str, &[&str])] = &[
+    ("beginprog", &["endprog"]),
+    ("scan", &["nextscan"]),
+    ("subscan", &["nextsubscan"]),
+    ("datatable", &["endtable"]),
+    ("sub", &["endsub"]),
+    ("function", &["endfunction"]),
+    ("for", &["next"]),
+    ("do", &["loop"]),
+    ("while", &["wend"]),
+    ("consttable", &["endconsttable"]),
+    ("dialsequence", &["enddialsequence"]),
+    ("shutdownbegin", &["shutdownend"]),
+    ("webpagebegin", &["webpageend"]),
+    ("displaymenu", &["endmenu"]),
+    ("submenu", &["endsubmenu"]),
+    ("modemhangup", &["endmodemhangup"]),
+];
+
+/// Statements that start a section of a program, for `blank_line_before_sections`.
+const SECTIONS: [&str; 4] = ["datatable", "sub", "function", "beginprog"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frame {
+    Block,
+    If,
+    Select { in_case: bool },
+}
+
+/// A formatted line, before the passes that look at its neighbours.
+#[derive(Debug)]
+enum Line<'a> {
+    /// A blank line; `kept` if it ends a continued statement and so must not be dropped.
+    Blank { kept: bool },
+    Text {
+        indent: String,
+        text: &'a str,
+        /// Where an inline comment starts in `text`.
+        comment: Option<usize>,
+        /// Only a comment.
+        comment_only: bool,
+        /// Opens a block (or a branch of one): the lines after it are indented deeper.
+        opens: bool,
+        /// Closes a block (or a branch of one): it is indented less than the lines before it.
+        closes: bool,
+        /// A top-level statement in [`SECTIONS`].
+        section: bool,
+        /// Inside a `DataTable` kept as written.
+        preserved: bool,
+    },
+}
+
+/// Formats `source`, returning the new text.
+pub fn format(source: &str, options: &FormatOptions) -> String {
+    let newline = if source.contains("\r\n") { "\r\n" } else { "\n" };
+    let physical = physical_lines(source);
+
+    // Group code tokens (no comments/newlines) by physical line, and note where comments start.
+    let mut code_by_line: Vec<Vec<Token>> = vec![Vec::new(); physical.len().max(1)];
+    let mut comment_by_line: Vec<Option<usize>> = vec![None; physical.len().max(1)];
+    for token in tokenize(source) {
+        let line = token.span.line as usize;
+        match token.kind {
+            TokenKind::Newline => {}
+            TokenKind::Comment => {
+                if let Some(slot) = comment_by_line.get_mut(line) {
+                    *slot = Some(token.span.start);
+                }
+            }
+            _ => {
+                if let Some(slot) = code_by_line.get_mut(line) {
+                    slot.push(token);
+                }
+            }
+        }
+    }
+
+    let unit = match options.indent_style {
+        IndentStyle::Spaces => " ".repeat(options.indent_width),
+        IndentStyle::Tabs => "\t".to_string(),
+    };
+
+    let mut lines: Vec<Line> = Vec::with_capacity(physical.len());
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut branches: Vec<Branches> = Vec::new();
+    let mut continuation_level = 0usize;
+    let mut in_table = false;
+    for (i, raw) in physical.iter().enumerate() {
+        let continued = i > 0 && code_by_line[i - 1].last().is_some_and(is_continuation);
+        let trimmed = raw.trim_start_matches(BLANKS);
+        if trimmed.is_empty() {
+            lines.push(Line::Blank { kept: continued });
+            continue;
+        }
+
+        let (level, opens, closes, head) = if continued {
+            (continuation_level, false, false, String::new())
+        } else {
+            let code = logical_line(&code_by_line, i);
+            let head = line_head(&code);
+            let before = depth(&stack);
+            let (level, next_stack) =
+                directive_indent(&head, &stack, &mut branches).unwrap_or_else(|| indent_for(&head, &code, &stack));
+            stack = next_stack;
+            continuation_level = level + 1;
+            (level, depth(&stack) > level, level < before, head)
+        };
+        if head == "endtable" {
+            in_table = false;
+        }
+        let preserved = options.preserve_table_alignment && in_table;
+        if head == "datatable" {
+            in_table = true;
+        }
+
+        let text = if options.trim_trailing_whitespace { trimmed.trim_end_matches(BLANKS) } else { trimmed };
+        let offset = text.as_ptr() as usize - source.as_ptr() as usize;
+        let comment_only = code_by_line[i].is_empty() && !continued;
+        let comment = comment_by_line[i].filter(|_| !comment_only).map(|start| start - offset);
+        let indent = if preserved { raw[..raw.len() - trimmed.len()].to_string() } else { unit.repeat(level) };
+        let section = level == 0 && SECTIONS.contains(&head.as_str());
+        lines.push(Line::Text { indent, text, comment, comment_only, opens, closes, section, preserved });
+    }
+
+    let lines = blank_lines(lines, options);
+    let columns = if options.align_comments { comment_columns(&lines) } else { vec![None; lines.len()] };
+    let mut out = String::with_capacity(source.len());
+    for (line, column) in lines.iter().zip(columns) {
+        if let Line::Text { indent, text, comment, .. } = line {
+            out.push_str(indent);
+            match (comment, column) {
+                (Some(at), Some(column)) => {
+                    let code = text[..*at].trim_end_matches(BLANKS);
+                    out.push_str(code);
+                    let width = indent.chars().count() + code.chars().count();
+                    out.push_str(&" ".repeat(column.saturating_sub(width).max(1)));
+                    out.push_str(&text[*at..]);
+                }
+                _ => out.push_str(text),
+            }
+        }
+        out.push_str(newline);
+    }
+
+    if !source.ends_with(['\n', '\r']) && out.ends_with(newline) {
+        out.truncate(out.len() - newline.len());
+    }
+    out
+}
+
+/// Applies the blank-line options: at most `max_blank_lines` in a row, none at block edges
+/// with `trim_blank_lines_in_blocks`, and one before sections with `blank_line_before_sections`.
+fn blank_lines<'a>(lines: Vec<Line<'a>>, options: &FormatOptions) -> Vec<Line<'a>> {
+    let mut next_closes = vec![false; lines.len()];
+    let mut closes = false;
+    for (i, line) in lines.iter().enumerate().rev() {
+        if let Line::Text { closes: c, .. } = line {
+            closes = *c;
+        }
+        next_closes[i] = closes;
+    }
+
+    let mut out: Vec<Line> = Vec::with_capacity(lines.len());
+    let (mut run, mut after_opener) = (0usize, false);
+    for (line, next_closes) in lines.into_iter().zip(next_closes) {
+        match line {
+            Line::Blank { kept } => {
+                run += 1;
+                let at_edge = options.trim_blank_lines_in_blocks && (after_opener || next_closes);
+                if kept || (run <= options.max_blank_lines && !at_edge) {
+                    out.push(line);
+                }
+            }
+            Line::Text { opens, section, .. } => {
+                run = 0;
+                after_opener = opens;
+                if options.blank_line_before_sections && section {
+                    // Above the comments that go with it.
+                    let first = out.iter().rposition(|l| !matches!(l, Line::Text { comment_only: true, .. })).map_or(0, |p| p + 1);
+                    if first > 0 && matches!(out[first - 1], Line::Text { .. }) {
+                        out.insert(first, Line::Blank { kept: false });
+                    }
+                }
+                out.push(line);
+            }
+        }
+    }
+    out
+}
+
+/// The column to align each line's inline comment to: runs of two or more consecutive lines
+/// with inline comments (outside preserved tables) share one.
+fn comment_columns(lines: &[Line]) -> Vec<Option<usize>> {
+    let code_width = |line: &Line| match line {
+        Line::Text { indent, text, comment: Some(at), preserved: false, .. } => {
+            Some(indent.chars().count() + text[..*at].trim_end_matches(BLANKS).chars().count())
+        }
+        _ => None,
+    };
+    let mut columns = vec![None; lines.len()];
+    let mut i = 0;
+    while i < lines.len() {
+        let run = lines[i..].iter().take_while(|l| code_width(l).is_some()).count();
+        if run >= 2 {
+            let column = lines[i..i + run].iter().filter_map(code_width).max().unwrap_or_default() + 1;
+            columns[i..i + run].fill(Some(column));
+        }
+        i += run.max(1);
+    }
+    columns
+}
+
+/// Whitespace between tokens, as the lexer skips it.
+const BLANKS: [char; 2] = [' ', '\t'];
+
+/// Physical lines, split where the lexer starts a new line: at `\r\n`, `\n` or a lone `\r`.
+fn physical_lines(source: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut rest = source;
+    while let Some(i) = rest.find(['\r', '\n']) {
+        lines.push(&rest[..i]);
+        let len = if rest[i..].starts_with("\r\n") { 2 } else { 1 };
+        rest = &rest[i + len..];
+    }
+    if !rest.is_empty() {
+        lines.push(rest);
+    }
+    lines
+}
+
+/// Code tokens of the statement starting on physical line `first`, joining continued lines
+/// without their ` _` markers.
+fn logical_line(code_by_line: &[Vec<Token>], first: usize) -> Vec<Token> {
+    let mut code = Vec::new();
+    for line in &code_by_line[first..] {
+        code.extend(line.iter().cloned());
+        if !line.last().is_some_and(is_continuation) {
+            break;
+        }
+        code.pop();
+    }
+    code
+}
+
+/// An open `#If` group: the block stack at the `#If`, and at the end of its first branch once
+/// a later branch has started.
+type Branches = (Vec<Frame>, Option<Vec<Frame>>);
+
+fn depth(stack: &[Frame]) -> usize {
+    stack
+        .iter()
+        .map(|f| match f {
+            Frame::Select { in_case: true } => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Indentation and following block stack for `#If`, `#ElseIf`, `#Else` and `#EndIf` lines,
+/// which are indented like their `#If`.
+fn directive_indent(head: &str, stack: &[Frame], branches: &mut Vec<Branches>) -> Option<(usize, Vec<Frame>)> {
+    match head {
+        "#if" => {
+            branches.push((stack.to_vec(), None));
+            Some((depth(stack), stack.to_vec()))
+        }
+        "#elseif" | "#else" => {
+            let (at_if, first_end) = branches.last_mut()?;
+            first_end.get_or_insert_with(|| stack.to_vec());
+            Some((depth(at_if), at_if.clone()))
+        }
+        "#endif" => {
+            let (at_if, first_end) = branches.pop()?;
+            Some((depth(&at_if), first_end.unwrap_or_else(|| stack.to_vec())))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the indentation level for a line with the given head keyword and the block stack
+/// to use for the lines after it.
+fn indent_for(head: &str, code: &[Token], stack: &[Frame]) -> (usize, Vec<Frame>) {
+    let mut stack = stack.to_vec();
+
+    match head {
+        "elseif" | "else" if stack.last() == Some(&Frame::If) => {
+            stack.pop();
+            let level = depth(&stack);
+            stack.push(Frame::If);
+            return (level, stack);
+        }
+        "case" => {
+            if let Some(Frame::Select { in_case }) = stack.last_mut() {
+                *in_case = false;
+                let level = depth(&stack);
+                if let Some(Frame::Select { in_case }) = stack.last_mut() {
+                    *in_case = true;
+                }
+                return (level, stack);
+            }
+        }
+        "endif" if stack.last() == Some(&Frame::If) => {
+            stack.pop();
+            return (depth(&stack), stack);
+        }
+        "endselect" if matches!(stack.last(), Some(Frame::Select { .. })) => {
+            stack.pop();
+            return (depth(&stack), stack);
+        }
+        _ => {}
+    }
+
+    if stack.last() == Some(&Frame::Block) && OPENERS.iter().any(|(_, closers)| closers.contains(&head)) {
+        stack.pop();
+        return (depth(&stack), stack);
+    }
+
+    let level = depth(&stack);
+    let opens_if = head == "if" && code.last().is_some_and(|t| t.is_word("Then"));
+    if opens_if {
+        stack.push(Frame::If);
+    } else if head == "selectcase" {
+        stack.push(Frame::Select { in_case: false });
+    } else if OPENERS.iter().any(|(open, _)| *open == head) {
+        stack.push(Frame::Block);
+    }
+    (level, stack)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reindents_nested_blocks() {
+        let src = "BeginProg\nScan(1,Sec,0,0)\nIf x Then\ny = 1 'set y\nElse\ny = 2\nEndIf\nNextScan\nEndProg\n";
+        let expected = "BeginProg\n  Scan(1,Sec,0,0)\n    If x Then\n      y = 1 'set y\n    Else\n      y = 2\n    EndIf\n  NextScan\nEndProg\n";
+        assert_eq!(format(src, &FormatOptions::default()), expected);
+    }
+
+    #[test]
+    fn formats_select_case_and_is_idempotent() {
+        let src = "Select Case x\nCase 1\ny = 1\nCase Else\ny = 2\nEndSelect\n\n\n\nz = 3";
+        let once = format(src, &FormatOptions::default());
+        assert_eq!(once, "Select Case x\n  Case 1\n    y = 1\n  Case Else\n    y = 2\nEndSelect\n\nz = 3");
+        assert_eq!(format(&once, &FormatOptions::default()), once);
+    }
+
+    #[test]
+    fn splits_lines_like_the_lexer() {
+        // A lone CR ends a line for the lexer, so it has to for the indentation as well.
+        let once = format("BeginProg\rx = 1\rEndProg\r", &FormatOptions::default());
+        assert_eq!(once, "BeginProg\n  x = 1\nEndProg\n");
+        // Other Unicode whitespace is not skipped by the lexer and therefore kept.
+        assert_eq!(format("x = 1\u{b}\n", &FormatOptions::default()), "x = 1\u{b}\n");
+    }
+
+    #[test]
+    fn indents_continued_lines() {
+        let src = "BeginProg\nIf a And _\nb Then\nCall Log(1, _\n2)\nEndIf\nEndProg\n";
+        let expected = "BeginProg\n  If a And _\n    b Then\n    Call Log(1, _\n      2)\n  EndIf\nEndProg\n";
+        assert_eq!(format(src, &FormatOptions::default()), expected);
+        // The blank line ending a continued statement is kept even when blank lines are not.
+        let options = FormatOptions { max_blank_lines: 0, ..FormatOptions::default() };
+        assert_eq!(format("x = _\n\n\ny\n", &options), "x = _\n\ny\n");
+    }
+
+    #[test]
+    fn indents_preprocessor_branches_alike() {
+        let src = "BeginProg\n#If X Then\nScan(1,Sec,0,0)\n#Else\nScan(5,Sec,0,0)\n#EndIf\nx = 1\nNextScan\nEndProg\n";
+        let expected =
+            "BeginProg\n  #If X Then\n  Scan(1,Sec,0,0)\n  #Else\n  Scan(5,Sec,0,0)\n  #EndIf\n    x = 1\n  NextScan\nEndProg\n";
+        assert_eq!(format(src, &FormatOptions::default()), expected);
+    }
+
+    #[test]
+    fn aligns_inline_comments_in_runs() {
+        let options = FormatOptions { align_comments: true, ..FormatOptions::default() };
+        let src = "Public BattV 'battery\nPublic PTemp_C   'panel\nPublic Flag(4) 'flags\n\nDim i 'loop   \n'a comment line\n";
+        let expected = "Public BattV   'battery\nPublic PTemp_C 'panel\nPublic Flag(4) 'flags\n\nDim i 'loop\n'a comment line\n";
+        assert_eq!(format(src, &options), expected);
+        assert_eq!(format(src, &FormatOptions::default()), src.replace("'loop   ", "'loop"), "off by default");
+    }
+
+    #[test]
+    fn preserves_table_alignment_when_asked() {
+        let src = "DataTable(Hourly,True,-1)\n    DataInterval(0,60,Min,10)  'hourly\n      Average(1,BattV,FP2,False) 'volts\nEndTable\nx = 1 'a\ny = 22 'b\n";
+        let options = FormatOptions { align_comments: true, preserve_table_alignment: true, ..FormatOptions::default() };
+        let expected = "DataTable(Hourly,True,-1)\n    DataInterval(0,60,Min,10)  'hourly\n      Average(1,BattV,FP2,False) 'volts\nEndTable\nx = 1  'a\ny = 22 'b\n";
+        assert_eq!(format(src, &options), expected);
+        assert!(format(src, &FormatOptions::default()).contains("\n  DataInterval(0,60,Min,10)  'hourly\n  Average"));
+    }
+
+    #[test]
+    fn applies_blank_line_policies() {
+        let src = "Public x\n'Main program\nBeginProg\n\n  Scan(1,Sec,0,0)\n\n    x = 1\n\n  NextScan\n\nEndProg\nSub Log\nEndSub\n";
+        let options = FormatOptions { trim_blank_lines_in_blocks: true, blank_line_before_sections: true, ..FormatOptions::default() };
+        let expected = "Public x\n\n'Main program\nBeginProg\n  Scan(1,Sec,0,0)\n    x = 1\n  NextScan\nEndProg\n\nSub Log\nEndSub\n";
+        assert_eq!(format(src, &options), expected);
+        // Sections get their blank line even when no others are allowed.
+        let options = FormatOptions { max_blank_lines: 0, ..options };
+        assert_eq!(format(src, &options), expected);
+    }
+}
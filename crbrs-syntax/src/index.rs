@@ -0,0 +1,173 @@
+// FILE: crbrs-syntax/src/index.rs
+
+//! Per-file symbol index: the callable units of a program and the calls between them.
+//!
+//! Callers are the places code runs from: each `Sub`/`Function`, each `Scan` in `BeginProg`
+//! (including those started by `SlowSequence`) and code in `BeginProg` outside any scan.
+//! Callee names are recorded as written; resolving them across files is up to the caller,
+//! which lets the language server combine the indexes of every open document.
+
+use crate::ast::*;
+use crate::lexer::Span;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnitKind {
+    Sub,
+    Function,
+    /// A `Scan ... NextScan` in the main task.
+    Scan,
+    /// A `Scan` following `SlowSequence`.
+    SlowSequence,
+    /// Statements directly in `BeginProg` outside any scan.
+    Program,
+}
+
+/// A callable unit or scan task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Unit {
+    /// Routine name, or a generated label such as `Scan` / `SlowSequence 2` for scans.
+    pub name: String,
+    pub kind: UnitKind,
+    /// Span of the name (routines) or the opening keyword line (scans, `BeginProg`).
+    pub name_span: Span,
+    /// Span of the opening line.
+    pub start: Span,
+    /// Span of the closing line (the opening line if the block is unclosed).
+    pub end: Span,
+}
+
+impl Unit {
+    pub fn is_routine(&self) -> bool {
+        matches!(self.kind, UnitKind::Sub | UnitKind::Function)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallSite {
+    /// Index into [`FileIndex::units`] of the unit containing the call.
+    pub caller: usize,
+    /// Called name as written.
+    pub callee: String,
+    /// Span of the called name.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileIndex {
+    pub units: Vec<Unit>,
+    pub calls: Vec<CallSite>,
+}
+
+impl FileIndex {
+    /// Routines (Subs and Functions) declared in this file.
+    pub fn routines(&self) -> impl Iterator<Item = &Unit> {
+        self.units.iter().filter(|u| u.is_routine())
+    }
+
+    /// The innermost unit whose lines contain `line`.
+    pub fn unit_at_line(&self, line: u32) -> Option<usize> {
+        self.units
+            .iter()
+            .enumerate()
+            .filter(|(_, u)| u.start.line <= line && line <= u.end.line)
+            .min_by_key(|(_, u)| u.end.line - u.start.line)
+            .map(|(i, _)| i)
+    }
+
+    /// Calls made from unit `caller`.
+    pub fn calls_from(&self, caller: usize) -> impl Iterator<Item = &CallSite> {
+        self.calls.iter().filter(move |c| c.caller == caller)
+    }
+
+    /// Calls to `name` (case-insensitive).
+    pub fn calls_to<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a CallSite> {
+        self.calls.iter().filter(move |c| c.callee.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Builds the index for `program`. Every call-like reference is recorded (instructions
+/// included); filter callees against known routines to get user calls only.
+pub fn index(program: &Program) -> FileIndex {
+    let mut idx = FileIndex::default();
+    for stmt in &program.body {
+        match &stmt.kind {
+            StmtKind::Routine(routine) => {
+                let kind = match routine.kind {
+                    RoutineKind::Sub => UnitKind::Sub,
+                    RoutineKind::Function => UnitKind::Function,
+                };
+                let unit = push_unit(&mut idx, routine.name.text.clone(), kind, routine.name.span, stmt.span, routine.end);
+                collect_calls(&mut idx, unit, &routine.body);
+            }
+            StmtKind::BeginProg(block) => index_program(&mut idx, stmt.span, block),
+            _ => {}
+        }
+    }
+    idx
+}
+
+fn push_unit(idx: &mut FileIndex, name: String, kind: UnitKind, name_span: Span, start: Span, end: Option<Span>) -> usize {
+    idx.units.push(Unit { name, kind, name_span, start, end: end.unwrap_or(start) });
+    idx.units.len() - 1
+}
+
+fn index_program(idx: &mut FileIndex, span: Span, block: &Block) {
+    let program_unit = push_unit(idx, "BeginProg".to_string(), UnitKind::Program, span, span, block.end);
+    let mut slow = false;
+    let mut slow_count = 0;
+    for stmt in &block.body {
+        match &stmt.kind {
+            StmtKind::SlowSequence => slow = true,
+            StmtKind::EndSequence => slow = false,
+            StmtKind::Scan(scan) => {
+                let (name, kind) = if slow {
+                    slow_count += 1;
+                    (format!("SlowSequence {}", slow_count), UnitKind::SlowSequence)
+                } else {
+                    ("Scan".to_string(), UnitKind::Scan)
+                };
+                let unit = push_unit(idx, name, kind, stmt.span, stmt.span, scan.end);
+                collect_calls(idx, unit, &scan.body);
+                // Each slow sequence holds exactly one scan.
+                slow = false;
+            }
+            _ => collect_calls(idx, program_unit, std::slice::from_ref(stmt)),
+        }
+    }
+}
+
+fn collect_calls(idx: &mut FileIndex, caller: usize, body: &[Stmt]) {
+    walk_stmts(body, &mut |stmt| {
+        if let StmtKind::Call(call) = &stmt.kind {
+            idx.calls.push(CallSite { caller, callee: call.name.text.clone(), span: call.name.span });
+        }
+        for expr in stmt.exprs() {
+            expr.walk(&mut |e| {
+                if let ExprKind::Ref { name, args: Some(_) } = &e.kind {
+                    let span = Span { end: e.span.start + name.len(), ..e.span };
+                    idx.calls.push(CallSite { caller, callee: name.clone(), span });
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn records_calls_per_scan_and_routine() {
+        let src = "Function Twice(x)\n  Twice = x * 2\nEndFunction\nSub Slow\n  Public y\n  y = Twice(1)\nEndSub\nBeginProg\n  Scan(1,Sec,0,0)\n    Call Slow\n  NextScan\n  SlowSequence\n  Scan(10,Sec,0,0)\n    Slow\n  NextScan\nEndProg\n";
+        let idx = index(&parse(src));
+        let names: Vec<&str> = idx.units.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, vec!["Twice", "Slow", "BeginProg", "Scan", "SlowSequence 1"]);
+
+        let callers: Vec<&str> = idx.calls_to("slow").map(|c| idx.units[c.caller].name.as_str()).collect();
+        assert_eq!(callers, vec!["Scan", "SlowSequence 1"]);
+        assert_eq!(idx.calls_to("Twice").next().map(|c| c.caller), Some(1));
+        assert_eq!(idx.unit_at_line(13), Some(4));
+    }
+}
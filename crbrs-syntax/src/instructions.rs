@@ -0,0 +1,210 @@
+// FILE: crbrs-syntax/src/instructions.rs
+
+//! Built-in CRBasic instruction database.
+//!
+//! Each entry records the canonical spelling, a category, where in a program the instruction
+//! may appear and its parameter names. The table covers the commonly used instructions of the
+//! CR1000/CR3000/CR6/CR300 family; it is not an exhaustive copy of the vendor manual.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Category {
+    Measurement,
+    OutputProcessing,
+    TableDefinition,
+    ProgramStructure,
+    ProgramControl,
+    Math,
+    String,
+    Communication,
+    Settings,
+    Other,
+}
+
+/// Where an instruction is allowed to appear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Context {
+    /// No restriction.
+    Anywhere,
+    /// Only inside a `Scan ... NextScan` (or a Sub/Function called from one).
+    Scan,
+    /// Only between `DataTable` and `EndTable`.
+    DataTable,
+    /// Only in the declarations section, before `BeginProg`.
+    Declarations,
+    /// Only inside `BeginProg ... EndProg`.
+    Program,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Instruction {
+    /// Canonical spelling as used by the vendor editor.
+    pub name: &'static str,
+    pub category: Category,
+    pub context: Context,
+    pub params: &'static [&'static str],
+    pub summary: &'static str,
+}
+
+macro_rules! ins {
+    ($name:literal, $cat:ident, $ctx:ident, [$($p:literal),*], $summary:literal) => {
+        Instruction {
+            name: $name,
+            category: Category::$cat,
+            context: Context::$ctx,
+            params: &[$($p),*],
+            summary: $summary,
+        }
+    };
+}
+
+pub static INSTRUCTIONS: &[Instruction] = &[
+    // --- Program structure ---
+    ins!("BeginProg", ProgramStructure, Declarations, [], "Marks the start of the program body."),
+    ins!("EndProg", ProgramStructure, Anywhere, [], "Marks the end of the program body."),
+    ins!("Scan", ProgramStructure, Program, ["Interval", "Units", "BufferOption", "Count"], "Starts a scan loop executed at a fixed interval."),
+    ins!("NextScan", ProgramStructure, Scan, [], "Ends a scan loop."),
+    ins!("SlowSequence", ProgramStructure, Program, [], "Starts a slow sequence running in its own task."),
+    ins!("EndSequence", ProgramStructure, Program, [], "Ends a slow sequence."),
+    ins!("SubScan", ProgramStructure, Scan, ["SubInterval", "Units", "SubScans"], "Starts a sub-scan inside a scan."),
+    ins!("NextSubScan", ProgramStructure, Scan, [], "Ends a sub-scan."),
+    ins!("ExitScan", ProgramControl, Scan, [], "Exits the current scan loop."),
+    ins!("ContinueScan", ProgramControl, Scan, [], "Continues with the next iteration of the scan."),
+    ins!("CallTable", ProgramControl, Anywhere, ["TableName"], "Runs the output processing of a data table."),
+    ins!("PipeLineMode", Settings, Declarations, [], "Runs measurement and processing in separate tasks."),
+    ins!("SequentialMode", Settings, Declarations, [], "Runs instructions in the order they are written."),
+    // --- Table definition ---
+    ins!("DataTable", TableDefinition, Declarations, ["Name", "TrigVar", "Size"], "Starts a data table definition."),
+    ins!("EndTable", TableDefinition, DataTable, [], "Ends a data table definition."),
+    ins!("DataInterval", TableDefinition, DataTable, ["TintoInt", "Interval", "Units", "Lapses"], "Sets the record interval of a table."),
+    ins!("CardOut", TableDefinition, DataTable, ["StopRing", "Size"], "Stores the table on the memory card."),
+    ins!("TableFile", TableDefinition, DataTable, ["FileName", "Options", "MaxFiles", "NumRecs", "TimeIntoInterval", "Interval", "Units", "FileOpenVar", "LastFileName"], "Writes the table to files."),
+    ins!("FieldNames", TableDefinition, DataTable, ["List"], "Overrides the field names of the preceding output instruction."),
+    ins!("DataEvent", TableDefinition, DataTable, ["PreTrigRecs", "StartTrig", "StopTrig", "PostTrigRecs"], "Stores records around a trigger event."),
+    ins!("OpenInterval", TableDefinition, DataTable, [], "Processes over the interval the table was open."),
+    // --- Output processing ---
+    ins!("Sample", OutputProcessing, DataTable, ["Reps", "Source", "DataType"], "Stores the current value of a variable."),
+    ins!("Average", OutputProcessing, DataTable, ["Reps", "Source", "DataType", "DisableVar"], "Stores the average over the output interval."),
+    ins!("Maximum", OutputProcessing, DataTable, ["Reps", "Source", "DataType", "DisableVar", "Time"], "Stores the maximum over the output interval."),
+    ins!("Minimum", OutputProcessing, DataTable, ["Reps", "Source", "DataType", "DisableVar", "Time"], "Stores the minimum over the output interval."),
+    ins!("Totalize", OutputProcessing, DataTable, ["Reps", "Source", "DataType", "DisableVar"], "Stores the total over the output interval."),
+    ins!("StdDev", OutputProcessing, DataTable, ["Reps", "Source", "DataType", "DisableVar"], "Stores the standard deviation over the output interval."),
+    ins!("Median", OutputProcessing, DataTable, ["Reps", "Source", "MaxN", "DataType", "DisableVar"], "Stores the median over the output interval."),
+    ins!("Moment", OutputProcessing, DataTable, ["Reps", "Source", "Order", "DataType", "DisableVar"], "Stores a statistical moment over the output interval."),
+    ins!("Covariance", OutputProcessing, DataTable, ["NumVals", "Source", "DataType", "DisableVar", "NumCov"], "Stores covariances over the output interval."),
+    ins!("WindVector", OutputProcessing, DataTable, ["Repetitions", "Speed/East", "Direction/North", "DataType", "DisableVar", "Subinterval", "SensorType", "OutputOpt"], "Stores wind vector statistics."),
+    ins!("Histogram", OutputProcessing, DataTable, ["BinSelect", "DataType", "DisableVar", "Bins", "Form", "WtVal", "LoLim", "UpLim"], "Stores a histogram over the output interval."),
+    ins!("ETsz", OutputProcessing, DataTable, ["Temp", "RH", "uZ", "Rn", "Longitude", "Latitude", "Altitude", "Zw", "Sz", "DataType", "DisableVar"], "Stores ASCE standardized reference evapotranspiration."),
+    ins!("SampleMaxMin", OutputProcessing, DataTable, ["Reps", "Source", "DataType", "DisableVar", "Setting"], "Samples when another variable hits a maximum or minimum."),
+    // --- Measurement ---
+    ins!("Battery", Measurement, Scan, ["Dest"], "Measures the logger supply voltage."),
+    ins!("PanelTemp", Measurement, Scan, ["Dest", "Integ"], "Measures the wiring panel temperature."),
+    ins!("VoltSe", Measurement, Scan, ["Dest", "Reps", "Range", "SEChan", "MeasOff", "SettlingTime", "Integ", "Mult", "Offset"], "Measures single-ended voltage."),
+    ins!("VoltDiff", Measurement, Scan, ["Dest", "Reps", "Range", "DiffChan", "RevDiff", "SettlingTime", "Integ", "Mult", "Offset"], "Measures differential voltage."),
+    ins!("TCDiff", Measurement, Scan, ["Dest", "Reps", "Range", "DiffChan", "TCType", "TRef", "RevDiff", "SettlingTime", "Integ", "Mult", "Offset"], "Measures a differential thermocouple."),
+    ins!("TCSe", Measurement, Scan, ["Dest", "Reps", "Range", "SEChan", "TCType", "TRef", "MeasOff", "SettlingTime", "Integ", "Mult", "Offset"], "Measures a single-ended thermocouple."),
+    ins!("BrHalf", Measurement, Scan, ["Dest", "Reps", "Range", "SEChan", "ExChan", "MeasPEx", "ExmV", "RevEx", "SettlingTime", "Integ", "Mult", "Offset"], "Measures a half bridge."),
+    ins!("BrHalf3W", Measurement, Scan, ["Dest", "Reps", "Range", "SEChan", "ExChan", "MeasPEx", "ExmV", "RevEx", "SettlingTime", "Integ", "Mult", "Offset"], "Measures a three-wire half bridge."),
+    ins!("BrHalf4W", Measurement, Scan, ["Dest", "Reps", "Range1", "Range2", "DiffChan", "ExChan", "MeasPEx", "ExmV", "RevEx", "RevDiff", "SettlingTime", "Integ", "Mult", "Offset"], "Measures a four-wire half bridge."),
+    ins!("BrFull", Measurement, Scan, ["Dest", "Reps", "Range", "DiffChan", "ExChan", "MeasPEx", "ExmV", "RevEx", "RevDiff", "SettlingTime", "Integ", "Mult", "Offset"], "Measures a full bridge."),
+    ins!("Resistance", Measurement, Scan, ["Dest", "Reps", "Range", "DiffChan", "ExChan", "MeasPEx", "ExuA", "RevEx", "RevDiff", "SettlingTime", "Integ", "Mult", "Offset"], "Measures resistance using current excitation."),
+    ins!("Therm107", Measurement, Scan, ["Dest", "Reps", "SEChan", "ExChan", "SettlingTime", "Integ", "Mult", "Offset"], "Measures a 107 thermistor."),
+    ins!("Therm109", Measurement, Scan, ["Dest", "Reps", "SEChan", "ExChan", "SettlingTime", "Integ", "Mult", "Offset"], "Measures a 109 thermistor."),
+    ins!("PRT", Measurement, Scan, ["Dest", "Reps", "Source", "Mult", "Offset"], "Converts a PRT resistance ratio to temperature."),
+    ins!("PulseCount", Measurement, Scan, ["Dest", "Reps", "PChan", "PConfig", "POption", "Mult", "Offset"], "Counts pulses or measures frequency."),
+    ins!("TimerIO", Measurement, Scan, ["Dest", "Edges", "Function", "Filter", "AutoRange"], "Measures timing on control ports."),
+    ins!("SDI12Recorder", Measurement, Scan, ["Dest", "SDIPort", "SDIAddress", "SDICommand", "Multiplier", "Offset", "FillNAN", "WaitonTimeout"], "Collects data from an SDI-12 sensor."),
+    ins!("SDI12SensorSetup", Measurement, Anywhere, ["Repetitions", "SDIPort", "SensorAddress", "ResponseTime"], "Configures the logger as an SDI-12 sensor."),
+    ins!("ModbusMaster", Measurement, Anywhere, ["ResultCode", "ComPort", "BaudRate", "ModbusAddr", "Function", "Variable", "Start", "Length", "Tries", "TimeOut", "ModbusOption"], "Queries a Modbus slave device."),
+    ins!("ModbusSlave", Communication, Anywhere, ["ComPort", "BaudRate", "ModbusAddr", "ModbusVariable", "ModbusBooleanVar", "ModbusOption"], "Makes the logger act as a Modbus slave."),
+    ins!("CS616", Measurement, Scan, ["Dest", "Reps", "SEChan", "ExChan", "MeasPerEx", "Mult", "Offset"], "Measures a CS616 water content reflectometer."),
+    ins!("AM25T", Measurement, Scan, ["Dest", "Reps", "Range", "AM25TChan", "DiffChan", "TCType", "Tref", "ClkPort", "ResPort", "VxChan", "RevDiff", "SettlingTime", "Integ", "Mult", "Offset"], "Measures thermocouples on an AM25T multiplexer."),
+    ins!("CDM_VoltSe", Measurement, Scan, ["CDM_Dest", "Reps", "Range", "SEChan", "MeasOff", "SettlingTime", "Integ", "Mult", "Offset"], "Measures single-ended voltage on a CDM module."),
+    ins!("ExciteV", Measurement, Scan, ["ExChan", "ExmV", "XDelay"], "Sets a voltage excitation channel."),
+    ins!("PortSet", Measurement, Anywhere, ["Port", "State"], "Sets a control port high or low."),
+    ins!("SW12", Measurement, Anywhere, ["SW12Port", "State"], "Switches the 12 V terminal."),
+    ins!("WriteIO", Measurement, Anywhere, ["Mask", "Source"], "Sets the state of several control ports."),
+    ins!("ReadIO", Measurement, Anywhere, ["Dest", "Mask"], "Reads the state of control ports."),
+    ins!("Delay", ProgramControl, Anywhere, ["Option", "Delay", "Units"], "Pauses program execution."),
+    // --- Math ---
+    ins!("ABS", Math, Anywhere, ["Source"], "Absolute value."),
+    ins!("ACOS", Math, Anywhere, ["Source"], "Arc cosine."),
+    ins!("ASIN", Math, Anywhere, ["Source"], "Arc sine."),
+    ins!("ATN", Math, Anywhere, ["Source"], "Arc tangent."),
+    ins!("ATN2", Math, Anywhere, ["Y", "X"], "Arc tangent of y/x."),
+    ins!("AvgSpa", Math, Anywhere, ["Dest", "Swath", "Source"], "Spatial average."),
+    ins!("AvgRun", Math, Anywhere, ["Dest", "Reps", "Source", "Number"], "Running average."),
+    ins!("COS", Math, Anywhere, ["Angle"], "Cosine."),
+    ins!("EXP", Math, Anywhere, ["Source"], "Exponential."),
+    ins!("FIX", Math, Anywhere, ["Source"], "Integer portion, truncated towards zero."),
+    ins!("FRAC", Math, Anywhere, ["Source"], "Fractional portion."),
+    ins!("INT", Math, Anywhere, ["Source"], "Integer portion, rounded down."),
+    ins!("LN", Math, Anywhere, ["Source"], "Natural logarithm."),
+    ins!("LOG", Math, Anywhere, ["Source"], "Natural logarithm."),
+    ins!("LOG10", Math, Anywhere, ["Source"], "Base-10 logarithm."),
+    ins!("MaxSpa", Math, Anywhere, ["Dest", "Swath", "Source"], "Spatial maximum."),
+    ins!("MinSpa", Math, Anywhere, ["Dest", "Swath", "Source"], "Spatial minimum."),
+    ins!("Round", Math, Anywhere, ["Number", "Decimal"], "Rounds to a number of decimal places."),
+    ins!("SGN", Math, Anywhere, ["Source"], "Sign of a number."),
+    ins!("SIN", Math, Anywhere, ["Angle"], "Sine."),
+    ins!("SQR", Math, Anywhere, ["Number"], "Square root."),
+    ins!("TAN", Math, Anywhere, ["Angle"], "Tangent."),
+    ins!("TotalRun", Math, Anywhere, ["Dest", "Reps", "Source", "Number"], "Running total."),
+    ins!("SatVP", Math, Anywhere, ["Dest", "Temp"], "Saturation vapor pressure."),
+    ins!("VaporPressure", Math, Anywhere, ["Dest", "Temp", "RH"], "Vapor pressure from temperature and RH."),
+    ins!("DewPoint", Math, Anywhere, ["Dest", "Temp", "RH"], "Dew point from temperature and RH."),
+    ins!("WetDryBulb", Math, Anywhere, ["Dest", "Temp", "WetTemp", "Pressure"], "Vapor pressure from wet/dry bulb readings."),
+    ins!("Move", Math, Anywhere, ["Dest", "DestReps", "Source", "SourceReps"], "Copies values between variables."),
+    // --- Strings ---
+    ins!("FormatFloat", String, Anywhere, ["Float", "FormatString"], "Formats a number as a string."),
+    ins!("InStr", String, Anywhere, ["Start", "SearchString", "FilterString", "SearchOption"], "Finds a string within another."),
+    ins!("Left", String, Anywhere, ["SourceString", "Length"], "Leftmost characters of a string."),
+    ins!("Len", String, Anywhere, ["Source"], "Length of a string."),
+    ins!("LTrim", String, Anywhere, ["SourceString"], "Removes leading whitespace."),
+    ins!("Mid", String, Anywhere, ["SourceString", "Start", "Length"], "Substring."),
+    ins!("Replace", String, Anywhere, ["SourceString", "SearchString", "ReplaceString"], "Replaces occurrences within a string."),
+    ins!("Right", String, Anywhere, ["SourceString", "Length"], "Rightmost characters of a string."),
+    ins!("RTrim", String, Anywhere, ["SourceString"], "Removes trailing whitespace."),
+    ins!("SplitStr", String, Anywhere, ["SplitResult", "SearchString", "FilterString", "NumSplit", "SplitOption"], "Splits a string into parts."),
+    ins!("Sprintf", String, Anywhere, ["Destination", "Format"], "Formats values into a string."),
+    ins!("StrComp", String, Anywhere, ["String1", "String2"], "Compares two strings."),
+    ins!("Trim", String, Anywhere, ["SourceString"], "Removes leading and trailing whitespace."),
+    ins!("UpperCase", String, Anywhere, ["SourceString"], "Converts a string to upper case."),
+    ins!("LowerCase", String, Anywhere, ["SourceString"], "Converts a string to lower case."),
+    // --- Communication / settings ---
+    ins!("SerialOpen", Communication, Anywhere, ["ComPort", "BaudRate", "Format", "TXDelay", "BufferSize", "Mode"], "Opens a serial port."),
+    ins!("SerialClose", Communication, Anywhere, ["ComPort"], "Closes a serial port."),
+    ins!("SerialIn", Communication, Anywhere, ["Dest", "ComPort", "TimeOut", "TerminationChar", "MaxNumChars"], "Reads from a serial port."),
+    ins!("SerialInRecord", Communication, Anywhere, ["ComPort", "Dest", "BeginWord", "NBytes", "EndWord", "NBytesReturned", "LastRecord"], "Reads a framed record from a serial port."),
+    ins!("SerialOut", Communication, Anywhere, ["ComPort", "OutString", "WaitString", "NumberTries", "TimeOut"], "Writes to a serial port."),
+    ins!("SerialFlush", Communication, Anywhere, ["ComPort"], "Clears a serial port buffer."),
+    ins!("SendVariables", Communication, Anywhere, ["ResultCode", "ComPort", "NeighborAddr", "PakBusAddr", "Security", "TimeOut", "TableName", "FieldName", "Variable", "Swath"], "Sends variables to another PakBus device."),
+    ins!("GetVariables", Communication, Anywhere, ["ResultCode", "ComPort", "NeighborAddr", "PakBusAddr", "Security", "TimeOut", "TableName", "FieldName", "Variable", "Swath"], "Reads variables from another PakBus device."),
+    ins!("EMailSend", Communication, Anywhere, ["ServerAddr", "ToAddr", "FromAddr", "Subject", "Message", "Attachment", "UserName", "Password", "Result"], "Sends an email."),
+    ins!("NetworkTimeProtocol", Communication, Anywhere, ["NTPServer", "NTPOffset", "NTPMaxMSec"], "Synchronizes the clock with an NTP server."),
+    ins!("PakBusClock", Communication, Anywhere, ["PakBusAddr"], "Synchronizes the clock with another PakBus device."),
+    ins!("SetStatus", Settings, Anywhere, ["FieldName", "Value"], "Changes a Status table setting."),
+    ins!("SetSetting", Settings, Anywhere, ["FieldName", "Value"], "Changes a logger setting."),
+    ins!("StationName", Settings, Declarations, ["Name"], "Sets the station name."),
+    ins!("PakBusAddress", Settings, Declarations, ["Address"], "Sets the PakBus address."),
+    ins!("SetSecurity", Settings, Declarations, ["Security1", "Security2", "Security3"], "Sets the security codes."),
+    ins!("RealTime", Other, Anywhere, ["Dest"], "Reads the logger clock into an array."),
+    ins!("TimeIntoInterval", Other, Anywhere, ["TintoInt", "Interval", "Units"], "True at a given time into an interval."),
+    ins!("IfTime", Other, Anywhere, ["TintoInt", "Interval", "Units"], "True at a given time into an interval."),
+    ins!("ClockSet", Other, Anywhere, ["Source"], "Sets the logger clock."),
+    ins!("Timer", Other, Anywhere, ["TimNo", "Units", "TimOpt"], "Reads, starts, stops or resets a timer."),
+    ins!("FileManage", Other, Anywhere, ["Device:FileName", "Attribute"], "Manages files on the logger."),
+    ins!("FileOpen", Other, Anywhere, ["FileName", "Mode", "SeekPoint"], "Opens a file."),
+    ins!("FileClose", Other, Anywhere, ["FileHandle"], "Closes a file."),
+    ins!("FileWrite", Other, Anywhere, ["FileHandle", "Source", "Length"], "Writes to a file."),
+    ins!("GetRecord", Other, Anywhere, ["Dest", "TableName", "RecsBack"], "Reads a stored record into a variable."),
+    ins!("WaitDigTrig", Other, Anywhere, ["ControlPort", "Option"], "Waits for a trigger on a control port."),
+    ins!("Units", Other, Declarations, ["Variable", "Units"], "Assigns units to a variable for table headers."),
+    ins!("Alias", Other, Declarations, ["VariableName", "AliasName"], "Gives a variable or array element another name."),
+];
+
+/// Looks up an instruction by name (case-insensitive).
+pub fn lookup(name: &str) -> Option<&'static Instruction> {
+    INSTRUCTIONS.iter().find(|i| i.name.eq_ignore_ascii_case(name))
+}
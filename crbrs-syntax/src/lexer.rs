@@ -0,0 +1,235 @@
+// FILE: crbrs-syntax/src/lexer.rs
+
+//! Tokenizer for CRBasic source text.
+//!
+//! The lexer never fails: anything it does not understand becomes a [`TokenKind::Unknown`]
+//! token so that the parser, linter and formatter can keep going on broken input.
+
+use serde::{Deserialize, Serialize};
+
+/// Byte range into the source text plus the 0-based line/column of its start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: u32,
+    pub col: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TokenKind {
+    /// Identifier or keyword (CRBasic keywords are case-insensitive, the parser decides).
+    Ident,
+    /// Decimal, scientific, `&H` hex or `&B` binary literal.
+    Number,
+    /// Double-quoted string literal, including the quotes.
+    String,
+    /// `'` or `REM` comment running to the end of the line, including the marker.
+    Comment,
+    /// End of a physical line.
+    Newline,
+    LParen,
+    RParen,
+    Comma,
+    Colon,
+    /// `.` in dotted references such as `Status.Battery`.
+    Dot,
+    /// Arithmetic/comparison/concatenation operator (`+ - * / \ ^ = <> < > <= >= &`).
+    Operator,
+    /// `#If`, `#Else`, `#ElseIf`, `#EndIf` style preprocessor marker (the `#` and the word).
+    Directive,
+    /// Unrecognized character.
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: Span,
+}
+
+impl Token {
+    /// Case-insensitive comparison for identifiers/keywords.
+    pub fn is_word(&self, word: &str) -> bool {
+        self.kind == TokenKind::Ident && self.text.eq_ignore_ascii_case(word)
+    }
+}
+
+/// Splits `source` into tokens. Whitespace is dropped; newlines are kept as tokens.
+pub fn tokenize(source: &str) -> Vec<Token> {
+    Lexer::new(source).run()
+}
+
+struct Lexer<'a> {
+    src: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    line: u32,
+    line_start: usize,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a str) -> Self {
+        Lexer { src, bytes: src.as_bytes(), pos: 0, line: 0, line_start: 0, tokens: Vec::new() }
+    }
+
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    fn push(&mut self, kind: TokenKind, start: usize) {
+        let span = Span {
+            start,
+            end: self.pos,
+            line: self.line,
+            col: self.src[self.line_start..start].chars().count() as u32,
+        };
+        self.tokens.push(Token { kind, text: self.src[start..self.pos].to_string(), span });
+    }
+
+    fn run(mut self) -> Vec<Token> {
+        while let Some(b) = self.peek(0) {
+            let start = self.pos;
+            match b {
+                b' ' | b'\t' => self.pos += 1,
+                b'\r' => {
+                    self.pos += 1;
+                    if self.peek(0) == Some(b'\n') {
+                        self.pos += 1;
+                    }
+                    self.newline(start);
+                }
+                b'\n' => {
+                    self.pos += 1;
+                    self.newline(start);
+                }
+                b'\'' => {
+                    self.skip_to_eol();
+                    self.push(TokenKind::Comment, start);
+                }
+                b'"' => {
+                    self.string();
+                    self.push(TokenKind::String, start);
+                }
+                b'(' => self.single(TokenKind::LParen, start),
+                b')' => self.single(TokenKind::RParen, start),
+                b',' => self.single(TokenKind::Comma, start),
+                b':' => self.single(TokenKind::Colon, start),
+                b'&' if matches!(self.peek(1), Some(b'H' | b'h' | b'B' | b'b'))
+                    && self.peek(2).is_some_and(|c| c.is_ascii_hexdigit()) =>
+                {
+                    self.pos += 2;
+                    while self.peek(0).is_some_and(|c| c.is_ascii_hexdigit()) {
+                        self.pos += 1;
+                    }
+                    self.push(TokenKind::Number, start);
+                }
+                b'<' => {
+                    self.pos += 1;
+                    if matches!(self.peek(0), Some(b'>' | b'=')) {
+                        self.pos += 1;
+                    }
+                    self.push(TokenKind::Operator, start);
+                }
+                b'>' => {
+                    self.pos += 1;
+                    if self.peek(0) == Some(b'=') {
+                        self.pos += 1;
+                    }
+                    self.push(TokenKind::Operator, start);
+                }
+                b'+' | b'-' | b'*' | b'/' | b'\\' | b'^' | b'=' | b'&' => self.single(TokenKind::Operator, start),
+                b'.' if !self.peek(1).is_some_and(|c| c.is_ascii_digit()) => self.single(TokenKind::Dot, start),
+                b'#' if self.peek(1).is_some_and(|c| c.is_ascii_alphabetic()) => {
+                    self.pos += 1;
+                    self.ident_tail();
+                    self.push(TokenKind::Directive, start);
+                }
+                b'0'..=b'9' => {
+                    self.number();
+                    self.push(TokenKind::Number, start);
+                }
+                b'.' if self.peek(1).is_some_and(|c| c.is_ascii_digit()) => {
+                    self.number();
+                    self.push(TokenKind::Number, start);
+                }
+                c if c.is_ascii_alphabetic() || c == b'_' => {
+                    self.ident_tail();
+                    if self.src[start..self.pos].eq_ignore_ascii_case("rem")
+                        && !self.peek(0).is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_')
+                    {
+                        self.skip_to_eol();
+                        self.push(TokenKind::Comment, start);
+                    } else {
+                        self.push(TokenKind::Ident, start);
+                    }
+                }
+                _ => {
+                    // Advance by one full character so multi-byte input never splits a code point.
+                    let ch_len = self.src[self.pos..].chars().next().map_or(1, char::len_utf8);
+                    self.pos += ch_len;
+                    self.push(TokenKind::Unknown, start);
+                }
+            }
+        }
+        self.tokens
+    }
+
+    fn single(&mut self, kind: TokenKind, start: usize) {
+        self.pos += 1;
+        self.push(kind, start);
+    }
+
+    fn newline(&mut self, start: usize) {
+        self.push(TokenKind::Newline, start);
+        self.line += 1;
+        self.line_start = self.pos;
+    }
+
+    fn skip_to_eol(&mut self) {
+        while let Some(c) = self.peek(0) {
+            if c == b'\n' || c == b'\r' {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn string(&mut self) {
+        self.pos += 1; // opening quote
+        while let Some(c) = self.peek(0) {
+            match c {
+                b'"' if self.peek(1) == Some(b'"') => self.pos += 2, // "" escape
+                b'"' => {
+                    self.pos += 1;
+                    return;
+                }
+                b'\n' | b'\r' => return, // unterminated; leave the newline for the main loop
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    fn ident_tail(&mut self) {
+        while self.peek(0).is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_') {
+            self.pos += 1;
+        }
+    }
+
+    fn number(&mut self) {
+        while self.peek(0).is_some_and(|c| c.is_ascii_digit() || c == b'.') {
+            self.pos += 1;
+        }
+        if matches!(self.peek(0), Some(b'e' | b'E')) {
+            let sign = usize::from(matches!(self.peek(1), Some(b'+' | b'-')));
+            if self.peek(1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                self.pos += 1 + sign;
+                while self.peek(0).is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+}
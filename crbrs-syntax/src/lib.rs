@@ -0,0 +1,27 @@
+// FILE: crbrs-syntax/src/lib.rs
+
+//! Pure-Rust CRBasic front end: tokenizer, parser, linter, formatter, refactorings and
+//! instruction database.
+//!
+//! Nothing in this crate performs I/O, which keeps it usable from `crbrs-lib` as well as from
+//! `wasm32-unknown-unknown` builds (`crbrs-wasm`).
+
+pub mod ast;
+pub mod calibration;
+pub mod case;
+pub mod directive;
+pub mod format;
+pub mod index;
+pub mod instructions;
+pub mod lexer;
+pub mod lint;
+pub mod parser;
+pub mod refactor;
+pub mod sensor;
+pub mod spelling;
+pub mod tables;
+pub mod units;
+pub mod version;
+pub mod wiring;
+
+pub use parser::parse;
@@ -0,0 +1,543 @@
+// FILE: crbrs-syntax/src/lint.rs
+
+//! Native CRBasic linter.
+//!
+//! Rules run on the parsed [`Program`] and never invoke the vendor compiler, so they are cheap
+//! enough to run on every keystroke. Parse errors are reported through the `syntax` rule.
+
+use crate::ast::*;
+use crate::directive;
+use crate::instructions::{self, Category, Context};
+use crate::lexer::Span;
+use crate::parser::parse;
+use crate::units::{self, UnitSource};
+use crate::version;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintDiagnostic {
+    /// Rule identifier, e.g. `duplicate-declaration`.
+    pub rule: &'static str,
+    /// The rule's stable code, e.g. `L002`.
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    /// Other places that explain the diagnostic: its root cause or an earlier declaration.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub related: Vec<Related>,
+}
+
+/// A note pointing at another place in the program, like rustc's "note:" lines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Related {
+    pub span: Span,
+    pub message: String,
+}
+
+impl LintDiagnostic {
+    fn note(mut self, span: Span, message: String) -> Self {
+        self.related.push(Related { span, message });
+        self
+    }
+}
+
+/// Static description of a lint rule.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Rule {
+    pub id: &'static str,
+    /// Stable code, e.g. `L002`, for searching docs and `crbrs explain`.
+    pub code: &'static str,
+    pub default_severity: Severity,
+    pub description: &'static str,
+}
+
+pub static RULES: &[Rule] = &[
+    Rule { id: "syntax", code: "L001", default_severity: Severity::Error, description: "The program could not be parsed." },
+    Rule { id: "duplicate-declaration", code: "L002", default_severity: Severity::Error, description: "A name is declared more than once in the same scope." },
+    Rule { id: "unknown-table", code: "L003", default_severity: Severity::Error, description: "CallTable refers to a table that is not declared." },
+    Rule { id: "table-never-called", code: "L004", default_severity: Severity::Warning, description: "A DataTable is declared but never stored with CallTable." },
+    Rule { id: "instruction-context", code: "L005", default_severity: Severity::Error, description: "An instruction is used where it is not allowed, e.g. a measurement outside a Scan or Sample outside a DataTable." },
+    Rule { id: "multiple-beginprog", code: "L006", default_severity: Severity::Error, description: "The program has more than one BeginProg." },
+    Rule { id: "missing-beginprog", code: "L007", default_severity: Severity::Warning, description: "The program has no BeginProg, so nothing runs." },
+    Rule { id: "scan-placement", code: "L008", default_severity: Severity::Error, description: "A Scan or SlowSequence is outside BeginProg, nested in another Scan, or a SlowSequence has no Scan." },
+    Rule { id: "unit-mismatch", code: "L009", default_severity: Severity::Warning, description: "Values with different units are added, subtracted, compared or assigned to each other." },
+    Rule { id: "unit-conflict", code: "L010", default_severity: Severity::Warning, description: "A variable is annotated with two different units." },
+    Rule { id: "unit-not-in-table", code: "L011", default_severity: Severity::Hint, description: "A stored variable has its unit only in a comment, so the unit is missing from the table header." },
+    Rule { id: "declaration-placement", code: "L012", default_severity: Severity::Error, description: "A DataTable, Sub, Function or Public declaration appears inside a block instead of the declarations section." },
+    Rule { id: "version-header", code: "L013", default_severity: Severity::Warning, description: "The program's version header is malformed or out of sync with its changelog or ProgramVersion constant." },
+    Rule { id: "toolchain-directive", code: "L014", default_severity: Severity::Warning, description: "A `'crbrs:` toolchain directive has an unknown key or is malformed." },
+    Rule { id: "spelling", code: "L015", default_severity: Severity::Warning, description: "A comment or string literal has a common misspelling. Off unless a level is configured for it." },
+];
+
+/// Looks up a rule by its identifier.
+pub fn rule(id: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|r| r.id == id)
+}
+
+/// Looks up a rule by its code (e.g. `L002`), ignoring case.
+pub fn rule_by_code(code: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|r| r.code.eq_ignore_ascii_case(code))
+}
+
+/// Configured level of a rule: its diagnostics' severity, or `off` to drop them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Off,
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+impl std::str::FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Level::Off),
+            "error" => Ok(Level::Error),
+            "warning" => Ok(Level::Warning),
+            "info" => Ok(Level::Info),
+            "hint" => Ok(Level::Hint),
+            _ => Err(format!("'{}' is not a lint level (off, error, warning, info or hint)", s)),
+        }
+    }
+}
+
+/// Applies configured rule levels (keyed by rule ID) to `diagnostics`; rules without a level
+/// keep their default severity.
+pub fn apply_levels(diagnostics: Vec<LintDiagnostic>, levels: &HashMap<String, Level>) -> Vec<LintDiagnostic> {
+    diagnostics
+        .into_iter()
+        .filter_map(|mut d| {
+            d.severity = match levels.get(d.rule) {
+                None => d.severity,
+                Some(Level::Off) => return None,
+                Some(Level::Error) => Severity::Error,
+                Some(Level::Warning) => Severity::Warning,
+                Some(Level::Info) => Severity::Info,
+                Some(Level::Hint) => Severity::Hint,
+            };
+            Some(d)
+        })
+        .collect()
+}
+
+/// Parses and lints `source`.
+pub fn lint_source(source: &str) -> Vec<LintDiagnostic> {
+    lint(&parse(source))
+}
+
+/// Runs every lint rule on `program`, returning diagnostics sorted by position.
+pub fn lint(program: &Program) -> Vec<LintDiagnostic> {
+    let mut out = Vec::new();
+    for err in &program.errors {
+        out.push(diag("syntax", err.message.clone(), err.span));
+    }
+    duplicate_declarations(program, &mut out);
+    table_calls(program, &mut out);
+    structure(program, &mut out);
+    unit_checks(program, &mut out);
+    if let Some(header) = version::header(program) {
+        for (span, message) in header.problems {
+            out.push(diag("version-header", message, span));
+        }
+    }
+    for (span, message) in directive::directives(program).problems {
+        out.push(diag("toolchain-directive", message, span));
+    }
+    out.sort_by_key(|d| (d.span.start, d.rule));
+    out
+}
+
+/// Why `name` may be reported as undeclared (by the compiler) although the program declares
+/// it: the declaration's line could not be parsed, e.g. `Public BattV, 2ndTemp`. Lines are
+/// matched as text, since a declaration that failed to parse may be missing from the tree.
+pub fn undeclared_cause(source: &str, name: &str) -> Option<Related> {
+    const DECLARING: [&str; 4] = ["public", "dim", "const", "alias"];
+    let lines: Vec<&str> = source.lines().collect();
+    let declares = |line: &str| {
+        let code = line.split('\'').next().unwrap_or_default();
+        let mut words = code.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).filter(|w| !w.is_empty());
+        words.next().is_some_and(|first| DECLARING.iter().any(|k| first.eq_ignore_ascii_case(k)))
+            && words.any(|word| word.eq_ignore_ascii_case(name))
+    };
+    parse(source).errors.into_iter().find(|e| lines.get(e.span.line as usize).is_some_and(|line| declares(line))).map(|e| Related {
+        span: e.span,
+        message: format!("'{}' is declared on line {}, which could not be parsed: {}", name, e.span.line + 1, e.message),
+    })
+}
+
+fn diag(rule_id: &'static str, message: String, span: Span) -> LintDiagnostic {
+    let (severity, code) = rule(rule_id).map_or((Severity::Warning, ""), |r| (r.default_severity, r.code));
+    LintDiagnostic { rule: rule_id, code, severity, message, span, related: Vec::new() }
+}
+
+/// Names declared directly in `body` (not in nested routines).
+pub(crate) fn declared_names(body: &[Stmt]) -> Vec<&Name> {
+    let mut names = Vec::new();
+    for stmt in body {
+        match &stmt.kind {
+            StmtKind::Declare(decl) => names.extend(decl.vars.iter().map(|v| &v.name)),
+            StmtKind::Const(consts) => names.extend(consts.iter().map(|c| &c.name)),
+            StmtKind::Alias { name, .. } => names.push(name),
+            StmtKind::DataTable(table) if !table.name.text.is_empty() => names.push(&table.name),
+            StmtKind::Routine(routine) if !routine.name.text.is_empty() => names.push(&routine.name),
+            _ => {}
+        }
+    }
+    names
+}
+
+fn duplicate_declarations(program: &Program, out: &mut Vec<LintDiagnostic>) {
+    let mut check_scope = |names: Vec<&Name>| {
+        let mut seen: HashMap<String, &Name> = HashMap::new();
+        for name in names {
+            let key = name.text.to_ascii_lowercase();
+            if let Some(first) = seen.get(&key) {
+                out.push(
+                    diag("duplicate-declaration", format!("'{}' is already declared on line {}", name.text, first.span.line + 1), name.span)
+                        .note(first.span, format!("'{}' is first declared here", first.text)),
+                );
+            } else {
+                seen.insert(key, name);
+            }
+        }
+    };
+
+    check_scope(declared_names(&program.body));
+    walk_stmts(&program.body, &mut |stmt| {
+        if let StmtKind::Routine(routine) = &stmt.kind {
+            let mut names: Vec<&Name> = routine.params.iter().map(|p| &p.name).collect();
+            names.extend(declared_names(&routine.body));
+            check_scope(names);
+        }
+    });
+}
+
+fn table_calls(program: &Program, out: &mut Vec<LintDiagnostic>) {
+    let mut tables: Vec<&Name> = Vec::new();
+    walk_stmts(&program.body, &mut |stmt| {
+        if let StmtKind::DataTable(table) = &stmt.kind {
+            if !table.name.text.is_empty() {
+                tables.push(&table.name);
+            }
+        }
+    });
+    let declared: HashSet<String> = tables.iter().map(|n| n.text.to_ascii_lowercase()).collect();
+    // Tables whose header could not be parsed: calls to unknown tables are probably theirs.
+    let mut unnamed: Vec<Span> = Vec::new();
+    walk_stmts(&program.body, &mut |stmt| {
+        if let StmtKind::DataTable(table) = &stmt.kind {
+            if table.name.text.is_empty() {
+                unnamed.push(stmt.span);
+            }
+        }
+    });
+
+    let mut called = HashSet::new();
+    walk_stmts(&program.body, &mut |stmt| {
+        if let StmtKind::Call(call) = &stmt.kind {
+            if call.name.is("CallTable") {
+                if let Some(table) = call.args.first().and_then(Expr::ref_name) {
+                    let key = table.to_ascii_lowercase();
+                    if !declared.contains(&key) {
+                        let mut d = diag("unknown-table", format!("Table '{}' is not declared", table), call.args[0].span);
+                        for span in &unnamed {
+                            d = d.note(*span, format!("this DataTable's name could not be parsed; it may be meant to declare '{}'", table));
+                        }
+                        out.push(d);
+                    }
+                    called.insert(key);
+                }
+            }
+        }
+    });
+
+    for table in tables {
+        if !called.contains(&table.text.to_ascii_lowercase()) {
+            out.push(diag(
+                "table-never-called",
+                format!("Table '{}' is never stored; add 'CallTable {}' inside a Scan", table.text, table.text),
+                table.span,
+            ));
+        }
+    }
+}
+
+/// Where a statement sits, for the structural rules.
+#[derive(Debug, Clone, Copy, Default)]
+struct Place {
+    program: bool,
+    scan: bool,
+    table: bool,
+    routine: bool,
+    /// Inside a For/Do/While loop that is not itself inside a Scan.
+    loop_outside_scan: bool,
+}
+
+impl Place {
+    fn allows(&self, context: Context) -> bool {
+        match context {
+            Context::Anywhere => true,
+            // Subs may be called from a scan, so their bodies get the benefit of the doubt.
+            Context::Scan => self.scan || self.routine,
+            Context::DataTable => self.table,
+            Context::Declarations => !self.program && !self.routine && !self.table,
+            Context::Program => self.program,
+        }
+    }
+}
+
+fn structure(program: &Program, out: &mut Vec<LintDiagnostic>) {
+    let progs: Vec<&Stmt> = program.body.iter().filter(|s| matches!(s.kind, StmtKind::BeginProg(_))).collect();
+    for extra in progs.iter().skip(1) {
+        out.push(
+            diag("multiple-beginprog", format!("Only one BeginProg is allowed; the first is on line {}", progs[0].span.line + 1), extra.span)
+                .note(progs[0].span, "the first BeginProg is here".to_string()),
+        );
+    }
+    // A file without BeginProg is usually an Include file; only complain if it has tables.
+    let has_tables = program.body.iter().any(|s| matches!(s.kind, StmtKind::DataTable(_)));
+    if progs.is_empty() && has_tables {
+        if let Some(first) = program.body.first() {
+            out.push(diag("missing-beginprog", "The program has DataTables but no BeginProg".to_string(), first.span));
+        }
+    }
+    check_placement(&program.body, Place::default(), out);
+}
+
+fn check_placement(body: &[Stmt], place: Place, out: &mut Vec<LintDiagnostic>) {
+    for (i, stmt) in body.iter().enumerate() {
+        let mut inner = place;
+        match &stmt.kind {
+            StmtKind::BeginProg(_) => inner.program = true,
+            StmtKind::Scan(_) => {
+                if place.scan {
+                    out.push(diag("scan-placement", "Scan cannot be nested inside another Scan; use SubScan".to_string(), stmt.span));
+                } else if !place.program {
+                    out.push(diag("scan-placement", "Scan must be inside BeginProg".to_string(), stmt.span));
+                }
+                inner.scan = true;
+                inner.loop_outside_scan = false;
+            }
+            StmtKind::SlowSequence => {
+                if !place.program || place.scan {
+                    out.push(diag("scan-placement", "SlowSequence must be directly inside BeginProg".to_string(), stmt.span));
+                } else if !body[i + 1..].iter().any(|s| matches!(s.kind, StmtKind::Scan(_))) {
+                    out.push(diag("scan-placement", "SlowSequence is not followed by a Scan".to_string(), stmt.span));
+                }
+            }
+            StmtKind::DataTable(_) | StmtKind::Routine(_) => {
+                if place.program || place.routine || place.table {
+                    let what = if matches!(stmt.kind, StmtKind::DataTable(_)) { "DataTable" } else { "Sub/Function" };
+                    out.push(diag("declaration-placement", format!("{} must be declared before BeginProg, outside other blocks", what), stmt.span));
+                }
+                inner.table = matches!(stmt.kind, StmtKind::DataTable(_));
+                inner.routine = matches!(stmt.kind, StmtKind::Routine(_));
+            }
+            StmtKind::Declare(Declaration { scope: DeclScope::Public, .. }) if place.program || place.routine => {
+                out.push(diag("declaration-placement", "Public variables must be declared before BeginProg".to_string(), stmt.span));
+            }
+            StmtKind::For(_) | StmtKind::Do(_) | StmtKind::While(_) if !place.scan => inner.loop_outside_scan = true,
+            StmtKind::Exit(keyword) if matches!(keyword.as_str(), "exitscan" | "continuescan") && !place.allows(Context::Scan) => {
+                out.push(diag("instruction-context", "ExitScan/ContinueScan can only be used inside a Scan".to_string(), stmt.span));
+            }
+            StmtKind::Call(call) => check_instruction(&call.name, place, out),
+            StmtKind::Block(block) => {
+                check_instruction(&block.keyword, place, out);
+                inner.scan |= block.keyword.is("SubScan");
+            }
+            _ => {}
+        }
+        for child in stmt.children() {
+            check_placement(child, inner, out);
+        }
+    }
+}
+
+fn check_instruction(name: &Name, place: Place, out: &mut Vec<LintDiagnostic>) {
+    let Some(ins) = instructions::lookup(&name.text) else {
+        return;
+    };
+    if place.allows(ins.context) {
+        return;
+    }
+    let message = match ins.context {
+        Context::Scan if place.loop_outside_scan && ins.category == Category::Measurement => format!(
+            "Measurement '{}' is in a loop outside any Scan; measurements only run inside Scan ... NextScan",
+            ins.name
+        ),
+        Context::Scan => format!("'{}' can only be used inside a Scan (or a Sub called from one)", ins.name),
+        Context::DataTable => format!("'{}' can only be used between DataTable and EndTable", ins.name),
+        Context::Declarations => format!("'{}' belongs in the declarations section before BeginProg", ins.name),
+        Context::Program => format!("'{}' can only be used inside BeginProg", ins.name),
+        Context::Anywhere => return,
+    };
+    out.push(diag("instruction-context", message, name.span));
+}
+
+fn unit_checks(program: &Program, out: &mut Vec<LintDiagnostic>) {
+    let annotations = units::annotations(program);
+    if annotations.is_empty() {
+        return;
+    }
+
+    let mut first_seen: HashMap<String, &units::UnitAnnotation> = HashMap::new();
+    for a in &annotations {
+        match first_seen.get(&a.name.to_ascii_lowercase()) {
+            Some(prev) if !units::same_unit(&prev.unit, &a.unit) => out.push(
+                diag(
+                    "unit-conflict",
+                    format!("'{}' is annotated as '{}' here but as '{}' on line {}", a.name, a.unit, prev.unit, prev.span.line + 1),
+                    a.span,
+                )
+                .note(prev.span, format!("'{}' is first annotated as '{}' here", a.name, prev.unit)),
+            ),
+            Some(_) => {}
+            None => {
+                first_seen.insert(a.name.to_ascii_lowercase(), a);
+            }
+        }
+    }
+
+    let map = units::unit_map(&annotations);
+    let mut mismatches = Vec::new();
+    walk_stmts(&program.body, &mut |stmt| match &stmt.kind {
+        StmtKind::Assign { target, value } => {
+            let value_unit = units::infer(value, &map, &mut mismatches);
+            let target_unit = target.ref_name().and_then(|n| map.get(&n.to_ascii_lowercase()));
+            if let (Some(t), Some(v)) = (target_unit, value_unit) {
+                if !units::same_unit(t, &v) {
+                    mismatches.push(units::Mismatch { op: "=".to_string(), left: t.to_string(), right: v, span: stmt.span });
+                }
+            }
+        }
+        _ => {
+            for expr in stmt.exprs() {
+                units::infer(expr, &map, &mut mismatches);
+            }
+        }
+    });
+    for m in mismatches {
+        let verb = match m.op.as_str() {
+            "+" => "Adding",
+            "-" => "Subtracting",
+            "=" => "Assigning",
+            _ => "Comparing",
+        };
+        out.push(diag("unit-mismatch", format!("{} values in '{}' and '{}'", verb, m.left, m.right), m.span));
+    }
+
+    // Output instructions whose source only has a comment unit.
+    let instruction_units: HashSet<String> =
+        annotations.iter().filter(|a| a.source == UnitSource::Instruction).map(|a| a.name.to_ascii_lowercase()).collect();
+    walk_stmts(&program.body, &mut |stmt| {
+        let StmtKind::DataTable(table) = &stmt.kind else { return };
+        walk_stmts(&table.body, &mut |inner| {
+            let StmtKind::Call(call) = &inner.kind else { return };
+            let Some(ins) = instructions::lookup(&call.name.text).filter(|i| i.category == Category::OutputProcessing) else {
+                return;
+            };
+            let Some(source) = ins.params.iter().position(|p| *p == "Source").and_then(|i| call.args.get(i)) else {
+                return;
+            };
+            let Some(name) = source.ref_name() else { return };
+            let key = name.to_ascii_lowercase();
+            if map.contains_key(&key) && !instruction_units.contains(&key) {
+                out.push(diag(
+                    "unit-not-in-table",
+                    format!("Add 'Units {} = {}' so the unit appears in the header of table '{}'", name, map[&key], table.name.text),
+                    source.span,
+                ));
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(source: &str) -> Vec<&'static str> {
+        lint_source(source).into_iter().map(|d| d.rule).collect()
+    }
+
+    #[test]
+    fn clean_program_has_no_diagnostics() {
+        let src = "Public PTemp\nDataTable(Test,1,-1)\n  Sample(1,PTemp,FP2)\nEndTable\nBeginProg\n  Scan(1,Sec,0,0)\n    PanelTemp(PTemp,60)\n    CallTable Test\n  NextScan\nEndProg\n";
+        assert!(lint_source(src).is_empty(), "{:?}", lint_source(src));
+    }
+
+    #[test]
+    fn reports_duplicates_and_unknown_tables() {
+        let src = "Public A\nDim a\nBeginProg\n  Scan(1,Sec,0,0)\n    CallTable Missing\n  NextScan\nEndProg\n";
+        assert_eq!(rules(src), vec!["duplicate-declaration", "unknown-table"]);
+    }
+
+    #[test]
+    fn reports_structural_mistakes() {
+        let src = "Public V\nSample(1,V,FP2)\nBeginProg\n  Do\n    VoltSe(V,1,mV5000,1,0,0,60,1,0)\n  Loop\n  SlowSequence\nEndProg\nBeginProg\nEndProg\n";
+        assert_eq!(rules(src), vec!["instruction-context", "instruction-context", "scan-placement", "multiple-beginprog"]);
+        assert!(lint_source(src)[1].message.contains("loop outside any Scan"));
+    }
+
+    #[test]
+    fn reports_unit_mistakes() {
+        let src = "Public T, P 'units: degC\nPublic V\nUnits V = mV\nUnits P = K\nDataTable(Out,1,-1)\n  Sample(1,T,FP2)\nEndTable\nBeginProg\n  Scan(1,Sec,0,0)\n    T = V\n    CallTable Out\n  NextScan\nEndProg\n";
+        assert_eq!(rules(src), vec!["unit-conflict", "unit-not-in-table", "unit-mismatch"]);
+    }
+
+    #[test]
+    fn configured_levels_override_or_drop_rules() {
+        let src = "Public A\nDim a\nDataTable(Unused,1,-1)\nEndTable\nBeginProg\nEndProg\n";
+        let levels = HashMap::from([
+            ("duplicate-declaration".to_string(), Level::Hint),
+            ("table-never-called".to_string(), Level::Off),
+        ]);
+        let configured = apply_levels(lint_source(src), &levels);
+        assert_eq!(configured.iter().map(|d| (d.rule, d.severity)).collect::<Vec<_>>(), vec![("duplicate-declaration", Severity::Hint)]);
+        assert_eq!("Warning".parse::<Level>(), Ok(Level::Warning));
+    }
+
+    #[test]
+    fn rule_codes_are_unique_and_carried_by_diagnostics() {
+        for (i, rule) in RULES.iter().enumerate() {
+            assert_eq!(rule.code, format!("L{:03}", i + 1), "{} is out of sequence", rule.id);
+        }
+        assert_eq!(rule_by_code("l002").map(|r| r.code), Some("L002"));
+        let src = "Public A\nDim a\nBeginProg\nEndProg\n";
+        let duplicate = lint_source(src).into_iter().find(|d| d.rule == "duplicate-declaration").unwrap();
+        assert_eq!(Some(duplicate.code), rule("duplicate-declaration").map(|r| r.code));
+    }
+
+    #[test]
+    fn cascading_diagnostics_point_at_their_cause() {
+        let src = "Public A\nDim a\nDataTable(,True,-1)\nEndTable\nBeginProg\n  Scan(1,Sec,0,0)\n    CallTable Hourly\n  NextScan\nEndProg\n";
+        let diagnostics = lint_source(src);
+        let duplicate = diagnostics.iter().find(|d| d.rule == "duplicate-declaration").unwrap();
+        assert_eq!(duplicate.related.iter().map(|r| (r.span.line, r.message.as_str())).collect::<Vec<_>>(), vec![(0, "'A' is first declared here")]);
+        let unknown = diagnostics.iter().find(|d| d.rule == "unknown-table").unwrap();
+        assert_eq!(unknown.related.len(), 1);
+        assert_eq!(unknown.related[0].span.line, 2);
+
+        let src = "Public BattV, 2ndTemp 'two sensors\nBeginProg\nEndProg\n";
+        let cause = undeclared_cause(src, "battv").unwrap();
+        assert_eq!(cause.span.line, 0);
+        assert!(cause.message.starts_with("'battv' is declared on line 1, which could not be parsed"), "{}", cause.message);
+        assert_eq!(undeclared_cause(src, "sensors"), None, "comments do not declare anything");
+        assert_eq!(undeclared_cause("Public BattV\nBeginProg\nEndProg\n", "BattV"), None);
+    }
+}
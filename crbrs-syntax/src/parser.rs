@@ -0,0 +1,1141 @@
+// FILE: crbrs-syntax/src/parser.rs
+
+//! Error-tolerant parser for CRBasic.
+//!
+//! Parsing works on logical lines: the token stream is split at newlines (and `:` statement
+//! separators), every line becomes one statement, and block statements own the lines up to
+//! their closing keyword. Errors are collected in [`Program::errors`] and never abort parsing.
+//!
+//! A line ending in ` _` continues on the next one. Of each `#If ... #ElseIf ... #Else ...
+//! #EndIf` group only the first branch is parsed, as if its condition held: the branches are
+//! alternatives, so parsing all of them would see blocks opened twice or constants declared
+//! twice. The directive lines themselves are kept as [`StmtKind::Directive`].
+
+use crate::ast::*;
+use crate::lexer::{tokenize, Span, Token, TokenKind};
+
+/// Block closers the parser knows about. A closer that doesn't match any open block is
+/// reported as stray instead of terminating unrelated blocks.
+const GENERIC_BLOCKS: &[(&str, &str)] = &[
+    ("subscan", "nextsubscan"),
+    ("consttable", "endconsttable"),
+    ("dialsequence", "enddialsequence"),
+    ("shutdownbegin", "shutdownend"),
+    ("webpagebegin", "webpageend"),
+    ("displaymenu", "endmenu"),
+    ("submenu", "endsubmenu"),
+    ("modemhangup", "endmodemhangup"),
+];
+
+const EXIT_KEYWORDS: &[&str] = &[
+    "exitscan", "exitfor", "exitdo", "exitsub", "exitfunction", "exitwhile", "continuescan", "return",
+];
+
+/// Words that can never start an expression.
+const RESERVED: &[&str] = &[
+    "then", "to", "step", "else", "and", "or", "not", "xor", "mod", "intdv", "imp", "eqv", "as",
+];
+
+/// Parses `source` into a [`Program`]. Never fails; check `errors` on the result.
+pub fn parse(source: &str) -> Program {
+    let mut comments = Vec::new();
+    let mut lines: Vec<Vec<Token>> = Vec::new();
+    let mut current: Vec<Token> = Vec::new();
+    let mut depth = 0i32;
+
+    for token in tokenize(source) {
+        match token.kind {
+            TokenKind::Comment => comments.push(Comment { text: token.text, span: token.span }),
+            TokenKind::Newline if current.last().is_some_and(is_continuation) => {
+                current.pop();
+            }
+            TokenKind::Newline => {
+                depth = 0;
+                if !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+            }
+            TokenKind::Colon if depth == 0 => {
+                if !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+            }
+            _ => {
+                match token.kind {
+                    TokenKind::LParen => depth += 1,
+                    TokenKind::RParen => depth -= 1,
+                    _ => {}
+                }
+                current.push(token);
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    let lines = first_branches(lines);
+
+    let mut parser = Parser { src: source, lines, idx: 0, errors: Vec::new(), open: Vec::new() };
+    let body = parser.block(&[]).0;
+    Program { body, comments, errors: parser.errors }
+}
+
+/// The ` _` line continuation marker.
+pub(crate) fn is_continuation(token: &Token) -> bool {
+    token.kind == TokenKind::Ident && token.text == "_"
+}
+
+/// Drops the lines of `#ElseIf` and `#Else` branches, keeping the directive lines.
+fn first_branches(lines: Vec<Vec<Token>>) -> Vec<Vec<Token>> {
+    // Per open `#If`: whether a later branch has started.
+    let mut skipping: Vec<bool> = Vec::new();
+    let mut kept = Vec::with_capacity(lines.len());
+    for line in lines {
+        match line_head(&line).as_str() {
+            "#if" => skipping.push(false),
+            "#elseif" | "#else" => {
+                if let Some(top) = skipping.last_mut() {
+                    *top = true;
+                }
+            }
+            "#endif" => {
+                skipping.pop();
+            }
+            head if !head.starts_with('#') && skipping.contains(&true) => continue,
+            _ => {}
+        }
+        kept.push(line);
+    }
+    kept
+}
+
+/// Normalized, lower-case leading keyword of a line, joining two-word forms
+/// (`End If` → `endif`, `Else If` → `elseif`, `Exit For` → `exitfor`, `Next Scan` → `nextscan`).
+pub(crate) fn line_head(tokens: &[Token]) -> String {
+    let Some(first) = tokens.first() else {
+        return String::new();
+    };
+    if !matches!(first.kind, TokenKind::Ident | TokenKind::Directive) {
+        return String::new();
+    }
+    let word = first.text.to_ascii_lowercase();
+    if let Some(second) = tokens.get(1).filter(|t| t.kind == TokenKind::Ident) {
+        let second_word = second.text.to_ascii_lowercase();
+        let joined = matches!(
+            (word.as_str(), second_word.as_str()),
+            ("end" | "exit", _)
+                | ("else" | "#else" | "#end", "if")
+                | ("next", "scan" | "subscan")
+                | ("select", "case")
+        );
+        if joined {
+            return word + &second_word;
+        }
+    }
+    word
+}
+
+/// Number of tokens making up the head keyword returned by [`line_head`].
+fn head_len(tokens: &[Token]) -> usize {
+    let head = line_head(tokens);
+    if tokens.first().is_some_and(|t| !t.text.eq_ignore_ascii_case(&head)) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Canonical spelling of a normalized closer keyword, for messages.
+fn display_closer(closer: &str) -> &str {
+    match closer {
+        "endprog" => "EndProg",
+        "nextscan" => "NextScan",
+        "nextsubscan" => "NextSubScan",
+        "endtable" => "EndTable",
+        "endsub" => "EndSub",
+        "endfunction" => "EndFunction",
+        "endif" => "EndIf",
+        "next" => "Next",
+        "loop" => "Loop",
+        "wend" => "Wend",
+        "endselect" => "EndSelect",
+        "endconsttable" => "EndConstTable",
+        "enddialsequence" => "EndDialSequence",
+        "shutdownend" => "ShutDownEnd",
+        "webpageend" => "WebPageEnd",
+        "endmenu" => "EndMenu",
+        "endsubmenu" => "EndSubMenu",
+        "endmodemhangup" => "EndModemHangup",
+        other => other,
+    }
+}
+
+fn line_span(tokens: &[Token]) -> Span {
+    match (tokens.first(), tokens.last()) {
+        (Some(first), Some(last)) => Span { end: last.span.end, ..first.span },
+        _ => Span::default(),
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    lines: Vec<Vec<Token>>,
+    idx: usize,
+    errors: Vec<ParseError>,
+    /// Terminator sets of the currently open blocks, innermost last.
+    open: Vec<&'static [&'static str]>,
+}
+
+/// Token cursor over a single line.
+struct Cursor {
+    toks: Vec<Token>,
+    pos: usize,
+}
+
+impl Cursor {
+    fn new(toks: Vec<Token>) -> Self {
+        Cursor { toks, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.toks.get(self.pos)
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.toks.len()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.toks.get(self.pos).cloned();
+        if t.is_some() {
+            self.pos += 1;
+        }
+        t
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        if self.peek().is_some_and(|t| t.is_word(word)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_kind(&mut self, kind: TokenKind) -> bool {
+        if self.peek().is_some_and(|t| t.kind == kind) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        if self.peek().is_some_and(|t| t.kind == TokenKind::Operator && t.text == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Span to use for errors at the current position.
+    fn here(&self) -> Span {
+        self.peek()
+            .or_else(|| self.toks.last())
+            .map(|t| t.span)
+            .unwrap_or_default()
+    }
+
+    fn last_end(&self) -> usize {
+        self.pos.checked_sub(1).and_then(|i| self.toks.get(i)).map_or(0, |t| t.span.end)
+    }
+
+    /// Splits off the remaining tokens.
+    fn rest(&mut self) -> Vec<Token> {
+        let rest = self.toks[self.pos..].to_vec();
+        self.pos = self.toks.len();
+        rest
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn error(&mut self, message: impl Into<String>, span: Span) {
+        self.errors.push(ParseError { message: message.into(), span });
+    }
+
+    fn is_open_closer(&self, head: &str) -> bool {
+        self.open.iter().any(|set| set.contains(&head))
+    }
+
+    /// Parses statements until one of `terminators` (returned, not consumed) or until a closer
+    /// belonging to an enclosing block / end of input (returns `None`).
+    fn block(&mut self, terminators: &'static [&'static str]) -> (Vec<Stmt>, Option<String>) {
+        self.open.push(terminators);
+        let mut body = Vec::new();
+        let mut found = None;
+        while self.idx < self.lines.len() {
+            let head = line_head(&self.lines[self.idx]);
+            if terminators.contains(&head.as_str()) {
+                found = Some(head);
+                break;
+            }
+            if self.is_open_closer(&head) {
+                break;
+            }
+            if let Some(stmt) = self.statement() {
+                body.push(stmt);
+            }
+        }
+        self.open.pop();
+        (body, found)
+    }
+
+    /// Consumes the closing line of a block if present, recording an error otherwise.
+    fn close(&mut self, found: Option<String>, closer: &str, opener: &str, open_span: Span) -> Option<Span> {
+        match found {
+            Some(head) if head == closer => {
+                let tokens = &self.lines[self.idx];
+                let span = line_span(tokens);
+                let extra = head_len(tokens);
+                let leftover = tokens.len() > extra;
+                self.idx += 1;
+                // `Next i` is fine; anything else after a closer is unusual but harmless.
+                if leftover && closer != "next" && closer != "loop" {
+                    self.error(format!("Unexpected tokens after '{}'", closer), span);
+                }
+                Some(span)
+            }
+            _ => {
+                self.error(format!("'{}' is missing its closing '{}'", opener, display_closer(closer)), open_span);
+                None
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Option<Stmt> {
+        let tokens = self.lines[self.idx].clone();
+        self.idx += 1;
+        let span = line_span(&tokens);
+        let head = line_head(&tokens);
+
+        let kind = match head.as_str() {
+            "public" | "dim" => self.declaration(tokens, &head),
+            "const" => self.constant(tokens),
+            "alias" => self.alias(tokens),
+            "units" => self.units(tokens),
+            "datatable" => self.data_table(tokens, span),
+            "sub" | "function" => self.routine(tokens, &head, span),
+            "beginprog" => {
+                let (body, found) = self.block(&["endprog"]);
+                let end = self.close(found, "endprog", "BeginProg", span);
+                StmtKind::BeginProg(Block { body, end })
+            }
+            "scan" => {
+                let mut c = Cursor::new(tokens);
+                c.next();
+                let args = self.arg_list(&mut c);
+                self.expect_end(&c);
+                let (body, found) = self.block(&["nextscan"]);
+                let end = self.close(found, "nextscan", "Scan", span);
+                StmtKind::Scan(Scan { args, body, end })
+            }
+            "slowsequence" => StmtKind::SlowSequence,
+            "endsequence" => StmtKind::EndSequence,
+            "if" => self.if_stmt(tokens, span),
+            "for" => self.for_loop(tokens, span),
+            "do" => self.do_loop(tokens, span),
+            "while" => self.while_loop(tokens, span),
+            "selectcase" => self.select(tokens, span),
+            h if h.starts_with('#') => StmtKind::Directive(self.src[span.start..span.end].to_string()),
+            h if EXIT_KEYWORDS.contains(&h) => StmtKind::Exit(h.to_string()),
+            h => {
+                if let Some(&(_, closer)) = GENERIC_BLOCKS.iter().find(|(open, _)| *open == h) {
+                    self.generic_block(tokens, closer, span)
+                } else if self.is_closer_anywhere(h) {
+                    self.error(format!("'{}' without a matching opening statement", tokens[0].text), span);
+                    return None;
+                } else {
+                    self.simple(tokens)
+                }
+            }
+        };
+        Some(Stmt { kind, span })
+    }
+
+    fn is_closer_anywhere(&self, head: &str) -> bool {
+        matches!(
+            head,
+            "endprog" | "nextscan" | "endtable" | "endsub" | "endfunction" | "endif" | "else" | "elseif"
+                | "next" | "loop" | "wend" | "endselect" | "case"
+        ) || GENERIC_BLOCKS.iter().any(|(_, close)| *close == head)
+    }
+
+    fn expect_end(&mut self, c: &Cursor) {
+        if let Some(t) = c.peek() {
+            let span = t.span;
+            self.error(format!("Unexpected '{}'", t.text), span);
+        }
+    }
+
+    // --- Declarations ---
+
+    fn declaration(&mut self, tokens: Vec<Token>, head: &str) -> StmtKind {
+        let scope = if head == "public" { DeclScope::Public } else { DeclScope::Dim };
+        let mut c = Cursor::new(tokens);
+        c.next();
+        let mut vars = Vec::new();
+        while let Some(v) = self.var_decl(&mut c) {
+            vars.push(v);
+            if !c.eat_kind(TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect_end(&c);
+        StmtKind::Declare(Declaration { scope, vars })
+    }
+
+    fn name(&mut self, c: &mut Cursor) -> Option<Name> {
+        match c.peek() {
+            Some(t) if t.kind == TokenKind::Ident && !RESERVED.contains(&t.text.to_ascii_lowercase().as_str()) => {
+                let t = c.next()?;
+                Some(Name { text: t.text, span: t.span })
+            }
+            _ => {
+                let span = c.here();
+                self.error("Expected a name", span);
+                None
+            }
+        }
+    }
+
+    fn var_decl(&mut self, c: &mut Cursor) -> Option<VarDecl> {
+        c.eat_word("ByRef");
+        c.eat_word("ByVal");
+        let name = self.name(c)?;
+        let mut dims = Vec::new();
+        if c.eat_kind(TokenKind::LParen) && !c.eat_kind(TokenKind::RParen) {
+            dims = self.comma_exprs(c);
+            self.expect_rparen(c);
+        }
+        let ty = self.type_spec(c);
+        Some(VarDecl { name, dims, ty })
+    }
+
+    fn type_spec(&mut self, c: &mut Cursor) -> Option<TypeSpec> {
+        if !c.eat_word("As") {
+            return None;
+        }
+        let name = self.name(c)?;
+        let size = if c.eat_op("*") { Some(self.expr(c)) } else { None };
+        Some(TypeSpec { name, size })
+    }
+
+    fn constant(&mut self, tokens: Vec<Token>) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        c.next();
+        let mut consts = Vec::new();
+        while let Some(name) = self.name(&mut c) {
+            let ty = self.type_spec(&mut c);
+            if !c.eat_op("=") {
+                let span = c.here();
+                self.error(format!("Expected '=' after constant '{}'", name.text), span);
+                break;
+            }
+            let value = self.expr(&mut c);
+            consts.push(ConstDecl { name, ty, value });
+            if !c.eat_kind(TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect_end(&c);
+        StmtKind::Const(consts)
+    }
+
+    fn alias(&mut self, tokens: Vec<Token>) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        c.next();
+        let target = self.postfix(&mut c);
+        if !c.eat_op("=") {
+            let span = c.here();
+            self.error("Expected '=' in Alias", span);
+            return StmtKind::Invalid(self.raw(&c.toks));
+        }
+        match self.name(&mut c) {
+            Some(name) => {
+                self.expect_end(&c);
+                StmtKind::Alias { target, name }
+            }
+            None => StmtKind::Invalid(self.raw(&c.toks)),
+        }
+    }
+
+    fn units(&mut self, tokens: Vec<Token>) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        c.next();
+        let mut names = Vec::new();
+        while let Some(name) = self.name(&mut c) {
+            names.push(name);
+            // Units on a single array element: `Units Temp(1) = degC`
+            if c.eat_kind(TokenKind::LParen) {
+                self.comma_exprs(&mut c);
+                self.expect_rparen(&mut c);
+            }
+            if !c.eat_kind(TokenKind::Comma) {
+                break;
+            }
+        }
+        if !c.eat_op("=") {
+            let span = c.here();
+            self.error("Expected '=' in Units", span);
+            return StmtKind::Units { names, units: String::new() };
+        }
+        let rest = c.rest();
+        StmtKind::Units { names, units: self.raw(&rest) }
+    }
+
+    fn raw(&self, tokens: &[Token]) -> String {
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => self.src[first.span.start..last.span.end].to_string(),
+            _ => String::new(),
+        }
+    }
+
+    // --- Blocks ---
+
+    fn data_table(&mut self, tokens: Vec<Token>, span: Span) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        c.next();
+        let mut args = self.arg_list(&mut c);
+        self.expect_end(&c);
+        let name = match args.first().and_then(|a| match &a.kind {
+            ExprKind::Ref { name, args: None } => Some(Name { text: name.clone(), span: a.span }),
+            _ => None,
+        }) {
+            Some(name) => {
+                args.remove(0);
+                name
+            }
+            None => {
+                self.error("DataTable requires a table name as its first argument", span);
+                Name { text: String::new(), span }
+            }
+        };
+        let (body, found) = self.block(&["endtable"]);
+        let end = self.close(found, "endtable", "DataTable", span);
+        StmtKind::DataTable(DataTable { name, args, body, end })
+    }
+
+    fn routine(&mut self, tokens: Vec<Token>, head: &str, span: Span) -> StmtKind {
+        let kind = if head == "sub" { RoutineKind::Sub } else { RoutineKind::Function };
+        let mut c = Cursor::new(tokens);
+        c.next();
+        let name = self.name(&mut c).unwrap_or(Name { text: String::new(), span });
+        let mut params = Vec::new();
+        if c.eat_kind(TokenKind::LParen) && !c.eat_kind(TokenKind::RParen) {
+            while let Some(p) = self.var_decl(&mut c) {
+                params.push(p);
+                if !c.eat_kind(TokenKind::Comma) {
+                    break;
+                }
+            }
+            self.expect_rparen(&mut c);
+        }
+        let return_type = self.type_spec(&mut c);
+        self.expect_end(&c);
+        let (closer, opener): (&'static [&'static str], &str) = match kind {
+            RoutineKind::Sub => (&["endsub"], "Sub"),
+            RoutineKind::Function => (&["endfunction"], "Function"),
+        };
+        let (body, found) = self.block(closer);
+        let end = self.close(found, closer[0], opener, span);
+        StmtKind::Routine(Routine { kind, name, params, return_type, body, end })
+    }
+
+    fn generic_block(&mut self, tokens: Vec<Token>, closer: &'static str, span: Span) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        let kw = c.next().expect("line has a head token");
+        let keyword = Name { text: kw.text, span: kw.span };
+        let args = self.arg_list(&mut c);
+        self.expect_end(&c);
+        let terminators: &'static [&'static str] = match GENERIC_BLOCKS.iter().find(|(_, close)| *close == closer) {
+            Some((_, close)) => std::slice::from_ref(close),
+            None => &[],
+        };
+        let (body, found) = self.block(terminators);
+        let end = self.close(found, closer, &keyword.text.clone(), span);
+        StmtKind::Block(GenericBlock { keyword, args, body, end })
+    }
+
+    fn if_stmt(&mut self, tokens: Vec<Token>, span: Span) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        c.next();
+        let cond = self.expr(&mut c);
+        if !c.eat_word("Then") {
+            let here = c.here();
+            self.error("Expected 'Then'", here);
+        }
+
+        if !c.at_end() {
+            // Single-line form: If cond Then stmt [Else stmt]
+            let rest = c.rest();
+            let else_at = rest.iter().position(|t| t.is_word("Else"));
+            let (then_toks, else_toks) = match else_at {
+                Some(i) => (rest[..i].to_vec(), Some(rest[i + 1..].to_vec())),
+                None => (rest, None),
+            };
+            let then_body = self.inline_stmt(then_toks).into_iter().collect();
+            let else_body = else_toks.map(|t| self.inline_stmt(t).into_iter().collect());
+            return StmtKind::If(IfStmt { cond, then_body, else_ifs: Vec::new(), else_body, single_line: true, end: None });
+        }
+
+        const BRANCHES: &[&str] = &["elseif", "else", "endif"];
+        let (then_body, mut found) = self.block(BRANCHES);
+        let mut else_ifs = Vec::new();
+        let mut else_body = None;
+        loop {
+            match found.as_deref() {
+                Some("elseif") => {
+                    let toks = self.lines[self.idx].clone();
+                    self.idx += 1;
+                    let mut c = Cursor::new(toks);
+                    c.pos = head_len(&c.toks);
+                    let cond = self.expr(&mut c);
+                    if !c.eat_word("Then") {
+                        let here = c.here();
+                        self.error("Expected 'Then'", here);
+                    }
+                    self.expect_end(&c);
+                    let (body, next) = self.block(BRANCHES);
+                    else_ifs.push((cond, body));
+                    found = next;
+                }
+                Some("else") => {
+                    self.idx += 1;
+                    let (body, next) = self.block(&["endif"]);
+                    else_body = Some(body);
+                    found = next;
+                }
+                _ => break,
+            }
+        }
+        let end = self.close(found, "endif", "If", span);
+        StmtKind::If(IfStmt { cond, then_body, else_ifs, else_body, single_line: false, end })
+    }
+
+    /// Parses a statement embedded in a single-line `If`.
+    fn inline_stmt(&mut self, tokens: Vec<Token>) -> Option<Stmt> {
+        if tokens.is_empty() {
+            return None;
+        }
+        let span = line_span(&tokens);
+        let head = line_head(&tokens);
+        let kind = if EXIT_KEYWORDS.contains(&head.as_str()) {
+            StmtKind::Exit(head)
+        } else {
+            self.simple(tokens)
+        };
+        Some(Stmt { kind, span })
+    }
+
+    fn for_loop(&mut self, tokens: Vec<Token>, span: Span) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        c.next();
+        let var = self.postfix(&mut c);
+        if !c.eat_op("=") {
+            let here = c.here();
+            self.error("Expected '=' in For", here);
+        }
+        let from = self.expr(&mut c);
+        if !c.eat_word("To") {
+            let here = c.here();
+            self.error("Expected 'To' in For", here);
+        }
+        let to = self.expr(&mut c);
+        let step = if c.eat_word("Step") { Some(self.expr(&mut c)) } else { None };
+        self.expect_end(&c);
+        let (body, found) = self.block(&["next"]);
+        let end = self.close(found, "next", "For", span);
+        StmtKind::For(ForLoop { var, from, to, step, body, end })
+    }
+
+    fn loop_cond(&mut self, c: &mut Cursor) -> Option<(LoopCondKind, Expr)> {
+        if c.eat_word("While") {
+            Some((LoopCondKind::While, self.expr(c)))
+        } else if c.eat_word("Until") {
+            Some((LoopCondKind::Until, self.expr(c)))
+        } else {
+            None
+        }
+    }
+
+    fn do_loop(&mut self, tokens: Vec<Token>, span: Span) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        c.next();
+        let pre = self.loop_cond(&mut c);
+        self.expect_end(&c);
+        let (body, found) = self.block(&["loop"]);
+        let mut post = None;
+        if found.is_some() {
+            let mut lc = Cursor::new(self.lines[self.idx].clone());
+            lc.next();
+            post = self.loop_cond(&mut lc);
+            self.expect_end(&lc);
+        }
+        let end = self.close(found, "loop", "Do", span);
+        StmtKind::Do(DoLoop { pre, post, body, end })
+    }
+
+    fn while_loop(&mut self, tokens: Vec<Token>, span: Span) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        c.next();
+        let cond = self.expr(&mut c);
+        self.expect_end(&c);
+        let (body, found) = self.block(&["wend"]);
+        let end = self.close(found, "wend", "While", span);
+        StmtKind::While(WhileLoop { cond, body, end })
+    }
+
+    fn select(&mut self, tokens: Vec<Token>, span: Span) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        c.pos = 2;
+        let subject = self.expr(&mut c);
+        self.expect_end(&c);
+
+        let mut cases = Vec::new();
+        self.open.push(&["case", "endselect"]);
+        // Anything before the first Case is an error.
+        while self.idx < self.lines.len() {
+            let head = line_head(&self.lines[self.idx]);
+            if head == "case" || head == "endselect" || self.is_open_closer(&head) {
+                break;
+            }
+            let bad = line_span(&self.lines[self.idx]);
+            self.error("Expected 'Case'", bad);
+            self.idx += 1;
+        }
+        self.open.pop();
+
+        let mut found = (self.idx < self.lines.len()).then(|| line_head(&self.lines[self.idx]));
+        while found.as_deref() == Some("case") {
+            let toks = self.lines[self.idx].clone();
+            let case_span = line_span(&toks);
+            self.idx += 1;
+            let mut c = Cursor::new(toks);
+            c.next();
+            let is_else = c.eat_word("Else");
+            let mut values = Vec::new();
+            if !is_else {
+                loop {
+                    values.push(self.case_value(&mut c));
+                    if !c.eat_kind(TokenKind::Comma) {
+                        break;
+                    }
+                }
+            }
+            self.expect_end(&c);
+            let (body, next) = self.block(&["case", "endselect"]);
+            cases.push(Case { values, is_else, body, span: case_span });
+            found = next;
+        }
+        let end = self.close(found, "endselect", "Select Case", span);
+        StmtKind::Select(SelectCase { subject, cases, end })
+    }
+
+    fn case_value(&mut self, c: &mut Cursor) -> Expr {
+        let start = c.here();
+        if c.eat_word("Is") {
+            let op = c.next().filter(|t| t.kind == TokenKind::Operator);
+            let Some(op) = op else {
+                self.error("Expected a comparison operator after 'Is'", start);
+                return Expr { kind: ExprKind::Error, span: start };
+            };
+            let rhs = self.expr(c);
+            let lhs = Expr { kind: ExprKind::Ref { name: "Is".to_string(), args: None }, span: start };
+            return Expr { kind: ExprKind::Binary { op: op.text, lhs: Box::new(lhs), rhs: Box::new(rhs) }, span: self.span_from(start, c) };
+        }
+        let lhs = self.expr(c);
+        if c.eat_word("To") {
+            let rhs = self.expr(c);
+            return Expr { kind: ExprKind::Binary { op: "To".to_string(), lhs: Box::new(lhs), rhs: Box::new(rhs) }, span: self.span_from(start, c) };
+        }
+        lhs
+    }
+
+    // --- Simple statements ---
+
+    fn simple(&mut self, tokens: Vec<Token>) -> StmtKind {
+        let mut c = Cursor::new(tokens);
+        c.eat_word("Call");
+        match c.peek() {
+            Some(t) if t.kind == TokenKind::Ident => {}
+            _ => {
+                let span = c.here();
+                self.error("Expected a statement", span);
+                return StmtKind::Invalid(self.raw(&c.toks));
+            }
+        }
+        let target = self.postfix(&mut c);
+        if c.eat_op("=") {
+            let value = self.expr(&mut c);
+            self.expect_end(&c);
+            return StmtKind::Assign { target, value };
+        }
+        let ExprKind::Ref { name, args } = target.kind else {
+            return StmtKind::Invalid(self.raw(&c.toks));
+        };
+        let name_span = Span { end: target.span.start + name.len(), ..target.span };
+        let name = Name { text: name, span: name_span };
+        let (args, parenthesized) = match args {
+            Some(args) => (args, true),
+            None if c.at_end() => (Vec::new(), false),
+            // Unparenthesized argument list: `CallTable Test`, `Battery BattV`
+            None => (self.comma_exprs(&mut c), false),
+        };
+        self.expect_end(&c);
+        StmtKind::Call(Call { name, args, parenthesized })
+    }
+
+    /// Parses `(a, b, c)` or an unparenthesized `a, b, c` list up to the end of the line.
+    fn arg_list(&mut self, c: &mut Cursor) -> Vec<Expr> {
+        if c.at_end() {
+            return Vec::new();
+        }
+        if c.eat_kind(TokenKind::LParen) {
+            if c.eat_kind(TokenKind::RParen) {
+                return Vec::new();
+            }
+            let args = self.comma_exprs(c);
+            self.expect_rparen(c);
+            args
+        } else {
+            self.comma_exprs(c)
+        }
+    }
+
+    fn comma_exprs(&mut self, c: &mut Cursor) -> Vec<Expr> {
+        let mut out = vec![self.expr(c)];
+        while c.eat_kind(TokenKind::Comma) {
+            out.push(self.expr(c));
+        }
+        out
+    }
+
+    fn expect_rparen(&mut self, c: &mut Cursor) {
+        if !c.eat_kind(TokenKind::RParen) {
+            let span = c.here();
+            self.error("Expected ')'", span);
+        }
+    }
+
+    // --- Expressions ---
+
+    fn span_from(&self, start: Span, c: &Cursor) -> Span {
+        Span { end: c.last_end().max(start.start), ..start }
+    }
+
+    fn expr(&mut self, c: &mut Cursor) -> Expr {
+        self.binary_words(c, &["or", "xor", "imp", "eqv"], Self::and_expr)
+    }
+
+    fn and_expr(&mut self, c: &mut Cursor) -> Expr {
+        self.binary_words(c, &["and"], Self::not_expr)
+    }
+
+    fn binary_words(&mut self, c: &mut Cursor, words: &[&str], next: fn(&mut Self, &mut Cursor) -> Expr) -> Expr {
+        let start = c.here();
+        let mut lhs = next(self, c);
+        while let Some(op) = c.peek().filter(|t| t.kind == TokenKind::Ident && words.iter().any(|w| t.is_word(w))) {
+            let op = op.text.clone();
+            c.next();
+            let rhs = next(self, c);
+            lhs = Expr { kind: ExprKind::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }, span: self.span_from(start, c) };
+        }
+        lhs
+    }
+
+    fn not_expr(&mut self, c: &mut Cursor) -> Expr {
+        let start = c.here();
+        if let Some(t) = c.peek().filter(|t| t.is_word("Not")) {
+            let op = t.text.clone();
+            c.next();
+            let inner = self.not_expr(c);
+            return Expr { kind: ExprKind::Unary { op, expr: Box::new(inner) }, span: self.span_from(start, c) };
+        }
+        self.binary_ops(c, &["=", "<>", "<", ">", "<=", ">="], Self::concat)
+    }
+
+    fn concat(&mut self, c: &mut Cursor) -> Expr {
+        self.binary_ops(c, &["&"], Self::additive)
+    }
+
+    fn additive(&mut self, c: &mut Cursor) -> Expr {
+        self.binary_ops(c, &["+", "-"], Self::multiplicative)
+    }
+
+    fn multiplicative(&mut self, c: &mut Cursor) -> Expr {
+        let start = c.here();
+        let mut lhs = self.unary(c);
+        while let Some(t) = c.peek() {
+            let is_op = (t.kind == TokenKind::Operator && matches!(t.text.as_str(), "*" | "/" | "\\"))
+                || t.is_word("mod")
+                || t.is_word("intdv");
+            if !is_op {
+                break;
+            }
+            let op = t.text.clone();
+            c.next();
+            let rhs = self.unary(c);
+            lhs = Expr { kind: ExprKind::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }, span: self.span_from(start, c) };
+        }
+        lhs
+    }
+
+    fn binary_ops(&mut self, c: &mut Cursor, ops: &[&str], next: fn(&mut Self, &mut Cursor) -> Expr) -> Expr {
+        let start = c.here();
+        let mut lhs = next(self, c);
+        while let Some(t) = c.peek().filter(|t| t.kind == TokenKind::Operator && ops.contains(&t.text.as_str())) {
+            let op = t.text.clone();
+            c.next();
+            let rhs = next(self, c);
+            lhs = Expr { kind: ExprKind::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }, span: self.span_from(start, c) };
+        }
+        lhs
+    }
+
+    fn unary(&mut self, c: &mut Cursor) -> Expr {
+        let start = c.here();
+        if let Some(t) = c.peek().filter(|t| t.kind == TokenKind::Operator && (t.text == "-" || t.text == "+")) {
+            let op = t.text.clone();
+            c.next();
+            let inner = self.unary(c);
+            return Expr { kind: ExprKind::Unary { op, expr: Box::new(inner) }, span: self.span_from(start, c) };
+        }
+        let base = self.primary(c);
+        if c.eat_op("^") {
+            let exponent = self.unary(c);
+            return Expr { kind: ExprKind::Binary { op: "^".to_string(), lhs: Box::new(base), rhs: Box::new(exponent) }, span: self.span_from(start, c) };
+        }
+        base
+    }
+
+    fn primary(&mut self, c: &mut Cursor) -> Expr {
+        let start = c.here();
+        let Some(t) = c.peek().cloned() else {
+            self.error("Expected an expression", start);
+            return Expr { kind: ExprKind::Error, span: start };
+        };
+        match t.kind {
+            TokenKind::Number => {
+                c.next();
+                Expr { kind: ExprKind::Number(t.text), span: t.span }
+            }
+            TokenKind::String => {
+                c.next();
+                let inner = t.text.strip_prefix('"').unwrap_or(&t.text);
+                let inner = inner.strip_suffix('"').unwrap_or(inner);
+                if !t.text.ends_with('"') || t.text.len() < 2 {
+                    self.error("Unterminated string literal", t.span);
+                }
+                Expr { kind: ExprKind::Str(inner.replace("\"\"", "\"")), span: t.span }
+            }
+            TokenKind::LParen => {
+                c.next();
+                let inner = self.expr(c);
+                self.expect_rparen(c);
+                Expr { kind: ExprKind::Paren(Box::new(inner)), span: self.span_from(start, c) }
+            }
+            TokenKind::Ident if !RESERVED.contains(&t.text.to_ascii_lowercase().as_str()) => self.postfix(c),
+            _ => {
+                self.error(format!("Expected an expression, found '{}'", t.text), t.span);
+                Expr { kind: ExprKind::Error, span: t.span }
+            }
+        }
+    }
+
+    /// Parses a (possibly dotted, possibly indexed) reference: `x`, `T(1)`, `Status.Battery(1,1)`.
+    fn postfix(&mut self, c: &mut Cursor) -> Expr {
+        let start = c.here();
+        let Some(first) = c.peek().filter(|t| t.kind == TokenKind::Ident).cloned() else {
+            self.error("Expected a name", start);
+            return Expr { kind: ExprKind::Error, span: start };
+        };
+        c.next();
+        let mut name = first.text;
+        while c.peek().is_some_and(|t| t.kind == TokenKind::Dot) {
+            c.next();
+            match c.peek().filter(|t| t.kind == TokenKind::Ident).cloned() {
+                Some(part) => {
+                    c.next();
+                    name.push('.');
+                    name.push_str(&part.text);
+                }
+                None => {
+                    let here = c.here();
+                    self.error("Expected a field name after '.'", here);
+                    break;
+                }
+            }
+        }
+        let args = if c.eat_kind(TokenKind::LParen) {
+            if c.eat_kind(TokenKind::RParen) {
+                Some(Vec::new())
+            } else {
+                let args = self.comma_exprs(c);
+                self.expect_rparen(c);
+                Some(args)
+            }
+        } else {
+            None
+        };
+        Expr { kind: ExprKind::Ref { name, args }, span: self.span_from(start, c) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+'CR1000X program
+Public PTemp, Batt_Volt
+Public Temp(4) As Float, Label As String * 24
+Const Offset = 1.5
+Units PTemp = Deg C
+DataTable (Test,1,-1)
+  DataInterval (0,15,Sec,10)
+  Minimum (1,Batt_Volt,FP2,False,False)
+  Sample (1,PTemp,FP2)
+EndTable
+Sub Reset(n As Long)
+  Dim i
+  For i = 1 To n Step 1
+    Temp(i) = 0
+  Next i
+EndSub
+BeginProg
+  Scan (1,Sec,0,0)
+    PanelTemp (PTemp,15000)
+    Battery (Batt_Volt)
+    If PTemp > 40 Then Call Reset(4)
+    Select Case Batt_Volt
+      Case Is < 11
+        Label = \"low\"
+      Case Else
+        Label = \"ok\"
+    End Select
+    CallTable Test
+  NextScan
+EndProg
+";
+
+    #[test]
+    fn parses_typical_program_without_errors() {
+        let program = parse(SAMPLE);
+        assert!(program.errors.is_empty(), "{:?}", program.errors);
+        assert_eq!(program.comments.len(), 1);
+        let kinds: Vec<&str> = program
+            .body
+            .iter()
+            .map(|s| match &s.kind {
+                StmtKind::Declare(_) => "declare",
+                StmtKind::Const(_) => "const",
+                StmtKind::Units { .. } => "units",
+                StmtKind::DataTable(_) => "table",
+                StmtKind::Routine(_) => "routine",
+                StmtKind::BeginProg(_) => "prog",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(kinds, ["declare", "declare", "const", "units", "table", "routine", "prog"]);
+
+        let StmtKind::Units { units, .. } = &program.body[3].kind else { panic!() };
+        assert_eq!(units, "Deg C");
+        let StmtKind::Declare(decl) = &program.body[1].kind else { panic!() };
+        assert_eq!(decl.vars[1].ty.as_ref().unwrap().name.text, "String");
+        assert!(decl.vars[1].ty.as_ref().unwrap().size.is_some());
+    }
+
+    #[test]
+    fn missing_closer_is_reported_once() {
+        let program = parse("BeginProg\n  Scan(1,Sec,0,0)\n    x = 1\nEndProg\n");
+        assert_eq!(program.errors.len(), 1);
+        assert_eq!(program.errors[0].message, "'Scan' is missing its closing 'NextScan'");
+        assert_eq!(program.errors[0].span.line, 1);
+    }
+
+    #[test]
+    fn stray_closer_is_reported() {
+        let program = parse("x = 1\nEndIf\ny = 2\n");
+        assert_eq!(program.errors.len(), 1);
+        assert_eq!(program.body.len(), 2);
+    }
+
+    fn assigned(stmt: &Stmt) -> &Expr {
+        match &stmt.kind {
+            StmtKind::Assign { value, .. } => value,
+            other => panic!("not an assignment: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn joins_continued_lines() {
+        let program = parse("x = 1 + _\n  2\nCall Log(1, _ 'first\n  2)\ny = _\n\nz = 3\n");
+        assert_eq!(program.errors.len(), 1, "{:?}", program.errors); // `y =` is cut off by the blank line
+        assert!(matches!(&assigned(&program.body[0]).kind, ExprKind::Binary { op, .. } if op == "+"));
+        let StmtKind::Call(call) = &program.body[1].kind else { panic!("{:?}", program.body[1]) };
+        assert_eq!(call.args.len(), 2);
+        assert_eq!(program.comments.len(), 1);
+        // `Name_` and `_Name` are identifiers, not continuations.
+        assert!(parse("x = a_\ny = _b\n").errors.is_empty());
+    }
+
+    #[test]
+    fn apostrophes_in_strings_are_not_comments() {
+        let program = parse("s = \"it's\" 'it's a comment\nt = \"say \"\"hi\"\" it's\"\n");
+        assert!(program.errors.is_empty(), "{:?}", program.errors);
+        assert!(matches!(&assigned(&program.body[0]).kind, ExprKind::Str(text) if text == "it's"));
+        assert!(matches!(&assigned(&program.body[1]).kind, ExprKind::Str(text) if text == "say \"hi\" it's"));
+        assert_eq!(program.comments.len(), 1);
+        assert_eq!(program.comments[0].text, "'it's a comment");
+    }
+
+    #[test]
+    fn parses_array_dimensions_and_type_clauses() {
+        let program = parse("Public T(3,4) As Float, Flags(2,2,2) As Boolean\nDim Names(5) As String * 24, n As Long, s As String*8\n");
+        assert!(program.errors.is_empty(), "{:?}", program.errors);
+        let StmtKind::Declare(public) = &program.body[0].kind else { panic!() };
+        assert_eq!(public.vars[0].dims.len(), 2);
+        assert_eq!(public.vars[1].dims.len(), 3);
+        let StmtKind::Declare(dim) = &program.body[1].kind else { panic!() };
+        let types: Vec<(&str, bool)> =
+            dim.vars.iter().map(|v| v.ty.as_ref().map(|t| (t.name.text.as_str(), t.size.is_some())).unwrap()).collect();
+        assert_eq!(types, [("String", true), ("Long", false), ("String", true)]);
+        assert_eq!(dim.vars[0].dims.len(), 1);
+
+        let program = parse("BeginProg\n  T(1,2) = Flags(1,1,1) + T(2,3)\nEndProg\n");
+        assert!(program.errors.is_empty(), "{:?}", program.errors);
+    }
+
+    #[test]
+    fn parses_only_the_first_preprocessor_branch() {
+        let src = "#If LoggerType = CR1000X Then\nConst N = 2\n#ElseIf LoggerType = CR300 Then\nConst N = 1\n#Else\nConst N = 3\n#End If\n\
+                   BeginProg\n#If N > 1 Then\nScan(1,Sec,0,0)\n#Else\nScan(5,Sec,0,0)\n#EndIf\nNextScan\nEndProg\n";
+        let program = parse(src);
+        assert!(program.errors.is_empty(), "{:?}", program.errors);
+        let kinds: Vec<String> = program
+            .body
+            .iter()
+            .map(|s| match &s.kind {
+                StmtKind::Directive(text) => text.clone(),
+                StmtKind::Const(c) => format!("Const {}", c[0].name.text),
+                StmtKind::BeginProg(_) => "BeginProg".to_string(),
+                other => format!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(kinds, ["#If LoggerType = CR1000X Then", "Const N", "#ElseIf LoggerType = CR300 Then", "#Else", "#End If", "BeginProg"]);
+        let StmtKind::BeginProg(prog) = &program.body[5].kind else { panic!() };
+        assert_eq!(prog.body.len(), 2); // #If, and the Scan holding the other directives
+    }
+}
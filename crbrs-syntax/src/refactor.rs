@@ -0,0 +1,354 @@
+// FILE: crbrs-syntax/src/refactor.rs
+
+//! Source-to-source refactorings used by editor integrations.
+//!
+//! Refactorings return whole-line [`LineEdit`]s rather than rewritten text so that the
+//! language server can hand them to the client as a workspace edit and keep undo history
+//! intact. Every refactoring refuses to run on a program with parse errors.
+
+use crate::ast::*;
+use crate::parser::parse;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Replaces source lines `start_line..end_line` (0-based, end exclusive) with `new_text`.
+/// An edit with `start_line == end_line` is a pure insertion before `start_line`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LineEdit {
+    pub start_line: u32,
+    pub end_line: u32,
+    pub new_text: String,
+}
+
+/// Applies non-overlapping `edits` to `source`.
+pub fn apply_edits(source: &str, edits: &[LineEdit]) -> String {
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let mut edits: Vec<&LineEdit> = edits.iter().collect();
+    edits.sort_by_key(|e| (e.start_line, e.end_line));
+
+    let mut out = String::with_capacity(source.len());
+    let mut line = 0usize;
+    for edit in edits {
+        let start = (edit.start_line as usize).min(lines.len());
+        out.extend(lines[line.min(start)..start].iter().copied());
+        out.push_str(&edit.new_text);
+        line = (edit.end_line as usize).clamp(start, lines.len());
+    }
+    out.extend(lines[line..].iter().copied());
+    out
+}
+
+pub(crate) fn newline_of(source: &str) -> &'static str {
+    if source.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+pub(crate) fn ensure_parses(program: &Program) -> Result<(), String> {
+    match program.errors.first() {
+        Some(err) => Err(format!("Fix the syntax error on line {} first: {}", err.span.line + 1, err.message)),
+        None => Ok(()),
+    }
+}
+
+/// Returns true for a line holding nothing but a comment.
+pub(crate) fn is_comment_line(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with('\'')
+        || trimmed.get(..3).is_some_and(|w| w.eq_ignore_ascii_case("rem"))
+            && trimmed[3..].chars().next().is_none_or(char::is_whitespace)
+}
+
+// --- Organize declarations ---
+
+/// Order in which declaration groups are written back.
+fn declaration_group(stmt: &Stmt) -> Option<usize> {
+    match &stmt.kind {
+        StmtKind::Const(_) => Some(0),
+        StmtKind::Declare(Declaration { scope: DeclScope::Public, .. }) => Some(1),
+        StmtKind::Declare(Declaration { scope: DeclScope::Dim, .. }) => Some(2),
+        StmtKind::Alias { .. } => Some(3),
+        StmtKind::Units { .. } => Some(4),
+        _ => None,
+    }
+}
+
+/// Groups the leading run of top-level declarations into `Const`, `Public`, `Dim`, `Alias`
+/// and `Units` blocks separated by one blank line. Order within a group is preserved, and
+/// comment lines directly above a declaration move with it.
+///
+/// Returns no edits when the declarations are already organized.
+pub fn organize_declarations(source: &str) -> Result<Vec<LineEdit>, String> {
+    let program = parse(source);
+    ensure_parses(&program)?;
+
+    let Some(first) = program.body.iter().position(|s| declaration_group(s).is_some()) else {
+        return Ok(Vec::new());
+    };
+    let run: Vec<&Stmt> = program.body[first..].iter().take_while(|s| declaration_group(s).is_some()).collect();
+    let start_line = run[0].span.line;
+    let end_line = run[run.len() - 1].span.line;
+
+    let mut by_line: HashMap<u32, &Stmt> = HashMap::new();
+    for stmt in &run {
+        if by_line.insert(stmt.span.line, stmt).is_some() {
+            return Err(format!(
+                "Line {} holds several statements; split it before organizing declarations",
+                stmt.span.line + 1
+            ));
+        }
+    }
+
+    let lines: Vec<&str> = source.lines().collect();
+    let mut groups: Vec<Vec<&str>> = vec![Vec::new(); 5];
+    let mut pending_comments: Vec<&str> = Vec::new();
+    for line_no in start_line..=end_line {
+        let text = lines[line_no as usize].trim_end();
+        match by_line.get(&line_no) {
+            Some(stmt) => {
+                let group = &mut groups[declaration_group(stmt).unwrap_or_default()];
+                group.append(&mut pending_comments);
+                group.push(text);
+            }
+            None if is_comment_line(text) => pending_comments.push(text),
+            None => {}
+        }
+    }
+
+    let newline = newline_of(source);
+    let mut new_text = groups
+        .iter()
+        .filter(|g| !g.is_empty())
+        .map(|g| g.join(newline))
+        .collect::<Vec<_>>()
+        .join(&format!("{newline}{newline}"));
+    new_text.push_str(newline);
+
+    let old_text: String = lines[start_line as usize..=end_line as usize].iter().map(|l| format!("{}{}", l.trim_end(), newline)).collect();
+    if old_text == new_text {
+        return Ok(Vec::new());
+    }
+    Ok(vec![LineEdit { start_line, end_line: end_line + 1, new_text }])
+}
+
+// --- Extract subroutine ---
+
+/// Statement list that fully contains a selection, plus the top-level statement around it.
+struct Selection<'a> {
+    stmts: &'a [Stmt],
+    top: &'a Stmt,
+}
+
+/// Finds the statements covered by lines `start..=end`, descending into blocks while the
+/// selection lies strictly inside a single statement.
+fn select<'a>(body: &'a [Stmt], start: u32, end: u32, top: Option<&'a Stmt>) -> Result<Selection<'a>, String> {
+    let hit: Vec<usize> = body
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.span.line <= end && s.end_line().unwrap_or(u32::MAX) >= start)
+        .map(|(i, _)| i)
+        .collect();
+    let (Some(&first), Some(&last)) = (hit.first(), hit.last()) else {
+        return Err("The selection does not contain any statements".to_string());
+    };
+
+    let outer = &body[first];
+    let inside = start > outer.span.line && outer.end_line().is_some_and(|e| end < e);
+    if hit.len() == 1 && inside {
+        let top = top.unwrap_or(outer);
+        let mut last_err = None;
+        for child in outer.children() {
+            match select(child, start, end, Some(top)) {
+                Ok(sel) => return Ok(sel),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        return Err(last_err.unwrap_or_else(|| "The selection does not contain any statements".to_string()));
+    }
+
+    let stmts = &body[first..=last];
+    let whole = stmts.iter().all(|s| s.span.line >= start && s.end_line().is_some_and(|e| e <= end));
+    let Some(top) = top.filter(|_| whole) else {
+        return Err("Select whole statements inside BeginProg or a Sub/Function".to_string());
+    };
+    Ok(Selection { stmts, top })
+}
+
+/// Rejects statements that cannot be moved into a subroutine unchanged.
+fn check_extractable(stmts: &[Stmt], loop_depth: usize) -> Result<(), String> {
+    for stmt in stmts {
+        let line = stmt.span.line + 1;
+        match &stmt.kind {
+            StmtKind::Declare(_) | StmtKind::Const(_) | StmtKind::Alias { .. } | StmtKind::Units { .. } => {
+                return Err(format!("Line {} declares a name; declarations cannot be extracted", line))
+            }
+            StmtKind::DataTable(_) | StmtKind::Routine(_) | StmtKind::BeginProg(_) | StmtKind::Scan(_) | StmtKind::SlowSequence | StmtKind::EndSequence | StmtKind::Directive(_) => {
+                return Err(format!("Line {} cannot be moved into a subroutine", line))
+            }
+            StmtKind::Exit(keyword) => {
+                let local_loop_exit = loop_depth > 0 && matches!(keyword.as_str(), "exitfor" | "exitdo" | "exitwhile");
+                if !local_loop_exit {
+                    return Err(format!("Line {} leaves the enclosing block and cannot be extracted", line));
+                }
+            }
+            _ => {}
+        }
+        let depth = loop_depth + usize::from(matches!(stmt.kind, StmtKind::For(_) | StmtKind::Do(_) | StmtKind::While(_)));
+        for child in stmt.children() {
+            check_extractable(child, depth)?;
+        }
+    }
+    Ok(())
+}
+
+/// Names declared in the top level of a program (variables, tables, routines, ...), lower-case.
+pub(crate) fn global_names(program: &Program) -> Vec<String> {
+    let mut names = Vec::new();
+    for stmt in &program.body {
+        match &stmt.kind {
+            StmtKind::Declare(d) => names.extend(d.vars.iter().map(|v| v.name.text.to_ascii_lowercase())),
+            StmtKind::Const(consts) => names.extend(consts.iter().map(|c| c.name.text.to_ascii_lowercase())),
+            StmtKind::Alias { name, .. } => names.push(name.text.to_ascii_lowercase()),
+            StmtKind::DataTable(t) => names.push(t.name.text.to_ascii_lowercase()),
+            StmtKind::Routine(r) => names.push(r.name.text.to_ascii_lowercase()),
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Returns `base`, or `base2`, `base3`, ... if the name is already declared in `source`.
+pub fn unused_name(source: &str, base: &str) -> String {
+    let taken = global_names(&parse(source));
+    (1..)
+        .map(|n| if n == 1 { base.to_string() } else { format!("{}{}", base, n) })
+        .find(|candidate| !taken.contains(&candidate.to_ascii_lowercase()))
+        .unwrap_or_else(|| base.to_string())
+}
+
+/// Local variables and parameters of a routine that the selected statements reference, in
+/// order of declaration. These become parameters of the extracted subroutine.
+fn captured_locals<'a>(routine: &'a Routine, stmts: &[Stmt]) -> Result<Vec<&'a VarDecl>, String> {
+    let mut locals: Vec<&VarDecl> = routine.params.iter().collect();
+    for stmt in &routine.body {
+        if let StmtKind::Declare(d) = &stmt.kind {
+            locals.extend(&d.vars);
+        }
+    }
+
+    let mut used = Vec::new();
+    walk_stmts(stmts, &mut |stmt| {
+        for expr in stmt.exprs() {
+            expr.walk(&mut |e| {
+                if let ExprKind::Ref { name, .. } = &e.kind {
+                    // `Table.Field` refers to `Table`.
+                    let base = name.split('.').next().unwrap_or(name);
+                    used.push(base.to_ascii_lowercase());
+                }
+            });
+        }
+    });
+
+    let captured: Vec<&VarDecl> = locals.into_iter().filter(|v| used.contains(&v.name.text.to_ascii_lowercase())).collect();
+    if let Some(array) = captured.iter().find(|v| !v.dims.is_empty()) {
+        return Err(format!("'{}' is a local array and cannot be passed to the new subroutine", array.name.text));
+    }
+    Ok(captured)
+}
+
+/// Moves the statements on lines `start_line..=end_line` (0-based) into a new `Sub name`
+/// declared just before the enclosing `BeginProg` or routine, and replaces them with a call.
+/// Locals of an enclosing routine that the statements use become parameters.
+pub fn extract_subroutine(source: &str, start_line: u32, end_line: u32, name: &str) -> Result<Vec<LineEdit>, String> {
+    let program = parse(source);
+    ensure_parses(&program)?;
+
+    if !name.chars().next().is_some_and(|c| c.is_ascii_alphabetic()) || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("'{}' is not a valid subroutine name", name));
+    }
+    if global_names(&program).contains(&name.to_ascii_lowercase()) {
+        return Err(format!("'{}' is already declared", name));
+    }
+
+    let (start, end) = (start_line.min(end_line), start_line.max(end_line));
+    let sel = select(&program.body, start, end, None)?;
+    check_extractable(sel.stmts, 0)?;
+
+    let params = match &sel.top.kind {
+        StmtKind::Routine(routine) => captured_locals(routine, sel.stmts)?,
+        _ => Vec::new(),
+    };
+
+    let lines: Vec<&str> = source.lines().collect();
+    let first = sel.stmts[0].span.line.min(start);
+    let last = sel.stmts[sel.stmts.len() - 1].end_line().unwrap_or(end).max(end).min(lines.len() as u32 - 1);
+    let body_lines = &lines[first as usize..=last as usize];
+    let indent_of = |l: &str| l.len() - l.trim_start().len();
+    let common = body_lines.iter().filter(|l| !l.trim().is_empty()).map(|l| indent_of(l)).min().unwrap_or(0);
+
+    let newline = newline_of(source);
+    let param_list = params
+        .iter()
+        .map(|p| match &p.ty {
+            Some(ty) => format!("{} As {}", p.name.text, ty.name.text),
+            None => p.name.text.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut sub_text = if params.is_empty() { format!("Sub {}{}", name, newline) } else { format!("Sub {}({}){}", name, param_list, newline) };
+    for line in body_lines {
+        let line = line.trim_end();
+        if !line.is_empty() {
+            sub_text.push_str("  ");
+            sub_text.push_str(&line[common.min(indent_of(line))..]);
+        }
+        sub_text.push_str(newline);
+    }
+    sub_text.push_str(&format!("EndSub{}{}", newline, newline));
+
+    // Keep comment lines directly above the enclosing statement attached to it.
+    let mut insert_at = sel.top.span.line;
+    while insert_at > 0 && is_comment_line(lines[insert_at as usize - 1]) {
+        insert_at -= 1;
+    }
+
+    let first_text = lines[sel.stmts[0].span.line as usize];
+    let indent = &first_text[..indent_of(first_text)];
+    let args = params.iter().map(|p| p.name.text.as_str()).collect::<Vec<_>>().join(", ");
+    let call = if args.is_empty() { format!("{}Call {}{}", indent, name, newline) } else { format!("{}Call {}({}){}", indent, name, args, newline) };
+
+    Ok(vec![
+        LineEdit { start_line: insert_at, end_line: insert_at, new_text: sub_text },
+        LineEdit { start_line: first, end_line: last + 1, new_text: call },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn organizes_declarations_into_groups() {
+        let src = "Public A\n'limit\nConst N = 4\nUnits A = V\nDim i\nPublic B(N)\nDataTable(T,1,-1)\nEndTable\n";
+        let out = apply_edits(src, &organize_declarations(src).unwrap());
+        assert_eq!(out, "'limit\nConst N = 4\n\nPublic A\nPublic B(N)\n\nDim i\n\nUnits A = V\nDataTable(T,1,-1)\nEndTable\n");
+        assert!(organize_declarations(&out).unwrap().is_empty());
+    }
+
+    #[test]
+    fn extracts_statements_into_a_sub() {
+        let src = "Public A, B\nBeginProg\n  Scan(1,Sec,0,0)\n    A = 1\n    B = A * 2\n  NextScan\nEndProg\n";
+        let out = apply_edits(src, &extract_subroutine(src, 3, 4, "Update").unwrap());
+        assert_eq!(out, "Public A, B\nSub Update\n  A = 1\n  B = A * 2\nEndSub\n\nBeginProg\n  Scan(1,Sec,0,0)\n    Call Update\n  NextScan\nEndProg\n");
+        assert!(parse(&out).errors.is_empty());
+
+        let src = "Sub Scale(X As Float)\n  Dim k\n  k = 2\n  X = X * k\nEndSub\n";
+        let out = apply_edits(src, &extract_subroutine(src, 3, 3, "Mul").unwrap());
+        assert!(out.starts_with("Sub Mul(X As Float, k)\n  X = X * k\nEndSub\n\nSub Scale"), "{out}");
+        assert!(out.contains("  Call Mul(X, k)\n"));
+
+        assert!(extract_subroutine(src, 1, 1, "Bad").is_err());
+    }
+}
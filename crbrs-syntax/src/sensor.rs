@@ -0,0 +1,703 @@
+// FILE: crbrs-syntax/src/sensor.rs
+
+//! Sensor code generators for `crbrs sensor add`.
+//!
+//! A [`SensorBlock`] is the code one sensor needs: a `Public` array for its values with an
+//! `Alias` and `Units` per value, the measurement instruction, and output instructions for a
+//! data table. [`add_sensor`] inserts it into an existing program using the parse tree:
+//! declarations go before the first `DataTable`, `Sub`/`Function` or `BeginProg`, the
+//! measurement into the main `Scan` before its first `CallTable`, and the output instruction
+//! at the end of the data table. Like the refactorings it returns [`LineEdit`]s and refuses to
+//! run on a program with parse errors.
+//!
+//! [`parse_register_map`] reads a Modbus register map (a CSV of address, type, scaling and
+//! name) for [`register_map`], which reads contiguous registers of the same type with one
+//! `ModbusMaster` call each and scales the values after a successful read.
+
+use crate::ast::*;
+use crate::parser::parse;
+use crate::refactor::{ensure_parses, global_names, is_comment_line, newline_of, LineEdit};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// A value reported by a sensor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SensorValue {
+    /// Appended to the sensor name for the value's alias: `Soil` + `VWC` → `Soil_VWC`.
+    pub suffix: String,
+    pub units: Option<String>,
+}
+
+/// `Name` or `Name:Units`, as given on the command line.
+impl FromStr for SensorValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (suffix, units) = match s.split_once(':') {
+            Some((suffix, units)) => (suffix.trim(), Some(units.trim().to_string()).filter(|u| !u.is_empty())),
+            None => (s.trim(), None),
+        };
+        if suffix.is_empty() || !suffix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("'{}' is not a valid value name", suffix));
+        }
+        Ok(SensorValue { suffix: suffix.to_string(), units })
+    }
+}
+
+/// SDI-12 sensor with a known measurement command and value layout.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Sdi12Model {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Measurement command without the address, e.g. `M!`.
+    pub command: &'static str,
+    /// Suffix and units of each value the command returns, in order.
+    pub values: &'static [(&'static str, &'static str)],
+}
+
+const SDI12_MODELS: &[Sdi12Model] = &[
+    Sdi12Model {
+        name: "CS655",
+        description: "Campbell Scientific water content reflectometer",
+        command: "M!",
+        values: &[("VWC", "m^3/m^3"), ("EC", "dS/m"), ("T", "Deg C")],
+    },
+    Sdi12Model {
+        name: "CS650",
+        description: "Campbell Scientific water content reflectometer",
+        command: "M!",
+        values: &[("VWC", "m^3/m^3"), ("EC", "dS/m"), ("T", "Deg C")],
+    },
+    Sdi12Model {
+        name: "CS451",
+        description: "Campbell Scientific pressure transducer",
+        command: "M!",
+        values: &[("Pressure", "psi"), ("T", "Deg C")],
+    },
+    Sdi12Model {
+        name: "TEROS11",
+        description: "METER soil moisture and temperature",
+        command: "M!",
+        values: &[("VWC_Raw", "mV"), ("T", "Deg C")],
+    },
+    Sdi12Model {
+        name: "TEROS12",
+        description: "METER soil moisture, temperature and EC",
+        command: "M!",
+        values: &[("VWC_Raw", "mV"), ("T", "Deg C"), ("EC", "uS/cm")],
+    },
+];
+
+/// Built-in SDI-12 models.
+pub fn sdi12_models() -> &'static [Sdi12Model] {
+    SDI12_MODELS
+}
+
+pub fn sdi12_model(name: &str) -> Option<&'static Sdi12Model> {
+    SDI12_MODELS.iter().find(|m| m.name.eq_ignore_ascii_case(name))
+}
+
+/// Table output instruction for the values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Processing {
+    #[default]
+    Average,
+    Sample,
+    Minimum,
+    Maximum,
+    /// No table output.
+    None,
+}
+
+impl FromStr for Processing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "average" => Ok(Processing::Average),
+            "sample" => Ok(Processing::Sample),
+            "minimum" => Ok(Processing::Minimum),
+            "maximum" => Ok(Processing::Maximum),
+            "none" => Ok(Processing::None),
+            other => Err(format!("unknown processing '{}' (expected average, sample, minimum, maximum or none)", other)),
+        }
+    }
+}
+
+/// Register layout of Modbus values, the `ModbusOption` argument of `ModbusMaster`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegisterFormat {
+    /// 32-bit float, low word first (CDAB).
+    #[default]
+    FloatCdab,
+    /// 32-bit float, high word first (ABCD).
+    FloatAbcd,
+    /// 32-bit signed integer, low word first (CDAB).
+    Int32Cdab,
+    /// 32-bit signed integer, high word first (ABCD).
+    Int32Abcd,
+    Int16,
+    Uint16,
+}
+
+impl RegisterFormat {
+    pub fn modbus_option(self) -> u8 {
+        match self {
+            RegisterFormat::FloatCdab => 0,
+            RegisterFormat::Int16 => 1,
+            RegisterFormat::FloatAbcd => 2,
+            RegisterFormat::Int32Cdab => 3,
+            RegisterFormat::Int32Abcd => 4,
+            RegisterFormat::Uint16 => 10,
+        }
+    }
+
+    /// Registers each value occupies.
+    pub fn registers(self) -> u32 {
+        match self {
+            RegisterFormat::FloatCdab | RegisterFormat::FloatAbcd | RegisterFormat::Int32Cdab | RegisterFormat::Int32Abcd => 2,
+            RegisterFormat::Int16 | RegisterFormat::Uint16 => 1,
+        }
+    }
+}
+
+impl FromStr for RegisterFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "float-cdab" | "float" | "float32" => Ok(RegisterFormat::FloatCdab),
+            "float-abcd" | "float32-abcd" => Ok(RegisterFormat::FloatAbcd),
+            "int32-cdab" | "int32" => Ok(RegisterFormat::Int32Cdab),
+            "int32-abcd" => Ok(RegisterFormat::Int32Abcd),
+            "int16" => Ok(RegisterFormat::Int16),
+            "uint16" => Ok(RegisterFormat::Uint16),
+            other => Err(format!(
+                "unknown register format '{}' (expected float-cdab, float-abcd, int32-cdab, int32-abcd, int16 or uint16)",
+                other
+            )),
+        }
+    }
+}
+
+/// The code for one sensor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SensorBlock {
+    /// Name of the `Public` array holding the values.
+    pub name: String,
+    pub values: Vec<SensorValue>,
+    /// Other declarations the instructions need, e.g. a Modbus result code.
+    pub declarations: Vec<String>,
+    /// Comment above the measurement, without the `'`.
+    pub comment: String,
+    /// Scan instructions that measure the sensor.
+    pub measurement: Vec<String>,
+}
+
+impl SensorBlock {
+    /// Alias of value `i`.
+    pub fn alias(&self, i: usize) -> String {
+        format!("{}_{}", self.name, self.values[i].suffix)
+    }
+
+    /// Names the block declares, for conflict checks.
+    pub fn declared_names(&self) -> Vec<String> {
+        let mut names = vec![self.name.clone()];
+        names.extend((0..self.values.len()).map(|i| self.alias(i)));
+        for declaration in &self.declarations {
+            let name = declaration.split_whitespace().nth(1).and_then(|n| n.split('(').next());
+            names.extend(name.map(str::to_string));
+        }
+        names
+    }
+
+    fn declaration_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("'{}", self.comment), format!("Public {}({})", self.name, self.values.len())];
+        lines.extend(self.declarations.iter().cloned());
+        for (i, _) in self.values.iter().enumerate() {
+            lines.push(format!("Alias {}({}) = {}", self.name, i + 1, self.alias(i)));
+        }
+        for (i, value) in self.values.iter().enumerate() {
+            if let Some(units) = &value.units {
+                lines.push(format!("Units {} = {}", self.alias(i), units));
+            }
+        }
+        lines
+    }
+
+    /// The block as text to paste: declarations, the measurement and the output instruction,
+    /// separated by blank lines.
+    pub fn snippet(&self, processing: Processing) -> String {
+        let mut sections = vec![self.declaration_lines(), self.measurement_lines()];
+        sections.extend(self.output_line(processing).map(|line| vec![line]));
+        sections.iter().map(|lines| lines.iter().map(|l| format!("{}\n", l)).collect::<String>()).collect::<Vec<_>>().join("\n")
+    }
+
+    fn measurement_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("'{}", self.comment)];
+        lines.extend(self.measurement.iter().cloned());
+        lines
+    }
+
+    fn output_line(&self, processing: Processing) -> Option<String> {
+        let (instruction, tail) = match processing {
+            Processing::Average => ("Average", ", FP2, False"),
+            Processing::Sample => ("Sample", ", FP2"),
+            Processing::Minimum => ("Minimum", ", FP2, False, False"),
+            Processing::Maximum => ("Maximum", ", FP2, False, False"),
+            Processing::None => return None,
+        };
+        Some(format!("{}({}, {}(){})", instruction, self.values.len(), self.name, tail))
+    }
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    if !name.chars().next().is_some_and(|c| c.is_ascii_alphabetic()) || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("'{}' is not a valid variable name", name));
+    }
+    Ok(())
+}
+
+/// An SDI-12 sensor read with `command` (e.g. `M!`) at `address` on `port` (e.g. `C1`).
+pub fn sdi12(name: &str, description: &str, address: char, port: &str, command: &str, values: Vec<SensorValue>) -> Result<SensorBlock, String> {
+    check_name(name)?;
+    if !address.is_ascii_alphanumeric() {
+        return Err(format!("'{}' is not an SDI-12 address (0-9, a-z or A-Z)", address));
+    }
+    if values.is_empty() {
+        return Err("The sensor needs at least one value".to_string());
+    }
+    Ok(SensorBlock {
+        name: name.to_string(),
+        values,
+        declarations: Vec::new(),
+        comment: format!("{} on {}, SDI-12 address {}", description, port, address),
+        measurement: vec![format!("SDI12Recorder({}(), {}, \"{}\", \"{}\", 1.0, 0)", name, port, address, command)],
+    })
+}
+
+/// Modbus connection settings for [`modbus`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModbusDevice {
+    /// e.g. `ComC1`.
+    pub port: String,
+    pub baud: u32,
+    /// Slave address.
+    pub address: u8,
+    /// Function code, 3 (holding registers) or 4 (input registers).
+    pub function: u8,
+    pub format: RegisterFormat,
+}
+
+/// A Modbus device read with one `ModbusMaster` call starting at register `start` (1-based,
+/// within the function's register space).
+pub fn modbus(name: &str, description: &str, device: &ModbusDevice, start: u32, values: Vec<SensorValue>) -> Result<SensorBlock, String> {
+    check_name(name)?;
+    if values.is_empty() {
+        return Err("The device needs at least one value".to_string());
+    }
+    let result = format!("{}_Result", name);
+    Ok(SensorBlock {
+        name: name.to_string(),
+        declarations: vec![format!("Public {} As Long", result)],
+        comment: format!(
+            "{} on {}, Modbus address {}, {} {} registers from {}",
+            description,
+            device.port,
+            device.address,
+            values.len() as u32 * device.format.registers(),
+            if device.function == 4 { "input" } else { "holding" },
+            start
+        ),
+        measurement: vec![format!(
+            "ModbusMaster({}, {}, {}, {}, {}, {}(), {}, {}, 3, 100, {})",
+            result,
+            device.port,
+            device.baud,
+            device.address,
+            device.function,
+            name,
+            start,
+            values.len(),
+            device.format.modbus_option()
+        )],
+        values,
+    })
+}
+
+/// One value of a Modbus register map.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RegisterEntry {
+    /// First register of the value, 1-based like the start register of `ModbusMaster`.
+    pub register: u32,
+    /// Function code, 3 (holding registers) or 4 (input registers).
+    pub function: u8,
+    pub format: RegisterFormat,
+    /// Factor the raw value is multiplied by, as written in the map.
+    pub scale: Option<String>,
+    pub value: SensorValue,
+}
+
+/// Most registers read with one `ModbusMaster` call (Modbus allows 125 per request).
+const MAX_REGISTERS: u32 = 120;
+
+/// Fields of a CSV line; fields may be quoted with `"`, doubling quotes inside.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields.iter().map(|f| f.trim().to_string()).collect()
+}
+
+/// Function code and 1-based register of a register map address. `30001`-style addresses
+/// (and `300001`-style ones) are input registers, `40001`-style ones holding registers; other
+/// numbers are registers of `default_function`.
+fn register_address(text: &str, default_function: u8) -> Result<(u8, u32), String> {
+    let number: u32 = text.parse().map_err(|_| format!("'{}' is not a register address", text))?;
+    let (function, register) = match (text.len(), number) {
+        (5, 30001..=39999) => (4, number - 30000),
+        (5, 40001..=49999) => (3, number - 40000),
+        (6, 300001..=365536) => (4, number - 300000),
+        (6, 400001..=465536) => (3, number - 400000),
+        (_, 0) => return Err("registers are numbered from 1".to_string()),
+        _ => (default_function, number),
+    };
+    Ok((function, register))
+}
+
+/// Reads a register map: CSV with a header row naming the `address`, `type`, `scaling` and
+/// `name` columns (any order, `scaling` and an optional `units` column may be left empty).
+pub fn parse_register_map(text: &str, default_function: u8) -> Result<Vec<RegisterEntry>, String> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines.next().ok_or("The register map is empty")?;
+    let header: Vec<String> = csv_fields(header).iter().map(|h| h.to_ascii_lowercase()).collect();
+    let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+    let required = |names: &[&str]| column(names).ok_or_else(|| format!("The register map has no '{}' column", names[0]));
+    let (address, kind, name) = (required(&["address", "register"])?, required(&["type", "format"])?, required(&["name"])?);
+    let (scaling, units) = (column(&["scaling", "scale", "multiplier"]), column(&["units", "unit"]));
+
+    let mut entries: Vec<RegisterEntry> = Vec::new();
+    for (index, line) in lines {
+        let fields = csv_fields(line);
+        let field = |i: usize| fields.get(i).map(String::as_str).unwrap_or_default();
+        let fail = |e: String| format!("line {}: {}", index + 1, e);
+        let (function, register) = register_address(field(address), default_function).map_err(fail)?;
+        let format: RegisterFormat = field(kind).parse().map_err(fail)?;
+        let scale = scaling.map(field).filter(|s| !s.is_empty() && s.parse::<f64>().ok() != Some(1.0));
+        if let Some(scale) = scale {
+            scale.parse::<f64>().map_err(|_| fail(format!("'{}' is not a scaling factor", scale)))?;
+        }
+        let value: SensorValue = field(name).parse().map_err(fail)?;
+        let value = SensorValue { units: units.map(field).filter(|u| !u.is_empty()).map(str::to_string), ..value };
+        if let Some(other) = entries.iter().find(|e| e.value.suffix.eq_ignore_ascii_case(&value.suffix)) {
+            return Err(fail(format!("'{}' is already the name of register {}", value.suffix, other.register)));
+        }
+        let end = register + format.registers();
+        if let Some(other) = entries.iter().find(|e| e.function == function && e.register < end && register < e.register + e.format.registers()) {
+            return Err(fail(format!("register {} overlaps '{}'", register, other.value.suffix)));
+        }
+        entries.push(RegisterEntry { register, function, format, scale: scale.map(str::to_string), value });
+    }
+    Ok(entries)
+}
+
+/// A Modbus device read according to a register map, in the order of `entries`. Each run of
+/// contiguous registers with the same function and format takes one `ModbusMaster` call with
+/// its own result code; scaled values are scaled only after a successful read.
+pub fn register_map(name: &str, description: &str, port: &str, baud: u32, address: u8, entries: &[RegisterEntry]) -> Result<SensorBlock, String> {
+    check_name(name)?;
+    if entries.is_empty() {
+        return Err("The register map has no registers".to_string());
+    }
+    // (first entry, number of entries)
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        match runs.last_mut() {
+            Some((first, count))
+                if entries[i - 1].function == entry.function
+                    && entries[i - 1].format == entry.format
+                    && entries[i - 1].register + entry.format.registers() == entry.register
+                    && (*count as u32 + 1) * entry.format.registers() <= MAX_REGISTERS
+                    && *first + *count == i =>
+            {
+                *count += 1
+            }
+            _ => runs.push((i, 1)),
+        }
+    }
+
+    let result = format!("{}_Result", name);
+    let result_of = |run: usize| if runs.len() == 1 { result.clone() } else { format!("{}({})", result, run + 1) };
+    let mut measurement = Vec::new();
+    for (run, &(first, count)) in runs.iter().enumerate() {
+        let entry = &entries[first];
+        measurement.push(format!(
+            "ModbusMaster({}, {}, {}, {}, {}, {}({}), {}, {}, 3, 100, {})",
+            result_of(run),
+            port,
+            baud,
+            address,
+            entry.function,
+            name,
+            first + 1,
+            entry.register,
+            count,
+            entry.format.modbus_option()
+        ));
+    }
+    for (run, &(first, count)) in runs.iter().enumerate() {
+        let scaled: Vec<&RegisterEntry> = entries[first..first + count].iter().filter(|e| e.scale.is_some()).collect();
+        if scaled.is_empty() {
+            continue;
+        }
+        measurement.push(format!("If {} = 0 Then", result_of(run)));
+        for entry in scaled {
+            measurement.push(format!("  {0}_{1} = {0}_{1} * {2}", name, entry.value.suffix, entry.scale.as_deref().unwrap_or_default()));
+        }
+        measurement.push("EndIf".to_string());
+    }
+    Ok(SensorBlock {
+        name: name.to_string(),
+        values: entries.iter().map(|e| e.value.clone()).collect(),
+        declarations: vec![if runs.len() == 1 {
+            format!("Public {} As Long", result)
+        } else {
+            format!("Public {}({}) As Long", result, runs.len())
+        }],
+        comment: format!(
+            "{} on {}, Modbus address {}, {} values in {} ModbusMaster call(s)",
+            description,
+            port,
+            address,
+            entries.len(),
+            runs.len()
+        ),
+        measurement,
+    })
+}
+
+/// Where to put a sensor's code.
+#[derive(Debug, Clone, Default)]
+pub struct Placement {
+    /// Data table for the values; the first table if `None`.
+    pub table: Option<String>,
+    pub processing: Processing,
+}
+
+fn indent_of(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Line to insert before `line` so that comment lines directly above stay attached to it.
+fn above_comments(lines: &[&str], mut line: u32) -> u32 {
+    while line > 0 && is_comment_line(lines[line as usize - 1]) {
+        line -= 1;
+    }
+    line
+}
+
+/// Inserts `block` into `source`.
+pub fn add_sensor(source: &str, block: &SensorBlock, placement: &Placement) -> Result<Vec<LineEdit>, String> {
+    let program = parse(source);
+    ensure_parses(&program)?;
+    let taken = global_names(&program);
+    if let Some(name) = block.declared_names().iter().find(|n| taken.contains(&n.to_ascii_lowercase())) {
+        return Err(format!("'{}' is already declared", name));
+    }
+
+    let lines: Vec<&str> = source.lines().collect();
+    let newline = newline_of(source);
+    let text = |indent: &str, lines: &[String]| lines.iter().map(|l| format!("{}{}{}", indent, l, newline)).collect::<String>();
+    let mut edits = Vec::new();
+
+    // Declarations, before the first table, routine or the program.
+    let first_block = program
+        .body
+        .iter()
+        .find(|s| matches!(s.kind, StmtKind::DataTable(_) | StmtKind::Routine(_) | StmtKind::BeginProg(_)))
+        .ok_or("The program has no BeginProg")?;
+    let at = above_comments(&lines, first_block.span.line);
+    edits.push(LineEdit { start_line: at, end_line: at, new_text: format!("{}{}", text("", &block.declaration_lines()), newline) });
+
+    // Output, at the end of the table.
+    if let Some(output) = block.output_line(placement.processing) {
+        let table = program
+            .body
+            .iter()
+            .filter_map(|s| match &s.kind {
+                StmtKind::DataTable(t) => Some(t),
+                _ => None,
+            })
+            .find(|t| placement.table.as_ref().is_none_or(|name| t.name.text.eq_ignore_ascii_case(name)));
+        let table = match (table, &placement.table) {
+            (Some(table), _) => table,
+            (None, Some(name)) => return Err(format!("The program has no DataTable '{}'", name)),
+            (None, None) => return Err("The program has no DataTable for the sensor's values".to_string()),
+        };
+        let end = table.end.ok_or("The DataTable is not closed")?.line;
+        let indent = table.body.last().map_or("  ", |s| indent_of(lines[s.span.line as usize]));
+        edits.push(LineEdit { start_line: end, end_line: end, new_text: text(indent, &[output]) });
+    }
+
+    // Measurement, in the main scan before the first CallTable.
+    let scan = program
+        .body
+        .iter()
+        .find_map(|s| match &s.kind {
+            StmtKind::BeginProg(prog) => prog.body.iter().find_map(|s| match &s.kind {
+                StmtKind::Scan(scan) => Some(scan),
+                _ => None,
+            }),
+            _ => None,
+        })
+        .ok_or("The program has no Scan in BeginProg")?;
+    let call_table = scan.body.iter().find(|s| matches!(&s.kind, StmtKind::Call(call) if call.name.text.eq_ignore_ascii_case("CallTable")));
+    let (at, indent) = match (call_table, scan.end) {
+        (Some(stmt), _) => (above_comments(&lines, stmt.span.line), indent_of(lines[stmt.span.line as usize]).to_string()),
+        (None, Some(end)) => {
+            let indent = scan.body.last().map_or_else(|| format!("{}  ", indent_of(lines[end.line as usize])), |s| indent_of(lines[s.span.line as usize]).to_string());
+            (end.line, indent)
+        }
+        (None, None) => return Err("The main Scan is not closed".to_string()),
+    };
+    edits.push(LineEdit { start_line: at, end_line: at, new_text: text(&indent, &block.measurement_lines()) });
+    Ok(edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::refactor::apply_edits;
+
+    const PROGRAM: &str = "\
+Public PTemp
+Units PTemp = Deg C
+
+'Hourly data
+DataTable(Hourly,True,-1)
+  DataInterval(0,60,Min,10)
+  Average(1,PTemp,FP2,False)
+EndTable
+
+BeginProg
+  Scan(5,Sec,0,0)
+    PanelTemp(PTemp,60)
+    CallTable Hourly
+  NextScan
+EndProg
+";
+
+    #[test]
+    fn adds_an_sdi12_sensor() {
+        let model = sdi12_model("cs655").unwrap();
+        let values = model.values.iter().map(|(suffix, units)| SensorValue { suffix: suffix.to_string(), units: Some(units.to_string()) }).collect();
+        let block = sdi12("Soil", model.name, '0', "C1", model.command, values).unwrap();
+        let out = apply_edits(PROGRAM, &add_sensor(PROGRAM, &block, &Placement::default()).unwrap());
+        assert_eq!(
+            out,
+            "\
+Public PTemp
+Units PTemp = Deg C
+
+'CS655 on C1, SDI-12 address 0
+Public Soil(3)
+Alias Soil(1) = Soil_VWC
+Alias Soil(2) = Soil_EC
+Alias Soil(3) = Soil_T
+Units Soil_VWC = m^3/m^3
+Units Soil_EC = dS/m
+Units Soil_T = Deg C
+
+'Hourly data
+DataTable(Hourly,True,-1)
+  DataInterval(0,60,Min,10)
+  Average(1,PTemp,FP2,False)
+  Average(3, Soil(), FP2, False)
+EndTable
+
+BeginProg
+  Scan(5,Sec,0,0)
+    PanelTemp(PTemp,60)
+    'CS655 on C1, SDI-12 address 0
+    SDI12Recorder(Soil(), C1, \"0\", \"M!\", 1.0, 0)
+    CallTable Hourly
+  NextScan
+EndProg
+"
+        );
+        assert!(parse(&out).errors.is_empty());
+        assert_eq!(add_sensor(&out, &block, &Placement::default()), Err("'Soil' is already declared".to_string()));
+    }
+
+    #[test]
+    fn adds_a_modbus_device() {
+        let device = ModbusDevice { port: "ComC1".to_string(), baud: 9600, address: 7, function: 3, format: RegisterFormat::FloatCdab };
+        let values = ["Flow", "Total"].iter().map(|s| SensorValue { suffix: s.to_string(), units: None }).collect();
+        let block = modbus("Meter", "Flow meter", &device, 1, values).unwrap();
+        let placement = Placement { table: Some("hourly".to_string()), processing: Processing::Sample };
+        let out = apply_edits(PROGRAM, &add_sensor(PROGRAM, &block, &placement).unwrap());
+        assert!(out.contains("Public Meter(2)\nPublic Meter_Result As Long\nAlias Meter(1) = Meter_Flow\n"), "{out}");
+        assert!(out.contains("    ModbusMaster(Meter_Result, ComC1, 9600, 7, 3, Meter(), 1, 2, 3, 100, 0)\n    CallTable Hourly\n"), "{out}");
+        assert!(out.contains("  Sample(2, Meter(), FP2)\nEndTable"));
+        assert!(parse(&out).errors.is_empty());
+
+        let placement = Placement { table: Some("Daily".to_string()), processing: Processing::Average };
+        assert!(add_sensor(PROGRAM, &block, &placement).is_err());
+    }
+
+    #[test]
+    fn reads_a_register_map() {
+        let map = "Address,Type,Scaling,Name,Units\n\
+40001,float,,Voltage,V\n\
+40003,float,1,Current,A\n\
+40011,int16,0.1,Freq,Hz\n\
+40012,int16,\"0.01\",PF,\n\
+30001,uint16,,Status,\n";
+        let entries = parse_register_map(map, 3).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!((entries[2].function, entries[2].register, entries[2].format), (3, 11, RegisterFormat::Int16));
+        assert_eq!(entries[1].scale, None);
+        assert_eq!(entries[3].scale.as_deref(), Some("0.01"));
+        assert_eq!((entries[4].function, entries[4].register), (4, 1));
+        assert_eq!(entries[0].value.units.as_deref(), Some("V"));
+
+        let block = register_map("Meter", "Power meter", "ComC1", 19200, 2, &entries).unwrap();
+        assert_eq!(
+            block.measurement,
+            [
+                "ModbusMaster(Meter_Result(1), ComC1, 19200, 2, 3, Meter(1), 1, 2, 3, 100, 0)",
+                "ModbusMaster(Meter_Result(2), ComC1, 19200, 2, 3, Meter(3), 11, 2, 3, 100, 1)",
+                "ModbusMaster(Meter_Result(3), ComC1, 19200, 2, 4, Meter(5), 1, 1, 3, 100, 10)",
+                "If Meter_Result(2) = 0 Then",
+                "  Meter_Freq = Meter_Freq * 0.1",
+                "  Meter_PF = Meter_PF * 0.01",
+                "EndIf",
+            ]
+        );
+        assert!(block.declared_names().contains(&"Meter_Result".to_string()));
+        let out = apply_edits(PROGRAM, &add_sensor(PROGRAM, &block, &Placement::default()).unwrap());
+        assert!(out.contains("    If Meter_Result(2) = 0 Then\n      Meter_Freq = Meter_Freq * 0.1\n"), "{out}");
+        assert!(parse(&out).errors.is_empty(), "{out}");
+    }
+
+    #[test]
+    fn rejects_bad_register_maps() {
+        assert!(parse_register_map("Address,Type,Name\n40001,float,A\n40002,float,B\n", 3).unwrap_err().contains("overlaps"));
+        assert!(parse_register_map("Address,Type,Name\n1,float,A\n3,float,a\n", 3).unwrap_err().starts_with("line 3:"));
+        assert!(parse_register_map("Register,Name\n1,A\n", 3).unwrap_err().contains("'type'"));
+        assert!(parse_register_map("Address,Type,Name\n0,int16,A\n", 3).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+// FILE: crbrs-syntax/src/spelling.rs
+
+//! The `spelling` lint rule: common misspellings in comments and string literals.
+//!
+//! Table and field descriptions, units and other strings end up in every file a logger
+//! writes, so a typo there is published with the data. The bundled dictionary
+//! (`misspellings.txt`) lists misspellings with their corrections rather than every English
+//! word, so the rule has no false positives on jargon, site names or abbreviations; words a
+//! project spells on purpose are accepted through its word list. Only whole words are
+//! checked: identifiers in comments (`AirTemp_Avg`, `Temprature2`) are left alone.
+//!
+//! The rule is off unless a level is configured for it, and is run by `crbrs-lib`, which
+//! reads the project's word list.
+
+use crate::lexer::{tokenize, Span, TokenKind};
+use crate::lint::{rule, LintDiagnostic};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+const MISSPELLINGS: &str = include_str!("misspellings.txt");
+
+/// Misspelling -> correction, both lower case.
+fn dictionary() -> &'static HashMap<&'static str, &'static str> {
+    static DICTIONARY: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+    DICTIONARY.get_or_init(|| {
+        MISSPELLINGS
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once(' '))
+            .collect()
+    })
+}
+
+/// The correction of `word`, in its case (`Temprature` -> `Temperature`), if it is a known
+/// misspelling.
+pub fn correction(word: &str) -> Option<String> {
+    let correction = dictionary().get(word.to_ascii_lowercase().as_str())?;
+    Some(if word.len() > 1 && word.chars().all(|c| c.is_ascii_uppercase()) {
+        correction.to_ascii_uppercase()
+    } else if word.starts_with(|c: char| c.is_ascii_uppercase()) {
+        let mut chars = correction.chars();
+        chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+    } else {
+        correction.to_string()
+    })
+}
+
+/// Misspelled words in the comments and string literals of `source`. `accepted` holds the
+/// project's words, in lower case; they are never reported.
+pub fn check(source: &str, accepted: &HashSet<String>) -> Vec<LintDiagnostic> {
+    let (severity, code) = rule("spelling").map(|r| (r.default_severity, r.code)).expect("the spelling rule is registered");
+    let mut out = Vec::new();
+    for token in tokenize(source) {
+        if !matches!(token.kind, TokenKind::Comment | TokenKind::String) {
+            continue;
+        }
+        let text = token.text.as_str();
+        let mut start = 0;
+        // Words are runs of letters, digits and underscores; only purely alphabetic ones are
+        // checked, so names and numbers in the text are skipped.
+        for piece in text.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+            let offset = start;
+            start += piece.len() + text[offset + piece.len()..].chars().next().map_or(0, char::len_utf8);
+            if piece.is_empty() || !piece.chars().all(|c| c.is_ascii_alphabetic()) || accepted.contains(&piece.to_ascii_lowercase()) {
+                continue;
+            }
+            let Some(correction) = correction(piece) else { continue };
+            let span = Span {
+                start: token.span.start + offset,
+                end: token.span.start + offset + piece.len(),
+                line: token.span.line,
+                col: token.span.col + text[..offset].chars().count() as u32,
+            };
+            out.push(LintDiagnostic {
+                rule: "spelling",
+                code,
+                severity,
+                message: format!("'{}' is misspelled; did you mean '{}'?", piece, correction),
+                span,
+                related: Vec::new(),
+            });
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_misspellings_in_comments_and_strings_only() {
+        let src = "Public Temprature 'Air temprature, see Temprature2\n\
+                   DataTable(Hourly,True,-1)\n  Average(1,Temprature,FP2,False) : FieldNames(\"AirT:Air TEMPRATURE at 2 m\")\nEndTable\n";
+        let diagnostics = check(src, &HashSet::new());
+        let found: Vec<(&str, &str)> = diagnostics.iter().map(|d| (&src[d.span.start..d.span.end], d.message.as_str())).collect();
+        assert_eq!(
+            found,
+            [
+                ("temprature", "'temprature' is misspelled; did you mean 'temperature'?"),
+                ("TEMPRATURE", "'TEMPRATURE' is misspelled; did you mean 'TEMPERATURE'?"),
+            ]
+        );
+        assert_eq!((diagnostics[0].span.line, diagnostics[0].span.col), (0, 23));
+        assert!(check(src, &HashSet::from(["temprature".to_string()])).is_empty());
+        assert_eq!(correction("Seperate").as_deref(), Some("Separate"));
+    }
+}
@@ -0,0 +1,249 @@
+// FILE: crbrs-syntax/src/tables.rs
+
+//! Data table layouts derived from `DataTable ... EndTable` definitions.
+//!
+//! Field names follow the logger's conventions (`Sample` keeps the variable name, `Average`
+//! appends `_Avg`, repetitions over an array produce `T(1)`, `T(2)`, ...; `FieldNames`
+//! overrides the names of the preceding instruction). Less common output instructions with
+//! option-dependent layouts (`WindVector`, `Histogram`, `ETsz`) are approximated by their
+//! default layout.
+
+use crate::ast::*;
+use crate::lexer::Span;
+use crate::units;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableInfo {
+    pub name: String,
+    /// Trigger variable expression as written (`True` for unconditional tables).
+    pub trigger: String,
+    /// Size argument as written (`-1` = auto-allocate).
+    pub size: String,
+    /// Record interval from `DataInterval`, e.g. `60 Min`; `None` for event-driven tables.
+    pub interval: Option<String>,
+    pub fields: Vec<FieldInfo>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldInfo {
+    pub name: String,
+    /// Processing label as it appears in a TOA5 header (`Smp`, `Avg`, `Max`, ...).
+    pub processing: String,
+    /// Variable the field is computed from, as written.
+    pub source: String,
+    /// Units from the `Units` instruction (or a `'units:` comment) of the source variable.
+    pub units: Option<String>,
+    /// Output data type as written (`FP2`, `IEEE4`, ...).
+    pub data_type: Option<String>,
+    /// Description given through `FieldNames("Name:Description")`.
+    pub description: Option<String>,
+    /// Output instruction producing the field.
+    pub instruction: String,
+    pub span: Span,
+}
+
+/// Text of `expr` as written in `source`.
+pub fn expr_text<'a>(source: &'a str, expr: &Expr) -> &'a str {
+    source.get(expr.span.start..expr.span.end).unwrap_or("").trim()
+}
+
+/// Numeric values of `Const` declarations whose value is a literal (or a negated literal).
+pub fn numeric_constants(program: &Program) -> HashMap<String, f64> {
+    let mut out = HashMap::new();
+    walk_stmts(&program.body, &mut |stmt| {
+        if let StmtKind::Const(consts) = &stmt.kind {
+            for c in consts {
+                if let Some(value) = eval_number(&c.value, &out) {
+                    out.insert(c.name.text.to_ascii_lowercase(), value);
+                }
+            }
+        }
+    });
+    out
+}
+
+/// Evaluates a literal, a known constant or their negation.
+pub fn eval_number(expr: &Expr, constants: &HashMap<String, f64>) -> Option<f64> {
+    match &expr.kind {
+        ExprKind::Number(text) => text.parse().ok(),
+        ExprKind::Ref { name, args: None } => constants.get(&name.to_ascii_lowercase()).copied(),
+        ExprKind::Paren(inner) => eval_number(inner, constants),
+        ExprKind::Unary { op, expr } if op == "-" => eval_number(expr, constants).map(|v| -v),
+        _ => None,
+    }
+}
+
+/// Field suffixes and TOA5 processing labels of the output instructions.
+fn processing_of(instruction: &str) -> Option<(&'static str, &'static str)> {
+    let found = match instruction.to_ascii_lowercase().as_str() {
+        "sample" => ("", "Smp"),
+        "average" => ("_Avg", "Avg"),
+        "maximum" => ("_Max", "Max"),
+        "minimum" => ("_Min", "Min"),
+        "totalize" => ("_Tot", "Tot"),
+        "stddev" => ("_Std", "Std"),
+        "median" => ("_Med", "Med"),
+        "moment" => ("_Mom", "Mom"),
+        "covariance" => ("_Cov", "Cov"),
+        "samplemaxmin" => ("", "SMM"),
+        "histogram" => ("_Hst", "Hst"),
+        _ => return None,
+    };
+    Some(found)
+}
+
+/// Lays out every data table in `program`.
+pub fn tables(source: &str, program: &Program) -> Vec<TableInfo> {
+    let constants = numeric_constants(program);
+    let unit_annotations = units::annotations(program);
+    let unit_map = units::unit_map(&unit_annotations);
+    let unit_of = |name: &str| {
+        let base = name.split('(').next().unwrap_or(name).trim();
+        unit_map.get(&base.to_ascii_lowercase()).map(|u| u.to_string())
+    };
+
+    let mut out = Vec::new();
+    walk_stmts(&program.body, &mut |stmt| {
+        let StmtKind::DataTable(table) = &stmt.kind else { return };
+        let arg = |i: usize| table.args.get(i).map(|e| expr_text(source, e).to_string()).unwrap_or_default();
+        let mut info = TableInfo {
+            name: table.name.text.clone(),
+            trigger: arg(0),
+            size: arg(1),
+            interval: None,
+            fields: Vec::new(),
+            span: stmt.span,
+        };
+
+        // Fields added by the most recent output instruction, for FieldNames.
+        let mut last_start = 0;
+        for inner in &table.body {
+            let StmtKind::Call(call) = &inner.kind else { continue };
+            let text = |i: usize| call.args.get(i).map(|e| expr_text(source, e).to_string());
+            let lower = call.name.text.to_ascii_lowercase();
+            match lower.as_str() {
+                "datainterval" => {
+                    info.interval = match (text(1), text(2)) {
+                        (Some(interval), Some(unit)) => Some(format!("{} {}", interval, unit)),
+                        _ => None,
+                    };
+                    continue;
+                }
+                "fieldnames" => {
+                    let Some(ExprKind::Str(list)) = call.args.first().map(|e| &e.kind) else { continue };
+                    let fields = &mut info.fields[last_start..];
+                    for (field, spec) in fields.iter_mut().zip(list.split(',')) {
+                        let mut parts = spec.splitn(2, ':');
+                        let name = parts.next().unwrap_or_default().trim();
+                        if !name.is_empty() {
+                            field.name = name.to_string();
+                        }
+                        field.description = parts.next().map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            last_start = info.fields.len();
+            let mut push = |name: String, processing: &str, source_var: &str, data_type: Option<String>| {
+                info.fields.push(FieldInfo {
+                    units: unit_of(source_var),
+                    name,
+                    processing: processing.to_string(),
+                    source: source_var.to_string(),
+                    data_type,
+                    description: None,
+                    instruction: call.name.text.clone(),
+                    span: inner.span,
+                });
+            };
+
+            if lower == "windvector" {
+                let speed = text(1).unwrap_or_default();
+                let dir = text(2).unwrap_or_default();
+                let data_type = text(3);
+                push(format!("{}_S_WVT", speed), "WVc", &speed, data_type.clone());
+                push(format!("{}_D1_WVT", dir), "WVc", &dir, data_type.clone());
+                push(format!("{}_SD1_WVT", dir), "WVc", &dir, data_type);
+                continue;
+            }
+            if lower == "etsz" {
+                push("ETos".to_string(), "ETsz", "", text(9));
+                push("Rso".to_string(), "ETsz", "", text(9));
+                continue;
+            }
+            let Some((suffix, processing)) = processing_of(&lower) else { continue };
+            let ins = crate::instructions::lookup(&call.name.text);
+            let param = |name: &str| ins.and_then(|i| i.params.iter().position(|p| *p == name));
+            let source_idx = param("Source").or_else(|| param("BinSelect")).unwrap_or(1);
+            let Some(source_expr) = call.args.get(source_idx) else { continue };
+            let data_type = param("DataType").and_then(&text);
+            let reps = param("Reps")
+                .and_then(|i| call.args.get(i))
+                .and_then(|e| eval_number(e, &constants))
+                .map_or(1, |r| r.max(1.0) as usize);
+
+            // `T`, `T()` and `T(3)` name fields after `T`; other sources keep their text.
+            let (base, explicit_index) = match &source_expr.kind {
+                ExprKind::Ref { name, args: None } => (name.clone(), None),
+                ExprKind::Ref { name, args: Some(args) } if args.is_empty() => (name.clone(), None),
+                ExprKind::Ref { name, args: Some(args) } if args.len() == 1 => {
+                    (name.clone(), eval_number(&args[0], &constants).map(|i| i as usize))
+                }
+                _ => (expr_text(source, source_expr).to_string(), None),
+            };
+            for rep in 0..reps {
+                let name = match explicit_index {
+                    Some(first) => format!("{}{}({})", base, suffix, first + rep),
+                    None if reps > 1 => format!("{}{}({})", base, suffix, rep + 1),
+                    None => format!("{}{}", base, suffix),
+                };
+                push(name, processing, &base, data_type.clone());
+            }
+
+            // Maximum/Minimum with the Time option add a time-of-extreme field per value.
+            let time_flag = param("Time").and_then(|i| call.args.get(i)).map(|e| expr_text(source, e).to_ascii_lowercase());
+            if matches!(time_flag.as_deref(), Some("true" | "-1" | "1")) {
+                let (t_suffix, t_proc) = if lower == "maximum" { ("_TMx", "TMx") } else { ("_TMn", "TMn") };
+                let values: Vec<String> = info.fields[last_start..].iter().map(|f| f.name.replace(suffix, t_suffix)).collect();
+                for name in values {
+                    info.fields.push(FieldInfo {
+                        name,
+                        processing: t_proc.to_string(),
+                        source: base.clone(),
+                        units: None,
+                        data_type: None,
+                        description: None,
+                        instruction: call.name.text.clone(),
+                        span: inner.span,
+                    });
+                }
+            }
+        }
+        out.push(info);
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn lays_out_fields() {
+        let src = "Const N = 2\nPublic T(N), Batt\nUnits T = degC\nDataTable(Hourly,True,-1)\n  DataInterval(0,60,Min,10)\n  Average(N,T(),FP2,False)\n  Minimum(1,Batt,FP2,False,True)\n  Sample(1,Batt,IEEE4)\n  FieldNames(\"BattNow:Battery voltage at output\")\nEndTable\n";
+        let tables = tables(src, &parse(src));
+        let t = &tables[0];
+        assert_eq!(t.interval.as_deref(), Some("60 Min"));
+        let names: Vec<&str> = t.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["T_Avg(1)", "T_Avg(2)", "Batt_Min", "Batt_TMn", "BattNow"]);
+        assert_eq!(t.fields[0].units.as_deref(), Some("degC"));
+        assert_eq!(t.fields[4].description.as_deref(), Some("Battery voltage at output"));
+        assert_eq!(t.fields[4].processing, "Smp");
+    }
+}
@@ -0,0 +1,149 @@
+// FILE: crbrs-syntax/src/units.rs
+
+//! Unit annotations and a small unit inference used by the `unit-*` lint rules.
+//!
+//! Units come from two places: the `Units Name = text` instruction (which also ends up in
+//! table headers) and a trailing `'units: text` comment on a `Public`/`Dim` line, which
+//! applies to every variable declared on that line. Inference is deliberately shallow: only
+//! `+`, `-`, comparisons and parentheses propagate units; anything involving `*`, `/`,
+//! functions or literals is treated as unknown so it never produces false positives.
+
+use crate::ast::*;
+use crate::lexer::Span;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum UnitSource {
+    /// The `Units` instruction.
+    Instruction,
+    /// A `'units:` comment.
+    Comment,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnitAnnotation {
+    /// Variable name as written.
+    pub name: String,
+    /// Unit text with surrounding whitespace removed.
+    pub unit: String,
+    pub source: UnitSource,
+    pub span: Span,
+}
+
+/// Parses the unit out of a `'units: W/m^2` comment.
+pub fn unit_from_comment(comment: &str) -> Option<&str> {
+    let body = comment.strip_prefix('\'').unwrap_or(comment).trim_start();
+    let (key, unit) = body.split_once(':')?;
+    let unit = unit.trim();
+    (key.trim().eq_ignore_ascii_case("units") && !unit.is_empty()).then_some(unit)
+}
+
+/// Every unit annotation in `program`, in source order.
+pub fn annotations(program: &Program) -> Vec<UnitAnnotation> {
+    let comment_units: HashMap<u32, (&str, Span)> = program
+        .comments
+        .iter()
+        .filter_map(|c| unit_from_comment(&c.text).map(|u| (c.span.line, (u, c.span))))
+        .collect();
+
+    let mut out = Vec::new();
+    walk_stmts(&program.body, &mut |stmt| match &stmt.kind {
+        StmtKind::Units { names, units } => {
+            for name in names {
+                out.push(UnitAnnotation {
+                    name: name.text.clone(),
+                    unit: units.trim().to_string(),
+                    source: UnitSource::Instruction,
+                    span: name.span,
+                });
+            }
+        }
+        StmtKind::Declare(decl) => {
+            if let Some((unit, span)) = comment_units.get(&stmt.span.line) {
+                for var in &decl.vars {
+                    out.push(UnitAnnotation { name: var.name.text.clone(), unit: unit.to_string(), source: UnitSource::Comment, span: *span });
+                }
+            }
+        }
+        _ => {}
+    });
+    out
+}
+
+/// Compares units ignoring whitespace (`W/m^2` and `W / m^2` are the same).
+pub fn same_unit(a: &str, b: &str) -> bool {
+    a.chars().filter(|c| !c.is_whitespace()).eq(b.chars().filter(|c| !c.is_whitespace()))
+}
+
+/// Unit lookup keyed by lower-case variable name. The `Units` instruction wins over comments.
+pub fn unit_map(annotations: &[UnitAnnotation]) -> HashMap<String, &str> {
+    let mut map: HashMap<String, &str> = HashMap::new();
+    for a in annotations.iter().filter(|a| a.source == UnitSource::Comment) {
+        map.insert(a.name.to_ascii_lowercase(), &a.unit);
+    }
+    for a in annotations.iter().filter(|a| a.source == UnitSource::Instruction) {
+        map.insert(a.name.to_ascii_lowercase(), &a.unit);
+    }
+    map
+}
+
+/// A place where two units meet and disagree.
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub op: String,
+    pub left: String,
+    pub right: String,
+    pub span: Span,
+}
+
+/// Infers the unit of `expr`, recording mismatches found along the way.
+pub fn infer(expr: &Expr, units: &HashMap<String, &str>, mismatches: &mut Vec<Mismatch>) -> Option<String> {
+    match &expr.kind {
+        ExprKind::Ref { name, .. } => units.get(&name.to_ascii_lowercase()).map(|u| u.to_string()),
+        ExprKind::Paren(inner) => infer(inner, units, mismatches),
+        ExprKind::Unary { op, expr } if op == "-" || op == "+" => infer(expr, units, mismatches),
+        ExprKind::Binary { op, lhs, rhs } => {
+            let left = infer(lhs, units, mismatches);
+            let right = infer(rhs, units, mismatches);
+            let additive = matches!(op.as_str(), "+" | "-");
+            let comparison = matches!(op.as_str(), "=" | "<>" | "<" | ">" | "<=" | ">=");
+            if !additive && !comparison {
+                return None;
+            }
+            if let (Some(l), Some(r)) = (&left, &right) {
+                if !same_unit(l, r) {
+                    mismatches.push(Mismatch { op: op.clone(), left: l.clone(), right: r.clone(), span: expr.span });
+                    return None;
+                }
+            }
+            if additive {
+                left.or(right)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn collects_annotations_and_infers_sums() {
+        let program = parse("Public AirT, PanelT 'units: degC\nPublic BattV\nUnits BattV = V\nAirT = PanelT + BattV\n");
+        let ann = annotations(&program);
+        let names: Vec<(&str, &str)> = ann.iter().map(|a| (a.name.as_str(), a.unit.as_str())).collect();
+        assert_eq!(names, vec![("AirT", "degC"), ("PanelT", "degC"), ("BattV", "V")]);
+
+        let map = unit_map(&ann);
+        let StmtKind::Assign { value, .. } = &program.body[3].kind else { panic!() };
+        let mut mismatches = Vec::new();
+        assert_eq!(infer(value, &map, &mut mismatches), None);
+        assert_eq!((mismatches[0].left.as_str(), mismatches[0].right.as_str()), ("degC", "V"));
+        assert!(same_unit("W/m^2", "W / m^2"));
+    }
+}
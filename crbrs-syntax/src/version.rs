@@ -0,0 +1,333 @@
+// FILE: crbrs-syntax/src/version.rs
+
+//! Structured program version header.
+//!
+//! The header is a comment block in the declarations section followed by a string constant
+//! the program can store in a table:
+//!
+//! ```text
+//! ' --- Version ---
+//! ' Version: 1.2.0
+//! ' Date: 2026-10-16
+//! ' Author: Jane Doe
+//! ' Changelog:
+//! '   1.2.0 | 2026-10-16 | Jane Doe | Added soil moisture sensors
+//! '   1.1.0 | 2026-09-01 | Jane Doe | Initial deployment
+//! ' ---
+//! Const ProgramVersion = "1.2.0"
+//! ```
+//!
+//! `header` reads it (reporting problems for the `version-header` lint rule) and `bump`
+//! rewrites it with a new version and changelog entry.
+
+use crate::ast::*;
+use crate::lexer::Span;
+use crate::parser::parse;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// Name of the constant that mirrors the header's version.
+pub const VERSION_CONST: &str = "ProgramVersion";
+
+const BEGIN_MARKER: &str = "--- Version ---";
+const END_MARKER: &str = "---";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangelogEntry {
+    pub version: String,
+    pub date: String,
+    pub author: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionHeader {
+    pub version: String,
+    pub date: String,
+    pub author: String,
+    /// Newest first.
+    pub changelog: Vec<ChangelogEntry>,
+    /// Value of the `ProgramVersion` constant, if declared.
+    pub constant: Option<String>,
+    /// From the start of the begin marker to the end of the end marker (or the last line
+    /// read); `line`/`col` are those of the begin marker.
+    pub span: Span,
+    /// Last line of the comment block (inclusive).
+    pub end_line: u32,
+    /// Span of the constant's value, if declared.
+    pub constant_span: Option<Span>,
+    pub closed: bool,
+    /// Problems found while reading the block, for the `version-header` lint rule.
+    pub problems: Vec<(Span, String)>,
+}
+
+/// Version component to increment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BumpLevel {
+    Major,
+    Minor,
+    Patch,
+}
+
+impl FromStr for BumpLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "major" => Ok(BumpLevel::Major),
+            "minor" => Ok(BumpLevel::Minor),
+            "patch" => Ok(BumpLevel::Patch),
+            other => Err(format!("unknown bump level '{}' (expected major, minor or patch)", other)),
+        }
+    }
+}
+
+fn comment_body(text: &str) -> &str {
+    let body = match text.strip_prefix('\'') {
+        Some(rest) => rest,
+        None if text.len() >= 3 && text[..3].eq_ignore_ascii_case("rem") => &text[3..],
+        None => text,
+    };
+    body.trim()
+}
+
+fn parse_semver(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.').map(|p| p.parse::<u64>().ok());
+    let parsed = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(parsed)
+}
+
+fn is_date(date: &str) -> bool {
+    let b = date.as_bytes();
+    if b.len() != 10 || b[4] != b'-' || b[7] != b'-' {
+        return false;
+    }
+    let (Ok(_), Ok(month), Ok(day)) = (date[..4].parse::<u32>(), date[5..7].parse::<u32>(), date[8..].parse::<u32>()) else {
+        return false;
+    };
+    (1..=12).contains(&month) && (1..=31).contains(&day)
+}
+
+/// Orders two `MAJOR.MINOR.PATCH` versions; `None` if either is malformed.
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    Some(parse_semver(a)?.cmp(&parse_semver(b)?))
+}
+
+/// Returns `version` with the given component incremented and lower components reset.
+pub fn bump_version(version: &str, level: BumpLevel) -> Result<String, String> {
+    let (major, minor, patch) =
+        parse_semver(version).ok_or_else(|| format!("'{}' is not a MAJOR.MINOR.PATCH version", version))?;
+    Ok(match level {
+        BumpLevel::Major => format!("{}.0.0", major + 1),
+        BumpLevel::Minor => format!("{}.{}.0", major, minor + 1),
+        BumpLevel::Patch => format!("{}.{}.{}", major, minor, patch + 1),
+    })
+}
+
+/// Reads the version header of `program`, or `None` if it has none.
+pub fn header(program: &Program) -> Option<VersionHeader> {
+    let comments = &program.comments;
+    let start = comments
+        .iter()
+        .position(|c| c.span.col == 0 && comment_body(&c.text).eq_ignore_ascii_case(BEGIN_MARKER))?;
+    let begin = comments[start].span;
+    let mut header = VersionHeader {
+        version: String::new(),
+        date: String::new(),
+        author: String::new(),
+        changelog: Vec::new(),
+        constant: None,
+        span: begin,
+        end_line: begin.line,
+        constant_span: None,
+        closed: false,
+        problems: Vec::new(),
+    };
+
+    let mut in_changelog = false;
+    for comment in &comments[start + 1..] {
+        // The block is a run of whole-line comments on consecutive lines.
+        if comment.span.col != 0 || comment.span.line != header.end_line + 1 {
+            break;
+        }
+        header.span.end = comment.span.end;
+        header.end_line = comment.span.line;
+        let body = comment_body(&comment.text);
+        if body == END_MARKER {
+            header.closed = true;
+            break;
+        }
+        if in_changelog && body.contains('|') {
+            let fields: Vec<&str> = body.splitn(4, '|').map(str::trim).collect();
+            if fields.len() < 4 {
+                header.problems.push((comment.span, "Changelog entries are 'version | date | author | message'".to_string()));
+                continue;
+            }
+            header.changelog.push(ChangelogEntry {
+                version: fields[0].to_string(),
+                date: fields[1].to_string(),
+                author: fields[2].to_string(),
+                message: fields[3].to_string(),
+            });
+            continue;
+        }
+        match body.split_once(':').map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string())) {
+            Some((key, value)) if key == "version" => header.version = value,
+            Some((key, value)) if key == "date" => header.date = value,
+            Some((key, value)) if key == "author" => header.author = value,
+            Some((key, _)) if key == "changelog" => in_changelog = true,
+            _ => header.problems.push((comment.span, format!("Unexpected line in version header: '{}'", body))),
+        }
+    }
+
+    for stmt in &program.body {
+        if let StmtKind::Const(consts) = &stmt.kind {
+            if let Some(c) = consts.iter().find(|c| c.name.is(VERSION_CONST)) {
+                header.constant_span = Some(c.value.span);
+                match &c.value.kind {
+                    ExprKind::Str(value) => header.constant = Some(value.clone()),
+                    _ => header.problems.push((c.value.span, format!("{} must be a string constant", VERSION_CONST))),
+                }
+            }
+        }
+    }
+
+    validate(&mut header, begin);
+    Some(header)
+}
+
+fn validate(header: &mut VersionHeader, begin: Span) {
+    let mut problems = Vec::new();
+    if !header.closed {
+        problems.push((begin, format!("Version header is not closed with a '{}' line", END_MARKER)));
+    }
+    if header.version.is_empty() {
+        problems.push((begin, "Version header has no 'Version:' line".to_string()));
+    } else if parse_semver(&header.version).is_none() {
+        problems.push((begin, format!("Version '{}' is not MAJOR.MINOR.PATCH", header.version)));
+    }
+    if !is_date(&header.date) {
+        problems.push((begin, format!("Date '{}' is not YYYY-MM-DD", header.date)));
+    }
+    if header.author.is_empty() {
+        problems.push((begin, "Version header has no 'Author:' line".to_string()));
+    }
+    match header.changelog.first() {
+        Some(latest) if latest.version != header.version => problems.push((
+            begin,
+            format!("Latest changelog entry is {} but the header says {}", latest.version, header.version),
+        )),
+        Some(_) => {}
+        None => problems.push((begin, "Version header has no changelog entries".to_string())),
+    }
+    match (&header.constant, header.constant_span) {
+        (Some(constant), Some(span)) if *constant != header.version => problems.push((
+            span,
+            format!("{} is \"{}\" but the header says {}", VERSION_CONST, constant, header.version),
+        )),
+        (None, None) => problems.push((begin, format!("No '{}' constant mirrors the header version", VERSION_CONST))),
+        _ => {}
+    }
+    header.problems.extend(problems);
+}
+
+fn render_block(version: &str, date: &str, author: &str, changelog: &[ChangelogEntry], newline: &str) -> String {
+    let mut lines = vec![
+        format!("' {}", BEGIN_MARKER),
+        format!("' Version: {}", version),
+        format!("' Date: {}", date),
+        format!("' Author: {}", author),
+        "' Changelog:".to_string(),
+    ];
+    for entry in changelog {
+        lines.push(format!("'   {} | {} | {} | {}", entry.version, entry.date, entry.author, entry.message));
+    }
+    lines.push(format!("' {}", END_MARKER));
+    lines.join(newline)
+}
+
+/// Bumps the version header of `source`, adding a changelog entry, and returns the new
+/// source and version. A program without a header gets one, starting at 1.0.0, inserted
+/// after its leading comments.
+pub fn bump(source: &str, level: BumpLevel, date: &str, author: &str, message: &str) -> Result<(String, String), String> {
+    // '|' separates changelog fields.
+    let author = author.replace('|', "/");
+    let message = message.replace('|', "/");
+    let newline = if source.contains("\r\n") { "\r\n" } else { "\n" };
+    let program = parse(source);
+
+    let Some(existing) = header(&program) else {
+        let version = "1.0.0".to_string();
+        let entry = ChangelogEntry { version: version.clone(), date: date.to_string(), author: author.clone(), message };
+        let block = render_block(&version, date, &author, &[entry], newline);
+        let at = leading_comments_end(source);
+        let inserted = format!("{block}{nl}Const {} = \"{version}\"{nl}{nl}", VERSION_CONST, nl = newline);
+        return Ok((format!("{}{}{}", &source[..at], inserted, &source[at..]), version));
+    };
+
+    if !existing.closed {
+        return Err(format!("the version header is not closed with a '{}' line", END_MARKER));
+    }
+    let version = bump_version(&existing.version, level)?;
+    let mut changelog = vec![ChangelogEntry { version: version.clone(), date: date.to_string(), author: author.clone(), message }];
+    changelog.extend(existing.changelog.iter().cloned());
+    let block = render_block(&version, date, &author, &changelog, newline);
+
+    // Edits are applied back to front so earlier offsets stay valid.
+    let mut edits = vec![(existing.span.start, existing.span.end, block)];
+    match existing.constant_span {
+        Some(span) => edits.push((span.start, span.end, format!("\"{}\"", version))),
+        None => edits.push((existing.span.end, existing.span.end, format!("{}Const {} = \"{}\"", newline, VERSION_CONST, version))),
+    }
+    edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+    let mut out = source.to_string();
+    for (start, end, text) in edits {
+        out.replace_range(start..end, &text);
+    }
+    Ok((out, version))
+}
+
+/// Byte offset of the first line that is neither blank nor a whole-line comment.
+fn leading_comments_end(source: &str) -> usize {
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if !(trimmed.is_empty() || trimmed.starts_with('\'')) {
+            break;
+        }
+        offset += line.len();
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bump_creates_then_updates_header() {
+        let src = "' Weather station\nPublic T\nBeginProg\nEndProg\n";
+        let (first, v1) = bump(src, BumpLevel::Patch, "2026-10-01", "Jane", "Initial").unwrap();
+        assert_eq!(v1, "1.0.0");
+        assert!(first.starts_with("' Weather station\n' --- Version ---\n"));
+        assert!(header(&parse(&first)).unwrap().problems.is_empty());
+
+        let (second, v2) = bump(&first, BumpLevel::Minor, "2026-10-16", "Sam", "Add soil | sensors").unwrap();
+        assert_eq!(v2, "1.1.0");
+        let h = header(&parse(&second)).unwrap();
+        assert!(h.problems.is_empty(), "{:?}", h.problems);
+        assert_eq!(h.constant.as_deref(), Some("1.1.0"));
+        assert_eq!(h.changelog.len(), 2);
+        assert_eq!(h.changelog[0].message, "Add soil / sensors");
+        assert_eq!((h.date.as_str(), h.author.as_str()), ("2026-10-16", "Sam"));
+    }
+
+    #[test]
+    fn reports_out_of_sync_constant() {
+        let src = "' --- Version ---\n' Version: 1.2.0\n' Date: 2026-10-16\n' Author: Jane\n' Changelog:\n'   1.2.0 | 2026-10-16 | Jane | x\n' ---\nConst ProgramVersion = \"1.1.0\"\n";
+        let h = header(&parse(src)).unwrap();
+        assert_eq!(h.problems.len(), 1);
+        assert!(h.problems[0].1.contains("ProgramVersion"));
+    }
+}
@@ -0,0 +1,79 @@
+// FILE: crbrs-syntax/src/wiring.rs
+
+//! Terminal usage ("wiring table") derived from measurement and I/O instructions.
+//!
+//! Every argument whose parameter name ends in `Chan` or `Port` (`SEChan`, `DiffChan`,
+//! `ExChan`, `PChan`, `SDIPort`, `ComPort`, ...) is reported as one terminal connection, with
+//! the destination variable and repetitions of the instruction for context.
+
+use crate::ast::*;
+use crate::instructions;
+use crate::lexer::Span;
+use crate::tables::expr_text;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WiringEntry {
+    /// Terminal argument as written (`1`, `U5`, `C1`, `ComRS232`, a constant name, ...).
+    pub terminal: String,
+    /// Parameter the terminal was passed as (`SEChan`, `ExChan`, ...).
+    pub role: String,
+    /// Instruction using the terminal, canonical spelling.
+    pub instruction: String,
+    /// Destination variable, if the instruction has one.
+    pub dest: Option<String>,
+    /// Repetitions as written; with reps > 1 consecutive terminals are used.
+    pub reps: Option<String>,
+    pub span: Span,
+}
+
+fn is_terminal_param(param: &str) -> bool {
+    param.ends_with("Chan") || param.ends_with("Port")
+}
+
+/// Collects every terminal connection in `program`, in source order.
+pub fn wiring(source: &str, program: &Program) -> Vec<WiringEntry> {
+    let mut out = Vec::new();
+    walk_stmts(&program.body, &mut |stmt| {
+        let StmtKind::Call(call) = &stmt.kind else { return };
+        let Some(ins) = instructions::lookup(&call.name.text) else { return };
+        let arg = |name: &str| {
+            ins.params
+                .iter()
+                .position(|p| *p == name)
+                .and_then(|i| call.args.get(i))
+                .map(|e| expr_text(source, e).to_string())
+        };
+        let dest = ins.params.iter().find(|p| p.ends_with("Dest")).and_then(|p| arg(p));
+        let reps = arg("Reps");
+        for (param, expr) in ins.params.iter().zip(&call.args) {
+            if is_terminal_param(param) {
+                out.push(WiringEntry {
+                    terminal: expr_text(source, expr).to_string(),
+                    role: param.to_string(),
+                    instruction: ins.name.to_string(),
+                    dest: dest.clone(),
+                    reps: reps.clone(),
+                    span: expr.span,
+                });
+            }
+        }
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn lists_terminals_with_roles() {
+        let src = "Public T, Soil(2)\nBeginProg\n  Scan(1,Sec,0,0)\n    Therm107(T,1,U1,U2,0,60,1,0)\n    VoltSe(Soil(),2,mV5000,3,1,0,60,1,0)\n  NextScan\nEndProg\n";
+        let entries = wiring(src, &parse(src));
+        let summary: Vec<(&str, &str, Option<&str>)> =
+            entries.iter().map(|e| (e.terminal.as_str(), e.role.as_str(), e.dest.as_deref())).collect();
+        assert_eq!(summary, vec![("U1", "SEChan", Some("T")), ("U2", "ExChan", Some("T")), ("3", "SEChan", Some("Soil()"))]);
+        assert_eq!(entries[2].reps.as_deref(), Some("2"));
+    }
+}
@@ -0,0 +1,49 @@
+// FILE: crbrs-wasm/src/lib.rs
+
+//! WebAssembly entry points over `crbrs-syntax`.
+//!
+//! Every function takes and returns plain strings; structured values are JSON so the JS side
+//! doesn't need generated type bindings.
+
+use crbrs_syntax::format::FormatOptions;
+use wasm_bindgen::prelude::*;
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    // Our own types always serialize; fall back to `null` rather than trapping the wasm instance.
+    serde_json::to_string(value).unwrap_or_else(|_| "null".to_string())
+}
+
+/// Returns the crate version.
+#[wasm_bindgen]
+pub fn version() -> String {
+    env!("CARGO_PKG_VERSION").to_string()
+}
+
+/// Parses `source` and returns the syntax tree (including `errors`) as JSON.
+#[wasm_bindgen]
+pub fn parse(source: &str) -> String {
+    to_json(&crbrs_syntax::parse(source))
+}
+
+/// Lints `source` and returns the diagnostics as a JSON array.
+#[wasm_bindgen]
+pub fn lint(source: &str) -> String {
+    to_json(&crbrs_syntax::lint::lint_source(source))
+}
+
+/// Formats `source`. `options_json` is a JSON `FormatOptions` object, or empty for defaults.
+#[wasm_bindgen]
+pub fn format(source: &str, options_json: &str) -> Result<String, String> {
+    let options = if options_json.trim().is_empty() {
+        FormatOptions::default()
+    } else {
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid format options: {}", e))?
+    };
+    Ok(crbrs_syntax::format::format(source, &options))
+}
+
+/// Returns the built-in instruction database as a JSON array.
+#[wasm_bindgen]
+pub fn instructions() -> String {
+    to_json(&crbrs_syntax::instructions::INSTRUCTIONS)
+}